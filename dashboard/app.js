@@ -0,0 +1,276 @@
+// Dashboard local del gateway: consume la API HTTP y el stream WebSocket
+"use strict";
+
+const MAX_LIVE_ROWS = 50;
+const REFRESH_MS = 10000;
+
+const $ = (id) => document.getElementById(id);
+
+function escapeHtml(value) {
+  return String(value ?? "").replace(/[&<>"']/g, (c) => ({
+    "&": "&amp;", "<": "&lt;", ">": "&gt;", '"': "&quot;", "'": "&#39;",
+  }[c]));
+}
+
+function formatTime(iso) {
+  return iso ? new Date(iso).toLocaleString() : "–";
+}
+
+function formatMetrics(metrics) {
+  return (metrics || [])
+    .map((m) => `${escapeHtml(m.measurement)}: ${Number(m.value).toFixed(2)}${m.unit ? " " + escapeHtml(m.unit) : ""}`)
+    .join("<br>");
+}
+
+function formatBytes(bytes) {
+  if (!bytes) return "0 B";
+  const units = ["B", "KB", "MB", "GB"];
+  const i = Math.min(Math.floor(Math.log(bytes) / Math.log(1024)), units.length - 1);
+  return `${(bytes / Math.pow(1024, i)).toFixed(1)} ${units[i]}`;
+}
+
+// Token de la API (VIEWER_API_TOKENS), pedido la primera vez que se requiere
+const TOKEN_KEY = "gatewayApiToken";
+
+async function fetchJson(path) {
+  const token = localStorage.getItem(TOKEN_KEY);
+  const headers = token ? { Authorization: `Bearer ${token}` } : {};
+  const response = await fetch(path, { headers });
+  if (response.status === 401) {
+    const entered = prompt("Token de acceso del gateway");
+    if (entered) {
+      localStorage.setItem(TOKEN_KEY, entered.trim());
+      return fetchJson(path);
+    }
+  }
+  if (!response.ok) throw new Error(`${path}: HTTP ${response.status}`);
+  return response.json();
+}
+
+// ==================== Estado general ====================
+
+async function refreshHealth() {
+  try {
+    const health = await fetchJson("/health");
+    $("gateway-id").textContent = health.gateway_id;
+    const badge = $("health");
+    const components = health.components || {};
+    badge.textContent = `${health.status} · MQTT ${components.mqtt} · cloud ${components.cloud_sync}`;
+    badge.className = "badge " + (health.status === "ok" ? "ok" : "warn");
+  } catch (e) {
+    $("health").textContent = "Sin respuesta";
+    $("health").className = "badge bad";
+  }
+}
+
+async function refreshMetrics() {
+  try {
+    const { metrics } = await fetchJson("/metrics");
+    $("devices-count").textContent = metrics.devices_count;
+    $("pending-sync").textContent = metrics.pending_sync_count;
+    $("sync-failing").textContent = metrics.sync_failing_count;
+    $("db-size").textContent = formatBytes(metrics.database_size_bytes);
+  } catch (e) {
+    console.error(e);
+  }
+}
+
+// ==================== Dispositivos ====================
+
+async function refreshDevices() {
+  try {
+    const { data } = await fetchJson("/api/v2/devices");
+    $("devices").innerHTML = data
+      .map((d) => `<tr>
+          <td>${escapeHtml(d.device_id)}</td>
+          <td>${escapeHtml(d.location)}</td>
+          <td>${formatTime(d.last_seen)}</td>
+          <td>${d.message_count}</td>
+          <td>${d.last_quality ?? "–"}</td>
+          <td>${d.battery != null ? Number(d.battery).toFixed(2) : "–"}</td>
+        </tr>`)
+      .join("");
+
+    const select = $("chart-device");
+    const selected = select.value || linked.get("device");
+    select.innerHTML = data
+      .map((d) => `<option value="${escapeHtml(d.device_id)}">${escapeHtml(d.device_id)}</option>`)
+      .join("");
+    if (selected && data.some((d) => d.device_id === selected)) select.value = selected;
+  } catch (e) {
+    console.error(e);
+  }
+}
+
+// ==================== Anomalías ====================
+
+async function refreshAnomalies() {
+  try {
+    const { data } = await fetchJson("/api/v2/data/anomalies?limit=20");
+    $("anomalies").innerHTML = data.length
+      ? data
+          .map((a) => `<tr class="anomaly">
+              <td>${formatTime(a.gateway_timestamp)}</td>
+              <td>${escapeHtml(a.device_id)}</td>
+              <td>${formatMetrics(a.metrics)}</td>
+              <td>${Object.entries(a.indicators || {})
+                .map(([k, v]) => `${escapeHtml(k)}: ${Number(v).toFixed(2)}`)
+                .join("<br>")}</td>
+              <td>${(a.quality_issues || []).map(escapeHtml).join("<br>")}</td>
+            </tr>`)
+          .join("")
+      : `<tr><td colspan="5" class="muted">Sin anomalías en las últimas 24 horas</td></tr>`;
+  } catch (e) {
+    console.error(e);
+  }
+}
+
+// ==================== Gráfico ====================
+
+let chartPoints = [];
+
+async function refreshChart() {
+  const device = $("chart-device").value;
+  const measurement = $("chart-measurement").value;
+  if (!device) return;
+
+  try {
+    const params = new URLSearchParams({ device_id: device, measurement, limit: "1000" });
+    const { data } = await fetchJson(`/api/v2/data/range?${params}`);
+    chartPoints = data
+      .filter((p) => p.value != null)
+      .map((p) => ({ t: new Date(p.timestamp).getTime(), v: p.value }))
+      .sort((a, b) => a.t - b.t);
+    drawChart();
+  } catch (e) {
+    console.error(e);
+  }
+}
+
+function drawChart() {
+  const canvas = $("chart");
+  const ctx = canvas.getContext("2d");
+  const width = (canvas.width = canvas.clientWidth);
+  const height = canvas.height;
+  const pad = { left: 48, right: 12, top: 12, bottom: 24 };
+
+  ctx.clearRect(0, 0, width, height);
+  ctx.font = "11px system-ui, sans-serif";
+  ctx.fillStyle = "#7b8794";
+
+  if (chartPoints.length < 2) {
+    ctx.fillText("Sin datos suficientes para graficar", pad.left, height / 2);
+    return;
+  }
+
+  const tMin = chartPoints[0].t;
+  const tMax = chartPoints[chartPoints.length - 1].t;
+  let vMin = Math.min(...chartPoints.map((p) => p.v));
+  let vMax = Math.max(...chartPoints.map((p) => p.v));
+  if (vMin === vMax) {
+    vMin -= 1;
+    vMax += 1;
+  }
+
+  const x = (t) => pad.left + ((t - tMin) / (tMax - tMin || 1)) * (width - pad.left - pad.right);
+  const y = (v) => pad.top + (1 - (v - vMin) / (vMax - vMin)) * (height - pad.top - pad.bottom);
+
+  // Ejes y etiquetas
+  ctx.strokeStyle = "#e4e7eb";
+  for (let i = 0; i <= 4; i++) {
+    const v = vMin + ((vMax - vMin) * i) / 4;
+    ctx.beginPath();
+    ctx.moveTo(pad.left, y(v));
+    ctx.lineTo(width - pad.right, y(v));
+    ctx.stroke();
+    ctx.fillText(v.toFixed(1), 4, y(v) + 4);
+  }
+  ctx.fillText(new Date(tMin).toLocaleTimeString(), pad.left, height - 6);
+  const endLabel = new Date(tMax).toLocaleTimeString();
+  ctx.fillText(endLabel, width - pad.right - ctx.measureText(endLabel).width, height - 6);
+
+  // Serie
+  ctx.strokeStyle = "#2680c2";
+  ctx.lineWidth = 2;
+  ctx.beginPath();
+  chartPoints.forEach((p, i) => (i === 0 ? ctx.moveTo(x(p.t), y(p.v)) : ctx.lineTo(x(p.t), y(p.v))));
+  ctx.stroke();
+  ctx.lineWidth = 1;
+}
+
+// ==================== Stream en vivo ====================
+
+function connectStream() {
+  const protocol = location.protocol === "https:" ? "wss:" : "ws:";
+  // Los navegadores no envían cabeceras en el WebSocket: el token va en la URL
+  const token = localStorage.getItem(TOKEN_KEY);
+  const query = token ? `?access_token=${encodeURIComponent(token)}` : "";
+  const socket = new WebSocket(`${protocol}//${location.host}/api/v2/stream${query}`);
+  const state = $("stream-state");
+
+  socket.onopen = () => {
+    state.textContent = "Stream en vivo";
+    state.className = "badge ok";
+  };
+
+  socket.onmessage = (event) => {
+    const reading = JSON.parse(event.data);
+    const row = document.createElement("tr");
+    if (reading.computed && reading.computed.is_anomaly) row.className = "anomaly";
+    row.innerHTML = `
+      <td>${formatTime(reading.gateway_timestamp)}</td>
+      <td>${escapeHtml(reading.header.deviceId)}</td>
+      <td>${escapeHtml(reading.header.location)}</td>
+      <td>${formatMetrics(reading.metrics)}</td>
+      <td>${reading.quality.score}</td>`;
+
+    const body = $("live");
+    body.prepend(row);
+    while (body.rows.length > MAX_LIVE_ROWS) body.deleteRow(-1);
+
+    // Agregar el punto al gráfico si corresponde a la serie visible
+    if (reading.header.deviceId === $("chart-device").value) {
+      const measurement = $("chart-measurement").value.toLowerCase();
+      const metric = reading.metrics.find((m) => m.measurement.toLowerCase() === measurement);
+      if (metric) {
+        chartPoints.push({ t: new Date(reading.gateway_timestamp).getTime(), v: metric.value });
+        drawChart();
+      }
+    }
+  };
+
+  // Reconectar automáticamente si el gateway se reinicia
+  socket.onclose = () => {
+    state.textContent = "Stream desconectado";
+    state.className = "badge bad";
+    setTimeout(connectStream, 3000);
+  };
+}
+
+// ==================== Inicio ====================
+
+// Los enlaces de las notificaciones de alertas abren el gráfico del dispositivo:
+// /?device=<id>&measurement=<medición>#chart
+const linked = new URLSearchParams(location.search);
+const linkedMeasurement = linked.get("measurement");
+if (linkedMeasurement) {
+  const measurementSelect = $("chart-measurement");
+  if (![...measurementSelect.options].some((o) => o.value === linkedMeasurement)) {
+    measurementSelect.add(new Option(linkedMeasurement, linkedMeasurement));
+  }
+  measurementSelect.value = linkedMeasurement;
+}
+
+async function refreshAll() {
+  await Promise.all([refreshHealth(), refreshMetrics(), refreshAnomalies()]);
+}
+
+$("chart-device").addEventListener("change", refreshChart);
+$("chart-measurement").addEventListener("change", refreshChart);
+window.addEventListener("resize", drawChart);
+
+refreshDevices().then(refreshChart);
+refreshAll();
+connectStream();
+setInterval(refreshAll, REFRESH_MS);
+setInterval(refreshDevices, REFRESH_MS * 3);
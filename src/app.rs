@@ -1,39 +1,209 @@
+use std::future::{Future, IntoFuture};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::pin::Pin;
 use std::sync::Arc;
-use tokio::{net::TcpListener, sync::Mutex};
+use tokio::{net::TcpListener, sync::mpsc};
 use tracing::info;
 
+#[cfg(feature = "ble")]
+use crate::services::ble::BleScanner;
+#[cfg(feature = "opcua")]
+use crate::services::opcua::OpcUaClient;
 use crate::{
-    config::Config,
+    config::{CloudSink, Config},
     database::Database,
-    services::{cloud_sync::CloudSync, edge_processor::EdgeProcessor, mqtt_handler::MqttHandler},
-    startup::{logger, router::build_router, state::AppState},
+    services::{
+        alerting::Alerting,
+        backlog_watchdog::BacklogWatchdog,
+        backup::BackupService,
+        cloud_sync::{CloudSync, CloudSyncHandle},
+        connection::ConnectionStatus,
+        device_presence::PresenceMonitor,
+        edge_processor::EdgeProcessor,
+        fusion::FusionService,
+        gateway_status::GatewayStatus,
+        gpio::GpioOutputs,
+        graphql,
+        host_metrics::HostMetrics,
+        lifecycle::ProcessLifecycle,
+        local_ingest::LocalIngest,
+        local_sink::LocalSink,
+        maintenance::MaintenanceService,
+        modbus::ModbusPoller,
+        mqtt_handler::MqttHandler,
+        notifications,
+        onboard_sensors::OnboardSensors,
+        remote_config::{REMOTE_CONFIG_CAPACITY, RemoteConfig},
+        rule_actions::RuleActionExecutor,
+        runtime_config::RuntimeConfig,
+        self_health::SelfHealthMonitor,
+        sensor_health::SensorHealthMonitor,
+        serial::SerialReader,
+        snmp::SnmpPoller,
+        webhook_mappings::WebhookMappings,
+    },
+    startup::{
+        coap, line_listener, lockout::AuthLockout, logger, router::build_router, state::AppState,
+        tls, versioning::ApiUsage,
+    },
+    telemetry::Telemetry,
 };
 
-pub async fn bootstrap() -> anyhow::Result<()> {
-    // Inicializar logger
-    logger::init();
-    info!("Iniciando IoT Gateway Edge Computing...");
+/// Eventos de reglas en cola antes de descartar nuevos
+const RULE_EVENTS_CAPACITY: usize = 256;
 
-    // Cargar configuración
-    let config = Arc::new(Config::load()?);
-    info!("Configuración cargada correctamente");
+pub async fn bootstrap(config_file: Option<&Path>) -> anyhow::Result<()> {
+    // Cargar configuración (el nivel de log depende de ella)
+    let config = Arc::new(Config::load(config_file)?);
+
+    // Inicializar logger (el guard vacía el archivo de log al terminar)
+    let _log_guard = logger::init(&config)?;
+    info!(
+        environment = config.environment.as_str(),
+        "Iniciando IoT Gateway Edge Computing..."
+    );
+    match &config.config_file {
+        Some(path) => info!(file = %path.display(), "Configuración cargada correctamente"),
+        None => info!("Configuración cargada correctamente"),
+    }
+
+    // Métricas del pipeline compartidas por los servicios
+    let telemetry = Arc::new(Telemetry::default());
 
     // Base de datos
-    let db = Database::new(&config.database_url).await?;
+    let db = Database::new(&config)
+        .await?
+        .with_telemetry(telemetry.clone());
     db.migrate().await?;
+    let db = db.with_write_buffer(&config);
     info!("Base de datos SQLite inicializada");
 
-    // Inicializar servicios
-    let edge_processor = Arc::new(EdgeProcessor::new(config.clone()));
-    let cloud_sync = Arc::new(Mutex::new(CloudSync::new(config.clone())));
+    // Alertas y sus canales de notificación, antes que los servicios que las disparan
+    let alerting = Arc::new(Alerting::load(config.clone(), db.clone()).await?);
+    notifications::start(&config, &alerting, &db)?;
+
+    // Arranques y apagados limpios para diagnosticar reinicios
+    let lifecycle = Arc::new(ProcessLifecycle::start(config.clone(), db.clone(), &alerting).await?);
 
+    // Inicializar servicios
+    let (rule_events_tx, rule_events_rx) = mpsc::channel(RULE_EVENTS_CAPACITY);
+    let runtime_config = Arc::new(RuntimeConfig::load(config.clone(), db.clone()).await?);
+    let edge_processor = Arc::new(EdgeProcessor::new(
+        config.clone(),
+        runtime_config.subscribe(),
+        rule_events_tx,
+        telemetry.clone(),
+    ));
+    edge_processor.load_definitions(&db).await?;
+    edge_processor.reload_scripts();
+    if config.scripts_reload_secs > 0 {
+        tokio::spawn(edge_processor.clone().start_script_reload_task());
+    }
+    if let Err(e) = edge_processor.seed_history(&db).await {
+        tracing::warn!("No se pudo inicializar el histórico de anomalías: {}", e);
+    }
+    if let Err(e) = edge_processor.seed_power(&db).await {
+        tracing::warn!("No se pudo inicializar el seguimiento de batería: {}", e);
+    }
+    let quotas = edge_processor.quotas();
+    if let Err(e) = quotas.refresh(&db).await {
+        tracing::warn!(
+            "No se pudo inicializar el uso de cuotas por dispositivo: {}",
+            e
+        );
+    }
+    tokio::spawn(quotas.start_refresh_task(db.clone()));
+    if config.baseline_days > 0 {
+        tokio::spawn(
+            edge_processor
+                .clone()
+                .start_baseline_refresh_task(db.clone()),
+        );
+    }
+    let mqtt_status = Arc::new(ConnectionStatus::default());
+    let cloud_status = Arc::new(ConnectionStatus::default());
+    let remote_config = (config.cloud_sync_enabled
+        && config.cloud_sink == CloudSink::Mqtt
+        && config.cloud_remote_config_enabled)
+        .then(|| {
+            let (sender, receiver) = mpsc::channel(REMOTE_CONFIG_CAPACITY);
+            let remote_config = RemoteConfig::new(
+                config.clone(),
+                db.clone(),
+                runtime_config.clone(),
+                edge_processor.clone(),
+            );
+            tokio::spawn(remote_config.run(receiver));
+            sender
+        });
     // Lanzar tareas en background
-    let db_clone = db.clone();
-    let cloud_sync_clone = cloud_sync.clone();
-    tokio::spawn(async move {
-        let mut cs = cloud_sync_clone.lock().await;
-        cs.start_sync_task(db_clone).await;
-    });
+    let cloud_sync = if config.cloud_sync_enabled {
+        CloudSync::new(
+            config.clone(),
+            runtime_config.subscribe(),
+            cloud_status.clone(),
+            edge_processor.overrides(),
+            remote_config,
+            telemetry.clone(),
+        )
+        .spawn(db.clone())
+    } else {
+        cloud_status.set_disabled();
+        info!("Sincronización con el cloud deshabilitada: las lecturas solo se guardan localmente");
+        CloudSyncHandle::disabled()
+    };
+
+    let backup = Arc::new(BackupService::new(config.clone(), db.clone()));
+    if config.backup_schedule_enabled {
+        tokio::spawn(backup.clone().start_schedule_task());
+    }
+
+    let maintenance = Arc::new(MaintenanceService::new(
+        config.clone(),
+        db.clone(),
+        runtime_config.subscribe(),
+    ));
+    if config.maintenance_enabled {
+        tokio::spawn(maintenance.clone().start_maintenance_task());
+    }
+
+    if config.fusion_window_secs > 0 {
+        let fusion = Arc::new(FusionService::new(
+            config.clone(),
+            db.clone(),
+            edge_processor.clone(),
+        ));
+        tokio::spawn(fusion.start_fusion_task());
+    }
+
+    if config.replay_window_secs > 0 {
+        tokio::spawn(db.clone().start_replay_flush_task());
+    }
+
+    let backlog_watchdog = Arc::new(BacklogWatchdog::new(
+        config.clone(),
+        db.clone(),
+        alerting.clone(),
+    ));
+    if config.cloud_sync_enabled && config.backlog_alarm_minutes > 0 {
+        tokio::spawn(backlog_watchdog.clone().start_watch_task());
+    }
+
+    let host_metrics = Arc::new(HostMetrics::new(config.clone()));
+    if config.host_metrics_interval_secs > 0 {
+        tokio::spawn(host_metrics.clone().start_sampling_task());
+    }
+
+    if config.self_health_check_secs > 0 {
+        let self_health = SelfHealthMonitor::new(
+            config.clone(),
+            alerting.clone(),
+            host_metrics.clone(),
+            telemetry.clone(),
+        );
+        tokio::spawn(self_health.start_check_task());
+    }
 
     info!("Servicios de edge computing listos");
 
@@ -43,42 +213,268 @@ pub async fn bootstrap() -> anyhow::Result<()> {
         db.clone(),
         edge_processor.clone(),
         cloud_sync.clone(),
+        runtime_config.subscribe(),
+        mqtt_status.clone(),
+        telemetry.clone(),
     )
     .await?;
-    let mqtt_task = mqtt_handler.start().await;
+
+    // Salidas GPIO accionadas por las reglas; arrancan apagadas
+    let gpio = Arc::new(GpioOutputs::new(&config));
+
+    // Las acciones de reglas publican a través del broker local
+    let rule_actions = RuleActionExecutor::new(
+        config.clone(),
+        alerting.clone(),
+        mqtt_handler.client(),
+        gpio.clone(),
+    );
+    tokio::spawn(rule_actions.run(rule_events_rx));
+
+    if config.gateway_status_interval_secs > 0 {
+        let gateway_status = GatewayStatus::new(
+            config.clone(),
+            mqtt_handler.client(),
+            host_metrics.clone(),
+            lifecycle.clone(),
+            gpio.clone(),
+            telemetry.clone(),
+        );
+        tokio::spawn(gateway_status.start_publish_task());
+    }
+
+    let presence = Arc::new(PresenceMonitor::new(
+        config.clone(),
+        db.clone(),
+        alerting.clone(),
+        mqtt_handler.client(),
+    ));
+    if config.device_presence_check_secs > 0 {
+        tokio::spawn(
+            presence
+                .clone()
+                .start_monitor_task(edge_processor.subscribe()),
+        );
+    }
+
+    // Equipos locales que el gateway consulta por su cuenta
+    let local_ingest = LocalIngest::new(db.clone(), edge_processor.clone());
+    if let Some(path) = &config.modbus_devices_path {
+        ModbusPoller::load(config.clone(), local_ingest.clone(), path)?.start();
+    }
+    if let Some(path) = &config.snmp_devices_path {
+        SnmpPoller::load(config.clone(), local_ingest.clone(), path)?.start();
+    }
+    if let Some(path) = &config.serial_devices_path {
+        SerialReader::load(local_ingest.clone(), path)?.start();
+    }
+    #[cfg(feature = "opcua")]
+    if let Some(path) = &config.opcua_devices_path {
+        OpcUaClient::load(config.clone(), local_ingest.clone(), path)?.start();
+    }
+    if config.onewire_enabled || !config.i2c_sensors.is_empty() {
+        tokio::spawn(OnboardSensors::new(config.clone(), local_ingest.clone()).run());
+    }
+    #[cfg(feature = "ble")]
+    if config.ble_enabled {
+        tokio::spawn(BleScanner::new(config.clone(), local_ingest.clone()).run());
+    }
+
+    if let Some(url) = &config.local_sink_url {
+        tokio::spawn(LocalSink::new(&config, url)?.run(edge_processor.subscribe()));
+    }
+
+    if config.sensor_stuck_after_secs > 0 || config.battery_low_alerts {
+        let sensor_health =
+            SensorHealthMonitor::new(config.clone(), edge_processor.clone(), alerting.clone());
+        tokio::spawn(sensor_health.run(edge_processor.subscribe()));
+    }
+
+    if config.time_sync_interval_secs > 0 {
+        tokio::spawn(mqtt_handler.time_sync().start_beacon_task());
+    }
+
+    let twins = mqtt_handler.twins();
+    let firmware = mqtt_handler.firmware();
+    let mut mqtt_task = mqtt_handler.start().await;
+
+    let webhooks = Arc::new(WebhookMappings::new(&config.webhook_mappings_dir));
+    webhooks.reload();
 
     // Crear estado compartido
     let state = AppState {
-        db,
+        db: db.clone(),
         edge_processor,
-        cloud_sync,
+        cloud_sync: cloud_sync.clone(),
+        mqtt_status,
+        cloud_status,
+        backup,
+        maintenance,
+        runtime_config,
+        auth_lockout: Arc::new(AuthLockout::new(&config)),
+        api_usage: Arc::new(ApiUsage::default()),
+        telemetry,
+        host_metrics,
+        backlog_watchdog,
+        lifecycle: lifecycle.clone(),
+        presence,
+        alerting,
+        twins,
+        firmware,
+        webhooks,
+        gpio,
+        graphql: graphql::schema(db.clone()),
         config: config.clone(),
     };
 
+    // HTTPS: el certificado se carga (o se genera) antes de aceptar conexiones
+    let tls = if config.http_tls_enabled {
+        Some(tls::load(&config).await?)
+    } else {
+        None
+    };
+
+    #[cfg(unix)]
+    tokio::spawn(reload_on_sighup(state.clone(), tls.clone()));
+
+    // CoAP para nodos que no hablan MQTT ni HTTP
+    if config.coap_enabled {
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = coap::serve(state).await {
+                tracing::error!("Servidor CoAP detenido: {}", e);
+            }
+        });
+    }
+
+    // Lecturas por líneas de dataloggers antiguos
+    if let Some(port) = config.line_tcp_port {
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = line_listener::serve_tcp(state, port).await {
+                tracing::error!("Recepción de lecturas por líneas (TCP) detenida: {}", e);
+            }
+        });
+    }
+    if let Some(port) = config.line_udp_port {
+        let state = state.clone();
+        tokio::spawn(async move {
+            if let Err(e) = line_listener::serve_udp(state, port).await {
+                tracing::error!("Recepción de lecturas por líneas (UDP) detenida: {}", e);
+            }
+        });
+    }
+
     // Construir el router
     let app = build_router(state);
 
     // Servidor HTTP
     let addr = format!("0.0.0.0:{}", config.http_port.unwrap_or(3000));
     let listener = TcpListener::bind(&addr).await?;
-    info!("Servidor HTTP escuchando en {}", addr);
+    let scheme = if tls.is_some() { "HTTPS" } else { "HTTP" };
+    info!("Servidor {} escuchando en {}", scheme, addr);
     info!(
         "Broker MQTT: {}:{}",
         config.mqtt_broker_host, config.mqtt_broker_port
     );
 
     // Ejecutar servidor + MQTT handler concurrentemente
-    let http_server = axum::serve(listener, app);
-    tokio::select! {
+    let service = app.into_make_service_with_connect_info::<SocketAddr>();
+    let http_server: Pin<Box<dyn Future<Output = std::io::Result<()>> + Send>> = match tls {
+        Some(tls) => {
+            Box::pin(axum_server::from_tcp_rustls(listener.into_std()?, tls).serve(service))
+        }
+        None => Box::pin(axum::serve(listener, service).into_future()),
+    };
+    let requested = tokio::select! {
         result = http_server => {
             if let Err(e) = result {
                 tracing::error!("Error en el servidor HTTP: {}", e);
             }
+            false
         }
-        _ = mqtt_task => {
+        _ = mqtt_task.finished() => {
             tracing::error!("MQTT Handler ha finalizado inesperadamente");
+            false
         }
+        _ = shutdown_signal() => {
+            info!("Señal de apagado recibida, deteniendo el gateway");
+            true
+        }
+    };
+
+    // Se procesan los mensajes MQTT ya confirmados al broker, se espera la
+    // sincronización en curso y se escriben las lecturas aún en memoria
+    let drained = mqtt_task.shutdown().await;
+    cloud_sync.shutdown().await;
+    let flushed = db.close_writes().await;
+
+    // El apagado solo es limpio si no se perdieron mensajes ni lecturas; lo
+    // pendiente de sincronizar sigue en SQLite y no cuenta
+    if requested && drained && flushed {
+        lifecycle.mark_clean_shutdown().await;
     }
 
     Ok(())
 }
+
+/// Espera SIGTERM (systemd, Docker) o Ctrl+C
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::warn!("No se pudo registrar el manejador de Ctrl+C: {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                tracing::warn!("No se pudo registrar el manejador de SIGTERM: {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// Recarga la configuración (y el certificado TLS) cada vez que el proceso
+/// recibe SIGHUP
+#[cfg(unix)]
+async fn reload_on_sighup(state: AppState, tls: Option<axum_server::tls_rustls::RustlsConfig>) {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            tracing::warn!("No se pudo registrar el manejador de SIGHUP: {}", e);
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        info!("SIGHUP recibido, recargando configuración");
+
+        if let Err(e) = state.reload_configuration().await {
+            tracing::error!("Error recargando la configuración: {}", e);
+        }
+        if let Some(tls) = &tls
+            && let Err(e) = tls::reload(&state.config, tls).await
+        {
+            tracing::error!("Error recargando el certificado TLS: {}", e);
+        }
+    }
+}
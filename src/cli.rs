@@ -0,0 +1,176 @@
+use crate::{
+    app,
+    config::{CloudSink, Config},
+    database::Database,
+    services::{backup::BackupService, export::ExportFormat},
+    startup::logger,
+};
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+mod export;
+mod simulate;
+
+/// Gateway IoT edge computing para Raspberry Pi
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Archivo de configuración TOML o YAML (por defecto CONFIG_PATH o gateway.toml)
+    #[arg(long, global = true, value_name = "RUTA")]
+    config: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Ejecuta el gateway (comando por defecto)
+    Run,
+    /// Valida la configuración y muestra un resumen
+    CheckConfig,
+    /// Aplica las migraciones de la base de datos local
+    Migrate,
+    /// Exporta lecturas a CSV o Parquet
+    Export(ExportArgs),
+    /// Publica lecturas sintéticas en el broker MQTT local
+    Simulate(SimulateArgs),
+    /// Genera un snapshot de la base de datos en BACKUP_DIR
+    Backup,
+}
+
+#[derive(Debug, Args)]
+struct ExportArgs {
+    /// Inicio del periodo (RFC 3339; por defecto 24 horas antes de --to)
+    #[arg(long)]
+    from: Option<DateTime<Utc>>,
+
+    /// Fin del periodo (RFC 3339; por defecto ahora)
+    #[arg(long)]
+    to: Option<DateTime<Utc>>,
+
+    /// Dispositivos incluidos (repetible; por defecto todos)
+    #[arg(long = "device-id", value_name = "ID")]
+    device_ids: Vec<String>,
+
+    /// Ubicaciones incluidas (repetible; por defecto todas)
+    #[arg(long = "location", value_name = "UBICACIÓN")]
+    locations: Vec<String>,
+
+    /// Solo dispositivos con alguna de estas etiquetas (repetible)
+    #[arg(long = "tag", value_name = "ETIQUETA")]
+    tags: Vec<String>,
+
+    /// Limita las columnas a una medición
+    #[arg(long)]
+    measurement: Option<String>,
+
+    #[arg(long, value_enum, default_value = "csv")]
+    format: ExportFormat,
+
+    /// Archivo de salida (obligatorio para Parquet; CSV por defecto a stdout)
+    #[arg(long, short)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+struct SimulateArgs {
+    /// Número de dispositivos simulados
+    #[arg(long, default_value_t = 3)]
+    devices: u32,
+
+    /// Segundos entre lecturas de cada dispositivo
+    #[arg(long, default_value_t = 5)]
+    interval: u64,
+
+    /// Lecturas por dispositivo antes de terminar (0 = sin límite)
+    #[arg(long, default_value_t = 0)]
+    count: u64,
+
+    /// Ubicación asignada a los dispositivos simulados
+    #[arg(long, default_value = "simulacion")]
+    location: String,
+}
+
+impl Cli {
+    pub async fn run(self) -> anyhow::Result<()> {
+        let config_file = self.config.as_deref();
+
+        match self.command.unwrap_or(Command::Run) {
+            Command::Run => app::bootstrap(config_file).await,
+            Command::CheckConfig => check_config(&load_config(config_file)?),
+            Command::Migrate => migrate(&load_config(config_file)?).await,
+            Command::Export(args) => export::run(&load_config(config_file)?, args).await,
+            Command::Simulate(args) => simulate::run(&load_config(config_file)?, args).await,
+            Command::Backup => backup(Arc::new(load_config(config_file)?)).await,
+        }
+    }
+}
+
+/// Prepara el logger y la configuración de los subcomandos de operación
+fn load_config(config_file: Option<&Path>) -> anyhow::Result<Config> {
+    logger::init_cli();
+    Config::load(config_file)
+}
+
+/// Muestra un resumen de la configuración ya validada (sin secretos)
+fn check_config(config: &Config) -> anyhow::Result<()> {
+    println!("Configuración válida");
+    match &config.config_file {
+        Some(path) => println!("  archivo:        {}", path.display()),
+        None => println!("  archivo:        (solo variables de entorno)"),
+    }
+    println!("  entorno:        {}", config.environment.as_str());
+    println!("  gateway_id:     {}", config.gateway_id);
+    println!("  base de datos:  {}", config.database_url);
+    println!(
+        "  mqtt local:     {}:{}",
+        config.mqtt_broker_host, config.mqtt_broker_port
+    );
+    if config.http_tls_enabled {
+        println!(
+            "  https:          {} ({})",
+            config.http_port.unwrap_or(3000),
+            config.http_tls_cert_path
+        );
+    } else {
+        println!("  http:           {}", config.http_port.unwrap_or(3000));
+    }
+    if config.cloud_sync_enabled {
+        match config.cloud_sink {
+            CloudSink::Mqtt => println!(
+                "  mqtt cloud:     {}:{} ({})",
+                config.cloud_mqtt_broker_host,
+                config.cloud_mqtt_broker_port,
+                config.cloud_mqtt_topic
+            ),
+            CloudSink::Log => println!("  mqtt cloud:     simulado (mensajes al log)"),
+        }
+        println!(
+            "  sincronización: batch {} cada {} s",
+            config.cloud_sync_batch_size, config.cloud_sync_interval_secs
+        );
+    } else {
+        println!("  sincronización: deshabilitada (solo local)");
+    }
+
+    Ok(())
+}
+
+async fn migrate(config: &Config) -> anyhow::Result<()> {
+    let db = Database::new(config).await?;
+    db.migrate().await?;
+
+    println!("Migraciones aplicadas en {}", config.database_url);
+    Ok(())
+}
+
+async fn backup(config: Arc<Config>) -> anyhow::Result<()> {
+    let db = Database::new(&config).await?;
+    let path = BackupService::new(config, db).create_snapshot().await?;
+
+    println!("{}", path.display());
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use super::ExportArgs;
+use crate::{
+    config::Config,
+    database::{Database, MetricFilter},
+    services::export::{ExportFormat, ExportService},
+};
+use chrono::{Duration, Utc};
+use tokio::io::AsyncWriteExt;
+use tokio_stream::StreamExt;
+
+/// Exporta las lecturas del periodo a un archivo o a stdout
+pub(super) async fn run(config: &Config, args: ExportArgs) -> anyhow::Result<()> {
+    let to = args.to.unwrap_or_else(Utc::now);
+    let from = args.from.unwrap_or(to - Duration::hours(24));
+    if from >= to {
+        anyhow::bail!("--from debe ser anterior a --to");
+    }
+
+    let filter = MetricFilter {
+        device_ids: args.device_ids,
+        locations: args.locations,
+        tags: args.tags,
+        measurement: args.measurement,
+        from,
+        to,
+        quality: Default::default(),
+    };
+
+    let db = Database::new(config).await?;
+    let export = ExportService::prepare(db, filter).await?;
+
+    match args.format {
+        ExportFormat::Csv => {
+            let mut output: Box<dyn tokio::io::AsyncWrite + Unpin> = match &args.output {
+                Some(path) => Box::new(tokio::fs::File::create(path).await?),
+                None => Box::new(tokio::io::stdout()),
+            };
+
+            let mut chunks = export.stream_csv();
+            while let Some(chunk) = chunks.next().await {
+                output.write_all(&chunk?).await?;
+            }
+            output.flush().await?;
+        }
+        ExportFormat::Parquet => {
+            let Some(path) = &args.output else {
+                anyhow::bail!("La exportación Parquet requiere --output");
+            };
+
+            let rows = export.write_parquet(path).await?;
+            eprintln!("{} lecturas exportadas a {}", rows, path.display());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,168 @@
+use super::SimulateArgs;
+use crate::{
+    config::Config,
+    models::{SensorDataInput, SensorHeader, SensorMetric},
+};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use std::time::Duration;
+
+/// Generador pseudoaleatorio xorshift (suficiente para ruido de simulación)
+struct Noise(u64);
+
+impl Noise {
+    fn new(seed: u64) -> Self {
+        Self(seed | 1)
+    }
+
+    /// Valor uniforme en [-1, 1)
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 40) as f32 / (1u64 << 23) as f32 - 1.0
+    }
+}
+
+/// Estado de un dispositivo simulado (paseo aleatorio acotado)
+struct SimulatedDevice {
+    device_id: String,
+    temperature: f32,
+    humidity: f32,
+    battery: f32,
+    sequence: u64,
+}
+
+impl SimulatedDevice {
+    fn new(index: u32, noise: &mut Noise) -> Self {
+        Self {
+            device_id: format!("sim-{:03}", index + 1),
+            temperature: 21.0 + 3.0 * noise.next(),
+            humidity: 50.0 + 10.0 * noise.next(),
+            battery: 90.0 + 10.0 * noise.next().abs(),
+            sequence: 0,
+        }
+    }
+
+    fn next_reading(&mut self, location: &str, noise: &mut Noise) -> SensorDataInput {
+        self.temperature = (self.temperature + 0.2 * noise.next()).clamp(10.0, 35.0);
+        self.humidity = (self.humidity + 0.8 * noise.next()).clamp(20.0, 90.0);
+        self.battery = (self.battery - 0.01).max(0.0);
+        self.sequence += 1;
+
+        let metric = |measurement: &str, value: f32, unit: &str| SensorMetric {
+            measurement: measurement.to_string(),
+            value,
+            unit: Some(unit.to_string()),
+        };
+
+        SensorDataInput {
+            header: SensorHeader {
+                user_uuid: None,
+                device_id: self.device_id.clone(),
+                device_type: None,
+                message_id: Some(self.sequence.to_string()),
+                location: location.to_string(),
+                topic: format!("sensors/{}/data", self.device_id),
+                should_requeue: false,
+                firmware_version: None,
+                hardware_model: None,
+                rssi: None,
+                sequence: None,
+                sent_at: None,
+            },
+            metrics: vec![
+                metric("Temperature", self.temperature, "°C"),
+                metric("Humidity", self.humidity, "%"),
+                metric("battery", self.battery, "%"),
+                metric("rssi", -60.0 + 8.0 * noise.next(), "dBm"),
+            ],
+            device_timestamp: None,
+        }
+    }
+}
+
+/// Publica lecturas sintéticas en el broker MQTT local como si fueran ESP32
+pub(super) async fn run(config: &Config, args: SimulateArgs) -> anyhow::Result<()> {
+    if args.devices == 0 || args.interval == 0 {
+        anyhow::bail!("--devices y --interval deben ser mayores que 0");
+    }
+
+    let mut options = MqttOptions::new(
+        format!("{}-simulator", config.mqtt_client_id),
+        &config.mqtt_broker_host,
+        config.mqtt_broker_port,
+    );
+    options.set_keep_alive(Duration::from_secs(30));
+    if let (Some(username), Some(password)) = (&config.mqtt_username, &config.mqtt_password) {
+        options.set_credentials(username, password);
+    }
+
+    let (client, mut eventloop) = AsyncClient::new(options, 100);
+
+    // Esperar la conexión antes de publicar para fallar pronto si el broker no responde
+    loop {
+        match eventloop.poll().await {
+            Ok(Event::Incoming(Packet::ConnAck(_))) => break,
+            Ok(_) => {}
+            Err(e) => anyhow::bail!(
+                "No se pudo conectar al broker MQTT {}:{}: {}",
+                config.mqtt_broker_host,
+                config.mqtt_broker_port,
+                e
+            ),
+        }
+    }
+
+    let connection = tokio::spawn(async move {
+        loop {
+            if let Err(e) = eventloop.poll().await {
+                tracing::error!("Error en la conexión MQTT del simulador: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    });
+
+    let seed = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_nanos() as u64;
+    let mut noise = Noise::new(seed);
+    let mut devices: Vec<SimulatedDevice> = (0..args.devices)
+        .map(|index| SimulatedDevice::new(index, &mut noise))
+        .collect();
+
+    eprintln!(
+        "Simulando {} dispositivos en {}:{} cada {} s",
+        args.devices, config.mqtt_broker_host, config.mqtt_broker_port, args.interval
+    );
+
+    let mut interval = tokio::time::interval(Duration::from_secs(args.interval));
+    let mut rounds = 0;
+    let mut published = 0;
+
+    while args.count == 0 || rounds < args.count {
+        interval.tick().await;
+
+        for device in &mut devices {
+            let reading = device.next_reading(&args.location, &mut noise);
+            client
+                .publish(
+                    &reading.header.topic,
+                    QoS::AtLeastOnce,
+                    false,
+                    serde_json::to_vec(&reading)?,
+                )
+                .await?;
+            published += 1;
+        }
+
+        rounds += 1;
+    }
+
+    // Dar tiempo a que el event loop entregue los últimos mensajes
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    client.disconnect().await.ok();
+    connection.abort();
+
+    eprintln!("{} lecturas publicadas", published);
+    Ok(())
+}
@@ -1,22 +1,438 @@
-use serde::Deserialize;
-use std::env;
+use crate::models::AlertSeverity;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+mod file;
+mod loader;
+mod profile;
+
+use file::ConfigSource;
+use loader::Loader;
+pub use profile::Environment;
+
+/// Filtro de ruido aplicable a una medición
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MeasurementFilter {
+    /// Filtro de Kalman 1D
+    Kalman,
+    /// Pasa-bajos de primer orden
+    LowPass,
+}
+
+impl FromStr for MeasurementFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "kalman" => Ok(MeasurementFilter::Kalman),
+            "lowpass" => Ok(MeasurementFilter::LowPass),
+            other => anyhow::bail!("Filtro desconocido: {} (usar kalman o lowpass)", other),
+        }
+    }
+}
+
+/// Método de relleno de métricas faltantes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InterpolationMethod {
+    /// Sin relleno
+    #[default]
+    Off,
+    /// Último valor observado (last observation carried forward)
+    Locf,
+    /// Extrapolación lineal con las dos últimas observaciones
+    Linear,
+}
+
+impl FromStr for InterpolationMethod {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "off" | "" => Ok(InterpolationMethod::Off),
+            "locf" => Ok(InterpolationMethod::Locf),
+            "linear" => Ok(InterpolationMethod::Linear),
+            other => anyhow::bail!(
+                "Método de interpolación desconocido: {} (usar off, locf o linear)",
+                other
+            ),
+        }
+    }
+}
+
+/// Exigencia del token de dispositivo en la ingesta HTTP
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceAuth {
+    /// No se verifica el token (compatibilidad con nodos existentes)
+    #[default]
+    Off,
+    /// Solo los dispositivos con credenciales emitidas deben presentar su token
+    Provisioned,
+    /// Toda lectura HTTP requiere el token de su dispositivo
+    Required,
+}
+
+impl FromStr for DeviceAuth {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "off" | "" => Ok(DeviceAuth::Off),
+            "provisioned" => Ok(DeviceAuth::Provisioned),
+            "required" => Ok(DeviceAuth::Required),
+            other => anyhow::bail!(
+                "Modo de autenticación de dispositivos desconocido: {} (usar off, provisioned o required)",
+                other
+            ),
+        }
+    }
+}
+
+/// Token de un cliente de la API (`nombre:token`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ApiToken {
+    /// Nombre con el que el cliente queda en el registro de auditoría
+    pub name: String,
+    pub token: String,
+}
+
+/// Cifrado de la conexión con el servidor SMTP
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Conexión en claro que se eleva a TLS con STARTTLS (puerto 587)
+    #[default]
+    StartTls,
+    /// TLS desde el inicio de la conexión (puerto 465)
+    Tls,
+    /// Sin cifrado, solo para relays en la red local
+    None,
+}
+
+impl FromStr for SmtpSecurity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "starttls" => Ok(SmtpSecurity::StartTls),
+            "tls" => Ok(SmtpSecurity::Tls),
+            "none" => Ok(SmtpSecurity::None),
+            other => anyhow::bail!(
+                "Cifrado SMTP desconocido: {} (usar starttls, tls o none)",
+                other
+            ),
+        }
+    }
+}
+
+/// Destino de un canal de notificación y la severidad mínima que recibe
+/// (`severidad:destino`; sin prefijo recibe todas)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeverityRoute {
+    pub min_severity: AlertSeverity,
+    pub target: String,
+}
+
+/// Qué hacer con las lecturas de un dispositivo que superó su cuota
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuotaAction {
+    /// Descarta todas las lecturas hasta que la cuota se libere
+    Throttle,
+    /// Conserva una de cada `DEVICE_QUOTA_DOWNSAMPLE_FACTOR` lecturas
+    #[default]
+    Downsample,
+}
+
+impl FromStr for QuotaAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "throttle" => Ok(QuotaAction::Throttle),
+            "downsample" | "" => Ok(QuotaAction::Downsample),
+            other => anyhow::bail!(
+                "Acción de cuota desconocida: {} (usar throttle o downsample)",
+                other
+            ),
+        }
+    }
+}
+
+/// Escala del índice de calidad del aire
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AqiScale {
+    /// US EPA (0-500)
+    #[default]
+    Epa,
+    /// Índice europeo de la EEA (niveles 1-6)
+    Eu,
+}
+
+impl FromStr for AqiScale {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "epa" => Ok(AqiScale::Epa),
+            "eu" => Ok(AqiScale::Eu),
+            other => anyhow::bail!("Escala AQI desconocida: {} (usar epa o eu)", other),
+        }
+    }
+}
+
+/// Modelo de confort térmico
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComfortModelKind {
+    /// Heurística por zonas de temperatura y humedad
+    #[default]
+    Heuristic,
+    /// ASHRAE 55 PMV/PPD
+    Pmv,
+    /// Confort adaptativo de ASHRAE 55
+    Adaptive,
+}
+
+impl FromStr for ComfortModelKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "heuristic" => Ok(ComfortModelKind::Heuristic),
+            "pmv" => Ok(ComfortModelKind::Pmv),
+            "adaptive" => Ok(ComfortModelKind::Adaptive),
+            other => anyhow::bail!(
+                "Modelo de confort desconocido: {} (usar heuristic, pmv o adaptive)",
+                other
+            ),
+        }
+    }
+}
+
+/// Destino de los datos sincronizados con el cloud
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CloudSink {
+    /// Broker MQTT del cloud
+    #[default]
+    Mqtt,
+    /// Sink simulado: los mensajes se escriben en el log y se dan por enviados
+    Log,
+}
+
+impl FromStr for CloudSink {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "mqtt" => Ok(CloudSink::Mqtt),
+            "log" => Ok(CloudSink::Log),
+            other => anyhow::bail!("Sink del cloud desconocido: {} (usar mqtt o log)", other),
+        }
+    }
+}
+
+/// Qué hacer con un mensaje MQTT cuando la cola de procesamiento está llena
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueueOverflow {
+    /// Descarta el mensaje para que el eventloop siga atendiendo al broker;
+    /// rumqttc ya lo confirmó (QoS 1), así que se pierde sin que el nodo lo reenvíe
+    Drop,
+    /// Espera hasta 5 s a que haya lugar; absorbe picos sin perder mensajes y,
+    /// si el procesamiento sigue atascado, descarta el mensaje antes de que el
+    /// broker corte la conexión por keep-alive
+    #[default]
+    Block,
+}
+
+impl FromStr for QueueOverflow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "drop" => Ok(QueueOverflow::Drop),
+            "block" | "" => Ok(QueueOverflow::Block),
+            other => anyhow::bail!(
+                "Política de cola MQTT desconocida: {} (usar drop o block)",
+                other
+            ),
+        }
+    }
+}
+
+/// Qué lectura descartar cuando el búfer de escritura en SQLite está lleno
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WriteOverload {
+    /// La lectura más antigua del búfer
+    #[default]
+    DropOldest,
+    /// La de menor puntaje de calidad (a igual puntaje, la más antigua)
+    DropLowestQuality,
+    /// Deja de recibir lecturas por MQTT hasta que el búfer se vacíe; lo que
+    /// llegue por otras vías con el búfer lleno se descarta
+    PauseSubscriptions,
+}
+
+impl FromStr for WriteOverload {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "drop-oldest" | "" => Ok(WriteOverload::DropOldest),
+            "drop-lowest-quality" => Ok(WriteOverload::DropLowestQuality),
+            "pause-subscriptions" => Ok(WriteOverload::PauseSubscriptions),
+            other => anyhow::bail!(
+                "Política de saturación desconocida: {} (usar drop-oldest, drop-lowest-quality o pause-subscriptions)",
+                other
+            ),
+        }
+    }
+}
+
+/// Formato de las líneas de log
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Formato legible para consola
+    #[default]
+    Text,
+    /// Un objeto JSON por línea (Loki, ELK)
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => anyhow::bail!("Formato de log desconocido: {} (usar text o json)", other),
+        }
+    }
+}
+
+/// Modelo de un sensor I2C conectado al gateway
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum I2cSensorModel {
+    /// Temperatura, humedad y presión (Bosch BME280)
+    Bme280,
+    /// Temperatura y humedad (Sensirion SHT31)
+    Sht31,
+}
+
+impl FromStr for I2cSensorModel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "bme280" => Ok(I2cSensorModel::Bme280),
+            "sht31" => Ok(I2cSensorModel::Sht31),
+            other => anyhow::bail!("Sensor I2C desconocido: {} (usar bme280 o sht31)", other),
+        }
+    }
+}
+
+/// Sensor I2C del gateway (`modelo:bus:dirección[:nombre]`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct I2cSensor {
+    pub model: I2cSensorModel,
+    /// Número del bus (`/dev/i2c-<bus>`)
+    pub bus: u8,
+    pub address: u8,
+    /// Sufijo de sus mediciones (`Temperature_<nombre>`), para distinguir
+    /// sensores que miden lo mismo
+    pub name: Option<String>,
+}
+
+/// Salida GPIO del gateway (`nombre:pin[:low]`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GpioOutput {
+    /// Nombre con el que la referencian las reglas y la API (`ventilador`)
+    pub name: String,
+    /// Número BCM del pin (GPIO17 = 17)
+    pub pin: u8,
+    /// El relé se activa con nivel bajo (la mayoría de los módulos de relés)
+    pub active_low: bool,
+}
 
 /// Configuración de la aplicación
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    /// Archivo de configuración cargado (TOML o YAML), si hay alguno
+    pub config_file: Option<PathBuf>,
+
     /// ID único del gateway edge
     pub gateway_id: String,
 
+    /// Perfil de entorno (`GATEWAY_ENV`) del que salen los valores por defecto
+    pub environment: Environment,
+
+    /// Nivel de log de la aplicación (`RUST_LOG` tiene prioridad)
+    pub log_level: String,
+
+    /// Formato de los logs (`text` o `json`)
+    pub log_format: LogFormat,
+
+    /// Escribir los logs en la salida estándar
+    pub log_stdout: bool,
+
+    /// Directorio del archivo de log (`gateway.log`); sin definir, no se escribe a archivo
+    pub log_file_dir: Option<String>,
+
+    /// Tamaño en bytes a partir del cual se rota el archivo de log (0 = sin límite)
+    pub log_file_max_bytes: u64,
+
+    /// Rotar además el archivo de log al cambiar de día
+    pub log_file_rotate_daily: bool,
+
+    /// Archivos de log rotados que se conservan
+    pub log_file_keep: usize,
+
+    /// Sincronización con el cloud; deshabilitada, el gateway funciona solo en local
+    pub cloud_sync_enabled: bool,
+
+    /// Destino de la sincronización (`log` simula el cloud sin broker)
+    pub cloud_sink: CloudSink,
+
     /// UUID del usuario para el cloud service
     pub user_uuid: String,
 
     /// URL de la base de datos SQLite local
     pub database_url: String,
 
+    /// Clave de cifrado SQLCipher (requiere compilar con la feature `sqlcipher`)
+    pub database_key: Option<String>,
+
+    /// Milisegundos que se acumulan las lecturas individuales antes de escribirlas
+    /// en una sola transacción (0 inserta cada lectura al llegar)
+    pub db_write_flush_ms: u64,
+
+    /// Lecturas acumuladas que fuerzan la escritura antes del plazo
+    pub db_write_max_rows: usize,
+
+    /// Lecturas que el búfer de escritura retiene como máximo si SQLite no da abasto
+    pub db_write_buffer_max: usize,
+
+    /// Qué hacer cuando el búfer de escritura está lleno
+    pub db_write_overload: WriteOverload,
+
     /// URL del servicio cloud principal
+    #[allow(dead_code)]
     pub cloud_service_url: String,
 
     /// API key para autenticación con el cloud
+    #[allow(dead_code)]
     pub cloud_api_key: String,
 
     /// Tamaño del batch antes de sincronizar
@@ -28,6 +444,9 @@ pub struct Config {
     /// Días para mantener datos sincronizados localmente
     pub data_retention_days: i64,
 
+    /// Días para mantener alertas resueltas en el historial
+    pub alert_retention_days: i64,
+
     // MQTT Config
     pub mqtt_broker_host: String,
     pub mqtt_broker_port: u16,
@@ -35,8 +454,303 @@ pub struct Config {
     pub mqtt_username: Option<String>,
     pub mqtt_password: Option<String>,
 
+    /// Tareas que procesan en paralelo los mensajes MQTT recibidos
+    pub mqtt_workers: usize,
+
+    /// Mensajes MQTT en espera de procesarse, entre todas las tareas
+    pub mqtt_queue_capacity: usize,
+
+    /// Política cuando la cola de mensajes MQTT está llena
+    pub mqtt_queue_overflow: QueueOverflow,
+
     pub http_port: Option<u16>,
 
+    /// Servir la API HTTP sobre TLS (HTTPS)
+    pub http_tls_enabled: bool,
+
+    /// Certificado del servidor en PEM (con la cadena intermedia, si la hay)
+    pub http_tls_cert_path: String,
+
+    /// Clave privada del certificado en PEM
+    pub http_tls_key_path: String,
+
+    /// Generar un certificado autofirmado si no existen el certificado y la clave
+    pub http_tls_self_signed: bool,
+
+    /// Nombres e IPs adicionales del certificado autofirmado
+    pub http_tls_hostnames: Vec<String>,
+
+    /// Solicitudes por segundo permitidas por cliente HTTP (0 deshabilita el límite)
+    pub http_rate_limit_per_sec: f64,
+
+    /// Ráfaga máxima de solicitudes por cliente HTTP
+    pub http_rate_limit_burst: u32,
+
+    /// Solicitudes HTTP atendidas en paralelo (0 sin límite)
+    pub http_max_concurrency: usize,
+
+    /// Tamaño máximo del cuerpo de las solicitudes HTTP en bytes
+    pub http_body_limit_bytes: usize,
+
+    /// Tamaño máximo del cuerpo de los batches de lecturas en bytes
+    pub http_batch_body_limit_bytes: usize,
+
+    /// Servidor CoAP (UDP) para nodos que no usan MQTT
+    pub coap_enabled: bool,
+
+    /// Puerto UDP del servidor CoAP
+    pub coap_port: u16,
+
+    /// Tamaño máximo del payload de una solicitud CoAP en bytes
+    pub coap_max_payload_bytes: usize,
+
+    /// Puerto TCP que acepta lecturas por líneas (JSON o CSV); None = deshabilitado
+    pub line_tcp_port: Option<u16>,
+
+    /// Puerto UDP que acepta lecturas por líneas; None = deshabilitado
+    pub line_udp_port: Option<u16>,
+
+    /// Ubicación de las lecturas CSV, que no la incluyen
+    pub line_location: String,
+
+    /// Direcciones desde las que se aceptan lecturas por líneas (vacío = todas)
+    pub line_allowed_ips: Vec<IpAddr>,
+
+    /// Directorio con el mapeo de cada fuente de webhooks (`<fuente>.json`)
+    pub webhook_mappings_dir: String,
+
+    /// Token de cada fuente de webhooks (`fuente:token`); las fuentes sin token
+    /// usan los tokens de dispositivo
+    pub webhook_tokens: Vec<ApiToken>,
+
+    /// Archivo JSON con los equipos Modbus a consultar (None = deshabilitado)
+    pub modbus_devices_path: Option<String>,
+
+    /// Intervalo de consulta de los equipos Modbus que no indican el suyo
+    pub modbus_poll_secs: u64,
+
+    /// Espera máxima de cada respuesta Modbus en milisegundos
+    pub modbus_timeout_ms: u64,
+
+    /// Archivo JSON con los equipos SNMP a consultar (None = deshabilitado)
+    pub snmp_devices_path: Option<String>,
+
+    /// Intervalo de consulta de los equipos SNMP que no indican el suyo
+    pub snmp_poll_secs: u64,
+
+    /// Espera máxima de cada respuesta SNMP en milisegundos
+    pub snmp_timeout_ms: u64,
+
+    /// Archivo JSON con los sensores conectados por puerto serie (None = deshabilitado)
+    pub serial_devices_path: Option<String>,
+
+    /// Escucha de anuncios BLE de sensores RuuviTag y Xiaomi (requiere la feature `ble`)
+    pub ble_enabled: bool,
+
+    /// Segundos mínimos entre dos lecturas de un mismo sensor BLE
+    pub ble_interval_secs: u64,
+
+    /// Ubicación de cada sensor BLE por dirección MAC (en minúsculas y sin
+    /// separadores); si hay alguno, solo se aceptan los sensores listados
+    pub ble_devices: HashMap<String, String>,
+
+    /// Ubicación de los sensores BLE que no están en `BLE_DEVICES`
+    pub ble_default_location: String,
+
+    /// Archivo JSON con los PLC y nodos OPC UA a suscribir (None = deshabilitado)
+    pub opcua_devices_path: Option<String>,
+
+    /// Intervalo de muestreo de los equipos OPC UA que no indican sampling_ms
+    pub opcua_sampling_ms: u64,
+
+    /// Directorio del certificado de aplicación del cliente OPC UA
+    pub opcua_pki_dir: String,
+
+    /// Dispositivo con el que se publican los sensores conectados al gateway
+    pub onboard_device_id: String,
+
+    /// Ubicación de los sensores conectados al gateway
+    pub onboard_location: String,
+
+    /// Intervalo de lectura de los sensores conectados al gateway
+    pub onboard_interval_secs: u64,
+
+    /// Lee las sondas de temperatura 1-Wire (DS18B20) conectadas al gateway
+    pub onewire_enabled: bool,
+
+    /// Directorio de los dispositivos 1-Wire del kernel
+    pub onewire_devices_dir: String,
+
+    /// Medición de cada sonda 1-Wire por su ID (`28-0316a2794aff`)
+    pub onewire_probes: HashMap<String, String>,
+
+    /// Sensores I2C conectados al gateway (requiere la feature `i2c`)
+    pub i2c_sensors: Vec<I2cSensor>,
+
+    /// Salidas GPIO que las reglas pueden accionar (requiere la feature `gpio`)
+    pub gpio_outputs: Vec<GpioOutput>,
+
+    /// Orígenes permitidos por CORS (vacío o `*` = cualquier origen)
+    pub cors_allowed_origins: Vec<String>,
+
+    /// Token para los endpoints de administración (`Authorization: Bearer`)
+    pub admin_api_token: Option<String>,
+
+    /// Tokens de solo lectura (rol viewer)
+    pub viewer_api_tokens: Vec<ApiToken>,
+
+    /// Tokens que pueden sincronizar y enviar órdenes a los dispositivos (rol operator)
+    pub operator_api_tokens: Vec<ApiToken>,
+
+    /// Tokens inválidos desde una IP antes de bloquearla (0 = sin bloqueo)
+    pub auth_max_failures_per_ip: u32,
+
+    /// Tokens inválidos para una misma clave (grupo de rutas o dispositivo) antes de bloquearla
+    pub auth_max_failures_per_key: u32,
+
+    /// Ventana en la que se cuentan los fallos de autenticación
+    pub auth_failure_window_secs: u64,
+
+    /// Duración del bloqueo
+    pub auth_lockout_secs: u64,
+
+    /// Servir el dashboard web embebido en `/`
+    pub dashboard_enabled: bool,
+
+    /// Fecha de retiro anunciada para `/api/v1` (cabecera `Sunset`)
+    pub api_v1_sunset: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Lecturas pendientes de sincronizar a partir de las cuales el gateway
+    /// deja de estar listo (0 deshabilita la verificación)
+    pub readiness_max_pending_sync: i64,
+
+    /// Si la desconexión del broker cloud marca el gateway como no listo
+    pub readiness_require_cloud: bool,
+
+    /// Minutos de crecimiento sostenido del backlog de sincronización que
+    /// disparan la alarma crítica (0 la deshabilita)
+    pub backlog_alarm_minutes: u64,
+
+    /// Intervalo de verificación del backlog en segundos
+    pub backlog_check_interval_secs: u64,
+
+    /// Apagados abruptos en 24 horas a partir de los cuales se considera que
+    /// el gateway está en un bucle de reinicios (0 deshabilita la alerta)
+    pub crash_loop_restarts: usize,
+
+    /// Segundos sin lecturas tras los que un dispositivo pasa a offline, salvo
+    /// que su perfil defina otro umbral (0 = solo los tipos con umbral propio)
+    pub device_offline_after_secs: u64,
+
+    /// Intervalo de la detección de dispositivos offline (0 la deshabilita)
+    pub device_presence_check_secs: u64,
+
+    /// Severidad de la alerta de dispositivo offline
+    pub device_offline_alert_severity: AlertSeverity,
+
+    /// Los dispositivos desconocidos quedan pendientes de aprobación y sus
+    /// lecturas en cuarentena
+    pub device_approval_required: bool,
+
+    /// Exigencia del token de dispositivo en `/api/vN/sensor/*`
+    pub device_auth: DeviceAuth,
+
+    /// Segundos durante los que el token anterior sigue válido tras rotarlo
+    pub device_token_grace_secs: u64,
+
+    /// Directorio donde se archivan (Parquet) las lecturas de los dispositivos
+    /// dados de baja
+    pub device_archive_dir: String,
+
+    /// Mensajes por día (UTC) admitidos por dispositivo (0 = sin límite)
+    pub device_quota_messages_per_day: u64,
+
+    /// Lecturas almacenadas admitidas por dispositivo (0 = sin límite)
+    pub device_quota_max_rows: u64,
+
+    /// Acción aplicada a un dispositivo que supera su cuota
+    pub device_quota_action: QuotaAction,
+
+    /// Con `downsample`, se conserva una de cada N lecturas fuera de cuota
+    pub device_quota_downsample_factor: u64,
+
+    /// Intervalo en segundos con el que se recuentan las lecturas almacenadas
+    pub device_quota_refresh_secs: u64,
+
+    /// Directorio donde se guardan los binarios de firmware para OTA
+    pub firmware_dir: String,
+
+    /// Tamaño máximo de una imagen de firmware subida por la API
+    pub firmware_max_bytes: usize,
+
+    /// URL base con la que los nodos alcanzan el gateway; sin ella los anuncios
+    /// OTA incluyen solo la ruta de descarga
+    pub firmware_base_url: Option<String>,
+
+    /// URLs a las que se envían los eventos de alertas (vacío = sin webhooks)
+    pub alert_webhook_urls: Vec<String>,
+
+    /// Clave HMAC-SHA256 con la que se firman los webhooks de alertas
+    pub alert_webhook_secret: Option<String>,
+
+    /// Tiempo máximo de cada envío de un webhook
+    pub alert_webhook_timeout_secs: u64,
+
+    /// Reintentos de un webhook fallido antes de descartar el evento
+    pub alert_webhook_max_retries: u32,
+
+    /// Espera antes del primer reintento; se duplica en cada uno
+    pub alert_webhook_backoff_secs: u64,
+
+    /// URL del dashboard para los enlaces al gráfico en las notificaciones
+    pub alert_chart_base_url: Option<String>,
+
+    /// Token del bot de Telegram
+    pub telegram_bot_token: Option<String>,
+
+    /// Chats de Telegram que reciben las alertas, por severidad mínima
+    pub telegram_chat_ids: Vec<SeverityRoute>,
+
+    /// Mensajes de Telegram por minuto; el exceso se descarta (0 = sin límite)
+    pub telegram_rate_limit_per_min: u32,
+
+    /// Plantilla de los mensajes de Telegram (None = plantilla por defecto)
+    pub telegram_template: Option<String>,
+
+    /// Incoming webhooks de Slack que reciben las alertas, por severidad mínima
+    pub slack_webhook_urls: Vec<SeverityRoute>,
+
+    /// Mensajes de Slack por minuto; el exceso se descarta (0 = sin límite)
+    pub slack_rate_limit_per_min: u32,
+
+    /// Plantilla de los mensajes de Slack (None = plantilla por defecto)
+    pub slack_template: Option<String>,
+
+    /// Servidor SMTP para las notificaciones por email (None = deshabilitadas)
+    pub smtp_host: Option<String>,
+    pub smtp_port: u16,
+    pub smtp_security: SmtpSecurity,
+    pub smtp_username: Option<String>,
+    pub smtp_password: Option<String>,
+
+    /// Remitente de los emails (`Gateway <gateway@ejemplo.com>`)
+    pub smtp_from: Option<String>,
+
+    /// Direcciones que reciben las alertas por email, por severidad mínima
+    pub alert_email_to: Vec<SeverityRoute>,
+
+    /// Emails de alertas por minuto; el exceso se descarta (0 = sin límite)
+    pub alert_email_rate_limit_per_min: u32,
+
+    /// Plantilla de los emails de alertas; la primera línea es el asunto
+    pub alert_email_template: Option<String>,
+
+    /// Direcciones que reciben el resumen diario (vacío = sin resumen)
+    pub email_digest_to: Vec<String>,
+
+    /// Hora local (0-23) de envío del resumen diario
+    pub email_digest_hour: u32,
+
     /// Configuración MQTT cloud (gateway → servidor)
     pub cloud_mqtt_broker_host: String,
     pub cloud_mqtt_broker_port: u16,
@@ -44,79 +758,1556 @@ pub struct Config {
     pub cloud_mqtt_username: Option<String>,
     pub cloud_mqtt_password: Option<String>,
     pub cloud_mqtt_topic: String,
+
+    /// Aceptar configuración remota en `gateways/{gateway_id}/config` del broker cloud
+    pub cloud_remote_config_enabled: bool,
+
+    /// Directorio donde se guardan los snapshots de la base de datos
+    pub backup_dir: String,
+
+    /// Habilita el respaldo nocturno programado
+    pub backup_schedule_enabled: bool,
+
+    /// Hora local (0-23) en la que se ejecuta el respaldo programado
+    pub backup_hour: u32,
+
+    /// Número de snapshots a conservar en la rotación
+    pub backup_keep: usize,
+
+    /// URL de escritura (line protocol) de InfluxDB o VictoriaMetrics donde se
+    /// replican las lecturas procesadas; sin ella no hay réplica
+    pub local_sink_url: Option<String>,
+
+    /// Token enviado como `Authorization: Token <token>` (InfluxDB 2.x)
+    pub local_sink_token: Option<String>,
+
+    /// Nombre de la measurement de InfluxDB en la que se escriben las lecturas
+    pub local_sink_measurement: String,
+
+    /// Lecturas máximas por escritura
+    pub local_sink_batch_size: usize,
+
+    /// Intervalo máximo entre escrituras
+    pub local_sink_flush_secs: u64,
+
+    /// Lecturas pendientes retenidas mientras la base no responde; al llenarse
+    /// se descartan las más antiguas
+    pub local_sink_max_buffer: usize,
+
+    /// Tiempo máximo de cada escritura
+    pub local_sink_timeout_secs: u64,
+
+    /// Dispositivos mantenidos en la caché de lecturas recientes
+    pub latest_cache_devices: usize,
+
+    /// Lecturas recientes guardadas por dispositivo en la caché (0 la deshabilita)
+    pub latest_cache_depth: usize,
+
+    /// Tiempo que se recuerdan en memoria los `message_id` recibidos (0 solo consulta SQLite)
+    pub dedup_window_secs: u64,
+
+    /// Antigüedad máxima, en segundos, del `sentAt` de un mensaje (0 deshabilita
+    /// la protección contra reenvíos)
+    pub replay_window_secs: u64,
+
+    /// Rechazar los mensajes sin `sequence` y `sentAt` con la protección activa
+    pub replay_required: bool,
+
+    /// Lecturas por dispositivo y medición en la ventana de detección de anomalías (0 la deshabilita)
+    pub anomaly_window_size: usize,
+
+    /// Desviaciones estándar respecto a la ventana a partir de las cuales una lectura es anómala
+    pub anomaly_zscore_threshold: f32,
+
+    /// Días de histórico usados para la línea base por hora del día (0 la deshabilita)
+    pub baseline_days: i64,
+
+    /// Valores mínimos en una hora para considerar su línea base
+    pub baseline_min_samples: i64,
+
+    /// Desviaciones estándar respecto a la línea base horaria a partir de las cuales una lectura es anómala
+    pub baseline_zscore_threshold: f32,
+
+    /// Intervalo de recálculo de las líneas base
+    pub baseline_refresh_secs: u64,
+
+    /// Horas de muestras de batería y RSSI usadas para estimar tendencias
+    pub battery_trend_hours: i64,
+
+    /// Nivel de batería considerado vacío para mediciones en porcentaje
+    pub battery_empty_percent: f32,
+
+    /// Nivel de batería considerado vacío para mediciones en voltios (`vbat`)
+    pub battery_empty_voltage: f32,
+
+    /// Días restantes de batería por debajo de los cuales el nodo requiere atención
+    pub battery_attention_days: f32,
+
+    /// Dispara una alerta cuando la batería de un nodo requiere atención
+    pub battery_low_alerts: bool,
+
+    /// Severidad de la alerta de batería baja
+    pub battery_low_alert_severity: AlertSeverity,
+
+    /// Segundos con el mismo valor tras los que un sensor se considera
+    /// bloqueado (0 deshabilita la detección)
+    pub sensor_stuck_after_secs: u64,
+
+    /// Lecturas idénticas mínimas para considerar un sensor bloqueado
+    pub sensor_stuck_min_readings: u32,
+
+    /// Mediciones vigiladas; vacío = todas salvo batería y RSSI
+    pub sensor_stuck_measurements: Vec<String>,
+
+    /// Severidad de la alerta de sensor bloqueado
+    pub sensor_stuck_alert_severity: AlertSeverity,
+
+    /// RSSI (dBm) por debajo del cual la señal se considera débil
+    pub rssi_poor_dbm: f32,
+
+    /// RSSI (dBm) por debajo del cual la señal se considera crítica
+    pub rssi_critical_dbm: f32,
+
+    /// Factor de suavizado EWMA por medición (0 lo deshabilita)
+    pub ewma_alpha: f32,
+
+    /// Enviar al cloud el valor suavizado (EWMA) en lugar de la muestra cruda
+    pub cloud_forward_smoothed: bool,
+
+    /// Directorio de scripts Rhai de métricas derivadas
+    pub scripts_dir: String,
+
+    /// Intervalo de revisión de cambios en los scripts (0 deshabilita la recarga en caliente)
+    pub scripts_reload_secs: u64,
+
+    /// Filtro de ruido por medición (`distance:kalman,voltage:lowpass`)
+    pub measurement_filters: HashMap<String, MeasurementFilter>,
+
+    /// Ruido del proceso (Q) del filtro de Kalman
+    pub kalman_process_noise: f32,
+
+    /// Ruido de la medición (R) del filtro de Kalman
+    pub kalman_measurement_noise: f32,
+
+    /// Frecuencia de corte del filtro pasa-bajos en Hz
+    pub lowpass_cutoff_hz: f32,
+
+    /// Muestras por dispositivo y medición en la ventana del filtro Hampel (0 lo deshabilita)
+    pub hampel_window_size: usize,
+
+    /// Desviaciones (MAD escalada) a partir de las cuales una muestra es un pico
+    pub hampel_threshold: f32,
+
+    /// Método de relleno de métricas faltantes o NaN (off lo deshabilita)
+    pub interpolation_method: InterpolationMethod,
+
+    /// Antigüedad máxima de la última observación usable para rellenar
+    pub interpolation_max_age_secs: u64,
+
+    /// Modelo de confort por defecto
+    pub comfort_model: ComfortModelKind,
+
+    /// Modelo de confort por ubicación (`oficina:pmv,invernadero:adaptive`)
+    pub comfort_models: HashMap<String, ComfortModelKind>,
+
+    /// Aislamiento de la vestimenta (clo) para PMV
+    pub comfort_clothing_clo: f32,
+
+    /// Tasa metabólica (met) para PMV
+    pub comfort_metabolic_rate: f32,
+
+    /// Velocidad del aire (m/s) para PMV
+    pub comfort_air_speed: f32,
+
+    /// Temperatura media exterior predominante (°C) para el modelo adaptativo
+    pub comfort_outdoor_mean_temp: f32,
+
+    /// Escala del índice de calidad del aire calculado con PM2.5/PM10
+    pub aqi_scale: AqiScale,
+
+    /// Adelanto máximo tolerado del reloj de un dispositivo respecto al gateway (segundos)
+    pub device_clock_tolerance_secs: u64,
+
+    /// Antigüedad máxima de una lectura acumulada offline (horas)
+    pub device_max_backfill_hours: u64,
+
+    /// Intervalo de la hora publicada en `gateway/time` (0 = sin baliza; las
+    /// solicitudes en `sensors/{id}/time/request` se responden igual)
+    pub time_sync_interval_secs: u64,
+
+    /// Ventanas de agregación en segundos (avg/min/max por medición en stats)
+    pub aggregation_windows: Vec<u64>,
+
+    /// Ventana de fusión de sensores por ubicación en segundos (0 la deshabilita)
+    pub fusion_window_secs: u64,
+
+    /// Divergencia máxima respecto a la mediana por medición (`temperature:1.5`)
+    pub fusion_tolerances: HashMap<String, f32>,
+
+    /// Divergencia máxima (% de la mediana) para mediciones sin tolerancia propia
+    pub fusion_default_tolerance_pct: f32,
+
+    /// Variación máxima por minuto de cada medición (`temperature:2,humidity:10`)
+    pub max_rate_of_change: HashMap<String, f32>,
+
+    /// Habilita el mantenimiento periódico (retención, VACUUM, quick_check)
+    pub maintenance_enabled: bool,
+
+    /// Cadencia del mantenimiento en horas
+    pub maintenance_interval_hours: u64,
+
+    /// Hora local (0-23) de baja actividad para ejecutar el mantenimiento
+    pub maintenance_hour: u32,
+
+    /// Intervalo de muestreo de CPU, memoria, disco y temperatura (0 = deshabilitado)
+    pub host_metrics_interval_secs: u64,
+
+    /// Temperatura del SoC (°C) a partir de la cual se registra un aviso y
+    /// se dispara la alerta de temperatura
+    pub host_temperature_warning_c: f32,
+
+    /// Uso del disco de la base de datos (%) que dispara una alerta (0 la deshabilita)
+    pub host_disk_alert_percent: f32,
+
+    /// Intervalo de las comprobaciones de salud del propio gateway (0 las deshabilita)
+    pub self_health_check_secs: u64,
+
+    /// Errores de SQLite entre dos comprobaciones que disparan una alerta (0 la deshabilita)
+    pub db_error_alert_count: u64,
+
+    /// Servidor NTP (`host:puerto`) con el que se mide la deriva del reloj
+    /// (None deshabilita la medición)
+    pub clock_drift_ntp_server: Option<String>,
+
+    /// Deriva del reloj (ms) que dispara una alerta
+    pub clock_drift_max_ms: u64,
+
+    /// Intervalo de la medición de deriva del reloj
+    pub clock_drift_check_secs: u64,
+
+    /// Intervalo de publicación del estado retenido del gateway (0 = deshabilitado)
+    pub gateway_status_interval_secs: u64,
 }
 
 impl Config {
-    /// Carga la configuración desde variables de entorno
-    pub fn load() -> anyhow::Result<Self> {
+    /// Carga la configuración desde el archivo de configuración y las variables
+    /// de entorno, que tienen prioridad sobre el archivo
+    pub fn load(config_file: Option<&Path>) -> anyhow::Result<Self> {
         // Cargar archivo .env si existe
         dotenv::dotenv().ok();
 
-        let gateway_id =
-            env::var("GATEWAY_ID").unwrap_or_else(|_| format!("gateway-{}", uuid::Uuid::new_v4()));
+        Self::from_source(&ConfigSource::load(config_file)?, None)
+    }
+
+    /// Vuelve a leer el archivo de configuración
+    ///
+    /// Las variables de entorno (incluido `.env`) quedan fijadas al arrancar el
+    /// proceso; si el ID del gateway se generó al arrancar, se conserva.
+    pub fn reload(&self) -> anyhow::Result<Self> {
+        Self::from_source(
+            &ConfigSource::load(self.config_file.as_deref())?,
+            Some(&self.gateway_id),
+        )
+    }
+
+    fn from_source(source: &ConfigSource, current_id: Option<&str>) -> anyhow::Result<Self> {
+        let loader = Loader::new(source);
+
+        let gateway_id = source
+            .var("GATEWAY_ID")
+            .unwrap_or_else(|_| match current_id {
+                Some(id) => id.to_string(),
+                None => format!("gateway-{}", uuid::Uuid::new_v4()),
+            });
+
+        // Los datos del cloud solo son obligatorios al sincronizar con el broker real
+        let cloud_sync_enabled: bool = loader.parse("CLOUD_SYNC_ENABLED", "true");
+        let cloud_sink: CloudSink = loader.parse("CLOUD_SINK", "mqtt");
+        let cloud_var = |name: &str| {
+            if cloud_sync_enabled && cloud_sink == CloudSink::Mqtt {
+                loader.required(name)
+            } else {
+                source.var(name).unwrap_or_default()
+            }
+        };
 
         let config = Config {
+            config_file: source.path().map(PathBuf::from),
             gateway_id: gateway_id.clone(),
 
-            user_uuid: env::var("USER_UUID").expect("USER_UUID debe estar configurada"),
+            environment: source.environment(),
+
+            log_level: source
+                .var("LOG_LEVEL")
+                .unwrap_or_else(|_| "info".to_string()),
+
+            log_format: loader.parse("LOG_FORMAT", "text"),
+
+            log_stdout: loader.parse("LOG_STDOUT", "true"),
+
+            log_file_dir: loader.optional("LOG_FILE_DIR"),
+
+            log_file_max_bytes: loader.parse("LOG_FILE_MAX_BYTES", "10485760"),
+
+            log_file_rotate_daily: loader.parse("LOG_FILE_ROTATE_DAILY", "true"),
 
-            database_url: env::var("DATABASE_URL")
+            log_file_keep: loader.parse("LOG_FILE_KEEP", "5"),
+
+            cloud_sync_enabled,
+
+            cloud_sink,
+
+            user_uuid: cloud_var("USER_UUID"),
+
+            database_url: source
+                .var("DATABASE_URL")
                 .unwrap_or_else(|_| "sqlite://sensor_data.db".to_string()),
 
-            cloud_service_url: env::var("CLOUD_SERVICE_URL")
-                .expect("CLOUD_SERVICE_URL debe estar configurada"),
+            database_key: Self::load_database_key(source),
+
+            db_write_flush_ms: loader.parse("DB_WRITE_FLUSH_MS", "250"),
+
+            db_write_max_rows: loader.parse("DB_WRITE_MAX_ROWS", "200"),
+
+            db_write_buffer_max: loader.parse("DB_WRITE_BUFFER_MAX", "5000"),
+
+            db_write_overload: loader.parse("DB_WRITE_OVERLOAD", "drop-oldest"),
 
-            cloud_api_key: env::var("CLOUD_API_KEY").expect("CLOUD_API_KEY debe estar configurada"),
+            cloud_service_url: cloud_var("CLOUD_SERVICE_URL"),
 
-            cloud_sync_batch_size: env::var("CLOUD_SYNC_BATCH_SIZE")
-                .unwrap_or_else(|_| "50".to_string())
-                .parse()?,
+            cloud_api_key: cloud_var("CLOUD_API_KEY"),
 
-            cloud_sync_interval_secs: env::var("CLOUD_SYNC_INTERVAL_SECS")
-                .unwrap_or_else(|_| "300".to_string()) // 5 minutos por defecto
-                .parse()?,
+            cloud_sync_batch_size: loader.parse("CLOUD_SYNC_BATCH_SIZE", "50"),
 
-            data_retention_days: env::var("DATA_RETENTION_DAYS")
-                .unwrap_or_else(|_| "7".to_string())
-                .parse()?,
+            cloud_sync_interval_secs: loader.parse("CLOUD_SYNC_INTERVAL_SECS", "300"),
+
+            data_retention_days: loader.parse("DATA_RETENTION_DAYS", "7"),
+
+            alert_retention_days: loader.parse("ALERT_RETENTION_DAYS", "90"),
 
             // MQTT Config
-            mqtt_broker_host: env::var("MQTT_BROKER_HOST")
+            mqtt_broker_host: source
+                .var("MQTT_BROKER_HOST")
                 .unwrap_or_else(|_| "localhost".to_string()),
 
-            mqtt_broker_port: env::var("MQTT_BROKER_PORT")
-                .unwrap_or_else(|_| "1883".to_string())
-                .parse()?,
+            mqtt_broker_port: loader.parse("MQTT_BROKER_PORT", "1883"),
 
-            mqtt_client_id: env::var("MQTT_CLIENT_ID")
+            mqtt_client_id: source
+                .var("MQTT_CLIENT_ID")
                 .unwrap_or_else(|_| format!("env_edge_gateway_rpi-{}", gateway_id)),
 
-            mqtt_username: env::var("MQTT_USERNAME").ok(),
-            mqtt_password: env::var("MQTT_PASSWORD").ok(),
+            mqtt_username: source.var("MQTT_USERNAME").ok(),
+            mqtt_password: source.var("MQTT_PASSWORD").ok(),
+
+            mqtt_workers: loader.parse("MQTT_WORKERS", "4"),
+            mqtt_queue_capacity: loader.parse("MQTT_QUEUE_CAPACITY", "1000"),
+            mqtt_queue_overflow: loader.parse("MQTT_QUEUE_OVERFLOW", "block"),
 
             // Configuración HTTP
-            http_port: env::var("HTTP_PORT")
+            http_port: loader.optional("HTTP_PORT"),
+
+            http_tls_enabled: loader.parse("HTTP_TLS_ENABLED", "false"),
+
+            http_tls_cert_path: source
+                .var("HTTP_TLS_CERT_PATH")
+                .unwrap_or_else(|_| "tls/gateway.crt".to_string()),
+
+            http_tls_key_path: source
+                .var("HTTP_TLS_KEY_PATH")
+                .unwrap_or_else(|_| "tls/gateway.key".to_string()),
+
+            http_tls_self_signed: loader.parse("HTTP_TLS_SELF_SIGNED", "true"),
+
+            http_tls_hostnames: source
+                .var("HTTP_TLS_HOSTNAMES")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(String::from)
+                .collect(),
+
+            http_rate_limit_per_sec: loader.parse("HTTP_RATE_LIMIT_PER_SEC", "20"),
+
+            http_rate_limit_burst: loader.parse("HTTP_RATE_LIMIT_BURST", "40"),
+
+            http_max_concurrency: loader.parse("HTTP_MAX_CONCURRENCY", "64"),
+
+            http_body_limit_bytes: loader.parse("HTTP_BODY_LIMIT_BYTES", "1048576"),
+
+            http_batch_body_limit_bytes: loader.parse("HTTP_BATCH_BODY_LIMIT_BYTES", "8388608"),
+
+            coap_enabled: loader.parse("COAP_ENABLED", "false"),
+
+            coap_port: loader.parse("COAP_PORT", "5683"),
+
+            coap_max_payload_bytes: loader.parse("COAP_MAX_PAYLOAD_BYTES", "16384"),
+
+            // Lecturas por líneas (dataloggers antiguos)
+            line_tcp_port: loader.optional("LINE_TCP_PORT"),
+
+            line_udp_port: loader.optional("LINE_UDP_PORT"),
+
+            line_location: source
+                .var("LINE_LOCATION")
+                .unwrap_or_else(|_| "datalogger".to_string()),
+
+            line_allowed_ips: loader.check(Self::parse_ip_list(
+                "LINE_ALLOWED_IPS",
+                &source.var("LINE_ALLOWED_IPS").unwrap_or_default(),
+            )),
+
+            webhook_mappings_dir: source
+                .var("WEBHOOK_MAPPINGS_DIR")
+                .unwrap_or_else(|_| "webhooks".to_string()),
+
+            webhook_tokens: loader.check(Self::parse_api_tokens(
+                "WEBHOOK_TOKENS",
+                "",
+                &source.var("WEBHOOK_TOKENS").unwrap_or_default(),
+            )),
+
+            modbus_devices_path: loader.optional("MODBUS_DEVICES_PATH"),
+
+            modbus_poll_secs: loader.parse("MODBUS_POLL_SECS", "30"),
+
+            modbus_timeout_ms: loader.parse("MODBUS_TIMEOUT_MS", "1000"),
+
+            snmp_devices_path: loader.optional("SNMP_DEVICES_PATH"),
+
+            snmp_poll_secs: loader.parse("SNMP_POLL_SECS", "60"),
+
+            snmp_timeout_ms: loader.parse("SNMP_TIMEOUT_MS", "2000"),
+
+            serial_devices_path: loader.optional("SERIAL_DEVICES_PATH"),
+
+            ble_enabled: loader.parse("BLE_ENABLED", "false"),
+
+            ble_interval_secs: loader.parse("BLE_INTERVAL_SECS", "60"),
+
+            ble_devices: loader.check(Self::parse_ble_devices(
+                &source.var("BLE_DEVICES").unwrap_or_default(),
+            )),
+
+            ble_default_location: source
+                .var("BLE_DEFAULT_LOCATION")
+                .unwrap_or_else(|_| "ble".to_string()),
+
+            opcua_devices_path: loader.optional("OPCUA_DEVICES_PATH"),
+
+            opcua_sampling_ms: loader.parse("OPCUA_SAMPLING_MS", "1000"),
+
+            opcua_pki_dir: source
+                .var("OPCUA_PKI_DIR")
+                .unwrap_or_else(|_| "opcua-pki".to_string()),
+
+            // Sensores conectados al gateway
+            onboard_device_id: source
+                .var("ONBOARD_DEVICE_ID")
+                .unwrap_or_else(|_| "gateway-onboard".to_string()),
+
+            onboard_location: source
+                .var("ONBOARD_LOCATION")
+                .unwrap_or_else(|_| "gateway".to_string()),
+
+            onboard_interval_secs: loader.parse("ONBOARD_INTERVAL_SECS", "60"),
+
+            onewire_enabled: loader.parse("ONEWIRE_ENABLED", "false"),
+
+            onewire_devices_dir: source
+                .var("ONEWIRE_DEVICES_DIR")
+                .unwrap_or_else(|_| "/sys/bus/w1/devices".to_string()),
+
+            onewire_probes: loader.check(Self::parse_measurement_map(
+                "ONEWIRE_PROBES",
+                &source.var("ONEWIRE_PROBES").unwrap_or_default(),
+            )),
+
+            i2c_sensors: loader.check(Self::parse_i2c_sensors(
+                &source.var("I2C_SENSORS").unwrap_or_default(),
+            )),
+
+            gpio_outputs: loader.check(Self::parse_gpio_outputs(
+                &source.var("GPIO_OUTPUTS").unwrap_or_default(),
+            )),
+
+            cors_allowed_origins: source
+                .var("CORS_ALLOWED_ORIGINS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|origin| !origin.is_empty())
+                .map(String::from)
+                .collect(),
+
+            admin_api_token: source
+                .var("ADMIN_API_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+
+            viewer_api_tokens: loader.check(Self::parse_api_tokens(
+                "VIEWER_API_TOKENS",
+                "viewer",
+                &source.var("VIEWER_API_TOKENS").unwrap_or_default(),
+            )),
+
+            operator_api_tokens: loader.check(Self::parse_api_tokens(
+                "OPERATOR_API_TOKENS",
+                "operator",
+                &source.var("OPERATOR_API_TOKENS").unwrap_or_default(),
+            )),
+
+            auth_max_failures_per_ip: loader.parse("AUTH_MAX_FAILURES_PER_IP", "10"),
+
+            auth_max_failures_per_key: loader.parse("AUTH_MAX_FAILURES_PER_KEY", "50"),
+
+            auth_failure_window_secs: loader.parse("AUTH_FAILURE_WINDOW_SECS", "300"),
+
+            auth_lockout_secs: loader.parse("AUTH_LOCKOUT_SECS", "900"),
+
+            dashboard_enabled: loader.parse("DASHBOARD_ENABLED", "true"),
+
+            api_v1_sunset: loader.optional("API_V1_SUNSET"),
+
+            readiness_max_pending_sync: loader.parse("READINESS_MAX_PENDING_SYNC", "10000"),
+
+            readiness_require_cloud: loader.parse("READINESS_REQUIRE_CLOUD", "false"),
+
+            backlog_alarm_minutes: loader.parse("BACKLOG_ALARM_MINUTES", "30"),
+
+            backlog_check_interval_secs: loader.parse("BACKLOG_CHECK_INTERVAL_SECS", "60"),
+
+            crash_loop_restarts: loader.parse("CRASH_LOOP_RESTARTS", "5"),
+
+            device_offline_after_secs: loader.parse("DEVICE_OFFLINE_AFTER_SECS", "900"),
+
+            device_presence_check_secs: loader.parse("DEVICE_PRESENCE_CHECK_SECS", "30"),
+
+            device_offline_alert_severity: loader.parse("DEVICE_OFFLINE_ALERT_SEVERITY", "warning"),
+
+            device_approval_required: loader.parse("DEVICE_APPROVAL_REQUIRED", "false"),
+
+            device_auth: loader.parse("DEVICE_AUTH", "off"),
+
+            device_token_grace_secs: loader.parse("DEVICE_TOKEN_GRACE_SECS", "86400"),
+
+            device_archive_dir: source
+                .var("DEVICE_ARCHIVE_DIR")
+                .unwrap_or_else(|_| "archive".to_string()),
+
+            // Cuotas por dispositivo
+            device_quota_messages_per_day: loader.parse("DEVICE_QUOTA_MESSAGES_PER_DAY", "0"),
+
+            device_quota_max_rows: loader.parse("DEVICE_QUOTA_MAX_ROWS", "0"),
+
+            device_quota_action: loader.parse("DEVICE_QUOTA_ACTION", "downsample"),
+
+            device_quota_downsample_factor: loader.parse("DEVICE_QUOTA_DOWNSAMPLE_FACTOR", "10"),
+
+            device_quota_refresh_secs: loader.parse("DEVICE_QUOTA_REFRESH_SECS", "300"),
+
+            // Actualizaciones de firmware (OTA)
+            firmware_dir: source
+                .var("FIRMWARE_DIR")
+                .unwrap_or_else(|_| "firmware".to_string()),
+
+            firmware_max_bytes: loader.parse("FIRMWARE_MAX_BYTES", "4194304"),
+
+            firmware_base_url: loader
+                .optional::<String>("FIRMWARE_BASE_URL")
+                .map(|url| url.trim_end_matches('/').to_string()),
+
+            // Webhooks de alertas
+            alert_webhook_urls: source
+                .var("ALERT_WEBHOOK_URLS")
+                .unwrap_or_default()
+                .split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(String::from)
+                .collect(),
+
+            alert_webhook_secret: source
+                .var("ALERT_WEBHOOK_SECRET")
+                .ok()
+                .filter(|secret| !secret.is_empty()),
+
+            alert_webhook_timeout_secs: loader.parse("ALERT_WEBHOOK_TIMEOUT_SECS", "10"),
+
+            alert_webhook_max_retries: loader.parse("ALERT_WEBHOOK_MAX_RETRIES", "5"),
+
+            alert_webhook_backoff_secs: loader.parse("ALERT_WEBHOOK_BACKOFF_SECS", "2"),
+
+            alert_chart_base_url: loader
+                .optional::<String>("ALERT_CHART_BASE_URL")
+                .map(|url| url.trim_end_matches('/').to_string()),
+
+            // Notificaciones por Telegram y Slack
+            telegram_bot_token: source
+                .var("TELEGRAM_BOT_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+
+            telegram_chat_ids: loader.check(Self::parse_severity_routes(
+                "TELEGRAM_CHAT_IDS",
+                &source.var("TELEGRAM_CHAT_IDS").unwrap_or_default(),
+            )),
+
+            telegram_rate_limit_per_min: loader.parse("TELEGRAM_RATE_LIMIT_PER_MIN", "20"),
+
+            telegram_template: Self::load_template(source, "TELEGRAM_TEMPLATE"),
+
+            slack_webhook_urls: loader.check(Self::parse_severity_routes(
+                "SLACK_WEBHOOK_URLS",
+                &source.var("SLACK_WEBHOOK_URLS").unwrap_or_default(),
+            )),
+
+            slack_rate_limit_per_min: loader.parse("SLACK_RATE_LIMIT_PER_MIN", "20"),
+
+            slack_template: Self::load_template(source, "SLACK_TEMPLATE"),
+
+            // Notificaciones por email
+            smtp_host: source.var("SMTP_HOST").ok().filter(|host| !host.is_empty()),
+            smtp_port: loader.parse("SMTP_PORT", "587"),
+            smtp_security: loader.parse("SMTP_SECURITY", "starttls"),
+            smtp_username: source
+                .var("SMTP_USERNAME")
                 .ok()
-                .and_then(|port| port.parse().ok()),
+                .filter(|user| !user.is_empty()),
+            smtp_password: source
+                .var("SMTP_PASSWORD")
+                .ok()
+                .filter(|password| !password.is_empty()),
+            smtp_from: source.var("SMTP_FROM").ok().filter(|from| !from.is_empty()),
+
+            alert_email_to: loader.check(Self::parse_severity_routes(
+                "ALERT_EMAIL_TO",
+                &source.var("ALERT_EMAIL_TO").unwrap_or_default(),
+            )),
+
+            alert_email_rate_limit_per_min: loader.parse("ALERT_EMAIL_RATE_LIMIT_PER_MIN", "10"),
+
+            alert_email_template: Self::load_template(source, "ALERT_EMAIL_TEMPLATE"),
+
+            email_digest_to: source
+                .var("EMAIL_DIGEST_TO")
+                .unwrap_or_default()
+                .split(',')
+                .map(|address| address.trim().to_string())
+                .filter(|address| !address.is_empty())
+                .collect(),
+
+            email_digest_hour: loader.parse("EMAIL_DIGEST_HOUR", "7"),
 
             // Configuración MQTT cloud (servidor)
-            cloud_mqtt_broker_host: env::var("CLOUD_MQTT_BROKER_HOST")
-                .expect("CLOUD_MQTT_BROKER_HOST debe estar configurada"),
+            cloud_mqtt_broker_host: cloud_var("CLOUD_MQTT_BROKER_HOST"),
 
-            cloud_mqtt_broker_port: env::var("CLOUD_MQTT_BROKER_PORT")
-                .unwrap_or_else(|_| "1883".to_string())
-                .parse()?,
+            cloud_mqtt_broker_port: loader.parse("CLOUD_MQTT_BROKER_PORT", "1883"),
 
-            cloud_mqtt_client_id: env::var("CLOUD_MQTT_CLIENT_ID")
+            cloud_mqtt_client_id: source
+                .var("CLOUD_MQTT_CLIENT_ID")
                 .unwrap_or_else(|_| format!("gateway-cloud-{}", gateway_id)),
 
-            cloud_mqtt_username: env::var("CLOUD_MQTT_USERNAME").ok(),
-            cloud_mqtt_password: env::var("CLOUD_MQTT_PASSWORD").ok(),
+            cloud_mqtt_username: source.var("CLOUD_MQTT_USERNAME").ok(),
+            cloud_mqtt_password: source.var("CLOUD_MQTT_PASSWORD").ok(),
 
-            cloud_mqtt_topic: env::var("CLOUD_MQTT_TOPIC")
+            cloud_mqtt_topic: source
+                .var("CLOUD_MQTT_TOPIC")
                 .unwrap_or_else(|_| "device/messages".to_string()),
+
+            cloud_remote_config_enabled: loader.parse("CLOUD_REMOTE_CONFIG_ENABLED", "true"),
+
+            // Respaldos de base de datos
+            backup_dir: source
+                .var("BACKUP_DIR")
+                .unwrap_or_else(|_| "backups".to_string()),
+
+            backup_schedule_enabled: loader.parse("BACKUP_SCHEDULE_ENABLED", "false"),
+
+            backup_hour: loader.parse("BACKUP_HOUR", "3"),
+
+            backup_keep: loader.parse("BACKUP_KEEP", "7"),
+
+            // Réplica en InfluxDB / VictoriaMetrics
+            local_sink_url: loader.optional("LOCAL_SINK_URL"),
+
+            local_sink_token: source
+                .var("LOCAL_SINK_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+
+            local_sink_measurement: source
+                .var("LOCAL_SINK_MEASUREMENT")
+                .unwrap_or_else(|_| "sensor_readings".to_string()),
+
+            local_sink_batch_size: loader.parse("LOCAL_SINK_BATCH_SIZE", "500"),
+
+            local_sink_flush_secs: loader.parse("LOCAL_SINK_FLUSH_SECS", "5"),
+
+            local_sink_max_buffer: loader.parse("LOCAL_SINK_MAX_BUFFER", "10000"),
+
+            local_sink_timeout_secs: loader.parse("LOCAL_SINK_TIMEOUT_SECS", "10"),
+
+            // Caché de lecturas recientes
+            latest_cache_devices: loader.parse("LATEST_CACHE_DEVICES", "256"),
+
+            latest_cache_depth: loader.parse("LATEST_CACHE_DEPTH", "20"),
+
+            dedup_window_secs: loader.parse("DEDUP_WINDOW_SECS", "600"),
+
+            replay_window_secs: loader.parse("REPLAY_WINDOW_SECS", "0"),
+
+            replay_required: loader.parse("REPLAY_REQUIRED", "false"),
+
+            // Detección de anomalías por histórico
+            anomaly_window_size: loader.parse("ANOMALY_WINDOW_SIZE", "60"),
+
+            anomaly_zscore_threshold: loader.parse("ANOMALY_ZSCORE_THRESHOLD", "3.0"),
+
+            // Líneas base por hora del día
+            baseline_days: loader.parse("BASELINE_DAYS", "14"),
+
+            baseline_min_samples: loader.parse("BASELINE_MIN_SAMPLES", "30"),
+
+            baseline_zscore_threshold: loader.parse("BASELINE_ZSCORE_THRESHOLD", "3.0"),
+
+            baseline_refresh_secs: loader.parse("BASELINE_REFRESH_SECS", "3600"),
+
+            // Batería y señal de los dispositivos
+            battery_trend_hours: loader.parse("BATTERY_TREND_HOURS", "72"),
+
+            battery_empty_percent: loader.parse("BATTERY_EMPTY_PERCENT", "5.0"),
+
+            battery_empty_voltage: loader.parse("BATTERY_EMPTY_VOLTAGE", "3.3"),
+
+            battery_attention_days: loader.parse("BATTERY_ATTENTION_DAYS", "7.0"),
+
+            battery_low_alerts: loader.parse("BATTERY_LOW_ALERTS", "true"),
+
+            battery_low_alert_severity: loader.parse("BATTERY_LOW_ALERT_SEVERITY", "warning"),
+
+            sensor_stuck_after_secs: loader.parse("SENSOR_STUCK_AFTER_SECS", "7200"),
+
+            sensor_stuck_min_readings: loader.parse("SENSOR_STUCK_MIN_READINGS", "10"),
+
+            sensor_stuck_measurements: source
+                .var("SENSOR_STUCK_MEASUREMENTS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|measurement| measurement.trim().to_lowercase())
+                .filter(|measurement| !measurement.is_empty())
+                .collect(),
+
+            sensor_stuck_alert_severity: loader.parse("SENSOR_STUCK_ALERT_SEVERITY", "warning"),
+
+            rssi_poor_dbm: loader.parse("RSSI_POOR_DBM", "-80.0"),
+
+            rssi_critical_dbm: loader.parse("RSSI_CRITICAL_DBM", "-90.0"),
+
+            // Suavizado de métricas
+            ewma_alpha: loader.parse("EWMA_ALPHA", "0.3"),
+
+            cloud_forward_smoothed: loader.parse("CLOUD_FORWARD_SMOOTHED", "false"),
+
+            // Scripts de métricas derivadas
+            scripts_dir: source
+                .var("SCRIPTS_DIR")
+                .unwrap_or_else(|_| "scripts".to_string()),
+
+            scripts_reload_secs: loader.parse("SCRIPTS_RELOAD_SECS", "10"),
+
+            // Filtros de ruido
+            measurement_filters: loader.check(Self::parse_measurement_map(
+                "MEASUREMENT_FILTERS",
+                &source.var("MEASUREMENT_FILTERS").unwrap_or_default(),
+            )),
+
+            kalman_process_noise: loader.parse("KALMAN_PROCESS_NOISE", "0.01"),
+
+            kalman_measurement_noise: loader.parse("KALMAN_MEASUREMENT_NOISE", "1.0"),
+
+            lowpass_cutoff_hz: loader.parse("LOWPASS_CUTOFF_HZ", "0.1"),
+
+            hampel_window_size: loader.parse("HAMPEL_WINDOW_SIZE", "0"),
+
+            hampel_threshold: loader.parse("HAMPEL_THRESHOLD", "3.0"),
+
+            // Relleno de métricas faltantes
+            interpolation_method: loader.parse("INTERPOLATION_METHOD", "off"),
+
+            interpolation_max_age_secs: loader.parse("INTERPOLATION_MAX_AGE_SECS", "600"),
+
+            // Modelos de confort
+            comfort_model: loader.parse("COMFORT_MODEL", "heuristic"),
+
+            comfort_models: loader.check(Self::parse_measurement_map(
+                "COMFORT_MODELS",
+                &source.var("COMFORT_MODELS").unwrap_or_default(),
+            )),
+
+            comfort_clothing_clo: loader.parse("COMFORT_CLOTHING_CLO", "0.7"),
+
+            comfort_metabolic_rate: loader.parse("COMFORT_METABOLIC_RATE", "1.1"),
+
+            comfort_air_speed: loader.parse("COMFORT_AIR_SPEED", "0.1"),
+
+            comfort_outdoor_mean_temp: loader.parse("COMFORT_OUTDOOR_MEAN_TEMP", "20.0"),
+
+            // Calidad del aire
+            aqi_scale: loader.parse("AQI_SCALE", "epa"),
+
+            // Marcas de tiempo de los dispositivos
+            device_clock_tolerance_secs: loader.parse("DEVICE_CLOCK_TOLERANCE_SECS", "120"),
+
+            device_max_backfill_hours: loader.parse("DEVICE_MAX_BACKFILL_HOURS", "168"),
+
+            time_sync_interval_secs: loader.parse("TIME_SYNC_INTERVAL_SECS", "60"),
+
+            // Agregados por ventana deslizante
+            aggregation_windows: loader.check(Self::parse_durations(
+                "AGGREGATION_WINDOWS",
+                &source
+                    .var("AGGREGATION_WINDOWS")
+                    .unwrap_or_else(|_| "1m,5m,15m".to_string()),
+            )),
+
+            // Fusión de sensores por ubicación
+            fusion_window_secs: loader.parse("FUSION_WINDOW_SECS", "0"),
+
+            fusion_tolerances: loader.check(Self::parse_measurement_map(
+                "FUSION_TOLERANCES",
+                &source.var("FUSION_TOLERANCES").unwrap_or_default(),
+            )),
+
+            fusion_default_tolerance_pct: loader.parse("FUSION_DEFAULT_TOLERANCE_PCT", "10.0"),
+
+            // Detección por velocidad de cambio
+            max_rate_of_change: loader.check(Self::parse_measurement_map(
+                "MAX_RATE_OF_CHANGE",
+                &source.var("MAX_RATE_OF_CHANGE").unwrap_or_default(),
+            )),
+
+            // Mantenimiento de base de datos
+            maintenance_enabled: loader.parse("MAINTENANCE_ENABLED", "true"),
+
+            maintenance_interval_hours: loader.parse("MAINTENANCE_INTERVAL_HOURS", "168"),
+
+            maintenance_hour: loader.parse("MAINTENANCE_HOUR", "4"),
+
+            host_metrics_interval_secs: loader.parse("HOST_METRICS_INTERVAL_SECS", "30"),
+
+            host_temperature_warning_c: loader.parse("HOST_TEMPERATURE_WARNING_C", "80"),
+
+            host_disk_alert_percent: loader.parse("HOST_DISK_ALERT_PERCENT", "90"),
+
+            self_health_check_secs: loader.parse("SELF_HEALTH_CHECK_SECS", "60"),
+
+            db_error_alert_count: loader.parse("DB_ERROR_ALERT_COUNT", "5"),
+
+            // Vacío deshabilita la medición
+            clock_drift_ntp_server: Some(
+                source
+                    .var("CLOCK_DRIFT_NTP_SERVER")
+                    .unwrap_or_else(|_| "pool.ntp.org:123".to_string())
+                    .trim()
+                    .to_string(),
+            )
+            .filter(|server| !server.is_empty()),
+
+            clock_drift_max_ms: loader.parse("CLOCK_DRIFT_MAX_MS", "2000"),
+
+            clock_drift_check_secs: loader.parse("CLOCK_DRIFT_CHECK_SECS", "900"),
+
+            gateway_status_interval_secs: loader.parse("GATEWAY_STATUS_INTERVAL_SECS", "60"),
         };
 
+        config.validate(&loader);
+
+        let problems = loader.into_problems();
+        if !problems.is_empty() {
+            anyhow::bail!(
+                "Configuración inválida ({} {}):\n{}",
+                problems.len(),
+                if problems.len() == 1 {
+                    "problema"
+                } else {
+                    "problemas"
+                },
+                problems
+                    .iter()
+                    .map(|problem| format!("  - {}", problem))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            );
+        }
+
         Ok(config)
     }
+
+    /// Comprueba la coherencia de los valores ya interpretados
+    fn validate(&self, loader: &Loader) {
+        let check = |ok: bool, message: &str| {
+            if !ok {
+                loader.problem(message);
+            }
+        };
+
+        check(
+            self.log_level
+                .parse::<tracing_subscriber::filter::LevelFilter>()
+                .is_ok(),
+            "LOG_LEVEL: usar trace, debug, info, warn, error u off",
+        );
+        check(
+            self.log_stdout || self.log_file_dir.is_some(),
+            "LOG_STDOUT: con false hay que definir LOG_FILE_DIR",
+        );
+        check(self.log_file_keep > 0, "LOG_FILE_KEEP: debe ser al menos 1");
+        check(
+            self.mqtt_broker_port != 0,
+            "MQTT_BROKER_PORT: el puerto no puede ser 0",
+        );
+        check(
+            (1..=10_000).contains(&self.db_write_max_rows),
+            "DB_WRITE_MAX_ROWS: debe estar entre 1 y 10000",
+        );
+        check(
+            self.db_write_buffer_max >= self.db_write_max_rows,
+            "DB_WRITE_BUFFER_MAX: debe ser al menos DB_WRITE_MAX_ROWS",
+        );
+        check(
+            (1..=64).contains(&self.mqtt_workers),
+            "MQTT_WORKERS: debe estar entre 1 y 64",
+        );
+        check(
+            self.mqtt_queue_capacity >= self.mqtt_workers,
+            "MQTT_QUEUE_CAPACITY: debe ser al menos MQTT_WORKERS",
+        );
+        check(
+            self.cloud_mqtt_broker_port != 0,
+            "CLOUD_MQTT_BROKER_PORT: el puerto no puede ser 0",
+        );
+        check(
+            self.http_port != Some(0),
+            "HTTP_PORT: el puerto no puede ser 0",
+        );
+        check(
+            !self.http_tls_enabled
+                || (!self.http_tls_cert_path.is_empty() && !self.http_tls_key_path.is_empty()),
+            "HTTP_TLS_CERT_PATH: con HTTP_TLS_ENABLED hay que indicar el certificado y la clave",
+        );
+        check(
+            self.admin_api_token.is_some()
+                || (self.viewer_api_tokens.is_empty() && self.operator_api_tokens.is_empty()),
+            "ADMIN_API_TOKEN: es obligatorio con VIEWER_API_TOKENS u OPERATOR_API_TOKENS",
+        );
+        let mut tokens: Vec<&str> = self
+            .viewer_api_tokens
+            .iter()
+            .chain(&self.operator_api_tokens)
+            .map(|entry| entry.token.as_str())
+            .chain(self.admin_api_token.as_deref())
+            .collect();
+        let configured = tokens.len();
+        tokens.sort_unstable();
+        tokens.dedup();
+        check(
+            tokens.len() == configured,
+            "VIEWER_API_TOKENS: cada token debe ser único entre todos los roles",
+        );
+        check(
+            self.auth_failure_window_secs > 0,
+            "AUTH_FAILURE_WINDOW_SECS: debe ser al menos 1",
+        );
+        check(
+            self.auth_lockout_secs > 0,
+            "AUTH_LOCKOUT_SECS: debe ser al menos 1",
+        );
+        check(
+            self.cloud_sync_batch_size > 0,
+            "CLOUD_SYNC_BATCH_SIZE: debe ser al menos 1",
+        );
+        check(
+            self.cloud_sync_interval_secs > 0,
+            "CLOUD_SYNC_INTERVAL_SECS: debe ser al menos 1",
+        );
+        check(
+            self.data_retention_days > 0,
+            "DATA_RETENTION_DAYS: debe ser al menos 1",
+        );
+        check(
+            self.alert_retention_days > 0,
+            "ALERT_RETENTION_DAYS: debe ser al menos 1",
+        );
+        check(
+            self.http_rate_limit_per_sec > 0.0,
+            "HTTP_RATE_LIMIT_PER_SEC: debe ser al menos 1",
+        );
+        check(
+            self.http_max_concurrency > 0,
+            "HTTP_MAX_CONCURRENCY: debe ser al menos 1",
+        );
+        check(
+            self.backup_hour < 24,
+            "BACKUP_HOUR: debe estar entre 0 y 23",
+        );
+        check(self.backup_keep > 0, "BACKUP_KEEP: debe ser al menos 1");
+        check(
+            self.local_sink_url
+                .as_deref()
+                .is_none_or(|url| url.starts_with("http://") || url.starts_with("https://")),
+            "LOCAL_SINK_URL: debe empezar por http:// o https://",
+        );
+        check(
+            !self.local_sink_measurement.is_empty(),
+            "LOCAL_SINK_MEASUREMENT: no puede estar vacío",
+        );
+        check(
+            self.local_sink_batch_size > 0,
+            "LOCAL_SINK_BATCH_SIZE: debe ser al menos 1",
+        );
+        check(
+            self.local_sink_flush_secs > 0,
+            "LOCAL_SINK_FLUSH_SECS: debe ser al menos 1",
+        );
+        check(
+            self.local_sink_max_buffer >= self.local_sink_batch_size,
+            "LOCAL_SINK_MAX_BUFFER: debe ser al menos LOCAL_SINK_BATCH_SIZE",
+        );
+        check(
+            self.local_sink_timeout_secs > 0,
+            "LOCAL_SINK_TIMEOUT_SECS: debe ser al menos 1",
+        );
+        check(
+            self.maintenance_hour < 24,
+            "MAINTENANCE_HOUR: debe estar entre 0 y 23",
+        );
+        check(
+            !self.maintenance_enabled || self.maintenance_interval_hours > 0,
+            "MAINTENANCE_INTERVAL_HOURS: debe ser al menos 1 con el mantenimiento habilitado",
+        );
+        check(
+            self.baseline_days <= 0 || self.baseline_refresh_secs > 0,
+            "BASELINE_REFRESH_SECS: debe ser al menos 1 con BASELINE_DAYS activo",
+        );
+        check(
+            self.anomaly_window_size >= 2,
+            "ANOMALY_WINDOW_SIZE: se necesitan al menos 2 lecturas",
+        );
+        check(
+            (0.0..=1.0).contains(&self.ewma_alpha),
+            "EWMA_ALPHA: debe estar entre 0 y 1",
+        );
+        check(
+            self.backlog_alarm_minutes == 0 || self.backlog_check_interval_secs > 0,
+            "BACKLOG_CHECK_INTERVAL_SECS: debe ser al menos 1 con BACKLOG_ALARM_MINUTES activo",
+        );
+        check(
+            self.cloud_sync_enabled || !self.readiness_require_cloud,
+            "READINESS_REQUIRE_CLOUD: no aplica con CLOUD_SYNC_ENABLED=false",
+        );
+        check(
+            self.device_quota_downsample_factor > 0,
+            "DEVICE_QUOTA_DOWNSAMPLE_FACTOR: debe ser al menos 1",
+        );
+        check(
+            self.device_quota_refresh_secs > 0,
+            "DEVICE_QUOTA_REFRESH_SECS: debe ser al menos 1",
+        );
+        check(
+            self.firmware_max_bytes > 0,
+            "FIRMWARE_MAX_BYTES: debe ser al menos 1",
+        );
+        check(
+            self.firmware_base_url
+                .as_ref()
+                .is_none_or(|url| url.starts_with("http://") || url.starts_with("https://")),
+            "FIRMWARE_BASE_URL: debe comenzar con http:// o https://",
+        );
+        check(
+            self.alert_webhook_urls
+                .iter()
+                .all(|url| url.starts_with("http://") || url.starts_with("https://")),
+            "ALERT_WEBHOOK_URLS: cada URL debe comenzar con http:// o https://",
+        );
+        let uses_email = !self.alert_email_to.is_empty() || !self.email_digest_to.is_empty();
+        check(
+            !uses_email || (self.smtp_host.is_some() && self.smtp_from.is_some()),
+            "SMTP_HOST: SMTP_HOST y SMTP_FROM son obligatorios con ALERT_EMAIL_TO o EMAIL_DIGEST_TO",
+        );
+        check(
+            self.smtp_from
+                .as_ref()
+                .is_none_or(|from| from.parse::<lettre::message::Mailbox>().is_ok()),
+            "SMTP_FROM: dirección de email inválida",
+        );
+        check(
+            self.alert_email_to
+                .iter()
+                .all(|route| route.target.parse::<lettre::message::Mailbox>().is_ok()),
+            "ALERT_EMAIL_TO: cada destinatario debe ser una dirección de email válida",
+        );
+        check(
+            self.email_digest_to
+                .iter()
+                .all(|address| address.parse::<lettre::message::Mailbox>().is_ok()),
+            "EMAIL_DIGEST_TO: cada destinatario debe ser una dirección de email válida",
+        );
+        check(
+            self.smtp_username.is_some() == self.smtp_password.is_some(),
+            "SMTP_USERNAME: SMTP_USERNAME y SMTP_PASSWORD se definen juntos",
+        );
+        check(
+            self.email_digest_hour < 24,
+            "EMAIL_DIGEST_HOUR: debe estar entre 0 y 23",
+        );
+        check(
+            self.telegram_chat_ids.is_empty() || self.telegram_bot_token.is_some(),
+            "TELEGRAM_BOT_TOKEN: es obligatorio con TELEGRAM_CHAT_IDS",
+        );
+        check(
+            self.slack_webhook_urls.iter().all(|route| {
+                route.target.starts_with("http://") || route.target.starts_with("https://")
+            }),
+            "SLACK_WEBHOOK_URLS: cada URL debe comenzar con http:// o https://",
+        );
+        check(
+            self.alert_chart_base_url
+                .as_ref()
+                .is_none_or(|url| url.starts_with("http://") || url.starts_with("https://")),
+            "ALERT_CHART_BASE_URL: debe comenzar con http:// o https://",
+        );
+        check(
+            self.alert_webhook_timeout_secs > 0,
+            "ALERT_WEBHOOK_TIMEOUT_SECS: debe ser al menos 1",
+        );
+        check(
+            self.alert_webhook_backoff_secs > 0,
+            "ALERT_WEBHOOK_BACKOFF_SECS: debe ser al menos 1",
+        );
+        check(
+            self.rssi_critical_dbm < self.rssi_poor_dbm,
+            "RSSI_CRITICAL_DBM: debe ser menor que RSSI_POOR_DBM",
+        );
+        check(
+            (0.0..=100.0).contains(&self.host_disk_alert_percent),
+            "HOST_DISK_ALERT_PERCENT: debe estar entre 0 y 100",
+        );
+        check(
+            !self.coap_enabled || self.coap_port != 0,
+            "COAP_PORT: el puerto no puede ser 0",
+        );
+        check(
+            (64..=65000).contains(&self.coap_max_payload_bytes),
+            "COAP_MAX_PAYLOAD_BYTES: debe estar entre 64 y 65000",
+        );
+        check(
+            self.line_tcp_port != Some(0),
+            "LINE_TCP_PORT: el puerto no puede ser 0",
+        );
+        check(
+            self.line_udp_port != Some(0),
+            "LINE_UDP_PORT: el puerto no puede ser 0",
+        );
+        check(
+            (1..=200).contains(&self.line_location.len()),
+            "LINE_LOCATION: debe tener entre 1 y 200 caracteres",
+        );
+        check(
+            self.modbus_poll_secs > 0,
+            "MODBUS_POLL_SECS: debe ser al menos 1",
+        );
+        check(
+            self.modbus_timeout_ms > 0,
+            "MODBUS_TIMEOUT_MS: debe ser al menos 1",
+        );
+        check(
+            self.snmp_poll_secs > 0,
+            "SNMP_POLL_SECS: debe ser al menos 1",
+        );
+        check(
+            self.snmp_timeout_ms > 0,
+            "SNMP_TIMEOUT_MS: debe ser al menos 1",
+        );
+        check(
+            !self.ble_enabled || cfg!(feature = "ble"),
+            "BLE_ENABLED: requiere compilar el gateway con la feature `ble`",
+        );
+        check(
+            !self.ble_default_location.trim().is_empty(),
+            "BLE_DEFAULT_LOCATION: no puede estar vacía",
+        );
+        check(
+            (1..=50).contains(&self.onboard_device_id.len()),
+            "ONBOARD_DEVICE_ID: debe tener entre 1 y 50 caracteres",
+        );
+        check(
+            (1..=200).contains(&self.onboard_location.len()),
+            "ONBOARD_LOCATION: debe tener entre 1 y 200 caracteres",
+        );
+        check(
+            self.onboard_interval_secs > 0,
+            "ONBOARD_INTERVAL_SECS: debe ser al menos 1",
+        );
+        check(
+            self.i2c_sensors.is_empty() || cfg!(feature = "i2c"),
+            "I2C_SENSORS: requiere compilar el gateway con la feature `i2c`",
+        );
+        check(
+            self.gpio_outputs.is_empty() || cfg!(feature = "gpio"),
+            "GPIO_OUTPUTS: requiere compilar el gateway con la feature `gpio`",
+        );
+        check(
+            self.opcua_devices_path.is_none() || cfg!(feature = "opcua"),
+            "OPCUA_DEVICES_PATH: requiere compilar el gateway con la feature `opcua`",
+        );
+        check(
+            self.opcua_sampling_ms > 0,
+            "OPCUA_SAMPLING_MS: debe ser al menos 1",
+        );
+        check(
+            self.clock_drift_check_secs > 0,
+            "CLOCK_DRIFT_CHECK_SECS: debe ser al menos 1",
+        );
+        check(
+            self.sensor_stuck_min_readings >= 2,
+            "SENSOR_STUCK_MIN_READINGS: debe ser al menos 2",
+        );
+
+        if let Err(e) = Self::check_database_path(&self.database_url) {
+            loader.problem(format!("DATABASE_URL: {}", e));
+        }
+    }
+
+    /// Verifica que la base de datos SQLite se pueda abrir (o crear) en la ruta indicada
+    fn check_database_path(url: &str) -> Result<(), String> {
+        let rest = url
+            .strip_prefix("sqlite://")
+            .or_else(|| url.strip_prefix("sqlite:"))
+            .ok_or_else(|| format!("se esperaba una URL sqlite:// ({})", url))?;
+        let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+        if path.is_empty() || path == ":memory:" || query.contains("mode=memory") {
+            return Ok(());
+        }
+
+        let path = Path::new(path);
+        if path.is_dir() {
+            return Err(format!("{} es un directorio", path.display()));
+        }
+
+        if path.exists() {
+            let metadata = std::fs::metadata(path).map_err(|e| e.to_string())?;
+            if metadata.permissions().readonly() && !query.contains("mode=ro") {
+                return Err(format!("{} es de solo lectura", path.display()));
+            }
+            return Ok(());
+        }
+
+        if !query.contains("mode=rwc") {
+            return Err(format!(
+                "{} no existe (añadir ?mode=rwc para crearla)",
+                path.display()
+            ));
+        }
+
+        let parent = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        match std::fs::metadata(parent) {
+            Ok(metadata) if !metadata.is_dir() => {
+                Err(format!("{} no es un directorio", parent.display()))
+            }
+            Ok(metadata) if metadata.permissions().readonly() => Err(format!(
+                "el directorio {} es de solo lectura",
+                parent.display()
+            )),
+            Ok(_) => Ok(()),
+            Err(_) => Err(format!("el directorio {} no existe", parent.display())),
+        }
+    }
+
+    /// Interpreta una lista `clave:valor` separada por comas (clave = medición o ubicación)
+    /// (`name` es la variable de entorno, usada en los mensajes de error)
+    fn parse_measurement_map<T>(name: &str, value: &str) -> anyhow::Result<HashMap<String, T>>
+    where
+        T: FromStr,
+        T::Err: std::fmt::Display,
+    {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (measurement, value) = entry
+                    .split_once(':')
+                    .ok_or_else(|| anyhow::anyhow!("Entrada inválida en {}: {}", name, entry))?;
+                let value = value.trim().parse().map_err(|e| {
+                    anyhow::anyhow!("Entrada inválida en {}: {} ({})", name, entry, e)
+                })?;
+                Ok((measurement.trim().to_string(), value))
+            })
+            .collect()
+    }
+
+    /// Interpreta una lista de direcciones IP separadas por comas
+    fn parse_ip_list(name: &str, value: &str) -> anyhow::Result<Vec<IpAddr>> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                entry
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Dirección IP inválida en {}: {}", name, entry))
+            })
+            .collect()
+    }
+
+    /// Interpreta la lista `modelo:bus:dirección[:nombre]` de sensores I2C; la
+    /// dirección admite hexadecimal (`0x76`) o decimal
+    fn parse_i2c_sensors(value: &str) -> anyhow::Result<Vec<I2cSensor>> {
+        let sensors = value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let invalid = || {
+                    anyhow::anyhow!(
+                        "Entrada inválida en I2C_SENSORS: {} (se esperaba modelo:bus:dirección[:nombre])",
+                        entry
+                    )
+                };
+                let fields: Vec<&str> = entry.split(':').map(str::trim).collect();
+                let (model, bus, address, name) = match fields[..] {
+                    [model, bus, address] => (model, bus, address, None),
+                    [model, bus, address, name] if !name.is_empty() => {
+                        (model, bus, address, Some(name.to_string()))
+                    }
+                    _ => return Err(invalid()),
+                };
+                let model: I2cSensorModel = model.parse()?;
+                let bus = bus.parse().map_err(|_| invalid())?;
+                let address = match address
+                    .strip_prefix("0x")
+                    .or_else(|| address.strip_prefix("0X"))
+                {
+                    Some(hex) => u8::from_str_radix(hex, 16),
+                    None => address.parse(),
+                }
+                .map_err(|_| invalid())?;
+
+                // Cada chip solo admite las dos direcciones que fija su pin de selección
+                let allowed: [u8; 2] = match model {
+                    I2cSensorModel::Bme280 => [0x76, 0x77],
+                    I2cSensorModel::Sht31 => [0x44, 0x45],
+                };
+                anyhow::ensure!(
+                    allowed.contains(&address),
+                    "I2C_SENSORS: {} usa la dirección 0x{:02x} o 0x{:02x}",
+                    entry,
+                    allowed[0],
+                    allowed[1]
+                );
+                Ok(I2cSensor {
+                    model,
+                    bus,
+                    address,
+                    name,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut seen = HashSet::new();
+        for sensor in &sensors {
+            anyhow::ensure!(
+                seen.insert((sensor.bus, sensor.address)),
+                "I2C_SENSORS: la dirección 0x{:02x} del bus {} aparece más de una vez",
+                sensor.address,
+                sensor.bus
+            );
+        }
+        Ok(sensors)
+    }
+
+    /// Interpreta la lista `nombre:pin[:low]` de salidas GPIO; el pin es su
+    /// número BCM
+    fn parse_gpio_outputs(value: &str) -> anyhow::Result<Vec<GpioOutput>> {
+        let outputs = value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let invalid = || {
+                    anyhow::anyhow!(
+                        "Entrada inválida en GPIO_OUTPUTS: {} (se esperaba nombre:pin[:low])",
+                        entry
+                    )
+                };
+                let fields: Vec<&str> = entry.split(':').map(str::trim).collect();
+                let (name, pin, active_low) = match fields[..] {
+                    [name, pin] => (name, pin, false),
+                    [name, pin, "low"] => (name, pin, true),
+                    [name, pin, "high"] => (name, pin, false),
+                    _ => return Err(invalid()),
+                };
+                anyhow::ensure!(
+                    (1..=50).contains(&name.len())
+                        && name
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+                    "GPIO_OUTPUTS: el nombre {} admite letras, números, _ y - (hasta 50)",
+                    name
+                );
+                let pin: u8 = pin.parse().map_err(|_| invalid())?;
+                // GPIO0-27 del header de 40 pines
+                anyhow::ensure!(
+                    pin <= 27,
+                    "GPIO_OUTPUTS: {} usa el pin {}; el header admite GPIO0 a GPIO27",
+                    name,
+                    pin
+                );
+                Ok(GpioOutput {
+                    name: name.to_string(),
+                    pin,
+                    active_low,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut names = HashSet::new();
+        let mut pins = HashSet::new();
+        for output in &outputs {
+            anyhow::ensure!(
+                names.insert(output.name.as_str()),
+                "GPIO_OUTPUTS: la salida {} aparece más de una vez",
+                output.name
+            );
+            anyhow::ensure!(
+                pins.insert(output.pin),
+                "GPIO_OUTPUTS: el pin {} está asignado a más de una salida",
+                output.pin
+            );
+        }
+        Ok(outputs)
+    }
+
+    /// Interpreta la lista `mac:ubicación` de sensores BLE; la MAC admite `:` o `-`
+    fn parse_ble_devices(value: &str) -> anyhow::Result<HashMap<String, String>> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let invalid = || anyhow::anyhow!("Entrada inválida en BLE_DEVICES: {}", entry);
+                let (mac, location) = entry.rsplit_once(':').ok_or_else(invalid)?;
+                let mac: String = mac
+                    .chars()
+                    .filter(|c| !matches!(c, ':' | '-'))
+                    .collect::<String>()
+                    .to_lowercase();
+                if mac.len() != 12
+                    || !mac.chars().all(|c| c.is_ascii_hexdigit())
+                    || location.trim().is_empty()
+                {
+                    return Err(invalid());
+                }
+                Ok((mac, location.trim().to_string()))
+            })
+            .collect()
+    }
+
+    /// Interpreta una lista de duraciones (`30s,5m,1h`) y la retorna en segundos
+    fn parse_durations(name: &str, value: &str) -> anyhow::Result<Vec<u64>> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (amount, multiplier) = match entry.char_indices().last() {
+                    Some((i, 's')) => (&entry[..i], 1),
+                    Some((i, 'm')) => (&entry[..i], 60),
+                    Some((i, 'h')) => (&entry[..i], 3600),
+                    _ => (entry, 1),
+                };
+                let amount: u64 = amount
+                    .trim()
+                    .parse()
+                    .ok()
+                    .filter(|a| *a > 0)
+                    .ok_or_else(|| anyhow::anyhow!("Duración inválida en {}: {}", name, entry))?;
+                Ok(amount * multiplier)
+            })
+            .collect()
+    }
+
+    /// Interpreta `severidad:destino,...`; sin severidad, el destino recibe todas
+    ///
+    /// El prefijo solo se toma como severidad si es `info`, `warning` o
+    /// `critical`, para admitir destinos que contienen `:` (URLs).
+    fn parse_severity_routes(name: &str, value: &str) -> anyhow::Result<Vec<SeverityRoute>> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (min_severity, target) = match entry.split_once(':') {
+                    Some((prefix, target)) => match prefix.trim().parse::<AlertSeverity>() {
+                        Ok(severity) => (severity, target.trim()),
+                        Err(_) => (AlertSeverity::Info, entry),
+                    },
+                    None => (AlertSeverity::Info, entry),
+                };
+                if target.is_empty() {
+                    anyhow::bail!("Destino vacío en {}: {}", name, entry);
+                }
+                Ok(SeverityRoute {
+                    min_severity,
+                    target: target.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Plantilla de notificación; `\n` se interpreta como salto de línea
+    fn load_template(source: &ConfigSource, name: &str) -> Option<String> {
+        source
+            .var(name)
+            .ok()
+            .filter(|template| !template.trim().is_empty())
+            .map(|template| template.replace("\\n", "\n"))
+    }
+
+    /// Interpreta `nombre:token,...`; sin nombre, el cliente se identifica por su rol
+    fn parse_api_tokens(name: &str, role: &str, value: &str) -> anyhow::Result<Vec<ApiToken>> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (client, token) = match entry.split_once(':') {
+                    Some((client, token)) => (client.trim(), token.trim()),
+                    None => (role, entry),
+                };
+                if client.is_empty() || token.is_empty() {
+                    anyhow::bail!("Token inválido en {}: se esperaba nombre:token", name);
+                }
+                Ok(ApiToken {
+                    name: client.to_string(),
+                    token: token.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Obtiene la clave de cifrado desde `DATABASE_KEY` (o `DATABASE_KEY_FILE`,
+    /// p. ej. un secreto montado)
+    fn load_database_key(source: &ConfigSource) -> Option<String> {
+        source
+            .var("DATABASE_KEY")
+            .ok()
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty())
+    }
 }
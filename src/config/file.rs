@@ -0,0 +1,286 @@
+use super::profile::Environment;
+use ::config::{File, Value, ValueKind};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Archivo de configuración buscado en el directorio de trabajo si no se indica otro
+const DEFAULT_CONFIG_FILE: &str = "gateway.toml";
+
+/// Sufijo de las variables que apuntan a un archivo con el valor (secretos montados)
+const FILE_SUFFIX: &str = "_FILE";
+
+/// Secciones que solo agrupan claves y no aportan prefijo al nombre de la variable
+const GROUPING_SECTIONS: &[&str] = &["general", "processing", "retention", "alerting"];
+
+/// Origen de los valores de configuración
+///
+/// Las variables de entorno (incluido `.env`) tienen prioridad sobre el archivo
+/// de configuración (TOML o YAML). Las secciones del archivo se traducen al nombre
+/// de la variable equivalente: `[mqtt] broker_host` es `MQTT_BROKER_HOST` y
+/// `[cloud.mqtt] topic` es `CLOUD_MQTT_TOPIC`; las secciones de agrupación
+/// (`processing`, `retention`, `alerting`, `general`) no añaden prefijo.
+///
+/// Cualquier variable admite la variante `NOMBRE_FILE` con la ruta de un archivo
+/// que contiene el valor (secretos de Docker/Kubernetes). El archivo solo se lee
+/// cuando la configuración pide `NOMBRE`, de modo que variables ajenas como
+/// `SSL_CERT_FILE` no se interpretan como secretos. `SECRETS_DIR` permite
+/// montar un directorio donde cada archivo (`cloud_api_key`, `mqtt_password`...)
+/// aporta la variable de su nombre. Orden de prioridad: `NOMBRE_FILE` y `NOMBRE`
+/// del entorno, `SECRETS_DIR`, y `NOMBRE_FILE` y `NOMBRE` del archivo. Los
+/// valores por defecto del perfil de `GATEWAY_ENV` se usan en último lugar.
+pub(super) struct ConfigSource {
+    path: Option<PathBuf>,
+    environment: Environment,
+    values: HashMap<String, String>,
+    /// Rutas de las variables `NOMBRE_FILE` del entorno, por `NOMBRE`
+    env_secret_files: HashMap<String, String>,
+    dir_secrets: HashMap<String, String>,
+    /// Rutas de las claves `NOMBRE_FILE` del archivo de configuración, por `NOMBRE`
+    file_secret_files: HashMap<String, String>,
+    /// Problemas de carga; los secretos ilegibles se agregan al pedir su variable
+    errors: RefCell<Vec<String>>,
+}
+
+impl ConfigSource {
+    /// Carga el archivo indicado (`--config <ruta>`) o el de `CONFIG_PATH`, o
+    /// `gateway.toml` si existe en el directorio de trabajo
+    pub fn load(explicit: Option<&Path>) -> anyhow::Result<Self> {
+        let path = match explicit {
+            Some(path) => Some(path.to_path_buf()),
+            None => match env::var("CONFIG_PATH") {
+                Ok(path) if !path.is_empty() => Some(PathBuf::from(path)),
+                _ => Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|path| path.exists()),
+            },
+        };
+
+        let values = match &path {
+            Some(path) => read_file(path)?,
+            None => HashMap::new(),
+        };
+
+        let mut errors = Vec::new();
+        let env_secret_files = secret_files(env::vars());
+        let file_secret_files = secret_files(values.clone());
+        let dir_secrets = match env::var("SECRETS_DIR")
+            .ok()
+            .or_else(|| values.get("SECRETS_DIR").cloned())
+        {
+            Some(dir) if !dir.is_empty() => read_secrets_dir(Path::new(&dir), &mut errors),
+            _ => HashMap::new(),
+        };
+
+        let environment = match env::var("GATEWAY_ENV")
+            .ok()
+            .or_else(|| values.get("GATEWAY_ENV").cloned())
+        {
+            Some(name) if !name.trim().is_empty() => name.parse().unwrap_or_else(|e| {
+                errors.push(format!("GATEWAY_ENV: {}", e));
+                Environment::default()
+            }),
+            _ => Environment::default(),
+        };
+
+        Ok(Self {
+            path,
+            environment,
+            values,
+            env_secret_files,
+            dir_secrets,
+            file_secret_files,
+            errors: RefCell::new(errors),
+        })
+    }
+
+    /// Ruta del archivo de configuración cargado, si hay alguno
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Perfil de entorno seleccionado
+    pub fn environment(&self) -> Environment {
+        self.environment
+    }
+
+    /// Valor de una variable: entorno primero, secretos, archivo y perfil después
+    pub fn var(&self, name: &str) -> Result<String, env::VarError> {
+        if let Some(value) = self.secret_file(&self.env_secret_files, name) {
+            return Ok(value);
+        }
+
+        env::var(name).or_else(|err| {
+            self.dir_secrets
+                .get(name)
+                .cloned()
+                .or_else(|| self.secret_file(&self.file_secret_files, name))
+                .or_else(|| self.values.get(name).cloned())
+                .or_else(|| self.profile_default(name))
+                .ok_or(err)
+        })
+    }
+
+    /// Lee el archivo de `NOMBRE_FILE`; si no se puede, lo registra como problema
+    fn secret_file(&self, paths: &HashMap<String, String>, name: &str) -> Option<String> {
+        let path = paths.get(name)?;
+
+        match read_secret(Path::new(path)) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                let error = format!("{}{}: no se pudo leer {} ({})", name, FILE_SUFFIX, path, e);
+                let mut errors = self.errors.borrow_mut();
+                if !errors.contains(&error) {
+                    errors.push(error);
+                }
+                None
+            }
+        }
+    }
+
+    fn profile_default(&self, name: &str) -> Option<String> {
+        self.environment
+            .defaults()
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.to_string())
+    }
+
+    /// Archivos de secretos que no se pudieron leer y otros problemas de carga
+    pub fn errors(&self) -> Vec<String> {
+        self.errors.borrow().clone()
+    }
+}
+
+/// Rutas indicadas por las variables `NOMBRE_FILE`, por `NOMBRE`
+fn secret_files(vars: impl IntoIterator<Item = (String, String)>) -> HashMap<String, String> {
+    vars.into_iter()
+        .filter(|(_, path)| !path.is_empty())
+        .filter_map(|(key, path)| {
+            key.strip_suffix(FILE_SUFFIX)
+                .filter(|name| !name.is_empty())
+                .map(|name| (name.to_string(), path))
+        })
+        .collect()
+}
+
+/// Lee un directorio de secretos: cada archivo aporta la variable de su nombre
+fn read_secrets_dir(dir: &Path, errors: &mut Vec<String>) -> HashMap<String, String> {
+    let mut secrets = HashMap::new();
+
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            errors.push(format!(
+                "SECRETS_DIR: no se pudo leer {} ({})",
+                dir.display(),
+                e
+            ));
+            return secrets;
+        }
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        // Kubernetes monta los secretos como enlaces a un subdirectorio oculto (`..data`)
+        let Some(name) = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .filter(|name| !name.starts_with('.'))
+        else {
+            continue;
+        };
+        if !path.is_file() {
+            continue;
+        }
+
+        match read_secret(&path) {
+            Ok(value) => {
+                secrets.insert(name.to_uppercase().replace(['-', '.'], "_"), value);
+            }
+            Err(e) => errors.push(format!(
+                "SECRETS_DIR: no se pudo leer {} ({})",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    secrets
+}
+
+/// Contenido de un secreto sin el salto de línea final
+fn read_secret(path: &Path) -> std::io::Result<String> {
+    let value = std::fs::read_to_string(path)?;
+    Ok(value.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Lee el archivo y lo aplana a nombres de variables de entorno
+fn read_file(path: &Path) -> anyhow::Result<HashMap<String, String>> {
+    let table: HashMap<String, Value> = ::config::Config::builder()
+        .add_source(File::from(path.to_path_buf()))
+        .build()
+        .and_then(|settings| settings.try_deserialize())
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "No se pudo leer el archivo de configuración {}: {}",
+                path.display(),
+                e
+            )
+        })?;
+
+    let mut values = HashMap::new();
+    for (key, value) in table {
+        let prefix = if GROUPING_SECTIONS.contains(&key.to_lowercase().as_str()) {
+            String::new()
+        } else {
+            key
+        };
+        flatten(&prefix, value, &mut values)?;
+    }
+
+    Ok(values)
+}
+
+fn flatten(name: &str, value: Value, values: &mut HashMap<String, String>) -> anyhow::Result<()> {
+    match value.kind {
+        ValueKind::Table(table) => {
+            for (key, value) in table {
+                let name = if name.is_empty() {
+                    key
+                } else {
+                    format!("{}_{}", name, key)
+                };
+                flatten(&name, value, values)?;
+            }
+        }
+        // Las listas se unen por comas, como en las variables de entorno
+        ValueKind::Array(items) => {
+            let items = items
+                .into_iter()
+                .map(|item| scalar(name, item))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            values.insert(name.to_uppercase(), items.join(","));
+        }
+        _ => {
+            values.insert(name.to_uppercase(), scalar(name, value)?);
+        }
+    }
+
+    Ok(())
+}
+
+fn scalar(name: &str, value: Value) -> anyhow::Result<String> {
+    Ok(match value.kind {
+        ValueKind::Nil => String::new(),
+        ValueKind::Boolean(v) => v.to_string(),
+        ValueKind::I64(v) => v.to_string(),
+        ValueKind::I128(v) => v.to_string(),
+        ValueKind::U64(v) => v.to_string(),
+        ValueKind::U128(v) => v.to_string(),
+        ValueKind::Float(v) => v.to_string(),
+        ValueKind::String(v) => v,
+        ValueKind::Table(_) | ValueKind::Array(_) => {
+            anyhow::bail!("Valor no soportado en {}: se esperaba un escalar", name)
+        }
+    })
+}
@@ -0,0 +1,97 @@
+use super::file::ConfigSource;
+use std::cell::RefCell;
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Lectura de variables que acumula los problemas en lugar de abortar en el
+/// primero, para informarlos todos juntos al arrancar
+///
+/// Ante un valor inválido se usa el valor por defecto solo para poder seguir
+/// validando; la configuración resultante se descarta si hubo problemas.
+pub(super) struct Loader<'a> {
+    source: &'a ConfigSource,
+    problems: RefCell<Vec<String>>,
+}
+
+impl<'a> Loader<'a> {
+    pub fn new(source: &'a ConfigSource) -> Self {
+        Self {
+            source,
+            problems: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Registra un problema de configuración
+    pub fn problem(&self, message: impl Into<String>) {
+        self.problems.borrow_mut().push(message.into());
+    }
+
+    /// Variable obligatoria y no vacía
+    pub fn required(&self, name: &str) -> String {
+        match self.source.var(name) {
+            Ok(value) if !value.trim().is_empty() => value,
+            _ => {
+                self.problem(format!("{}: variable obligatoria no definida", name));
+                String::new()
+            }
+        }
+    }
+
+    /// Variable con valor por defecto
+    pub fn parse<T>(&self, name: &str, default: &str) -> T
+    where
+        T: FromStr + Default,
+        T::Err: Display,
+    {
+        let value = self
+            .source
+            .var(name)
+            .unwrap_or_else(|_| default.to_string());
+        self.parse_value(name, &value)
+            .or_else(|| default.parse().ok())
+            .unwrap_or_default()
+    }
+
+    /// Variable opcional (ausente o vacía = None)
+    pub fn optional<T>(&self, name: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.source
+            .var(name)
+            .ok()
+            .filter(|value| !value.trim().is_empty())
+            .and_then(|value| self.parse_value(name, &value))
+    }
+
+    /// Resultado de un parser propio; sus errores ya nombran la variable
+    pub fn check<T: Default>(&self, result: anyhow::Result<T>) -> T {
+        result.unwrap_or_else(|e| {
+            self.problem(e.to_string());
+            T::default()
+        })
+    }
+
+    fn parse_value<T>(&self, name: &str, value: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        match value.trim().parse() {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                self.problem(format!("{}: valor inválido \"{}\" ({})", name, value, e));
+                None
+            }
+        }
+    }
+
+    /// Problemas encontrados durante la carga
+    /// Incluye los secretos `NOMBRE_FILE` ilegibles de las variables leídas
+    pub fn into_problems(self) -> Vec<String> {
+        let mut problems = self.source.errors();
+        problems.extend(self.problems.into_inner());
+        problems
+    }
+}
@@ -0,0 +1,54 @@
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Perfil de entorno seleccionado con `GATEWAY_ENV`
+///
+/// Cada perfil aporta valores por defecto para las variables que no se definan
+/// en el entorno, los secretos ni el archivo de configuración.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Environment {
+    /// Desarrollo local y CI: base en memoria, CORS abierto y cloud simulado
+    Dev,
+    /// Preproducción: como producción con logs detallados
+    Staging,
+    #[default]
+    Prod,
+}
+
+impl Environment {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Environment::Dev => "dev",
+            Environment::Staging => "staging",
+            Environment::Prod => "prod",
+        }
+    }
+
+    /// Valores por defecto del perfil, por nombre de variable
+    pub(super) fn defaults(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Environment::Dev => &[
+                ("LOG_LEVEL", "debug"),
+                ("CORS_ALLOWED_ORIGINS", "*"),
+                ("DATABASE_URL", "sqlite::memory:"),
+                ("CLOUD_SINK", "log"),
+            ],
+            Environment::Staging => &[("LOG_LEVEL", "debug")],
+            Environment::Prod => &[("LOG_LEVEL", "info")],
+        }
+    }
+}
+
+impl FromStr for Environment {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "dev" | "development" => Ok(Environment::Dev),
+            "staging" => Ok(Environment::Staging),
+            "prod" | "production" => Ok(Environment::Prod),
+            other => anyhow::bail!("Perfil desconocido: {} (usar dev, staging o prod)", other),
+        }
+    }
+}
@@ -1,24 +1,166 @@
+use crate::config::Config;
 use crate::models::ProcessedSensorData;
-use sqlx::Row;
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use crate::telemetry::Telemetry;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::{
+    Sqlite, SqliteAutoVacuum, SqliteConnectOptions, SqliteConnection, SqlitePool, SqlitePoolOptions,
+};
+use sqlx::{QueryBuilder, Row};
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
 use uuid::Uuid;
 
+mod alerts;
+mod audit;
+mod backup;
+mod cache;
+mod calibrations;
+mod cloud_notices;
+mod credentials;
+mod decommission;
+mod dedup;
+mod devices;
+mod firmware;
+mod maintenance;
+mod metrics;
+mod overrides;
+mod process_runs;
+mod profiles;
+mod quarantine;
+mod replay;
+mod rules;
+mod settings;
+mod silences;
+mod sync_queue;
+mod twins;
+mod write_buffer;
+
+use cache::LatestCache;
+use dedup::MessageDedup;
+use replay::ReplayGuard;
+use write_buffer::WriteBuffer;
+
+pub use alerts::AlertFilter;
+pub use audit::AuditFilter;
+pub use metrics::{GroupBy, MetricFilter, QualityFilter};
+pub use process_runs::ProcessRun;
+pub use replay::ReplayRejection;
+
+/// Límite conservador de parámetros por sentencia
+/// (SQLITE_MAX_VARIABLE_NUMBER en versiones de SQLite anteriores a 3.32)
+const SQLITE_MAX_BIND_PARAMS: usize = 999;
+
+/// Columnas enlazadas por cada lectura en `insert_readings_chunk`
+const READING_COLUMNS: usize = 27;
+
 /// Capa de acceso a datos usando SQLite para almacenamiento local en edge
 /// Versión 2: Soporta el nuevo modelo con header y metrics flexibles
 #[derive(Clone)]
 pub struct Database {
     pool: SqlitePool,
+    cache: Arc<LatestCache>,
+    dedup: Arc<MessageDedup>,
+    replay: Arc<ReplayGuard>,
+    telemetry: Arc<Telemetry>,
+    /// Los dispositivos desconocidos quedan pendientes de aprobación
+    approval_required: bool,
+    /// Dispositivos dados de baja, cuyas lecturas se rechazan
+    decommissioned: Arc<RwLock<HashSet<String>>>,
+    /// Lecturas individuales pendientes de escribirse en lote
+    writes: Option<Arc<WriteBuffer>>,
+    /// Clave SQLCipher con la que se cifran también los respaldos
+    encryption_key: Option<String>,
 }
 
 impl Database {
     /// Crea una nueva conexión a la base de datos SQLite
-    pub async fn new(database_url: &str) -> anyhow::Result<Self> {
-        let pool = SqlitePoolOptions::new()
-            .max_connections(5)
-            .connect(database_url)
+    /// Si hay clave configurada, la base de datos se abre cifrada con SQLCipher
+    pub async fn new(config: &Config) -> anyhow::Result<Self> {
+        let encryption_key = config.database_key.as_deref();
+
+        // Las bases nuevas se crean en modo incremental para poder liberar espacio en caliente
+        let mut options = SqliteConnectOptions::from_str(&config.database_url)?
+            .auto_vacuum(SqliteAutoVacuum::Incremental);
+
+        if let Some(key) = encryption_key {
+            // Sin SQLCipher el PRAGMA key se ignora en silencio y los datos quedarían en claro
+            if !cfg!(feature = "sqlcipher") {
+                anyhow::bail!(
+                    "Se configuró una clave de cifrado pero el binario no fue compilado con la feature `sqlcipher`"
+                );
+            }
+
+            // SQLx ejecuta el PRAGMA key antes que cualquier otra sentencia
+            options = options.pragma("key", format!("'{}'", key.replace('\'', "''")));
+        }
+
+        let mut pool_options = SqlitePoolOptions::new().max_connections(5);
+        if config.database_url.contains(":memory:") || config.database_url.contains("mode=memory") {
+            // Una base en memoria desaparece al cerrarse su última conexión
+            pool_options = pool_options
+                .min_connections(1)
+                .idle_timeout(None)
+                .max_lifetime(None);
+        }
+        let pool = pool_options.connect_with(options).await?;
+
+        let db = Self {
+            pool,
+            cache: Arc::new(LatestCache::new(
+                config.latest_cache_devices,
+                config.latest_cache_depth,
+            )),
+            dedup: Arc::new(MessageDedup::new(config.dedup_window_secs)),
+            replay: Arc::new(ReplayGuard::new(
+                config.replay_window_secs,
+                config.replay_required,
+            )),
+            telemetry: Arc::new(Telemetry::default()),
+            approval_required: config.device_approval_required,
+            decommissioned: Arc::new(RwLock::new(HashSet::new())),
+            writes: None,
+            encryption_key: encryption_key.map(str::to_string),
+        };
+        if encryption_key.is_some() {
+            db.verify_encryption().await?;
+        }
+
+        Ok(db)
+    }
+
+    /// Registra la duración de las inserciones en las métricas compartidas
+    pub fn with_telemetry(mut self, telemetry: Arc<Telemetry>) -> Self {
+        self.telemetry = telemetry;
+        self
+    }
+
+    /// Verifica que SQLCipher esté activo y que la clave abra la base de datos
+    async fn verify_encryption(&self) -> anyhow::Result<()> {
+        let cipher_version: Option<String> = sqlx::query_scalar("PRAGMA cipher_version")
+            .fetch_optional(&self.pool)
             .await?;
 
-        Ok(Self { pool })
+        let Some(version) = cipher_version else {
+            anyhow::bail!(
+                "SQLite no reporta soporte SQLCipher; la base de datos no estaría cifrada"
+            );
+        };
+
+        // Con una clave incorrecta la primera lectura del esquema falla
+        sqlx::query("SELECT count(*) FROM sqlite_master")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| {
+                anyhow::anyhow!(
+                    "Clave de cifrado inválida o base de datos no cifrada: {}",
+                    e
+                )
+            })?;
+
+        tracing::info!(cipher_version = %version, "Base de datos cifrada con SQLCipher");
+        Ok(())
     }
 
     /// Ejecuta las migraciones necesarias
@@ -30,13 +172,22 @@ impl Database {
                 id TEXT PRIMARY KEY,
                 
                 -- Header information
+                user_uuid TEXT,
                 device_id TEXT NOT NULL,
+                device_type TEXT,
+                message_id TEXT,
                 location TEXT NOT NULL,
                 topic TEXT NOT NULL,
                 should_requeue INTEGER NOT NULL,
                 
                 -- Timestamps
                 gateway_timestamp TEXT NOT NULL,
+                device_timestamp TEXT,
+                clock_skew_ms INTEGER,
+                measured_at TEXT,
+                profile_json TEXT,
+                sync_priority INTEGER NOT NULL DEFAULT 0,
+                forward_to_cloud INTEGER NOT NULL DEFAULT 1,
                 
                 -- Métricas (almacenadas como JSON para flexibilidad)
                 metrics_json TEXT NOT NULL,
@@ -48,6 +199,7 @@ impl Database {
                 quality_score INTEGER NOT NULL,
                 quality_issues TEXT,
                 quality_corrected INTEGER NOT NULL,
+                is_anomaly INTEGER NOT NULL DEFAULT 0,
                 
                 -- Metadatos procesados
                 metrics_count INTEGER NOT NULL,
@@ -57,6 +209,7 @@ impl Database {
                 synced INTEGER NOT NULL DEFAULT 0,
                 sync_attempts INTEGER NOT NULL DEFAULT 0,
                 last_sync_attempt TEXT,
+                last_sync_error TEXT,
                 
                 created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
             );
@@ -65,11 +218,76 @@ impl Database {
         .execute(&self.pool)
         .await?;
 
+        // Columnas agregadas después de la versión inicial del esquema
+        self.add_column_if_missing("sensor_readings", "user_uuid", "TEXT")
+            .await?;
+        self.add_column_if_missing("sensor_readings", "last_sync_error", "TEXT")
+            .await?;
+        self.add_column_if_missing("sensor_readings", "message_id", "TEXT")
+            .await?;
+        self.add_column_if_missing("sensor_readings", "device_timestamp", "TEXT")
+            .await?;
+        self.add_column_if_missing("sensor_readings", "clock_skew_ms", "INTEGER")
+            .await?;
+        // Hasta esta columna la hora corregida del dispositivo se guardaba en gateway_timestamp
+        if self
+            .add_column_if_missing("sensor_readings", "measured_at", "TEXT")
+            .await?
+        {
+            sqlx::query("UPDATE sensor_readings SET measured_at = gateway_timestamp")
+                .execute(&self.pool)
+                .await?;
+        }
+        self.add_column_if_missing("sensor_readings", "device_type", "TEXT")
+            .await?;
+        self.add_column_if_missing("sensor_readings", "profile_json", "TEXT")
+            .await?;
+        // Metadatos del nodo informados en el header
+        self.add_column_if_missing("sensor_readings", "firmware_version", "TEXT")
+            .await?;
+        self.add_column_if_missing("sensor_readings", "hardware_model", "TEXT")
+            .await?;
+        self.add_column_if_missing("sensor_readings", "rssi", "INTEGER")
+            .await?;
+        self.add_column_if_missing(
+            "sensor_readings",
+            "sync_priority",
+            "INTEGER NOT NULL DEFAULT 0",
+        )
+        .await?;
+        self.add_column_if_missing(
+            "sensor_readings",
+            "forward_to_cloud",
+            "INTEGER NOT NULL DEFAULT 1",
+        )
+        .await?;
+
+        // Columna dedicada para consultar anomalías sin recorrer computed_json
+        if self
+            .add_column_if_missing(
+                "sensor_readings",
+                "is_anomaly",
+                "INTEGER NOT NULL DEFAULT 0",
+            )
+            .await?
+        {
+            sqlx::query(
+                "UPDATE sensor_readings SET is_anomaly = 1 WHERE json_extract(computed_json, '$.is_anomaly') = 1",
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+
         // Índices para mejorar performance
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_device_id ON sensor_readings(device_id);")
             .execute(&self.pool)
             .await?;
 
+        // Ingesta idempotente: un message_id se almacena una sola vez por dispositivo
+        sqlx::query("CREATE UNIQUE INDEX IF NOT EXISTS idx_device_message ON sensor_readings(device_id, message_id) WHERE message_id IS NOT NULL;")
+            .execute(&self.pool)
+            .await?;
+
         sqlx::query("CREATE INDEX IF NOT EXISTS idx_location ON sensor_readings(location);")
             .execute(&self.pool)
             .await?;
@@ -86,94 +304,647 @@ impl Database {
             .execute(&self.pool)
             .await?;
 
-        tracing::info!("Migraciones de base de datos ejecutadas (v2)");
-        Ok(())
-    }
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_measured_at ON sensor_readings(measured_at);")
+            .execute(&self.pool)
+            .await?;
 
-    /// Inserta una lectura procesada
-    pub async fn insert_reading(&self, data: &ProcessedSensorData) -> anyhow::Result<()> {
-        let metrics_json = serde_json::to_string(&data.metrics)?;
-        let computed_json = serde_json::to_string(&data.computed)?;
-        let quality_issues = serde_json::to_string(&data.quality.issues)?;
-        let measurement_types = serde_json::to_string(&data.metadata.measurement_types)?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_device_measured_at ON sensor_readings(device_id, measured_at);",
+        )
+        .execute(&self.pool)
+        .await?;
 
+        // Las anomalías se ordenan por el momento de la medición
+        sqlx::query("DROP INDEX IF EXISTS idx_anomalies;")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_anomalies_measured_at ON sensor_readings(measured_at) WHERE is_anomaly = 1;")
+            .execute(&self.pool)
+            .await?;
+
+        // Registro de dispositivos (actualizado en cada ingesta)
         sqlx::query(
             r#"
-            INSERT INTO sensor_readings (
-                id, device_id, location, topic, should_requeue,
-                gateway_timestamp, metrics_json, computed_json,
-                quality_score, quality_issues, quality_corrected,
-                metrics_count, measurement_types
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            CREATE TABLE IF NOT EXISTS devices (
+                device_id TEXT PRIMARY KEY,
+                location TEXT NOT NULL,
+                first_seen TEXT,
+                last_seen TEXT,
+                message_count INTEGER NOT NULL DEFAULT 0,
+                last_quality INTEGER,
+                metadata TEXT NOT NULL DEFAULT '{}',
+                provisioned_at TEXT,
+                expected_location TEXT,
+                device_type TEXT,
+                credential_hash TEXT,
+                offline_since TEXT
+            );
             "#,
         )
-        .bind(data.id.to_string())
-        .bind(&data.header.device_id)
-        .bind(&data.header.location)
-        .bind(&data.header.topic)
-        .bind(data.header.should_requeue as i32)
-        .bind(data.gateway_timestamp.to_rfc3339())
-        .bind(metrics_json)
-        .bind(computed_json)
-        .bind(data.quality.score as i32)
-        .bind(quality_issues)
-        .bind(data.quality.corrected as i32)
-        .bind(data.metadata.metrics_count as i32)
-        .bind(measurement_types)
         .execute(&self.pool)
         .await?;
 
-        Ok(())
-    }
-
-    /// Inserta un batch de lecturas
-    pub async fn insert_batch(&self, data: &[ProcessedSensorData]) -> anyhow::Result<()> {
-        let mut tx = self.pool.begin().await?;
-
-        for reading in data {
-            let metrics_json = serde_json::to_string(&reading.metrics)?;
-            let computed_json = serde_json::to_string(&reading.computed)?;
-            let quality_issues = serde_json::to_string(&reading.quality.issues)?;
-            let measurement_types = serde_json::to_string(&reading.metadata.measurement_types)?;
+        // Los dispositivos provisionados aún no tienen lecturas: first_seen y
+        // last_seen dejan de ser obligatorios en las bases anteriores
+        let seen_required: bool = sqlx::query_scalar(
+            r#"SELECT COUNT(*) > 0 FROM pragma_table_info('devices') WHERE name = 'first_seen' AND "notnull" = 1"#,
+        )
+        .fetch_one(&self.pool)
+        .await?;
 
+        if seen_required {
+            let mut tx = self.pool.begin().await?;
             sqlx::query(
                 r#"
-                INSERT INTO sensor_readings (
-                    id, device_id, location, topic, should_requeue,
-                    gateway_timestamp, metrics_json, computed_json,
-                    quality_score, quality_issues, quality_corrected,
-                    metrics_count, measurement_types
-                ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                CREATE TABLE devices_new (
+                    device_id TEXT PRIMARY KEY,
+                    location TEXT NOT NULL,
+                    first_seen TEXT,
+                    last_seen TEXT,
+                    message_count INTEGER NOT NULL DEFAULT 0,
+                    last_quality INTEGER,
+                    metadata TEXT NOT NULL DEFAULT '{}'
+                );
+                INSERT INTO devices_new
+                SELECT device_id, location, first_seen, last_seen,
+                       message_count, last_quality, metadata
+                FROM devices;
+                DROP TABLE devices;
+                ALTER TABLE devices_new RENAME TO devices;
                 "#,
             )
-            .bind(reading.id.to_string())
-            .bind(&reading.header.device_id)
-            .bind(&reading.header.location)
-            .bind(&reading.header.topic)
-            .bind(reading.header.should_requeue as i32)
-            .bind(reading.gateway_timestamp.to_rfc3339())
-            .bind(&metrics_json)
-            .bind(&computed_json)
-            .bind(reading.quality.score as i32)
-            .bind(&quality_issues)
-            .bind(reading.quality.corrected as i32)
-            .bind(reading.metadata.metrics_count as i32)
-            .bind(&measurement_types)
             .execute(&mut *tx)
             .await?;
+            tx.commit().await?;
+
+            tracing::info!("Tabla devices migrada para admitir dispositivos provisionados");
+        }
+
+        self.add_column_if_missing("devices", "provisioned_at", "TEXT")
+            .await?;
+        self.add_column_if_missing("devices", "expected_location", "TEXT")
+            .await?;
+        self.add_column_if_missing("devices", "device_type", "TEXT")
+            .await?;
+        self.add_column_if_missing("devices", "credential_hash", "TEXT")
+            .await?;
+        self.add_column_if_missing("devices", "offline_since", "TEXT")
+            .await?;
+        // Etiquetas del dispositivo (arreglo JSON) para operar por grupos
+        self.add_column_if_missing("devices", "tags", "TEXT NOT NULL DEFAULT '[]'")
+            .await?;
+        // Los dispositivos desconocidos quedan en `pending` hasta que un operador los apruebe
+        self.add_column_if_missing("devices", "approval", "TEXT NOT NULL DEFAULT 'approved'")
+            .await?;
+        // Baja del dispositivo (approval = 'decommissioned')
+        self.add_column_if_missing("devices", "decommissioned_at", "TEXT")
+            .await?;
+        self.add_column_if_missing("devices", "decommission_reason", "TEXT")
+            .await?;
+        // Último firmware, modelo y señal informados por el nodo (inventario)
+        self.add_column_if_missing("devices", "firmware_version", "TEXT")
+            .await?;
+        self.add_column_if_missing("devices", "hardware_model", "TEXT")
+            .await?;
+        self.add_column_if_missing("devices", "rssi", "INTEGER")
+            .await?;
+        // Secuencia más alta recibida (protección contra reenvíos)
+        self.add_column_if_missing("devices", "replay_sequence", "INTEGER")
+            .await?;
+        // Token anterior tras una rotación, válido hasta su vencimiento
+        self.add_column_if_missing("devices", "previous_credential_hash", "TEXT")
+            .await?;
+        self.add_column_if_missing("devices", "previous_credential_expires_at", "TEXT")
+            .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_devices_credential ON devices(credential_hash);",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Lecturas de dispositivos pendientes de aprobación, sin procesar
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS quarantined_readings (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                device_id TEXT NOT NULL,
+                received_at TEXT NOT NULL,
+                payload TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_quarantined_device ON quarantined_readings(device_id);
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_devices_last_seen ON devices(last_seen);")
+            .execute(&self.pool)
+            .await?;
+
+        // Valores de métricas normalizados (consultables en SQL)
+        let mut metric_values_exists: bool = sqlx::query_scalar(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'metric_values'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        // La tabla se deriva de sensor_readings: sin la columna measured_at se
+        // recrea y se vuelve a poblar
+        let has_measured_at: bool = sqlx::query_scalar(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('metric_values') WHERE name = 'measured_at'",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        if metric_values_exists && !has_measured_at {
+            sqlx::query("DROP TABLE metric_values")
+                .execute(&self.pool)
+                .await?;
+            metric_values_exists = false;
         }
 
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS metric_values (
+                reading_id TEXT NOT NULL REFERENCES sensor_readings(id) ON DELETE CASCADE,
+                device_id TEXT NOT NULL,
+                location TEXT NOT NULL,
+                measurement TEXT NOT NULL COLLATE NOCASE,
+                value REAL,
+                measured_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_metric_values_measurement_ts ON metric_values(measurement, measured_at);")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_metric_values_device ON metric_values(device_id, measurement, measured_at);")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS idx_metric_values_reading ON metric_values(reading_id);",
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Poblar desde metrics_json las lecturas existentes antes de esta tabla
+        if !metric_values_exists {
+            let result = sqlx::query(
+                r#"
+                INSERT INTO metric_values (
+                    reading_id, device_id, location,
+                    measurement, value, measured_at
+                )
+                SELECT r.id, r.device_id, r.location,
+                       json_extract(m.value, '$.measurement'),
+                       json_extract(m.value, '$.value'),
+                       r.measured_at
+                FROM sensor_readings r, json_each(r.metrics_json) m
+                "#,
+            )
+            .execute(&self.pool)
+            .await?;
+
+            tracing::info!(
+                rows = result.rows_affected(),
+                "Tabla metric_values poblada desde lecturas existentes"
+            );
+        }
+
+        // Historial de alertas disparadas por las reglas
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS alerts (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                rule_id TEXT NOT NULL,
+                device_id TEXT NOT NULL,
+                metric TEXT NOT NULL,
+                value REAL NOT NULL,
+                severity TEXT NOT NULL,
+                fired_at TEXT NOT NULL,
+                resolved_at TEXT,
+                acked_by TEXT
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_alerts_fired_at ON alerts(fired_at);")
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_alerts_device ON alerts(device_id, fired_at);")
+            .execute(&self.pool)
+            .await?;
+        self.add_column_if_missing("alerts", "acked_at", "TEXT")
+            .await?;
+        self.add_column_if_missing("alerts", "ack_note", "TEXT")
+            .await?;
+
+        // Silencios de notificaciones (mantenimiento, condiciones conocidas)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS alert_silences (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                device_id TEXT,
+                rule_id TEXT,
+                reason TEXT,
+                created_by TEXT NOT NULL,
+                created_at TEXT NOT NULL,
+                expires_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Rangos de validez por medición (editables en tiempo de ejecución)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS measurement_rules (
+                measurement TEXT PRIMARY KEY,
+                min_value REAL NOT NULL,
+                max_value REAL NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        self.seed_measurement_rules().await?;
+
+        // Reglas de acciones evaluadas en cada lectura
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS rules (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                measurement TEXT NOT NULL,
+                device_id TEXT,
+                location TEXT,
+                operator TEXT NOT NULL,
+                threshold REAL NOT NULL,
+                for_secs INTEGER NOT NULL DEFAULT 0,
+                actions_json TEXT NOT NULL,
+                enabled INTEGER NOT NULL DEFAULT 1,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+        self.add_column_if_missing("rules", "tag", "TEXT").await?;
+        self.add_column_if_missing("rules", "consecutive", "INTEGER NOT NULL DEFAULT 1")
+            .await?;
+        self.add_column_if_missing("rules", "cooldown_secs", "INTEGER NOT NULL DEFAULT 0")
+            .await?;
+        self.add_column_if_missing("rules", "clear_threshold", "REAL")
+            .await?;
+
+        // Calibraciones por dispositivo y medición (valor * gain + offset)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS calibrations (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                device_id TEXT NOT NULL,
+                measurement TEXT NOT NULL,
+                offset REAL NOT NULL DEFAULT 0,
+                gain REAL NOT NULL DEFAULT 1,
+                valid_from TEXT NOT NULL,
+                created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_calibrations_device ON calibrations(device_id, measurement);")
+            .execute(&self.pool)
+            .await?;
+
+        // Perfiles de métricas esperadas por tipo de dispositivo
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS device_profiles (
+                device_type TEXT PRIMARY KEY,
+                measurements_json TEXT NOT NULL,
+                allow_unknown INTEGER NOT NULL DEFAULT 0,
+                updated_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        self.add_column_if_missing("device_profiles", "offline_after_secs", "INTEGER")
+            .await?;
+
+        // Ajustes de procesamiento y sincronización por dispositivo o ubicación
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS device_overrides (
+                scope TEXT NOT NULL,
+                target TEXT NOT NULL,
+                profile TEXT,
+                calibrations_json TEXT NOT NULL DEFAULT '{}',
+                anomaly_zscore_threshold REAL,
+                baseline_zscore_threshold REAL,
+                sync_priority INTEGER,
+                forward_to_cloud INTEGER,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (scope, target)
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        self.add_column_if_missing("device_overrides", "quota_messages_per_day", "INTEGER")
+            .await?;
+        self.add_column_if_missing("device_overrides", "quota_max_rows", "INTEGER")
+            .await?;
+
+        // Ajustes persistidos (overrides de configuración en tiempo de ejecución)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS settings (
+                key TEXT PRIMARY KEY,
+                value TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Estado deseado y reportado de cada dispositivo (device twin)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS device_twins (
+                device_id TEXT PRIMARY KEY,
+                desired_json TEXT NOT NULL DEFAULT '{}',
+                desired_version INTEGER NOT NULL DEFAULT 0,
+                desired_updated_at TEXT,
+                reported_json TEXT NOT NULL DEFAULT '{}',
+                reported_updated_at TEXT
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Imágenes de firmware para OTA; el binario se guarda en FIRMWARE_DIR
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS firmware (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                version TEXT NOT NULL,
+                device_type TEXT,
+                file_name TEXT NOT NULL,
+                size_bytes INTEGER NOT NULL,
+                sha256 TEXT NOT NULL,
+                notes TEXT,
+                uploaded_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Estado de la actualización de cada dispositivo (pending, downloading, applied, failed)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS firmware_rollouts (
+                firmware_id INTEGER NOT NULL,
+                device_id TEXT NOT NULL,
+                state TEXT NOT NULL,
+                error TEXT,
+                created_at TEXT NOT NULL,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (firmware_id, device_id)
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Arranques del proceso; stopped_at solo se completa en un apagado limpio
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS process_runs (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                started_at TEXT NOT NULL,
+                stopped_at TEXT,
+                version TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Avisos pendientes de publicar en el cloud (se eliminan al enviarse)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS cloud_notices (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind TEXT NOT NULL,
+                device_id TEXT,
+                payload TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        // Operaciones de la API que modifican el gateway, con el cliente que las hizo
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS audit_log (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                at TEXT NOT NULL,
+                principal TEXT NOT NULL,
+                role TEXT NOT NULL,
+                method TEXT NOT NULL,
+                path TEXT NOT NULL,
+                status INTEGER NOT NULL
+            );
+            "#,
+        )
+        .execute(&self.pool)
+        .await?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_audit_log_at ON audit_log(at);")
+            .execute(&self.pool)
+            .await?;
+
+        self.load_decommissioned().await?;
+        self.load_replay_sequences().await?;
+
+        tracing::info!("Migraciones de base de datos ejecutadas (v2)");
+        Ok(())
+    }
+
+    /// Agrega una columna a una tabla existente si aún no existe
+    async fn add_column_if_missing(
+        &self,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> anyhow::Result<bool> {
+        let exists: bool =
+            sqlx::query_scalar("SELECT COUNT(*) > 0 FROM pragma_table_info(?) WHERE name = ?")
+                .bind(table)
+                .bind(column)
+                .fetch_one(&self.pool)
+                .await?;
+
+        if !exists {
+            sqlx::query(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                table, column, definition
+            ))
+            .execute(&self.pool)
+            .await?;
+
+            tracing::info!(table, column, "Columna agregada al esquema");
+        }
+
+        Ok(!exists)
+    }
+
+    /// Inserta una lectura procesada
+    /// Con el búfer de escritura activo la lectura se almacena en el próximo lote
+    pub async fn insert_reading(&self, data: &ProcessedSensorData) -> anyhow::Result<()> {
+        if self.buffer_reading(data) {
+            return Ok(());
+        }
+        self.insert_batch(std::slice::from_ref(data)).await
+    }
+
+    /// Inserta un batch de lecturas
+    /// Usa INSERTs multi-fila en bloques que respetan el límite de parámetros de SQLite
+    pub async fn insert_batch(&self, data: &[ProcessedSensorData]) -> anyhow::Result<()> {
+        if data.is_empty() {
+            return Ok(());
+        }
+
+        let started = Instant::now();
+        if let Err(e) = self.write_batch(data).await {
+            self.telemetry.db_error("insert");
+            return Err(e);
+        }
+        self.telemetry.observe_db_insert(started.elapsed());
+
+        self.cache.record(data);
+        self.dedup.record(data);
+        Ok(())
+    }
+
+    async fn write_batch(&self, data: &[ProcessedSensorData]) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for chunk in data.chunks(SQLITE_MAX_BIND_PARAMS / READING_COLUMNS) {
+            Self::insert_readings_chunk(&mut tx, chunk).await?;
+        }
+        Self::insert_metric_values(&mut tx, data).await?;
+        Self::upsert_devices(&mut tx, data).await?;
+
         tx.commit().await?;
         Ok(())
     }
 
+    /// Inserta un bloque de lecturas con una única sentencia multi-fila
+    async fn insert_readings_chunk(
+        conn: &mut SqliteConnection,
+        chunk: &[ProcessedSensorData],
+    ) -> anyhow::Result<()> {
+        // Serializar antes de construir la consulta: push_values no admite errores
+        let mut serialized = Vec::with_capacity(chunk.len());
+        for data in chunk {
+            serialized.push((
+                serde_json::to_string(&data.metrics)?,
+                serde_json::to_string(&data.computed)?,
+                serde_json::to_string(&data.quality.issues)?,
+                serde_json::to_string(&data.metadata.measurement_types)?,
+                data.metadata
+                    .profile
+                    .as_ref()
+                    .map(serde_json::to_string)
+                    .transpose()?,
+            ));
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new(
+            r#"
+            INSERT INTO sensor_readings (
+                id, user_uuid, device_id, device_type, message_id, location, topic, should_requeue,
+                gateway_timestamp, device_timestamp, clock_skew_ms, measured_at, profile_json,
+                metrics_json, computed_json,
+                quality_score, quality_issues, quality_corrected, is_anomaly,
+                metrics_count, measurement_types,
+                sync_priority, forward_to_cloud, synced,
+                firmware_version, hardware_model, rssi
+            ) "#,
+        );
+
+        query.push_values(
+            chunk.iter().zip(serialized),
+            |mut row,
+             (
+                data,
+                (metrics_json, computed_json, quality_issues, measurement_types, profile_json),
+            )| {
+                row.push_bind(data.id.to_string())
+                    .push_bind(data.header.user_uuid.clone())
+                    .push_bind(data.header.device_id.clone())
+                    .push_bind(data.header.device_type.clone())
+                    .push_bind(data.header.message_id.clone())
+                    .push_bind(data.header.location.clone())
+                    .push_bind(data.header.topic.clone())
+                    .push_bind(data.header.should_requeue as i32)
+                    .push_bind(data.gateway_timestamp.to_rfc3339())
+                    .push_bind(data.metadata.device_timestamp.map(|at| at.to_rfc3339()))
+                    .push_bind(data.metadata.clock_skew_ms)
+                    .push_bind(data.measured_at.to_rfc3339())
+                    .push_bind(profile_json)
+                    .push_bind(metrics_json)
+                    .push_bind(computed_json)
+                    .push_bind(data.quality.score as i32)
+                    .push_bind(quality_issues)
+                    .push_bind(data.quality.corrected as i32)
+                    .push_bind(data.computed.is_anomaly as i32)
+                    .push_bind(data.metadata.metrics_count as i32)
+                    .push_bind(measurement_types)
+                    .push_bind(data.metadata.sync_priority)
+                    .push_bind(data.metadata.forward_to_cloud as i32)
+                    // Las lecturas solo locales no entran en la cola de sincronización
+                    .push_bind(!data.metadata.forward_to_cloud as i32)
+                    .push_bind(data.header.firmware_version.clone())
+                    .push_bind(data.header.hardware_model.clone())
+                    .push_bind(data.header.rssi);
+            },
+        );
+
+        query.build().execute(&mut *conn).await?;
+        Ok(())
+    }
+
     /// Obtiene lecturas pendientes de sincronizar
     pub async fn get_pending_sync(&self, limit: usize) -> anyhow::Result<Vec<ProcessedSensorData>> {
         let rows = sqlx::query(
             r#"
             SELECT * FROM sensor_readings
             WHERE synced = 0
-            ORDER BY gateway_timestamp ASC
+            ORDER BY sync_priority DESC, gateway_timestamp ASC
             LIMIT ?
             "#,
         )
@@ -190,12 +961,44 @@ impl Database {
     }
 
     /// Cuenta lecturas pendientes de sincronizar
+    /// Incluye las lecturas del búfer de escritura que irán al cloud
     pub async fn count_pending_sync(&self) -> anyhow::Result<i64> {
         let row = sqlx::query("SELECT COUNT(*) as count FROM sensor_readings WHERE synced = 0")
             .fetch_one(&self.pool)
             .await?;
 
-        Ok(row.get("count"))
+        Ok(row.get::<i64, _>("count") + self.buffered_for_sync())
+    }
+
+    /// Lecturas almacenadas por dispositivo: total y desde `since`
+    pub async fn count_readings_by_device(
+        &self,
+        since: DateTime<Utc>,
+    ) -> anyhow::Result<HashMap<String, (u64, u64)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT device_id, COUNT(*) AS total,
+                   SUM(gateway_timestamp >= ?) AS recent
+            FROM sensor_readings
+            GROUP BY device_id
+            "#,
+        )
+        .bind(since.to_rfc3339())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.get("device_id"),
+                    (
+                        row.get::<i64, _>("total") as u64,
+                        row.get::<i64, _>("recent") as u64,
+                    ),
+                )
+            })
+            .collect())
     }
 
     /// Marca lecturas como sincronizadas
@@ -206,7 +1009,8 @@ impl Database {
             sqlx::query(
                 r#"
                 UPDATE sensor_readings
-                SET synced = 1, last_sync_attempt = CURRENT_TIMESTAMP
+                SET synced = 1, sync_attempts = sync_attempts + 1,
+                    last_sync_attempt = CURRENT_TIMESTAMP, last_sync_error = NULL
                 WHERE id = ?
                 "#,
             )
@@ -219,17 +1023,56 @@ impl Database {
         Ok(())
     }
 
+    /// Registra el intento fallido de sincronizar lecturas y su causa
+    pub async fn mark_sync_failed(&self, failures: &[(Uuid, String)]) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for (id, error) in failures {
+            sqlx::query(
+                r#"
+                UPDATE sensor_readings
+                SET sync_attempts = sync_attempts + 1,
+                    last_sync_attempt = CURRENT_TIMESTAMP, last_sync_error = ?
+                WHERE id = ?
+                "#,
+            )
+            .bind(error)
+            .bind(id.to_string())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Cuenta lecturas pendientes cuyo último intento de sincronización falló
+    pub async fn count_sync_failures(&self) -> anyhow::Result<i64> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) as count FROM sensor_readings WHERE synced = 0 AND last_sync_error IS NOT NULL",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(row.get("count"))
+    }
+
     /// Obtiene lecturas recientes para un dispositivo
+    /// Se sirven desde la caché en memoria cuando es posible
     pub async fn get_recent_readings(
         &self,
         device_id: &str,
         limit: usize,
     ) -> anyhow::Result<Vec<ProcessedSensorData>> {
+        if let Some(cached) = self.cache.recent(device_id, limit) {
+            return Ok(cached);
+        }
+
         let rows = sqlx::query(
             r#"
             SELECT * FROM sensor_readings
             WHERE device_id = ?
-            ORDER BY gateway_timestamp DESC
+            ORDER BY measured_at DESC, rowid DESC
             LIMIT ?
             "#,
         )
@@ -243,6 +1086,87 @@ impl Database {
             results.push(self.row_to_processed_data(row)?);
         }
 
+        self.cache.fill(device_id, &results, limit);
+        Ok(results)
+    }
+
+    /// Obtiene las lecturas anómalas dentro del filtro, más recientes primero
+    pub async fn get_anomalies(
+        &self,
+        filter: &MetricFilter,
+        limit: usize,
+    ) -> anyhow::Result<Vec<ProcessedSensorData>> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM sensor_readings");
+        filter.push_reading_conditions(&mut query);
+
+        query
+            .push(" AND is_anomaly = 1 ORDER BY measured_at DESC LIMIT ")
+            .push_bind(limit as i64);
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+
+        let mut results = Vec::with_capacity(rows.len());
+        for row in rows {
+            results.push(self.row_to_processed_data(row)?);
+        }
+
+        Ok(results)
+    }
+
+    /// Lecturas más recientes que cumplen el filtro (incluida la calidad)
+    pub async fn get_recent_matching(
+        &self,
+        filter: &MetricFilter,
+        limit: usize,
+    ) -> anyhow::Result<Vec<ProcessedSensorData>> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM sensor_readings");
+        filter.push_reading_conditions(&mut query);
+
+        query
+            .push(" ORDER BY measured_at DESC LIMIT ")
+            .push_bind(limit as i64);
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+
+        let mut results = Vec::with_capacity(rows.len());
+        for row in rows {
+            results.push(self.row_to_processed_data(row)?);
+        }
+
+        Ok(results)
+    }
+
+    /// Obtiene una página de lecturas dentro del filtro en orden cronológico
+    /// `after` es el cursor (timestamp, id) de la última lectura de la página anterior
+    pub async fn get_readings_page(
+        &self,
+        filter: &MetricFilter,
+        after: Option<&(DateTime<Utc>, String)>,
+        limit: usize,
+    ) -> anyhow::Result<Vec<ProcessedSensorData>> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM sensor_readings");
+        filter.push_reading_conditions(&mut query);
+
+        if let Some((timestamp, id)) = after {
+            query
+                .push(" AND (measured_at, id) > (")
+                .push_bind(timestamp.to_rfc3339())
+                .push(", ")
+                .push_bind(id.clone())
+                .push(")");
+        }
+
+        query
+            .push(" ORDER BY measured_at ASC, id ASC LIMIT ")
+            .push_bind(limit as i64);
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+
+        let mut results = Vec::with_capacity(rows.len());
+        for row in rows {
+            results.push(self.row_to_processed_data(row)?);
+        }
+
         Ok(results)
     }
 
@@ -265,14 +1189,22 @@ impl Database {
         Ok(ProcessedSensorData {
             id: Uuid::parse_str(&row.get::<String, _>("id"))?,
             header: SensorHeader {
-                user_uuid: None, // No se almacena en DB local
+                user_uuid: row.get("user_uuid"),
                 device_id: row.get("device_id"),
+                device_type: row.get("device_type"),
+                message_id: row.get("message_id"),
                 location: row.get("location"),
                 topic: row.get("topic"),
                 should_requeue: row.get::<i32, _>("should_requeue") != 0,
+                firmware_version: row.get("firmware_version"),
+                hardware_model: row.get("hardware_model"),
+                rssi: row.get("rssi"),
+                sequence: None,
+                sent_at: None,
             },
             metrics,
             gateway_timestamp: row.get::<String, _>("gateway_timestamp").parse()?,
+            measured_at: row.get::<String, _>("measured_at").parse()?,
             computed,
             quality: DataQuality {
                 score: row.get::<i32, _>("quality_score") as u8,
@@ -283,6 +1215,17 @@ impl Database {
                 metrics_count: row.get::<i32, _>("metrics_count") as usize,
                 measurement_types,
                 should_requeue: row.get::<i32, _>("should_requeue") != 0,
+                device_timestamp: row
+                    .get::<Option<String>, _>("device_timestamp")
+                    .map(|at| at.parse())
+                    .transpose()?,
+                clock_skew_ms: row.get("clock_skew_ms"),
+                profile: row
+                    .get::<Option<String>, _>("profile_json")
+                    .map(|json| serde_json::from_str(&json))
+                    .transpose()?,
+                sync_priority: row.get("sync_priority"),
+                forward_to_cloud: row.get::<i32, _>("forward_to_cloud") != 0,
             },
         })
     }
@@ -300,6 +1243,66 @@ impl Database {
         .execute(&self.pool)
         .await?;
 
+        if result.rows_affected() > 0 {
+            self.cache.invalidate(None);
+        }
+
         Ok(result.rows_affected())
     }
+
+    /// Elimina (o con `dry_run` solo cuenta) las lecturas de un dispositivo y/o
+    /// periodo; los valores de métricas se eliminan en cascada
+    /// `from` es inclusivo y `before` exclusivo
+    pub async fn purge_readings(
+        &self,
+        device_id: Option<&str>,
+        from: Option<DateTime<Utc>>,
+        before: Option<DateTime<Utc>>,
+        dry_run: bool,
+    ) -> anyhow::Result<u64> {
+        // Las lecturas aún en el búfer de escritura también se purgan
+        self.flush_writes().await;
+
+        let mut query = QueryBuilder::<Sqlite>::new(if dry_run {
+            "SELECT COUNT(*) FROM sensor_readings WHERE 1 = 1"
+        } else {
+            "DELETE FROM sensor_readings WHERE 1 = 1"
+        });
+
+        if let Some(device_id) = device_id {
+            query
+                .push(" AND device_id = ")
+                .push_bind(device_id.to_string());
+        }
+        if let Some(from) = from {
+            query
+                .push(" AND measured_at >= ")
+                .push_bind(from.to_rfc3339());
+        }
+        if let Some(before) = before {
+            query
+                .push(" AND measured_at < ")
+                .push_bind(before.to_rfc3339());
+        }
+
+        if dry_run {
+            let count: i64 = query.build_query_scalar().fetch_one(&self.pool).await?;
+            return Ok(count as u64);
+        }
+
+        let result = query.build().execute(&self.pool).await?;
+        let discarded = self.discard_buffered(|data| {
+            device_id.is_none_or(|device_id| data.header.device_id == device_id)
+                && from.is_none_or(|from| data.measured_at >= from)
+                && before.is_none_or(|before| data.measured_at < before)
+        });
+        self.cache.invalidate(device_id);
+
+        Ok(result.rows_affected() + discarded)
+    }
+
+    /// Aciertos y fallos de la caché de lecturas recientes
+    pub fn cache_stats(&self) -> (u64, u64) {
+        self.cache.stats()
+    }
 }
@@ -0,0 +1,199 @@
+use super::Database;
+use super::devices::push_tag_filter;
+use crate::models::{AlertRecord, AlertSeverity};
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use sqlx::sqlite::SqliteRow;
+
+/// Filtros para consultar el historial de alertas
+#[derive(Debug, Clone, Default)]
+pub struct AlertFilter {
+    pub device_id: Option<String>,
+    /// Solo dispositivos con alguna de estas etiquetas (vacío = sin filtro)
+    pub tags: Vec<String>,
+    pub rule_id: Option<String>,
+    /// Solo alertas sin resolver
+    pub active_only: bool,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Historial de alertas disparadas por las reglas
+impl Database {
+    /// Registra una alerta disparada y retorna su ID
+    pub async fn insert_alert(
+        &self,
+        rule_id: &str,
+        device_id: &str,
+        metric: &str,
+        value: f64,
+        severity: AlertSeverity,
+        fired_at: DateTime<Utc>,
+    ) -> anyhow::Result<i64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO alerts (rule_id, device_id, metric, value, severity, fired_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(rule_id)
+        .bind(device_id)
+        .bind(metric)
+        .bind(value)
+        .bind(severity.as_str())
+        .bind(fired_at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// ID de la alerta activa de una regla en un dispositivo
+    pub async fn active_alert_id(
+        &self,
+        rule_id: &str,
+        device_id: &str,
+    ) -> anyhow::Result<Option<i64>> {
+        let id = sqlx::query_scalar(
+            r#"
+            SELECT id FROM alerts
+            WHERE rule_id = ? AND device_id = ? AND resolved_at IS NULL
+            ORDER BY fired_at DESC LIMIT 1
+            "#,
+        )
+        .bind(rule_id)
+        .bind(device_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(id)
+    }
+
+    /// Marca como resueltas las alertas activas de una regla en un dispositivo
+    /// Retorna las alertas resueltas
+    pub async fn resolve_alerts(
+        &self,
+        rule_id: &str,
+        device_id: &str,
+        resolved_at: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<AlertRecord>> {
+        let rows = sqlx::query(
+            r#"
+            UPDATE alerts SET resolved_at = ?
+            WHERE rule_id = ? AND device_id = ? AND resolved_at IS NULL
+            RETURNING *
+            "#,
+        )
+        .bind(resolved_at.to_rfc3339())
+        .bind(rule_id)
+        .bind(device_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(row_to_alert).collect()
+    }
+
+    /// Lista alertas (más recientes primero)
+    pub async fn list_alerts(
+        &self,
+        filter: &AlertFilter,
+        limit: usize,
+    ) -> anyhow::Result<Vec<AlertRecord>> {
+        let mut query = sqlx::QueryBuilder::new("SELECT * FROM alerts WHERE 1 = 1");
+
+        if let Some(device_id) = &filter.device_id {
+            query.push(" AND device_id = ").push_bind(device_id.clone());
+        }
+        push_tag_filter(&mut query, "device_id", &filter.tags);
+        if let Some(rule_id) = &filter.rule_id {
+            query.push(" AND rule_id = ").push_bind(rule_id.clone());
+        }
+        if filter.active_only {
+            query.push(" AND resolved_at IS NULL");
+        }
+        if let Some(from) = filter.from {
+            query.push(" AND fired_at >= ").push_bind(from.to_rfc3339());
+        }
+        if let Some(to) = filter.to {
+            query.push(" AND fired_at <= ").push_bind(to.to_rfc3339());
+        }
+
+        query
+            .push(" ORDER BY fired_at DESC LIMIT ")
+            .push_bind(limit as i64);
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+        rows.into_iter().map(row_to_alert).collect()
+    }
+
+    /// Reconoce una alerta; retorna None si no existe
+    pub async fn ack_alert(
+        &self,
+        id: i64,
+        acked_by: &str,
+        note: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<Option<AlertRecord>> {
+        let row = sqlx::query(
+            "UPDATE alerts SET acked_by = ?, acked_at = ?, ack_note = ? WHERE id = ? RETURNING *",
+        )
+        .bind(acked_by)
+        .bind(at.to_rfc3339())
+        .bind(note)
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(row_to_alert).transpose()
+    }
+
+    /// Obtiene una alerta por ID
+    pub async fn get_alert(&self, id: i64) -> anyhow::Result<Option<AlertRecord>> {
+        let row = sqlx::query("SELECT * FROM alerts WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(row_to_alert).transpose()
+    }
+
+    /// Elimina alertas resueltas más antiguas que la retención configurada
+    /// Las alertas activas se conservan siempre
+    pub async fn cleanup_old_alerts(&self, days_to_keep: i64) -> anyhow::Result<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM alerts
+            WHERE resolved_at IS NOT NULL
+            AND datetime(fired_at) < datetime('now', '-' || ? || ' days')
+            "#,
+        )
+        .bind(days_to_keep)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+/// Convierte una fila de SQL a AlertRecord
+fn row_to_alert(row: SqliteRow) -> anyhow::Result<AlertRecord> {
+    Ok(AlertRecord {
+        id: row.get("id"),
+        rule_id: row.get("rule_id"),
+        device_id: row.get("device_id"),
+        metric: row.get("metric"),
+        value: row.get("value"),
+        severity: row.get::<String, _>("severity").parse()?,
+        fired_at: row.get::<String, _>("fired_at").parse()?,
+        resolved_at: row
+            .get::<Option<String>, _>("resolved_at")
+            .map(|t| t.parse())
+            .transpose()?,
+        acked_by: row.get("acked_by"),
+        acked_at: row
+            .get::<Option<String>, _>("acked_at")
+            .map(|t| t.parse())
+            .transpose()?,
+        ack_note: row.get("ack_note"),
+    })
+}
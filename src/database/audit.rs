@@ -0,0 +1,106 @@
+use super::Database;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::Row;
+
+/// Días de registro de auditoría que se conservan
+const AUDIT_RETENTION_DAYS: i64 = 365;
+
+/// Operación registrada en la auditoría
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub at: DateTime<Utc>,
+    /// Cliente que hizo la operación (nombre de su token)
+    pub principal: String,
+    pub role: String,
+    pub method: String,
+    pub path: String,
+    /// Código HTTP de la respuesta
+    pub status: u16,
+}
+
+/// Filtros de consulta del registro de auditoría
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    pub principal: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Registro de auditoría de las operaciones de la API
+impl Database {
+    /// Registra una operación y descarta las más antiguas que la retención
+    pub async fn record_audit(
+        &self,
+        principal: &str,
+        role: &str,
+        method: &str,
+        path: &str,
+        status: u16,
+    ) -> anyhow::Result<()> {
+        let now = Utc::now();
+        let cutoff = now - chrono::Duration::days(AUDIT_RETENTION_DAYS);
+
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM audit_log WHERE at < ?")
+            .bind(cutoff.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO audit_log (at, principal, role, method, path, status)
+            VALUES (?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(now.to_rfc3339())
+        .bind(principal)
+        .bind(role)
+        .bind(method)
+        .bind(path)
+        .bind(status)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Operaciones registradas, las más recientes primero
+    pub async fn list_audit(
+        &self,
+        filter: &AuditFilter,
+        limit: usize,
+    ) -> anyhow::Result<Vec<AuditEntry>> {
+        let mut query = sqlx::QueryBuilder::new("SELECT * FROM audit_log WHERE 1 = 1");
+
+        if let Some(principal) = &filter.principal {
+            query.push(" AND principal = ").push_bind(principal.clone());
+        }
+        if let Some(from) = filter.from {
+            query.push(" AND at >= ").push_bind(from.to_rfc3339());
+        }
+        if let Some(to) = filter.to {
+            query.push(" AND at <= ").push_bind(to.to_rfc3339());
+        }
+        query
+            .push(" ORDER BY id DESC LIMIT ")
+            .push_bind(limit as i64);
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+        rows.into_iter()
+            .map(|row| {
+                Ok(AuditEntry {
+                    id: row.try_get("id")?,
+                    at: row.try_get::<String, _>("at")?.parse()?,
+                    principal: row.try_get("principal")?,
+                    role: row.try_get("role")?,
+                    method: row.try_get("method")?,
+                    path: row.try_get("path")?,
+                    status: row.try_get("status")?,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,186 @@
+use super::Database;
+use libsqlite3_sys as ffi;
+use std::ffi::{CStr, CString};
+use std::path::Path;
+use std::ptr::{self, NonNull};
+use std::time::Duration;
+
+/// Páginas copiadas en cada paso del respaldo
+const BACKUP_STEP_PAGES: i32 = 1024;
+
+/// Pausa entre pasos para ceder el archivo a los escritores
+const BACKUP_STEP_PAUSE: Duration = Duration::from_millis(20);
+
+/// Reinicios tolerados antes de copiar el resto en un solo paso
+///
+/// Cada escritura de otra conexión entre dos pasos obliga a SQLite a reiniciar
+/// la copia; con el gateway recibiendo lecturas el respaldo podría no terminar.
+const BACKUP_MAX_RESTARTS: u32 = 3;
+
+/// Pasos ocupados (BUSY/LOCKED) tolerados en total antes de abortar el respaldo
+///
+/// Con la pausa entre pasos equivale a unos 10 s esperando a los escritores.
+const BACKUP_MAX_BUSY_RETRIES: u32 = 500;
+
+/// Conexión SQLite propia del archivo de destino, cerrada al soltarse
+struct Destination(*mut ffi::sqlite3);
+
+impl Destination {
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = CString::new(path.to_string_lossy().as_bytes())?;
+        let mut handle = ptr::null_mut();
+        // SAFETY: `file` es una cadena C válida; SQLite asigna el handle aunque falle
+        let rc = unsafe {
+            ffi::sqlite3_open_v2(
+                file.as_ptr(),
+                &mut handle,
+                ffi::SQLITE_OPEN_READWRITE | ffi::SQLITE_OPEN_CREATE,
+                ptr::null(),
+            )
+        };
+        let destination = Self(handle);
+        if rc != ffi::SQLITE_OK {
+            anyhow::bail!(
+                "No se pudo abrir el archivo de respaldo: {}",
+                destination.error()
+            );
+        }
+        Ok(destination)
+    }
+
+    /// Cifra el respaldo con la misma clave que la base de origen (SQLCipher)
+    fn set_key(&self, key: &str) -> anyhow::Result<()> {
+        let pragma = CString::new(format!("PRAGMA key = '{}'", key.replace('\'', "''")))?;
+        // SAFETY: el handle está abierto y `pragma` es una cadena C válida
+        let rc = unsafe {
+            ffi::sqlite3_exec(
+                self.0,
+                pragma.as_ptr(),
+                None,
+                ptr::null_mut(),
+                ptr::null_mut(),
+            )
+        };
+        if rc != ffi::SQLITE_OK {
+            anyhow::bail!("No se pudo cifrar el respaldo: {}", self.error());
+        }
+        Ok(())
+    }
+
+    fn error(&self) -> String {
+        // SAFETY: sqlite3_errmsg acepta el handle y devuelve una cadena propia de SQLite
+        unsafe { CStr::from_ptr(ffi::sqlite3_errmsg(self.0)) }
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+impl Drop for Destination {
+    fn drop(&mut self) {
+        // SAFETY: el handle proviene de sqlite3_open_v2 y no se usa después
+        unsafe {
+            ffi::sqlite3_close(self.0);
+        }
+    }
+}
+
+/// Copia la base `source` a `path` con la API de respaldo en línea de SQLite
+///
+/// Se copian `BACKUP_STEP_PAGES` páginas por paso y entre pasos se libera el
+/// bloqueo de lectura. Bloquea el hilo: debe llamarse desde `spawn_blocking`.
+fn online_backup(
+    source: NonNull<ffi::sqlite3>,
+    path: &Path,
+    key: Option<&str>,
+) -> anyhow::Result<()> {
+    let destination = Destination::open(path)?;
+    if let Some(key) = key {
+        destination.set_key(key)?;
+    }
+
+    let main = c"main";
+    // SAFETY: ambos handles están abiertos y nadie más los usa durante la copia
+    let backup = unsafe {
+        ffi::sqlite3_backup_init(destination.0, main.as_ptr(), source.as_ptr(), main.as_ptr())
+    };
+    if backup.is_null() {
+        anyhow::bail!("No se pudo iniciar el respaldo: {}", destination.error());
+    }
+
+    let mut pages = BACKUP_STEP_PAGES;
+    let mut restarts = 0;
+    let mut busy_retries = 0;
+    let mut remaining = i32::MAX;
+    let rc = loop {
+        // SAFETY: `backup` es válido hasta sqlite3_backup_finish
+        let rc = unsafe { ffi::sqlite3_backup_step(backup, pages) };
+        match rc {
+            ffi::SQLITE_OK => {}
+            ffi::SQLITE_BUSY | ffi::SQLITE_LOCKED => {
+                busy_retries += 1;
+                if busy_retries > BACKUP_MAX_BUSY_RETRIES {
+                    break rc;
+                }
+            }
+            _ => break rc,
+        }
+
+        // Si quedan más páginas que antes, otra conexión escribió y la copia se reinició
+        // SAFETY: `backup` sigue siendo válido; solo se lee su contador de páginas
+        let now_remaining = unsafe { ffi::sqlite3_backup_remaining(backup) };
+        if now_remaining > remaining {
+            restarts += 1;
+            if restarts >= BACKUP_MAX_RESTARTS {
+                tracing::debug!(
+                    restarts,
+                    "Respaldo reiniciado por escrituras, se copia el resto de una vez"
+                );
+                pages = -1;
+            }
+        }
+        remaining = now_remaining;
+
+        std::thread::sleep(BACKUP_STEP_PAUSE);
+    };
+
+    // SAFETY: libera `backup`; el código final refleja el error de cualquier paso
+    let finish = unsafe { ffi::sqlite3_backup_finish(backup) };
+    if matches!(rc, ffi::SQLITE_BUSY | ffi::SQLITE_LOCKED) {
+        anyhow::bail!(
+            "Respaldo abortado: la base de datos siguió ocupada tras {} reintentos",
+            BACKUP_MAX_BUSY_RETRIES
+        );
+    }
+    if rc != ffi::SQLITE_DONE || finish != ffi::SQLITE_OK {
+        anyhow::bail!("Error durante el respaldo: {}", destination.error());
+    }
+    Ok(())
+}
+
+impl Database {
+    /// Genera un snapshot consistente de la base de datos sin detener el gateway
+    ///
+    /// Usa la API de respaldo en línea de SQLite, copiando por tramos de páginas
+    /// para no retener la base mientras llegan lecturas.
+    pub async fn backup_to(&self, path: &Path) -> anyhow::Result<()> {
+        // El snapshot incluye las lecturas aún en el búfer de escritura
+        self.flush_writes().await;
+
+        let mut conn = self.pool.acquire().await?;
+        let path_buf = path.to_path_buf();
+        let key = self.encryption_key.clone();
+        let runtime = tokio::runtime::Handle::current();
+
+        let result = tokio::task::spawn_blocking(move || {
+            let mut handle = runtime.block_on(conn.lock_handle())?;
+            online_backup(handle.as_raw_handle(), &path_buf, key.as_deref())
+        })
+        .await?;
+
+        // Un respaldo a medias no debe confundirse con un snapshot válido
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(path).await;
+        }
+        result
+    }
+}
@@ -0,0 +1,141 @@
+use crate::models::ProcessedSensorData;
+use lru::LruCache;
+use std::collections::VecDeque;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Lecturas recientes de un dispositivo, de la más nueva a la más antigua
+struct CacheEntry {
+    readings: VecDeque<ProcessedSensorData>,
+    /// La entrada contiene todo el histórico del dispositivo (cargado desde SQLite
+    /// y con menos lecturas que la profundidad de la caché)
+    complete: bool,
+}
+
+/// Caché LRU en memoria de las últimas lecturas por dispositivo
+/// Evita leer SQLite (y desgastar la SD) cuando los dashboards consultan cada pocos segundos
+pub struct LatestCache {
+    entries: Mutex<LruCache<String, CacheEntry>>,
+    depth: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl LatestCache {
+    pub fn new(devices: usize, depth: usize) -> Self {
+        Self {
+            entries: Mutex::new(LruCache::new(
+                NonZeroUsize::new(devices).unwrap_or(NonZeroUsize::MIN),
+            )),
+            depth,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Registra lecturas recién insertadas (en orden de llegada)
+    /// Se ordenan como la consulta de respaldo en SQLite: por `measured_at`
+    /// descendente y, a igual timestamp, la última en llegar primero (`rowid DESC`)
+    pub fn record(&self, readings: &[ProcessedSensorData]) {
+        if self.depth == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        for data in readings {
+            let entry = entries.get_or_insert_mut(data.header.device_id.clone(), || CacheEntry {
+                readings: VecDeque::with_capacity(self.depth),
+                complete: false,
+            });
+
+            // Las lecturas acumuladas offline pueden llegar después de otras más nuevas
+            let position = entry
+                .readings
+                .iter()
+                .position(|cached| cached.measured_at <= data.measured_at)
+                .unwrap_or(entry.readings.len());
+
+            // Más antigua que todo lo cacheado: podría haber lecturas intermedias solo en SQLite
+            if position == entry.readings.len() && !entry.complete && position > 0 {
+                continue;
+            }
+
+            entry.readings.insert(position, data.clone());
+            if entry.readings.len() > self.depth {
+                entry.readings.pop_back();
+                entry.complete = false;
+            }
+        }
+    }
+
+    /// Obtiene las `limit` lecturas más recientes si la caché puede responder
+    pub fn recent(&self, device_id: &str, limit: usize) -> Option<Vec<ProcessedSensorData>> {
+        let mut entries = self.entries.lock().unwrap();
+
+        let result = entries
+            .get(device_id)
+            .filter(|entry| entry.complete || entry.readings.len() >= limit)
+            .map(|entry| entry.readings.iter().take(limit).cloned().collect());
+
+        let counter = if result.is_some() {
+            &self.hits
+        } else {
+            &self.misses
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        result
+    }
+
+    /// Reemplaza la entrada de un dispositivo con lecturas leídas de SQLite
+    /// `requested` es el LIMIT usado en la consulta
+    pub fn fill(&self, device_id: &str, readings: &[ProcessedSensorData], requested: usize) {
+        if self.depth == 0 {
+            return;
+        }
+
+        let entry = CacheEntry {
+            readings: readings.iter().take(self.depth).cloned().collect(),
+            complete: readings.len() < requested && readings.len() <= self.depth,
+        };
+
+        self.entries
+            .lock()
+            .unwrap()
+            .put(device_id.to_string(), entry);
+    }
+
+    /// Retira lecturas que se descartaron antes de llegar a SQLite
+    pub fn forget(&self, readings: &[ProcessedSensorData]) {
+        if self.depth == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        for data in readings {
+            if let Some(entry) = entries.peek_mut(&data.header.device_id) {
+                entry.readings.retain(|cached| cached.id != data.id);
+            }
+        }
+    }
+
+    /// Descarta las lecturas cacheadas de un dispositivo (o de todos con None)
+    pub fn invalidate(&self, device_id: Option<&str>) {
+        let mut entries = self.entries.lock().unwrap();
+        match device_id {
+            Some(device_id) => {
+                entries.pop(device_id);
+            }
+            None => entries.clear(),
+        }
+    }
+
+    /// Aciertos y fallos acumulados
+    pub fn stats(&self) -> (u64, u64) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+}
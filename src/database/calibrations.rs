@@ -0,0 +1,105 @@
+use super::Database;
+use crate::models::{Calibration, CalibrationInput};
+use chrono::Utc;
+use sqlx::Row;
+use sqlx::sqlite::SqliteRow;
+
+/// Calibraciones por dispositivo y medición
+impl Database {
+    /// Lista calibraciones, opcionalmente de un único dispositivo
+    pub async fn list_calibrations(
+        &self,
+        device_id: Option<&str>,
+    ) -> anyhow::Result<Vec<Calibration>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT * FROM calibrations
+            WHERE ? IS NULL OR device_id = ?
+            ORDER BY device_id, measurement, valid_from
+            "#,
+        )
+        .bind(device_id)
+        .bind(device_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(row_to_calibration).collect()
+    }
+
+    /// Obtiene una calibración por ID
+    pub async fn get_calibration(&self, id: i64) -> anyhow::Result<Option<Calibration>> {
+        let row = sqlx::query("SELECT * FROM calibrations WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(row_to_calibration).transpose()
+    }
+
+    /// Crea una calibración y retorna su ID
+    pub async fn insert_calibration(&self, input: &CalibrationInput) -> anyhow::Result<i64> {
+        let result = sqlx::query(
+            r#"
+            INSERT INTO calibrations (device_id, measurement, offset, gain, valid_from)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&input.device_id)
+        .bind(&input.measurement)
+        .bind(input.offset)
+        .bind(input.gain)
+        .bind(input.valid_from.unwrap_or_else(Utc::now).to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Actualiza una calibración existente; retorna false si no existe
+    pub async fn update_calibration(
+        &self,
+        id: i64,
+        input: &CalibrationInput,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE calibrations
+            SET device_id = ?, measurement = ?, offset = ?, gain = ?,
+                valid_from = COALESCE(?, valid_from)
+            WHERE id = ?
+            "#,
+        )
+        .bind(&input.device_id)
+        .bind(&input.measurement)
+        .bind(input.offset)
+        .bind(input.gain)
+        .bind(input.valid_from.map(|t| t.to_rfc3339()))
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Elimina una calibración; retorna false si no existe
+    pub async fn delete_calibration(&self, id: i64) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM calibrations WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Convierte una fila de SQL a Calibration
+fn row_to_calibration(row: SqliteRow) -> anyhow::Result<Calibration> {
+    Ok(Calibration {
+        id: row.get("id"),
+        device_id: row.get("device_id"),
+        measurement: row.get("measurement"),
+        offset: row.get::<f64, _>("offset") as f32,
+        gain: row.get::<f64, _>("gain") as f32,
+        valid_from: row.get::<String, _>("valid_from").parse()?,
+    })
+}
@@ -0,0 +1,72 @@
+use super::Database;
+use crate::models::CloudNotice;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::{Sqlite, SqliteConnection, SqliteRow};
+use sqlx::{QueryBuilder, Row};
+
+/// Avisos del gateway pendientes de publicar en el cloud
+///
+/// Se encolan en la misma transacción que el cambio que anuncian, de modo que
+/// sobreviven a una caída del cloud o a un reinicio, y se eliminan al enviarse.
+impl Database {
+    pub(super) async fn queue_cloud_notice(
+        conn: &mut SqliteConnection,
+        kind: &str,
+        device_id: Option<&str>,
+        payload: &serde_json::Value,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO cloud_notices (kind, device_id, payload, created_at)
+            VALUES (?, ?, ?, ?)
+            "#,
+        )
+        .bind(kind)
+        .bind(device_id)
+        .bind(payload.to_string())
+        .bind(at.to_rfc3339())
+        .execute(conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Avisos pendientes, en orden de creación
+    pub async fn pending_cloud_notices(&self, limit: usize) -> anyhow::Result<Vec<CloudNotice>> {
+        let rows = sqlx::query("SELECT * FROM cloud_notices ORDER BY id LIMIT ?")
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter().map(row_to_notice).collect()
+    }
+
+    /// Elimina los avisos ya publicados
+    pub async fn delete_cloud_notices(&self, ids: &[i64]) -> anyhow::Result<()> {
+        if ids.is_empty() {
+            return Ok(());
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new("DELETE FROM cloud_notices WHERE id IN (");
+        let mut separated = query.separated(", ");
+        for id in ids {
+            separated.push_bind(*id);
+        }
+        separated.push_unseparated(")");
+        query.build().execute(&self.pool).await?;
+
+        Ok(())
+    }
+}
+
+/// Convierte una fila de SQL a CloudNotice
+fn row_to_notice(row: SqliteRow) -> anyhow::Result<CloudNotice> {
+    Ok(CloudNotice {
+        id: row.get("id"),
+        kind: row.get("kind"),
+        device_id: row.get("device_id"),
+        payload: serde_json::from_str(&row.get::<String, _>("payload"))?,
+        created_at: row.get::<String, _>("created_at").parse()?,
+    })
+}
@@ -0,0 +1,107 @@
+use super::Database;
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::Sqlite;
+use sqlx::{QueryBuilder, Row};
+
+/// Credenciales (tokens) de los dispositivos
+///
+/// Solo se guarda el hash SHA-256 del secreto. Tras una rotación, el token
+/// anterior sigue siendo válido hasta `previous_credential_expires_at`.
+impl Database {
+    /// Dispositivo al que pertenece un token, por su hash
+    ///
+    /// Los dispositivos dados de baja no tienen credenciales válidas.
+    pub async fn device_for_credential(
+        &self,
+        credential_hash: &str,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Option<String>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT device_id, credential_hash, previous_credential_expires_at
+            FROM devices
+            WHERE (credential_hash = ? OR previous_credential_hash = ?)
+              AND approval != 'decommissioned'
+            "#,
+        )
+        .bind(credential_hash)
+        .bind(credential_hash)
+        .fetch_all(&self.pool)
+        .await?;
+
+        for row in rows {
+            if row.get::<Option<String>, _>("credential_hash").as_deref() == Some(credential_hash) {
+                return Ok(Some(row.get("device_id")));
+            }
+
+            let expires_at = row.get::<Option<String>, _>("previous_credential_expires_at");
+            if let Some(expires_at) = expires_at
+                && expires_at.parse::<DateTime<Utc>>()? > now
+            {
+                return Ok(Some(row.get("device_id")));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Dispositivos de la lista que tienen credenciales emitidas
+    pub async fn devices_with_credentials(
+        &self,
+        device_ids: &[&str],
+    ) -> anyhow::Result<Vec<String>> {
+        if device_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "SELECT device_id FROM devices WHERE credential_hash IS NOT NULL AND device_id IN (",
+        );
+        let mut separated = query.separated(", ");
+        for device_id in device_ids {
+            separated.push_bind(*device_id);
+        }
+        separated.push_unseparated(") ORDER BY device_id");
+
+        Ok(query.build_query_scalar().fetch_all(&self.pool).await?)
+    }
+
+    /// Reemplaza el token de un dispositivo
+    ///
+    /// El anterior, si existía, sigue siendo válido hasta `previous_valid_until`
+    /// (None lo revoca de inmediato). También reinicia la ventana de secuencias,
+    /// ya que el nodo reprogramado con el token nuevo suele arrancar de cero.
+    /// Retorna false si el dispositivo no existe o está dado de baja.
+    pub async fn rotate_device_credential(
+        &self,
+        device_id: &str,
+        credential_hash: &str,
+        previous_valid_until: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<bool> {
+        let previous_valid_until = previous_valid_until.map(|at| at.to_rfc3339());
+
+        let result = sqlx::query(
+            r#"
+            UPDATE devices SET
+                previous_credential_hash = CASE WHEN ? IS NULL THEN NULL ELSE credential_hash END,
+                previous_credential_expires_at = CASE
+                    WHEN credential_hash IS NULL THEN NULL ELSE ?
+                END,
+                credential_hash = ?
+            WHERE device_id = ? AND approval != 'decommissioned'
+            "#,
+        )
+        .bind(&previous_valid_until)
+        .bind(&previous_valid_until)
+        .bind(credential_hash)
+        .bind(device_id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        self.reset_replay_sequence(device_id).await?;
+        Ok(true)
+    }
+}
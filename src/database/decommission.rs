@@ -0,0 +1,156 @@
+use super::Database;
+use crate::models::SensorDataInput;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+/// Configuración eliminada al dar de baja un dispositivo
+#[derive(Debug, Default, Serialize)]
+pub struct DecommissionCleanup {
+    pub calibrations: u64,
+    pub rules: u64,
+    pub overrides: u64,
+    pub quarantined_readings: u64,
+}
+
+/// Baja de dispositivos
+impl Database {
+    /// Carga los dispositivos dados de baja (al migrar)
+    pub(super) async fn load_decommissioned(&self) -> anyhow::Result<()> {
+        let ids: Vec<String> =
+            sqlx::query_scalar("SELECT device_id FROM devices WHERE approval = 'decommissioned'")
+                .fetch_all(&self.pool)
+                .await?;
+
+        *self.decommissioned.write().unwrap() = ids.into_iter().collect();
+        Ok(())
+    }
+
+    pub fn is_decommissioned(&self, device_id: &str) -> bool {
+        self.decommissioned.read().unwrap().contains(device_id)
+    }
+
+    /// Descarta las lecturas de dispositivos dados de baja
+    /// Retorna las lecturas aceptadas y la cantidad rechazada
+    pub fn reject_decommissioned(
+        &self,
+        inputs: Vec<SensorDataInput>,
+    ) -> (Vec<SensorDataInput>, usize) {
+        let decommissioned = self.decommissioned.read().unwrap();
+        if decommissioned.is_empty() {
+            return (inputs, 0);
+        }
+
+        let total = inputs.len();
+        let accepted: Vec<_> = inputs
+            .into_iter()
+            .filter(|input| !decommissioned.contains(&input.header.device_id))
+            .collect();
+        let rejected = total - accepted.len();
+
+        (accepted, rejected)
+    }
+
+    /// Primera y última lectura almacenada de un dispositivo
+    pub async fn device_reading_span(
+        &self,
+        device_id: &str,
+    ) -> anyhow::Result<Option<(DateTime<Utc>, DateTime<Utc>)>> {
+        let (first, last): (Option<String>, Option<String>) = sqlx::query_as(
+            "SELECT MIN(measured_at), MAX(measured_at) FROM sensor_readings WHERE device_id = ?",
+        )
+        .bind(device_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        match (first, last) {
+            (Some(first), Some(last)) => Ok(Some((first.parse()?, last.parse()?))),
+            _ => Ok(None),
+        }
+    }
+
+    /// Da de baja un dispositivo y elimina su configuración
+    ///
+    /// Borra sus calibraciones, las reglas y ajustes propios del dispositivo, su
+    /// twin, su historial de firmware, sus credenciales y sus lecturas en
+    /// cuarentena; el registro se conserva con estado `decommissioned`. El aviso, si se indica, se
+    /// encola para el cloud en la misma transacción. Retorna None si el
+    /// dispositivo no existe o ya estaba dado de baja.
+    pub async fn decommission_device(
+        &self,
+        device_id: &str,
+        reason: Option<&str>,
+        notice: Option<&serde_json::Value>,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<Option<DecommissionCleanup>> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            r#"
+            UPDATE devices SET
+                approval = 'decommissioned',
+                decommissioned_at = ?,
+                decommission_reason = ?,
+                offline_since = NULL,
+                credential_hash = NULL,
+                previous_credential_hash = NULL,
+                previous_credential_expires_at = NULL
+            WHERE device_id = ? AND approval != 'decommissioned'
+            "#,
+        )
+        .bind(at.to_rfc3339())
+        .bind(reason)
+        .bind(device_id)
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let mut cleanup = DecommissionCleanup::default();
+        for (table, condition, removed) in [
+            ("calibrations", "device_id = ?", &mut cleanup.calibrations),
+            ("rules", "device_id = ?", &mut cleanup.rules),
+            (
+                "device_overrides",
+                "scope = 'device' AND target = ?",
+                &mut cleanup.overrides,
+            ),
+            (
+                "quarantined_readings",
+                "device_id = ?",
+                &mut cleanup.quarantined_readings,
+            ),
+        ] {
+            *removed = sqlx::query(&format!("DELETE FROM {} WHERE {}", table, condition))
+                .bind(device_id)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        }
+        for table in ["device_twins", "firmware_rollouts"] {
+            sqlx::query(&format!("DELETE FROM {} WHERE device_id = ?", table))
+                .bind(device_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        if let Some(notice) = notice {
+            Self::queue_cloud_notice(
+                &mut tx,
+                "device_decommissioned",
+                Some(device_id),
+                notice,
+                at,
+            )
+            .await?;
+        }
+
+        tx.commit().await?;
+        self.decommissioned
+            .write()
+            .unwrap()
+            .insert(device_id.to_string());
+
+        Ok(Some(cleanup))
+    }
+}
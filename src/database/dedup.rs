@@ -0,0 +1,133 @@
+use super::Database;
+use crate::models::{ProcessedSensorData, SensorDataInput, SensorHeader};
+use lru::LruCache;
+use std::collections::HashSet;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Mensajes recordados en memoria como máximo
+const DEDUP_CACHE_CAPACITY: usize = 10_000;
+
+/// Registro de corta duración de los `message_id` ya almacenados
+/// Evita consultar SQLite por cada reintento de un ESP32 tras un corte de WiFi
+pub struct MessageDedup {
+    seen: Mutex<LruCache<(String, String), Instant>>,
+    window: Duration,
+    dropped: AtomicU64,
+}
+
+impl MessageDedup {
+    pub fn new(window_secs: u64) -> Self {
+        Self {
+            seen: Mutex::new(LruCache::new(
+                NonZeroUsize::new(DEDUP_CACHE_CAPACITY).unwrap_or(NonZeroUsize::MIN),
+            )),
+            window: Duration::from_secs(window_secs),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Registra los mensajes de lecturas recién insertadas
+    pub fn record(&self, readings: &[ProcessedSensorData]) {
+        if self.window.is_zero() {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut seen = self.seen.lock().unwrap();
+        for data in readings {
+            if let Some(message_id) = &data.header.message_id {
+                seen.put((data.header.device_id.clone(), message_id.clone()), now);
+            }
+        }
+    }
+
+    /// Olvida los mensajes de lecturas que no llegaron a almacenarse
+    pub fn forget(&self, readings: &[ProcessedSensorData]) {
+        if self.window.is_zero() {
+            return;
+        }
+
+        let mut seen = self.seen.lock().unwrap();
+        for data in readings {
+            if let Some(message_id) = &data.header.message_id {
+                seen.pop(&(data.header.device_id.clone(), message_id.clone()));
+            }
+        }
+    }
+
+    /// El mensaje fue registrado dentro de la ventana
+    fn contains(&self, key: &(String, String)) -> bool {
+        let mut seen = self.seen.lock().unwrap();
+        match seen.get(key) {
+            Some(at) if at.elapsed() <= self.window => true,
+            Some(_) => {
+                seen.pop(key);
+                false
+            }
+            None => false,
+        }
+    }
+}
+
+/// Detección de mensajes duplicados por (`device_id`, `message_id`)
+impl Database {
+    /// Indica si el mensaje ya fue almacenado (caché en memoria y luego SQLite)
+    /// Los mensajes sin `message_id` nunca se consideran duplicados
+    pub async fn is_duplicate(&self, header: &SensorHeader) -> anyhow::Result<bool> {
+        let Some(message_id) = &header.message_id else {
+            return Ok(false);
+        };
+
+        let key = (header.device_id.clone(), message_id.clone());
+        let duplicate = self.dedup.contains(&key)
+            || sqlx::query_scalar(
+                "SELECT EXISTS(SELECT 1 FROM sensor_readings WHERE device_id = ? AND message_id = ?)",
+            )
+            .bind(&key.0)
+            .bind(&key.1)
+            .fetch_one(&self.pool)
+            .await?;
+
+        if duplicate {
+            self.dedup.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+        Ok(duplicate)
+    }
+
+    /// Descarta de un batch los mensajes ya almacenados o repetidos dentro del mismo batch
+    /// Retorna las lecturas restantes y el número de duplicados descartados
+    pub async fn filter_duplicates(
+        &self,
+        inputs: Vec<SensorDataInput>,
+    ) -> anyhow::Result<(Vec<SensorDataInput>, usize)> {
+        let mut batch_ids = HashSet::new();
+        let mut unique = Vec::with_capacity(inputs.len());
+        let mut duplicates = 0;
+
+        for input in inputs {
+            let repeated_in_batch =
+                input.header.message_id.as_ref().is_some_and(|id| {
+                    !batch_ids.insert((input.header.device_id.clone(), id.clone()))
+                });
+
+            if repeated_in_batch {
+                self.dedup.dropped.fetch_add(1, Ordering::Relaxed);
+                duplicates += 1;
+            } else if self.is_duplicate(&input.header).await? {
+                duplicates += 1;
+            } else {
+                unique.push(input);
+            }
+        }
+
+        Ok((unique, duplicates))
+    }
+
+    /// Mensajes duplicados descartados desde el arranque
+    pub fn duplicates_dropped(&self) -> u64 {
+        self.dedup.dropped.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,463 @@
+use super::Database;
+use crate::models::{
+    CalibrationInput, DeviceGroup, DevicePatch, DeviceProvisionInput, DeviceRecord,
+    ProcessedSensorData,
+};
+use chrono::{DateTime, Utc};
+use sqlx::sqlite::{Sqlite, SqliteConnection, SqliteRow};
+use sqlx::{QueryBuilder, Row};
+use std::collections::HashMap;
+
+/// Columnas del registro más el último nivel de batería conocido
+const DEVICE_COLUMNS: &str = r#"
+    devices.*,
+    (
+        SELECT value FROM metric_values
+        WHERE metric_values.device_id = devices.device_id
+        AND metric_values.measurement IN ('battery', 'vbat')
+        AND value IS NOT NULL
+        ORDER BY measured_at DESC
+        LIMIT 1
+    ) AS battery,
+    (
+        SELECT COUNT(*) FROM quarantined_readings
+        WHERE quarantined_readings.device_id = devices.device_id
+    ) AS quarantined
+"#;
+
+/// Accesos al registro de dispositivos
+impl Database {
+    /// Registra o actualiza los dispositivos de un conjunto de lecturas ingeridas
+    /// Agrupa por dispositivo para ejecutar un único UPSERT por cada uno
+    pub(super) async fn upsert_devices(
+        conn: &mut SqliteConnection,
+        readings: &[ProcessedSensorData],
+    ) -> anyhow::Result<()> {
+        // device_id -> (lectura más antigua, lectura más reciente, cantidad)
+        let mut per_device: HashMap<&str, (&ProcessedSensorData, &ProcessedSensorData, i64)> =
+            HashMap::new();
+
+        for data in readings {
+            per_device
+                .entry(data.header.device_id.as_str())
+                .and_modify(|(first, latest, count)| {
+                    if data.measured_at < first.measured_at {
+                        *first = data;
+                    }
+                    if data.measured_at >= latest.measured_at {
+                        *latest = data;
+                    }
+                    *count += 1;
+                })
+                .or_insert((data, data, 1));
+        }
+
+        for (first, latest, count) in per_device.into_values() {
+            Self::upsert_device(conn, first, latest, count).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Registra o actualiza un dispositivo
+    ///
+    /// La ubicación y la calidad solo se toman de lecturas posteriores a la
+    /// última vista: un lote atrasado no pisa el estado actual.
+    async fn upsert_device(
+        conn: &mut SqliteConnection,
+        first: &ProcessedSensorData,
+        latest: &ProcessedSensorData,
+        count: i64,
+    ) -> anyhow::Result<()> {
+        let metadata = serde_json::json!({
+            "topic": latest.header.topic,
+            "measurement_types": latest.metadata.measurement_types,
+        });
+
+        sqlx::query(
+            r#"
+            INSERT INTO devices (
+                device_id, location, first_seen, last_seen,
+                message_count, last_quality, metadata, device_type,
+                firmware_version, hardware_model, rssi
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(device_id) DO UPDATE SET
+                location = CASE
+                    WHEN devices.last_seen IS NULL OR excluded.last_seen >= devices.last_seen
+                    THEN excluded.location ELSE devices.location
+                END,
+                first_seen = MIN(COALESCE(devices.first_seen, excluded.first_seen), excluded.first_seen),
+                last_seen = MAX(COALESCE(devices.last_seen, excluded.last_seen), excluded.last_seen),
+                message_count = devices.message_count + excluded.message_count,
+                last_quality = CASE
+                    WHEN devices.last_seen IS NULL OR excluded.last_seen >= devices.last_seen
+                    THEN excluded.last_quality ELSE devices.last_quality
+                END,
+                metadata = json_patch(devices.metadata, excluded.metadata),
+                device_type = COALESCE(devices.device_type, excluded.device_type),
+                firmware_version = COALESCE(excluded.firmware_version, devices.firmware_version),
+                hardware_model = COALESCE(excluded.hardware_model, devices.hardware_model),
+                rssi = COALESCE(excluded.rssi, devices.rssi)
+            "#,
+        )
+        .bind(&latest.header.device_id)
+        .bind(&latest.header.location)
+        .bind(first.measured_at.to_rfc3339())
+        .bind(latest.measured_at.to_rfc3339())
+        .bind(count)
+        .bind(latest.quality.score as i32)
+        .bind(metadata.to_string())
+        .bind(&latest.header.device_type)
+        .bind(&latest.header.firmware_version)
+        .bind(&latest.header.hardware_model)
+        .bind(latest.header.rssi)
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Lista los dispositivos conocidos, más recientes primero
+    pub async fn list_devices(&self) -> anyhow::Result<Vec<DeviceRecord>> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM devices ORDER BY last_seen DESC",
+            DEVICE_COLUMNS
+        ))
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(row_to_device).collect()
+    }
+
+    /// Obtiene un dispositivo del registro
+    pub async fn get_device(&self, device_id: &str) -> anyhow::Result<Option<DeviceRecord>> {
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM devices WHERE device_id = ?",
+            DEVICE_COLUMNS
+        ))
+        .bind(device_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(row_to_device).transpose()
+    }
+
+    /// Provisiona un dispositivo con sus calibraciones iniciales
+    ///
+    /// Un dispositivo que ya envió datos se adopta conservando su historial, y
+    /// uno pendiente de aprobación queda aprobado, con su ventana de secuencias
+    /// reiniciada. Retorna false si ya estaba provisionado.
+    pub async fn provision_device(
+        &self,
+        input: &DeviceProvisionInput,
+        calibrations: &[CalibrationInput],
+        credential_hash: &str,
+        provisioned_at: DateTime<Utc>,
+    ) -> anyhow::Result<bool> {
+        let metadata = input
+            .metadata
+            .clone()
+            .unwrap_or_else(|| serde_json::json!({}));
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query(
+            r#"
+            INSERT INTO devices (
+                device_id, location, metadata, provisioned_at,
+                expected_location, device_type, credential_hash, tags
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(device_id) DO UPDATE SET
+                metadata = json_patch(devices.metadata, excluded.metadata),
+                provisioned_at = excluded.provisioned_at,
+                expected_location = excluded.expected_location,
+                device_type = excluded.device_type,
+                credential_hash = excluded.credential_hash,
+                previous_credential_hash = NULL,
+                previous_credential_expires_at = NULL,
+                tags = excluded.tags,
+                approval = 'approved',
+                decommissioned_at = NULL,
+                decommission_reason = NULL
+            WHERE devices.provisioned_at IS NULL
+            "#,
+        )
+        .bind(&input.device_id)
+        .bind(&input.location)
+        .bind(metadata.to_string())
+        .bind(provisioned_at.to_rfc3339())
+        .bind(&input.location)
+        .bind(&input.device_type)
+        .bind(credential_hash)
+        .bind(serde_json::to_string(&input.tags)?)
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        for calibration in calibrations {
+            sqlx::query(
+                r#"
+                INSERT INTO calibrations (device_id, measurement, offset, gain, valid_from)
+                VALUES (?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&calibration.device_id)
+            .bind(&calibration.measurement)
+            .bind(calibration.offset)
+            .bind(calibration.gain)
+            .bind(provisioned_at.to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        self.decommissioned
+            .write()
+            .unwrap()
+            .remove(&input.device_id);
+        self.reset_replay_sequence(&input.device_id).await?;
+        Ok(true)
+    }
+
+    /// Aplica cambios parciales a un dispositivo; retorna false si no existe
+    ///
+    /// La ubicación reportada solo se reemplaza si el dispositivo aún no envió datos.
+    pub async fn update_device(
+        &self,
+        device_id: &str,
+        patch: &DevicePatch,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE devices SET
+                expected_location = COALESCE(?, expected_location),
+                location = CASE WHEN last_seen IS NULL THEN COALESCE(?, location) ELSE location END,
+                device_type = COALESCE(?, device_type),
+                metadata = json_patch(metadata, COALESCE(?, '{}')),
+                tags = COALESCE(?, tags)
+            WHERE device_id = ?
+            "#,
+        )
+        .bind(&patch.location)
+        .bind(&patch.location)
+        .bind(&patch.device_type)
+        .bind(patch.metadata.as_ref().map(|m| m.to_string()))
+        .bind(patch.tags.as_ref().map(serde_json::to_string).transpose()?)
+        .bind(device_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Elimina un dispositivo del registro junto con sus calibraciones, su twin,
+    /// su historial de actualizaciones de firmware y sus lecturas en cuarentena
+    ///
+    /// Las lecturas se conservan; si el dispositivo vuelve a enviar datos se
+    /// registra de nuevo, aunque se hubiera dado de baja. Retorna false si no existe.
+    pub async fn delete_device(&self, device_id: &str) -> anyhow::Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM calibrations WHERE device_id = ?")
+            .bind(device_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM device_twins WHERE device_id = ?")
+            .bind(device_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM firmware_rollouts WHERE device_id = ?")
+            .bind(device_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM quarantined_readings WHERE device_id = ?")
+            .bind(device_id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM devices WHERE device_id = ?")
+            .bind(device_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        self.decommissioned.write().unwrap().remove(device_id);
+        self.forget_replay_sequence(device_id);
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Estado de presencia de los dispositivos que ya enviaron datos
+    pub async fn list_device_presence(&self) -> anyhow::Result<Vec<DevicePresence>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT device_id, location, device_type, last_seen, offline_since FROM devices
+            WHERE last_seen IS NOT NULL
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(DevicePresence {
+                    device_id: row.get("device_id"),
+                    location: row.get("location"),
+                    device_type: row.get("device_type"),
+                    last_seen: row.get::<String, _>("last_seen").parse()?,
+                    offline_since: parse_optional_time(row.get("offline_since"))?,
+                })
+            })
+            .collect()
+    }
+
+    /// Marca un dispositivo como offline desde `since`, o como online con None
+    pub async fn set_device_offline(
+        &self,
+        device_id: &str,
+        since: Option<DateTime<Utc>>,
+    ) -> anyhow::Result<()> {
+        sqlx::query("UPDATE devices SET offline_since = ? WHERE device_id = ?")
+            .bind(since.map(|at| at.to_rfc3339()))
+            .bind(device_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Dispositivos con alguna de las etiquetas indicadas
+    pub async fn device_ids_with_tags(&self, tags: &[String]) -> anyhow::Result<Vec<String>> {
+        if tags.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT device_id FROM devices WHERE 1 = 1");
+        push_tag_filter(&mut query, "device_id", tags);
+        query.push(" ORDER BY device_id");
+
+        Ok(query.build_query_scalar().fetch_all(&self.pool).await?)
+    }
+
+    /// Etiquetas de cada dispositivo etiquetado
+    pub async fn list_device_tags(&self) -> anyhow::Result<HashMap<String, Vec<String>>> {
+        let rows = sqlx::query("SELECT device_id, tags FROM devices WHERE tags != '[]'")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok((
+                    row.get("device_id"),
+                    serde_json::from_str(&row.get::<String, _>("tags"))?,
+                ))
+            })
+            .collect()
+    }
+
+    /// Grupos definidos por las etiquetas, con sus dispositivos
+    pub async fn list_device_groups(&self) -> anyhow::Result<Vec<DeviceGroup>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT json_each.value AS tag, devices.device_id
+            FROM devices, json_each(devices.tags)
+            ORDER BY tag, devices.device_id
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut groups: Vec<DeviceGroup> = Vec::new();
+        for row in rows {
+            let tag: String = row.get("tag");
+            let device_id: String = row.get("device_id");
+            match groups.last_mut() {
+                Some(group) if group.tag == tag => group.device_ids.push(device_id),
+                _ => groups.push(DeviceGroup {
+                    tag,
+                    device_ids: vec![device_id],
+                }),
+            }
+        }
+
+        Ok(groups)
+    }
+
+    /// Cuenta los dispositivos registrados
+    pub async fn count_devices(&self) -> anyhow::Result<i64> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM devices")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(row.get("count"))
+    }
+}
+
+/// Última actividad de un dispositivo para la detección de silencio
+#[derive(Debug, Clone)]
+pub struct DevicePresence {
+    pub device_id: String,
+    pub location: String,
+    pub device_type: Option<String>,
+    pub last_seen: DateTime<Utc>,
+    pub offline_since: Option<DateTime<Utc>>,
+}
+
+/// Agrega `AND column IN (...)` con los dispositivos que tienen alguna de las etiquetas
+pub(super) fn push_tag_filter(query: &mut QueryBuilder<'_, Sqlite>, column: &str, tags: &[String]) {
+    if tags.is_empty() {
+        return;
+    }
+
+    query.push(format!(
+        " AND {} IN (SELECT devices.device_id FROM devices, json_each(devices.tags) WHERE json_each.value IN (",
+        column
+    ));
+    let mut separated = query.separated(", ");
+    for tag in tags {
+        separated.push_bind(tag.clone());
+    }
+    separated.push_unseparated("))");
+}
+
+/// Convierte una fila de SQL a DeviceRecord
+fn row_to_device(row: SqliteRow) -> anyhow::Result<DeviceRecord> {
+    let last_seen = parse_optional_time(row.get("last_seen"))?;
+    let offline_since = parse_optional_time(row.get("offline_since"))?;
+    let approval = row.get::<String, _>("approval");
+    let status = match (last_seen, offline_since) {
+        _ if approval == "pending" => "pending",
+        _ if approval == "decommissioned" => "decommissioned",
+        (None, _) => "provisioned",
+        (Some(_), Some(_)) => "offline",
+        (Some(_), None) => "online",
+    };
+
+    Ok(DeviceRecord {
+        device_id: row.get("device_id"),
+        location: row.get("location"),
+        first_seen: parse_optional_time(row.get("first_seen"))?,
+        last_seen,
+        status,
+        offline_since,
+        provisioned_at: parse_optional_time(row.get("provisioned_at"))?,
+        expected_location: row.get("expected_location"),
+        device_type: row.get("device_type"),
+        has_credentials: row.get::<Option<String>, _>("credential_hash").is_some(),
+        message_count: row.get("message_count"),
+        last_quality: row.get::<Option<i32>, _>("last_quality").map(|q| q as u8),
+        battery: row.get::<Option<f64>, _>("battery").map(|b| b as f32),
+        metadata: serde_json::from_str(&row.get::<String, _>("metadata"))?,
+        tags: serde_json::from_str(&row.get::<String, _>("tags"))?,
+        firmware_version: row.get("firmware_version"),
+        hardware_model: row.get("hardware_model"),
+        rssi: row.get("rssi"),
+        quarantined: row.get("quarantined"),
+        decommissioned_at: parse_optional_time(row.get("decommissioned_at"))?,
+        decommission_reason: row.get("decommission_reason"),
+    })
+}
+
+fn parse_optional_time(value: Option<String>) -> anyhow::Result<Option<DateTime<Utc>>> {
+    Ok(value.map(|value| value.parse()).transpose()?)
+}
@@ -0,0 +1,223 @@
+use super::Database;
+use crate::models::{FirmwareImage, FirmwareRollout, FirmwareUploadInput, RolloutState};
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use sqlx::sqlite::SqliteRow;
+
+/// Imágenes de firmware y su despliegue en los dispositivos
+impl Database {
+    /// Registra una imagen de firmware ya guardada en disco
+    pub async fn insert_firmware(
+        &self,
+        input: &FirmwareUploadInput,
+        file_name: &str,
+        size_bytes: i64,
+        sha256: &str,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<FirmwareImage> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO firmware (version, device_type, file_name, size_bytes, sha256, notes, uploaded_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(&input.version)
+        .bind(&input.device_type)
+        .bind(file_name)
+        .bind(size_bytes)
+        .bind(sha256)
+        .bind(&input.notes)
+        .bind(at.to_rfc3339())
+        .fetch_one(&self.pool)
+        .await?;
+
+        row_to_firmware(row)
+    }
+
+    /// Lista las imágenes de firmware (más recientes primero)
+    pub async fn list_firmware(&self) -> anyhow::Result<Vec<FirmwareImage>> {
+        let rows = sqlx::query("SELECT * FROM firmware ORDER BY uploaded_at DESC, id DESC")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter().map(row_to_firmware).collect()
+    }
+
+    pub async fn get_firmware(&self, id: i64) -> anyhow::Result<Option<FirmwareImage>> {
+        let row = sqlx::query("SELECT * FROM firmware WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(row_to_firmware).transpose()
+    }
+
+    /// Busca una versión ya subida para el mismo tipo de dispositivo
+    pub async fn find_firmware(
+        &self,
+        version: &str,
+        device_type: Option<&str>,
+    ) -> anyhow::Result<Option<FirmwareImage>> {
+        let row = sqlx::query("SELECT * FROM firmware WHERE version = ? AND device_type IS ?")
+            .bind(version)
+            .bind(device_type)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(row_to_firmware).transpose()
+    }
+
+    /// Elimina una imagen y el historial de su despliegue
+    pub async fn delete_firmware(&self, id: i64) -> anyhow::Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM firmware_rollouts WHERE firmware_id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        let result = sqlx::query("DELETE FROM firmware WHERE id = ?")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Dispositivos del registro con un tipo dado
+    pub async fn device_ids_by_type(&self, device_type: &str) -> anyhow::Result<Vec<String>> {
+        let ids = sqlx::query_scalar(
+            "SELECT device_id FROM devices WHERE device_type = ? ORDER BY device_id",
+        )
+        .bind(device_type)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(ids)
+    }
+
+    /// Inicia (o reinicia) la actualización de un dispositivo en estado pending
+    pub async fn start_rollout(
+        &self,
+        firmware_id: i64,
+        device_id: &str,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO firmware_rollouts (firmware_id, device_id, state, error, created_at, updated_at)
+            VALUES (?, ?, 'pending', NULL, ?, ?)
+            ON CONFLICT(firmware_id, device_id) DO UPDATE SET
+                state = 'pending',
+                error = NULL,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(firmware_id)
+        .bind(device_id)
+        .bind(at.to_rfc3339())
+        .bind(at.to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Actualizaciones de un firmware
+    pub async fn list_rollouts(&self, firmware_id: i64) -> anyhow::Result<Vec<FirmwareRollout>> {
+        let rows =
+            sqlx::query("SELECT * FROM firmware_rollouts WHERE firmware_id = ? ORDER BY device_id")
+                .bind(firmware_id)
+                .fetch_all(&self.pool)
+                .await?;
+
+        rows.into_iter().map(row_to_rollout).collect()
+    }
+
+    /// Actualización de un dispositivo: la del firmware indicado o la última en curso
+    pub async fn get_rollout(
+        &self,
+        device_id: &str,
+        firmware_id: Option<i64>,
+    ) -> anyhow::Result<Option<FirmwareRollout>> {
+        let row = match firmware_id {
+            Some(firmware_id) => {
+                sqlx::query(
+                    "SELECT * FROM firmware_rollouts WHERE device_id = ? AND firmware_id = ?",
+                )
+                .bind(device_id)
+                .bind(firmware_id)
+                .fetch_optional(&self.pool)
+                .await?
+            }
+            None => {
+                sqlx::query(
+                    r#"
+                    SELECT * FROM firmware_rollouts
+                    WHERE device_id = ? AND state IN ('pending', 'downloading')
+                    ORDER BY updated_at DESC
+                    LIMIT 1
+                    "#,
+                )
+                .bind(device_id)
+                .fetch_optional(&self.pool)
+                .await?
+            }
+        };
+
+        row.map(row_to_rollout).transpose()
+    }
+
+    /// Cambia el estado de la actualización de un dispositivo
+    pub async fn set_rollout_state(
+        &self,
+        firmware_id: i64,
+        device_id: &str,
+        state: RolloutState,
+        error: Option<&str>,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE firmware_rollouts SET state = ?, error = ?, updated_at = ?
+            WHERE firmware_id = ? AND device_id = ?
+            "#,
+        )
+        .bind(state.as_str())
+        .bind(error)
+        .bind(at.to_rfc3339())
+        .bind(firmware_id)
+        .bind(device_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Convierte una fila de SQL a FirmwareImage
+fn row_to_firmware(row: SqliteRow) -> anyhow::Result<FirmwareImage> {
+    Ok(FirmwareImage {
+        id: row.get("id"),
+        version: row.get("version"),
+        device_type: row.get("device_type"),
+        size_bytes: row.get("size_bytes"),
+        sha256: row.get("sha256"),
+        notes: row.get("notes"),
+        uploaded_at: row.get::<String, _>("uploaded_at").parse()?,
+        file_name: row.get("file_name"),
+    })
+}
+
+/// Convierte una fila de SQL a FirmwareRollout
+fn row_to_rollout(row: SqliteRow) -> anyhow::Result<FirmwareRollout> {
+    Ok(FirmwareRollout {
+        firmware_id: row.get("firmware_id"),
+        device_id: row.get("device_id"),
+        state: row.get::<String, _>("state").parse()?,
+        error: row.get("error"),
+        created_at: row.get::<String, _>("created_at").parse()?,
+        updated_at: row.get::<String, _>("updated_at").parse()?,
+    })
+}
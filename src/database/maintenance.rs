@@ -0,0 +1,70 @@
+use super::Database;
+
+/// Operaciones de mantenimiento del archivo SQLite
+impl Database {
+    /// Tamaño actual del archivo de base de datos en bytes (page_count * page_size)
+    pub async fn database_size_bytes(&self) -> anyhow::Result<i64> {
+        let page_count: i64 = sqlx::query_scalar("PRAGMA page_count")
+            .fetch_one(&self.pool)
+            .await?;
+        let page_size: i64 = sqlx::query_scalar("PRAGMA page_size")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(page_count * page_size)
+    }
+
+    /// Compacta la base de datos para devolver al sistema el espacio liberado
+    ///
+    /// Si la base ya está en modo `auto_vacuum = INCREMENTAL` solo se liberan
+    /// las páginas libres. En caso contrario se ejecuta un VACUUM completo que
+    /// además convierte la base a modo incremental para las siguientes ejecuciones.
+    /// Retorna el modo usado ("incremental" o "full").
+    pub async fn vacuum(&self) -> anyhow::Result<&'static str> {
+        let mut conn = self.pool.acquire().await?;
+
+        let auto_vacuum: i64 = sqlx::query_scalar("PRAGMA auto_vacuum")
+            .fetch_one(&mut *conn)
+            .await?;
+
+        // 2 = INCREMENTAL
+        if auto_vacuum == 2 {
+            sqlx::query("PRAGMA incremental_vacuum")
+                .execute(&mut *conn)
+                .await?;
+            return Ok("incremental");
+        }
+
+        sqlx::query("PRAGMA auto_vacuum = INCREMENTAL")
+            .execute(&mut *conn)
+            .await?;
+        sqlx::query("VACUUM").execute(&mut *conn).await?;
+
+        Ok("full")
+    }
+
+    /// Ejecuta `PRAGMA quick_check` y retorna los problemas encontrados
+    /// (vacío si la base de datos está íntegra)
+    pub async fn quick_check(&self) -> anyhow::Result<Vec<String>> {
+        let results: Vec<String> = sqlx::query_scalar("PRAGMA quick_check")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(results.into_iter().filter(|r| r != "ok").collect())
+    }
+
+    /// Verifica que la base de datos acepte escrituras
+    ///
+    /// Abre una transacción de escritura (sin modificar filas) y la revierte:
+    /// falla si el archivo es de solo lectura o está bloqueado por otro proceso.
+    pub async fn check_writable(&self) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE settings SET value = value WHERE 0")
+            .execute(&mut *tx)
+            .await?;
+
+        tx.rollback().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,450 @@
+use super::devices::push_tag_filter;
+use super::{Database, SQLITE_MAX_BIND_PARAMS};
+use crate::models::{
+    GroupSummary, HourlyBaseline, MetricPoint, MetricSummary, ProcessedSensorData,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{Sqlite, SqliteConnection, SqliteRow};
+use sqlx::{QueryBuilder, Row};
+use std::collections::{BTreeMap, HashMap};
+
+/// Columnas enlazadas por cada valor en `insert_metric_values`
+const METRIC_VALUE_COLUMNS: usize = 6;
+
+/// Filtro común para consultas sobre valores de métricas
+#[derive(Debug, Clone)]
+pub struct MetricFilter {
+    /// Dispositivos incluidos (vacío = todos)
+    pub device_ids: Vec<String>,
+    /// Ubicaciones incluidas (vacío = todas)
+    pub locations: Vec<String>,
+    /// Solo dispositivos con alguna de estas etiquetas (vacío = sin filtro)
+    pub tags: Vec<String>,
+    pub measurement: Option<String>,
+    pub from: DateTime<Utc>,
+    pub to: DateTime<Utc>,
+    pub quality: QualityFilter,
+}
+
+/// Filtro por la calidad asignada a las lecturas
+#[derive(Debug, Clone, Default)]
+pub struct QualityFilter {
+    pub min_score: Option<u8>,
+    pub max_score: Option<u8>,
+    /// Texto contenido en alguno de los issues de calidad (sin distinguir mayúsculas)
+    pub issue: Option<String>,
+}
+
+impl QualityFilter {
+    pub fn is_empty(&self) -> bool {
+        self.min_score.is_none() && self.max_score.is_none() && self.issue.is_none()
+    }
+
+    /// Agrega las condiciones sobre las columnas de calidad de `sensor_readings`
+    fn push_conditions(&self, query: &mut QueryBuilder<'_, Sqlite>) {
+        if let Some(min_score) = self.min_score {
+            query
+                .push(" AND quality_score >= ")
+                .push_bind(min_score as i64);
+        }
+        if let Some(max_score) = self.max_score {
+            query
+                .push(" AND quality_score <= ")
+                .push_bind(max_score as i64);
+        }
+        if let Some(issue) = &self.issue {
+            // Los comodines de LIKE presentes en el texto se buscan literalmente
+            let escaped = issue
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            query
+                .push(" AND quality_issues LIKE ")
+                .push_bind(format!("%{}%", escaped))
+                .push(" ESCAPE '\\'");
+        }
+    }
+}
+
+/// Criterio de agrupación de los resultados de una consulta
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupBy {
+    Device,
+    Location,
+}
+
+impl GroupBy {
+    fn column(self) -> &'static str {
+        match self {
+            GroupBy::Device => "device_id",
+            GroupBy::Location => "location",
+        }
+    }
+
+    /// Clave del grupo al que pertenece un resultado
+    pub fn key<'a>(self, device_id: &'a str, location: &'a str) -> &'a str {
+        match self {
+            GroupBy::Device => device_id,
+            GroupBy::Location => location,
+        }
+    }
+}
+
+impl MetricFilter {
+    /// Agrupación implícita cuando se consultan varios dispositivos o ubicaciones
+    pub fn implied_group(&self) -> Option<GroupBy> {
+        if self.device_ids.len() > 1 {
+            Some(GroupBy::Device)
+        } else if self.locations.len() > 1 {
+            Some(GroupBy::Location)
+        } else {
+            None
+        }
+    }
+
+    /// Agrega las condiciones WHERE del filtro a una consulta sobre `metric_values`
+    pub(super) fn push_conditions(&self, query: &mut QueryBuilder<'_, Sqlite>) {
+        self.push_scope(query);
+
+        // La calidad se guarda por lectura: se resuelve contra sensor_readings
+        if !self.quality.is_empty() {
+            query.push(" AND reading_id IN (SELECT id FROM sensor_readings WHERE 1 = 1");
+            self.quality.push_conditions(query);
+            query.push(")");
+        }
+    }
+
+    /// Agrega las condiciones WHERE del filtro a una consulta sobre `sensor_readings`
+    pub(super) fn push_reading_conditions(&self, query: &mut QueryBuilder<'_, Sqlite>) {
+        self.push_scope(query);
+        self.quality.push_conditions(query);
+    }
+
+    /// Periodo, dispositivos, ubicaciones y medición
+    fn push_scope(&self, query: &mut QueryBuilder<'_, Sqlite>) {
+        query
+            .push(" WHERE measured_at >= ")
+            .push_bind(self.from.to_rfc3339())
+            .push(" AND measured_at <= ")
+            .push_bind(self.to.to_rfc3339());
+
+        push_in_list(query, "device_id", &self.device_ids);
+        push_in_list(query, "location", &self.locations);
+        push_tag_filter(query, "device_id", &self.tags);
+        if let Some(measurement) = &self.measurement {
+            // La columna es COLLATE NOCASE: la comparación ignora mayúsculas y usa los índices
+            query
+                .push(" AND measurement = ")
+                .push_bind(measurement.clone());
+        }
+    }
+}
+
+/// Agrega `AND column IN (...)` si la lista no está vacía
+fn push_in_list(query: &mut QueryBuilder<'_, Sqlite>, column: &str, values: &[String]) {
+    if values.is_empty() {
+        return;
+    }
+
+    query.push(format!(" AND {} IN (", column));
+    let mut separated = query.separated(", ");
+    for value in values {
+        separated.push_bind(value.clone());
+    }
+    separated.push_unseparated(")");
+}
+
+fn row_to_summary(row: &SqliteRow) -> MetricSummary {
+    MetricSummary {
+        measurement: row.get("measurement"),
+        min: row.get::<Option<f64>, _>("min_value").unwrap_or_default() as f32,
+        max: row.get::<Option<f64>, _>("max_value").unwrap_or_default() as f32,
+        avg: row.get::<Option<f64>, _>("avg_value").unwrap_or_default() as f32,
+        count: row.get::<i64, _>("count") as u32,
+    }
+}
+
+/// Accesos a la tabla normalizada de valores de métricas
+impl Database {
+    /// Escribe los valores individuales de las métricas junto a la lectura
+    pub(super) async fn insert_metric_values(
+        conn: &mut SqliteConnection,
+        readings: &[ProcessedSensorData],
+    ) -> anyhow::Result<()> {
+        let values: Vec<_> = readings
+            .iter()
+            .flat_map(|data| data.metrics.iter().map(move |metric| (data, metric)))
+            .collect();
+
+        for chunk in values.chunks(SQLITE_MAX_BIND_PARAMS / METRIC_VALUE_COLUMNS) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                r#"
+                INSERT INTO metric_values (
+                    reading_id, device_id, location,
+                    measurement, value, measured_at
+                ) "#,
+            );
+
+            query.push_values(chunk, |mut row, (data, metric)| {
+                row.push_bind(data.id.to_string())
+                    .push_bind(data.header.device_id.clone())
+                    .push_bind(data.header.location.clone())
+                    .push_bind(metric.measurement.clone())
+                    // NaN/infinito no son representables en SQL: se guardan como NULL
+                    .push_bind(metric.value.is_finite().then_some(metric.value as f64))
+                    .push_bind(data.measured_at.to_rfc3339());
+            });
+
+            query.build().execute(&mut *conn).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Resumen min/max/avg por medición dentro del filtro
+    pub async fn metric_summary(
+        &self,
+        filter: &MetricFilter,
+    ) -> anyhow::Result<HashMap<String, MetricSummary>> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            r#"
+            SELECT measurement,
+                   MIN(value) as min_value,
+                   MAX(value) as max_value,
+                   AVG(value) as avg_value,
+                   COUNT(value) as count
+            FROM metric_values"#,
+        );
+        filter.push_conditions(&mut query);
+        query.push(" GROUP BY measurement");
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let summary = row_to_summary(row);
+                (summary.measurement.clone(), summary)
+            })
+            .collect())
+    }
+
+    /// Resumen por medición y conteo de lecturas de cada grupo dentro del filtro
+    pub async fn grouped_summary(
+        &self,
+        filter: &MetricFilter,
+        group_by: GroupBy,
+    ) -> anyhow::Result<BTreeMap<String, GroupSummary>> {
+        let column = group_by.column();
+
+        let mut query = QueryBuilder::<Sqlite>::new(format!(
+            r#"
+            SELECT {column} as group_key,
+                   measurement,
+                   MIN(value) as min_value,
+                   MAX(value) as max_value,
+                   AVG(value) as avg_value,
+                   COUNT(value) as count
+            FROM metric_values"#
+        ));
+        filter.push_conditions(&mut query);
+        query.push(" GROUP BY group_key, measurement");
+
+        let mut groups: BTreeMap<String, GroupSummary> = BTreeMap::new();
+        for row in query.build().fetch_all(&self.pool).await? {
+            let summary = row_to_summary(&row);
+            groups
+                .entry(row.get("group_key"))
+                .or_default()
+                .metrics_summary
+                .insert(summary.measurement.clone(), summary);
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new(format!(
+            "SELECT {column} as group_key, COUNT(DISTINCT reading_id) as count FROM metric_values"
+        ));
+        filter.push_conditions(&mut query);
+        query.push(" GROUP BY group_key");
+
+        for row in query.build().fetch_all(&self.pool).await? {
+            groups.entry(row.get("group_key")).or_default().count =
+                row.get::<i64, _>("count") as u32;
+        }
+
+        Ok(groups)
+    }
+
+    /// Mediciones distintas presentes dentro del filtro
+    pub async fn distinct_measurements(
+        &self,
+        filter: &MetricFilter,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut query =
+            QueryBuilder::<Sqlite>::new("SELECT DISTINCT measurement FROM metric_values");
+        filter.push_conditions(&mut query);
+        query.push(" ORDER BY measurement");
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+        Ok(rows.into_iter().map(|row| row.get("measurement")).collect())
+    }
+
+    /// Cuenta las lecturas distintas que cumplen el filtro
+    pub async fn count_readings_in(&self, filter: &MetricFilter) -> anyhow::Result<i64> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "SELECT COUNT(DISTINCT reading_id) as count FROM metric_values",
+        );
+        filter.push_conditions(&mut query);
+
+        let row = query.build().fetch_one(&self.pool).await?;
+        Ok(row.get("count"))
+    }
+
+    /// Serie temporal de valores dentro del filtro, en orden cronológico
+    pub async fn metric_range(
+        &self,
+        filter: &MetricFilter,
+        limit: usize,
+    ) -> anyhow::Result<Vec<MetricPoint>> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "SELECT reading_id, device_id, location, measurement, value, measured_at FROM metric_values",
+        );
+        filter.push_conditions(&mut query);
+        query
+            .push(" ORDER BY measured_at ASC LIMIT ")
+            .push_bind(limit as i64);
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+
+        let mut points = Vec::with_capacity(rows.len());
+        for row in rows {
+            points.push(MetricPoint {
+                reading_id: row.get("reading_id"),
+                device_id: row.get("device_id"),
+                location: row.get("location"),
+                measurement: row.get("measurement"),
+                value: row.get::<Option<f64>, _>("value").map(|v| v as f32),
+                timestamp: row.get::<String, _>("measured_at").parse()?,
+            });
+        }
+
+        Ok(points)
+    }
+
+    /// Últimos `per_series` valores de cada serie (dispositivo, medición) de los
+    /// últimos `days` días, en orden cronológico
+    pub async fn recent_metric_series(
+        &self,
+        per_series: usize,
+        days: i64,
+    ) -> anyhow::Result<Vec<(String, String, f32)>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT device_id, measurement, value FROM (
+                SELECT device_id, measurement, value, measured_at,
+                       ROW_NUMBER() OVER (
+                           PARTITION BY device_id, measurement
+                           ORDER BY measured_at DESC
+                       ) AS rn
+                FROM metric_values
+                WHERE value IS NOT NULL
+                AND datetime(measured_at) >= datetime('now', '-' || ? || ' days')
+            )
+            WHERE rn <= ?
+            ORDER BY measured_at ASC
+            "#,
+        )
+        .bind(days)
+        .bind(per_series as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                (
+                    row.get("device_id"),
+                    row.get("measurement"),
+                    row.get::<f64, _>("value") as f32,
+                )
+            })
+            .collect())
+    }
+
+    /// Media y desviación estándar por dispositivo, medición y hora local del día
+    /// Solo incluye las horas con al menos `min_samples` valores en los últimos `days` días
+    pub async fn hourly_baselines(
+        &self,
+        days: i64,
+        min_samples: i64,
+    ) -> anyhow::Result<Vec<HourlyBaseline>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT device_id, measurement,
+                   CAST(strftime('%H', measured_at, 'localtime') AS INTEGER) AS hour,
+                   AVG(value) AS mean,
+                   AVG(value * value) AS mean_sq,
+                   COUNT(*) AS count
+            FROM metric_values
+            WHERE value IS NOT NULL
+            AND datetime(measured_at) >= datetime('now', '-' || ? || ' days')
+            GROUP BY device_id, measurement, hour
+            HAVING COUNT(*) >= ?
+            "#,
+        )
+        .bind(days)
+        .bind(min_samples)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| {
+                let mean: f64 = row.get("mean");
+                let mean_sq: f64 = row.get("mean_sq");
+
+                HourlyBaseline {
+                    device_id: row.get("device_id"),
+                    measurement: row.get("measurement"),
+                    hour: row.get::<i64, _>("hour") as u32,
+                    mean: mean as f32,
+                    std_dev: (mean_sq - mean * mean).max(0.0).sqrt() as f32,
+                    count: row.get("count"),
+                }
+            })
+            .collect())
+    }
+
+    /// Valores de las mediciones indicadas en las últimas `hours` horas, en orden cronológico
+    pub async fn metric_series_since(
+        &self,
+        measurements: &[&str],
+        hours: i64,
+    ) -> anyhow::Result<Vec<(String, String, f32, DateTime<Utc>)>> {
+        let mut builder = QueryBuilder::<Sqlite>::new(
+            "SELECT device_id, measurement, value, measured_at FROM metric_values \
+             WHERE value IS NOT NULL AND datetime(measured_at) >= datetime('now', '-' || ",
+        );
+        builder.push_bind(hours);
+        builder.push(" || ' hours') AND measurement IN (");
+        let mut separated = builder.separated(", ");
+        for measurement in measurements {
+            separated.push_bind(measurement.to_string());
+        }
+        builder.push(") ORDER BY measured_at ASC");
+
+        let rows = builder.build().fetch_all(&self.pool).await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok((
+                    row.get("device_id"),
+                    row.get("measurement"),
+                    row.get::<f64, _>("value") as f32,
+                    row.get::<String, _>("measured_at").parse()?,
+                ))
+            })
+            .collect()
+    }
+}
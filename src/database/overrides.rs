@@ -0,0 +1,118 @@
+use super::Database;
+use crate::models::{DeviceOverride, DeviceOverrideInput, OverrideScope};
+use chrono::Utc;
+use sqlx::Row;
+use sqlx::sqlite::SqliteRow;
+
+/// Ajustes por dispositivo y por ubicación
+impl Database {
+    /// Lista los ajustes configurados
+    pub async fn list_device_overrides(&self) -> anyhow::Result<Vec<DeviceOverride>> {
+        let rows = sqlx::query("SELECT * FROM device_overrides ORDER BY scope, target")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter().map(row_to_override).collect()
+    }
+
+    /// Obtiene el ajuste de un dispositivo o ubicación
+    pub async fn get_device_override(
+        &self,
+        scope: OverrideScope,
+        target: &str,
+    ) -> anyhow::Result<Option<DeviceOverride>> {
+        let row = sqlx::query("SELECT * FROM device_overrides WHERE scope = ? AND target = ?")
+            .bind(scope.as_str())
+            .bind(target)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(row_to_override).transpose()
+    }
+
+    /// Crea o reemplaza el ajuste de un dispositivo o ubicación
+    pub async fn upsert_device_override(
+        &self,
+        scope: OverrideScope,
+        target: &str,
+        input: &DeviceOverrideInput,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO device_overrides (
+                scope, target, profile, calibrations_json, anomaly_zscore_threshold,
+                baseline_zscore_threshold, sync_priority, forward_to_cloud,
+                quota_messages_per_day, quota_max_rows, updated_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(scope, target) DO UPDATE SET
+                profile = excluded.profile,
+                calibrations_json = excluded.calibrations_json,
+                anomaly_zscore_threshold = excluded.anomaly_zscore_threshold,
+                baseline_zscore_threshold = excluded.baseline_zscore_threshold,
+                sync_priority = excluded.sync_priority,
+                forward_to_cloud = excluded.forward_to_cloud,
+                quota_messages_per_day = excluded.quota_messages_per_day,
+                quota_max_rows = excluded.quota_max_rows,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(scope.as_str())
+        .bind(target)
+        .bind(&input.profile)
+        .bind(serde_json::to_string(&input.calibrations)?)
+        .bind(input.anomaly_zscore_threshold)
+        .bind(input.baseline_zscore_threshold)
+        .bind(input.sync_priority)
+        .bind(input.forward_to_cloud.map(|forward| forward as i32))
+        .bind(input.quota_messages_per_day.map(|limit| limit as i64))
+        .bind(input.quota_max_rows.map(|limit| limit as i64))
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Elimina un ajuste; retorna false si no existe
+    pub async fn delete_device_override(
+        &self,
+        scope: OverrideScope,
+        target: &str,
+    ) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM device_overrides WHERE scope = ? AND target = ?")
+            .bind(scope.as_str())
+            .bind(target)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Convierte una fila de SQL a DeviceOverride
+fn row_to_override(row: SqliteRow) -> anyhow::Result<DeviceOverride> {
+    Ok(DeviceOverride {
+        scope: row.get::<String, _>("scope").parse()?,
+        target: row.get("target"),
+        profile: row.get("profile"),
+        calibrations: serde_json::from_str(&row.get::<String, _>("calibrations_json"))?,
+        anomaly_zscore_threshold: row
+            .get::<Option<f64>, _>("anomaly_zscore_threshold")
+            .map(|v| v as f32),
+        baseline_zscore_threshold: row
+            .get::<Option<f64>, _>("baseline_zscore_threshold")
+            .map(|v| v as f32),
+        sync_priority: row.get("sync_priority"),
+        forward_to_cloud: row
+            .get::<Option<i32>, _>("forward_to_cloud")
+            .map(|forward| forward != 0),
+        quota_messages_per_day: row
+            .get::<Option<i64>, _>("quota_messages_per_day")
+            .map(|limit| limit as u64),
+        quota_max_rows: row
+            .get::<Option<i64>, _>("quota_max_rows")
+            .map(|limit| limit as u64),
+        updated_at: row.get::<String, _>("updated_at").parse()?,
+    })
+}
@@ -0,0 +1,85 @@
+use super::Database;
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+
+/// Días de historial de arranques que se conservan
+const PROCESS_RUNS_RETENTION_DAYS: i64 = 30;
+
+/// Arranque registrado del proceso
+#[derive(Debug, Clone)]
+pub struct ProcessRun {
+    pub started_at: DateTime<Utc>,
+    /// Momento del apagado limpio (None si el proceso terminó abruptamente o sigue en marcha)
+    pub stopped_at: Option<DateTime<Utc>>,
+    pub version: String,
+}
+
+/// Historial de arranques y apagados del gateway
+impl Database {
+    /// Registra el arranque del proceso y retorna su ID
+    ///
+    /// Aprovecha para descartar los arranques más antiguos que la retención.
+    pub async fn record_process_start(
+        &self,
+        started_at: DateTime<Utc>,
+        version: &str,
+    ) -> anyhow::Result<i64> {
+        let cutoff = started_at - chrono::Duration::days(PROCESS_RUNS_RETENTION_DAYS);
+        sqlx::query("DELETE FROM process_runs WHERE started_at < ?")
+            .bind(cutoff.to_rfc3339())
+            .execute(&self.pool)
+            .await?;
+
+        let result = sqlx::query("INSERT INTO process_runs (started_at, version) VALUES (?, ?)")
+            .bind(started_at.to_rfc3339())
+            .bind(version)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.last_insert_rowid())
+    }
+
+    /// Marca el arranque como terminado con un apagado limpio
+    pub async fn mark_clean_shutdown(
+        &self,
+        run_id: i64,
+        stopped_at: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        sqlx::query("UPDATE process_runs SET stopped_at = ? WHERE id = ?")
+            .bind(stopped_at.to_rfc3339())
+            .bind(run_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Arranques anteriores a `run_id` dentro de la retención (más recientes primero)
+    pub async fn previous_process_runs(&self, run_id: i64) -> anyhow::Result<Vec<ProcessRun>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT started_at, stopped_at, version FROM process_runs
+            WHERE id < ?
+            ORDER BY id DESC
+            "#,
+        )
+        .bind(run_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                let started_at: String = row.try_get("started_at")?;
+                let stopped_at: Option<String> = row.try_get("stopped_at")?;
+                Ok(ProcessRun {
+                    started_at: DateTime::parse_from_rfc3339(&started_at)?.with_timezone(&Utc),
+                    stopped_at: stopped_at
+                        .map(|at| DateTime::parse_from_rfc3339(&at))
+                        .transpose()?
+                        .map(|at| at.with_timezone(&Utc)),
+                    version: row.try_get("version")?,
+                })
+            })
+            .collect()
+    }
+}
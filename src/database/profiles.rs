@@ -0,0 +1,82 @@
+use super::Database;
+use crate::models::{DeviceProfile, DeviceProfileInput};
+use chrono::Utc;
+use sqlx::Row;
+use sqlx::sqlite::SqliteRow;
+
+/// Perfiles de métricas por tipo de dispositivo
+impl Database {
+    /// Lista los perfiles configurados
+    pub async fn list_device_profiles(&self) -> anyhow::Result<Vec<DeviceProfile>> {
+        let rows = sqlx::query("SELECT * FROM device_profiles ORDER BY device_type")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter().map(row_to_profile).collect()
+    }
+
+    /// Obtiene el perfil de un tipo de dispositivo
+    pub async fn get_device_profile(
+        &self,
+        device_type: &str,
+    ) -> anyhow::Result<Option<DeviceProfile>> {
+        let row = sqlx::query("SELECT * FROM device_profiles WHERE device_type = ?")
+            .bind(device_type)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(row_to_profile).transpose()
+    }
+
+    /// Crea o reemplaza el perfil de un tipo de dispositivo
+    pub async fn upsert_device_profile(
+        &self,
+        device_type: &str,
+        input: &DeviceProfileInput,
+    ) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO device_profiles (
+                device_type, measurements_json, allow_unknown, offline_after_secs, updated_at
+            ) VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(device_type) DO UPDATE SET
+                measurements_json = excluded.measurements_json,
+                allow_unknown = excluded.allow_unknown,
+                offline_after_secs = excluded.offline_after_secs,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(device_type)
+        .bind(serde_json::to_string(&input.measurements)?)
+        .bind(input.allow_unknown as i32)
+        .bind(input.offline_after_secs.map(|secs| secs as i64))
+        .bind(Utc::now().to_rfc3339())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Elimina un perfil; retorna false si no existe
+    pub async fn delete_device_profile(&self, device_type: &str) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM device_profiles WHERE device_type = ?")
+            .bind(device_type)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Convierte una fila de SQL a DeviceProfile
+fn row_to_profile(row: SqliteRow) -> anyhow::Result<DeviceProfile> {
+    Ok(DeviceProfile {
+        device_type: row.get("device_type"),
+        measurements: serde_json::from_str(&row.get::<String, _>("measurements_json"))?,
+        allow_unknown: row.get::<i32, _>("allow_unknown") != 0,
+        offline_after_secs: row
+            .get::<Option<i64>, _>("offline_after_secs")
+            .map(|secs| secs as u64),
+        updated_at: row.get::<String, _>("updated_at").parse()?,
+    })
+}
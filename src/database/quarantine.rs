@@ -0,0 +1,128 @@
+use super::Database;
+use crate::models::SensorDataInput;
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use std::collections::HashMap;
+
+/// Dispositivos desconocidos pendientes de aprobación y sus lecturas en cuarentena
+impl Database {
+    /// Separa las lecturas de dispositivos no aprobados
+    ///
+    /// Un dispositivo desconocido se registra en estado `pending`, y sus lecturas
+    /// se guardan sin procesar hasta que un operador lo apruebe. Sin
+    /// `DEVICE_APPROVAL_REQUIRED` todas las lecturas se aceptan. Retorna las
+    /// lecturas aprobadas y la cantidad puesta en cuarentena.
+    pub async fn quarantine_unapproved(
+        &self,
+        inputs: Vec<SensorDataInput>,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<(Vec<SensorDataInput>, usize)> {
+        if !self.approval_required || inputs.is_empty() {
+            return Ok((inputs, 0));
+        }
+
+        let mut approved_devices: HashMap<String, bool> = HashMap::new();
+        let mut approved = Vec::with_capacity(inputs.len());
+        let mut quarantined = 0;
+        let mut tx = self.pool.begin().await?;
+
+        for input in inputs {
+            let device_id = input.header.device_id.clone();
+            let is_approved = match approved_devices.get(&device_id) {
+                Some(is_approved) => *is_approved,
+                None => {
+                    let approval: Option<String> =
+                        sqlx::query_scalar("SELECT approval FROM devices WHERE device_id = ?")
+                            .bind(&device_id)
+                            .fetch_optional(&mut *tx)
+                            .await?;
+
+                    if approval.is_none() {
+                        let metadata = serde_json::json!({ "topic": input.header.topic });
+                        sqlx::query(
+                            r#"
+                            INSERT INTO devices (device_id, location, metadata, device_type, approval)
+                            VALUES (?, ?, ?, ?, 'pending')
+                            "#,
+                        )
+                        .bind(&device_id)
+                        .bind(&input.header.location)
+                        .bind(metadata.to_string())
+                        .bind(&input.header.device_type)
+                        .execute(&mut *tx)
+                        .await?;
+
+                        tracing::warn!(
+                            device_id = %device_id,
+                            location = %input.header.location,
+                            "Dispositivo desconocido registrado pendiente de aprobación"
+                        );
+                    }
+
+                    let is_approved = approval.as_deref() == Some("approved");
+                    approved_devices.insert(device_id, is_approved);
+                    is_approved
+                }
+            };
+
+            if is_approved {
+                approved.push(input);
+                continue;
+            }
+
+            sqlx::query(
+                "INSERT INTO quarantined_readings (device_id, received_at, payload) VALUES (?, ?, ?)",
+            )
+            .bind(&input.header.device_id)
+            .bind(at.to_rfc3339())
+            .bind(serde_json::to_string(&input)?)
+            .execute(&mut *tx)
+            .await?;
+            quarantined += 1;
+        }
+
+        tx.commit().await?;
+        Ok((approved, quarantined))
+    }
+
+    /// Aprueba un dispositivo pendiente; retorna false si no estaba pendiente
+    ///
+    /// Reinicia su ventana de secuencias: el nodo aprobado puede haberse reiniciado.
+    pub async fn approve_device(&self, device_id: &str) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            "UPDATE devices SET approval = 'approved' WHERE device_id = ? AND approval = 'pending'",
+        )
+        .bind(device_id)
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+        self.reset_replay_sequence(device_id).await?;
+        Ok(true)
+    }
+
+    /// Lecturas en cuarentena de un dispositivo, en orden de llegada
+    pub async fn list_quarantined(&self, device_id: &str) -> anyhow::Result<Vec<SensorDataInput>> {
+        let rows =
+            sqlx::query("SELECT payload FROM quarantined_readings WHERE device_id = ? ORDER BY id")
+                .bind(device_id)
+                .fetch_all(&self.pool)
+                .await?;
+
+        rows.into_iter()
+            .map(|row| Ok(serde_json::from_str(&row.get::<String, _>("payload"))?))
+            .collect()
+    }
+
+    /// Elimina las lecturas en cuarentena de un dispositivo
+    pub async fn delete_quarantined(&self, device_id: &str) -> anyhow::Result<u64> {
+        let result = sqlx::query("DELETE FROM quarantined_readings WHERE device_id = ?")
+            .bind(device_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,326 @@
+use super::Database;
+use crate::models::{SensorDataInput, SensorHeader};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Secuencias anteriores a la más alta que aún se aceptan (mensajes fuera de orden)
+const SEQUENCE_WINDOW: u64 = 64;
+
+/// Intervalo con el que se persisten las secuencias más altas
+const FLUSH_INTERVAL_SECS: u64 = 10;
+
+/// Dispositivos cuyas secuencias se siguen en memoria
+///
+/// El `device_id` llega en el tópico o el payload: sin límite, cualquiera que
+/// publique podría hacer crecer el mapa indefinidamente.
+const MAX_TRACKED_DEVICES: usize = 10_000;
+
+/// Motivo por el que se rechaza un mensaje
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplayRejection {
+    /// Faltan `sequence` o `sentAt` con `REPLAY_REQUIRED`
+    Missing,
+    /// `sentAt` fuera de la ventana o basado en el uptime
+    Stale,
+    /// Secuencia ya recibida o demasiado antigua
+    Replayed,
+}
+
+impl ReplayRejection {
+    pub fn message(self) -> &'static str {
+        match self {
+            ReplayRejection::Missing => "sequence y sentAt son obligatorios",
+            ReplayRejection::Stale => "sentAt fuera de la ventana de REPLAY_WINDOW_SECS",
+            ReplayRejection::Replayed => "secuencia ya recibida",
+        }
+    }
+}
+
+/// Secuencias recibidas de un dispositivo (ventana deslizante, como en IPsec)
+struct SequenceWindow {
+    highest: u64,
+    /// Bit i: se recibió `highest - i`
+    seen: u64,
+    /// `highest` cambió desde la última persistencia
+    dirty: bool,
+    /// El dispositivo existe en el registro (su secuencia se persiste)
+    registered: bool,
+}
+
+impl SequenceWindow {
+    /// Registra la secuencia; retorna false si ya se recibió o quedó fuera de la ventana
+    fn accept(&mut self, sequence: u64) -> bool {
+        if sequence > self.highest {
+            let shift = sequence - self.highest;
+            self.seen = if shift >= SEQUENCE_WINDOW {
+                1
+            } else {
+                (self.seen << shift) | 1
+            };
+            self.highest = sequence;
+            self.dirty = true;
+            return true;
+        }
+
+        let offset = self.highest - sequence;
+        if offset >= SEQUENCE_WINDOW || self.seen & (1 << offset) != 0 {
+            return false;
+        }
+        self.seen |= 1 << offset;
+        true
+    }
+}
+
+/// Protección contra el reenvío de mensajes capturados
+///
+/// Cada mensaje declara su momento de envío (`sentAt`), que debe estar dentro
+/// de `REPLAY_WINDOW_SECS` del reloj del gateway, y un número de secuencia
+/// creciente por dispositivo. Se admiten hasta 64 mensajes fuera de orden; una
+/// secuencia repetida o más antigua se rechaza. La secuencia más alta de cada
+/// dispositivo se persiste para que un reinicio no reabra la ventana.
+pub struct ReplayGuard {
+    /// None = protección deshabilitada
+    window: Option<chrono::Duration>,
+    required: bool,
+    sequences: Mutex<HashMap<String, SequenceWindow>>,
+    /// Serializa la persistencia y los reinicios para que una persistencia en
+    /// curso no vuelva a escribir la secuencia de un dispositivo reiniciado
+    flush_lock: tokio::sync::Mutex<()>,
+    rejected: AtomicU64,
+}
+
+impl ReplayGuard {
+    pub fn new(window_secs: u64, required: bool) -> Self {
+        Self {
+            window: (window_secs > 0).then(|| chrono::Duration::seconds(window_secs as i64)),
+            required,
+            sequences: Mutex::new(HashMap::new()),
+            flush_lock: tokio::sync::Mutex::new(()),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    fn check(&self, header: &SensorHeader, now: DateTime<Utc>) -> Result<(), ReplayRejection> {
+        let Some(window) = self.window else {
+            return Ok(());
+        };
+        if self.required && (header.sequence.is_none() || header.sent_at.is_none()) {
+            return Err(ReplayRejection::Missing);
+        }
+
+        if let Some(sent_at) = &header.sent_at {
+            let fresh = sent_at
+                .absolute()
+                .is_some_and(|sent_at| (now - sent_at).abs() <= window);
+            if !fresh {
+                return Err(ReplayRejection::Stale);
+            }
+        }
+
+        if let Some(sequence) = header.sequence {
+            let mut sequences = self.sequences.lock().unwrap();
+            let accepted = match sequences.get_mut(&header.device_id) {
+                Some(state) => state.accept(sequence),
+                None => {
+                    if make_room(&mut sequences) {
+                        sequences.insert(
+                            header.device_id.clone(),
+                            SequenceWindow {
+                                highest: sequence,
+                                seen: 1,
+                                dirty: true,
+                                registered: false,
+                            },
+                        );
+                    } else {
+                        tracing::warn!(
+                            device_id = %header.device_id,
+                            "Límite de dispositivos con secuencia alcanzado, mensaje aceptado sin seguimiento"
+                        );
+                    }
+                    true
+                }
+            };
+            if !accepted {
+                return Err(ReplayRejection::Replayed);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Libera lugar para un dispositivo nuevo; retorna false si el mapa sigue lleno
+///
+/// Se descartan primero los dispositivos ausentes del registro, cuya secuencia
+/// no se persiste: su ventana se reabre, pero no pueden desplazar a los registrados.
+fn make_room(sequences: &mut HashMap<String, SequenceWindow>) -> bool {
+    if sequences.len() < MAX_TRACKED_DEVICES {
+        return true;
+    }
+    sequences.retain(|_, state| state.registered);
+    sequences.len() < MAX_TRACKED_DEVICES
+}
+
+/// Protección contra reenvíos
+impl Database {
+    /// Carga las secuencias persistidas (al migrar)
+    ///
+    /// Tras un reinicio se desconoce qué secuencias intermedias llegaron: solo
+    /// se aceptan las posteriores a la más alta.
+    pub(super) async fn load_replay_sequences(&self) -> anyhow::Result<()> {
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "SELECT device_id, replay_sequence FROM devices WHERE replay_sequence IS NOT NULL",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut sequences = self.replay.sequences.lock().unwrap();
+        for (device_id, highest) in rows {
+            sequences.insert(
+                device_id,
+                SequenceWindow {
+                    highest: highest.max(0) as u64,
+                    seen: u64::MAX,
+                    dirty: false,
+                    registered: true,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    /// Verifica la frescura y la secuencia de un mensaje
+    pub fn check_replay(
+        &self,
+        header: &SensorHeader,
+        now: DateTime<Utc>,
+    ) -> Result<(), ReplayRejection> {
+        let result = self.replay.check(header, now);
+        if let Err(rejection) = result {
+            self.replay.rejected.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(
+                device_id = %header.device_id,
+                sequence = ?header.sequence,
+                reason = rejection.message(),
+                "Mensaje rechazado por la protección contra reenvíos"
+            );
+        }
+        result
+    }
+
+    /// Descarta las lecturas reenviadas o fuera de la ventana de un batch
+    /// Retorna las lecturas aceptadas y la cantidad rechazada
+    pub fn reject_replayed(
+        &self,
+        inputs: Vec<SensorDataInput>,
+        now: DateTime<Utc>,
+    ) -> (Vec<SensorDataInput>, usize) {
+        if self.replay.window.is_none() {
+            return (inputs, 0);
+        }
+
+        let total = inputs.len();
+        let accepted: Vec<_> = inputs
+            .into_iter()
+            .filter(|input| self.check_replay(&input.header, now).is_ok())
+            .collect();
+        let rejected = total - accepted.len();
+
+        (accepted, rejected)
+    }
+
+    /// Mensajes rechazados por la protección contra reenvíos desde el arranque
+    pub fn replays_rejected(&self) -> u64 {
+        self.replay.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Olvida las secuencias de un dispositivo eliminado del registro
+    pub(super) fn forget_replay_sequence(&self, device_id: &str) {
+        self.replay.sequences.lock().unwrap().remove(device_id);
+    }
+
+    /// Reinicia la ventana de secuencias de un dispositivo
+    ///
+    /// El siguiente mensaje fija la nueva secuencia de partida, así un nodo
+    /// cuyo contador volvió a cero tras un reinicio vuelve a ser aceptado. Se
+    /// aplica al rotar su token, al aprobarlo y desde la API de administración.
+    /// Retorna false si el dispositivo no existe en el registro.
+    pub async fn reset_replay_sequence(&self, device_id: &str) -> anyhow::Result<bool> {
+        let _flush = self.replay.flush_lock.lock().await;
+        self.forget_replay_sequence(device_id);
+
+        let result = sqlx::query("UPDATE devices SET replay_sequence = NULL WHERE device_id = ?")
+            .bind(device_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Persiste las secuencias más altas que cambiaron
+    ///
+    /// Las de dispositivos aún no registrados se quedan solo en memoria hasta
+    /// que vuelvan a cambiar: el primer mensaje puede llegar antes del registro.
+    pub async fn flush_replay_sequences(&self) -> anyhow::Result<()> {
+        let _flush = self.replay.flush_lock.lock().await;
+        let dirty: Vec<(String, u64)> = {
+            let mut sequences = self.replay.sequences.lock().unwrap();
+            sequences
+                .iter_mut()
+                .filter(|(_, state)| state.dirty)
+                .map(|(device_id, state)| {
+                    state.dirty = false;
+                    (device_id.clone(), state.highest)
+                })
+                .collect()
+        };
+        if dirty.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let mut registered = Vec::new();
+        for (device_id, highest) in dirty {
+            let result = sqlx::query(
+                r#"
+                UPDATE devices
+                SET replay_sequence = MAX(COALESCE(replay_sequence, 0), ?)
+                WHERE device_id = ?
+                "#,
+            )
+            .bind(i64::try_from(highest).unwrap_or(i64::MAX))
+            .bind(&device_id)
+            .execute(&mut *tx)
+            .await?;
+            if result.rows_affected() > 0 {
+                registered.push(device_id);
+            }
+        }
+        tx.commit().await?;
+
+        let mut sequences = self.replay.sequences.lock().unwrap();
+        for device_id in registered {
+            if let Some(state) = sequences.get_mut(&device_id) {
+                state.registered = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Tarea de persistencia periódica de las secuencias
+    pub async fn start_replay_flush_task(self) {
+        let mut interval = tokio::time::interval(Duration::from_secs(FLUSH_INTERVAL_SECS));
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.flush_replay_sequences().await {
+                tracing::error!("Error persistiendo las secuencias anti-reenvío: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use super::Database;
+use crate::models::{MeasurementRange, Rule, RuleInput, RuleOperator};
+use sqlx::Row;
+use sqlx::sqlite::SqliteRow;
+
+/// Rangos de validez aplicados cuando la tabla está vacía
+const DEFAULT_RANGES: [(&str, f32, f32); 7] = [
+    ("temperature", -10.0, 50.0),
+    ("humidity", 10.0, 95.0),
+    ("humedad", 10.0, 95.0),
+    ("voltage", 0.0, 50.0),
+    ("voltaje", 0.0, 50.0),
+    ("distance", 0.0, 10000.0),
+    ("distancia", 0.0, 10000.0),
+];
+
+/// Rangos de validez por medición y reglas de acciones
+impl Database {
+    /// Inserta los rangos por defecto si aún no hay reglas configuradas
+    pub(super) async fn seed_measurement_rules(&self) -> anyhow::Result<()> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM measurement_rules")
+            .fetch_one(&self.pool)
+            .await?;
+
+        if count == 0 {
+            let defaults: Vec<_> = DEFAULT_RANGES
+                .iter()
+                .map(|(measurement, min, max)| MeasurementRange {
+                    measurement: measurement.to_string(),
+                    min: *min,
+                    max: *max,
+                })
+                .collect();
+            self.replace_measurement_ranges(&defaults).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Lista los rangos de validez configurados
+    pub async fn list_measurement_ranges(&self) -> anyhow::Result<Vec<MeasurementRange>> {
+        let rows = sqlx::query("SELECT * FROM measurement_rules ORDER BY measurement")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| MeasurementRange {
+                measurement: row.get("measurement"),
+                min: row.get::<f64, _>("min_value") as f32,
+                max: row.get::<f64, _>("max_value") as f32,
+            })
+            .collect())
+    }
+
+    /// Reemplaza el conjunto completo de rangos de validez
+    pub async fn replace_measurement_ranges(
+        &self,
+        ranges: &[MeasurementRange],
+    ) -> anyhow::Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM measurement_rules")
+            .execute(&mut *tx)
+            .await?;
+
+        for range in ranges {
+            sqlx::query(
+                r#"
+                INSERT INTO measurement_rules (measurement, min_value, max_value, updated_at)
+                VALUES (?, ?, ?, CURRENT_TIMESTAMP)
+                "#,
+            )
+            .bind(range.measurement.to_lowercase())
+            .bind(range.min)
+            .bind(range.max)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Lista las reglas de acciones
+    pub async fn list_rules(&self) -> anyhow::Result<Vec<Rule>> {
+        let rows = sqlx::query("SELECT * FROM rules ORDER BY created_at")
+            .fetch_all(&self.pool)
+            .await?;
+
+        rows.into_iter().map(row_to_rule).collect()
+    }
+
+    /// Obtiene una regla por ID
+    pub async fn get_rule(&self, id: &str) -> anyhow::Result<Option<Rule>> {
+        let row = sqlx::query("SELECT * FROM rules WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(row_to_rule).transpose()
+    }
+
+    /// Crea una regla con el ID indicado
+    pub async fn insert_rule(&self, id: &str, input: &RuleInput) -> anyhow::Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO rules (
+                id, name, measurement, device_id, location, tag, operator,
+                threshold, for_secs, consecutive, cooldown_secs, clear_threshold,
+                actions_json, enabled, created_at, updated_at
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP)
+            "#,
+        )
+        .bind(id)
+        .bind(&input.name)
+        .bind(&input.measurement)
+        .bind(&input.device_id)
+        .bind(&input.location)
+        .bind(&input.tag)
+        .bind(operator_to_str(input.operator)?)
+        .bind(input.threshold)
+        .bind(input.for_secs as i64)
+        .bind(input.consecutive as i64)
+        .bind(input.cooldown_secs as i64)
+        .bind(input.clear_threshold)
+        .bind(serde_json::to_string(&input.actions)?)
+        .bind(input.enabled as i32)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Actualiza una regla existente; retorna false si no existe
+    pub async fn update_rule(&self, id: &str, input: &RuleInput) -> anyhow::Result<bool> {
+        let result = sqlx::query(
+            r#"
+            UPDATE rules
+            SET name = ?, measurement = ?, device_id = ?, location = ?, tag = ?, operator = ?,
+                threshold = ?, for_secs = ?, consecutive = ?, cooldown_secs = ?,
+                clear_threshold = ?, actions_json = ?, enabled = ?,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE id = ?
+            "#,
+        )
+        .bind(&input.name)
+        .bind(&input.measurement)
+        .bind(&input.device_id)
+        .bind(&input.location)
+        .bind(&input.tag)
+        .bind(operator_to_str(input.operator)?)
+        .bind(input.threshold)
+        .bind(input.for_secs as i64)
+        .bind(input.consecutive as i64)
+        .bind(input.cooldown_secs as i64)
+        .bind(input.clear_threshold)
+        .bind(serde_json::to_string(&input.actions)?)
+        .bind(input.enabled as i32)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Elimina una regla; retorna false si no existe
+    pub async fn delete_rule(&self, id: &str) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM rules WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Serializa el operador como texto plano ("gt", "lte", ...)
+fn operator_to_str(operator: RuleOperator) -> anyhow::Result<String> {
+    match serde_json::to_value(operator)? {
+        serde_json::Value::String(s) => Ok(s),
+        other => anyhow::bail!("Operador inválido: {}", other),
+    }
+}
+
+/// Convierte una fila de SQL a Rule
+fn row_to_rule(row: SqliteRow) -> anyhow::Result<Rule> {
+    Ok(Rule {
+        id: row.get("id"),
+        name: row.get("name"),
+        measurement: row.get("measurement"),
+        device_id: row.get("device_id"),
+        location: row.get("location"),
+        tag: row.get("tag"),
+        operator: serde_json::from_value(serde_json::Value::String(row.get("operator")))?,
+        threshold: row.get::<f64, _>("threshold") as f32,
+        for_secs: row.get::<i64, _>("for_secs") as u64,
+        consecutive: row.get::<i64, _>("consecutive") as u32,
+        cooldown_secs: row.get::<i64, _>("cooldown_secs") as u64,
+        clear_threshold: row
+            .get::<Option<f64>, _>("clear_threshold")
+            .map(|value| value as f32),
+        actions: serde_json::from_str(&row.get::<String, _>("actions_json"))?,
+        enabled: row.get::<i32, _>("enabled") != 0,
+    })
+}
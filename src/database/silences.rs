@@ -0,0 +1,88 @@
+use super::Database;
+use crate::models::AlertSilence;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::Row;
+use sqlx::sqlite::SqliteRow;
+
+/// Días que se conservan los silencios vencidos
+const SILENCE_RETENTION_DAYS: i64 = 30;
+
+/// Silencios de notificaciones de alertas
+impl Database {
+    /// Registra un silencio y retorna el registro creado
+    ///
+    /// Aprovecha la escritura para eliminar los silencios vencidos hace más de
+    /// la retención.
+    pub async fn insert_silence(
+        &self,
+        device_id: Option<&str>,
+        rule_id: Option<&str>,
+        reason: Option<&str>,
+        created_by: &str,
+        created_at: DateTime<Utc>,
+        expires_at: DateTime<Utc>,
+    ) -> anyhow::Result<AlertSilence> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM alert_silences WHERE expires_at < ?")
+            .bind((created_at - Duration::days(SILENCE_RETENTION_DAYS)).to_rfc3339())
+            .execute(&mut *tx)
+            .await?;
+
+        let row = sqlx::query(
+            r#"
+            INSERT INTO alert_silences (device_id, rule_id, reason, created_by, created_at, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(device_id)
+        .bind(rule_id)
+        .bind(reason)
+        .bind(created_by)
+        .bind(created_at.to_rfc3339())
+        .bind(expires_at.to_rfc3339())
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+        row_to_silence(row)
+    }
+
+    /// Silencios vigentes en el momento indicado
+    pub async fn list_active_silences(
+        &self,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<AlertSilence>> {
+        let rows =
+            sqlx::query("SELECT * FROM alert_silences WHERE expires_at > ? ORDER BY expires_at")
+                .bind(at.to_rfc3339())
+                .fetch_all(&self.pool)
+                .await?;
+
+        rows.into_iter().map(row_to_silence).collect()
+    }
+
+    /// Elimina un silencio; retorna false si no existe
+    pub async fn delete_silence(&self, id: i64) -> anyhow::Result<bool> {
+        let result = sqlx::query("DELETE FROM alert_silences WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Convierte una fila de SQL a AlertSilence
+fn row_to_silence(row: SqliteRow) -> anyhow::Result<AlertSilence> {
+    Ok(AlertSilence {
+        id: row.get("id"),
+        device_id: row.get("device_id"),
+        rule_id: row.get("rule_id"),
+        reason: row.get("reason"),
+        created_by: row.get("created_by"),
+        created_at: row.get::<String, _>("created_at").parse()?,
+        expires_at: row.get::<String, _>("expires_at").parse()?,
+    })
+}
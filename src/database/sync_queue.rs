@@ -0,0 +1,96 @@
+use super::Database;
+use super::devices::push_tag_filter;
+use crate::models::PendingSyncEntry;
+use sqlx::sqlite::Sqlite;
+use sqlx::{QueryBuilder, Row};
+use uuid::Uuid;
+
+/// Inspección y corrección manual de la cola de sincronización
+impl Database {
+    /// Lista lecturas pendientes de sincronizar con sus intentos y último error
+    /// Con `failed_only` solo incluye las que ya fallaron al menos una vez
+    pub async fn list_pending_sync(
+        &self,
+        failed_only: bool,
+        tags: &[String],
+        offset: usize,
+        limit: usize,
+    ) -> anyhow::Result<Vec<PendingSyncEntry>> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            r#"
+            SELECT id, device_id, location, gateway_timestamp,
+                   sync_attempts, last_sync_attempt, last_sync_error
+            FROM sensor_readings
+            WHERE synced = 0
+            "#,
+        );
+        if failed_only {
+            query.push(" AND last_sync_error IS NOT NULL");
+        }
+        push_tag_filter(&mut query, "device_id", tags);
+        query
+            .push(" ORDER BY sync_priority DESC, gateway_timestamp ASC LIMIT ")
+            .push_bind(limit as i64)
+            .push(" OFFSET ")
+            .push_bind(offset as i64);
+
+        let rows = query.build().fetch_all(&self.pool).await?;
+
+        rows.into_iter()
+            .map(|row| {
+                Ok(PendingSyncEntry {
+                    id: Uuid::parse_str(&row.get::<String, _>("id"))?,
+                    device_id: row.get("device_id"),
+                    location: row.get("location"),
+                    gateway_timestamp: row.get::<String, _>("gateway_timestamp").parse()?,
+                    sync_attempts: row.get("sync_attempts"),
+                    last_sync_attempt: row.get("last_sync_attempt"),
+                    last_sync_error: row.get("last_sync_error"),
+                })
+            })
+            .collect()
+    }
+
+    /// Reinicia los intentos y el error de lecturas pendientes para reintentarlas
+    /// Retorna la cantidad de lecturas afectadas
+    pub async fn requeue_sync(&self, ids: &[Uuid]) -> anyhow::Result<u64> {
+        self.update_pending(
+            ids,
+            "sync_attempts = 0, last_sync_attempt = NULL, last_sync_error = NULL",
+        )
+        .await
+    }
+
+    /// Marca como sincronizadas lecturas atascadas que no deben reenviarse
+    /// Retorna la cantidad de lecturas afectadas
+    pub async fn force_mark_synced(&self, ids: &[Uuid]) -> anyhow::Result<u64> {
+        self.update_pending(ids, "synced = 1, last_sync_error = NULL")
+            .await
+    }
+
+    /// Retira de la cola lecturas que ya no deben reenviarse al cloud
+    /// Retorna la cantidad de lecturas afectadas
+    pub async fn mark_local_only(&self, ids: &[Uuid]) -> anyhow::Result<u64> {
+        self.update_pending(ids, "synced = 1, forward_to_cloud = 0")
+            .await
+    }
+
+    async fn update_pending(&self, ids: &[Uuid], assignments: &str) -> anyhow::Result<u64> {
+        if ids.is_empty() {
+            return Ok(0);
+        }
+
+        let mut query = QueryBuilder::<Sqlite>::new("UPDATE sensor_readings SET ");
+        query
+            .push(assignments)
+            .push(" WHERE synced = 0 AND id IN (");
+        let mut separated = query.separated(", ");
+        for id in ids {
+            separated.push_bind(id.to_string());
+        }
+        query.push(")");
+
+        let result = query.build().execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,94 @@
+use super::Database;
+use crate::models::DeviceTwin;
+use chrono::{DateTime, Utc};
+use sqlx::Row;
+use sqlx::sqlite::SqliteRow;
+
+/// Estado deseado y reportado de los dispositivos
+impl Database {
+    /// Obtiene el twin de un dispositivo, si tiene propiedades
+    pub async fn get_device_twin(&self, device_id: &str) -> anyhow::Result<Option<DeviceTwin>> {
+        let row = sqlx::query("SELECT * FROM device_twins WHERE device_id = ?")
+            .bind(device_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(row_to_twin).transpose()
+    }
+
+    /// Combina un cambio con el estado deseado e incrementa su versión
+    pub async fn patch_twin_desired(
+        &self,
+        device_id: &str,
+        patch: &serde_json::Value,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<DeviceTwin> {
+        // json_patch sobre '{}' descarta las claves nulas también en el primer cambio
+        let row = sqlx::query(
+            r#"
+            INSERT INTO device_twins (device_id, desired_json, desired_version, desired_updated_at)
+            VALUES (?, json_patch('{}', ?), 1, ?)
+            ON CONFLICT(device_id) DO UPDATE SET
+                desired_json = json_patch(device_twins.desired_json, excluded.desired_json),
+                desired_version = device_twins.desired_version + 1,
+                desired_updated_at = excluded.desired_updated_at
+            RETURNING *
+            "#,
+        )
+        .bind(device_id)
+        .bind(patch.to_string())
+        .bind(at.to_rfc3339())
+        .fetch_one(&self.pool)
+        .await?;
+
+        row_to_twin(row)
+    }
+
+    /// Combina las propiedades reportadas por el dispositivo con las anteriores
+    pub async fn patch_twin_reported(
+        &self,
+        device_id: &str,
+        patch: &serde_json::Value,
+        at: DateTime<Utc>,
+    ) -> anyhow::Result<DeviceTwin> {
+        let row = sqlx::query(
+            r#"
+            INSERT INTO device_twins (device_id, reported_json, reported_updated_at)
+            VALUES (?, json_patch('{}', ?), ?)
+            ON CONFLICT(device_id) DO UPDATE SET
+                reported_json = json_patch(device_twins.reported_json, excluded.reported_json),
+                reported_updated_at = excluded.reported_updated_at
+            RETURNING *
+            "#,
+        )
+        .bind(device_id)
+        .bind(patch.to_string())
+        .bind(at.to_rfc3339())
+        .fetch_one(&self.pool)
+        .await?;
+
+        row_to_twin(row)
+    }
+}
+
+/// Convierte una fila de SQL a DeviceTwin
+fn row_to_twin(row: SqliteRow) -> anyhow::Result<DeviceTwin> {
+    let desired: serde_json::Value = serde_json::from_str(&row.get::<String, _>("desired_json"))?;
+    let reported: serde_json::Value = serde_json::from_str(&row.get::<String, _>("reported_json"))?;
+
+    Ok(DeviceTwin {
+        device_id: row.get("device_id"),
+        delta: DeviceTwin::compute_delta(&desired, &reported),
+        desired,
+        desired_version: row.get("desired_version"),
+        desired_updated_at: row
+            .get::<Option<String>, _>("desired_updated_at")
+            .map(|at| at.parse())
+            .transpose()?,
+        reported,
+        reported_updated_at: row
+            .get::<Option<String>, _>("reported_updated_at")
+            .map(|at| at.parse())
+            .transpose()?,
+    })
+}
@@ -0,0 +1,385 @@
+use super::Database;
+use crate::config::{Config, WriteOverload};
+use crate::models::ProcessedSensorData;
+use crate::telemetry::Telemetry;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, watch};
+
+/// Lecturas individuales a la espera de escribirse juntas en una transacción
+///
+/// Cada lectura de MQTT o HTTP se insertaba con su propia transacción (y su
+/// fsync), lo que a decenas de mensajes por segundo desgasta la SD. El búfer
+/// las acumula y una tarea las escribe cada `interval` o al juntar `max_rows`.
+///
+/// Si SQLite no da abasto (SD lenta, disco lleno) el búfer retiene hasta
+/// `capacity` lecturas y luego aplica `DB_WRITE_OVERLOAD` en lugar de crecer
+/// sin límite.
+pub struct WriteBuffer {
+    pending: Mutex<VecDeque<ProcessedSensorData>>,
+    /// Serializa los vaciados de la tarea periódica y del apagado
+    flushing: tokio::sync::Mutex<()>,
+    /// Despierta a la tarea al alcanzar `max_rows`
+    full: Notify,
+    interval: Duration,
+    max_rows: usize,
+    capacity: usize,
+    overload: WriteOverload,
+    /// Suscripciones MQTT de lecturas pausadas (`pause-subscriptions`)
+    paused: watch::Sender<bool>,
+    /// La última escritura falló por el almacenamiento; se reintenta en cada ciclo
+    failing: AtomicBool,
+    /// Tras el apagado las lecturas se insertan directamente
+    closed: AtomicBool,
+    telemetry: Arc<Telemetry>,
+}
+
+impl WriteBuffer {
+    pub fn new(config: &Config, telemetry: Arc<Telemetry>) -> Self {
+        telemetry.set_write_buffer(0, config.db_write_buffer_max);
+        Self {
+            pending: Mutex::new(VecDeque::with_capacity(config.db_write_max_rows)),
+            flushing: tokio::sync::Mutex::new(()),
+            full: Notify::new(),
+            interval: Duration::from_millis(config.db_write_flush_ms),
+            max_rows: config.db_write_max_rows,
+            capacity: config.db_write_buffer_max,
+            overload: config.db_write_overload,
+            paused: watch::Sender::new(false),
+            failing: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            telemetry,
+        }
+    }
+
+    /// Agrega una lectura; None si el búfer ya fue cerrado
+    ///
+    /// Retorna las lecturas descartadas por la saturación, que pueden incluir
+    /// la recién agregada (`pause-subscriptions` descarta la más nueva y
+    /// `drop-lowest-quality` puede elegirla).
+    fn push(&self, data: &ProcessedSensorData) -> Option<Vec<ProcessedSensorData>> {
+        // Se comprueba con el búfer bloqueado para no perder lecturas al cerrar
+        let mut pending = self.pending.lock().unwrap();
+        if self.closed.load(Ordering::Relaxed) {
+            return None;
+        }
+        pending.push_back(data.clone());
+        let shed = self.shed_excess(&mut pending);
+        if pending.len() >= self.max_rows {
+            self.full.notify_one();
+        }
+        self.update_pause(pending.len(), false);
+        Some(shed)
+    }
+
+    /// Devuelve al frente del búfer un lote que no se pudo escribir
+    /// Retorna las lecturas descartadas por la saturación
+    fn requeue(&self, readings: Vec<ProcessedSensorData>) -> Vec<ProcessedSensorData> {
+        let mut pending = self.pending.lock().unwrap();
+        for data in readings.into_iter().rev() {
+            pending.push_front(data);
+        }
+        self.shed_excess(&mut pending)
+    }
+
+    /// Descarta lecturas según `DB_WRITE_OVERLOAD` hasta volver a la capacidad
+    /// y las retorna
+    fn shed_excess(&self, pending: &mut VecDeque<ProcessedSensorData>) -> Vec<ProcessedSensorData> {
+        let mut discarded = Vec::new();
+        while pending.len() > self.capacity {
+            let (reason, shed) = match self.overload {
+                WriteOverload::DropOldest => ("oldest", pending.pop_front()),
+                WriteOverload::DropLowestQuality => {
+                    let lowest = pending.iter().map(|data| data.quality.score).min();
+                    let position = pending
+                        .iter()
+                        .position(|data| Some(data.quality.score) == lowest);
+                    (
+                        "lowest_quality",
+                        position.and_then(|position| pending.remove(position)),
+                    )
+                }
+                // Con las suscripciones pausadas se rechaza lo que siga llegando
+                WriteOverload::PauseSubscriptions => ("newest", pending.pop_back()),
+            };
+            let Some(shed) = shed else {
+                break;
+            };
+
+            let total = self.telemetry.reading_shed(reason);
+            // Se avisa al llenarse y luego cada 1000 descartes
+            if total % 1000 == 1 {
+                tracing::warn!(
+                    device_id = %shed.header.device_id,
+                    reason,
+                    shed = total,
+                    capacity = self.capacity,
+                    "Búfer de escritura lleno: se descartan lecturas"
+                );
+            }
+            discarded.push(shed);
+        }
+        self.telemetry
+            .set_write_buffer(pending.len(), self.capacity);
+        discarded
+    }
+
+    /// Pausa las suscripciones al llegar al 75 % de la capacidad y las
+    /// reanuda al bajar de la mitad, solo tras un vaciado (`flushed`): mientras
+    /// se escribe un lote el búfer parece vacío aunque el lote pueda volver
+    fn update_pause(&self, depth: usize, flushed: bool) {
+        if self.overload != WriteOverload::PauseSubscriptions {
+            return;
+        }
+
+        let paused = *self.paused.borrow();
+        if !paused && depth >= self.capacity * 3 / 4 {
+            tracing::warn!(
+                depth,
+                capacity = self.capacity,
+                "Almacenamiento saturado: se pausan las suscripciones MQTT de lecturas"
+            );
+        } else if paused && flushed && depth <= self.capacity / 2 {
+            tracing::info!(depth, "Se reanudan las suscripciones MQTT de lecturas");
+        } else {
+            return;
+        }
+        self.paused.send_replace(!paused);
+        self.telemetry.set_ingest_paused(!paused);
+    }
+
+    /// Retira las lecturas que cumplen `purged`; devuelve cuántas retiró
+    fn discard(&self, purged: impl Fn(&ProcessedSensorData) -> bool) -> usize {
+        let mut pending = self.pending.lock().unwrap();
+        let before = pending.len();
+        pending.retain(|data| !purged(data));
+        self.telemetry
+            .set_write_buffer(pending.len(), self.capacity);
+        before - pending.len()
+    }
+
+    fn take(&self) -> Vec<ProcessedSensorData> {
+        let mut pending = self.pending.lock().unwrap();
+        self.telemetry.set_write_buffer(0, self.capacity);
+        pending.drain(..).collect()
+    }
+
+    fn close(&self) {
+        let _pending = self.pending.lock().unwrap();
+        self.closed.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Fallos del almacenamiento (disco lleno, E/S, base ocupada o de solo lectura)
+/// en los que reintentar lectura por lectura no ayuda
+fn is_storage_error(error: &anyhow::Error) -> bool {
+    match error.downcast_ref::<sqlx::Error>() {
+        // Códigos primarios de SQLite: BUSY, LOCKED, READONLY, IOERR, FULL, CANTOPEN
+        Some(sqlx::Error::Database(e)) => e
+            .code()
+            .and_then(|code| code.parse::<i32>().ok())
+            .is_some_and(|code| matches!(code & 0xff, 5 | 6 | 8 | 10 | 13 | 14)),
+        Some(sqlx::Error::Io(_) | sqlx::Error::PoolTimedOut) => true,
+        _ => false,
+    }
+}
+
+impl Database {
+    /// Acumula las lecturas individuales y las escribe en lotes
+    /// (`DB_WRITE_*`); debe llamarse dentro del runtime
+    pub fn with_write_buffer(mut self, config: &Config) -> Self {
+        if config.db_write_flush_ms == 0 {
+            return self;
+        }
+
+        let buffer = Arc::new(WriteBuffer::new(config, self.telemetry.clone()));
+        self.writes = Some(buffer.clone());
+        tokio::spawn(self.clone().run_write_buffer(buffer));
+
+        tracing::info!(
+            flush_ms = config.db_write_flush_ms,
+            max_rows = config.db_write_max_rows,
+            buffer_max = config.db_write_buffer_max,
+            overload = ?config.db_write_overload,
+            "Escrituras de lecturas agrupadas"
+        );
+        self
+    }
+
+    /// Estado de pausa de las suscripciones MQTT de lecturas; None si la
+    /// política de saturación no es `pause-subscriptions`
+    pub fn ingest_pause(&self) -> Option<watch::Receiver<bool>> {
+        self.writes
+            .as_ref()
+            .filter(|writes| writes.overload == WriteOverload::PauseSubscriptions)
+            .map(|writes| writes.paused.subscribe())
+    }
+
+    /// Agrega la lectura al búfer de escritura; false si no hay búfer activo
+    ///
+    /// Una lectura descartada por la saturación del búfer cuenta como manejada:
+    /// no se inserta directamente, igual que las que desplaza.
+    pub(super) fn buffer_reading(&self, data: &ProcessedSensorData) -> bool {
+        let Some(writes) = &self.writes else {
+            return false;
+        };
+        let Some(shed) = writes.push(data) else {
+            return false;
+        };
+
+        // Consultas y duplicados ven la lectura aunque aún no esté en SQLite
+        if !shed.iter().any(|discarded| discarded.id == data.id) {
+            let data = std::slice::from_ref(data);
+            self.cache.record(data);
+            self.dedup.record(data);
+        }
+        self.forget_shed(&shed);
+        true
+    }
+
+    /// Retira de la caché de últimas lecturas y de la deduplicación las
+    /// lecturas descartadas: no llegarán a SQLite, y un reintento del nodo
+    /// debe poder almacenarse
+    fn forget_shed(&self, shed: &[ProcessedSensorData]) {
+        if shed.is_empty() {
+            return;
+        }
+        self.cache.forget(shed);
+        self.dedup.forget(shed);
+    }
+
+    /// Descarta del búfer las lecturas que una purga eliminó de SQLite
+    ///
+    /// Solo quedan si el último vaciado falló; de lo contrario se escribirían
+    /// después de la purga y las lecturas eliminadas volverían a aparecer.
+    pub(super) fn discard_buffered(&self, purged: impl Fn(&ProcessedSensorData) -> bool) -> u64 {
+        self.writes
+            .as_ref()
+            .map_or(0, |writes| writes.discard(purged) as u64)
+    }
+
+    /// Lecturas del búfer que entrarán en la cola de sincronización
+    pub(super) fn buffered_for_sync(&self) -> i64 {
+        self.writes.as_ref().map_or(0, |writes| {
+            writes
+                .pending
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|data| data.metadata.forward_to_cloud)
+                .count() as i64
+        })
+    }
+
+    async fn run_write_buffer(self, buffer: Arc<WriteBuffer>) {
+        loop {
+            // Mientras el almacenamiento falla solo se reintenta cada intervalo
+            let failing = buffer.failing.load(Ordering::Relaxed);
+            tokio::select! {
+                _ = tokio::time::sleep(buffer.interval) => {}
+                _ = buffer.full.notified(), if !failing => {}
+            }
+            if buffer.closed.load(Ordering::Relaxed) {
+                break;
+            }
+            self.flush_writes().await;
+        }
+    }
+
+    /// Escribe las lecturas acumuladas; devuelve cuántas se almacenaron
+    pub async fn flush_writes(&self) -> usize {
+        let Some(buffer) = &self.writes else {
+            return 0;
+        };
+        let _flushing = buffer.flushing.lock().await;
+        let readings = buffer.take();
+        if readings.is_empty() {
+            return 0;
+        }
+
+        let started = Instant::now();
+        let stored = match self.write_batch(&readings).await {
+            Ok(()) => {
+                self.telemetry.observe_db_insert(started.elapsed());
+                if buffer.failing.swap(false, Ordering::Relaxed) {
+                    tracing::info!(
+                        readings = readings.len(),
+                        "Escritura en SQLite restablecida, lecturas retenidas almacenadas"
+                    );
+                } else {
+                    tracing::debug!(readings = readings.len(), "Lecturas agrupadas almacenadas");
+                }
+                readings.len()
+            }
+            Err(e) if is_storage_error(&e) => {
+                // El lote vuelve al búfer; si se llena, decide DB_WRITE_OVERLOAD
+                self.telemetry.db_error("insert");
+                if !buffer.failing.swap(true, Ordering::Relaxed) {
+                    tracing::error!(
+                        readings = readings.len(),
+                        "No se pudo escribir en SQLite, las lecturas se retienen en memoria: {}",
+                        e
+                    );
+                }
+                let shed = buffer.requeue(readings);
+                self.forget_shed(&shed);
+                0
+            }
+            Err(e) => {
+                // Una lectura inválida no debe arrastrar al resto del lote
+                tracing::warn!(
+                    readings = readings.len(),
+                    "Error al escribir el lote de lecturas, se reintentan una a una: {}",
+                    e
+                );
+                let mut stored = 0;
+                for data in &readings {
+                    match self.write_batch(std::slice::from_ref(data)).await {
+                        Ok(()) => stored += 1,
+                        Err(e) => {
+                            self.telemetry.db_error("insert");
+                            tracing::error!(
+                                device_id = %data.header.device_id,
+                                reading_id = %data.id,
+                                "Lectura descartada al no poder almacenarse: {}",
+                                e
+                            );
+                        }
+                    }
+                }
+                stored
+            }
+        };
+
+        buffer.update_pause(buffer.pending.lock().unwrap().len(), true);
+        stored
+    }
+
+    /// Cierra el búfer y escribe lo pendiente; se llama al apagar el gateway
+    /// Retorna `false` si quedaron lecturas sin escribir
+    pub async fn close_writes(&self) -> bool {
+        let Some(buffer) = &self.writes else {
+            return true;
+        };
+        buffer.close();
+        buffer.full.notify_one();
+
+        let stored = self.flush_writes().await;
+        if stored > 0 {
+            tracing::info!(
+                readings = stored,
+                "Lecturas pendientes almacenadas antes del apagado"
+            );
+        }
+        let lost = buffer.pending.lock().unwrap().len();
+        if lost > 0 {
+            tracing::error!(
+                readings = lost,
+                "Lecturas perdidas al apagar: no se pudieron escribir en SQLite"
+            );
+        }
+        lost == 0
+    }
+}
@@ -24,6 +24,21 @@ pub enum AppError {
     #[error("Recurso no encontrado: {0}")]
     NotFound(String),
 
+    #[error("No autorizado: {0}")]
+    Unauthorized(String),
+
+    #[error("Prohibido: {0}")]
+    Forbidden(String),
+
+    #[error("Conflicto: {0}")]
+    Conflict(String),
+
+    #[error("Demasiadas solicitudes: {0}")]
+    TooManyRequests(String),
+
+    #[error("Servicio no disponible: {0}")]
+    ServiceUnavailable(String),
+
     #[error("Error de configuración: {0}")]
     ConfigError(String),
 }
@@ -68,6 +83,26 @@ impl IntoResponse for AppError {
                 tracing::warn!("Recurso no encontrado: {}", msg);
                 (StatusCode::NOT_FOUND, msg)
             }
+            AppError::Unauthorized(msg) => {
+                tracing::warn!("Solicitud no autorizada: {}", msg);
+                (StatusCode::UNAUTHORIZED, msg)
+            }
+            AppError::Forbidden(msg) => {
+                tracing::warn!("Solicitud rechazada: {}", msg);
+                (StatusCode::FORBIDDEN, msg)
+            }
+            AppError::Conflict(msg) => {
+                tracing::warn!("Conflicto: {}", msg);
+                (StatusCode::CONFLICT, msg)
+            }
+            AppError::TooManyRequests(msg) => {
+                tracing::warn!("Solicitud limitada: {}", msg);
+                (StatusCode::TOO_MANY_REQUESTS, msg)
+            }
+            AppError::ServiceUnavailable(msg) => {
+                tracing::warn!("Servicio no disponible: {}", msg);
+                (StatusCode::SERVICE_UNAVAILABLE, msg)
+            }
             AppError::ConfigError(msg) => {
                 tracing::error!("Error de configuración: {}", msg);
                 (
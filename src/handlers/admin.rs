@@ -0,0 +1,175 @@
+use crate::{database::AuditFilter, error::AppError, startup::state::AppState};
+use axum::{
+    Json,
+    body::Body,
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use tokio_util::io::ReaderStream;
+
+#[derive(Debug, Deserialize)]
+pub struct BackupQuery {
+    /// Si es true, el snapshot se descarga en la respuesta en lugar de guardarse
+    #[serde(default)]
+    pub download: bool,
+}
+
+/// Handler para generar un respaldo de la base de datos
+/// POST /api/v1/admin/backup?download=false
+///
+/// Genera un snapshot consistente sin detener la ingesta.
+/// Con `download=true` el archivo se transmite como descarga.
+pub async fn create_backup(
+    State(state): State<AppState>,
+    Query(params): Query<BackupQuery>,
+) -> Result<Response, AppError> {
+    if !params.download {
+        let path = state.backup.create_snapshot().await?;
+        let size = tokio::fs::metadata(&path)
+            .await
+            .map_err(|e| AppError::InternalError(e.to_string()))?
+            .len();
+
+        return Ok(Json(json!({
+            "status": "success",
+            "message": "Respaldo generado correctamente",
+            "data": {
+                "path": path.display().to_string(),
+                "size_bytes": size,
+            }
+        }))
+        .into_response());
+    }
+
+    let path = state.backup.create_download_snapshot().await?;
+    let file = tokio::fs::File::open(&path)
+        .await
+        .map_err(|e| AppError::InternalError(e.to_string()))?;
+
+    // El descriptor abierto mantiene el contenido disponible tras borrar el archivo temporal
+    if let Err(e) = tokio::fs::remove_file(&path).await {
+        tracing::warn!("No se pudo eliminar el snapshot temporal: {}", e);
+    }
+
+    let file_name = format!(
+        "{}-{}.db",
+        state.config.gateway_id,
+        chrono::Utc::now().format("%Y%m%d-%H%M%S")
+    );
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/vnd.sqlite3".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(file)),
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PurgeQuery {
+    pub device_id: Option<String>,
+    /// Inicio del periodo a eliminar (inclusivo)
+    pub from: Option<DateTime<Utc>>,
+    /// Fin del periodo a eliminar (exclusivo)
+    pub before: Option<DateTime<Utc>>,
+    /// Solo cuenta las lecturas que se eliminarían
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Handler para eliminar lecturas
+/// DELETE /api/v1/data?device_id=&from=&before=&dry_run=true
+///
+/// Purga las lecturas de dispositivos retirados o de ingestas erróneas.
+/// Exige ADMIN_API_TOKEN y al menos `device_id` o `before` para evitar
+/// borrar toda la base por accidente.
+pub async fn purge_data(
+    State(state): State<AppState>,
+    Query(params): Query<PurgeQuery>,
+) -> Result<Json<Value>, AppError> {
+    if params.device_id.is_none() && params.before.is_none() {
+        return Err(AppError::ValidationError(
+            "Se requiere 'device_id' o 'before' para eliminar lecturas".to_string(),
+        ));
+    }
+
+    if let (Some(from), Some(before)) = (params.from, params.before)
+        && from >= before
+    {
+        return Err(AppError::ValidationError(
+            "El parámetro 'from' debe ser anterior a 'before'".to_string(),
+        ));
+    }
+
+    let count = state
+        .db
+        .purge_readings(
+            params.device_id.as_deref(),
+            params.from,
+            params.before,
+            params.dry_run,
+        )
+        .await?;
+
+    if !params.dry_run {
+        tracing::warn!(
+            device_id = ?params.device_id,
+            from = ?params.from,
+            before = ?params.before,
+            deleted = count,
+            "Lecturas eliminadas por solicitud de administración"
+        );
+    }
+
+    Ok(Json(json!({
+        "status": "success",
+        "dry_run": params.dry_run,
+        "device_id": params.device_id,
+        "from": params.from,
+        "before": params.before,
+        (if params.dry_run { "matched" } else { "deleted" }): count,
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub principal: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default = "default_audit_limit")]
+    pub limit: usize,
+}
+
+fn default_audit_limit() -> usize {
+    100
+}
+
+/// Handler para consultar el registro de auditoría
+/// GET /api/v1/admin/audit?principal=&from=&to=&limit=100
+pub async fn list_audit(
+    State(state): State<AppState>,
+    Query(params): Query<AuditQuery>,
+) -> Result<Json<Value>, AppError> {
+    let filter = AuditFilter {
+        principal: params.principal,
+        from: params.from,
+        to: params.to,
+    };
+
+    let entries = state.db.list_audit(&filter, params.limit).await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "count": entries.len(),
+        "data": entries,
+    })))
+}
@@ -0,0 +1,151 @@
+use super::query::TagQuery;
+use crate::{
+    database::AlertFilter,
+    error::AppError,
+    models::{AlertAckInput, AlertSilenceInput},
+    startup::{auth::Principal, state::AppState},
+};
+use axum::{
+    Extension, Json,
+    extract::{Path, Query, State},
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use validator::Validate;
+
+#[derive(Debug, Deserialize)]
+pub struct AlertsQuery {
+    pub device_id: Option<String>,
+    #[serde(flatten)]
+    pub tags: TagQuery,
+    pub rule_id: Option<String>,
+    /// Solo alertas sin resolver
+    #[serde(default)]
+    pub active: bool,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    #[serde(default = "default_alerts_limit")]
+    pub limit: usize,
+}
+
+fn default_alerts_limit() -> usize {
+    100
+}
+
+/// Handler para consultar el historial de alertas
+/// GET /api/v1/alerts?device_id=&tags=&rule_id=&active=true&from=&to=&limit=100
+pub async fn list_alerts(
+    State(state): State<AppState>,
+    Query(params): Query<AlertsQuery>,
+) -> Result<Json<Value>, AppError> {
+    let filter = AlertFilter {
+        device_id: params.device_id,
+        tags: params.tags.to_tags()?,
+        rule_id: params.rule_id,
+        active_only: params.active,
+        from: params.from,
+        to: params.to,
+    };
+
+    let alerts = state.db.list_alerts(&filter, params.limit).await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "count": alerts.len(),
+        "data": alerts,
+    })))
+}
+
+/// Handler para reconocer una alerta
+/// POST /api/v1/alerts/{id}/ack
+///
+/// Registra quién la reconoció (el nombre de su token) y una nota opcional
+pub async fn ack_alert(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Extension(principal): Extension<Principal>,
+    body: Option<Json<AlertAckInput>>,
+) -> Result<Json<Value>, AppError> {
+    let input = body.map(|Json(input)| input).unwrap_or_default();
+    input
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let Some(alert) = state.db.get_alert(id).await? else {
+        return Err(AppError::NotFound(format!("Alerta {} no existe", id)));
+    };
+    if let Some(acked_by) = alert.acked_by {
+        return Err(AppError::Conflict(format!(
+            "La alerta {} ya fue reconocida por {}",
+            id, acked_by
+        )));
+    }
+
+    let alert = state
+        .alerting
+        .ack(id, &principal.name, input.note.as_deref())
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Alerta {} no existe", id)))?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Alerta reconocida",
+        "data": alert,
+    })))
+}
+
+/// Handler para silenciar las notificaciones de un dispositivo y/o regla
+/// POST /api/v1/alerts/silence
+///
+/// Las alertas se siguen registrando en el historial; solo se omiten sus notificaciones
+pub async fn create_silence(
+    State(state): State<AppState>,
+    Extension(principal): Extension<Principal>,
+    Json(input): Json<AlertSilenceInput>,
+) -> Result<Json<Value>, AppError> {
+    input
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+    if input.device_id.is_none() && input.rule_id.is_none() {
+        return Err(AppError::ValidationError(
+            "Indique device_id, rule_id o ambos".to_string(),
+        ));
+    }
+
+    let silence = state.alerting.silence(&input, &principal.name).await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Notificaciones silenciadas",
+        "data": silence,
+    })))
+}
+
+/// Handler para listar los silencios vigentes
+/// GET /api/v1/alerts/silences
+pub async fn list_silences(State(state): State<AppState>) -> Json<Value> {
+    let silences = state.alerting.active_silences();
+
+    Json(json!({
+        "status": "success",
+        "count": silences.len(),
+        "data": silences,
+    }))
+}
+
+/// Handler para levantar un silencio antes de su vencimiento
+/// DELETE /api/v1/alerts/silences/{id}
+pub async fn delete_silence(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, AppError> {
+    if !state.alerting.lift_silence(id).await? {
+        return Err(AppError::NotFound(format!("Silencio {} no existe", id)));
+    }
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Silencio levantado",
+    })))
+}
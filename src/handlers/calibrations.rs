@@ -0,0 +1,139 @@
+use crate::{error::AppError, models::CalibrationInput, startup::state::AppState};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use validator::Validate;
+
+#[derive(Debug, Deserialize)]
+pub struct CalibrationsQuery {
+    pub device_id: Option<String>,
+}
+
+/// Valida la entrada de una calibración
+pub(super) fn validate_input(input: &CalibrationInput) -> Result<(), AppError> {
+    input
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    if !(input.offset.is_finite() && input.gain.is_finite()) || input.gain == 0.0 {
+        return Err(AppError::ValidationError(
+            "offset y gain deben ser números finitos y gain distinto de 0".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// Recarga las calibraciones en el procesador edge tras un cambio
+pub(super) async fn reload_calibrations(state: &AppState) -> Result<(), AppError> {
+    state
+        .edge_processor
+        .set_calibrations(state.db.list_calibrations(None).await?);
+    Ok(())
+}
+
+/// Handler para listar calibraciones
+/// GET /api/v1/admin/calibrations?device_id=XXX
+pub async fn list_calibrations(
+    State(state): State<AppState>,
+    Query(params): Query<CalibrationsQuery>,
+) -> Result<Json<Value>, AppError> {
+    let calibrations = state
+        .db
+        .list_calibrations(params.device_id.as_deref())
+        .await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "count": calibrations.len(),
+        "data": calibrations,
+    })))
+}
+
+/// Handler para obtener una calibración
+/// GET /api/v1/admin/calibrations/{id}
+pub async fn get_calibration(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, AppError> {
+    let calibration = state
+        .db
+        .get_calibration(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Calibración {} no existe", id)))?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": calibration,
+    })))
+}
+
+/// Handler para crear una calibración
+/// POST /api/v1/admin/calibrations
+pub async fn create_calibration(
+    State(state): State<AppState>,
+    Json(input): Json<CalibrationInput>,
+) -> Result<Json<Value>, AppError> {
+    validate_input(&input)?;
+
+    let id = state.db.insert_calibration(&input).await?;
+    reload_calibrations(&state).await?;
+
+    tracing::info!(
+        id,
+        device_id = %input.device_id,
+        measurement = %input.measurement,
+        "Calibración creada"
+    );
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Calibración creada",
+        "data": state.db.get_calibration(id).await?,
+    })))
+}
+
+/// Handler para actualizar una calibración
+/// PUT /api/v1/admin/calibrations/{id}
+pub async fn update_calibration(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(input): Json<CalibrationInput>,
+) -> Result<Json<Value>, AppError> {
+    validate_input(&input)?;
+
+    if !state.db.update_calibration(id, &input).await? {
+        return Err(AppError::NotFound(format!("Calibración {} no existe", id)));
+    }
+    reload_calibrations(&state).await?;
+
+    tracing::info!(id, "Calibración actualizada");
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Calibración actualizada",
+        "data": state.db.get_calibration(id).await?,
+    })))
+}
+
+/// Handler para eliminar una calibración
+/// DELETE /api/v1/admin/calibrations/{id}
+pub async fn delete_calibration(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, AppError> {
+    if !state.db.delete_calibration(id).await? {
+        return Err(AppError::NotFound(format!("Calibración {} no existe", id)));
+    }
+    reload_calibrations(&state).await?;
+
+    tracing::info!(id, "Calibración eliminada");
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Calibración eliminada",
+    })))
+}
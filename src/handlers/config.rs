@@ -0,0 +1,65 @@
+use crate::{
+    error::AppError, services::runtime_config::RuntimeSettingsPatch, startup::state::AppState,
+};
+use axum::{Json, extract::State};
+use serde_json::{Value, json};
+use validator::Validate;
+
+/// Handler para consultar la configuración modificable en tiempo de ejecución
+/// GET /api/v1/admin/config
+pub async fn get_config(State(state): State<AppState>) -> Json<Value> {
+    let runtime = &state.runtime_config;
+
+    Json(json!({
+        "status": "success",
+        "data": runtime.current(),
+        "defaults": runtime.defaults(),
+        "overrides": runtime.overrides().await,
+    }))
+}
+
+/// Handler para modificar la configuración en tiempo de ejecución
+/// PATCH /api/v1/admin/config
+///
+/// Los cambios se aplican en vivo y se persisten para sobrevivir reinicios
+pub async fn patch_config(
+    State(state): State<AppState>,
+    Json(patch): Json<RuntimeSettingsPatch>,
+) -> Result<Json<Value>, AppError> {
+    patch
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let settings = state.runtime_config.update(&patch).await?;
+
+    tracing::info!(?patch, "Configuración actualizada en tiempo de ejecución");
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Configuración actualizada",
+        "data": settings,
+    })))
+}
+
+/// Handler para recargar el archivo de configuración sin reiniciar
+/// POST /api/v1/admin/reload
+///
+/// Equivale a enviar SIGHUP al proceso
+pub async fn reload_config(State(state): State<AppState>) -> Result<Json<Value>, AppError> {
+    let report = state
+        .reload_configuration()
+        .await
+        .map_err(|e| AppError::ConfigError(e.to_string()))?;
+
+    let message = if report.restart_required.is_empty() {
+        "Configuración recargada"
+    } else {
+        "Configuración recargada; algunos cambios requieren reiniciar el gateway"
+    };
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": message,
+        "data": report,
+    })))
+}
@@ -0,0 +1,43 @@
+use axum::extract::Path;
+use axum::http::{StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use rust_embed::Embed;
+
+/// Archivos del dashboard incrustados en el binario: no requiere archivos
+/// adicionales en el despliegue
+#[derive(Embed)]
+#[folder = "dashboard/"]
+struct Assets;
+
+/// Cache corto para que una actualización del gateway se vea al recargar
+const CACHE_CONTROL: &str = "no-cache";
+
+/// Handler del dashboard web local
+/// GET /
+///
+/// Página única con lecturas en vivo (WebSocket), dispositivos, cola de
+/// sincronización, anomalías y gráfico de las últimas 24 horas
+pub async fn index() -> Response {
+    serve("index.html")
+}
+
+/// GET /dashboard/{*path}
+pub async fn asset(Path(path): Path<String>) -> Response {
+    serve(&path)
+}
+
+/// Responde el archivo incrustado con el tipo MIME según su extensión
+fn serve(path: &str) -> Response {
+    let Some(file) = Assets::get(path) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+
+    (
+        [
+            (header::CONTENT_TYPE, file.metadata.mimetype().to_string()),
+            (header::CACHE_CONTROL, CACHE_CONTROL.to_string()),
+        ],
+        file.data,
+    )
+        .into_response()
+}
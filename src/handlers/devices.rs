@@ -0,0 +1,746 @@
+use super::calibrations::{reload_calibrations, validate_input};
+use super::query::TagQuery;
+use crate::{
+    database::MetricFilter,
+    error::AppError,
+    models::{
+        CalibrationInput, DataDisposition, DeviceDecommissionInput, DevicePatch,
+        DeviceProvisionInput, DeviceTokenRotateInput, DeviceTwinPatch,
+    },
+    services::export::ExportService,
+    startup::{auth, state::AppState},
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::HashSet;
+use std::path::PathBuf;
+use validator::Validate;
+
+/// Los metadatos se combinan con `json_patch`, por lo que deben ser un objeto
+fn validate_metadata(metadata: Option<&Value>) -> Result<(), AppError> {
+    if metadata.is_some_and(|m| !m.is_object()) {
+        return Err(AppError::ValidationError(
+            "metadata debe ser un objeto JSON".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/// Máximo de etiquetas por dispositivo
+const MAX_DEVICE_TAGS: usize = 20;
+
+/// Las etiquetas se usan en listas separadas por coma y en rutas
+pub(super) fn validate_tag(tag: &str) -> Result<(), AppError> {
+    let valid = (1..=50).contains(&tag.len())
+        && tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+
+    if !valid {
+        return Err(AppError::ValidationError(format!(
+            "Etiqueta inválida '{}': de 1 a 50 letras, números, '-', '_', '.' o ':'",
+            tag
+        )));
+    }
+    Ok(())
+}
+
+/// Valida las etiquetas y descarta las repetidas conservando el orden
+fn normalize_tags(tags: &mut Vec<String>) -> Result<(), AppError> {
+    let mut seen = HashSet::new();
+    tags.retain(|tag| seen.insert(tag.clone()));
+
+    if tags.len() > MAX_DEVICE_TAGS {
+        return Err(AppError::ValidationError(format!(
+            "Un dispositivo admite como máximo {} etiquetas",
+            MAX_DEVICE_TAGS
+        )));
+    }
+    tags.iter().try_for_each(|tag| validate_tag(tag))
+}
+
+/// Recarga las etiquetas en el procesador edge tras un cambio
+async fn reload_device_tags(state: &AppState) -> Result<(), AppError> {
+    state
+        .edge_processor
+        .set_device_tags(state.db.list_device_tags().await?);
+    Ok(())
+}
+
+/// Verifica que el perfil de tipo de dispositivo exista
+pub(super) async fn ensure_profile(
+    state: &AppState,
+    device_type: Option<&str>,
+) -> Result<(), AppError> {
+    if let Some(device_type) = device_type
+        && state.db.get_device_profile(device_type).await?.is_none()
+    {
+        return Err(AppError::ValidationError(format!(
+            "Perfil de dispositivo {} no existe",
+            device_type
+        )));
+    }
+    Ok(())
+}
+
+/// Handler para listar los dispositivos del registro
+/// GET /api/v1/devices?tags=greenhouse-3,battery-powered
+pub async fn list_devices(
+    State(state): State<AppState>,
+    Query(params): Query<TagQuery>,
+) -> Result<Json<Value>, AppError> {
+    let tags = params.to_tags()?;
+    let devices: Vec<_> = state
+        .db
+        .list_devices()
+        .await?
+        .into_iter()
+        .filter(|device| tags.is_empty() || device.tags.iter().any(|tag| tags.contains(tag)))
+        .collect();
+
+    Ok(Json(json!({
+        "status": "success",
+        "count": devices.len(),
+        "data": devices,
+    })))
+}
+
+/// Handler para el detalle de un dispositivo
+/// GET /api/v1/devices/{id}
+///
+/// Incluye la última lectura y el estado de batería y señal si se conoce
+pub async fn get_device(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let device = state
+        .db
+        .get_device(&device_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Dispositivo {} no existe", device_id)))?;
+
+    let latest_reading = state
+        .db
+        .get_recent_readings(&device_id, 1)
+        .await?
+        .into_iter()
+        .next();
+
+    let power = state
+        .edge_processor
+        .power_report()
+        .into_iter()
+        .find(|status| status.device_id == device_id);
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": {
+            "device": device,
+            "latest_reading": latest_reading,
+            "power": power,
+        },
+    })))
+}
+
+/// Handler para las estadísticas de ingesta MQTT de un dispositivo
+/// GET /api/v1/devices/{id}/stats
+///
+/// Tasa de mensajes, bytes recibidos y antigüedad del último mensaje desde el
+/// arranque del gateway, uso de las cuotas del dispositivo y su última
+/// sincronización horaria
+pub async fn get_device_stats(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let stats = state.telemetry.device_stats(&device_id);
+    if stats.messages_total == 0 && state.db.get_device(&device_id).await?.is_none() {
+        return Err(AppError::NotFound(format!(
+            "Dispositivo {} no existe",
+            device_id
+        )));
+    }
+
+    let quota = state.edge_processor.quotas().status(&device_id);
+    let time_sync = state.edge_processor.last_time_sync(&device_id);
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": stats,
+        "quota": quota,
+        "time_sync": time_sync,
+    })))
+}
+
+/// Handler para el uso de las cuotas de los dispositivos
+/// GET /api/v1/quotas?exceeded_only=false
+pub async fn list_quotas(
+    State(state): State<AppState>,
+    Query(params): Query<QuotaQuery>,
+) -> Json<Value> {
+    let quotas: Vec<_> = state
+        .edge_processor
+        .quotas()
+        .statuses()
+        .into_iter()
+        .filter(|quota| !params.exceeded_only || quota.exceeded)
+        .collect();
+
+    Json(json!({
+        "status": "success",
+        "count": quotas.len(),
+        "data": quotas,
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct QuotaQuery {
+    #[serde(default)]
+    pub exceeded_only: bool,
+}
+
+/// Handler para provisionar un dispositivo antes de que envíe datos
+/// POST /api/v1/devices
+///
+/// Registra ubicación esperada, perfil y calibraciones iniciales, y emite el
+/// secreto del dispositivo, que solo se muestra en esta respuesta
+pub async fn provision_device(
+    State(state): State<AppState>,
+    Json(mut input): Json<DeviceProvisionInput>,
+) -> Result<(StatusCode, Json<Value>), AppError> {
+    input
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+    validate_metadata(input.metadata.as_ref())?;
+    normalize_tags(&mut input.tags)?;
+    ensure_profile(&state, input.device_type.as_deref()).await?;
+
+    let calibrations: Vec<CalibrationInput> = input
+        .calibrations
+        .iter()
+        .map(|calibration| CalibrationInput {
+            device_id: input.device_id.clone(),
+            measurement: calibration.measurement.clone(),
+            offset: calibration.offset,
+            gain: calibration.gain,
+            valid_from: None,
+        })
+        .collect();
+    for calibration in &calibrations {
+        validate_input(calibration)?;
+    }
+
+    let (secret, secret_hash) = auth::issue_device_secret();
+    if !state
+        .db
+        .provision_device(&input, &calibrations, &secret_hash, Utc::now())
+        .await?
+    {
+        return Err(AppError::Conflict(format!(
+            "Dispositivo {} ya está provisionado",
+            input.device_id
+        )));
+    }
+    if !calibrations.is_empty() {
+        reload_calibrations(&state).await?;
+    }
+    if !input.tags.is_empty() {
+        reload_device_tags(&state).await?;
+    }
+    // Provisionar un dispositivo pendiente también lo aprueba
+    let released = release_quarantine(&state, &input.device_id).await?;
+
+    tracing::info!(
+        device_id = %input.device_id,
+        location = %input.location,
+        calibrations = calibrations.len(),
+        released,
+        "Dispositivo provisionado"
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "status": "success",
+            "message": "Dispositivo provisionado",
+            "data": state.db.get_device(&input.device_id).await?,
+            "credentials": {
+                "device_secret": secret,
+            },
+        })),
+    ))
+}
+
+/// Procesa y almacena las lecturas en cuarentena de un dispositivo aprobado
+async fn release_quarantine(state: &AppState, device_id: &str) -> Result<usize, AppError> {
+    let readings = state.db.list_quarantined(device_id).await?;
+    if readings.is_empty() {
+        return Ok(0);
+    }
+
+    let (readings, _) = state.db.filter_duplicates(readings).await?;
+    let processed = state.edge_processor.process_batch(readings).await;
+    state.db.insert_batch(&processed).await?;
+    state.db.delete_quarantined(device_id).await?;
+
+    Ok(processed.len())
+}
+
+/// Handler para aprobar un dispositivo desconocido
+/// POST /api/v1/devices/{id}/approve
+///
+/// Sus lecturas en cuarentena se procesan y almacenan como si acabaran de
+/// llegar, y las siguientes se procesan normalmente
+pub async fn approve_device(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    if !state.db.approve_device(&device_id).await? {
+        return Err(match state.db.get_device(&device_id).await? {
+            Some(_) => AppError::Conflict(format!(
+                "Dispositivo {} no está pendiente de aprobación",
+                device_id
+            )),
+            None => AppError::NotFound(format!("Dispositivo {} no existe", device_id)),
+        });
+    }
+
+    let released = release_quarantine(&state, &device_id).await?;
+    tracing::info!(device_id = %device_id, released, "Dispositivo aprobado");
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Dispositivo aprobado",
+        "data": {
+            "device_id": device_id,
+            "released_readings": released,
+        },
+    })))
+}
+
+/// Handler para rotar el token de un dispositivo
+/// POST /api/v1/devices/{id}/token
+///
+/// Emite un secreto nuevo, que solo se muestra en esta respuesta. El anterior
+/// sigue siendo válido durante `DEVICE_TOKEN_GRACE_SECS` para que el nodo
+/// adopte el nuevo sin perder lecturas, salvo con `{"revoke_previous": true}`.
+/// También emite el primer token de un dispositivo registrado por tráfico.
+pub async fn rotate_device_token(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    input: Option<Json<DeviceTokenRotateInput>>,
+) -> Result<Json<Value>, AppError> {
+    let Json(input) = input.unwrap_or_default();
+    let device = state
+        .db
+        .get_device(&device_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Dispositivo {} no existe", device_id)))?;
+
+    let grace = state.config.device_token_grace_secs;
+    let previous_valid_until = (device.has_credentials && !input.revoke_previous && grace > 0)
+        .then(|| Utc::now() + chrono::Duration::seconds(grace as i64));
+
+    let (secret, secret_hash) = auth::issue_device_secret();
+    if !state
+        .db
+        .rotate_device_credential(&device_id, &secret_hash, previous_valid_until)
+        .await?
+    {
+        return Err(AppError::Conflict(format!(
+            "Dispositivo {} dado de baja",
+            device_id
+        )));
+    }
+
+    tracing::info!(
+        device_id = %device_id,
+        previous_valid_until = ?previous_valid_until,
+        "Token de dispositivo rotado"
+    );
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Token de dispositivo emitido",
+        "data": {
+            "device_id": device_id,
+            "previous_valid_until": previous_valid_until,
+        },
+        "credentials": {
+            "device_secret": secret,
+        },
+    })))
+}
+
+/// Handler para reiniciar la ventana anti-reenvío de un dispositivo
+/// DELETE /api/v1/devices/{id}/sequence
+///
+/// Tras un reinicio que pierde el contador, el nodo vuelve a enviar secuencias
+/// bajas que se rechazarían; el siguiente mensaje fija la nueva secuencia de partida.
+pub async fn reset_device_sequence(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    if !state.db.reset_replay_sequence(&device_id).await? {
+        return Err(AppError::NotFound(format!(
+            "Dispositivo {} no existe",
+            device_id
+        )));
+    }
+
+    tracing::info!(device_id = %device_id, "Secuencia anti-reenvío reiniciada");
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Secuencia reiniciada",
+        "data": {
+            "device_id": device_id,
+        },
+    })))
+}
+
+/// Handler para modificar un dispositivo
+/// PATCH /api/v1/devices/{id}
+pub async fn update_device(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Json(mut patch): Json<DevicePatch>,
+) -> Result<Json<Value>, AppError> {
+    patch
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+    validate_metadata(patch.metadata.as_ref())?;
+    if let Some(tags) = &mut patch.tags {
+        normalize_tags(tags)?;
+    }
+    ensure_profile(&state, patch.device_type.as_deref()).await?;
+
+    if !state.db.update_device(&device_id, &patch).await? {
+        return Err(AppError::NotFound(format!(
+            "Dispositivo {} no existe",
+            device_id
+        )));
+    }
+    if patch.tags.is_some() {
+        reload_device_tags(&state).await?;
+    }
+
+    tracing::info!(device_id = %device_id, "Dispositivo actualizado");
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Dispositivo actualizado",
+        "data": state.db.get_device(&device_id).await?,
+    })))
+}
+
+/// Handler para eliminar un dispositivo del registro
+/// DELETE /api/v1/devices/{id}
+///
+/// Elimina también sus calibraciones; las lecturas almacenadas se conservan
+pub async fn delete_device(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    if !state.db.delete_device(&device_id).await? {
+        return Err(AppError::NotFound(format!(
+            "Dispositivo {} no existe",
+            device_id
+        )));
+    }
+    reload_calibrations(&state).await?;
+    reload_device_tags(&state).await?;
+
+    tracing::info!(device_id = %device_id, "Dispositivo eliminado del registro");
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Dispositivo eliminado",
+    })))
+}
+
+/// Exporta a Parquet las lecturas almacenadas de un dispositivo
+/// Retorna el archivo y la cantidad de lecturas, o None si no tiene lecturas
+async fn archive_readings(
+    state: &AppState,
+    device_id: &str,
+) -> Result<Option<(PathBuf, usize)>, AppError> {
+    // El archivo debe incluir las lecturas aún en el búfer de escritura
+    state.db.flush_writes().await;
+    let Some((from, to)) = state.db.device_reading_span(device_id).await? else {
+        return Ok(None);
+    };
+
+    let dir = PathBuf::from(&state.config.device_archive_dir);
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .map_err(|e| AppError::InternalError(e.to_string()))?;
+
+    // El ID llega del tráfico ingerido: no debe poder salir del directorio
+    let safe_id: String = device_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let path = dir.join(format!(
+        "{}-{}.parquet",
+        safe_id,
+        Utc::now().format("%Y%m%d%H%M%S")
+    ));
+
+    let filter = MetricFilter {
+        device_ids: vec![device_id.to_string()],
+        locations: Vec::new(),
+        tags: Vec::new(),
+        measurement: None,
+        from,
+        to,
+        quality: Default::default(),
+    };
+    let export = ExportService::prepare(state.db.clone(), filter).await?;
+
+    // Se escribe con otro nombre y se renombra para no dejar archivos incompletos
+    let partial = path.with_extension("parquet.part");
+    let rows = export.write_parquet(&partial).await?;
+    tokio::fs::rename(&partial, &path)
+        .await
+        .map_err(|e| AppError::InternalError(e.to_string()))?;
+
+    Ok(Some((path, rows)))
+}
+
+/// Handler para dar de baja un dispositivo
+/// POST /api/v1/devices/{id}/decommission
+///
+/// Ejemplo: `{"data": "archive", "reason": "Reemplazado por esp32-14"}`. Desde
+/// la baja se rechazan sus lecturas y se eliminan sus calibraciones, reglas,
+/// ajustes, twin e historial de firmware; el registro se conserva con estado
+/// `decommissioned` y se publica un aviso final en el cloud. Sus lecturas se
+/// conservan (`keep`, por defecto), se eliminan (`purge`) o se exportan a
+/// Parquet en `DEVICE_ARCHIVE_DIR` antes de eliminarse (`archive`). Repetir la
+/// baja solo aplica el destino de las lecturas.
+pub async fn decommission_device(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    input: Option<Json<DeviceDecommissionInput>>,
+) -> Result<Json<Value>, AppError> {
+    let Json(input) = input.unwrap_or_default();
+    input
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let device = state
+        .db
+        .get_device(&device_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Dispositivo {} no existe", device_id)))?;
+
+    let newly_decommissioned = device.status != "decommissioned";
+    let mut cleanup = None;
+    if newly_decommissioned {
+        // Sin sincronización con el cloud no hay a quién avisar
+        let notice = state.config.cloud_sync_enabled.then(|| {
+            json!({
+                "device_id": device.device_id,
+                "location": device.location,
+                "device_type": device.device_type,
+                "tags": device.tags,
+                "reason": input.reason,
+                "data": input.data,
+                "first_seen": device.first_seen,
+                "last_seen": device.last_seen,
+                "message_count": device.message_count,
+            })
+        });
+
+        cleanup = state
+            .db
+            .decommission_device(
+                &device_id,
+                input.reason.as_deref(),
+                notice.as_ref(),
+                Utc::now(),
+            )
+            .await?;
+        state.edge_processor.load_definitions(&state.db).await?;
+    }
+
+    let archive = match input.data {
+        DataDisposition::Archive => archive_readings(&state, &device_id).await?,
+        DataDisposition::Keep | DataDisposition::Purge => None,
+    };
+    let purged = match input.data {
+        DataDisposition::Keep => 0,
+        DataDisposition::Purge | DataDisposition::Archive => {
+            state
+                .db
+                .purge_readings(Some(&device_id), None, None, false)
+                .await?
+        }
+    };
+
+    tracing::info!(
+        device_id = %device_id,
+        reason = ?input.reason,
+        data = ?input.data,
+        purged,
+        archived = ?archive.as_ref().map(|(path, _)| path),
+        "Dispositivo dado de baja"
+    );
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": if newly_decommissioned {
+            "Dispositivo dado de baja"
+        } else {
+            "Dispositivo ya dado de baja; se aplicó el destino de sus lecturas"
+        },
+        "data": {
+            "device": state.db.get_device(&device_id).await?,
+            "removed": cleanup,
+            "readings": {
+                "disposition": input.data,
+                "purged": purged,
+                "archive_file": archive.as_ref().map(|(path, _)| path.display().to_string()),
+                "archived": archive.map(|(_, rows)| rows).unwrap_or(0),
+            },
+            "cloud_notice_queued": newly_decommissioned && state.config.cloud_sync_enabled,
+        },
+    })))
+}
+
+/// Handler para el device twin de un dispositivo
+/// GET /api/v1/devices/{id}/twin
+pub async fn get_device_twin(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let twin = state.twins.get(&device_id).await?;
+    if twin.desired_version == 0
+        && twin.reported_updated_at.is_none()
+        && state.db.get_device(&device_id).await?.is_none()
+    {
+        return Err(AppError::NotFound(format!(
+            "Dispositivo {} no existe",
+            device_id
+        )));
+    }
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": twin,
+    })))
+}
+
+/// Handler para modificar el estado deseado de un dispositivo
+/// PATCH /api/v1/devices/{id}/twin
+///
+/// Publica el delta resultante en `sensors/{id}/desired`
+pub async fn update_device_twin(
+    State(state): State<AppState>,
+    Path(device_id): Path<String>,
+    Json(patch): Json<DeviceTwinPatch>,
+) -> Result<Json<Value>, AppError> {
+    if !patch.desired.is_object() {
+        return Err(AppError::ValidationError(
+            "desired debe ser un objeto JSON".to_string(),
+        ));
+    }
+    if state.db.get_device(&device_id).await?.is_none() {
+        return Err(AppError::NotFound(format!(
+            "Dispositivo {} no existe",
+            device_id
+        )));
+    }
+
+    let twin = state
+        .twins
+        .update_desired(&device_id, &patch.desired)
+        .await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Estado deseado actualizado",
+        "data": twin,
+    })))
+}
+
+/// Handler para listar los grupos de dispositivos (uno por etiqueta)
+/// GET /api/v1/groups
+pub async fn list_groups(State(state): State<AppState>) -> Result<Json<Value>, AppError> {
+    let groups = state.db.list_device_groups().await?;
+
+    let data: Vec<Value> = groups
+        .into_iter()
+        .map(|group| {
+            json!({
+                "tag": group.tag,
+                "device_count": group.device_ids.len(),
+                "device_ids": group.device_ids,
+            })
+        })
+        .collect();
+
+    Ok(Json(json!({
+        "status": "success",
+        "count": data.len(),
+        "data": data,
+    })))
+}
+
+/// Handler para modificar el estado deseado de todos los dispositivos de un grupo
+/// PATCH /api/v1/groups/{tag}/twin
+///
+/// Ejemplo: `{"desired": {"interval_secs": 300}}`; cada dispositivo recibe su
+/// delta en `sensors/{id}/desired`
+pub async fn update_group_twin(
+    State(state): State<AppState>,
+    Path(tag): Path<String>,
+    Json(patch): Json<DeviceTwinPatch>,
+) -> Result<Json<Value>, AppError> {
+    if !patch.desired.is_object() {
+        return Err(AppError::ValidationError(
+            "desired debe ser un objeto JSON".to_string(),
+        ));
+    }
+    validate_tag(&tag)?;
+
+    let device_ids = state
+        .db
+        .device_ids_with_tags(std::slice::from_ref(&tag))
+        .await?;
+    if device_ids.is_empty() {
+        return Err(AppError::NotFound(format!(
+            "No hay dispositivos con la etiqueta {}",
+            tag
+        )));
+    }
+
+    for device_id in &device_ids {
+        state
+            .twins
+            .update_desired(device_id, &patch.desired)
+            .await?;
+    }
+
+    tracing::info!(tag = %tag, devices = device_ids.len(), "Estado deseado del grupo actualizado");
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Estado deseado del grupo actualizado",
+        "data": {
+            "tag": tag,
+            "devices": device_ids,
+        },
+    })))
+}
@@ -0,0 +1,60 @@
+use crate::startup::state::AppState;
+use axum::{
+    extract::{Query, State},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use tokio_stream::{Stream, StreamExt, wrappers::BroadcastStream};
+
+#[derive(Debug, Deserialize)]
+pub struct EventsQuery {
+    pub device_id: Option<String>,
+}
+
+/// Handler del stream de eventos de los dispositivos (SSE)
+/// GET /api/v1/events?device_id=XXX
+///
+/// Cada cambio de conectividad se envía como un evento `device_status` con el
+/// JSON del dispositivo, y cada disparo o resolución de alerta como `alert`
+pub async fn stream_events(
+    State(state): State<AppState>,
+    Query(params): Query<EventsQuery>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let device_filter = params.device_id.clone();
+    let statuses = BroadcastStream::new(state.presence.subscribe()).filter_map(move |event| {
+        // Un cliente atrasado pierde los eventos más antiguos
+        let event = event.ok()?;
+        if device_filter
+            .as_ref()
+            .is_some_and(|device_id| *device_id != event.device_id)
+        {
+            return None;
+        }
+        sse_event("device_status", &event)
+    });
+
+    let alerts = BroadcastStream::new(state.alerting.subscribe()).filter_map(move |event| {
+        let event = event.ok()?;
+        if params
+            .device_id
+            .as_ref()
+            .is_some_and(|device_id| *device_id != event.device_id)
+        {
+            return None;
+        }
+        sse_event("alert", &event)
+    });
+
+    Sse::new(statuses.merge(alerts)).keep_alive(KeepAlive::default())
+}
+
+fn sse_event(name: &str, data: &impl Serialize) -> Option<Result<Event, Infallible>> {
+    match Event::default().event(name).json_data(data) {
+        Ok(event) => Some(Ok(event)),
+        Err(e) => {
+            tracing::error!("Error serializando evento {}: {}", name, e);
+            None
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use crate::{
+    error::AppError,
+    handlers::query::MetricsQuery,
+    services::export::{ExportFormat, ExportService},
+    startup::state::AppState,
+};
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use tokio_util::io::ReaderStream;
+
+#[derive(Debug, Deserialize)]
+pub struct ExportQuery {
+    #[serde(flatten)]
+    pub filter: MetricsQuery,
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+/// Handler para exportar lecturas
+/// GET /api/v1/data/export?device_id=&location=&tags=&from=&to=&format=csv|parquet
+///
+/// Las métricas se aplanan en una columna por medición
+pub async fn export_data(
+    State(state): State<AppState>,
+    Query(params): Query<ExportQuery>,
+) -> Result<Response, AppError> {
+    let filter = params.filter.to_filter()?;
+    let base_name = format!(
+        "{}-{}-{}",
+        state.config.gateway_id,
+        filter.from.format("%Y%m%d%H%M%S"),
+        filter.to.format("%Y%m%d%H%M%S")
+    );
+
+    let export = ExportService::prepare(state.db.clone(), filter).await?;
+
+    match params.format {
+        ExportFormat::Csv => Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}.csv\"", base_name),
+                ),
+            ],
+            Body::from_stream(export.stream_csv()),
+        )
+            .into_response()),
+        ExportFormat::Parquet => {
+            let path = std::env::temp_dir().join(format!(
+                "{}-{}.parquet",
+                base_name,
+                uuid::Uuid::new_v4()
+            ));
+            let rows = export.write_parquet(&path).await;
+
+            let file = tokio::fs::File::open(&path).await;
+            // El descriptor abierto mantiene el contenido disponible tras borrar el archivo temporal
+            let _ = tokio::fs::remove_file(&path).await;
+
+            tracing::info!(rows = rows?, "Exportación Parquet generada");
+            let file = file.map_err(|e| AppError::InternalError(e.to_string()))?;
+
+            Ok((
+                [
+                    (
+                        header::CONTENT_TYPE,
+                        "application/vnd.apache.parquet".to_string(),
+                    ),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"{}.parquet\"", base_name),
+                    ),
+                ],
+                Body::from_stream(ReaderStream::new(file)),
+            )
+                .into_response())
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use super::devices::ensure_profile;
+use crate::{
+    error::AppError,
+    models::{FirmwareImage, FirmwareRolloutInput, FirmwareUploadInput},
+    services::firmware::FirmwareService,
+    startup::state::AppState,
+};
+use axum::{
+    Json,
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::SeekFrom;
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+use tokio_util::io::ReaderStream;
+use validator::Validate;
+
+async fn find_image(state: &AppState, id: i64) -> Result<FirmwareImage, AppError> {
+    state
+        .db
+        .get_firmware(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Firmware {} no existe", id)))
+}
+
+/// Handler para subir una imagen de firmware
+/// POST /api/v1/admin/firmware?version=1.4.0&device_type=esp32-bme280&notes=
+///
+/// El cuerpo es el binario (`application/octet-stream`), hasta `FIRMWARE_MAX_BYTES`
+pub async fn upload_firmware(
+    State(state): State<AppState>,
+    Query(input): Query<FirmwareUploadInput>,
+    binary: Bytes,
+) -> Result<(StatusCode, Json<Value>), AppError> {
+    input
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+    // La versión forma el nombre del archivo en Content-Disposition
+    if !input
+        .version
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    {
+        return Err(AppError::ValidationError(format!(
+            "Versión de firmware inválida '{}': solo letras, números, '.', '_' o '-'",
+            input.version
+        )));
+    }
+    if binary.is_empty() {
+        return Err(AppError::ValidationError(
+            "El binario de firmware está vacío".to_string(),
+        ));
+    }
+    ensure_profile(&state, input.device_type.as_deref()).await?;
+
+    if state
+        .db
+        .find_firmware(&input.version, input.device_type.as_deref())
+        .await?
+        .is_some()
+    {
+        return Err(AppError::Conflict(format!(
+            "La versión {} de firmware ya existe",
+            input.version
+        )));
+    }
+
+    let image = state.firmware.store(&input, &binary).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(json!({
+            "status": "success",
+            "message": "Firmware registrado",
+            "data": {
+                "download_path": FirmwareService::download_path(image.id),
+                "firmware": image,
+            },
+        })),
+    ))
+}
+
+/// Handler para listar las imágenes de firmware
+/// GET /api/v1/admin/firmware
+pub async fn list_firmware(State(state): State<AppState>) -> Result<Json<Value>, AppError> {
+    let images = state.db.list_firmware().await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "count": images.len(),
+        "data": images,
+    })))
+}
+
+/// Handler para una imagen de firmware y el estado de su despliegue
+/// GET /api/v1/admin/firmware/{id}
+pub async fn get_firmware(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, AppError> {
+    let image = find_image(&state, id).await?;
+    let rollouts = state.db.list_rollouts(id).await?;
+
+    let mut summary: BTreeMap<&str, usize> = BTreeMap::new();
+    for rollout in &rollouts {
+        *summary.entry(rollout.state.as_str()).or_default() += 1;
+    }
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": {
+            "firmware": image,
+            "rollout_summary": summary,
+            "rollouts": rollouts,
+        },
+    })))
+}
+
+/// Handler para eliminar una imagen de firmware
+/// DELETE /api/v1/admin/firmware/{id}
+pub async fn delete_firmware(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+) -> Result<Json<Value>, AppError> {
+    let image = find_image(&state, id).await?;
+    state.firmware.delete(&image).await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Firmware eliminado",
+    })))
+}
+
+/// Handler para desplegar un firmware en dispositivos
+/// POST /api/v1/admin/firmware/{id}/rollout
+///
+/// Ejemplo: `{"device_ids": ["esp32-01"], "tags": ["greenhouse-3"]}`; sin
+/// dispositivos ni etiquetas se despliega en todos los dispositivos del tipo de
+/// la imagen. Cada dispositivo recibe el anuncio retenido en `sensors/{id}/ota`.
+pub async fn rollout_firmware(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Json(input): Json<FirmwareRolloutInput>,
+) -> Result<Json<Value>, AppError> {
+    let image = find_image(&state, id).await?;
+
+    let device_ids: Vec<String> = if input.device_ids.is_empty() && input.tags.is_empty() {
+        let Some(device_type) = &image.device_type else {
+            return Err(AppError::ValidationError(
+                "device_ids o tags es obligatorio para firmware sin device_type".to_string(),
+            ));
+        };
+        state.db.device_ids_by_type(device_type).await?
+    } else {
+        let tagged = state.db.device_ids_with_tags(&input.tags).await?;
+        input
+            .device_ids
+            .into_iter()
+            .chain(tagged)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect()
+    };
+
+    if device_ids.is_empty() {
+        return Err(AppError::ValidationError(
+            "No hay dispositivos a los que desplegar el firmware".to_string(),
+        ));
+    }
+
+    for device_id in &device_ids {
+        let device =
+            state.db.get_device(device_id).await?.ok_or_else(|| {
+                AppError::NotFound(format!("Dispositivo {} no existe", device_id))
+            })?;
+
+        // Un binario de otro tipo de nodo puede dejarlo inservible
+        if let (Some(expected), Some(actual)) = (&image.device_type, &device.device_type)
+            && expected != actual
+        {
+            return Err(AppError::ValidationError(format!(
+                "El dispositivo {} es de tipo {} y el firmware es para {}",
+                device_id, actual, expected
+            )));
+        }
+    }
+
+    state.firmware.rollout(&image, &device_ids).await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Actualización de firmware anunciada",
+        "data": {
+            "firmware_id": image.id,
+            "version": image.version,
+            "devices": device_ids,
+        },
+    })))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadQuery {
+    /// Dispositivo que descarga, para marcar su actualización como downloading
+    pub device_id: Option<String>,
+}
+
+/// Rango solicitado en la cabecera `Range`
+enum ByteRange {
+    Full,
+    /// Inicio y fin inclusivos
+    Partial(u64, u64),
+    Unsatisfiable,
+}
+
+/// Interpreta un único rango `bytes=`; los rangos múltiples o mal formados se
+/// ignoran y se sirve el archivo completo, como permite RFC 9110
+fn parse_range(value: Option<&str>, size: u64) -> ByteRange {
+    let Some(spec) = value.and_then(|value| value.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(suffix) => (size.saturating_sub(suffix), size.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) => (start, size.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(size.saturating_sub(1))),
+            _ => return ByteRange::Full,
+        },
+    };
+
+    if start >= size {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial(start, end)
+}
+
+/// Handler para descargar el binario de un firmware
+/// GET /api/v1/firmware/{id}/download?device_id=XXX
+///
+/// Admite `Range` para reanudar descargas interrumpidas; la cabecera
+/// `X-Firmware-Sha256` (y el `ETag`) permiten verificar el binario antes de
+/// aplicarlo.
+pub async fn download_firmware(
+    State(state): State<AppState>,
+    Path(id): Path<i64>,
+    Query(params): Query<DownloadQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let image = find_image(&state, id).await?;
+    let mut file = tokio::fs::File::open(state.firmware.file_path(&image))
+        .await
+        .map_err(|_| AppError::NotFound(format!("Binario del firmware {} no disponible", id)))?;
+    let size = image.size_bytes as u64;
+
+    if let Some(device_id) = &params.device_id
+        && let Err(e) = state.firmware.mark_downloading(id, device_id).await
+    {
+        tracing::error!(device_id = %device_id, "Error registrando la descarga de firmware: {}", e);
+    }
+
+    let range = parse_range(
+        headers
+            .get(header::RANGE)
+            .and_then(|value| value.to_str().ok()),
+        size,
+    );
+
+    let common = [
+        (header::CONTENT_TYPE, "application/octet-stream".to_string()),
+        (header::ACCEPT_RANGES, "bytes".to_string()),
+        (header::ETAG, format!("\"{}\"", image.sha256)),
+        (
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"firmware-{}.bin\"", image.version),
+        ),
+    ];
+    let checksum = [("x-firmware-sha256", image.sha256.clone())];
+
+    match range {
+        ByteRange::Full => Ok((
+            common,
+            checksum,
+            [(header::CONTENT_LENGTH, size.to_string())],
+            Body::from_stream(ReaderStream::new(file)),
+        )
+            .into_response()),
+        ByteRange::Partial(start, end) => {
+            file.seek(SeekFrom::Start(start))
+                .await
+                .map_err(|e| AppError::InternalError(e.to_string()))?;
+            let length = end - start + 1;
+
+            Ok((
+                StatusCode::PARTIAL_CONTENT,
+                common,
+                checksum,
+                [
+                    (header::CONTENT_LENGTH, length.to_string()),
+                    (
+                        header::CONTENT_RANGE,
+                        format!("bytes {}-{}/{}", start, end, size),
+                    ),
+                ],
+                Body::from_stream(ReaderStream::new(file.take(length))),
+            )
+                .into_response())
+        }
+        ByteRange::Unsatisfiable => Ok((
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            [(header::CONTENT_RANGE, format!("bytes */{}", size))],
+        )
+            .into_response()),
+    }
+}
@@ -0,0 +1,63 @@
+use super::query::TagQuery;
+use crate::{error::AppError, startup::state::AppState};
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::HashSet;
+
+#[derive(Debug, Deserialize)]
+pub struct PowerQuery {
+    /// Solo los nodos que requieren atención
+    #[serde(default)]
+    pub attention_only: bool,
+    #[serde(flatten)]
+    pub tags: TagQuery,
+}
+
+/// Handler para el reporte de batería y señal de la flota
+/// GET /api/v1/fleet/power?attention_only=true&tags=
+pub async fn get_power_report(
+    State(state): State<AppState>,
+    Query(params): Query<PowerQuery>,
+) -> Result<Json<Value>, AppError> {
+    let tags = params.tags.to_tags()?;
+    let tagged: Option<HashSet<String>> = if tags.is_empty() {
+        None
+    } else {
+        Some(
+            state
+                .db
+                .device_ids_with_tags(&tags)
+                .await?
+                .into_iter()
+                .collect(),
+        )
+    };
+
+    let report: Vec<_> = state
+        .edge_processor
+        .power_report()
+        .into_iter()
+        .filter(|status| !params.attention_only || status.needs_attention)
+        .filter(|status| {
+            tagged
+                .as_ref()
+                .is_none_or(|tagged| tagged.contains(&status.device_id))
+        })
+        .collect();
+
+    let attention = report
+        .iter()
+        .filter(|status| status.needs_attention)
+        .count();
+
+    Ok(Json(json!({
+        "status": "success",
+        "count": report.len(),
+        "needs_attention": attention,
+        "data": report,
+    })))
+}
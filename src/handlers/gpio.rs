@@ -0,0 +1,44 @@
+use crate::{error::AppError, services::gpio::GpioMode, startup::state::AppState};
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+
+#[derive(Debug, Deserialize)]
+pub struct GpioModeInput {
+    pub mode: GpioMode,
+}
+
+/// Handler para consultar las salidas GPIO
+/// GET /api/v1/gpio
+pub async fn list_outputs(State(state): State<AppState>) -> Json<Value> {
+    let outputs = state.gpio.states();
+
+    Json(json!({
+        "status": "success",
+        "count": outputs.len(),
+        "data": outputs,
+    }))
+}
+
+/// Handler para fijar el control manual de una salida (`on`, `off` o `auto`)
+/// PUT /api/v1/gpio/{name}
+pub async fn set_output_mode(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(input): Json<GpioModeInput>,
+) -> Result<Json<Value>, AppError> {
+    let output = state
+        .gpio
+        .set_mode(&name, input.mode)
+        .ok_or_else(|| AppError::NotFound(format!("Salida GPIO {} no existe", name)))?;
+
+    tracing::info!(output = %name, mode = ?input.mode, "Control manual de salida GPIO actualizado");
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": output,
+    })))
+}
@@ -0,0 +1,15 @@
+use crate::{services::graphql, startup::state::AppState};
+use async_graphql::{Request, Response};
+use axum::{Json, extract::State};
+
+/// Handler para consultas GraphQL
+/// POST /api/v1/graphql  {"query": "...", "variables": {...}}
+///
+/// Expone dispositivos, lecturas, agregados y alertas con filtros y selección
+/// anidada; los errores se reportan en `errors` junto a los datos parciales
+pub async fn graphql(
+    State(state): State<AppState>,
+    Json(request): Json<Request>,
+) -> Json<Response> {
+    Json(graphql::execute(&state.graphql, request).await)
+}
@@ -1,33 +1,145 @@
-use crate::startup::state::AppState;
-use axum::{Json, extract::State};
+use crate::{services::connection::ConnectionState, startup::state::AppState};
+use axum::{Json, extract::State, http::StatusCode};
 use serde_json::{Value, json};
 
+/// Resultado de las verificaciones de readiness
+struct Readiness {
+    ready: bool,
+    database_ok: bool,
+    pending_sync: i64,
+    checks: Value,
+}
+
+fn check_status(ok: bool) -> &'static str {
+    if ok { "pass" } else { "fail" }
+}
+
+/// Evalúa si el gateway puede recibir y procesar tráfico
+///
+/// Requiere conexión real con el broker local, una base de datos que acepte
+/// escrituras y un backlog de sincronización por debajo del umbral y sin la
+/// alarma de crecimiento sostenido activa. La
+/// conexión con el cloud solo cuenta si `READINESS_REQUIRE_CLOUD` está activo,
+/// ya que sin ella el gateway sigue acumulando lecturas localmente.
+async fn evaluate_readiness(state: &AppState) -> Readiness {
+    let database = state.db.check_writable().await;
+    let database_ok = database.is_ok();
+
+    let mqtt = state.mqtt_status.report();
+    let mqtt_ok = mqtt.state == ConnectionState::Connected;
+
+    let cloud = state.cloud_status.report();
+    let cloud_required = state.config.readiness_require_cloud;
+    let cloud_ok = !cloud_required || cloud.state != ConnectionState::Disconnected;
+
+    let pending = state.db.count_pending_sync().await;
+    let threshold = state.config.readiness_max_pending_sync;
+    let backlog_ok = match &pending {
+        Ok(count) => threshold == 0 || *count < threshold,
+        Err(_) => false,
+    };
+    let pending_sync = pending.unwrap_or(-1);
+
+    let growth_ok = !state.backlog_watchdog.is_alarming();
+
+    let checks = json!({
+        "database": {
+            "status": check_status(database_ok),
+            "error": database.err().map(|e| e.to_string()),
+        },
+        "mqtt": {
+            "status": check_status(mqtt_ok),
+            "connection": mqtt,
+        },
+        "cloud": {
+            "status": check_status(cloud_ok),
+            "required": cloud_required,
+            "connection": cloud,
+        },
+        "backlog": {
+            "status": check_status(backlog_ok),
+            "pending_sync": pending_sync,
+            "threshold": threshold,
+        },
+        "backlog_growth": {
+            "status": check_status(growth_ok),
+            "alarm_minutes": state.config.backlog_alarm_minutes,
+            "trend": state.backlog_watchdog.trend(),
+        },
+    });
+
+    Readiness {
+        ready: database_ok && mqtt_ok && cloud_ok && backlog_ok && growth_ok,
+        database_ok,
+        pending_sync,
+        checks,
+    }
+}
+
 /// Handler para health check
 /// GET /health
 ///
 /// Verifica el estado del gateway y sus componentes
 pub async fn health_check(State(state): State<AppState>) -> Json<Value> {
-    // Verificar conexión a la base de datos
-    let db_status = match state.db.count_pending_sync().await {
-        Ok(_) => "healthy",
-        Err(_) => "unhealthy",
-    };
+    let readiness = evaluate_readiness(&state).await;
 
-    // Obtener conteo de datos pendientes
-    let pending_sync = state.db.count_pending_sync().await.unwrap_or(-1);
+    let db_status = if readiness.database_ok {
+        "healthy"
+    } else {
+        "unhealthy"
+    };
 
     Json(json!({
-        "status": "ok",
+        "status": if readiness.ready { "ok" } else { "degraded" },
         "gateway_id": state.config.gateway_id,
         "version": env!("CARGO_PKG_VERSION"),
         "timestamp": chrono::Utc::now().to_rfc3339(),
         "components": {
             "database": db_status,
             "edge_processor": "healthy",
-            "cloud_sync": "healthy",
+            "mqtt": state.mqtt_status.state(),
+            "cloud_sync": state.cloud_status.state(),
         },
         "metrics": {
-            "pending_sync": pending_sync,
-        }
+            "pending_sync": readiness.pending_sync,
+        },
+        "lifecycle": state.lifecycle.report(),
     }))
 }
+
+/// Handler para liveness probe
+/// GET /health/live
+///
+/// Solo indica que el proceso atiende solicitudes; no depende de servicios externos
+pub async fn liveness(State(state): State<AppState>) -> Json<Value> {
+    Json(json!({
+        "status": "alive",
+        "gateway_id": state.config.gateway_id,
+        "version": env!("CARGO_PKG_VERSION"),
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    }))
+}
+
+/// Handler para readiness probe
+/// GET /health/ready
+///
+/// Responde 503 si alguna verificación falla, con el detalle de cada una
+pub async fn readiness(State(state): State<AppState>) -> (StatusCode, Json<Value>) {
+    let readiness = evaluate_readiness(&state).await;
+
+    let status = if readiness.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(json!({
+            "status": if readiness.ready { "ready" } else { "not_ready" },
+            "gateway_id": state.config.gateway_id,
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "checks": readiness.checks,
+        })),
+    )
+}
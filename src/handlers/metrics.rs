@@ -1,5 +1,11 @@
+use crate::services::connection::ConnectionState;
 use crate::startup::state::AppState;
-use axum::{Json, extract::State};
+use axum::{
+    Json,
+    extract::State,
+    http::header,
+    response::{IntoResponse, Response},
+};
 use serde_json::{Value, json};
 
 /// Handler para métricas del sistema
@@ -8,12 +14,17 @@ use serde_json::{Value, json};
 /// Retorna métricas de operación del gateway
 pub async fn get_metrics(State(state): State<AppState>) -> Json<Value> {
     let pending_sync = state.db.count_pending_sync().await.unwrap_or(0);
+    let sync_failures = state.db.count_sync_failures().await.unwrap_or(0);
+    let devices_count = state.db.count_devices().await.unwrap_or(0);
+    let database_size = state.db.database_size_bytes().await.unwrap_or(0);
+    let maintenance = state.maintenance.last_report().await;
+    let (cache_hits, cache_misses) = state.db.cache_stats();
+    let settings = state.runtime_config.current();
+    let (auth_failures, auth_lockouts) = state.auth_lockout.stats();
 
     // Aquí podrías agregar más métricas como:
     // - Tasa de lecturas por minuto
     // - Sensores activos
-    // - Uso de memoria
-    // - Uso de disco
     // - etc.
 
     Json(json!({
@@ -21,8 +32,90 @@ pub async fn get_metrics(State(state): State<AppState>) -> Json<Value> {
         "timestamp": chrono::Utc::now().to_rfc3339(),
         "metrics": {
             "pending_sync_count": pending_sync,
-            "sync_batch_size": state.config.cloud_sync_batch_size,
-            "sync_interval_secs": state.config.cloud_sync_interval_secs,
+            "sync_failing_count": sync_failures,
+            "duplicates_dropped": state.db.duplicates_dropped(),
+            "replays_rejected": state.db.replays_rejected(),
+            "auth_failures": auth_failures,
+            "auth_lockouts": auth_lockouts,
+            "devices_count": devices_count,
+            "database_size_bytes": database_size,
+            "last_maintenance": maintenance,
+            "latest_cache": {
+                "hits": cache_hits,
+                "misses": cache_misses,
+            },
+            "sync_batch_size": settings.cloud_sync_batch_size,
+            "sync_interval_secs": settings.cloud_sync_interval_secs,
+            "api_requests": state.api_usage.snapshot(),
+            "host": state.host_metrics.latest(),
+            "connections": {
+                "mqtt_local": state.mqtt_status.report(),
+                "mqtt_cloud": state.cloud_status.report(),
+            },
+            "devices": state.telemetry.devices_stats(),
+            "mqtt_queue": state.telemetry.mqtt_queue_stats(),
+            "write_buffer": state.telemetry.write_buffer_stats(),
+            "gpio": state.gpio.states(),
         }
     }))
 }
+
+/// Handler para métricas del pipeline y del host en formato Prometheus
+/// GET /metrics/prometheus
+pub async fn get_prometheus_metrics(State(state): State<AppState>) -> Response {
+    // El backlog se actualiza también aquí para que refleje el estado al hacer scrape
+    if let Ok(pending) = state.db.count_pending_sync().await {
+        state.telemetry.set_sync_backlog(pending);
+    }
+
+    let mut body = state.telemetry.render();
+    body.push_str(&connection_metrics(&state));
+    body.push_str(&state.host_metrics.render_prometheus());
+    body.push_str(&state.gpio.render_prometheus());
+
+    (
+        [(
+            header::CONTENT_TYPE,
+            "text/plain; version=0.0.4; charset=utf-8",
+        )],
+        body,
+    )
+        .into_response()
+}
+
+/// Estado y contadores de conexión de ambos brokers en formato Prometheus
+fn connection_metrics(state: &AppState) -> String {
+    let brokers = [
+        ("local", state.mqtt_status.report()),
+        ("cloud", state.cloud_status.report()),
+    ];
+
+    let mut out = String::from(
+        "# HELP gateway_mqtt_connected Conexión activa con el broker (1 = conectado)\n\
+         # TYPE gateway_mqtt_connected gauge\n",
+    );
+    for (broker, report) in &brokers {
+        let connected = report.state == ConnectionState::Connected;
+        out.push_str(&format!(
+            "gateway_mqtt_connected{{broker=\"{}\"}} {}\n",
+            broker, connected as u8
+        ));
+    }
+
+    out.push_str(
+        "# HELP gateway_mqtt_connection_events_total Conexiones establecidas (up) y perdidas (down)\n\
+         # TYPE gateway_mqtt_connection_events_total counter\n",
+    );
+    for (broker, report) in &brokers {
+        out.push_str(&format!(
+            "gateway_mqtt_connection_events_total{{broker=\"{}\",event=\"up\"}} {}\n",
+            broker, report.connects
+        ));
+        out.push_str(&format!(
+            "gateway_mqtt_connection_events_total{{broker=\"{}\",event=\"down\"}} {}\n",
+            broker, report.disconnects
+        ));
+    }
+
+    out
+}
@@ -1,5 +1,23 @@
 // Módulo de handlers HTTP
+pub mod admin;
+pub mod alerts;
+pub mod calibrations;
+pub mod config;
+pub mod dashboard;
+pub mod devices;
+pub mod events;
+pub mod export;
+pub mod firmware;
+pub mod fleet;
+pub mod gpio;
+pub mod graphql;
 pub mod health;
 pub mod metrics;
+pub mod overrides;
+pub mod profiles;
 pub mod query;
+pub mod rules;
 pub mod sensor;
+pub mod stream;
+pub mod sync;
+pub mod webhook;
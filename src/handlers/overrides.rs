@@ -0,0 +1,160 @@
+use crate::{
+    error::AppError,
+    models::{DeviceOverrideInput, OverrideScope},
+    startup::state::AppState,
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use validator::Validate;
+
+#[derive(Debug, Deserialize)]
+pub struct EffectiveQuery {
+    pub device_id: String,
+    #[serde(default)]
+    pub location: String,
+}
+
+/// Valida la entrada de un ajuste
+async fn validate_input(state: &AppState, input: &DeviceOverrideInput) -> Result<(), AppError> {
+    input
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    for (measurement, calibration) in &input.calibrations {
+        if !(calibration.offset.is_finite() && calibration.gain.is_finite())
+            || calibration.gain == 0.0
+        {
+            return Err(AppError::ValidationError(format!(
+                "Calibración inválida para {}: offset y gain deben ser números finitos y gain distinto de 0",
+                measurement
+            )));
+        }
+    }
+
+    if let Some(profile) = &input.profile
+        && state.db.get_device_profile(profile).await?.is_none()
+    {
+        return Err(AppError::ValidationError(format!(
+            "Perfil {} no existe",
+            profile
+        )));
+    }
+
+    Ok(())
+}
+
+/// Recarga los ajustes en el procesador edge tras un cambio
+async fn reload_overrides(state: &AppState) -> Result<(), AppError> {
+    state
+        .edge_processor
+        .set_overrides(state.db.list_device_overrides().await?);
+    Ok(())
+}
+
+/// Handler para listar ajustes por dispositivo y ubicación
+/// GET /api/v1/admin/overrides
+pub async fn list_overrides(State(state): State<AppState>) -> Result<Json<Value>, AppError> {
+    let overrides = state.db.list_device_overrides().await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "count": overrides.len(),
+        "data": overrides,
+    })))
+}
+
+/// Handler para obtener un ajuste
+/// GET /api/v1/admin/overrides/{scope}/{target}
+pub async fn get_override(
+    State(state): State<AppState>,
+    Path((scope, target)): Path<(OverrideScope, String)>,
+) -> Result<Json<Value>, AppError> {
+    let entry = state
+        .db
+        .get_device_override(scope, &target)
+        .await?
+        .ok_or_else(|| {
+            AppError::NotFound(format!("Ajuste {}/{} no existe", scope.as_str(), target))
+        })?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": entry,
+    })))
+}
+
+/// Handler para consultar los ajustes efectivos de un dispositivo en una ubicación
+/// GET /api/v1/admin/overrides/effective?device_id=...&location=...
+pub async fn get_effective_override(
+    State(state): State<AppState>,
+    Query(params): Query<EffectiveQuery>,
+) -> Result<Json<Value>, AppError> {
+    let resolved = state
+        .edge_processor
+        .overrides()
+        .resolve(&params.device_id, &params.location);
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": resolved,
+    })))
+}
+
+/// Handler para crear o reemplazar un ajuste
+/// PUT /api/v1/admin/overrides/{scope}/{target}
+pub async fn upsert_override(
+    State(state): State<AppState>,
+    Path((scope, target)): Path<(OverrideScope, String)>,
+    Json(input): Json<DeviceOverrideInput>,
+) -> Result<Json<Value>, AppError> {
+    validate_input(&state, &input).await?;
+
+    state
+        .db
+        .upsert_device_override(scope, &target, &input)
+        .await?;
+    reload_overrides(&state).await?;
+
+    tracing::info!(
+        scope = scope.as_str(),
+        target = %target,
+        "Ajuste de dispositivo guardado"
+    );
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Ajuste guardado",
+        "data": state.db.get_device_override(scope, &target).await?,
+    })))
+}
+
+/// Handler para eliminar un ajuste
+/// DELETE /api/v1/admin/overrides/{scope}/{target}
+pub async fn delete_override(
+    State(state): State<AppState>,
+    Path((scope, target)): Path<(OverrideScope, String)>,
+) -> Result<Json<Value>, AppError> {
+    if !state.db.delete_device_override(scope, &target).await? {
+        return Err(AppError::NotFound(format!(
+            "Ajuste {}/{} no existe",
+            scope.as_str(),
+            target
+        )));
+    }
+    reload_overrides(&state).await?;
+
+    tracing::info!(
+        scope = scope.as_str(),
+        target = %target,
+        "Ajuste de dispositivo eliminado"
+    );
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Ajuste eliminado",
+    })))
+}
@@ -0,0 +1,125 @@
+use crate::{error::AppError, models::DeviceProfileInput, startup::state::AppState};
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use serde_json::{Value, json};
+use validator::Validate;
+
+/// Valida la entrada de un perfil
+fn validate_input(input: &DeviceProfileInput) -> Result<(), AppError> {
+    input
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    for expected in &input.measurements {
+        if let (Some(min), Some(max)) = (expected.min, expected.max)
+            && min > max
+        {
+            return Err(AppError::ValidationError(format!(
+                "Rango inválido para {}: min mayor que max",
+                expected.measurement
+            )));
+        }
+
+        let repeated = input
+            .measurements
+            .iter()
+            .filter(|m| m.measurement.eq_ignore_ascii_case(&expected.measurement))
+            .count()
+            > 1;
+        if repeated {
+            return Err(AppError::ValidationError(format!(
+                "Medición repetida en el perfil: {}",
+                expected.measurement
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Recarga los perfiles en el procesador edge tras un cambio
+async fn reload_profiles(state: &AppState) -> Result<(), AppError> {
+    state
+        .edge_processor
+        .set_profiles(state.db.list_device_profiles().await?);
+    Ok(())
+}
+
+/// Handler para listar perfiles de tipo de dispositivo
+/// GET /api/v1/admin/profiles
+pub async fn list_profiles(State(state): State<AppState>) -> Result<Json<Value>, AppError> {
+    let profiles = state.db.list_device_profiles().await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "count": profiles.len(),
+        "data": profiles,
+    })))
+}
+
+/// Handler para obtener un perfil
+/// GET /api/v1/admin/profiles/{device_type}
+pub async fn get_profile(
+    State(state): State<AppState>,
+    Path(device_type): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let profile = state
+        .db
+        .get_device_profile(&device_type)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Perfil {} no existe", device_type)))?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": profile,
+    })))
+}
+
+/// Handler para crear o reemplazar un perfil
+/// PUT /api/v1/admin/profiles/{device_type}
+pub async fn upsert_profile(
+    State(state): State<AppState>,
+    Path(device_type): Path<String>,
+    Json(input): Json<DeviceProfileInput>,
+) -> Result<Json<Value>, AppError> {
+    validate_input(&input)?;
+
+    state.db.upsert_device_profile(&device_type, &input).await?;
+    reload_profiles(&state).await?;
+
+    tracing::info!(
+        device_type = %device_type,
+        measurements = input.measurements.len(),
+        "Perfil de dispositivo guardado"
+    );
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Perfil guardado",
+        "data": state.db.get_device_profile(&device_type).await?,
+    })))
+}
+
+/// Handler para eliminar un perfil
+/// DELETE /api/v1/admin/profiles/{device_type}
+pub async fn delete_profile(
+    State(state): State<AppState>,
+    Path(device_type): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    if !state.db.delete_device_profile(&device_type).await? {
+        return Err(AppError::NotFound(format!(
+            "Perfil {} no existe",
+            device_type
+        )));
+    }
+    reload_profiles(&state).await?;
+
+    tracing::info!(device_type = %device_type, "Perfil de dispositivo eliminado");
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Perfil eliminado",
+    })))
+}
@@ -1,14 +1,141 @@
-use crate::{error::AppError, startup::state::AppState};
+use crate::{
+    database::{GroupBy, MetricFilter, QualityFilter},
+    error::AppError,
+    models::SensorStatistics,
+    startup::state::AppState,
+};
 use axum::{
     Json,
     extract::{Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
 };
-use serde::Deserialize;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Deserializer};
 use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+
+/// Máximo de dispositivos o ubicaciones por consulta
+const MAX_FILTER_VALUES: usize = 50;
+
+/// Combina el parámetro simple y la lista separada por comas, sin repetidos
+fn merge_values(
+    name: &str,
+    single: &Option<String>,
+    list: &Option<String>,
+) -> Result<Vec<String>, AppError> {
+    let mut values: Vec<String> = Vec::new();
+
+    let candidates = single
+        .iter()
+        .map(String::as_str)
+        .chain(list.iter().flat_map(|list| list.split(',')))
+        .map(str::trim)
+        .filter(|value| !value.is_empty());
+
+    for value in candidates {
+        if !values.iter().any(|v| v == value) {
+            values.push(value.to_string());
+        }
+    }
+
+    if values.len() > MAX_FILTER_VALUES {
+        return Err(AppError::ValidationError(format!(
+            "El parámetro '{}' admite como máximo {} valores",
+            name, MAX_FILTER_VALUES
+        )));
+    }
+
+    Ok(values)
+}
+
+/// Agrupa los resultados por dispositivo o ubicación conservando su orden
+fn group_results<T, F>(items: Vec<T>, key: F) -> BTreeMap<String, Vec<T>>
+where
+    F: Fn(&T) -> String,
+{
+    let mut groups: BTreeMap<String, Vec<T>> = BTreeMap::new();
+    for item in items {
+        groups.entry(key(&item)).or_default().push(item);
+    }
+    groups
+}
+
+/// Lee un número opcional que llega como texto en la query string
+///
+/// Necesario en structs aplanados con `#[serde(flatten)]`, donde los valores
+/// no se convierten automáticamente al tipo numérico
+fn optional_number<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    Option::<String>::deserialize(deserializer)?
+        .filter(|value| !value.is_empty())
+        .map(|value| value.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// Filtro por las etiquetas de grupo de los dispositivos
+#[derive(Debug, Default, Deserialize)]
+pub struct TagQuery {
+    pub tag: Option<String>,
+    /// Varias etiquetas separadas por coma (dispositivos con alguna de ellas)
+    pub tags: Option<String>,
+}
+
+impl TagQuery {
+    pub fn to_tags(&self) -> Result<Vec<String>, AppError> {
+        merge_values("tags", &self.tag, &self.tags)
+    }
+}
+
+/// Filtros por calidad de las lecturas
+#[derive(Debug, Default, Deserialize)]
+pub struct QualityQuery {
+    /// Score de calidad mínimo (inclusive)
+    #[serde(default, deserialize_with = "optional_number")]
+    pub min_quality: Option<u8>,
+    /// Score de calidad máximo (inclusive)
+    #[serde(default, deserialize_with = "optional_number")]
+    pub max_quality: Option<u8>,
+    /// Texto contenido en alguno de los issues de calidad
+    pub issue: Option<String>,
+}
+
+impl QualityQuery {
+    pub fn to_filter(&self) -> Result<QualityFilter, AppError> {
+        if let (Some(min), Some(max)) = (self.min_quality, self.max_quality)
+            && min > max
+        {
+            return Err(AppError::ValidationError(
+                "El parámetro 'min_quality' no puede superar a 'max_quality'".to_string(),
+            ));
+        }
+
+        Ok(QualityFilter {
+            min_score: self.min_quality,
+            max_score: self.max_quality,
+            issue: self
+                .issue
+                .as_deref()
+                .map(str::trim)
+                .filter(|issue| !issue.is_empty())
+                .map(String::from),
+        })
+    }
+}
 
 #[derive(Debug, Deserialize)]
 pub struct RecentDataQuery {
     pub sensor_id: Option<String>,
+    /// Varios dispositivos separados por coma (resultados agrupados por dispositivo)
+    pub device_ids: Option<String>,
+    #[serde(flatten)]
+    pub tags: TagQuery,
+    #[serde(flatten)]
+    pub quality: QualityQuery,
     #[serde(default = "default_limit")]
     pub limit: usize,
 }
@@ -17,14 +144,174 @@ fn default_limit() -> usize {
     20
 }
 
+/// Filtros comunes para estadísticas y series temporales
+#[derive(Debug, Deserialize)]
+pub struct MetricsQuery {
+    pub device_id: Option<String>,
+    /// Varios dispositivos separados por coma
+    pub device_ids: Option<String>,
+    pub location: Option<String>,
+    /// Varias ubicaciones separadas por coma
+    pub locations: Option<String>,
+    #[serde(flatten)]
+    pub tags: TagQuery,
+    pub measurement: Option<String>,
+    /// Inicio del periodo (por defecto, últimas 24 horas)
+    pub from: Option<DateTime<Utc>>,
+    /// Fin del periodo (por defecto, ahora)
+    pub to: Option<DateTime<Utc>>,
+    /// Agrupación de los resultados (por defecto, por dispositivo o ubicación
+    /// si se consultan varios)
+    pub group_by: Option<GroupBy>,
+    #[serde(flatten)]
+    pub quality: QualityQuery,
+}
+
+impl MetricsQuery {
+    pub fn to_filter(&self) -> Result<MetricFilter, AppError> {
+        let to = self.to.unwrap_or_else(Utc::now);
+        let from = self.from.unwrap_or(to - Duration::hours(24));
+
+        if from > to {
+            return Err(AppError::ValidationError(
+                "El parámetro 'from' debe ser anterior a 'to'".to_string(),
+            ));
+        }
+
+        Ok(MetricFilter {
+            device_ids: merge_values("device_ids", &self.device_id, &self.device_ids)?,
+            locations: merge_values("locations", &self.location, &self.locations)?,
+            tags: self.tags.to_tags()?,
+            measurement: self.measurement.clone(),
+            from,
+            to,
+            quality: self.quality.to_filter()?,
+        })
+    }
+
+    /// Agrupación solicitada o implícita según el filtro
+    pub fn grouping(&self, filter: &MetricFilter) -> Option<GroupBy> {
+        self.group_by.or_else(|| filter.implied_group())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RangeQuery {
+    #[serde(flatten)]
+    pub filter: MetricsQuery,
+    #[serde(default = "default_range_limit")]
+    pub limit: usize,
+}
+
+fn default_range_limit() -> usize {
+    1000
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LatestQuery {
+    pub device_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AnomaliesQuery {
+    pub device_id: Option<String>,
+    pub device_ids: Option<String>,
+    pub location: Option<String>,
+    pub locations: Option<String>,
+    #[serde(flatten)]
+    pub tags: TagQuery,
+    /// Inicio del periodo (por defecto, últimas 24 horas)
+    pub from: Option<DateTime<Utc>>,
+    /// Fin del periodo (por defecto, ahora)
+    pub to: Option<DateTime<Utc>>,
+    pub group_by: Option<GroupBy>,
+    #[serde(flatten)]
+    pub quality: QualityQuery,
+    #[serde(default = "default_anomalies_limit")]
+    pub limit: usize,
+}
+
+fn default_anomalies_limit() -> usize {
+    100
+}
+
 /// Handler para obtener datos recientes
 /// GET /api/v1/data/recent?sensor_id=XXX&limit=20
+/// GET /api/v1/data/recent?device_ids=a,b,c&limit=20
+/// GET /api/v1/data/recent?max_quality=69&issue=NaN&limit=20
+/// GET /api/v1/data/recent?tags=greenhouse-3&limit=20
 ///
 /// Útil para debugging y monitoreo local
 pub async fn get_recent_data(
     State(state): State<AppState>,
     Query(params): Query<RecentDataQuery>,
 ) -> Result<Json<Value>, AppError> {
+    let quality = params.quality.to_filter()?;
+    let tags = params.tags.to_tags()?;
+
+    // Con filtros de calidad se consulta SQLite: la caché solo guarda las últimas lecturas
+    if !quality.is_empty() {
+        let filter = MetricFilter {
+            device_ids: merge_values("device_ids", &params.sensor_id, &params.device_ids)?,
+            locations: Vec::new(),
+            tags,
+            measurement: None,
+            from: DateTime::UNIX_EPOCH,
+            to: Utc::now(),
+            quality,
+        };
+        let data = state.db.get_recent_matching(&filter, params.limit).await?;
+
+        let mut response = json!({
+            "status": "success",
+            "count": data.len(),
+        });
+        match filter.implied_group() {
+            Some(group_by) => {
+                response["group_by"] = json!(group_by);
+                response["groups"] = json!(group_results(data, |reading| {
+                    reading.header.device_id.clone()
+                }));
+            }
+            None => response["data"] = json!(data),
+        }
+
+        return Ok(Json(response));
+    }
+
+    if params.device_ids.is_some() || !tags.is_empty() {
+        let mut device_ids = merge_values("device_ids", &params.sensor_id, &params.device_ids)?;
+
+        // Con etiquetas se consultan los dispositivos del grupo (o los indicados que pertenecen a él)
+        if !tags.is_empty() {
+            let tagged = state.db.device_ids_with_tags(&tags).await?;
+            device_ids = if device_ids.is_empty() {
+                tagged
+            } else {
+                device_ids
+                    .into_iter()
+                    .filter(|device_id| tagged.contains(device_id))
+                    .collect()
+            };
+        }
+
+        let mut groups = BTreeMap::new();
+        for device_id in device_ids {
+            let data = state
+                .db
+                .get_recent_readings(&device_id, params.limit)
+                .await?;
+            groups.insert(device_id, data);
+        }
+
+        return Ok(Json(json!({
+            "status": "success",
+            "group_by": GroupBy::Device,
+            "count": groups.values().map(Vec::len).sum::<usize>(),
+            "groups": groups,
+        })));
+    }
+
     let data = if let Some(sensor_id) = params.sensor_id {
         state
             .db
@@ -42,16 +329,224 @@ pub async fn get_recent_data(
     })))
 }
 
+/// Formato de fecha HTTP (RFC 7231) para Last-Modified
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// Indica si la copia del cliente sigue vigente según If-None-Match o,
+/// en su ausencia, If-Modified-Since
+fn not_modified(headers: &HeaderMap, etag: &str, last_modified: DateTime<Utc>) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+        return if_none_match.to_str().is_ok_and(|value| {
+            value
+                .split(',')
+                .map(str::trim)
+                .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+        });
+    }
+
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok())
+        .is_some_and(|since| last_modified.timestamp() <= since.timestamp())
+}
+
+/// Handler para obtener la última lectura de un dispositivo
+/// GET /api/v1/data/latest?device_id=XXX
+///
+/// Se sirve desde la caché en memoria de últimas lecturas y admite peticiones
+/// condicionales (ETag / Last-Modified): los kioscos que consultan cada pocos
+/// segundos reciben 304 sin cuerpo mientras no llegue una lectura nueva
+pub async fn get_latest(
+    State(state): State<AppState>,
+    Query(params): Query<LatestQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let reading = state
+        .db
+        .get_recent_readings(&params.device_id, 1)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "No hay lecturas del dispositivo {}",
+                params.device_id
+            ))
+        })?;
+
+    let etag = format!("\"{}\"", reading.id);
+    let last_modified = reading.gateway_timestamp;
+
+    let mut response_headers = HeaderMap::new();
+    response_headers.insert(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    if let Ok(value) = HeaderValue::from_str(&etag) {
+        response_headers.insert(header::ETAG, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&last_modified.format(HTTP_DATE_FORMAT).to_string()) {
+        response_headers.insert(header::LAST_MODIFIED, value);
+    }
+
+    if not_modified(&headers, &etag, last_modified) {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+
+    Ok((
+        response_headers,
+        Json(json!({
+            "status": "success",
+            "data": reading,
+        })),
+    )
+        .into_response())
+}
+
 /// Handler para obtener estadísticas
-/// GET /api/v1/data/stats
-pub async fn get_statistics(State(state): State<AppState>) -> Result<Json<Value>, AppError> {
+/// GET /api/v1/data/stats?device_ids=a,b&locations=&tags=&measurement=&from=&to=&group_by=device|location
+///
+/// Incluye min/max/avg por medición en el periodo consultado y, si se agrupa,
+/// el mismo resumen para cada dispositivo o ubicación
+pub async fn get_statistics(
+    State(state): State<AppState>,
+    Query(params): Query<MetricsQuery>,
+) -> Result<Json<Value>, AppError> {
+    let filter = params.to_filter()?;
+    let group_by = params.grouping(&filter);
     let pending_sync = state.db.count_pending_sync().await?;
+    let devices = state.db.list_devices().await?;
+
+    let groups = match group_by {
+        Some(group_by) => Some(state.db.grouped_summary(&filter, group_by).await?),
+        None => None,
+    };
+
+    let period = SensorStatistics {
+        count: state.db.count_readings_in(&filter).await? as u32,
+        metrics_summary: state.db.metric_summary(&filter).await?,
+        device_ids: filter.device_ids,
+        locations: filter.locations,
+        period_start: filter.from,
+        period_end: filter.to,
+    };
 
     Ok(Json(json!({
         "status": "success",
         "statistics": {
             "pending_sync": pending_sync,
             "gateway_id": state.config.gateway_id,
+            "devices_count": devices.len(),
+            "devices": devices,
+            "period": period,
+            "group_by": group_by,
+            "groups": groups,
         }
     })))
 }
+
+/// Handler para obtener la serie temporal de una o varias métricas
+/// GET /api/v1/data/range?measurement=Temperature&locations=sala,cocina&tags=&from=&to=&limit=1000
+///
+/// Con varios dispositivos o ubicaciones (o `group_by`) las series se agrupan;
+/// el límite aplica al total de puntos
+pub async fn get_range(
+    State(state): State<AppState>,
+    Query(params): Query<RangeQuery>,
+) -> Result<Json<Value>, AppError> {
+    let filter = params.filter.to_filter()?;
+    let group_by = params.filter.grouping(&filter);
+    let points = state.db.metric_range(&filter, params.limit).await?;
+
+    let mut response = json!({
+        "status": "success",
+        "count": points.len(),
+        "from": filter.from,
+        "to": filter.to,
+    });
+
+    match group_by {
+        Some(group_by) => {
+            response["group_by"] = json!(group_by);
+            response["groups"] = json!(group_results(points, |point| {
+                group_by.key(&point.device_id, &point.location).to_string()
+            }));
+        }
+        None => response["data"] = json!(points),
+    }
+
+    Ok(Json(response))
+}
+
+/// Handler para consultar lecturas anómalas
+/// GET /api/v1/data/anomalies?device_ids=&locations=&tags=&from=&to=&limit=100&group_by=
+///
+/// Retorna las métricas de cada lectura, los indicadores que la marcaron
+/// (z-scores) y los issues de calidad, para investigar incidentes
+pub async fn get_anomalies(
+    State(state): State<AppState>,
+    Query(params): Query<AnomaliesQuery>,
+) -> Result<Json<Value>, AppError> {
+    let query = MetricsQuery {
+        device_id: params.device_id,
+        device_ids: params.device_ids,
+        location: params.location,
+        locations: params.locations,
+        tags: params.tags,
+        measurement: None,
+        from: params.from,
+        to: params.to,
+        group_by: params.group_by,
+        quality: params.quality,
+    };
+    let filter = query.to_filter()?;
+    let group_by = query.grouping(&filter);
+
+    let readings = state.db.get_anomalies(&filter, params.limit).await?;
+
+    let data: Vec<Value> = readings
+        .into_iter()
+        .map(|reading| {
+            let indicators: HashMap<&String, &f32> = reading
+                .computed
+                .stats
+                .iter()
+                .filter(|(name, _)| name.ends_with("_zscore"))
+                .collect();
+
+            json!({
+                "id": reading.id,
+                "device_id": reading.header.device_id,
+                "location": reading.header.location,
+                "gateway_timestamp": reading.gateway_timestamp,
+                "measured_at": reading.measured_at,
+                "metrics": reading.metrics,
+                "indicators": indicators,
+                "quality_score": reading.quality.score,
+                "quality_issues": reading.quality.issues,
+            })
+        })
+        .collect();
+
+    let mut response = json!({
+        "status": "success",
+        "count": data.len(),
+        "from": filter.from,
+        "to": filter.to,
+    });
+
+    match group_by {
+        Some(group_by) => {
+            response["group_by"] = json!(group_by);
+            response["groups"] = json!(group_results(data, |reading| {
+                group_by
+                    .key(
+                        reading["device_id"].as_str().unwrap_or_default(),
+                        reading["location"].as_str().unwrap_or_default(),
+                    )
+                    .to_string()
+            }));
+        }
+        None => response["data"] = json!(data),
+    }
+
+    Ok(Json(response))
+}
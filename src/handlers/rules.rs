@@ -0,0 +1,172 @@
+use super::devices::validate_tag;
+use crate::{
+    error::AppError,
+    models::{MeasurementRange, RuleAction, RuleInput},
+    startup::state::AppState,
+};
+use axum::{
+    Json,
+    extract::{Path, State},
+};
+use serde_json::{Value, json};
+
+/// Handler para consultar los rangos de validez por medición
+/// GET /api/v1/admin/rules/ranges
+pub async fn get_ranges(State(state): State<AppState>) -> Result<Json<Value>, AppError> {
+    let ranges = state.db.list_measurement_ranges().await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "count": ranges.len(),
+        "data": ranges,
+    })))
+}
+
+/// Handler para reemplazar los rangos de validez por medición
+/// PUT /api/v1/admin/rules/ranges
+///
+/// Recibe el conjunto completo de rangos; se aplican de inmediato
+/// a las nuevas lecturas sin reiniciar el gateway
+pub async fn put_ranges(
+    State(state): State<AppState>,
+    Json(ranges): Json<Vec<MeasurementRange>>,
+) -> Result<Json<Value>, AppError> {
+    MeasurementRange::check_all(&ranges).map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    state.db.replace_measurement_ranges(&ranges).await?;
+    state
+        .edge_processor
+        .set_ranges(state.db.list_measurement_ranges().await?);
+
+    tracing::info!(count = ranges.len(), "Rangos de validez actualizados");
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Rangos de validez actualizados",
+        "count": ranges.len(),
+    })))
+}
+
+/// Valida la entrada de una regla de acciones
+fn validate_rule(state: &AppState, input: &RuleInput) -> Result<(), AppError> {
+    input
+        .check()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+    if let Some(tag) = &input.tag {
+        validate_tag(tag)?;
+    }
+    for action in &input.actions {
+        if let RuleAction::Gpio { output } = action
+            && !state.gpio.contains(output)
+        {
+            return Err(AppError::ValidationError(format!(
+                "La salida GPIO {} no está configurada en GPIO_OUTPUTS",
+                output
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Recarga las reglas en el procesador edge tras un cambio
+async fn reload_rules(state: &AppState) -> Result<(), AppError> {
+    state.edge_processor.set_rules(state.db.list_rules().await?);
+    Ok(())
+}
+
+/// Handler para listar las reglas de acciones
+/// GET /api/v1/admin/rules
+pub async fn list_rules(State(state): State<AppState>) -> Result<Json<Value>, AppError> {
+    let rules = state.db.list_rules().await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "count": rules.len(),
+        "data": rules,
+    })))
+}
+
+/// Handler para obtener una regla
+/// GET /api/v1/admin/rules/{id}
+pub async fn get_rule(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    let rule = state
+        .db
+        .get_rule(&id)
+        .await?
+        .ok_or_else(|| AppError::NotFound(format!("Regla {} no existe", id)))?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "data": rule,
+    })))
+}
+
+/// Handler para crear una regla
+/// POST /api/v1/admin/rules
+///
+/// Ejemplo: `{"name": "Invernadero caliente", "measurement": "Temperature",
+/// "location": "invernadero", "operator": "gt", "threshold": 35, "for_secs": 300,
+/// "actions": [{"type": "alert", "severity": "warning"}, {"type": "flag"}]}`
+pub async fn create_rule(
+    State(state): State<AppState>,
+    Json(input): Json<RuleInput>,
+) -> Result<Json<Value>, AppError> {
+    validate_rule(&state, &input)?;
+
+    let id = uuid::Uuid::new_v4().to_string();
+    state.db.insert_rule(&id, &input).await?;
+    reload_rules(&state).await?;
+
+    tracing::info!(id = %id, name = %input.name, "Regla creada");
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Regla creada",
+        "data": state.db.get_rule(&id).await?,
+    })))
+}
+
+/// Handler para actualizar una regla
+/// PUT /api/v1/admin/rules/{id}
+pub async fn update_rule(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+    Json(input): Json<RuleInput>,
+) -> Result<Json<Value>, AppError> {
+    validate_rule(&state, &input)?;
+
+    if !state.db.update_rule(&id, &input).await? {
+        return Err(AppError::NotFound(format!("Regla {} no existe", id)));
+    }
+    reload_rules(&state).await?;
+
+    tracing::info!(id = %id, "Regla actualizada");
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Regla actualizada",
+        "data": state.db.get_rule(&id).await?,
+    })))
+}
+
+/// Handler para eliminar una regla
+/// DELETE /api/v1/admin/rules/{id}
+pub async fn delete_rule(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<Value>, AppError> {
+    if !state.db.delete_rule(&id).await? {
+        return Err(AppError::NotFound(format!("Regla {} no existe", id)));
+    }
+    reload_rules(&state).await?;
+
+    tracing::info!(id = %id, "Regla eliminada");
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Regla eliminada",
+    })))
+}
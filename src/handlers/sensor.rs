@@ -1,11 +1,20 @@
-use axum::{Json, extract::State};
+use axum::{
+    Json,
+    extract::{ConnectInfo, State},
+    http::HeaderMap,
+};
+use chrono::Utc;
 use serde_json::{Value, json};
+use std::collections::BTreeSet;
+use std::net::{IpAddr, SocketAddr};
 use validator::Validate;
 
 use crate::{
+    config::DeviceAuth,
+    database::ReplayRejection,
     error::AppError,
-    models::{SensorDataBatch, SensorDataInput},
-    startup::state::AppState,
+    models::{SensorDataBatch, SensorDataBatchV2, SensorDataInput, SensorDataInputV2},
+    startup::{auth, state::AppState},
 };
 
 /// Handler para recibir datos individuales de un sensor
@@ -15,12 +24,51 @@ use crate::{
 /// Aplica procesamiento edge computing y almacena localmente
 pub async fn ingest_sensor_data(
     State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(payload): Json<SensorDataInput>,
+) -> Result<Json<Value>, AppError> {
+    ingest_reading(&state, auth::bearer_token(&headers), client.ip(), payload).await
+}
+
+/// Handler para recibir datos individuales en el formato v2
+/// POST /api/v2/sensor/data
+///
+/// Métricas como mapa `medición → valor`; se convierten al formato interno
+/// y siguen el mismo procesamiento que `/api/v1`
+pub async fn ingest_sensor_data_v2(
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<SensorDataInputV2>,
+) -> Result<Json<Value>, AppError> {
+    payload
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    ingest_reading(
+        &state,
+        auth::bearer_token(&headers),
+        client.ip(),
+        payload.into(),
+    )
+    .await
+}
+
+/// Valida, procesa y almacena una lectura individual
+///
+/// Compartido con el servidor CoAP; `token` es el token del dispositivo, si lo envió
+pub(crate) async fn ingest_reading(
+    state: &AppState,
+    token: Option<&str>,
+    client: IpAddr,
+    payload: SensorDataInput,
 ) -> Result<Json<Value>, AppError> {
     // Validar entrada
     payload
         .validate()
         .map_err(|e| AppError::ValidationError(e.to_string()))?;
+    authorize_readings(state, token, client, std::slice::from_ref(&payload)).await?;
 
     // tracing::info!(
     //     sensor_id = %payload.sensor_id,
@@ -29,8 +77,60 @@ pub async fn ingest_sensor_data(
     //     "📡 Recibiendo datos de sensor"
     // );
 
-    // Procesar datos con edge computing
-    let processed = state.edge_processor.process_reading(payload).await;
+    if state.db.is_decommissioned(&payload.header.device_id) {
+        return Err(AppError::Forbidden(format!(
+            "Dispositivo {} dado de baja",
+            payload.header.device_id
+        )));
+    }
+
+    // Descartar retransmisiones de un mensaje ya almacenado
+    if state.db.is_duplicate(&payload.header).await? {
+        tracing::debug!(
+            device_id = %payload.header.device_id,
+            message_id = ?payload.header.message_id,
+            "Mensaje duplicado descartado"
+        );
+
+        return Ok(Json(json!({
+            "status": "duplicate",
+            "message": "Mensaje duplicado ignorado",
+            "data": {
+                "message_id": payload.header.message_id,
+            }
+        })));
+    }
+
+    // Rechazar mensajes capturados y reenviados
+    if let Err(rejection) = state.db.check_replay(&payload.header, Utc::now()) {
+        return Err(match rejection {
+            ReplayRejection::Missing => AppError::ValidationError(rejection.message().to_string()),
+            _ => AppError::Conflict(format!("Mensaje rechazado: {}", rejection.message())),
+        });
+    }
+
+    // Los dispositivos desconocidos esperan aprobación con sus lecturas en cuarentena
+    let device_id = payload.header.device_id.clone();
+    let (mut approved, _) = state
+        .db
+        .quarantine_unapproved(vec![payload], Utc::now())
+        .await?;
+    let Some(payload) = approved.pop() else {
+        return Ok(Json(json!({
+            "status": "quarantined",
+            "message": "Dispositivo pendiente de aprobación, lectura en cuarentena",
+        })));
+    };
+
+    // Procesar datos con edge computing, salvo que la cuota del dispositivo la descarte
+    let Some(processed) = state.edge_processor.ingest_reading(payload).await else {
+        tracing::debug!(device_id = %device_id, "Lectura descartada por cuota");
+
+        return Ok(Json(json!({
+            "status": "throttled",
+            "message": "Cuota del dispositivo excedida, lectura descartada",
+        })));
+    };
 
     // Registrar anomalías detectadas
     // if processed.computed.is_anomaly {
@@ -45,22 +145,14 @@ pub async fn ingest_sensor_data(
 
     // Verificar si es necesario sincronizar con la nube
     let pending_count = state.db.count_pending_sync().await?;
-    if pending_count >= state.config.cloud_sync_batch_size.into() {
+    if pending_count >= state.runtime_config.current().cloud_sync_batch_size.into() {
         tracing::info!(
             pending = pending_count,
             "Iniciando sincronización con cloud"
         );
 
         // Disparar sincronización asíncrona
-        let cloud_sync = state.cloud_sync.clone();
-        let db = state.db.clone();
-        tokio::spawn(async move {
-            let mut cs = cloud_sync.lock().await;
-
-            if let Err(e) = cs.sync_data(db).await {
-                tracing::error!("Error en sincronización: {}", e);
-            }
-        });
+        state.cloud_sync.sync_now();
     }
 
     // Responder al ESP32 con confirmación y métricas procesadas
@@ -70,10 +162,16 @@ pub async fn ingest_sensor_data(
         "data": {
             "id": processed.id,
             "gateway_timestamp": processed.gateway_timestamp,
+            "measured_at": processed.measured_at,
             "computed_metrics": {
                 "heat_index": processed.computed.heat_index,
                 "dew_point": processed.computed.dew_point,
                 "comfort_level": processed.computed.comfort_level,
+                "absolute_humidity": processed.computed.absolute_humidity,
+                "vapor_pressure_deficit": processed.computed.vapor_pressure_deficit,
+                "wet_bulb": processed.computed.wet_bulb,
+                "air_quality_index": processed.computed.air_quality_index,
+                "ventilation_score": processed.computed.ventilation_score,
                 "is_anomaly": processed.computed.is_anomaly,
             },
             "quality_score": processed.quality.score,
@@ -89,17 +187,69 @@ pub async fn ingest_sensor_data(
 /// Útil cuando el sensor acumula datos offline
 pub async fn ingest_batch_data(
     State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(payload): Json<SensorDataBatch>,
 ) -> Result<Json<Value>, AppError> {
     payload
         .validate()
         .map_err(|e| AppError::ValidationError(e.to_string()))?;
 
-    let batch_size = payload.readings.len();
-    tracing::info!(batch_size = batch_size, "Recibiendo batch de datos");
+    ingest_readings(
+        &state,
+        auth::bearer_token(&headers),
+        client.ip(),
+        payload.readings,
+    )
+    .await
+}
+
+/// Handler para recibir batch de datos en el formato v2
+/// POST /api/v2/sensor/batch
+pub async fn ingest_batch_data_v2(
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<SensorDataBatchV2>,
+) -> Result<Json<Value>, AppError> {
+    payload
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let readings = payload.readings.into_iter().map(Into::into).collect();
 
-    // Procesar todo el batch
-    let processed_batch = state.edge_processor.process_batch(payload.readings).await;
+    ingest_readings(&state, auth::bearer_token(&headers), client.ip(), readings).await
+}
+
+/// Verifica el token de los dispositivos y procesa un batch ya validado
+pub(crate) async fn ingest_readings(
+    state: &AppState,
+    token: Option<&str>,
+    client: IpAddr,
+    readings: Vec<SensorDataInput>,
+) -> Result<Json<Value>, AppError> {
+    authorize_readings(state, token, client, &readings).await?;
+    process_readings(state, readings).await
+}
+
+/// Descarta duplicados, procesa y almacena un batch ya validado y autorizado
+pub(crate) async fn process_readings(
+    state: &AppState,
+    readings: Vec<SensorDataInput>,
+) -> Result<Json<Value>, AppError> {
+    tracing::info!(batch_size = readings.len(), "Recibiendo batch de datos");
+
+    // Descartar lecturas ya almacenadas (reenvíos tras cortes de conexión)
+    let (readings, rejected) = state.db.reject_decommissioned(readings);
+    let (readings, duplicates) = state.db.filter_duplicates(readings).await?;
+    let (readings, replayed) = state.db.reject_replayed(readings, Utc::now());
+    let (readings, quarantined) = state.db.quarantine_unapproved(readings, Utc::now()).await?;
+    let received = readings.len();
+
+    // Procesar todo el batch (sin las lecturas descartadas por cuota)
+    let processed_batch = state.edge_processor.process_batch(readings).await;
+    let batch_size = processed_batch.len();
+    let throttled = received - batch_size;
 
     // Estadísticas del batch
     let mut anomalies = 0;
@@ -123,6 +273,11 @@ pub async fn ingest_batch_data(
 
     tracing::info!(
         processed = batch_size,
+        duplicates = duplicates,
+        rejected = rejected,
+        replayed = replayed,
+        quarantined = quarantined,
+        throttled = throttled,
         anomalies = anomalies,
         avg_quality = %avg_quality,
         "Batch procesado"
@@ -130,13 +285,8 @@ pub async fn ingest_batch_data(
 
     // Verificar sincronización
     let pending_count = state.db.count_pending_sync().await?;
-    if pending_count >= state.config.cloud_sync_batch_size.into() {
-        let cloud_sync = state.cloud_sync.clone();
-        let db = state.db.clone();
-        tokio::spawn(async move {
-            let mut cs = cloud_sync.lock().await;
-            let _ = cs.sync_data(db).await;
-        });
+    if pending_count >= state.runtime_config.current().cloud_sync_batch_size.into() {
+        state.cloud_sync.sync_now();
     }
 
     Ok(Json(json!({
@@ -144,6 +294,11 @@ pub async fn ingest_batch_data(
         "message": "Batch procesado correctamente",
         "data": {
             "processed_count": batch_size,
+            "duplicates_dropped": duplicates,
+            "rejected_count": rejected,
+            "replayed_count": replayed,
+            "quarantined_count": quarantined,
+            "throttled_dropped": throttled,
             "anomalies_detected": anomalies,
             "average_quality_score": avg_quality,
             "pending_sync": pending_count,
@@ -151,7 +306,101 @@ pub async fn ingest_batch_data(
     })))
 }
 
+/// Verifica el token del dispositivo según `DEVICE_AUTH`
+///
+/// Cada token identifica a un único dispositivo: todas las lecturas de la
+/// solicitud deben llevar su `deviceId`, de modo que un nodo no puede publicar
+/// en nombre de otro.
+async fn authorize_readings(
+    state: &AppState,
+    token: Option<&str>,
+    client: IpAddr,
+    readings: &[SensorDataInput],
+) -> Result<(), AppError> {
+    if state.config.device_auth == DeviceAuth::Off {
+        return Ok(());
+    }
+
+    // Los fallos cuentan para la IP y para el dispositivo de la primera lectura
+    let key = format!(
+        "device:{}",
+        readings
+            .first()
+            .map(|reading| reading.header.device_id.as_str())
+            .unwrap_or_default()
+    );
+    state.auth_lockout.check_ip(client)?;
+
+    let Some(token) = token else {
+        if state.config.device_auth == DeviceAuth::Required {
+            return Err(AppError::Unauthorized(
+                "Se requiere el token del dispositivo".to_string(),
+            ));
+        }
+
+        // En modo provisioned solo se exige a los dispositivos con credenciales
+        let device_ids: BTreeSet<&str> = readings
+            .iter()
+            .map(|reading| reading.header.device_id.as_str())
+            .collect();
+        let device_ids: Vec<&str> = device_ids.into_iter().collect();
+        if let Some(device_id) = state
+            .db
+            .devices_with_credentials(&device_ids)
+            .await?
+            .first()
+        {
+            return Err(AppError::Unauthorized(format!(
+                "El dispositivo {} requiere su token",
+                device_id
+            )));
+        }
+        return Ok(());
+    };
+
+    let owner = state
+        .db
+        .device_for_credential(&auth::hash_device_secret(token), Utc::now())
+        .await?;
+    let spoofed = owner.as_ref().and_then(|owner| {
+        readings
+            .iter()
+            .find(|reading| &reading.header.device_id != owner)
+    });
+    state
+        .auth_lockout
+        .check_key(client, &key, owner.is_some() && spoofed.is_none())?;
+
+    let Some(owner) = owner else {
+        state
+            .auth_lockout
+            .record_failure(client, &key, "token de dispositivo inválido");
+        return Err(AppError::Unauthorized(
+            "Token de dispositivo inválido".to_string(),
+        ));
+    };
+
+    if let Some(spoofed) = spoofed {
+        tracing::warn!(
+            device_id = %owner,
+            claimed = %spoofed.header.device_id,
+            "Lectura con un deviceId ajeno al token rechazada"
+        );
+        state
+            .auth_lockout
+            .record_failure(client, &key, "token de otro dispositivo");
+        return Err(AppError::Forbidden(format!(
+            "El token pertenece a {}, no a {}",
+            owner, spoofed.header.device_id
+        )));
+    }
+
+    state.auth_lockout.record_success(client, &key);
+    Ok(())
+}
+
 /// Estructura de respuesta genérica para éxito
+#[allow(dead_code)]
 #[derive(serde::Serialize)]
 pub struct SuccessResponse<T> {
     pub status: String,
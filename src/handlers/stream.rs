@@ -0,0 +1,83 @@
+use crate::{models::ProcessedSensorData, startup::state::AppState};
+use axum::{
+    extract::{
+        Query, State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    response::Response,
+};
+use serde::Deserialize;
+use tokio::sync::broadcast::error::RecvError;
+
+#[derive(Debug, Deserialize)]
+pub struct StreamQuery {
+    pub device_id: Option<String>,
+    pub location: Option<String>,
+    /// Solo lecturas anómalas
+    #[serde(default)]
+    pub anomalies_only: bool,
+}
+
+impl StreamQuery {
+    fn matches(&self, data: &ProcessedSensorData) -> bool {
+        self.device_id
+            .as_ref()
+            .is_none_or(|device_id| *device_id == data.header.device_id)
+            && self
+                .location
+                .as_ref()
+                .is_none_or(|location| *location == data.header.location)
+            && (!self.anomalies_only || data.computed.is_anomaly)
+    }
+}
+
+/// Handler del stream en vivo de lecturas procesadas (WebSocket)
+/// GET /api/v1/stream?device_id=&location=&anomalies_only=true
+pub async fn stream_readings(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Query(params): Query<StreamQuery>,
+) -> Response {
+    ws.on_upgrade(move |socket| forward_readings(socket, state, params))
+}
+
+/// Reenvía al cliente cada lectura procesada que cumpla el filtro
+async fn forward_readings(mut socket: WebSocket, state: AppState, params: StreamQuery) {
+    let mut readings = state.edge_processor.subscribe();
+    tracing::info!(?params, "Cliente conectado al stream en vivo");
+
+    loop {
+        tokio::select! {
+            reading = readings.recv() => match reading {
+                Ok(data) => {
+                    if !params.matches(&data) {
+                        continue;
+                    }
+
+                    let payload = match serde_json::to_string(&*data) {
+                        Ok(payload) => payload,
+                        Err(e) => {
+                            tracing::error!("Error serializando lectura para el stream: {}", e);
+                            continue;
+                        }
+                    };
+
+                    if socket.send(Message::Text(payload.into())).await.is_err() {
+                        break;
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    tracing::warn!(skipped, "Cliente del stream en vivo atrasado, lecturas omitidas");
+                }
+                Err(RecvError::Closed) => break,
+            },
+            message = socket.recv() => match message {
+                // Los mensajes del cliente se ignoran; solo se detecta el cierre
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+
+    tracing::info!("Cliente desconectado del stream en vivo");
+}
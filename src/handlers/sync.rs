@@ -0,0 +1,159 @@
+use super::query::TagQuery;
+use crate::{
+    error::AppError, models::SyncRequeueRequest, services::cloud_sync::SyncCommand,
+    startup::state::AppState,
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use validator::Validate;
+
+/// Máximo de lecturas por página de la cola de sincronización
+const MAX_PAGE_SIZE: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct PendingQuery {
+    /// Solo lecturas cuyo último intento falló
+    #[serde(default)]
+    pub failed_only: bool,
+    #[serde(flatten)]
+    pub tags: TagQuery,
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default = "default_pending_limit")]
+    pub limit: usize,
+}
+
+fn default_pending_limit() -> usize {
+    100
+}
+
+/// Handler para inspeccionar la cola de sincronización
+/// GET /api/v1/sync/pending?failed_only=false&tags=&offset=0&limit=100
+pub async fn list_pending(
+    State(state): State<AppState>,
+    Query(params): Query<PendingQuery>,
+) -> Result<Json<Value>, AppError> {
+    let limit = params.limit.clamp(1, MAX_PAGE_SIZE);
+
+    let entries = state
+        .db
+        .list_pending_sync(
+            params.failed_only,
+            &params.tags.to_tags()?,
+            params.offset,
+            limit,
+        )
+        .await?;
+
+    Ok(Json(json!({
+        "status": "success",
+        "total_pending": state.db.count_pending_sync().await?,
+        "total_failed": state.db.count_sync_failures().await?,
+        "paused": state.cloud_sync.is_paused(),
+        "offset": params.offset,
+        "limit": limit,
+        "count": entries.len(),
+        "data": entries,
+    })))
+}
+
+/// Handler para corregir manualmente lecturas atascadas en la cola
+/// POST /api/v1/sync/requeue
+///
+/// Reinicia los intentos de las lecturas indicadas o, con `mark_synced`,
+/// las marca como sincronizadas para que dejen de reenviarse
+pub async fn requeue(
+    State(state): State<AppState>,
+    Json(request): Json<SyncRequeueRequest>,
+) -> Result<Json<Value>, AppError> {
+    request
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+
+    let updated = if request.mark_synced {
+        state.db.force_mark_synced(&request.ids).await?
+    } else {
+        state.db.requeue_sync(&request.ids).await?
+    };
+
+    tracing::info!(
+        requested = request.ids.len(),
+        updated,
+        mark_synced = request.mark_synced,
+        "Cola de sincronización corregida manualmente"
+    );
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": if request.mark_synced {
+            "Lecturas marcadas como sincronizadas"
+        } else {
+            "Lecturas reencoladas"
+        },
+        "requested": request.ids.len(),
+        "updated": updated,
+    })))
+}
+
+/// Handler para sincronizar con el cloud sin esperar al intervalo
+/// POST /api/v1/sync/now
+pub async fn sync_now(State(state): State<AppState>) -> Result<Json<Value>, AppError> {
+    if !state.cloud_sync.is_running() {
+        return Err(sync_disabled());
+    }
+    if state.cloud_sync.is_paused() {
+        return Err(AppError::Conflict(
+            "La sincronización está pausada; reanudarla con POST /sync/resume".to_string(),
+        ));
+    }
+
+    state.cloud_sync.sync_now();
+    tracing::info!("Sincronización con el cloud solicitada manualmente");
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": "Sincronización solicitada",
+    })))
+}
+
+/// Handler para suspender o reanudar la sincronización con el cloud
+/// POST /api/v1/sync/pause, POST /api/v1/sync/resume
+///
+/// Las lecturas se siguen almacenando y encolando; al reanudar se
+/// sincroniza de inmediato. La pausa no sobrevive a un reinicio.
+pub async fn pause_sync(State(state): State<AppState>) -> Result<Json<Value>, AppError> {
+    set_paused(&state, true).await
+}
+
+pub async fn resume_sync(State(state): State<AppState>) -> Result<Json<Value>, AppError> {
+    set_paused(&state, false).await
+}
+
+async fn set_paused(state: &AppState, paused: bool) -> Result<Json<Value>, AppError> {
+    let command = if paused {
+        SyncCommand::Pause
+    } else {
+        SyncCommand::Resume
+    };
+    if !state.cloud_sync.send(command).await {
+        return Err(sync_disabled());
+    }
+
+    Ok(Json(json!({
+        "status": "success",
+        "message": if paused {
+            "Sincronización pausada"
+        } else {
+            "Sincronización reanudada"
+        },
+        "paused": paused,
+    })))
+}
+
+fn sync_disabled() -> AppError {
+    AppError::ServiceUnavailable("La sincronización con el cloud está deshabilitada".to_string())
+}
@@ -0,0 +1,80 @@
+use axum::{
+    Json,
+    extract::{ConnectInfo, Path, Query, State},
+    http::HeaderMap,
+};
+use serde::Deserialize;
+use serde_json::Value;
+use std::net::SocketAddr;
+use validator::Validate;
+
+use super::sensor::{ingest_readings, process_readings};
+use crate::{
+    error::AppError,
+    startup::{auth, state::AppState},
+};
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookQuery {
+    /// Alternativa a `Authorization: Bearer` para fuentes que no envían cabeceras
+    pub token: Option<String>,
+}
+
+/// Handler para recibir el payload propio de una fuente externa
+/// POST /api/v1/ingest/webhook/{source}
+///
+/// El mapeo de `WEBHOOK_MAPPINGS_DIR/<source>.json` convierte el payload en
+/// lecturas, que siguen el mismo procesamiento que `/api/v1/sensor/batch`.
+/// Una fuente con token en `WEBHOOK_TOKENS` se autentica con él; las demás
+/// usan el token del dispositivo según `DEVICE_AUTH`.
+pub async fn ingest_webhook(
+    State(state): State<AppState>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    Path(source): Path<String>,
+    Query(query): Query<WebhookQuery>,
+    headers: HeaderMap,
+    Json(payload): Json<Value>,
+) -> Result<Json<Value>, AppError> {
+    let mapping = state
+        .webhooks
+        .get(&source)
+        .ok_or_else(|| AppError::NotFound(format!("Fuente de webhooks sin mapeo: {}", source)))?;
+    let token = auth::bearer_token(&headers).or(query.token.as_deref());
+
+    let source_token = state
+        .config
+        .webhook_tokens
+        .iter()
+        .find(|entry| entry.name == source);
+    if let Some(expected) = source_token {
+        let key = format!("webhook:{}", source);
+        state.auth_lockout.check_ip(client.ip())?;
+        let valid = token.is_some_and(|token| {
+            auth::constant_time_eq(token.as_bytes(), expected.token.as_bytes())
+        });
+        state.auth_lockout.check_key(client.ip(), &key, valid)?;
+        if !valid {
+            state
+                .auth_lockout
+                .record_failure(client.ip(), &key, "token de webhook inválido");
+            return Err(AppError::Unauthorized(
+                "Token de la fuente de webhooks inválido".to_string(),
+            ));
+        }
+        state.auth_lockout.record_success(client.ip(), &key);
+    }
+
+    let readings = mapping.readings(&source, &payload)?;
+    for reading in &readings {
+        reading
+            .validate()
+            .map_err(|e| AppError::ValidationError(e.to_string()))?;
+    }
+    tracing::debug!(source = %source, readings = readings.len(), "Webhook recibido");
+
+    if source_token.is_some() {
+        process_readings(&state, readings).await
+    } else {
+        ingest_readings(&state, token, client.ip(), readings).await
+    }
+}
@@ -1,4 +1,5 @@
 mod app;
+mod cli;
 mod config;
 mod database;
 mod error;
@@ -6,8 +7,11 @@ mod handlers;
 mod models;
 mod services;
 mod startup;
+mod telemetry;
+
+use clap::Parser;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    app::bootstrap().await
+    cli::Cli::parse().run().await
 }
@@ -1,6 +1,6 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Datelike, Utc};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use uuid::Uuid;
 use validator::Validate;
 
@@ -14,6 +14,50 @@ pub struct SensorDataInput {
     /// Métricas del sensor (flexibles)
     #[validate(length(min = 1))]
     pub metrics: Vec<SensorMetric>,
+
+    /// Momento de la medición según el reloj del dispositivo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_timestamp: Option<DeviceTimestamp>,
+}
+
+/// Marca de tiempo enviada por el dispositivo
+/// RFC 3339, o numérica: epoch en segundos/milisegundos o `millis()` desde el arranque
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum DeviceTimestamp {
+    Date(DateTime<Utc>),
+    Number(f64),
+}
+
+/// Valores numéricos menores se interpretan como `millis()` desde el arranque
+const EPOCH_SECONDS_MIN: f64 = 1e9;
+/// Valores numéricos mayores o iguales están en milisegundos desde epoch
+const EPOCH_MILLIS_MIN: f64 = 1e12;
+/// Fechas anteriores corresponden a un RTC sin sincronizar (segundos desde el arranque)
+const MIN_VALID_YEAR: i32 = 2020;
+
+impl DeviceTimestamp {
+    /// Fecha absoluta representada, si no es un reloj basado en el uptime
+    pub fn absolute(&self) -> Option<DateTime<Utc>> {
+        match self {
+            DeviceTimestamp::Date(at) if at.year() >= MIN_VALID_YEAR => Some(*at),
+            DeviceTimestamp::Number(n) if *n >= EPOCH_MILLIS_MIN => {
+                DateTime::from_timestamp_millis(*n as i64)
+            }
+            DeviceTimestamp::Number(n) if *n >= EPOCH_SECONDS_MIN => {
+                DateTime::from_timestamp_millis((*n * 1000.0) as i64)
+            }
+            _ => None,
+        }
+    }
+
+    /// Indica si el valor es un reloj basado en el uptime del dispositivo
+    pub fn is_uptime(&self) -> bool {
+        match self {
+            DeviceTimestamp::Date(at) => at.year() < MIN_VALID_YEAR,
+            DeviceTimestamp::Number(n) => *n < EPOCH_SECONDS_MIN,
+        }
+    }
 }
 
 /// Header con información del dispositivo
@@ -28,6 +72,20 @@ pub struct SensorHeader {
     #[serde(rename = "deviceId")]
     pub device_id: String,
 
+    /// Tipo de dispositivo (selecciona el perfil de métricas esperadas)
+    #[validate(length(min = 1, max = 50))]
+    #[serde(
+        rename = "deviceType",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub device_type: Option<String>,
+
+    /// ID del mensaje asignado por el dispositivo (para descartar retransmisiones)
+    #[validate(length(min = 1, max = 100))]
+    #[serde(rename = "messageId", default, skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+
     /// Ubicación del sensor
     #[validate(length(min = 1, max = 200))]
     pub location: String,
@@ -38,6 +96,38 @@ pub struct SensorHeader {
     /// Si debe reencolar el mensaje
     #[serde(rename = "shouldRequeue")]
     pub should_requeue: bool,
+
+    /// Versión del firmware que ejecuta el nodo
+    #[validate(length(min = 1, max = 50))]
+    #[serde(
+        rename = "firmwareVersion",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub firmware_version: Option<String>,
+
+    /// Modelo de hardware del nodo (p. ej. `esp32-c3-devkitm-1`)
+    #[validate(length(min = 1, max = 100))]
+    #[serde(
+        rename = "hardwareModel",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub hardware_model: Option<String>,
+
+    /// Intensidad de la señal WiFi o radio en dBm
+    #[validate(range(min = -150, max = 0))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rssi: Option<i32>,
+
+    /// Número de secuencia del mensaje, creciente por dispositivo (anti-reenvío)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u64>,
+
+    /// Momento de envío según el dispositivo (RFC 3339 o epoch), distinto del
+    /// de la medición en lecturas acumuladas offline
+    #[serde(rename = "sentAt", default, skip_serializing_if = "Option::is_none")]
+    pub sent_at: Option<DeviceTimestamp>,
 }
 
 /// Métrica individual del sensor
@@ -49,6 +139,11 @@ pub struct SensorMetric {
 
     /// Valor de la medición
     pub value: f32,
+
+    /// Unidad de la medición (se normaliza a la unidad canónica en el gateway)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[validate(length(max = 20))]
+    pub unit: Option<String>,
 }
 
 /// Datos procesados y enriquecidos por el edge gateway
@@ -66,6 +161,11 @@ pub struct ProcessedSensorData {
     /// Timestamp de recepción en el gateway
     pub gateway_timestamp: DateTime<Utc>,
 
+    /// Momento de la medición: la marca del dispositivo corregida o, si no
+    /// envió una válida, la llegada al gateway. Ordena las series y las
+    /// lecturas acumuladas offline
+    pub measured_at: DateTime<Utc>,
+
     /// Datos calculados por edge computing
     pub computed: ComputedMetrics,
 
@@ -88,6 +188,26 @@ pub struct ComputedMetrics {
     /// Nivel de confort (0-100) si aplica
     pub comfort_level: Option<f32>,
 
+    /// Humedad absoluta en g/m³ (si hay temperatura y humedad)
+    #[serde(default)]
+    pub absolute_humidity: Option<f32>,
+
+    /// Déficit de presión de vapor en kPa (si hay temperatura y humedad)
+    #[serde(default)]
+    pub vapor_pressure_deficit: Option<f32>,
+
+    /// Temperatura de bulbo húmedo en °C (si hay temperatura y humedad)
+    #[serde(default)]
+    pub wet_bulb: Option<f32>,
+
+    /// Índice de calidad del aire (si hay PM2.5 o PM10), en la escala configurada
+    #[serde(default)]
+    pub air_quality_index: Option<f32>,
+
+    /// Recomendación de ventilación (0-100) según CO2
+    #[serde(default)]
+    pub ventilation_score: Option<f32>,
+
     /// Anomalía detectada (basado en histórico local)
     pub is_anomaly: bool,
 
@@ -119,6 +239,100 @@ pub struct ProcessedMetadata {
 
     /// Si el mensaje debe reencolar
     pub should_requeue: bool,
+
+    /// Momento de la medición según el dispositivo, ya corregido
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_timestamp: Option<DateTime<Utc>>,
+
+    /// Diferencia entre la llegada al gateway y el reloj del dispositivo (ms)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock_skew_ms: Option<i64>,
+
+    /// Validación contra el perfil del tipo de dispositivo
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<ProfileCheck>,
+
+    /// Prioridad de sincronización (mayor = antes)
+    #[serde(default)]
+    pub sync_priority: i32,
+
+    /// Si la lectura se reenvía al cloud o solo se guarda localmente
+    #[serde(default = "default_enabled")]
+    pub forward_to_cloud: bool,
+}
+
+/// Dispositivo del registro, provisionado o registrado a partir del tráfico ingerido
+#[derive(Debug, Serialize, Clone)]
+pub struct DeviceRecord {
+    /// ID del dispositivo
+    pub device_id: String,
+
+    /// Última ubicación reportada (la esperada si aún no envió datos)
+    pub location: String,
+
+    /// Primera lectura recibida (None si aún no envió datos)
+    pub first_seen: Option<DateTime<Utc>>,
+
+    /// Última lectura recibida
+    pub last_seen: Option<DateTime<Utc>>,
+
+    /// Momento del alta por la API de provisión (None si se registró solo por tráfico)
+    pub provisioned_at: Option<DateTime<Utc>>,
+
+    /// Ubicación esperada indicada al provisionar
+    pub expected_location: Option<String>,
+
+    /// Perfil de tipo de dispositivo asignado al provisionar
+    pub device_type: Option<String>,
+
+    /// El dispositivo tiene credenciales emitidas
+    pub has_credentials: bool,
+
+    /// `online`, `offline`, `provisioned` (aún no envió datos), `pending`
+    /// (desconocido, a la espera de aprobación) o `decommissioned` (dado de baja)
+    pub status: &'static str,
+
+    /// Momento en que se detectó el silencio del dispositivo
+    pub offline_since: Option<DateTime<Utc>>,
+
+    /// Número total de lecturas recibidas
+    pub message_count: i64,
+
+    /// Score de calidad de la última lectura
+    pub last_quality: Option<u8>,
+
+    /// Último nivel de batería reportado (`battery` o `vbat`), si se conoce
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery: Option<f32>,
+
+    /// Metadatos adicionales (topic, tipos de medición, etc.)
+    pub metadata: serde_json::Value,
+
+    /// Etiquetas de grupo (p. ej. `greenhouse-3`, `battery-powered`)
+    pub tags: Vec<String>,
+
+    /// Última versión de firmware informada por el nodo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub firmware_version: Option<String>,
+
+    /// Último modelo de hardware informado por el nodo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hardware_model: Option<String>,
+
+    /// Última intensidad de señal informada (dBm)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rssi: Option<i32>,
+
+    /// Lecturas en cuarentena a la espera de aprobación
+    pub quarantined: i64,
+
+    /// Momento de la baja del dispositivo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decommissioned_at: Option<DateTime<Utc>>,
+
+    /// Motivo indicado al dar de baja el dispositivo
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decommission_reason: Option<String>,
 }
 
 /// Batch de múltiples lecturas
@@ -129,11 +343,120 @@ pub struct SensorDataBatch {
     pub readings: Vec<SensorDataInput>,
 }
 
+/// Lectura en el formato de `/api/v2`
+///
+/// Campos planos en snake_case y métricas como mapa `medición → valor`, donde el
+/// valor es un número o un objeto `{value, unit}`.
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct SensorDataInputV2 {
+    #[validate(length(min = 1, max = 50))]
+    pub device_id: String,
+
+    #[validate(length(min = 1, max = 200))]
+    pub location: String,
+
+    #[validate(length(min = 1, max = 50))]
+    #[serde(default)]
+    pub device_type: Option<String>,
+
+    #[validate(length(min = 1, max = 100))]
+    #[serde(default)]
+    pub message_id: Option<String>,
+
+    /// Momento de la medición según el reloj del dispositivo
+    #[serde(default)]
+    pub timestamp: Option<DeviceTimestamp>,
+
+    #[validate(length(min = 1, max = 50))]
+    #[serde(default)]
+    pub firmware_version: Option<String>,
+
+    #[validate(length(min = 1, max = 100))]
+    #[serde(default)]
+    pub hardware_model: Option<String>,
+
+    /// Intensidad de la señal en dBm
+    #[validate(range(min = -150, max = 0))]
+    #[serde(default)]
+    pub rssi: Option<i32>,
+
+    /// Número de secuencia del mensaje, creciente por dispositivo
+    #[serde(default)]
+    pub sequence: Option<u64>,
+
+    /// Momento de envío según el dispositivo
+    #[serde(default)]
+    pub sent_at: Option<DeviceTimestamp>,
+
+    #[validate(length(min = 1))]
+    pub metrics: BTreeMap<String, MetricValueV2>,
+}
+
+/// Valor de una métrica en `/api/v2`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MetricValueV2 {
+    Value(f32),
+    Detailed {
+        value: f32,
+        #[serde(default)]
+        unit: Option<String>,
+    },
+}
+
+impl From<SensorDataInputV2> for SensorDataInput {
+    fn from(input: SensorDataInputV2) -> Self {
+        let metrics = input
+            .metrics
+            .into_iter()
+            .map(|(measurement, metric)| {
+                let (value, unit) = match metric {
+                    MetricValueV2::Value(value) => (value, None),
+                    MetricValueV2::Detailed { value, unit } => (value, unit),
+                };
+
+                SensorMetric {
+                    measurement,
+                    value,
+                    unit,
+                }
+            })
+            .collect();
+
+        SensorDataInput {
+            header: SensorHeader {
+                user_uuid: None,
+                topic: format!("api/v2/{}", input.device_id),
+                device_id: input.device_id,
+                device_type: input.device_type,
+                message_id: input.message_id,
+                location: input.location,
+                should_requeue: false,
+                firmware_version: input.firmware_version,
+                hardware_model: input.hardware_model,
+                rssi: input.rssi,
+                sequence: input.sequence,
+                sent_at: input.sent_at,
+            },
+            metrics,
+            device_timestamp: input.timestamp,
+        }
+    }
+}
+
+/// Batch de lecturas en el formato de `/api/v2`
+#[derive(Debug, Deserialize, Validate)]
+pub struct SensorDataBatchV2 {
+    #[validate(length(min = 1, max = 100))]
+    #[validate(nested)]
+    pub readings: Vec<SensorDataInputV2>,
+}
+
 /// Estadísticas agregadas para un sensor
 #[derive(Debug, Serialize)]
 pub struct SensorStatistics {
-    pub device_id: String,
-    pub location: String,
+    pub device_ids: Vec<String>,
+    pub locations: Vec<String>,
     pub period_start: DateTime<Utc>,
     pub period_end: DateTime<Utc>,
     pub count: u32,
@@ -149,6 +472,736 @@ pub struct MetricSummary {
     pub count: u32,
 }
 
+/// Estadísticas de un grupo (dispositivo o ubicación) dentro del periodo
+#[derive(Debug, Default, Serialize)]
+pub struct GroupSummary {
+    pub count: u32,
+    pub metrics_summary: HashMap<String, MetricSummary>,
+}
+
+/// Valor puntual de una métrica en una serie temporal
+#[derive(Debug, Serialize)]
+pub struct MetricPoint {
+    pub reading_id: String,
+    pub device_id: String,
+    pub location: String,
+    pub measurement: String,
+    pub value: Option<f32>,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Lectura pendiente en la cola de sincronización con el cloud
+#[derive(Debug, Serialize, Clone)]
+pub struct PendingSyncEntry {
+    pub id: Uuid,
+    pub device_id: String,
+    pub location: String,
+    pub gateway_timestamp: DateTime<Utc>,
+    pub sync_attempts: i64,
+    pub last_sync_attempt: Option<String>,
+    pub last_sync_error: Option<String>,
+}
+
+/// Solicitud de corrección manual de la cola de sincronización
+#[derive(Debug, Deserialize, Validate)]
+pub struct SyncRequeueRequest {
+    /// IDs de las lecturas a corregir
+    #[validate(length(min = 1, max = 1000))]
+    pub ids: Vec<Uuid>,
+
+    /// Marcar como sincronizadas en lugar de reintentarlas
+    #[serde(default)]
+    pub mark_synced: bool,
+}
+
+/// Estado de batería y señal de un dispositivo para el reporte de flota
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DevicePowerStatus {
+    pub device_id: String,
+
+    /// Último nivel de batería (% o V según la medición)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery: Option<f32>,
+
+    /// Medición que reporta la batería (`battery` o `vbat`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery_measurement: Option<String>,
+
+    /// Tendencia de la batería por día (regresión lineal sobre la ventana)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub battery_trend_per_day: Option<f32>,
+
+    /// Días estimados hasta llegar al nivel de batería vacía
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub days_to_empty: Option<f32>,
+
+    /// Último RSSI reportado (dBm)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rssi: Option<f32>,
+
+    /// RSSI promedio en la ventana (dBm)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rssi_avg: Option<f32>,
+
+    pub last_seen: DateTime<Utc>,
+
+    /// El nodo requiere atención (batería por agotarse o señal deficiente)
+    pub needs_attention: bool,
+
+    /// Motivos por los que requiere atención
+    pub reasons: Vec<String>,
+}
+
+/// Línea base de una medición de un dispositivo para una hora del día (hora local)
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HourlyBaseline {
+    pub device_id: String,
+    pub measurement: String,
+    pub hour: u32,
+    pub mean: f32,
+    pub std_dev: f32,
+    pub count: i64,
+}
+
+/// Rango de valores válidos para una medición
+#[derive(Debug, Serialize, Deserialize, Validate, Clone)]
+pub struct MeasurementRange {
+    /// Nombre de la medición (sin distinguir mayúsculas)
+    #[validate(length(min = 1, max = 100))]
+    pub measurement: String,
+
+    /// Valor mínimo válido
+    pub min: f32,
+
+    /// Valor máximo válido
+    pub max: f32,
+}
+
+impl MeasurementRange {
+    /// Valida un conjunto completo de rangos (sin mediciones repetidas)
+    pub fn check_all(ranges: &[MeasurementRange]) -> anyhow::Result<()> {
+        let mut seen = std::collections::HashSet::new();
+
+        for range in ranges {
+            range.validate()?;
+
+            if !(range.min.is_finite() && range.max.is_finite()) || range.min > range.max {
+                anyhow::bail!(
+                    "Rango inválido para '{}': min debe ser menor o igual que max",
+                    range.measurement
+                );
+            }
+
+            if !seen.insert(range.measurement.to_lowercase()) {
+                anyhow::bail!("Medición duplicada: {}", range.measurement);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Calibración de una medición de un dispositivo: `valor * gain + offset`
+#[derive(Debug, Serialize, Clone)]
+pub struct Calibration {
+    pub id: i64,
+    pub device_id: String,
+    pub measurement: String,
+    pub offset: f32,
+    pub gain: f32,
+
+    /// Momento a partir del cual se aplica
+    pub valid_from: DateTime<Utc>,
+}
+
+/// Datos para crear o actualizar una calibración
+#[derive(Debug, Deserialize, Validate)]
+pub struct CalibrationInput {
+    #[validate(length(min = 1, max = 50))]
+    pub device_id: String,
+
+    #[validate(length(min = 1, max = 100))]
+    pub measurement: String,
+
+    #[serde(default)]
+    pub offset: f32,
+
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+
+    /// Por defecto, desde el momento de la creación
+    pub valid_from: Option<DateTime<Utc>>,
+}
+
+fn default_gain() -> f32 {
+    1.0
+}
+
+/// Calibración inicial de una medición al provisionar un dispositivo
+#[derive(Debug, Deserialize)]
+pub struct DeviceCalibrationInput {
+    pub measurement: String,
+
+    #[serde(default)]
+    pub offset: f32,
+
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+}
+
+/// Alta de un dispositivo antes de que envíe datos
+#[derive(Debug, Deserialize, Validate)]
+pub struct DeviceProvisionInput {
+    #[validate(length(min = 1, max = 50))]
+    pub device_id: String,
+
+    /// Ubicación esperada del nodo
+    #[validate(length(min = 1, max = 100))]
+    pub location: String,
+
+    /// Perfil de tipo de dispositivo (debe existir en `/admin/profiles`)
+    #[validate(length(min = 1, max = 50))]
+    pub device_type: Option<String>,
+
+    #[serde(default)]
+    pub calibrations: Vec<DeviceCalibrationInput>,
+
+    /// Metadatos libres (instalador, número de serie, etc.)
+    pub metadata: Option<serde_json::Value>,
+
+    /// Etiquetas de grupo
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Destino de las lecturas almacenadas de un dispositivo dado de baja
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DataDisposition {
+    /// Se conservan en la base de datos
+    #[default]
+    Keep,
+    /// Se eliminan
+    Purge,
+    /// Se exportan a Parquet en `DEVICE_ARCHIVE_DIR` y se eliminan
+    Archive,
+}
+
+/// Baja de un dispositivo
+#[derive(Debug, Default, Deserialize, Validate)]
+pub struct DeviceDecommissionInput {
+    #[serde(default)]
+    pub data: DataDisposition,
+
+    /// Motivo de la baja (reemplazo, avería, retiro del sitio, etc.)
+    #[validate(length(min = 1, max = 200))]
+    pub reason: Option<String>,
+}
+
+/// Rotación del token de un dispositivo
+#[derive(Debug, Default, Deserialize)]
+pub struct DeviceTokenRotateInput {
+    /// Invalidar el token anterior de inmediato (token comprometido) en lugar
+    /// de mantenerlo durante `DEVICE_TOKEN_GRACE_SECS`
+    #[serde(default)]
+    pub revoke_previous: bool,
+}
+
+/// Aviso del gateway pendiente de publicar en el cloud
+#[derive(Debug, Clone, Serialize)]
+pub struct CloudNotice {
+    pub id: i64,
+    /// Tipo de aviso (p. ej. `device_decommissioned`)
+    pub kind: String,
+    pub device_id: Option<String>,
+    pub payload: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Grupo de dispositivos que comparten una etiqueta
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceGroup {
+    pub tag: String,
+    pub device_ids: Vec<String>,
+}
+
+/// Cambios parciales de un dispositivo provisionado
+#[derive(Debug, Deserialize, Validate)]
+pub struct DevicePatch {
+    #[validate(length(min = 1, max = 100))]
+    pub location: Option<String>,
+
+    #[validate(length(min = 1, max = 50))]
+    pub device_type: Option<String>,
+
+    /// Se combina con los metadatos existentes (`null` elimina una clave)
+    pub metadata: Option<serde_json::Value>,
+
+    /// Reemplaza las etiquetas del dispositivo
+    pub tags: Option<Vec<String>>,
+}
+
+/// Operador de comparación de una regla
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleOperator {
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Eq,
+    Ne,
+    /// El z-score de la medición (respecto al histórico o a la línea base
+    /// horaria) supera el umbral en valor absoluto
+    Anomaly,
+}
+
+impl RuleOperator {
+    /// Evalúa `value <operador> threshold` (con `anomaly`, `value` es el z-score)
+    pub fn matches(&self, value: f32, threshold: f32) -> bool {
+        match self {
+            RuleOperator::Anomaly => value.abs() > threshold,
+            RuleOperator::Gt => value > threshold,
+            RuleOperator::Gte => value >= threshold,
+            RuleOperator::Lt => value < threshold,
+            RuleOperator::Lte => value <= threshold,
+            RuleOperator::Eq => value == threshold,
+            RuleOperator::Ne => value != threshold,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            RuleOperator::Gt => ">",
+            RuleOperator::Gte => ">=",
+            RuleOperator::Lt => "<",
+            RuleOperator::Lte => "<=",
+            RuleOperator::Eq => "==",
+            RuleOperator::Ne => "!=",
+            RuleOperator::Anomaly => "|z| >",
+        }
+    }
+}
+
+/// Acción ejecutada cuando una regla se activa
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum RuleAction {
+    /// Publica el evento en un topic MQTT del broker local
+    Mqtt { topic: String },
+
+    /// Registra una alerta en el historial
+    Alert { severity: AlertSeverity },
+
+    /// Marca la lectura con un issue de calidad
+    Flag,
+
+    /// Enciende una salida GPIO del gateway mientras la regla está activa
+    Gpio { output: String },
+}
+
+/// Regla "SI medición X en dispositivo/ubicación cumple condición DURANTE t ENTONCES acción"
+#[derive(Debug, Clone, Serialize)]
+pub struct Rule {
+    pub id: String,
+    pub name: String,
+
+    /// Medición evaluada (sin distinguir mayúsculas)
+    pub measurement: String,
+
+    /// Limita la regla a un dispositivo (None = todos)
+    pub device_id: Option<String>,
+
+    /// Limita la regla a una ubicación (None = todas)
+    pub location: Option<String>,
+
+    /// Limita la regla a los dispositivos con una etiqueta (None = todos)
+    pub tag: Option<String>,
+
+    pub operator: RuleOperator,
+    pub threshold: f32,
+
+    /// Segundos que la condición debe mantenerse antes de activar la regla
+    pub for_secs: u64,
+
+    /// Lecturas seguidas que deben cumplir la condición antes de activar la regla
+    pub consecutive: u32,
+
+    /// Segundos mínimos entre dos activaciones de la regla en un dispositivo
+    pub cooldown_secs: u64,
+
+    /// Umbral con el que se resuelve la regla activa (histéresis; None = `threshold`)
+    pub clear_threshold: Option<f32>,
+
+    pub actions: Vec<RuleAction>,
+    pub enabled: bool,
+}
+
+/// Datos para crear o actualizar una regla
+#[derive(Debug, Deserialize, Validate)]
+pub struct RuleInput {
+    #[validate(length(min = 1, max = 100))]
+    pub name: String,
+
+    #[validate(length(min = 1, max = 100))]
+    pub measurement: String,
+
+    pub device_id: Option<String>,
+    pub location: Option<String>,
+    pub tag: Option<String>,
+    pub operator: RuleOperator,
+    pub threshold: f32,
+
+    #[serde(default)]
+    pub for_secs: u64,
+
+    #[serde(default = "default_consecutive")]
+    #[validate(range(min = 1, max = 1000))]
+    pub consecutive: u32,
+
+    #[serde(default)]
+    pub cooldown_secs: u64,
+
+    pub clear_threshold: Option<f32>,
+
+    #[validate(length(min = 1))]
+    pub actions: Vec<RuleAction>,
+
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_consecutive() -> u32 {
+    1
+}
+
+impl RuleInput {
+    /// Valida la regla además de las restricciones declarativas
+    pub fn check(&self) -> anyhow::Result<()> {
+        self.validate()?;
+
+        if !self.threshold.is_finite() {
+            anyhow::bail!("El umbral debe ser un número finito");
+        }
+        if self.operator == RuleOperator::Anomaly && self.threshold <= 0.0 {
+            anyhow::bail!("El umbral de una regla anomaly es un z-score mayor que 0");
+        }
+
+        // El umbral de resolución debe quedar del lado en que la condición ya no se cumple
+        if let Some(clear) = self.clear_threshold {
+            let valid = clear.is_finite()
+                && match self.operator {
+                    RuleOperator::Gt | RuleOperator::Gte => clear <= self.threshold,
+                    RuleOperator::Lt | RuleOperator::Lte => clear >= self.threshold,
+                    RuleOperator::Anomaly => clear > 0.0 && clear <= self.threshold,
+                    RuleOperator::Eq | RuleOperator::Ne => false,
+                };
+            if !valid {
+                anyhow::bail!(
+                    "clear_threshold inválido para el operador {}: debe estar del lado opuesto del umbral ({})",
+                    self.operator.symbol(),
+                    self.threshold
+                );
+            }
+        }
+
+        for action in &self.actions {
+            if let RuleAction::Mqtt { topic } = action
+                && (topic.is_empty() || topic.contains(['+', '#']))
+            {
+                anyhow::bail!("Topic MQTT inválido para publicar: '{}'", topic);
+            }
+            if let RuleAction::Gpio { output } = action
+                && output.is_empty()
+            {
+                anyhow::bail!("La acción gpio necesita el nombre de la salida");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Medición declarada en un perfil de tipo de dispositivo
+#[derive(Debug, Clone, Serialize, Deserialize, Validate)]
+pub struct ProfileMeasurement {
+    #[validate(length(min = 1, max = 100))]
+    pub measurement: String,
+
+    /// Unidad canónica esperada (p. ej. "°C", "hPa")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f32>,
+
+    /// Su ausencia descuenta calidad
+    #[serde(default = "default_enabled")]
+    pub required: bool,
+}
+
+/// Perfil de un tipo de dispositivo: mediciones esperadas, unidades y rangos
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceProfile {
+    /// Tipo de dispositivo (`deviceType` del header)
+    pub device_type: String,
+    pub measurements: Vec<ProfileMeasurement>,
+
+    /// Acepta mediciones no declaradas sin descontar calidad
+    pub allow_unknown: bool,
+
+    /// Silencio tras el cual el dispositivo se considera offline
+    /// (por defecto `DEVICE_OFFLINE_AFTER_SECS`)
+    pub offline_after_secs: Option<u64>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Datos para crear o reemplazar un perfil
+#[derive(Debug, Deserialize, Validate)]
+pub struct DeviceProfileInput {
+    #[validate(length(min = 1))]
+    #[validate(nested)]
+    pub measurements: Vec<ProfileMeasurement>,
+
+    #[serde(default)]
+    pub allow_unknown: bool,
+
+    #[validate(range(min = 1))]
+    pub offline_after_secs: Option<u64>,
+}
+
+/// Resultado de validar una lectura contra el perfil de su tipo de dispositivo
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileCheck {
+    pub device_type: String,
+    pub missing: Vec<String>,
+    pub unknown: Vec<String>,
+    pub out_of_range: Vec<String>,
+    pub unit_mismatch: Vec<String>,
+}
+
+/// Alcance de un ajuste por dispositivo o por ubicación
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OverrideScope {
+    Device,
+    Location,
+}
+
+impl OverrideScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OverrideScope::Device => "device",
+            OverrideScope::Location => "location",
+        }
+    }
+}
+
+impl std::str::FromStr for OverrideScope {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "device" => Ok(OverrideScope::Device),
+            "location" => Ok(OverrideScope::Location),
+            other => anyhow::bail!("Alcance de ajuste desconocido: {}", other),
+        }
+    }
+}
+
+/// Corrección lineal fija de un ajuste (`valor * gain + offset`)
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct OverrideCalibration {
+    #[serde(default)]
+    pub offset: f32,
+
+    #[serde(default = "default_gain")]
+    pub gain: f32,
+}
+
+/// Ajustes de procesamiento y sincronización de un dispositivo o ubicación
+/// Los campos ausentes heredan de la ubicación (si es un dispositivo) o de la
+/// configuración global
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceOverride {
+    pub scope: OverrideScope,
+    /// ID del dispositivo o nombre de la ubicación
+    pub target: String,
+
+    /// Perfil de métricas esperadas (reemplaza al del `deviceType`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+
+    /// Calibración por medición para las series sin calibración propia
+    pub calibrations: HashMap<String, OverrideCalibration>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anomaly_zscore_threshold: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub baseline_zscore_threshold: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sync_priority: Option<i32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub forward_to_cloud: Option<bool>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota_messages_per_day: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub quota_max_rows: Option<u64>,
+
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Datos para crear o reemplazar un ajuste
+#[derive(Debug, Deserialize, Validate)]
+pub struct DeviceOverrideInput {
+    #[validate(length(min = 1, max = 50))]
+    pub profile: Option<String>,
+
+    #[serde(default)]
+    pub calibrations: HashMap<String, OverrideCalibration>,
+
+    #[validate(range(min = 0.5, max = 20.0))]
+    pub anomaly_zscore_threshold: Option<f32>,
+
+    #[validate(range(min = 0.5, max = 20.0))]
+    pub baseline_zscore_threshold: Option<f32>,
+
+    #[validate(range(min = -100, max = 100))]
+    pub sync_priority: Option<i32>,
+
+    pub forward_to_cloud: Option<bool>,
+
+    /// Mensajes por día admitidos (0 = sin límite)
+    pub quota_messages_per_day: Option<u64>,
+
+    /// Lecturas almacenadas admitidas (0 = sin límite)
+    pub quota_max_rows: Option<u64>,
+}
+
+/// Severidad de una alerta (ordenadas de menor a mayor)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertSeverity {
+    Info,
+    #[default]
+    Warning,
+    Critical,
+}
+
+impl AlertSeverity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertSeverity::Info => "info",
+            AlertSeverity::Warning => "warning",
+            AlertSeverity::Critical => "critical",
+        }
+    }
+}
+
+impl std::str::FromStr for AlertSeverity {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "info" => Ok(AlertSeverity::Info),
+            "warning" => Ok(AlertSeverity::Warning),
+            "critical" => Ok(AlertSeverity::Critical),
+            other => anyhow::bail!("Severidad de alerta desconocida: {}", other),
+        }
+    }
+}
+
+/// Alerta registrada en el historial
+#[derive(Debug, Serialize, Clone)]
+pub struct AlertRecord {
+    pub id: i64,
+
+    /// Regla que disparó la alerta
+    pub rule_id: String,
+
+    pub device_id: String,
+
+    /// Medición evaluada
+    pub metric: String,
+
+    /// Valor que disparó la alerta
+    pub value: f64,
+
+    pub severity: AlertSeverity,
+
+    pub fired_at: DateTime<Utc>,
+
+    /// Momento en que la condición dejó de cumplirse (None si sigue activa)
+    pub resolved_at: Option<DateTime<Utc>>,
+
+    /// Usuario que reconoció la alerta
+    pub acked_by: Option<String>,
+
+    pub acked_at: Option<DateTime<Utc>>,
+
+    /// Nota del técnico al reconocerla
+    pub ack_note: Option<String>,
+}
+
+/// Datos para reconocer una alerta
+#[derive(Debug, Default, Deserialize, Validate)]
+pub struct AlertAckInput {
+    #[validate(length(max = 500))]
+    pub note: Option<String>,
+}
+
+/// Silencio de notificaciones de alertas durante un periodo
+#[derive(Debug, Clone, Serialize)]
+pub struct AlertSilence {
+    pub id: i64,
+
+    /// Dispositivo silenciado (None = todos)
+    pub device_id: Option<String>,
+
+    /// Regla silenciada (None = todas)
+    pub rule_id: Option<String>,
+
+    pub reason: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl AlertSilence {
+    /// Si el silencio cubre una alerta en el momento indicado
+    pub fn covers(&self, rule_id: &str, device_id: &str, at: DateTime<Utc>) -> bool {
+        at < self.expires_at
+            && self.device_id.as_deref().is_none_or(|d| d == device_id)
+            && self.rule_id.as_deref().is_none_or(|r| r == rule_id)
+    }
+}
+
+/// Datos para crear un silencio
+#[derive(Debug, Deserialize, Validate)]
+pub struct AlertSilenceInput {
+    pub device_id: Option<String>,
+    pub rule_id: Option<String>,
+
+    /// Duración del silencio (1 min a 30 días)
+    #[validate(range(min = 60, max = 2_592_000))]
+    pub duration_secs: u64,
+
+    #[validate(length(max = 500))]
+    pub reason: Option<String>,
+}
+
 /// Datos enviados al servicio cloud principal via MQTT
 #[derive(Debug, Serialize, Clone)]
 pub struct CloudPayload {
@@ -158,6 +1211,9 @@ pub struct CloudPayload {
     /// Métricas originales más las computadas
     pub metrics: Vec<SensorMetric>,
 
+    /// Momento de la medición (corregido con el reloj del dispositivo si era válido)
+    pub measured_at: DateTime<Utc>,
+
     /// Timestamp de envío
     pub sent_at: DateTime<Utc>,
 
@@ -171,9 +1227,17 @@ pub struct CloudHeader {
     #[serde(rename = "userUUID")]
     pub user_uuid: String,
 
+    /// UUID original enviado por el dispositivo (si lo incluía)
+    #[serde(rename = "sourceUserUUID", skip_serializing_if = "Option::is_none")]
+    pub source_user_uuid: Option<String>,
+
     #[serde(rename = "deviceId")]
     pub device_id: String,
 
+    /// ID del mensaje asignado por el dispositivo (si lo incluía)
+    #[serde(rename = "messageId", skip_serializing_if = "Option::is_none")]
+    pub message_id: Option<String>,
+
     pub location: String,
 
     pub topic: String,
@@ -183,9 +1247,19 @@ pub struct CloudHeader {
 
     /// ID del gateway que procesó
     pub gateway_id: String,
+
+    #[serde(rename = "firmwareVersion", skip_serializing_if = "Option::is_none")]
+    pub firmware_version: Option<String>,
+
+    #[serde(rename = "hardwareModel", skip_serializing_if = "Option::is_none")]
+    pub hardware_model: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rssi: Option<i32>,
 }
 
 /// Estadísticas de batch para cloud
+#[allow(dead_code)]
 #[derive(Debug, Serialize, Clone)]
 pub struct CloudBatchStats {
     pub total_readings: u32,
@@ -194,3 +1268,217 @@ pub struct CloudBatchStats {
     pub avg_quality_score: f32,
     pub gateway_id: String,
 }
+
+/// Cambio de conectividad de un dispositivo detectado por su silencio
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceStatusEvent {
+    pub device_id: String,
+    /// `online` u `offline`
+    pub status: &'static str,
+    pub last_seen: DateTime<Utc>,
+    pub at: DateTime<Utc>,
+}
+
+/// Etapa del ciclo de vida de una alerta
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertState {
+    Firing,
+    Resolved,
+}
+
+impl AlertState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertState::Firing => "firing",
+            AlertState::Resolved => "resolved",
+        }
+    }
+}
+
+/// Disparo o resolución de una alerta, consumido por los canales de notificación
+#[derive(Debug, Clone, Serialize)]
+pub struct AlertEvent {
+    pub alert_id: i64,
+    pub state: AlertState,
+    pub gateway_id: String,
+    pub rule_id: String,
+    pub rule_name: String,
+    pub severity: AlertSeverity,
+    pub device_id: String,
+    pub location: Option<String>,
+    pub metric: String,
+    /// Valor que disparó la alerta, o el que la resolvió
+    pub value: f64,
+    /// Condición legible, p. ej. `temperature > 35 durante 300 s`
+    pub condition: String,
+    pub fired_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    /// Cubierta por un silencio: los canales de notificación no la envían
+    pub silenced: bool,
+}
+
+/// Estado deseado y reportado de un dispositivo (device twin)
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceTwin {
+    pub device_id: String,
+
+    /// Propiedades que el gateway pide al dispositivo (intervalo de muestreo, umbrales)
+    pub desired: serde_json::Value,
+
+    /// Se incrementa con cada cambio del estado deseado
+    pub desired_version: i64,
+    pub desired_updated_at: Option<DateTime<Utc>>,
+
+    /// Propiedades reportadas por el dispositivo (firmware, batería)
+    pub reported: serde_json::Value,
+    pub reported_updated_at: Option<DateTime<Utc>>,
+
+    /// Propiedades deseadas que el dispositivo aún no reporta con el mismo valor
+    pub delta: serde_json::Value,
+}
+
+impl DeviceTwin {
+    /// Twin vacío de un dispositivo sin propiedades
+    pub fn empty(device_id: &str) -> Self {
+        Self {
+            device_id: device_id.to_string(),
+            desired: serde_json::json!({}),
+            desired_version: 0,
+            desired_updated_at: None,
+            reported: serde_json::json!({}),
+            reported_updated_at: None,
+            delta: serde_json::json!({}),
+        }
+    }
+
+    /// Calcula las propiedades deseadas pendientes de aplicar
+    pub fn compute_delta(
+        desired: &serde_json::Value,
+        reported: &serde_json::Value,
+    ) -> serde_json::Value {
+        let delta: serde_json::Map<String, serde_json::Value> = desired
+            .as_object()
+            .into_iter()
+            .flatten()
+            .filter(|(key, value)| reported.get(key.as_str()) != Some(*value))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect();
+
+        serde_json::Value::Object(delta)
+    }
+}
+
+/// Cambio del estado deseado de un twin
+#[derive(Debug, Deserialize)]
+pub struct DeviceTwinPatch {
+    /// Se combina con el estado deseado actual (`null` elimina una propiedad)
+    pub desired: serde_json::Value,
+}
+
+/// Imagen de firmware disponible para actualizar los nodos (OTA)
+#[derive(Debug, Clone, Serialize)]
+pub struct FirmwareImage {
+    pub id: i64,
+
+    pub version: String,
+
+    /// Perfil de tipo de dispositivo al que va dirigida (None = cualquiera)
+    pub device_type: Option<String>,
+
+    pub size_bytes: i64,
+
+    /// SHA-256 del binario en hexadecimal
+    pub sha256: String,
+
+    pub notes: Option<String>,
+
+    pub uploaded_at: DateTime<Utc>,
+
+    /// Archivo del binario dentro de `FIRMWARE_DIR`
+    #[serde(skip)]
+    pub file_name: String,
+}
+
+/// Metadatos de una imagen de firmware subida por la API
+#[derive(Debug, Deserialize, Validate)]
+pub struct FirmwareUploadInput {
+    #[validate(length(min = 1, max = 50))]
+    pub version: String,
+
+    /// Perfil de tipo de dispositivo (debe existir en `/admin/profiles`)
+    #[validate(length(min = 1, max = 50))]
+    pub device_type: Option<String>,
+
+    #[validate(length(max = 500))]
+    pub notes: Option<String>,
+}
+
+/// Estado de la actualización de un dispositivo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RolloutState {
+    Pending,
+    Downloading,
+    Applied,
+    Failed,
+}
+
+impl RolloutState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RolloutState::Pending => "pending",
+            RolloutState::Downloading => "downloading",
+            RolloutState::Applied => "applied",
+            RolloutState::Failed => "failed",
+        }
+    }
+}
+
+impl std::str::FromStr for RolloutState {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pending" => Ok(RolloutState::Pending),
+            "downloading" => Ok(RolloutState::Downloading),
+            "applied" => Ok(RolloutState::Applied),
+            "failed" => Ok(RolloutState::Failed),
+            other => anyhow::bail!("Estado de actualización desconocido: {}", other),
+        }
+    }
+}
+
+/// Actualización de un firmware en un dispositivo
+#[derive(Debug, Clone, Serialize)]
+pub struct FirmwareRollout {
+    pub firmware_id: i64,
+    pub device_id: String,
+    pub state: RolloutState,
+
+    /// Motivo informado por el dispositivo cuando falla
+    pub error: Option<String>,
+
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Dispositivos a los que se anuncia un firmware
+#[derive(Debug, Default, Deserialize)]
+pub struct FirmwareRolloutInput {
+    #[serde(default)]
+    pub device_ids: Vec<String>,
+
+    /// Agrega los dispositivos con alguna de estas etiquetas
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Progreso informado por un dispositivo en `sensors/{id}/ota/status`
+#[derive(Debug, Deserialize)]
+pub struct OtaStatusReport {
+    /// Firmware al que se refiere (por defecto, la última actualización en curso)
+    pub firmware_id: Option<i64>,
+    pub state: RolloutState,
+    pub error: Option<String>,
+}
@@ -0,0 +1,295 @@
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::{
+    AlertEvent, AlertRecord, AlertSeverity, AlertSilence, AlertSilenceInput, AlertState, Rule,
+};
+use chrono::{DateTime, Utc};
+use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+
+/// Eventos de alertas en cola por suscriptor antes de descartar los más antiguos
+const ALERT_EVENTS_CAPACITY: usize = 256;
+
+/// Alerta que se dispara o se resuelve
+#[derive(Debug, Clone)]
+pub struct AlertContext {
+    pub rule_id: String,
+    pub rule_name: String,
+    pub severity: AlertSeverity,
+    pub device_id: String,
+    pub location: Option<String>,
+    pub metric: String,
+    pub value: f64,
+    pub condition: String,
+    pub at: DateTime<Utc>,
+}
+
+impl AlertContext {
+    /// Alerta de una regla evaluada sobre una lectura
+    pub fn from_rule(
+        rule: &Rule,
+        severity: AlertSeverity,
+        device_id: &str,
+        location: &str,
+        value: f64,
+        at: DateTime<Utc>,
+    ) -> Self {
+        let mut condition = format!(
+            "{} {} {}",
+            rule.measurement,
+            rule.operator.symbol(),
+            rule.threshold
+        );
+        if rule.for_secs > 0 {
+            condition.push_str(&format!(" durante {} s", rule.for_secs));
+        }
+        if rule.consecutive > 1 {
+            condition.push_str(&format!(" en {} lecturas seguidas", rule.consecutive));
+        }
+        if let Some(clear) = rule.clear_threshold {
+            condition.push_str(&format!(", se resuelve al cruzar {}", clear));
+        }
+
+        Self {
+            rule_id: rule.id.clone(),
+            rule_name: rule.name.clone(),
+            severity,
+            device_id: device_id.to_string(),
+            location: Some(location.to_string()),
+            metric: rule.measurement.clone(),
+            value,
+            condition,
+            at,
+        }
+    }
+}
+
+/// Ciclo de vida de las alertas
+///
+/// Cada disparo y cada resolución se registran en el historial de alertas y se
+/// emiten como `AlertEvent` a los suscriptores (canales de notificación y
+/// stream `/api/v1/events`). Los eventos cubiertos por un silencio vigente se
+/// emiten marcados como `silenced`, y los canales de notificación los omiten.
+pub struct Alerting {
+    config: Arc<Config>,
+    db: Database,
+    events: broadcast::Sender<AlertEvent>,
+    /// Silencios vigentes, en memoria para no consultar la base en cada evento
+    silences: RwLock<Vec<AlertSilence>>,
+}
+
+impl Alerting {
+    /// Crea el servicio con los silencios vigentes de la base
+    pub async fn load(config: Arc<Config>, db: Database) -> anyhow::Result<Self> {
+        let silences = db.list_active_silences(Utc::now()).await?;
+
+        Ok(Self {
+            config,
+            db,
+            events: broadcast::channel(ALERT_EVENTS_CAPACITY).0,
+            silences: RwLock::new(silences),
+        })
+    }
+
+    /// Suscribe a los disparos y resoluciones de alertas
+    pub fn subscribe(&self) -> broadcast::Receiver<AlertEvent> {
+        self.events.subscribe()
+    }
+
+    /// Registra una alerta disparada y la emite; retorna su ID
+    ///
+    /// Si la regla ya tiene una alerta activa en el dispositivo (p. ej. tras
+    /// reiniciar el gateway) no se duplica: se retorna la existente sin emitirla.
+    pub async fn fire(&self, alert: &AlertContext) -> anyhow::Result<i64> {
+        if let Some(alert_id) = self
+            .db
+            .active_alert_id(&alert.rule_id, &alert.device_id)
+            .await?
+        {
+            tracing::debug!(
+                alert_id,
+                rule = %alert.rule_name,
+                device_id = %alert.device_id,
+                "Alerta ya activa, no se duplica"
+            );
+            return Ok(alert_id);
+        }
+
+        let alert_id = self
+            .db
+            .insert_alert(
+                &alert.rule_id,
+                &alert.device_id,
+                &alert.metric,
+                alert.value,
+                alert.severity,
+                alert.at,
+            )
+            .await?;
+
+        tracing::warn!(
+            alert_id,
+            rule = %alert.rule_name,
+            device_id = %alert.device_id,
+            severity = alert.severity.as_str(),
+            condition = %alert.condition,
+            value = alert.value,
+            "Alerta disparada"
+        );
+        self.emit(alert, alert_id, AlertState::Firing, alert.at, None);
+
+        Ok(alert_id)
+    }
+
+    /// Resuelve las alertas activas de la regla en el dispositivo y las emite
+    pub async fn resolve(&self, alert: &AlertContext) -> anyhow::Result<Vec<AlertRecord>> {
+        let resolved = self
+            .db
+            .resolve_alerts(&alert.rule_id, &alert.device_id, alert.at)
+            .await?;
+
+        for record in &resolved {
+            tracing::info!(
+                alert_id = record.id,
+                rule = %alert.rule_name,
+                device_id = %alert.device_id,
+                value = alert.value,
+                "Alerta resuelta"
+            );
+            self.emit(
+                alert,
+                record.id,
+                AlertState::Resolved,
+                record.fired_at,
+                Some(alert.at),
+            );
+        }
+
+        Ok(resolved)
+    }
+
+    /// Reconoce una alerta; retorna None si no existe
+    pub async fn ack(
+        &self,
+        alert_id: i64,
+        acked_by: &str,
+        note: Option<&str>,
+    ) -> anyhow::Result<Option<AlertRecord>> {
+        let record = self
+            .db
+            .ack_alert(alert_id, acked_by, note, Utc::now())
+            .await?;
+
+        if let Some(record) = &record {
+            tracing::info!(
+                alert_id,
+                device_id = %record.device_id,
+                acked_by,
+                "Alerta reconocida"
+            );
+        }
+
+        Ok(record)
+    }
+
+    /// Silencia las notificaciones de un dispositivo y/o regla durante un periodo
+    pub async fn silence(
+        &self,
+        input: &AlertSilenceInput,
+        created_by: &str,
+    ) -> anyhow::Result<AlertSilence> {
+        let now = Utc::now();
+        let expires_at = now + chrono::Duration::seconds(input.duration_secs as i64);
+        let silence = self
+            .db
+            .insert_silence(
+                input.device_id.as_deref(),
+                input.rule_id.as_deref(),
+                input.reason.as_deref(),
+                created_by,
+                now,
+                expires_at,
+            )
+            .await?;
+
+        tracing::info!(
+            silence_id = silence.id,
+            device_id = ?silence.device_id,
+            rule_id = ?silence.rule_id,
+            expires_at = %silence.expires_at,
+            created_by,
+            "Notificaciones de alertas silenciadas"
+        );
+
+        let mut silences = self.silences.write().unwrap();
+        silences.retain(|s| s.expires_at > now);
+        silences.push(silence.clone());
+
+        Ok(silence)
+    }
+
+    /// Levanta un silencio antes de su vencimiento; retorna false si no existe
+    pub async fn lift_silence(&self, silence_id: i64) -> anyhow::Result<bool> {
+        let deleted = self.db.delete_silence(silence_id).await?;
+        self.silences
+            .write()
+            .unwrap()
+            .retain(|s| s.id != silence_id);
+
+        if deleted {
+            tracing::info!(silence_id, "Silencio de alertas levantado");
+        }
+
+        Ok(deleted)
+    }
+
+    /// Silencios vigentes
+    pub fn active_silences(&self) -> Vec<AlertSilence> {
+        let now = Utc::now();
+        self.silences
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|s| s.expires_at > now)
+            .cloned()
+            .collect()
+    }
+
+    fn is_silenced(&self, rule_id: &str, device_id: &str) -> bool {
+        let now = Utc::now();
+        self.silences
+            .read()
+            .unwrap()
+            .iter()
+            .any(|s| s.covers(rule_id, device_id, now))
+    }
+
+    fn emit(
+        &self,
+        alert: &AlertContext,
+        alert_id: i64,
+        state: AlertState,
+        fired_at: DateTime<Utc>,
+        resolved_at: Option<DateTime<Utc>>,
+    ) {
+        let event = AlertEvent {
+            alert_id,
+            state,
+            gateway_id: self.config.gateway_id.clone(),
+            rule_id: alert.rule_id.clone(),
+            rule_name: alert.rule_name.clone(),
+            severity: alert.severity,
+            device_id: alert.device_id.clone(),
+            location: alert.location.clone(),
+            metric: alert.metric.clone(),
+            value: alert.value,
+            condition: alert.condition.clone(),
+            fired_at,
+            resolved_at,
+            silenced: self.is_silenced(&alert.rule_id, &alert.device_id),
+        };
+
+        // Sin suscriptores el envío falla, lo que no es un error
+        let _ = self.events.send(event);
+    }
+}
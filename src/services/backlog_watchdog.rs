@@ -0,0 +1,161 @@
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::AlertSeverity;
+use crate::services::alerting::{AlertContext, Alerting};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Regla con la que se registran las alarmas de backlog en el historial de alertas
+pub const BACKLOG_ALARM_RULE: &str = "system:backlog_growth";
+
+/// Tendencia observada del backlog de sincronización
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BacklogTrend {
+    /// Lecturas pendientes en la última verificación
+    pub pending: i64,
+    pub checked_at: Option<DateTime<Utc>>,
+    /// Inicio del crecimiento sostenido, si lo hay
+    pub growing_since: Option<DateTime<Utc>>,
+    /// Pendientes al inicio del crecimiento
+    pub pending_at_start: i64,
+    /// Momento en que se disparó la alarma (None si no está activa)
+    pub alarm_since: Option<DateTime<Utc>>,
+}
+
+/// Vigila la tendencia del backlog de sincronización
+///
+/// Si las lecturas pendientes crecen sin interrupción durante
+/// `BACKLOG_ALARM_MINUTES` (caída del cloud), registra una alerta crítica y
+/// marca el gateway como no listo; la alerta se resuelve en cuanto el backlog
+/// empieza a drenarse.
+pub struct BacklogWatchdog {
+    config: Arc<Config>,
+    db: Database,
+    alerting: Arc<Alerting>,
+    trend: Mutex<BacklogTrend>,
+}
+
+impl BacklogWatchdog {
+    pub fn new(config: Arc<Config>, db: Database, alerting: Arc<Alerting>) -> Self {
+        Self {
+            config,
+            db,
+            alerting,
+            trend: Mutex::new(BacklogTrend::default()),
+        }
+    }
+
+    /// Tendencia actual (para health checks y métricas)
+    pub fn trend(&self) -> BacklogTrend {
+        self.trend.lock().unwrap().clone()
+    }
+
+    /// Indica si la alarma de crecimiento está activa
+    pub fn is_alarming(&self) -> bool {
+        self.trend.lock().unwrap().alarm_since.is_some()
+    }
+
+    /// Tarea periódica de verificación
+    pub async fn start_watch_task(self: Arc<Self>) {
+        // Las alarmas de una ejecución anterior se reevalúan desde cero
+        let pending = self.db.count_pending_sync().await.unwrap_or_default();
+        if let Err(e) = self
+            .alerting
+            .resolve(&self.alert(pending, Utc::now()))
+            .await
+        {
+            tracing::warn!("No se pudieron resolver alarmas de backlog previas: {}", e);
+        }
+
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.backlog_check_interval_secs));
+        tracing::info!(
+            alarm_minutes = self.config.backlog_alarm_minutes,
+            "Vigilancia del backlog de sincronización iniciada"
+        );
+
+        loop {
+            interval.tick().await;
+
+            match self.db.count_pending_sync().await {
+                Ok(pending) => {
+                    if let Err(e) = self.observe(pending, Utc::now()).await {
+                        tracing::error!("Error registrando la alarma de backlog: {}", e);
+                    }
+                }
+                Err(e) => tracing::error!("Error consultando el backlog: {}", e),
+            }
+        }
+    }
+
+    /// Actualiza la tendencia con una nueva observación y dispara o resuelve la alarma
+    async fn observe(&self, pending: i64, now: DateTime<Utc>) -> anyhow::Result<()> {
+        let period = chrono::Duration::minutes(self.config.backlog_alarm_minutes as i64);
+
+        let (fire, resolve) = {
+            let mut trend = self.trend.lock().unwrap();
+            let previous = trend.pending;
+            trend.pending = pending;
+
+            // La primera observación solo fija la referencia
+            if trend.checked_at.replace(now).is_none() {
+                return Ok(());
+            }
+
+            if pending == 0 || pending < previous {
+                // El backlog se está drenando
+                trend.growing_since = None;
+                (false, trend.alarm_since.take().is_some())
+            } else {
+                if trend.growing_since.is_none() && pending > previous {
+                    trend.growing_since = Some(now);
+                    trend.pending_at_start = previous;
+                }
+
+                let sustained = trend
+                    .growing_since
+                    .is_some_and(|since| now - since >= period);
+                let fire = sustained && trend.alarm_since.is_none();
+                if fire {
+                    trend.alarm_since = Some(now);
+                }
+                (fire, false)
+            }
+        };
+
+        if fire {
+            tracing::error!(
+                pending,
+                minutes = self.config.backlog_alarm_minutes,
+                "El backlog de sincronización crece sin interrupción (¿caída del cloud?)"
+            );
+            self.alerting.fire(&self.alert(pending, now)).await?;
+        }
+
+        if resolve {
+            tracing::info!(pending, "El backlog de sincronización vuelve a drenarse");
+            self.alerting.resolve(&self.alert(pending, now)).await?;
+        }
+
+        Ok(())
+    }
+
+    fn alert(&self, pending: i64, now: DateTime<Utc>) -> AlertContext {
+        AlertContext {
+            rule_id: BACKLOG_ALARM_RULE.to_string(),
+            rule_name: "Backlog de sincronización creciente".to_string(),
+            severity: AlertSeverity::Critical,
+            device_id: self.config.gateway_id.clone(),
+            location: None,
+            metric: "pending_sync".to_string(),
+            value: pending as f64,
+            condition: format!(
+                "lecturas pendientes en aumento durante {} min",
+                self.config.backlog_alarm_minutes
+            ),
+            at: now,
+        }
+    }
+}
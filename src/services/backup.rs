@@ -0,0 +1,104 @@
+use crate::config::Config;
+use crate::database::Database;
+use crate::services::scheduling::duration_until_local_hour;
+use chrono::Local;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Prefijo de los archivos de snapshot generados por el gateway
+const BACKUP_PREFIX: &str = "backup-";
+
+/// Servicio de respaldos de la base de datos local
+/// Genera snapshots consistentes en caliente y rota los más antiguos
+pub struct BackupService {
+    config: Arc<Config>,
+    db: Database,
+}
+
+impl BackupService {
+    pub fn new(config: Arc<Config>, db: Database) -> Self {
+        Self { config, db }
+    }
+
+    /// Genera un snapshot en el directorio de respaldos configurado
+    /// y aplica la rotación de los últimos N snapshots
+    pub async fn create_snapshot(&self) -> anyhow::Result<PathBuf> {
+        let dir = PathBuf::from(&self.config.backup_dir);
+        tokio::fs::create_dir_all(&dir).await?;
+
+        // El sufijo evita que dos snapshots del mismo segundo compartan archivo
+        let file_name = format!(
+            "{}{}-{}.db",
+            BACKUP_PREFIX,
+            Local::now().format("%Y%m%d-%H%M%S"),
+            &uuid::Uuid::new_v4().simple().to_string()[..8]
+        );
+        let path = dir.join(file_name);
+
+        self.db.backup_to(&path).await?;
+
+        let size = tokio::fs::metadata(&path).await?.len();
+        tracing::info!(path = %path.display(), size_bytes = size, "Snapshot de base de datos creado");
+
+        if let Err(e) = self.rotate(&dir).await {
+            tracing::warn!("Error rotando snapshots antiguos: {}", e);
+        }
+
+        Ok(path)
+    }
+
+    /// Genera un snapshot temporal para descarga directa
+    pub async fn create_download_snapshot(&self) -> anyhow::Result<PathBuf> {
+        let path = std::env::temp_dir().join(format!(
+            "{}{}-{}.db",
+            BACKUP_PREFIX,
+            self.config.gateway_id,
+            uuid::Uuid::new_v4()
+        ));
+
+        self.db.backup_to(&path).await?;
+        Ok(path)
+    }
+
+    /// Elimina los snapshots más antiguos conservando los últimos `backup_keep`
+    async fn rotate(&self, dir: &Path) -> anyhow::Result<()> {
+        let mut snapshots = Vec::new();
+        let mut entries = tokio::fs::read_dir(dir).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with(BACKUP_PREFIX) && name.ends_with(".db") {
+                snapshots.push(entry.path());
+            }
+        }
+
+        // Los nombres incluyen el timestamp, por lo que el orden lexicográfico es cronológico
+        snapshots.sort();
+
+        let excess = snapshots.len().saturating_sub(self.config.backup_keep);
+        for old in snapshots.into_iter().take(excess) {
+            tokio::fs::remove_file(&old).await?;
+            tracing::info!(path = %old.display(), "Snapshot antiguo eliminado");
+        }
+
+        Ok(())
+    }
+
+    /// Tarea nocturna de respaldo programado
+    pub async fn start_schedule_task(self: Arc<Self>) {
+        tracing::info!(
+            hour = self.config.backup_hour,
+            keep = self.config.backup_keep,
+            dir = %self.config.backup_dir,
+            "Tarea de respaldo programado iniciada"
+        );
+
+        loop {
+            tokio::time::sleep(duration_until_local_hour(self.config.backup_hour)).await;
+
+            if let Err(e) = self.create_snapshot().await {
+                tracing::error!("Error en respaldo programado: {}", e);
+            }
+        }
+    }
+}
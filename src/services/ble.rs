@@ -0,0 +1,305 @@
+use crate::config::Config;
+use crate::models::{SensorDataInput, SensorMetric};
+use crate::services::edge_processor::RSSI_MEASUREMENT;
+use crate::services::local_ingest::LocalIngest;
+use anyhow::Context;
+use btleplug::api::bleuuid::uuid_from_u16;
+use btleplug::api::{
+    Central, CentralEvent, Manager as _, Peripheral, PeripheralProperties, ScanFilter,
+};
+use btleplug::platform::Manager;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_stream::StreamExt;
+use uuid::Uuid;
+
+/// Origen de las lecturas BLE en el topic (`ble/<device_id>`)
+const SOURCE: &str = "ble";
+
+/// Espera antes de reintentar cuando el adaptador falla o desaparece
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Identificador de fabricante de Ruuvi Innovations
+const RUUVI_MANUFACTURER_ID: u16 = 0x0499;
+
+/// Servicio Environmental Sensing: firmware ATC y pvvx de los Xiaomi LYWSD03MMC
+const ENVIRONMENTAL_SENSING: Uuid = uuid_from_u16(0x181A);
+
+/// Servicio MiBeacon del firmware original de Xiaomi
+const MIBEACON: Uuid = uuid_from_u16(0xFE95);
+
+/// Lectura decodificada de un anuncio
+struct Advertisement {
+    model: &'static str,
+    metrics: Vec<SensorMetric>,
+}
+
+/// Escucha los anuncios BLE de sensores RuuviTag y Xiaomi y los inyecta en el
+/// pipeline como lecturas de dispositivos
+///
+/// Los sensores no necesitan conexión: cada anuncio lleva la medición. Cada
+/// sensor se publica como `ble-<mac>` con a lo sumo una lectura cada
+/// `BLE_INTERVAL_SECS`. Los Xiaomi con firmware original cifran sus anuncios
+/// salvo los modelos antiguos (LYWSDCGQ, LYWSD02); los LYWSD03MMC requieren el
+/// firmware ATC o pvvx.
+pub struct BleScanner {
+    config: Arc<Config>,
+    ingest: LocalIngest,
+    /// Última lectura aceptada de cada sensor (MAC)
+    last_reading: HashMap<String, Instant>,
+}
+
+impl BleScanner {
+    pub fn new(config: Arc<Config>, ingest: LocalIngest) -> Self {
+        Self {
+            config,
+            ingest,
+            last_reading: HashMap::new(),
+        }
+    }
+
+    /// Escucha hasta que se detenga el gateway; los errores del adaptador se reintentan
+    pub async fn run(mut self) {
+        loop {
+            if let Err(e) = self.scan().await {
+                tracing::error!("Error en la escucha de sensores BLE: {:#}", e);
+            }
+            tokio::time::sleep(RETRY_DELAY).await;
+        }
+    }
+
+    async fn scan(&mut self) -> anyhow::Result<()> {
+        let manager = Manager::new().await?;
+        let adapter = manager
+            .adapters()
+            .await?
+            .into_iter()
+            .next()
+            .context("no hay adaptadores Bluetooth")?;
+
+        let mut events = adapter.events().await?;
+        adapter.start_scan(ScanFilter::default()).await?;
+        let adapter_info = adapter.adapter_info().await.unwrap_or_default();
+        tracing::info!(
+            adapter = %adapter_info,
+            interval_secs = self.config.ble_interval_secs,
+            "Escucha de sensores BLE iniciada"
+        );
+
+        while let Some(event) = events.next().await {
+            let id = match event {
+                CentralEvent::ManufacturerDataAdvertisement { id, .. }
+                | CentralEvent::ServiceDataAdvertisement { id, .. } => id,
+                _ => continue,
+            };
+            // El dispositivo puede desaparecer entre el evento y la consulta
+            let Ok(peripheral) = adapter.peripheral(&id).await else {
+                continue;
+            };
+            if let Ok(Some(properties)) = peripheral.properties().await {
+                self.observe(properties).await;
+            }
+        }
+
+        anyhow::bail!("el adaptador Bluetooth dejó de emitir eventos")
+    }
+
+    async fn observe(&mut self, properties: PeripheralProperties) {
+        let mac = properties.address.to_string_no_delim().to_lowercase();
+        let location = match self.config.ble_devices.get(&mac) {
+            Some(location) => location.clone(),
+            None if self.config.ble_devices.is_empty() => self.config.ble_default_location.clone(),
+            None => return,
+        };
+
+        let interval = Duration::from_secs(self.config.ble_interval_secs);
+        if self
+            .last_reading
+            .get(&mac)
+            .is_some_and(|at| at.elapsed() < interval)
+        {
+            return;
+        }
+        let Some(advertisement) = decode(&properties) else {
+            return;
+        };
+        self.last_reading.insert(mac.clone(), Instant::now());
+
+        let device_id = format!("ble-{}", mac);
+        let mut header =
+            LocalIngest::header(SOURCE, &device_id, &location, Some(advertisement.model));
+        header.rssi = properties.rssi.map(i32::from);
+
+        let mut metrics = advertisement.metrics;
+        if let Some(rssi) = properties.rssi {
+            metrics.push(metric(RSSI_MEASUREMENT, rssi as f32, "dBm"));
+        }
+
+        let input = SensorDataInput {
+            header,
+            metrics,
+            device_timestamp: None,
+        };
+        if let Err(e) = self.ingest.submit(input).await {
+            tracing::error!(device_id = %device_id, "Error almacenando la lectura BLE: {}", e);
+        }
+    }
+}
+
+fn decode(properties: &PeripheralProperties) -> Option<Advertisement> {
+    if let Some(data) = properties.manufacturer_data.get(&RUUVI_MANUFACTURER_ID)
+        && let Some(metrics) = decode_ruuvi(data)
+    {
+        return Some(Advertisement {
+            model: "ruuvitag",
+            metrics,
+        });
+    }
+    if let Some(data) = properties.service_data.get(&ENVIRONMENTAL_SENSING)
+        && let Some(metrics) = decode_custom_xiaomi(data)
+    {
+        return Some(Advertisement {
+            model: "xiaomi-lywsd03mmc",
+            metrics,
+        });
+    }
+    if let Some(data) = properties.service_data.get(&MIBEACON)
+        && let Some(metrics) = decode_mibeacon(data)
+    {
+        return Some(Advertisement {
+            model: "xiaomi-mibeacon",
+            metrics,
+        });
+    }
+    None
+}
+
+fn metric(measurement: &str, value: f32, unit: &str) -> SensorMetric {
+    SensorMetric {
+        measurement: measurement.to_string(),
+        value,
+        unit: Some(unit.to_string()),
+    }
+}
+
+/// RuuviTag: formato 5 (RAWv2) y formato 3 (RAWv1)
+fn decode_ruuvi(data: &[u8]) -> Option<Vec<SensorMetric>> {
+    let u16_at = |i: usize| u16::from_be_bytes([data[i], data[i + 1]]);
+    let mut metrics = Vec::new();
+
+    match *data.first()? {
+        5 if data.len() >= 24 => {
+            // Los valores máximos (o mínimo en la temperatura) marcan un dato no disponible
+            let temperature = u16_at(1) as i16;
+            if temperature != i16::MIN {
+                metrics.push(metric("Temperature", temperature as f32 * 0.005, "°C"));
+            }
+            let humidity = u16_at(3);
+            if humidity != u16::MAX {
+                metrics.push(metric("Humidity", humidity as f32 * 0.0025, "%"));
+            }
+            let pressure = u16_at(5);
+            if pressure != u16::MAX {
+                metrics.push(metric(
+                    "Pressure",
+                    (pressure as f32 + 50_000.0) / 100.0,
+                    "hPa",
+                ));
+            }
+            let battery_mv = u16_at(13) >> 5;
+            if battery_mv != 0x7FF {
+                metrics.push(metric("vbat", (battery_mv as f32 + 1600.0) / 1000.0, "V"));
+            }
+        }
+        3 if data.len() >= 14 => {
+            metrics.push(metric("Humidity", data[1] as f32 * 0.5, "%"));
+            let temperature = (data[2] & 0x7F) as f32 + data[3] as f32 / 100.0;
+            let temperature = if data[2] & 0x80 != 0 {
+                -temperature
+            } else {
+                temperature
+            };
+            metrics.push(metric("Temperature", temperature, "°C"));
+            metrics.push(metric(
+                "Pressure",
+                (u16_at(4) as f32 + 50_000.0) / 100.0,
+                "hPa",
+            ));
+            metrics.push(metric("vbat", u16_at(12) as f32 / 1000.0, "V"));
+        }
+        _ => return None,
+    }
+
+    Some(metrics).filter(|metrics| !metrics.is_empty())
+}
+
+/// Xiaomi LYWSD03MMC con firmware ATC (13 bytes) o pvvx (15 bytes)
+fn decode_custom_xiaomi(data: &[u8]) -> Option<Vec<SensorMetric>> {
+    match data.len() {
+        13 => Some(vec![
+            metric(
+                "Temperature",
+                i16::from_be_bytes([data[6], data[7]]) as f32 / 10.0,
+                "°C",
+            ),
+            metric("Humidity", data[8] as f32, "%"),
+            metric("battery", data[9] as f32, "%"),
+        ]),
+        15.. => Some(vec![
+            metric(
+                "Temperature",
+                i16::from_le_bytes([data[6], data[7]]) as f32 / 100.0,
+                "°C",
+            ),
+            metric(
+                "Humidity",
+                u16::from_le_bytes([data[8], data[9]]) as f32 / 100.0,
+                "%",
+            ),
+            metric("battery", data[12] as f32, "%"),
+        ]),
+        _ => None,
+    }
+}
+
+/// MiBeacon sin cifrar (LYWSDCGQ, LYWSD02 y otros modelos del firmware original)
+fn decode_mibeacon(data: &[u8]) -> Option<Vec<SensorMetric>> {
+    let frame_control = u16::from_le_bytes([*data.first()?, *data.get(1)?]);
+    // Bit 3: cifrado; bit 6: incluye objetos (mediciones)
+    if frame_control & 0x08 != 0 || frame_control & 0x40 == 0 {
+        return None;
+    }
+
+    // Control de trama, ID de producto y contador; luego MAC y capacidades opcionales
+    let mut offset = 5;
+    if frame_control & 0x10 != 0 {
+        offset += 6;
+    }
+    if frame_control & 0x20 != 0 {
+        let capability = *data.get(offset)?;
+        offset += if capability & 0x20 != 0 { 3 } else { 1 };
+    }
+
+    let mut metrics = Vec::new();
+    while let Some(header) = data.get(offset..offset + 3) {
+        let kind = u16::from_le_bytes([header[0], header[1]]);
+        let size = header[2] as usize;
+        let value = data.get(offset + 3..offset + 3 + size)?;
+        offset += 3 + size;
+
+        let i16_at = |i: usize| i16::from_le_bytes([value[i], value[i + 1]]);
+        match (kind, size) {
+            (0x1004, 2) => metrics.push(metric("Temperature", i16_at(0) as f32 / 10.0, "°C")),
+            (0x1006, 2) => metrics.push(metric("Humidity", i16_at(0) as f32 / 10.0, "%")),
+            (0x100A, 1) => metrics.push(metric("battery", value[0] as f32, "%")),
+            (0x100D, 4) => {
+                metrics.push(metric("Temperature", i16_at(0) as f32 / 10.0, "°C"));
+                metrics.push(metric("Humidity", i16_at(2) as f32 / 10.0, "%"));
+            }
+            _ => {}
+        }
+    }
+
+    Some(metrics).filter(|metrics| !metrics.is_empty())
+}
@@ -1,23 +1,140 @@
-use crate::config::Config;
+use crate::config::{CloudSink, Config};
 use crate::database::Database;
 use crate::models::{CloudHeader, CloudPayload, SensorMetric};
+use crate::services::connection::ConnectionStatus;
+use crate::services::device_overrides::DeviceOverrides;
+use crate::services::remote_config::{RemoteConfig, RemoteConfigMessage};
+use crate::services::runtime_config::RuntimeSettings;
+use crate::telemetry::Telemetry;
 use chrono::Utc;
-use rumqttc::{AsyncClient, MqttOptions, QoS};
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
 use std::sync::Arc;
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, watch};
+
+/// Avisos del gateway publicados por cada sincronización
+const NOTICES_PER_SYNC: usize = 50;
+
+/// Órdenes en cola para la tarea de sincronización
+const SYNC_COMMANDS_CAPACITY: usize = 16;
+
+/// Espera máxima a que termine la sincronización en curso al apagar
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Órdenes a la tarea de sincronización
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncCommand {
+    /// Sincronizar ahora (p. ej. al superar el tamaño de batch)
+    SyncNow,
+    /// Suspender las sincronizaciones, periódicas y pedidas
+    Pause,
+    /// Reanudarlas, sincronizando de inmediato
+    Resume,
+    /// Terminar la tarea tras la sincronización en curso
+    Shutdown,
+}
+
+/// Acceso a la tarea de sincronización desde handlers y servicios
+///
+/// Pedir una sincronización nunca espera a la que esté en curso: la orden
+/// queda en cola y las que llegan mientras hay una pendiente se agrupan.
+#[derive(Clone)]
+pub struct CloudSyncHandle {
+    commands: mpsc::Sender<SyncCommand>,
+    /// Hay un `SyncNow` en cola que la tarea aún no atendió
+    requested: Arc<AtomicBool>,
+    paused: watch::Receiver<bool>,
+}
+
+impl CloudSyncHandle {
+    /// Handle sin tarea, con la sincronización deshabilitada
+    pub fn disabled() -> Self {
+        let (commands, _) = mpsc::channel(1);
+        Self {
+            commands,
+            requested: Arc::new(AtomicBool::new(false)),
+            paused: watch::Sender::new(false).subscribe(),
+        }
+    }
+
+    /// Pide una sincronización sin esperar a que termine
+    pub fn sync_now(&self) {
+        if !self.requested.swap(true, Ordering::AcqRel)
+            && self.commands.try_send(SyncCommand::SyncNow).is_err()
+        {
+            self.requested.store(false, Ordering::Release);
+        }
+    }
+
+    /// Envía una orden; false si la sincronización está deshabilitada
+    pub async fn send(&self, command: SyncCommand) -> bool {
+        self.commands.send(command).await.is_ok()
+    }
+
+    /// La tarea de sincronización está en marcha
+    pub fn is_running(&self) -> bool {
+        !self.commands.is_closed()
+    }
+
+    /// Las sincronizaciones están suspendidas por un operador
+    pub fn is_paused(&self) -> bool {
+        *self.paused.borrow()
+    }
+
+    /// Detiene la tarea y espera a que termine la sincronización en curso
+    ///
+    /// El envío de la orden también cuenta para el plazo: con la cola de
+    /// órdenes llena y una sincronización atascada, el apagado no debe colgarse.
+    pub async fn shutdown(&self) {
+        let stop = async {
+            if self.send(SyncCommand::Shutdown).await {
+                self.commands.closed().await;
+            }
+        };
+        if tokio::time::timeout(SHUTDOWN_TIMEOUT, stop).await.is_err() {
+            tracing::warn!("La sincronización en curso no terminó antes del apagado");
+        }
+    }
+}
 
 /// Servicio de sincronización con el cloud principal via MQTT
 /// Maneja el envío de datos procesados al servicio central
+///
+/// Corre como una tarea propia (`spawn`) que es dueña del cliente MQTT del
+/// cloud y recibe órdenes por un canal; el resto del gateway solo conoce el
+/// `CloudSyncHandle`.
 pub struct CloudSync {
     config: Arc<Config>,
+    /// Tamaño de batch e intervalo vigentes (modificables en tiempo de ejecución)
+    settings: watch::Receiver<RuntimeSettings>,
     mqtt_client: Option<AsyncClient>,
+    /// Conectividad real con el broker cloud (para los health checks)
+    status: Arc<ConnectionStatus>,
+    /// Ajustes por dispositivo y ubicación (reenvío al cloud)
+    overrides: Arc<DeviceOverrides>,
+    /// Destino de los mensajes del topic de configuración remota, si está habilitada
+    remote_config: Option<mpsc::Sender<RemoteConfigMessage>>,
+    telemetry: Arc<Telemetry>,
 }
 
 impl CloudSync {
-    pub fn new(config: Arc<Config>) -> Self {
+    pub fn new(
+        config: Arc<Config>,
+        settings: watch::Receiver<RuntimeSettings>,
+        status: Arc<ConnectionStatus>,
+        overrides: Arc<DeviceOverrides>,
+        remote_config: Option<mpsc::Sender<RemoteConfigMessage>>,
+        telemetry: Arc<Telemetry>,
+    ) -> Self {
         Self {
             config,
+            settings,
             mqtt_client: None,
+            status,
+            overrides,
+            remote_config,
+            telemetry,
         }
     }
 
@@ -49,11 +166,41 @@ impl CloudSync {
         );
 
         // Iniciar eventloop en background
+        let status = self.status.clone();
+        let remote_config = self
+            .remote_config
+            .clone()
+            .map(|sender| (sender, RemoteConfig::topic(&self.config), client.clone()));
         tokio::spawn(async move {
             loop {
                 match eventloop.poll().await {
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        status.set_connected();
+                        tracing::info!("Conectado al broker MQTT del cloud");
+
+                        // La sesión es limpia: la suscripción se renueva en cada conexión
+                        if let Some((_, topic, client)) = &remote_config
+                            && let Err(e) = client.try_subscribe(topic, QoS::AtLeastOnce)
+                        {
+                            tracing::error!("Error suscribiendo a la configuración remota: {}", e);
+                        }
+                    }
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        if let Some((sender, topic, client)) = &remote_config
+                            && publish.topic == *topic
+                        {
+                            let message = RemoteConfigMessage {
+                                payload: publish.payload.to_vec(),
+                                client: client.clone(),
+                            };
+                            if let Err(e) = sender.try_send(message) {
+                                tracing::warn!("Configuración remota descartada: {}", e);
+                            }
+                        }
+                    }
                     Ok(_) => {}
                     Err(e) => {
+                        status.set_disconnected(&e);
                         tracing::error!("Error en MQTT eventloop del cloud: {}", e);
                         tokio::time::sleep(Duration::from_secs(5)).await;
                     }
@@ -68,42 +215,67 @@ impl CloudSync {
     }
 
     /// Sincroniza datos pendientes con el cloud via MQTT
-    pub async fn sync_data(&mut self, db: Database) -> anyhow::Result<()> {
+    async fn sync_data(&mut self, db: Database) -> anyhow::Result<()> {
+        if !self.config.cloud_sync_enabled {
+            return Ok(());
+        }
+
         tracing::info!("Iniciando sincronización con cloud via MQTT");
 
+        if let Err(e) = self.publish_notices(&db).await {
+            tracing::error!("Error publicando avisos en el cloud: {}", e);
+        }
+
+        // Las lecturas aún en el búfer de escritura entran en este envío
+        db.flush_writes().await;
+
         // Obtener datos pendientes de sincronizar
-        let pending_data = db
-            .get_pending_sync(self.config.cloud_sync_batch_size as usize)
-            .await?;
+        let batch_size = self.settings.borrow().cloud_sync_batch_size;
+        // Lecturas encoladas antes de que su dispositivo o ubicación dejara de
+        // reenviarse al cloud: se retiran de la cola sin enviarlas
+        let (pending_data, local_only): (Vec<_>, Vec<_>) = db
+            .get_pending_sync(batch_size as usize)
+            .await?
+            .into_iter()
+            .partition(|data| {
+                self.overrides
+                    .forwards_to_cloud(&data.header.device_id, &data.header.location)
+            });
+        if !local_only.is_empty() {
+            let ids: Vec<_> = local_only.iter().map(|data| data.id).collect();
+            db.mark_local_only(&ids).await?;
+            tracing::debug!(
+                count = ids.len(),
+                "Lecturas retiradas de la cola por ajustes sin reenvío al cloud"
+            );
+        }
 
         if pending_data.is_empty() {
             tracing::debug!("No hay datos pendientes de sincronización");
             return Ok(());
         }
 
-        // Asegurar cliente MQTT inicializado
-        if self.mqtt_client.is_none() {
-            self.mqtt_client = Some(self.init_mqtt_client().await?);
-        }
-
-        let client = self.mqtt_client.as_ref().unwrap();
+        self.ensure_client().await?;
+        let client = self.mqtt_client.as_ref();
 
         // Enviar cada dato procesado como mensaje individual
         let mut sent_count = 0;
-        let mut failed_ids = Vec::new();
+        let mut failures = Vec::new();
 
         for data in &pending_data {
             match self.send_to_cloud_mqtt(client, data).await {
                 Ok(_) => {
                     sent_count += 1;
+                    self.telemetry.cloud_message("sent");
                 }
                 Err(e) => {
+                    self.telemetry.cloud_message("failed");
                     tracing::error!(
                         id = %data.id,
                         error = %e,
                         "Error enviando dato al cloud"
                     );
-                    failed_ids.push(data.id);
+                    failures.push((data.id, e.to_string()));
                 }
             }
 
@@ -115,7 +287,7 @@ impl CloudSync {
         if sent_count > 0 {
             let successful_ids: Vec<_> = pending_data
                 .iter()
-                .filter(|d| !failed_ids.contains(&d.id))
+                .filter(|d| !failures.iter().any(|(id, _)| *id == d.id))
                 .map(|d| d.id)
                 .collect();
 
@@ -123,42 +295,133 @@ impl CloudSync {
 
             tracing::info!(
                 sent = sent_count,
-                failed = failed_ids.len(),
+                failed = failures.len(),
                 "Sincronización completada via MQTT"
             );
         }
 
-        if !failed_ids.is_empty() {
-            anyhow::bail!("Falló el envío de {} mensajes", failed_ids.len());
+        if let Ok(pending) = db.count_pending_sync().await {
+            self.telemetry.set_sync_backlog(pending);
+        }
+
+        if !failures.is_empty() {
+            db.mark_sync_failed(&failures).await?;
+            anyhow::bail!("Falló el envío de {} mensajes", failures.len());
+        }
+
+        Ok(())
+    }
+
+    /// Asegura el cliente MQTT inicializado (el sink simulado no lo necesita)
+    async fn ensure_client(&mut self) -> anyhow::Result<()> {
+        if self.config.cloud_sink == CloudSink::Mqtt && self.mqtt_client.is_none() {
+            self.mqtt_client = Some(self.init_mqtt_client().await?);
+        }
+        Ok(())
+    }
+
+    /// Topic de avisos del gateway en el broker cloud
+    pub fn notices_topic(config: &Config) -> String {
+        format!("gateways/{}/notices", config.gateway_id)
+    }
+
+    /// Publica los avisos pendientes del gateway (p. ej. bajas de dispositivos)
+    ///
+    /// Un aviso solo se elimina de la cola cuando se publicó; los demás se
+    /// reintentan en la siguiente sincronización.
+    async fn publish_notices(&mut self, db: &Database) -> anyhow::Result<()> {
+        let notices = db.pending_cloud_notices(NOTICES_PER_SYNC).await?;
+        if notices.is_empty() {
+            return Ok(());
         }
 
+        self.ensure_client().await?;
+        let topic = Self::notices_topic(&self.config);
+        let mut sent = Vec::with_capacity(notices.len());
+
+        for notice in &notices {
+            let payload = serde_json::json!({
+                "gateway_id": self.config.gateway_id,
+                "user_uuid": self.config.user_uuid,
+                "kind": notice.kind,
+                "device_id": notice.device_id,
+                "created_at": notice.created_at,
+                "sent_at": Utc::now(),
+                "data": notice.payload,
+            })
+            .to_string();
+
+            match &self.mqtt_client {
+                Some(client) => {
+                    if let Err(e) = client
+                        .publish(&topic, QoS::AtLeastOnce, false, payload.as_bytes())
+                        .await
+                    {
+                        tracing::error!(
+                            notice_id = notice.id,
+                            "Error enviando aviso al cloud: {}",
+                            e
+                        );
+                        break;
+                    }
+                }
+                None => tracing::info!(
+                    topic = %topic,
+                    payload = %payload,
+                    "Aviso enviado al cloud simulado"
+                ),
+            }
+            sent.push(notice.id);
+        }
+
+        db.delete_cloud_notices(&sent).await?;
+        tracing::info!(count = sent.len(), topic = %topic, "Avisos publicados en el cloud");
         Ok(())
     }
 
-    /// Envía un dato procesado al cloud via MQTT
+    /// Envía un dato procesado al cloud via MQTT, o al log sin cliente (sink simulado)
     async fn send_to_cloud_mqtt(
         &self,
-        client: &AsyncClient,
+        client: Option<&AsyncClient>,
         data: &crate::models::ProcessedSensorData,
     ) -> anyhow::Result<()> {
         // Construir header con UUID del usuario del gateway
         let cloud_header = CloudHeader {
             user_uuid: self.config.user_uuid.clone(),
+            source_user_uuid: data.header.user_uuid.clone(),
             device_id: data.header.device_id.clone(),
+            message_id: data.header.message_id.clone(),
             location: data.header.location.clone(),
             topic: data.header.topic.clone(),
             should_requeue: data.header.should_requeue,
             gateway_id: self.config.gateway_id.clone(),
+            firmware_version: data.header.firmware_version.clone(),
+            hardware_model: data.header.hardware_model.clone(),
+            rssi: data.header.rssi,
         };
 
         // Construir métricas incluyendo las computadas si existen
         let mut all_metrics = data.metrics.clone();
 
+        // Reemplazar las muestras crudas por su EWMA si está configurado
+        if self.config.cloud_forward_smoothed {
+            for metric in &mut all_metrics {
+                if let Some(ewma) = data
+                    .computed
+                    .stats
+                    .get(&format!("{}_ewma", metric.measurement))
+                {
+                    metric.value = *ewma;
+                }
+            }
+        }
+
         // Agregar métricas computadas como métricas adicionales
         if let Some(hi) = data.computed.heat_index {
             all_metrics.push(SensorMetric {
                 measurement: "HeatIndex".to_string(),
                 value: hi,
+                unit: None,
             });
         }
 
@@ -166,6 +429,7 @@ impl CloudSync {
             all_metrics.push(SensorMetric {
                 measurement: "DewPoint".to_string(),
                 value: dp,
+                unit: None,
             });
         }
 
@@ -173,19 +437,39 @@ impl CloudSync {
             all_metrics.push(SensorMetric {
                 measurement: "ComfortLevel".to_string(),
                 value: cl,
+                unit: None,
             });
         }
 
+        let extra_metrics = [
+            ("AbsoluteHumidity", data.computed.absolute_humidity),
+            ("VaporPressureDeficit", data.computed.vapor_pressure_deficit),
+            ("WetBulb", data.computed.wet_bulb),
+            ("AirQualityIndex", data.computed.air_quality_index),
+            ("VentilationScore", data.computed.ventilation_score),
+        ];
+        for (measurement, value) in extra_metrics {
+            if let Some(value) = value {
+                all_metrics.push(SensorMetric {
+                    measurement: measurement.to_string(),
+                    value,
+                    unit: None,
+                });
+            }
+        }
+
         // Agregar quality score como métrica
         all_metrics.push(SensorMetric {
             measurement: "QualityScore".to_string(),
             value: data.quality.score as f32,
+            unit: None,
         });
 
         // Construir payload
         let payload = CloudPayload {
             header: cloud_header,
             metrics: all_metrics,
+            measured_at: data.measured_at,
             sent_at: Utc::now(),
             quality: data.quality.clone(),
         };
@@ -193,45 +477,128 @@ impl CloudSync {
         // Serializar a JSON
         let payload_json = serde_json::to_string(&payload)?;
 
-        // Publicar en el topic del cloud
+        // Publicar en el topic del cloud (modificable en tiempo de ejecución)
+        let topic = self.settings.borrow().cloud_mqtt_topic.clone();
+        let Some(client) = client else {
+            tracing::info!(
+                topic = %topic,
+                payload = %payload_json,
+                "Dato enviado al cloud simulado"
+            );
+            return Ok(());
+        };
+        let started = Instant::now();
         client
-            .publish(
-                &self.config.cloud_mqtt_topic,
-                QoS::AtLeastOnce,
-                false,
-                payload_json.as_bytes(),
-            )
+            .publish(&topic, QoS::AtLeastOnce, false, payload_json.as_bytes())
             .await?;
+        self.telemetry.observe_cloud_publish(started.elapsed());
 
         tracing::debug!(
             device_id = %data.header.device_id,
-            topic = %self.config.cloud_mqtt_topic,
+            topic = %topic,
             "Dato enviado al cloud via MQTT"
         );
 
         Ok(())
     }
 
-    /// Tarea periódica de sincronización
-    pub async fn start_sync_task(&mut self, db: Database) {
-        let interval_secs = self.config.cloud_sync_interval_secs;
+    /// Lanza la tarea de sincronización y retorna su handle
+    pub fn spawn(self, db: Database) -> CloudSyncHandle {
+        let (commands, receiver) = mpsc::channel(SYNC_COMMANDS_CAPACITY);
+        let requested = Arc::new(AtomicBool::new(false));
+        let paused = watch::Sender::new(false);
+        let handle = CloudSyncHandle {
+            commands,
+            requested: requested.clone(),
+            paused: paused.subscribe(),
+        };
+        tokio::spawn(self.run(db, receiver, requested, paused));
+        handle
+    }
+
+    /// Tarea de sincronización: periódica y a pedido
+    /// El intervalo se reprograma en cuanto cambia en la configuración en tiempo de ejecución
+    async fn run(
+        mut self,
+        db: Database,
+        mut commands: mpsc::Receiver<SyncCommand>,
+        requested: Arc<AtomicBool>,
+        paused: watch::Sender<bool>,
+    ) {
+        let mut settings = self.settings.clone();
+        let mut interval_secs = settings.borrow_and_update().cloud_sync_interval_secs;
         let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
 
+        // Con configuración remota la conexión se abre de inmediato para recibirla
+        // aunque no haya datos pendientes
+        if self.remote_config.is_some() {
+            match self.init_mqtt_client().await {
+                Ok(client) => self.mqtt_client = Some(client),
+                Err(e) => tracing::error!("Error conectando al broker MQTT del cloud: {}", e),
+            }
+        }
+
         tracing::info!(
             interval_secs = interval_secs,
             "Tarea de sincronización periódica iniciada (MQTT)"
         );
 
         loop {
-            interval.tick().await;
-
-            if let Err(e) = self.sync_data(db.clone()).await {
-                tracing::error!("Error en sincronización periódica: {}", e);
+            tokio::select! {
+                _ = interval.tick() => {
+                    if *paused.borrow() {
+                        continue;
+                    }
+                    if let Err(e) = self.sync_data(db.clone()).await {
+                        tracing::error!("Error en sincronización periódica: {}", e);
+                    }
+                }
+                command = commands.recv() => match command {
+                    Some(SyncCommand::SyncNow) => {
+                        requested.store(false, Ordering::Release);
+                        if *paused.borrow() {
+                            tracing::debug!("Sincronización pedida con la sincronización pausada");
+                            continue;
+                        }
+                        if let Err(e) = self.sync_data(db.clone()).await {
+                            tracing::error!("Error en sincronización: {}", e);
+                        }
+                    }
+                    Some(SyncCommand::Pause) => {
+                        if !paused.send_replace(true) {
+                            tracing::info!("Sincronización con el cloud pausada");
+                        }
+                    }
+                    Some(SyncCommand::Resume) => {
+                        if paused.send_replace(false) {
+                            tracing::info!("Sincronización con el cloud reanudada");
+                            if let Err(e) = self.sync_data(db.clone()).await {
+                                tracing::error!("Error en sincronización: {}", e);
+                            }
+                        }
+                    }
+                    Some(SyncCommand::Shutdown) | None => {
+                        tracing::info!("Tarea de sincronización detenida");
+                        break;
+                    }
+                },
+                Ok(()) = settings.changed() => {
+                    let updated = settings.borrow_and_update().cloud_sync_interval_secs;
+                    if updated != interval_secs {
+                        interval_secs = updated;
+                        interval = tokio::time::interval_at(
+                            tokio::time::Instant::now() + Duration::from_secs(interval_secs),
+                            Duration::from_secs(interval_secs),
+                        );
+                        tracing::info!(interval_secs, "Intervalo de sincronización actualizado");
+                    }
+                }
             }
         }
     }
 
     /// Intenta resincronizar datos que fallaron previamente
+    #[allow(dead_code)]
     pub async fn retry_failed_syncs(&mut self, db: Database) -> anyhow::Result<()> {
         tracing::info!("Reintentando sincronizaciones fallidas");
         self.sync_data(db).await
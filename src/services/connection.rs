@@ -0,0 +1,77 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Mutex;
+
+/// Estado de la conexión con un broker MQTT
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    /// El cliente aún no intentó conectarse
+    #[default]
+    Idle,
+    Connected,
+    Disconnected,
+    /// El servicio está deshabilitado por configuración
+    Disabled,
+}
+
+/// Estado observado de una conexión, tal como se reporta en los health checks
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConnectionReport {
+    pub state: ConnectionState,
+    /// Momento del último cambio de estado
+    pub since: Option<DateTime<Utc>>,
+    /// Último error del eventloop (se conserva tras reconectar)
+    pub last_error: Option<String>,
+    /// Conexiones establecidas desde el arranque
+    pub connects: u64,
+    /// Conexiones perdidas desde el arranque
+    pub disconnects: u64,
+}
+
+/// Estado de conexión compartido entre el eventloop MQTT y los health checks
+///
+/// El eventloop lo actualiza con cada ConnAck o error de conexión; así el
+/// estado reportado refleja la conectividad real y no la mera existencia del cliente.
+#[derive(Default)]
+pub struct ConnectionStatus {
+    report: Mutex<ConnectionReport>,
+}
+
+impl ConnectionStatus {
+    pub fn set_connected(&self) {
+        let mut report = self.report.lock().unwrap();
+        if report.state != ConnectionState::Connected {
+            report.state = ConnectionState::Connected;
+            report.since = Some(Utc::now());
+            report.connects += 1;
+        }
+    }
+
+    pub fn set_disconnected(&self, error: impl ToString) {
+        let mut report = self.report.lock().unwrap();
+        if report.state != ConnectionState::Disconnected {
+            // Solo cuenta como caída si había conexión (no los reintentos fallidos)
+            if report.state == ConnectionState::Connected {
+                report.disconnects += 1;
+            }
+            report.state = ConnectionState::Disconnected;
+            report.since = Some(Utc::now());
+        }
+        report.last_error = Some(error.to_string());
+    }
+
+    pub fn set_disabled(&self) {
+        let mut report = self.report.lock().unwrap();
+        report.state = ConnectionState::Disabled;
+        report.since = Some(Utc::now());
+    }
+
+    pub fn report(&self) -> ConnectionReport {
+        self.report.lock().unwrap().clone()
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.report.lock().unwrap().state
+    }
+}
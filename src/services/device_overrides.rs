@@ -0,0 +1,104 @@
+use crate::models::{DeviceOverride, OverrideCalibration, OverrideScope};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Ajustes efectivos de una lectura tras combinar dispositivo, ubicación y
+/// configuración global
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedOverrides {
+    pub profile: Option<String>,
+    /// Calibraciones por medición (clave en minúsculas)
+    pub calibrations: HashMap<String, OverrideCalibration>,
+    pub anomaly_zscore_threshold: Option<f32>,
+    pub baseline_zscore_threshold: Option<f32>,
+    pub sync_priority: i32,
+    pub forward_to_cloud: bool,
+    /// Cuotas propias; sin ellas rigen las globales
+    pub quota_messages_per_day: Option<u64>,
+    pub quota_max_rows: Option<u64>,
+}
+
+impl Default for ResolvedOverrides {
+    fn default() -> Self {
+        Self {
+            profile: None,
+            calibrations: HashMap::new(),
+            anomaly_zscore_threshold: None,
+            baseline_zscore_threshold: None,
+            sync_priority: 0,
+            forward_to_cloud: true,
+            quota_messages_per_day: None,
+            quota_max_rows: None,
+        }
+    }
+}
+
+/// Ajustes por dispositivo y por ubicación en memoria
+///
+/// Compartido entre el procesador edge (calibración, umbrales y perfil) y la
+/// sincronización con el cloud (reenvío). El ajuste del dispositivo tiene
+/// prioridad sobre el de su ubicación campo a campo.
+#[derive(Default)]
+pub struct DeviceOverrides {
+    entries: RwLock<HashMap<(OverrideScope, String), DeviceOverride>>,
+}
+
+impl DeviceOverrides {
+    /// Reemplaza los ajustes cargados
+    pub fn replace(&self, overrides: Vec<DeviceOverride>) {
+        *self.entries.write().unwrap() = overrides
+            .into_iter()
+            .map(|entry| ((entry.scope, entry.target.clone()), entry))
+            .collect();
+    }
+
+    /// Ajustes efectivos para un dispositivo en una ubicación
+    pub fn resolve(&self, device_id: &str, location: &str) -> ResolvedOverrides {
+        let entries = self.entries.read().unwrap();
+        let mut resolved = ResolvedOverrides::default();
+        if entries.is_empty() {
+            return resolved;
+        }
+
+        // Primero la ubicación y después el dispositivo, que la sobrescribe
+        let layers = [
+            entries.get(&(OverrideScope::Location, location.to_string())),
+            entries.get(&(OverrideScope::Device, device_id.to_string())),
+        ];
+
+        for entry in layers.into_iter().flatten() {
+            if entry.profile.is_some() {
+                resolved.profile = entry.profile.clone();
+            }
+            for (measurement, calibration) in &entry.calibrations {
+                resolved
+                    .calibrations
+                    .insert(measurement.to_lowercase(), *calibration);
+            }
+            resolved.anomaly_zscore_threshold = entry
+                .anomaly_zscore_threshold
+                .or(resolved.anomaly_zscore_threshold);
+            resolved.baseline_zscore_threshold = entry
+                .baseline_zscore_threshold
+                .or(resolved.baseline_zscore_threshold);
+            if let Some(priority) = entry.sync_priority {
+                resolved.sync_priority = priority;
+            }
+            if let Some(forward) = entry.forward_to_cloud {
+                resolved.forward_to_cloud = forward;
+            }
+            resolved.quota_messages_per_day = entry
+                .quota_messages_per_day
+                .or(resolved.quota_messages_per_day);
+            resolved.quota_max_rows = entry.quota_max_rows.or(resolved.quota_max_rows);
+        }
+
+        resolved
+    }
+
+    /// Indica si las lecturas del dispositivo deben reenviarse al cloud
+    pub fn forwards_to_cloud(&self, device_id: &str, location: &str) -> bool {
+        self.resolve(device_id, location).forward_to_cloud
+    }
+}
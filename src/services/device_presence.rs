@@ -0,0 +1,289 @@
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::{DeviceStatusEvent, ProcessedSensorData};
+use crate::services::alerting::{AlertContext, Alerting};
+use chrono::{DateTime, Utc};
+use rumqttc::{AsyncClient, QoS};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Regla con la que se registran los dispositivos silenciosos en el historial de alertas
+pub const DEVICE_OFFLINE_RULE: &str = "system:device_offline";
+
+/// Eventos de conectividad en cola por suscriptor antes de descartar los más antiguos
+const STATUS_EVENTS_CAPACITY: usize = 64;
+
+#[derive(Default)]
+struct PresenceState {
+    /// Dispositivos marcados offline y desde cuándo
+    offline: HashMap<String, DateTime<Utc>>,
+    /// Última lectura vista en el pipeline, que puede adelantarse al registro
+    activity: HashMap<String, DateTime<Utc>>,
+}
+
+/// Detecta dispositivos que dejan de enviar datos
+///
+/// Un dispositivo pasa a offline tras `offline_after_secs` de su perfil (o
+/// `DEVICE_OFFLINE_AFTER_SECS`) sin lecturas, y vuelve a online con la
+/// siguiente. Cada cambio se persiste en el registro, se emite en
+/// `/api/v1/events`, dispara o resuelve una alerta y se publica retenido en
+/// `sensors/{id}/status` del broker local.
+pub struct PresenceMonitor {
+    config: Arc<Config>,
+    db: Database,
+    alerting: Arc<Alerting>,
+    client: AsyncClient,
+    events: broadcast::Sender<DeviceStatusEvent>,
+    state: Mutex<PresenceState>,
+}
+
+impl PresenceMonitor {
+    pub fn new(
+        config: Arc<Config>,
+        db: Database,
+        alerting: Arc<Alerting>,
+        client: AsyncClient,
+    ) -> Self {
+        Self {
+            config,
+            db,
+            alerting,
+            client,
+            events: broadcast::channel(STATUS_EVENTS_CAPACITY).0,
+            state: Mutex::new(PresenceState::default()),
+        }
+    }
+
+    /// Suscribe a los cambios de conectividad de los dispositivos
+    pub fn subscribe(&self) -> broadcast::Receiver<DeviceStatusEvent> {
+        self.events.subscribe()
+    }
+
+    /// Topic de estado de un dispositivo en el broker local
+    pub fn topic(device_id: &str) -> String {
+        format!("sensors/{}/status", device_id)
+    }
+
+    /// Tarea de detección: revisa el registro periódicamente y marca online
+    /// en cuanto llega una lectura de un dispositivo offline
+    pub async fn start_monitor_task(
+        self: Arc<Self>,
+        mut readings: broadcast::Receiver<Arc<ProcessedSensorData>>,
+    ) {
+        match self.db.list_device_presence().await {
+            Ok(devices) => {
+                let mut state = self.state.lock().unwrap();
+                state.offline = devices
+                    .into_iter()
+                    .filter_map(|device| Some((device.device_id, device.offline_since?)))
+                    .collect();
+            }
+            Err(e) => tracing::error!("Error cargando el estado de los dispositivos: {}", e),
+        }
+
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.device_presence_check_secs));
+        tracing::info!(
+            offline_after_secs = self.config.device_offline_after_secs,
+            "Detección de dispositivos offline iniciada"
+        );
+
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    if let Err(e) = self.check(Utc::now()).await {
+                        tracing::error!("Error revisando la presencia de dispositivos: {}", e);
+                    }
+                }
+                reading = readings.recv() => match reading {
+                    Ok(data) => self.record_activity(&data).await,
+                    // La revisión periódica cubre las lecturas omitidas
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return,
+                },
+            }
+        }
+    }
+
+    /// Registra la actividad de una lectura y recupera el dispositivo si estaba offline
+    async fn record_activity(&self, data: &ProcessedSensorData) {
+        let device_id = &data.header.device_id;
+        let was_offline = {
+            let mut state = self.state.lock().unwrap();
+            let activity = state
+                .activity
+                .entry(device_id.clone())
+                .or_insert(data.gateway_timestamp);
+            *activity = (*activity).max(data.gateway_timestamp);
+            state.offline.contains_key(device_id)
+        };
+
+        if was_offline
+            && let Err(e) = self
+                .mark_online(
+                    device_id,
+                    &data.header.location,
+                    data.gateway_timestamp,
+                    Utc::now(),
+                )
+                .await
+        {
+            tracing::error!(device_id = %device_id, "Error marcando el dispositivo online: {}", e);
+        }
+    }
+
+    /// Compara la última actividad de cada dispositivo con su umbral de silencio
+    async fn check(&self, now: DateTime<Utc>) -> anyhow::Result<()> {
+        let thresholds: HashMap<String, u64> = self
+            .db
+            .list_device_profiles()
+            .await?
+            .into_iter()
+            .filter_map(|profile| Some((profile.device_type, profile.offline_after_secs?)))
+            .collect();
+
+        for device in self.db.list_device_presence().await? {
+            let last_seen = {
+                let state = self.state.lock().unwrap();
+                state
+                    .activity
+                    .get(&device.device_id)
+                    .map_or(device.last_seen, |at| (*at).max(device.last_seen))
+            };
+
+            match device.offline_since {
+                None => {
+                    let threshold = device
+                        .device_type
+                        .as_ref()
+                        .and_then(|device_type| thresholds.get(device_type).copied())
+                        .unwrap_or(self.config.device_offline_after_secs);
+                    let silence = (now - last_seen).num_seconds();
+                    if threshold > 0 && silence > threshold as i64 {
+                        self.mark_offline(
+                            &device.device_id,
+                            &device.location,
+                            last_seen,
+                            threshold,
+                            now,
+                        )
+                        .await?;
+                    }
+                }
+                Some(since) if last_seen > since => {
+                    self.mark_online(&device.device_id, &device.location, last_seen, now)
+                        .await?;
+                }
+                Some(_) => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn mark_offline(
+        &self,
+        device_id: &str,
+        location: &str,
+        last_seen: DateTime<Utc>,
+        threshold: u64,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        let silence_secs = (now - last_seen).num_seconds();
+        tracing::warn!(device_id = %device_id, silence_secs, "Dispositivo sin datos, marcado offline");
+
+        self.db.set_device_offline(device_id, Some(now)).await?;
+        self.state
+            .lock()
+            .unwrap()
+            .offline
+            .insert(device_id.to_string(), now);
+        let alert = self.alert(device_id, location, silence_secs, Some(threshold), now);
+        self.alerting.fire(&alert).await?;
+
+        self.announce(device_id, "offline", last_seen, now);
+        Ok(())
+    }
+
+    async fn mark_online(
+        &self,
+        device_id: &str,
+        location: &str,
+        last_seen: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<()> {
+        // Solo la primera lectura tras el silencio anuncia el cambio
+        let Some(since) = self.state.lock().unwrap().offline.remove(device_id) else {
+            return Ok(());
+        };
+        tracing::info!(device_id = %device_id, "Dispositivo de nuevo online");
+
+        self.db.set_device_offline(device_id, None).await?;
+        // Tiempo desde que se marcó offline, sin contar el umbral previo
+        let offline_secs = (last_seen - since).num_seconds().max(0);
+        let alert = self.alert(device_id, location, offline_secs, None, now);
+        self.alerting.resolve(&alert).await?;
+
+        self.announce(device_id, "online", last_seen, now);
+        Ok(())
+    }
+
+    /// Alerta de silencio del dispositivo; el umbral solo se conoce al dispararla
+    fn alert(
+        &self,
+        device_id: &str,
+        location: &str,
+        silence_secs: i64,
+        threshold: Option<u64>,
+        now: DateTime<Utc>,
+    ) -> AlertContext {
+        AlertContext {
+            rule_id: DEVICE_OFFLINE_RULE.to_string(),
+            rule_name: "Dispositivo sin datos".to_string(),
+            severity: self.config.device_offline_alert_severity,
+            device_id: device_id.to_string(),
+            location: Some(location.to_string()),
+            metric: "silence_secs".to_string(),
+            value: silence_secs as f64,
+            condition: match threshold {
+                Some(secs) => format!("sin lecturas durante más de {} s", secs),
+                None => "sin lecturas".to_string(),
+            },
+            at: now,
+        }
+    }
+
+    /// Emite el cambio en el stream de eventos y lo publica retenido en el broker local
+    fn announce(
+        &self,
+        device_id: &str,
+        status: &'static str,
+        last_seen: DateTime<Utc>,
+        now: DateTime<Utc>,
+    ) {
+        let event = DeviceStatusEvent {
+            device_id: device_id.to_string(),
+            status,
+            last_seen,
+            at: now,
+        };
+
+        match serde_json::to_vec(&event) {
+            // try_publish evita bloquear la detección si el broker no está disponible
+            Ok(payload) => {
+                if let Err(e) =
+                    self.client
+                        .try_publish(Self::topic(device_id), QoS::AtLeastOnce, true, payload)
+                {
+                    tracing::warn!(device_id = %device_id, "No se pudo publicar el estado del dispositivo: {}", e);
+                }
+            }
+            Err(e) => tracing::error!("Error serializando el estado del dispositivo: {}", e),
+        }
+
+        // Sin suscriptores el envío falla, lo que no es un error
+        let _ = self.events.send(event);
+    }
+}
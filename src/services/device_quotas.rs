@@ -0,0 +1,238 @@
+use crate::config::{Config, QuotaAction};
+use crate::database::Database;
+use crate::services::device_overrides::DeviceOverrides;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Decisión sobre una lectura entrante
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuotaDecision {
+    /// Dentro de la cuota
+    Accept,
+    /// Fuera de cuota, pero se conserva como muestra (downsample)
+    Sampled,
+    /// Fuera de cuota y descartada
+    Drop,
+}
+
+/// Uso de un dispositivo en el día UTC en curso
+struct DeviceUsage {
+    day: NaiveDate,
+    location: String,
+    /// Mensajes recibidos en el día, incluidos los descartados
+    messages: u64,
+    /// Mensajes fuera de cuota en el día
+    over_quota: u64,
+    dropped: u64,
+    /// Lecturas almacenadas (recontadas periódicamente desde SQLite)
+    stored_rows: u64,
+}
+
+impl DeviceUsage {
+    fn new(day: NaiveDate, location: &str) -> Self {
+        Self {
+            day,
+            location: location.to_string(),
+            messages: 0,
+            over_quota: 0,
+            dropped: 0,
+            stored_rows: 0,
+        }
+    }
+
+    /// Reinicia los contadores diarios al cambiar de día
+    fn roll(&mut self, today: NaiveDate) {
+        if self.day != today {
+            self.day = today;
+            self.messages = 0;
+            self.over_quota = 0;
+            self.dropped = 0;
+        }
+    }
+}
+
+/// Uso y límites de un dispositivo
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceQuotaStatus {
+    pub device_id: String,
+    pub day: NaiveDate,
+    pub messages_today: u64,
+    /// Límite de mensajes por día (None = sin límite)
+    pub messages_per_day: Option<u64>,
+    pub stored_rows: u64,
+    /// Límite de lecturas almacenadas (None = sin límite)
+    pub max_rows: Option<u64>,
+    pub over_quota_today: u64,
+    pub dropped_today: u64,
+    pub exceeded: bool,
+}
+
+/// Cuotas de mensajes y almacenamiento por dispositivo
+///
+/// Protege el almacenamiento compartido de un nodo que publica de más: al
+/// superar los mensajes diarios o las lecturas almacenadas, sus lecturas se
+/// descartan (`throttle`) o se conserva una de cada N (`downsample`), marcadas
+/// con un issue de calidad. Los límites globales se pueden ajustar por
+/// dispositivo o ubicación en los overrides.
+pub struct DeviceQuotas {
+    config: Arc<Config>,
+    overrides: Arc<DeviceOverrides>,
+    usage: Mutex<HashMap<String, DeviceUsage>>,
+}
+
+impl DeviceQuotas {
+    pub fn new(config: Arc<Config>, overrides: Arc<DeviceOverrides>) -> Self {
+        Self {
+            config,
+            overrides,
+            usage: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Límites efectivos (mensajes por día, lecturas almacenadas); 0 = sin límite
+    fn limits(&self, device_id: &str, location: &str) -> (Option<u64>, Option<u64>) {
+        let resolved = self.overrides.resolve(device_id, location);
+        let messages = resolved
+            .quota_messages_per_day
+            .unwrap_or(self.config.device_quota_messages_per_day);
+        let rows = resolved
+            .quota_max_rows
+            .unwrap_or(self.config.device_quota_max_rows);
+
+        (
+            (messages > 0).then_some(messages),
+            (rows > 0).then_some(rows),
+        )
+    }
+
+    /// Contabiliza un mensaje del dispositivo y decide si se almacena
+    pub fn admit(&self, device_id: &str, location: &str, now: DateTime<Utc>) -> QuotaDecision {
+        let (messages_limit, rows_limit) = self.limits(device_id, location);
+        let today = now.date_naive();
+
+        let mut usage = self.usage.lock().unwrap();
+        let entry = usage
+            .entry(device_id.to_string())
+            .or_insert_with(|| DeviceUsage::new(today, location));
+        entry.roll(today);
+        entry.location = location.to_string();
+        entry.messages += 1;
+
+        let exceeded = messages_limit.is_some_and(|limit| entry.messages > limit)
+            || rows_limit.is_some_and(|limit| entry.stored_rows >= limit);
+        if !exceeded {
+            entry.stored_rows += 1;
+            return QuotaDecision::Accept;
+        }
+
+        entry.over_quota += 1;
+        if entry.over_quota == 1 {
+            tracing::warn!(
+                device_id = %device_id,
+                messages_today = entry.messages,
+                stored_rows = entry.stored_rows,
+                action = ?self.config.device_quota_action,
+                "Dispositivo fuera de cuota"
+            );
+        }
+
+        // Con downsample se conserva la primera lectura fuera de cuota y una de cada N
+        let sampled = self.config.device_quota_action == QuotaAction::Downsample
+            && (entry.over_quota - 1).is_multiple_of(self.config.device_quota_downsample_factor);
+        if sampled {
+            entry.stored_rows += 1;
+            QuotaDecision::Sampled
+        } else {
+            entry.dropped += 1;
+            QuotaDecision::Drop
+        }
+    }
+
+    /// Actualiza las lecturas almacenadas por dispositivo: (total, desde hoy)
+    ///
+    /// Las de hoy cubren los mensajes contados antes de un reinicio del gateway.
+    pub fn set_stored_rows(&self, counts: HashMap<String, (u64, u64)>, now: DateTime<Utc>) {
+        let today = now.date_naive();
+        let mut usage = self.usage.lock().unwrap();
+
+        for (device_id, usage) in usage.iter_mut() {
+            if !counts.contains_key(device_id) {
+                usage.stored_rows = 0;
+            }
+        }
+        for (device_id, (total, today_rows)) in counts {
+            let entry = usage
+                .entry(device_id)
+                .or_insert_with(|| DeviceUsage::new(today, ""));
+            entry.roll(today);
+            entry.stored_rows = total;
+            entry.messages = entry.messages.max(today_rows);
+        }
+    }
+
+    /// Uso de un dispositivo
+    pub fn status(&self, device_id: &str) -> Option<DeviceQuotaStatus> {
+        let usage = self.usage.lock().unwrap();
+        usage
+            .get(device_id)
+            .map(|usage| self.build_status(device_id, usage))
+    }
+
+    /// Uso de todos los dispositivos contabilizados
+    pub fn statuses(&self) -> Vec<DeviceQuotaStatus> {
+        let usage = self.usage.lock().unwrap();
+        let mut statuses: Vec<_> = usage
+            .iter()
+            .map(|(device_id, usage)| self.build_status(device_id, usage))
+            .collect();
+        statuses.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+        statuses
+    }
+
+    fn build_status(&self, device_id: &str, usage: &DeviceUsage) -> DeviceQuotaStatus {
+        let (messages_per_day, max_rows) = self.limits(device_id, &usage.location);
+        let today = Utc::now().date_naive();
+        let (messages_today, over_quota_today, dropped_today) = if usage.day == today {
+            (usage.messages, usage.over_quota, usage.dropped)
+        } else {
+            (0, 0, 0)
+        };
+
+        DeviceQuotaStatus {
+            device_id: device_id.to_string(),
+            day: today,
+            messages_today,
+            messages_per_day,
+            stored_rows: usage.stored_rows,
+            max_rows,
+            over_quota_today,
+            dropped_today,
+            exceeded: messages_per_day.is_some_and(|limit| messages_today >= limit)
+                || max_rows.is_some_and(|limit| usage.stored_rows >= limit),
+        }
+    }
+
+    /// Recuenta las lecturas almacenadas desde SQLite
+    pub async fn refresh(&self, db: &Database) -> anyhow::Result<()> {
+        let now = Utc::now();
+        let midnight = now.date_naive().and_time(chrono::NaiveTime::MIN).and_utc();
+        self.set_stored_rows(db.count_readings_by_device(midnight).await?, now);
+        Ok(())
+    }
+
+    /// Tarea de recuento periódico; la retención y las purgas liberan cuota
+    pub async fn start_refresh_task(self: Arc<Self>, db: Database) {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.device_quota_refresh_secs));
+
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.refresh(&db).await {
+                tracing::error!("Error recontando las lecturas por dispositivo: {}", e);
+            }
+        }
+    }
+}
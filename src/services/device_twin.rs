@@ -0,0 +1,101 @@
+use crate::database::Database;
+use crate::models::DeviceTwin;
+use chrono::Utc;
+use rumqttc::{AsyncClient, QoS};
+
+/// Device twin: estado deseado que el gateway pide a cada dispositivo y
+/// estado que el dispositivo reporta
+///
+/// Las propiedades deseadas pendientes (delta) se publican retenidas en
+/// `sensors/{id}/desired`, de modo que un nodo que despierta de deep sleep las
+/// recibe al suscribirse; el nodo confirma publicando en `sensors/{id}/reported`.
+pub struct DeviceTwins {
+    db: Database,
+    client: AsyncClient,
+}
+
+impl DeviceTwins {
+    pub fn new(db: Database, client: AsyncClient) -> Self {
+        Self { db, client }
+    }
+
+    /// Topic en el que el gateway publica el delta de un dispositivo
+    pub fn desired_topic(device_id: &str) -> String {
+        format!("sensors/{}/desired", device_id)
+    }
+
+    /// Twin de un dispositivo (vacío si aún no tiene propiedades)
+    pub async fn get(&self, device_id: &str) -> anyhow::Result<DeviceTwin> {
+        Ok(self
+            .db
+            .get_device_twin(device_id)
+            .await?
+            .unwrap_or_else(|| DeviceTwin::empty(device_id)))
+    }
+
+    /// Modifica el estado deseado y publica el delta resultante
+    pub async fn update_desired(
+        &self,
+        device_id: &str,
+        patch: &serde_json::Value,
+    ) -> anyhow::Result<DeviceTwin> {
+        let twin = self
+            .db
+            .patch_twin_desired(device_id, patch, Utc::now())
+            .await?;
+
+        tracing::info!(
+            device_id = %device_id,
+            version = twin.desired_version,
+            "Estado deseado del dispositivo actualizado"
+        );
+        self.publish_delta(&twin);
+        Ok(twin)
+    }
+
+    /// Registra las propiedades reportadas por un dispositivo
+    ///
+    /// Si cambian las propiedades pendientes, se vuelve a publicar el delta
+    /// (vacío cuando el dispositivo ya aplicó todo lo deseado).
+    pub async fn apply_reported(&self, device_id: &str, payload: &[u8]) -> anyhow::Result<()> {
+        let reported: serde_json::Value = serde_json::from_slice(payload)?;
+        if !reported.is_object() {
+            anyhow::bail!("El estado reportado debe ser un objeto JSON");
+        }
+
+        let previous = self.get(device_id).await?;
+        let twin = self
+            .db
+            .patch_twin_reported(device_id, &reported, Utc::now())
+            .await?;
+
+        tracing::debug!(device_id = %device_id, "Estado reportado del dispositivo recibido");
+
+        if twin.delta != previous.delta {
+            self.publish_delta(&twin);
+        }
+        Ok(())
+    }
+
+    /// try_publish evita bloquear el loop MQTT, desde el que se procesan los reportes
+    fn publish_delta(&self, twin: &DeviceTwin) {
+        let payload = serde_json::json!({
+            "version": twin.desired_version,
+            "desired": twin.delta,
+            "at": Utc::now(),
+        });
+
+        if let Err(e) = self.client.try_publish(
+            Self::desired_topic(&twin.device_id),
+            QoS::AtLeastOnce,
+            true,
+            payload.to_string(),
+        ) {
+            tracing::warn!(
+                device_id = %twin.device_id,
+                "No se pudo publicar el estado deseado: {}",
+                e
+            );
+        }
+    }
+}
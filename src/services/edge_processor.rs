@@ -1,24 +1,411 @@
+mod air_quality;
+mod baselines;
+mod calibration;
+mod clock;
+mod comfort;
+mod filters;
+mod hampel;
+mod history;
+mod interpolation;
+mod power;
+mod profiles;
+mod rate_of_change;
+mod rules;
+mod scripting;
+mod smoothing;
+mod units;
+mod windows;
+
 use crate::config::Config;
+use crate::database::Database;
 use crate::models::*;
-use chrono::Utc;
+use crate::services::device_overrides::DeviceOverrides;
+use crate::services::device_quotas::{DeviceQuotas, QuotaDecision};
+use crate::services::runtime_config::RuntimeSettings;
+use crate::telemetry::Telemetry;
+use baselines::HourlyBaselines;
+use calibration::CalibrationStore;
+use chrono::{DateTime, Utc};
+use clock::{ClockResolver, ReadingTime};
+use comfort::ComfortModels;
+use filters::NoiseFilters;
+use hampel::HampelFilter;
+use history::MetricHistory;
+use interpolation::MissingValueFiller;
+use power::PowerTracker;
+use profiles::ProfileStore;
+use rate_of_change::RateOfChangeDetector;
+use rules::RuleEngine;
+use scripting::ScriptHooks;
+use smoothing::EwmaTracker;
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+use tokio::sync::{broadcast, mpsc, watch};
 use uuid::Uuid;
+use windows::WindowAggregator;
+
+pub use clock::UptimeAnchor;
+pub use power::{BATTERY_MEASUREMENTS, BatteryLevel, RSSI_MEASUREMENT};
+pub use rules::{RuleEvent, RuleTransition};
+
+/// Días de histórico consultados para inicializar las ventanas al arrancar
+const HISTORY_SEED_DAYS: i64 = 7;
+
+/// Lecturas retenidas para suscriptores lentos del stream en vivo
+const LIVE_STREAM_CAPACITY: usize = 256;
 
 /// Servicio de procesamiento edge computing
 /// Realiza cálculos y análisis locales antes de enviar a la nube
 pub struct EdgeProcessor {
     config: Arc<Config>,
+    /// Ajustes modificables en tiempo de ejecución (umbrales de anomalías)
+    settings: watch::Receiver<RuntimeSettings>,
+    history: MetricHistory,
+    baselines: HourlyBaselines,
+    ewma: EwmaTracker,
+    filters: NoiseFilters,
+    hampel: HampelFilter,
+    windows: WindowAggregator,
+    filler: MissingValueFiller,
+    comfort: ComfortModels,
+    rate_of_change: RateOfChangeDetector,
+    clock: ClockResolver,
+    power: PowerTracker,
+    /// Rangos de validez por medición (clave en minúsculas)
+    ranges: RwLock<HashMap<String, MeasurementRange>>,
+    calibrations: CalibrationStore,
+    profiles: ProfileStore,
+    /// Ajustes por dispositivo y ubicación (compartidos con la sincronización)
+    overrides: Arc<DeviceOverrides>,
+    /// Cuotas de mensajes y almacenamiento por dispositivo
+    quotas: Arc<DeviceQuotas>,
+    rules: RuleEngine,
+    scripts: ScriptHooks,
+    /// Eventos de reglas enviados al ejecutor de acciones
+    rule_events: mpsc::Sender<RuleEvent>,
+    /// Lecturas procesadas publicadas a los clientes del stream en vivo
+    live: broadcast::Sender<Arc<ProcessedSensorData>>,
+    telemetry: Arc<Telemetry>,
 }
 
 impl EdgeProcessor {
-    pub fn new(config: Arc<Config>) -> Self {
-        Self { config }
+    pub fn new(
+        config: Arc<Config>,
+        settings: watch::Receiver<RuntimeSettings>,
+        rule_events: mpsc::Sender<RuleEvent>,
+        telemetry: Arc<Telemetry>,
+    ) -> Self {
+        let overrides = Arc::new(DeviceOverrides::default());
+
+        Self {
+            settings,
+            history: MetricHistory::new(config.anomaly_window_size),
+            baselines: HourlyBaselines::default(),
+            ewma: EwmaTracker::new(config.ewma_alpha),
+            filters: NoiseFilters::new(
+                &config.measurement_filters,
+                config.kalman_process_noise,
+                config.kalman_measurement_noise,
+                config.lowpass_cutoff_hz,
+            ),
+            hampel: HampelFilter::new(config.hampel_window_size, config.hampel_threshold),
+            windows: WindowAggregator::new(&config.aggregation_windows),
+            filler: MissingValueFiller::new(
+                config.interpolation_method,
+                config.interpolation_max_age_secs,
+            ),
+            comfort: ComfortModels::new(&config),
+            clock: ClockResolver::new(
+                config.device_clock_tolerance_secs,
+                config.device_max_backfill_hours,
+            ),
+            power: PowerTracker::new(&config),
+            rate_of_change: RateOfChangeDetector::new(&config.max_rate_of_change),
+            ranges: RwLock::new(HashMap::new()),
+            calibrations: CalibrationStore::default(),
+            profiles: ProfileStore::default(),
+            quotas: Arc::new(DeviceQuotas::new(config.clone(), overrides.clone())),
+            overrides,
+            rules: RuleEngine::default(),
+            scripts: ScriptHooks::new(&config.scripts_dir),
+            rule_events,
+            live: broadcast::channel(LIVE_STREAM_CAPACITY).0,
+            telemetry,
+            config,
+        }
+    }
+
+    /// Reemplaza los rangos de validez usados en la detección de anomalías
+    pub fn set_ranges(&self, ranges: Vec<MeasurementRange>) {
+        *self.ranges.write().unwrap() = ranges
+            .into_iter()
+            .map(|range| (range.measurement.to_lowercase(), range))
+            .collect();
+    }
+
+    /// Reemplaza las calibraciones aplicadas a las lecturas
+    pub fn set_calibrations(&self, calibrations: Vec<Calibration>) {
+        self.calibrations.replace(calibrations);
+    }
+
+    /// Reemplaza los perfiles de métricas por tipo de dispositivo
+    pub fn set_profiles(&self, profiles: Vec<DeviceProfile>) {
+        self.profiles.replace(profiles);
+    }
+
+    /// Reemplaza los ajustes por dispositivo y ubicación
+    pub fn set_overrides(&self, overrides: Vec<DeviceOverride>) {
+        self.overrides.replace(overrides);
+    }
+
+    /// Ajustes por dispositivo y ubicación, para compartirlos con otros servicios
+    pub fn overrides(&self) -> Arc<DeviceOverrides> {
+        self.overrides.clone()
+    }
+
+    /// Reemplaza las reglas de acciones evaluadas en cada lectura
+    pub fn set_rules(&self, rules: Vec<Rule>) {
+        self.rules.replace(rules);
+    }
+
+    /// Reemplaza las etiquetas de los dispositivos (reglas limitadas a un grupo)
+    pub fn set_device_tags(&self, tags: HashMap<String, Vec<String>>) {
+        self.rules.set_device_tags(tags);
+    }
+
+    /// Vuelve a leer de la base de datos rangos, calibraciones, perfiles, ajustes
+    /// por dispositivo, reglas y etiquetas de los dispositivos
+    pub async fn load_definitions(&self, db: &Database) -> anyhow::Result<()> {
+        self.set_ranges(db.list_measurement_ranges().await?);
+        self.set_calibrations(db.list_calibrations(None).await?);
+        self.set_profiles(db.list_device_profiles().await?);
+        self.set_overrides(db.list_device_overrides().await?);
+        self.set_rules(db.list_rules().await?);
+        self.set_device_tags(db.list_device_tags().await?);
+        Ok(())
+    }
+
+    /// Recarga los scripts de métricas derivadas modificados en disco
+    pub fn reload_scripts(&self) {
+        self.scripts.reload();
+    }
+
+    /// Revisa periódicamente el directorio de scripts para recargarlos sin reiniciar
+    pub async fn start_script_reload_task(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+            self.config.scripts_reload_secs,
+        ));
+
+        loop {
+            interval.tick().await;
+
+            let processor = self.clone();
+            if let Err(e) = tokio::task::spawn_blocking(move || processor.reload_scripts()).await {
+                tracing::error!("Error recargando scripts: {}", e);
+            }
+        }
+    }
+
+    /// Inicializa las ventanas de histórico con las lecturas recientes de la base de datos
+    pub async fn seed_history(&self, db: &Database) -> anyhow::Result<()> {
+        if !self.history.is_enabled() {
+            return Ok(());
+        }
+
+        let series = db
+            .recent_metric_series(self.config.anomaly_window_size, HISTORY_SEED_DAYS)
+            .await?;
+
+        for (device_id, measurement, value) in &series {
+            self.history.seed(device_id, measurement, *value);
+        }
+
+        tracing::info!(
+            values = series.len(),
+            window = self.config.anomaly_window_size,
+            "Ventanas de detección de anomalías inicializadas"
+        );
+        Ok(())
+    }
+
+    /// Inicializa el seguimiento de batería y señal con las muestras recientes
+    pub async fn seed_power(&self, db: &Database) -> anyhow::Result<()> {
+        let mut measurements = power::BATTERY_MEASUREMENTS.to_vec();
+        measurements.push(power::RSSI_MEASUREMENT);
+
+        let series = db
+            .metric_series_since(&measurements, self.config.battery_trend_hours)
+            .await?;
+
+        for (device_id, measurement, value, at) in &series {
+            self.power.seed(device_id, measurement, *value, *at);
+        }
+
+        tracing::info!(
+            values = series.len(),
+            "Seguimiento de batería y señal inicializado"
+        );
+        Ok(())
+    }
+
+    /// Suscribe un cliente a las lecturas procesadas en vivo
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<ProcessedSensorData>> {
+        self.live.subscribe()
+    }
+
+    /// Reporte de batería y señal de la flota de dispositivos
+    pub fn power_report(&self) -> Vec<DevicePowerStatus> {
+        self.power.report()
+    }
+
+    /// Último nivel de batería de un dispositivo
+    pub fn battery(&self, device_id: &str) -> Option<BatteryLevel> {
+        self.power.battery(device_id)
+    }
+
+    /// Recalcula las líneas base por hora del día desde la base de datos
+    pub async fn refresh_baselines(&self, db: &Database) -> anyhow::Result<()> {
+        let baselines = db
+            .hourly_baselines(self.config.baseline_days, self.config.baseline_min_samples)
+            .await?;
+        let count = baselines.len();
+
+        self.baselines.replace(baselines);
+
+        tracing::info!(
+            baselines = count,
+            "Líneas base por hora del día actualizadas"
+        );
+        Ok(())
+    }
+
+    /// Recalcula periódicamente las líneas base con el histórico más reciente
+    pub async fn start_baseline_refresh_task(self: Arc<Self>, db: Database) {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+            self.config.baseline_refresh_secs.max(1),
+        ));
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = self.refresh_baselines(&db).await {
+                tracing::error!("Error calculando líneas base por hora: {}", e);
+            }
+        }
     }
 
     /// Procesa un dato individual de sensor aplicando edge computing
     pub async fn process_reading(&self, input: SensorDataInput) -> ProcessedSensorData {
-        let gateway_timestamp = Utc::now();
+        let time = self.clock.resolve(
+            &input.header.device_id,
+            input.device_timestamp.as_ref(),
+            Utc::now(),
+            None,
+        );
+
+        self.process_at(input, time, QuotaDecision::Accept)
+    }
+
+    /// Procesa una lectura recibida de un dispositivo si está dentro de su cuota
+    ///
+    /// Retorna None si la cuota del dispositivo la descarta.
+    pub async fn ingest_reading(&self, input: SensorDataInput) -> Option<ProcessedSensorData> {
+        let now = Utc::now();
+        let decision = self
+            .quotas
+            .admit(&input.header.device_id, &input.header.location, now);
+        if decision == QuotaDecision::Drop {
+            return None;
+        }
+
+        let time = self.clock.resolve(
+            &input.header.device_id,
+            input.device_timestamp.as_ref(),
+            now,
+            None,
+        );
+        Some(self.process_at(input, time, decision))
+    }
+
+    /// Cuotas por dispositivo
+    pub fn quotas(&self) -> Arc<DeviceQuotas> {
+        self.quotas.clone()
+    }
+
+    /// Registra la sincronización horaria de un dispositivo sin RTC
+    /// Sus lecturas con uptime se convierten a partir de ella
+    pub fn record_time_sync(&self, device_id: &str, anchor: UptimeAnchor) {
+        self.clock.record_sync(device_id, anchor);
+    }
+
+    /// Última sincronización horaria de un dispositivo
+    pub fn last_time_sync(&self, device_id: &str) -> Option<UptimeAnchor> {
+        self.clock.last_sync(device_id)
+    }
+
+    /// Procesa una lectura en el momento ya resuelto
+    fn process_at(
+        &self,
+        input: SensorDataInput,
+        time: ReadingTime,
+        quota: QuotaDecision,
+    ) -> ProcessedSensorData {
+        let started = Instant::now();
+        let processed = self.process_timed(input, time, quota);
+        self.telemetry.observe_processing(started.elapsed());
+        processed
+    }
+
+    fn process_timed(
+        &self,
+        mut input: SensorDataInput,
+        time: ReadingTime,
+        quota: QuotaDecision,
+    ) -> ProcessedSensorData {
+        // Los cálculos sobre series usan el momento de la medición; la llegada
+        // se conserva en `gateway_timestamp` para medir el desfase y el retraso
+        let gateway_timestamp = time.received_at;
+        let measured_at = time.measured_at;
+
+        // Ajustes del dispositivo o de su ubicación sobre la configuración global
+        let overrides = self
+            .overrides
+            .resolve(&input.header.device_id, &input.header.location);
+        let (anomaly_threshold, baseline_threshold) = {
+            let settings = self.settings.borrow();
+            (
+                overrides
+                    .anomaly_zscore_threshold
+                    .unwrap_or(settings.anomaly_zscore_threshold),
+                overrides
+                    .baseline_zscore_threshold
+                    .unwrap_or(settings.baseline_zscore_threshold),
+            )
+        };
+
+        // Normalizar unidades antes de cualquier cálculo
+        for metric in &mut input.metrics {
+            units::normalize(metric);
+        }
+
+        // Aplicar calibraciones del dispositivo (sobre unidades canónicas)
+        let calibrated = self.calibrations.apply(
+            &input.header.device_id,
+            &mut input.metrics,
+            measured_at,
+            &overrides.calibrations,
+        );
+
+        // Rellenar métricas faltantes o NaN con el histórico reciente del dispositivo
+        let filled = self
+            .filler
+            .fill(&input.header.device_id, &mut input.metrics, measured_at);
+
+        // Reemplazar picos aislados por la mediana reciente de la serie
+        let despiked = self
+            .hampel
+            .apply(&input.header.device_id, &mut input.metrics);
 
         // Extraer temperatura y humedad si existen en las métricas
         let temp_metric = input
@@ -29,11 +416,139 @@ impl EdgeProcessor {
             m.measurement.to_lowercase() == "humidity" || m.measurement.to_lowercase() == "humedad"
         });
 
+        // Detectar saltos imposibles respecto a la muestra anterior
+        let rate_issues: Vec<String> = input
+            .metrics
+            .iter()
+            .filter_map(|metric| {
+                self.rate_of_change
+                    .check(
+                        &input.header.device_id,
+                        &metric.measurement,
+                        metric.value,
+                        measured_at,
+                    )
+                    .map(|rate| {
+                        format!(
+                            "Cambio brusco en métrica {}: {:.2}/min",
+                            metric.measurement, rate
+                        )
+                    })
+            })
+            .collect();
+
         // Calcular métricas derivadas
-        let computed = self.compute_metrics(&input.metrics, temp_metric, hum_metric);
+        let mut computed = self.compute_metrics(
+            &input.header,
+            &input.metrics,
+            temp_metric,
+            hum_metric,
+            measured_at,
+            anomaly_threshold,
+        );
+
+        if !rate_issues.is_empty() {
+            computed.is_anomaly = true;
+        }
+
+        // Seguir batería y señal del dispositivo
+        let (power_penalty, power_issues) = self.power.observe(
+            &input.header.device_id,
+            &input.metrics,
+            measured_at,
+            &mut computed.stats,
+        );
+
+        // Comparar con la línea base de la hora del día del dispositivo
+        let mut baseline_issues = Vec::new();
+        for metric in &input.metrics {
+            let Some(zscore) = self.baselines.zscore(
+                &input.header.device_id,
+                &metric.measurement,
+                metric.value,
+                measured_at,
+            ) else {
+                continue;
+            };
+
+            computed
+                .stats
+                .insert(format!("{}_baseline_zscore", metric.measurement), zscore);
+
+            if zscore.abs() > baseline_threshold {
+                computed.is_anomaly = true;
+                baseline_issues.push(format!(
+                    "Desviación respecto a la línea base horaria en métrica {}: z = {:.2}",
+                    metric.measurement, zscore
+                ));
+            }
+        }
 
         // Evaluar calidad de los datos
-        let quality = self.assess_quality(&input, &computed);
+        let corrected = calibrated || !filled.is_empty() || !despiked.is_empty();
+        let mut quality = self.assess_quality(&input, &computed, corrected);
+
+        quality.issues.extend(rate_issues);
+        quality.issues.extend(baseline_issues);
+        quality.score = quality.score.saturating_sub(power_penalty);
+        quality.issues.extend(power_issues);
+
+        // Validar contra el perfil asignado o, si no hay, el del tipo de dispositivo
+        let profile_name = overrides
+            .profile
+            .as_deref()
+            .or(input.header.device_type.as_deref());
+        let profile = profile_name.and_then(|device_type| {
+            let (check, penalty, issues) = self.profiles.check(device_type, &input.metrics)?;
+            quality.score = quality.score.saturating_sub(penalty);
+            quality.issues.extend(issues);
+            Some(check)
+        });
+
+        if let Some(issue) = time.issue {
+            quality.score = quality.score.saturating_sub(10);
+            quality.issues.push(issue);
+        }
+
+        for (measurement, original) in despiked {
+            quality.issues.push(format!(
+                "Pico reemplazado por la mediana en métrica {}: {}",
+                measurement, original
+            ));
+        }
+
+        for measurement in filled {
+            quality
+                .issues
+                .push(format!("Valor interpolado en métrica: {}", measurement));
+        }
+
+        if quota == QuotaDecision::Sampled {
+            quality
+                .issues
+                .push("Cuota del dispositivo excedida: lectura submuestreada".to_string());
+        }
+
+        // Evaluar reglas de acciones
+        let outcome = self.rules.evaluate(
+            &input.header.device_id,
+            &input.header.location,
+            &input.metrics,
+            &computed.stats,
+            measured_at,
+        );
+
+        for rule_name in outcome.flagged {
+            quality
+                .issues
+                .push(format!("Marcada por la regla: {}", rule_name));
+        }
+
+        for event in outcome.events {
+            if let Err(e) = self.rule_events.try_send(event) {
+                tracing::warn!("Evento de regla descartado: {}", e);
+            }
+        }
 
         // Construir metadatos
         let metadata = ProcessedMetadata {
@@ -44,26 +559,43 @@ impl EdgeProcessor {
                 .map(|m| m.measurement.clone())
                 .collect(),
             should_requeue: input.header.should_requeue,
+            device_timestamp: time.device_timestamp,
+            clock_skew_ms: time.skew_ms,
+            profile,
+            sync_priority: overrides.sync_priority,
+            forward_to_cloud: self.config.cloud_sync_enabled && overrides.forward_to_cloud,
         };
 
-        ProcessedSensorData {
+        let processed = ProcessedSensorData {
             id: Uuid::new_v4(),
             header: input.header,
             metrics: input.metrics,
             gateway_timestamp,
+            measured_at,
             computed,
             quality,
             metadata,
+        };
+
+        // Publicar en vivo solo si hay clientes conectados
+        if self.live.receiver_count() > 0 {
+            let _ = self.live.send(Arc::new(processed.clone()));
         }
+
+        processed
     }
 
     /// Calcula métricas derivadas usando algoritmos de edge computing
     fn compute_metrics(
         &self,
+        header: &SensorHeader,
         metrics: &[SensorMetric],
         temp_metric: Option<&SensorMetric>,
         hum_metric: Option<&SensorMetric>,
+        at: DateTime<Utc>,
+        anomaly_threshold: f32,
     ) -> ComputedMetrics {
+        let (device_id, location) = (header.device_id.as_str(), header.location.as_str());
         let mut stats = HashMap::new();
 
         // Calcular Heat Index y Dew Point si hay temperatura y humedad
@@ -71,25 +603,89 @@ impl EdgeProcessor {
             if let (Some(temp), Some(hum)) = (temp_metric, hum_metric) {
                 let hi = self.calculate_heat_index(temp.value, hum.value);
                 let dp = self.calculate_dew_point(temp.value, hum.value);
-                let cl = self.calculate_comfort_level(temp.value, hum.value);
+                let cl = self
+                    .comfort
+                    .for_location(location)
+                    .comfort_level(temp.value, hum.value, &mut stats);
                 (Some(hi), Some(dp), Some(cl))
             } else {
                 (None, None, None)
             };
 
+        // Métricas psicrométricas para invernaderos y agricultura
+        let (absolute_humidity, vapor_pressure_deficit, wet_bulb) =
+            if let (Some(temp), Some(hum)) = (temp_metric, hum_metric) {
+                (
+                    Some(self.calculate_absolute_humidity(temp.value, hum.value)),
+                    Some(self.calculate_vapor_pressure_deficit(temp.value, hum.value)),
+                    Some(self.calculate_wet_bulb(temp.value, hum.value)),
+                )
+            } else {
+                (None, None, None)
+            };
+
+        // Calidad del aire (material particulado y CO2)
+        let air_quality_index = air_quality::air_quality_index(metrics, self.config.aqi_scale);
+        let ventilation_score = air_quality::ventilation_score(metrics);
+
         // Calcular estadísticas básicas para cada métrica
         for metric in metrics {
             // Aquí podrías agregar más estadísticas si tienes histórico
             stats.insert(format!("{}_current", metric.measurement), metric.value);
+
+            if let Some(ewma) = self
+                .ewma
+                .update(device_id, &metric.measurement, metric.value)
+            {
+                stats.insert(format!("{}_ewma", metric.measurement), ewma);
+            }
+
+            self.windows
+                .update(device_id, &metric.measurement, metric.value, at, &mut stats);
+
+            // El valor crudo se conserva en las métricas; el filtrado va en stats
+            if let Some(filtered) =
+                self.filters
+                    .apply(device_id, &metric.measurement, metric.value, at)
+            {
+                stats.insert(format!("{}_filtered", metric.measurement), filtered);
+            }
         }
 
-        // Detectar anomalías
-        let is_anomaly = self.detect_anomaly(metrics, temp_metric, hum_metric);
+        // Métricas derivadas definidas por scripts del sitio
+        self.scripts.run(
+            device_id,
+            location,
+            header.device_type.as_deref(),
+            metrics,
+            &mut stats,
+        );
+
+        // Detectar anomalías por rangos estáticos y por desviación del histórico
+        let mut is_anomaly = self.detect_anomaly(metrics);
+
+        for metric in metrics {
+            if let Some(zscore) = self
+                .history
+                .observe(device_id, &metric.measurement, metric.value)
+            {
+                stats.insert(format!("{}_zscore", metric.measurement), zscore);
+
+                if zscore.abs() > anomaly_threshold {
+                    is_anomaly = true;
+                }
+            }
+        }
 
         ComputedMetrics {
             heat_index,
             dew_point,
             comfort_level,
+            absolute_humidity,
+            vapor_pressure_deficit,
+            wet_bulb,
+            air_quality_index,
+            ventilation_score,
             is_anomaly,
             stats,
         }
@@ -97,6 +693,7 @@ impl EdgeProcessor {
 
     /// Calcula el índice de calor (Heat Index)
     /// Fórmula de Rothfusz basada en NOAA
+    #[allow(clippy::excessive_precision)]
     fn calculate_heat_index(&self, temp_c: f32, humidity: f32) -> f32 {
         // Convertir a Fahrenheit para la fórmula
         let temp_f = temp_c * 9.0 / 5.0 + 32.0;
@@ -128,80 +725,56 @@ impl EdgeProcessor {
         let b = 237.7;
 
         let alpha = ((a * temp_c) / (b + temp_c)) + (humidity / 100.0).ln();
-        let dew_point = (b * alpha) / (a - alpha);
 
-        dew_point
+        (b * alpha) / (a - alpha)
     }
 
-    /// Calcula nivel de confort basado en temperatura y humedad
-    /// Retorna un valor de 0 (muy incómodo) a 100 (muy cómodo)
-    fn calculate_comfort_level(&self, temp_c: f32, humidity: f32) -> f32 {
-        // Zona de confort ideal: 20-24°C y 40-60% humedad
-        let temp_score = if temp_c >= 20.0 && temp_c <= 24.0 {
-            100.0
-        } else if temp_c >= 18.0 && temp_c <= 26.0 {
-            80.0 - (temp_c - 22.0).abs() * 10.0
-        } else {
-            50.0 - (temp_c - 22.0).abs() * 5.0
-        };
+    /// Presión de vapor de saturación en kPa (Tetens)
+    fn saturation_vapor_pressure(&self, temp_c: f32) -> f32 {
+        0.6108 * ((17.27 * temp_c) / (temp_c + 237.3)).exp()
+    }
 
-        let humidity_score = if humidity >= 40.0 && humidity <= 60.0 {
-            100.0
-        } else if humidity >= 30.0 && humidity <= 70.0 {
-            80.0 - (humidity - 50.0).abs()
-        } else {
-            50.0 - (humidity - 50.0).abs() * 0.5
-        };
+    /// Calcula la humedad absoluta en g/m³
+    /// Ecuación de gas ideal para el vapor de agua
+    fn calculate_absolute_humidity(&self, temp_c: f32, humidity: f32) -> f32 {
+        let vapor_pressure = self.saturation_vapor_pressure(temp_c) * humidity / 100.0;
 
-        // Promedio ponderado
-        let comfort = (temp_score * 0.6 + humidity_score * 0.4)
-            .max(0.0)
-            .min(100.0);
-        comfort
+        2167.0 * vapor_pressure / (temp_c + 273.15)
     }
 
-    /// Detecta anomalías en las lecturas
-    fn detect_anomaly(
-        &self,
-        metrics: &[SensorMetric],
-        temp_metric: Option<&SensorMetric>,
-        hum_metric: Option<&SensorMetric>,
-    ) -> bool {
-        // Rangos extremos para temperatura
-        if let Some(temp) = temp_metric {
-            if temp.value < -10.0 || temp.value > 50.0 {
-                return true;
-            }
-        }
+    /// Calcula el déficit de presión de vapor (VPD) en kPa
+    fn calculate_vapor_pressure_deficit(&self, temp_c: f32, humidity: f32) -> f32 {
+        (self.saturation_vapor_pressure(temp_c) * (1.0 - humidity / 100.0)).max(0.0)
+    }
 
-        // Rangos extremos para humedad
-        if let Some(hum) = hum_metric {
-            if hum.value < 10.0 || hum.value > 95.0 {
-                return true;
-            }
-        }
+    /// Calcula la temperatura de bulbo húmedo
+    /// Fórmula empírica de Stull (2011), válida para 5-99% de humedad
+    fn calculate_wet_bulb(&self, temp_c: f32, humidity: f32) -> f32 {
+        let t = temp_c;
+        let rh = humidity;
+
+        t * (0.151977 * (rh + 8.313659).sqrt()).atan() + (t + rh).atan() - (rh - 1.676331).atan()
+            + 0.00391838 * rh.powf(1.5) * (0.023101 * rh).atan()
+            - 4.686035
+    }
+
+    /// Detecta anomalías en las lecturas según los rangos de validez configurados
+    fn detect_anomaly(&self, metrics: &[SensorMetric]) -> bool {
+        let ranges = self.ranges.read().unwrap();
 
-        // Detectar valores extremos en cualquier métrica
         for metric in metrics {
-            // Valores muy negativos o muy altos podrían ser anomalías
             if metric.value.is_nan() || metric.value.is_infinite() {
                 return true;
             }
 
-            // Rangos específicos por tipo de medición
-            match metric.measurement.to_lowercase().as_str() {
-                "distance" | "distancia" => {
-                    if metric.value < 0.0 || metric.value > 10000.0 {
+            match ranges.get(&metric.measurement.to_lowercase()) {
+                Some(range) => {
+                    if metric.value < range.min || metric.value > range.max {
                         return true;
                     }
                 }
-                "voltage" | "voltaje" => {
-                    if metric.value < 0.0 || metric.value > 50.0 {
-                        return true;
-                    }
-                }
-                _ => {
-                    // Detección genérica
+                None => {
+                    // Detección genérica para mediciones sin rango configurado
                     if metric.value.abs() > 10000.0 {
                         return true;
                     }
@@ -213,10 +786,14 @@ impl EdgeProcessor {
     }
 
     /// Evalúa la calidad de los datos recibidos
-    fn assess_quality(&self, input: &SensorDataInput, computed: &ComputedMetrics) -> DataQuality {
+    fn assess_quality(
+        &self,
+        input: &SensorDataInput,
+        computed: &ComputedMetrics,
+        corrected: bool,
+    ) -> DataQuality {
         let mut score = 100u8;
         let mut issues = Vec::new();
-        let corrected = false;
 
         // Verificar que haya métricas
         if input.metrics.is_empty() {
@@ -255,12 +832,35 @@ impl EdgeProcessor {
         }
     }
 
-    /// Procesa un batch de lecturas
+    /// Procesa un batch de lecturas recibido de los dispositivos
+    /// Las lecturas acumuladas offline se procesan en el orden del reloj del
+    /// dispositivo; las descartadas por la cuota no se incluyen en el resultado
     pub async fn process_batch(&self, inputs: Vec<SensorDataInput>) -> Vec<ProcessedSensorData> {
-        let mut results = Vec::with_capacity(inputs.len());
-
-        for input in inputs {
-            results.push(self.process_reading(input).await);
+        let received_at = Utc::now();
+        let references = ClockResolver::uptime_references(&inputs);
+
+        let mut timed: Vec<_> = inputs
+            .into_iter()
+            .map(|input| {
+                let time = self.clock.resolve(
+                    &input.header.device_id,
+                    input.device_timestamp.as_ref(),
+                    received_at,
+                    references.get(&input.header.device_id).copied(),
+                );
+                (time, input)
+            })
+            .collect();
+        timed.sort_by_key(|(time, _)| time.measured_at);
+
+        let mut results = Vec::with_capacity(timed.len());
+        for (time, input) in timed {
+            let decision =
+                self.quotas
+                    .admit(&input.header.device_id, &input.header.location, received_at);
+            if decision != QuotaDecision::Drop {
+                results.push(self.process_at(input, time, decision));
+            }
         }
 
         results
@@ -0,0 +1,92 @@
+use crate::config::AqiScale;
+use crate::models::SensorMetric;
+
+/// Tramo de un índice: concentración (inicio, fin) → índice (inicio, fin)
+type Breakpoint = (f32, f32, f32, f32);
+
+/// US EPA, PM2.5 en µg/m³ (revisión 2024)
+const EPA_PM25: [Breakpoint; 6] = [
+    (0.0, 9.0, 0.0, 50.0),
+    (9.1, 35.4, 51.0, 100.0),
+    (35.5, 55.4, 101.0, 150.0),
+    (55.5, 125.4, 151.0, 200.0),
+    (125.5, 225.4, 201.0, 300.0),
+    (225.5, 325.4, 301.0, 500.0),
+];
+
+/// US EPA, PM10 en µg/m³
+const EPA_PM10: [Breakpoint; 6] = [
+    (0.0, 54.0, 0.0, 50.0),
+    (55.0, 154.0, 51.0, 100.0),
+    (155.0, 254.0, 101.0, 150.0),
+    (255.0, 354.0, 151.0, 200.0),
+    (355.0, 424.0, 201.0, 300.0),
+    (425.0, 604.0, 301.0, 500.0),
+];
+
+/// Índice europeo (EEA): límite superior de cada nivel 1 (bueno) a 5; por encima es 6
+const EU_PM25: [f32; 5] = [5.0, 15.0, 50.0, 90.0, 140.0];
+const EU_PM10: [f32; 5] = [15.0, 45.0, 120.0, 195.0, 270.0];
+
+/// CO2 (ppm) a partir del cual se recomienda ventilar y nivel de ventilación urgente
+const CO2_VENTILATION_START: f32 = 600.0;
+const CO2_VENTILATION_URGENT: f32 = 1500.0;
+
+/// Índice de calidad del aire a partir de PM2.5 y PM10 (el peor de ambos)
+/// EPA: 0-500; europeo: niveles 1-6
+pub fn air_quality_index(metrics: &[SensorMetric], scale: AqiScale) -> Option<f32> {
+    let pm25 = find(metrics, &["pm2.5", "pm25", "pm2_5"]);
+    let pm10 = find(metrics, &["pm10"]);
+
+    let (pm25_index, pm10_index) = match scale {
+        AqiScale::Epa => (
+            pm25.map(|c| epa_index(&EPA_PM25, (c * 10.0).trunc() / 10.0)),
+            pm10.map(|c| epa_index(&EPA_PM10, c.trunc())),
+        ),
+        AqiScale::Eu => (
+            pm25.map(|c| eu_level(&EU_PM25, c)),
+            pm10.map(|c| eu_level(&EU_PM10, c)),
+        ),
+    };
+
+    match (pm25_index, pm10_index) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Recomendación de ventilación según CO2: 0 (no necesaria) a 100 (urgente)
+pub fn ventilation_score(metrics: &[SensorMetric]) -> Option<f32> {
+    let co2 = find(metrics, &["co2", "eco2"])?;
+
+    Some(
+        ((co2 - CO2_VENTILATION_START) / (CO2_VENTILATION_URGENT - CO2_VENTILATION_START) * 100.0)
+            .clamp(0.0, 100.0),
+    )
+}
+
+fn find(metrics: &[SensorMetric], names: &[&str]) -> Option<f32> {
+    metrics
+        .iter()
+        .find(|m| names.contains(&m.measurement.to_lowercase().as_str()))
+        .map(|m| m.value)
+        .filter(|v| v.is_finite() && *v >= 0.0)
+}
+
+/// Interpolación lineal dentro del tramo EPA; por encima del último tramo se satura en 500
+fn epa_index(breakpoints: &[Breakpoint], concentration: f32) -> f32 {
+    for &(c_low, c_high, i_low, i_high) in breakpoints {
+        if concentration <= c_high {
+            let c = concentration.max(c_low);
+            return ((i_high - i_low) / (c_high - c_low) * (c - c_low) + i_low).round();
+        }
+    }
+    500.0
+}
+
+fn eu_level(limits: &[f32], concentration: f32) -> f32 {
+    limits
+        .iter()
+        .position(|limit| concentration <= *limit)
+        .map_or(limits.len() + 1, |level| level + 1) as f32
+}
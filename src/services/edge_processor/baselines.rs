@@ -0,0 +1,53 @@
+use crate::models::HourlyBaseline;
+use chrono::{DateTime, Local, Timelike, Utc};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Líneas base aprendidas por dispositivo, medición y hora del día
+#[derive(Default)]
+pub struct HourlyBaselines {
+    baselines: RwLock<HashMap<(String, String, u32), HourlyBaseline>>,
+}
+
+impl HourlyBaselines {
+    /// Reemplaza las líneas base cargadas
+    pub fn replace(&self, baselines: Vec<HourlyBaseline>) {
+        *self.baselines.write().unwrap() = baselines
+            .into_iter()
+            .map(|baseline| {
+                (
+                    (
+                        baseline.device_id.clone(),
+                        baseline.measurement.to_lowercase(),
+                        baseline.hour,
+                    ),
+                    baseline,
+                )
+            })
+            .collect();
+    }
+
+    /// Z-score del valor respecto a la línea base de la hora local de la lectura
+    /// None si no hay línea base para esa hora o la serie no tiene dispersión
+    pub fn zscore(
+        &self,
+        device_id: &str,
+        measurement: &str,
+        value: f32,
+        at: DateTime<Utc>,
+    ) -> Option<f32> {
+        if !value.is_finite() {
+            return None;
+        }
+
+        let hour = at.with_timezone(&Local).hour();
+        let baselines = self.baselines.read().unwrap();
+        let baseline = baselines.get(&(device_id.to_string(), measurement.to_lowercase(), hour))?;
+
+        if baseline.std_dev <= f32::EPSILON {
+            return None;
+        }
+
+        Some((value - baseline.mean) / baseline.std_dev)
+    }
+}
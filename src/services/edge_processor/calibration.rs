@@ -0,0 +1,66 @@
+use crate::models::{Calibration, OverrideCalibration, SensorMetric};
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Calibraciones vigentes en memoria, indexadas por dispositivo y medición
+#[derive(Default)]
+pub struct CalibrationStore {
+    /// Calibraciones de cada serie ordenadas por `valid_from` ascendente
+    entries: RwLock<HashMap<(String, String), Vec<Calibration>>>,
+}
+
+impl CalibrationStore {
+    /// Reemplaza todas las calibraciones cargadas
+    pub fn replace(&self, calibrations: Vec<Calibration>) {
+        let mut entries: HashMap<_, Vec<_>> = HashMap::new();
+        for calibration in calibrations {
+            entries
+                .entry((
+                    calibration.device_id.clone(),
+                    calibration.measurement.to_lowercase(),
+                ))
+                .or_default()
+                .push(calibration);
+        }
+        for series in entries.values_mut() {
+            series.sort_by_key(|c| c.valid_from);
+        }
+
+        *self.entries.write().unwrap() = entries;
+    }
+
+    /// Aplica la calibración vigente en `at` a las métricas del dispositivo
+    /// Las series sin calibración propia usan la de `fallback` (ajustes del
+    /// dispositivo o de su ubicación, clave en minúsculas)
+    /// Retorna true si se corrigió alguna métrica
+    pub fn apply(
+        &self,
+        device_id: &str,
+        metrics: &mut [SensorMetric],
+        at: DateTime<Utc>,
+        fallback: &HashMap<String, OverrideCalibration>,
+    ) -> bool {
+        let entries = self.entries.read().unwrap();
+        if entries.is_empty() && fallback.is_empty() {
+            return false;
+        }
+
+        let mut corrected = false;
+        for metric in metrics {
+            let measurement = metric.measurement.to_lowercase();
+            let active = entries
+                .get(&(device_id.to_string(), measurement.clone()))
+                .and_then(|series| series.iter().rev().find(|c| c.valid_from <= at))
+                .map(|c| (c.gain, c.offset))
+                .or_else(|| fallback.get(&measurement).map(|c| (c.gain, c.offset)));
+
+            if let Some((gain, offset)) = active {
+                metric.value = metric.value * gain + offset;
+                corrected = true;
+            }
+        }
+
+        corrected
+    }
+}
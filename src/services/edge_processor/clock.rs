@@ -0,0 +1,179 @@
+use crate::models::{DeviceTimestamp, SensorDataInput};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Reloj reportado por el dispositivo, ya clasificado
+enum DeviceClock {
+    /// Fecha absoluta
+    Absolute(DateTime<Utc>),
+    /// Milisegundos desde el arranque del dispositivo
+    Uptime(f64),
+}
+
+/// Momento resuelto de una lectura
+pub struct ReadingTime {
+    /// Momento de la medición (corregido o, sin marca válida, la llegada)
+    pub measured_at: DateTime<Utc>,
+    /// Llegada al gateway
+    pub received_at: DateTime<Utc>,
+    /// Momento según el dispositivo, ya corregido
+    pub device_timestamp: Option<DateTime<Utc>>,
+    /// Llegada al gateway menos el momento del dispositivo, en milisegundos
+    pub skew_ms: Option<i64>,
+    /// Motivo por el que se descartó la marca del dispositivo
+    pub issue: Option<String>,
+}
+
+/// Uptime de un dispositivo en un momento conocido del reloj del gateway
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct UptimeAnchor {
+    pub uptime_ms: f64,
+    pub at: DateTime<Utc>,
+}
+
+/// Resolución de las marcas de tiempo enviadas por los dispositivos
+pub struct ClockResolver {
+    future_tolerance: chrono::Duration,
+    max_backfill: chrono::Duration,
+    /// Última sincronización horaria de cada dispositivo sin RTC
+    anchors: Mutex<HashMap<String, UptimeAnchor>>,
+}
+
+impl ClockResolver {
+    pub fn new(future_tolerance_secs: u64, max_backfill_hours: u64) -> Self {
+        Self {
+            future_tolerance: chrono::Duration::seconds(future_tolerance_secs as i64),
+            max_backfill: chrono::Duration::hours(max_backfill_hours as i64),
+            anchors: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Registra el uptime informado por un dispositivo al sincronizar la hora
+    pub fn record_sync(&self, device_id: &str, anchor: UptimeAnchor) {
+        self.anchors
+            .lock()
+            .unwrap()
+            .insert(device_id.to_string(), anchor);
+    }
+
+    /// Última sincronización horaria de un dispositivo
+    pub fn last_sync(&self, device_id: &str) -> Option<UptimeAnchor> {
+        self.anchors.lock().unwrap().get(device_id).copied()
+    }
+
+    /// Uptime más reciente de cada dispositivo en un batch
+    /// Esa lectura se asume enviada al momento de llegar al gateway
+    pub fn uptime_references(inputs: &[SensorDataInput]) -> HashMap<String, f64> {
+        let mut references = HashMap::new();
+
+        for input in inputs {
+            if let Some(DeviceClock::Uptime(uptime)) =
+                input.device_timestamp.as_ref().and_then(classify)
+            {
+                references
+                    .entry(input.header.device_id.clone())
+                    .and_modify(|latest: &mut f64| *latest = latest.max(uptime))
+                    .or_insert(uptime);
+            }
+        }
+
+        references
+    }
+
+    /// Resuelve el momento de una lectura recibida en `received_at`
+    /// `uptime_reference` es el uptime del dispositivo al momento de la llegada
+    ///
+    /// Los uptimes se convierten con la última sincronización horaria del
+    /// dispositivo si es del mismo arranque (su uptime no volvió a empezar);
+    /// si no, la lectura más reciente se asume enviada al llegar.
+    pub fn resolve(
+        &self,
+        device_id: &str,
+        device_timestamp: Option<&DeviceTimestamp>,
+        received_at: DateTime<Utc>,
+        uptime_reference: Option<f64>,
+    ) -> ReadingTime {
+        let Some(raw) = device_timestamp else {
+            return ReadingTime {
+                measured_at: received_at,
+                received_at,
+                device_timestamp: None,
+                skew_ms: None,
+                issue: None,
+            };
+        };
+
+        let corrected = classify(raw).map(|clock| match clock {
+            DeviceClock::Absolute(at) => at,
+            DeviceClock::Uptime(uptime) => {
+                let reference = uptime_reference.unwrap_or(uptime).max(uptime);
+                match self
+                    .last_sync(device_id)
+                    .filter(|anchor| anchor.uptime_ms <= reference)
+                {
+                    Some(anchor) => {
+                        anchor.at
+                            + chrono::Duration::milliseconds((uptime - anchor.uptime_ms) as i64)
+                    }
+                    None => {
+                        received_at - chrono::Duration::milliseconds((reference - uptime) as i64)
+                    }
+                }
+            }
+        });
+
+        match corrected {
+            Some(at) if at > received_at + self.future_tolerance => ReadingTime {
+                measured_at: received_at,
+                received_at,
+                device_timestamp: None,
+                skew_ms: Some((received_at - at).num_milliseconds()),
+                issue: Some(format!(
+                    "Marca de tiempo del dispositivo en el futuro descartada: {}",
+                    at.to_rfc3339()
+                )),
+            },
+            Some(at) if at < received_at - self.max_backfill => ReadingTime {
+                measured_at: received_at,
+                received_at,
+                device_timestamp: None,
+                skew_ms: Some((received_at - at).num_milliseconds()),
+                issue: Some(format!(
+                    "Marca de tiempo del dispositivo demasiado antigua descartada: {}",
+                    at.to_rfc3339()
+                )),
+            },
+            Some(at) => ReadingTime {
+                // Una marca apenas adelantada (dentro de la tolerancia) no supera la llegada
+                measured_at: at.min(received_at),
+                received_at,
+                device_timestamp: Some(at),
+                skew_ms: Some((received_at - at).num_milliseconds()),
+                issue: None,
+            },
+            None => ReadingTime {
+                measured_at: received_at,
+                received_at,
+                device_timestamp: None,
+                skew_ms: None,
+                issue: Some("Marca de tiempo del dispositivo inválida".to_string()),
+            },
+        }
+    }
+}
+
+/// Distingue fechas absolutas de relojes basados en el uptime del dispositivo
+/// Retorna `None` si el valor no es una fecha representable
+fn classify(raw: &DeviceTimestamp) -> Option<DeviceClock> {
+    if !raw.is_uptime() {
+        return raw.absolute().map(DeviceClock::Absolute);
+    }
+
+    match raw {
+        // RTC sin NTP: cuenta desde 1970 a partir del arranque
+        DeviceTimestamp::Date(at) => Some(DeviceClock::Uptime(at.timestamp_millis() as f64)),
+        DeviceTimestamp::Number(n) => Some(DeviceClock::Uptime(n.max(0.0))),
+    }
+}
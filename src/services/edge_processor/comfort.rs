@@ -0,0 +1,176 @@
+use crate::config::{ComfortModelKind, Config};
+use std::collections::HashMap;
+
+/// Modelo de confort térmico
+/// Retorna un nivel de 0 (muy incómodo) a 100 (muy cómodo) y puede publicar
+/// valores intermedios del modelo en `stats`
+pub trait ComfortModel: Send + Sync {
+    fn comfort_level(&self, temp_c: f32, humidity: f32, stats: &mut HashMap<String, f32>) -> f32;
+}
+
+/// Heurística original: zona ideal de 20-24°C y 40-60% de humedad
+pub struct HeuristicComfort;
+
+impl ComfortModel for HeuristicComfort {
+    fn comfort_level(&self, temp_c: f32, humidity: f32, _stats: &mut HashMap<String, f32>) -> f32 {
+        // Zona de confort ideal: 20-24°C y 40-60% humedad
+        let temp_score = if (20.0..=24.0).contains(&temp_c) {
+            100.0
+        } else if (18.0..=26.0).contains(&temp_c) {
+            80.0 - (temp_c - 22.0).abs() * 10.0
+        } else {
+            50.0 - (temp_c - 22.0).abs() * 5.0
+        };
+
+        let humidity_score = if (40.0..=60.0).contains(&humidity) {
+            100.0
+        } else if (30.0..=70.0).contains(&humidity) {
+            80.0 - (humidity - 50.0).abs()
+        } else {
+            50.0 - (humidity - 50.0).abs() * 0.5
+        };
+
+        // Promedio ponderado
+        (temp_score * 0.6 + humidity_score * 0.4).clamp(0.0, 100.0)
+    }
+}
+
+/// ASHRAE 55 / ISO 7730: voto medio estimado (PMV) y porcentaje de insatisfechos (PPD)
+/// Se asume temperatura radiante media igual a la del aire y sin trabajo externo
+pub struct PmvComfort {
+    /// Aislamiento de la vestimenta (clo)
+    pub clothing: f64,
+    /// Tasa metabólica (met)
+    pub metabolic_rate: f64,
+    /// Velocidad del aire (m/s)
+    pub air_speed: f64,
+}
+
+impl PmvComfort {
+    /// Cálculo iterativo de ISO 7730 (anexo D)
+    fn pmv(&self, ta: f64, rh: f64) -> f64 {
+        let tr = ta;
+        let pa = rh * 10.0 * (16.6536 - 4030.183 / (ta + 235.0)).exp();
+        let icl = 0.155 * self.clothing;
+        let m = self.metabolic_rate * 58.15;
+        let mw = m;
+        let fcl = if icl <= 0.078 {
+            1.0 + 1.29 * icl
+        } else {
+            1.05 + 0.645 * icl
+        };
+        let hcf = 12.1 * self.air_speed.sqrt();
+        let taa = ta + 273.0;
+        let tra = tr + 273.0;
+
+        // Temperatura superficial de la ropa por iteración
+        let tcla = taa + (35.5 - ta) / (3.5 * icl + 0.1);
+        let p1 = icl * fcl;
+        let p2 = p1 * 3.96;
+        let p3 = p1 * 100.0;
+        let p4 = p1 * taa;
+        let p5 = 308.7 - 0.028 * mw + p2 * (tra / 100.0).powi(4);
+
+        let mut xn = tcla / 100.0;
+        let mut xf = tcla / 50.0;
+        let mut hc = hcf;
+        let mut iterations = 0;
+        while (xn - xf).abs() > 0.00015 && iterations < 150 {
+            xf = (xf + xn) / 2.0;
+            let hcn = 2.38 * (100.0 * xf - taa).abs().powf(0.25);
+            hc = hcf.max(hcn);
+            xn = (p5 + p4 * hc - p2 * xf.powi(4)) / (100.0 + p3 * hc);
+            iterations += 1;
+        }
+        let tcl = 100.0 * xn - 273.0;
+
+        // Pérdidas de calor
+        let hl1 = 3.05 * 0.001 * (5733.0 - 6.99 * mw - pa);
+        let hl2 = if mw > 58.15 { 0.42 * (mw - 58.15) } else { 0.0 };
+        let hl3 = 1.7e-5 * m * (5867.0 - pa);
+        let hl4 = 0.0014 * m * (34.0 - ta);
+        let hl5 = 3.96 * fcl * (xn.powi(4) - (tra / 100.0).powi(4));
+        let hl6 = fcl * hc * (tcl - ta);
+
+        let ts = 0.303 * (-0.036 * m).exp() + 0.028;
+        ts * (mw - hl1 - hl2 - hl3 - hl4 - hl5 - hl6)
+    }
+}
+
+impl ComfortModel for PmvComfort {
+    fn comfort_level(&self, temp_c: f32, humidity: f32, stats: &mut HashMap<String, f32>) -> f32 {
+        let pmv = self.pmv(temp_c as f64, humidity as f64);
+        let ppd = 100.0 - 95.0 * (-0.03353 * pmv.powi(4) - 0.2179 * pmv.powi(2)).exp();
+
+        stats.insert("comfort_pmv".to_string(), pmv as f32);
+        stats.insert("comfort_ppd".to_string(), ppd as f32);
+
+        (100.0 - ppd).clamp(0.0, 100.0) as f32
+    }
+}
+
+/// Confort adaptativo de ASHRAE 55 para espacios ventilados naturalmente
+/// La temperatura operativa de confort depende de la media exterior predominante
+pub struct AdaptiveComfort {
+    /// Temperatura media exterior predominante (°C)
+    pub outdoor_mean_temp: f32,
+}
+
+impl ComfortModel for AdaptiveComfort {
+    fn comfort_level(&self, temp_c: f32, _humidity: f32, stats: &mut HashMap<String, f32>) -> f32 {
+        let neutral = 0.31 * self.outdoor_mean_temp + 17.8;
+        let deviation = (temp_c - neutral).abs();
+
+        stats.insert("comfort_neutral_temp".to_string(), neutral);
+
+        // Dentro de ±2.5°C el 90% de los ocupantes lo acepta; dentro de ±3.5°C, el 80%
+        if deviation <= 2.5 {
+            100.0
+        } else if deviation <= 3.5 {
+            100.0 - (deviation - 2.5) * 20.0
+        } else {
+            (80.0 - (deviation - 3.5) * 20.0).max(0.0)
+        }
+    }
+}
+
+/// Modelos de confort configurados: uno por defecto y otros por ubicación
+pub struct ComfortModels {
+    default: Box<dyn ComfortModel>,
+    by_location: HashMap<String, Box<dyn ComfortModel>>,
+}
+
+impl ComfortModels {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            default: build(config.comfort_model, config),
+            by_location: config
+                .comfort_models
+                .iter()
+                .map(|(location, kind)| (location.to_lowercase(), build(*kind, config)))
+                .collect(),
+        }
+    }
+
+    /// Modelo aplicable a una ubicación
+    pub fn for_location(&self, location: &str) -> &dyn ComfortModel {
+        self.by_location
+            .get(&location.to_lowercase())
+            .unwrap_or(&self.default)
+            .as_ref()
+    }
+}
+
+fn build(kind: ComfortModelKind, config: &Config) -> Box<dyn ComfortModel> {
+    match kind {
+        ComfortModelKind::Heuristic => Box::new(HeuristicComfort),
+        ComfortModelKind::Pmv => Box::new(PmvComfort {
+            clothing: config.comfort_clothing_clo as f64,
+            metabolic_rate: config.comfort_metabolic_rate as f64,
+            air_speed: config.comfort_air_speed as f64,
+        }),
+        ComfortModelKind::Adaptive => Box::new(AdaptiveComfort {
+            outdoor_mean_temp: config.comfort_outdoor_mean_temp,
+        }),
+    }
+}
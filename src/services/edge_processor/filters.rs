@@ -0,0 +1,95 @@
+use crate::config::MeasurementFilter;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Estado de un filtro para una serie
+enum FilterState {
+    /// Kalman 1D: estimación y varianza del error
+    Kalman { estimate: f32, error: f32 },
+    /// Pasa-bajos de primer orden: salida y momento de la última muestra
+    LowPass { output: f32, at: DateTime<Utc> },
+}
+
+/// Filtros de ruido por medición (Kalman 1D o pasa-bajos de primer orden)
+pub struct NoiseFilters {
+    filters: HashMap<String, MeasurementFilter>,
+    states: Mutex<HashMap<(String, String), FilterState>>,
+    process_noise: f32,
+    measurement_noise: f32,
+    /// Constante de tiempo RC del pasa-bajos en segundos
+    time_constant: f32,
+}
+
+impl NoiseFilters {
+    pub fn new(
+        filters: &HashMap<String, MeasurementFilter>,
+        process_noise: f32,
+        measurement_noise: f32,
+        cutoff_hz: f32,
+    ) -> Self {
+        Self {
+            filters: filters
+                .iter()
+                .map(|(measurement, filter)| (measurement.to_lowercase(), *filter))
+                .collect(),
+            states: Mutex::new(HashMap::new()),
+            process_noise,
+            measurement_noise,
+            time_constant: 1.0 / (2.0 * std::f32::consts::PI * cutoff_hz),
+        }
+    }
+
+    /// Filtra el valor si la medición tiene filtro configurado y retorna el valor filtrado
+    pub fn apply(
+        &self,
+        device_id: &str,
+        measurement: &str,
+        value: f32,
+        at: DateTime<Utc>,
+    ) -> Option<f32> {
+        let measurement = measurement.to_lowercase();
+        let filter = self.filters.get(&measurement)?;
+        if !value.is_finite() {
+            return None;
+        }
+
+        let mut states = self.states.lock().unwrap();
+        let key = (device_id.to_string(), measurement);
+
+        let filtered = match (filter, states.get_mut(&key)) {
+            (MeasurementFilter::Kalman, Some(FilterState::Kalman { estimate, error })) => {
+                // Predicción (modelo constante) y corrección con la nueva muestra
+                *error += self.process_noise;
+                let gain = *error / (*error + self.measurement_noise);
+                *estimate += gain * (value - *estimate);
+                *error *= 1.0 - gain;
+                *estimate
+            }
+            (MeasurementFilter::LowPass, Some(FilterState::LowPass { output, at: last })) => {
+                let dt = ((at - *last).num_milliseconds().max(0) as f32) / 1000.0;
+                let alpha = dt / (self.time_constant + dt);
+                *output += alpha * (value - *output);
+                *last = at;
+                *output
+            }
+            // Primera muestra de la serie: el filtro arranca en el valor medido
+            (MeasurementFilter::Kalman, _) => {
+                states.insert(
+                    key,
+                    FilterState::Kalman {
+                        estimate: value,
+                        error: self.measurement_noise,
+                    },
+                );
+                value
+            }
+            (MeasurementFilter::LowPass, _) => {
+                states.insert(key, FilterState::LowPass { output: value, at });
+                value
+            }
+        };
+
+        Some(filtered)
+    }
+}
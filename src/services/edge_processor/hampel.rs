@@ -0,0 +1,105 @@
+use crate::models::SensorMetric;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Muestras mínimas en la ventana antes de evaluar picos
+const MIN_SAMPLES: usize = 3;
+
+/// Factor que convierte la MAD en un estimador de la desviación estándar
+const MAD_SCALE: f32 = 1.4826;
+
+/// Mediana de una lista de valores (se ordena en el lugar)
+fn median(values: &mut [f32]) -> f32 {
+    values.sort_by(f32::total_cmp);
+    let mid = values.len() / 2;
+
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+/// Filtro Hampel sobre la ventana reciente de cada dispositivo y medición
+///
+/// Una muestra que se aleja de la mediana de la ventana más de `threshold`
+/// desviaciones (MAD escalada) se reemplaza por la mediana. La ventana guarda
+/// los valores crudos, de modo que un cambio de nivel sostenido se acepta en
+/// cuanto ocupa la mitad de la ventana.
+pub struct HampelFilter {
+    windows: Mutex<HashMap<(String, String), VecDeque<f32>>>,
+    window_size: usize,
+    threshold: f32,
+}
+
+impl HampelFilter {
+    pub fn new(window_size: usize, threshold: f32) -> Self {
+        Self {
+            windows: Mutex::new(HashMap::new()),
+            window_size,
+            threshold,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.window_size > 0
+    }
+
+    /// Reemplaza los picos aislados por la mediana de la ventana
+    /// Retorna las mediciones corregidas con su valor original
+    pub fn apply(&self, device_id: &str, metrics: &mut [SensorMetric]) -> Vec<(String, f32)> {
+        if !self.is_enabled() {
+            return Vec::new();
+        }
+
+        let mut windows = self.windows.lock().unwrap();
+        let mut replaced = Vec::new();
+
+        for metric in metrics.iter_mut() {
+            if !metric.value.is_finite() {
+                continue;
+            }
+
+            let window = windows
+                .entry((device_id.to_string(), metric.measurement.to_lowercase()))
+                .or_insert_with(|| VecDeque::with_capacity(self.window_size));
+
+            let original = metric.value;
+            if let Some(median) = self.spike_median(window, original) {
+                metric.value = median;
+                replaced.push((metric.measurement.clone(), original));
+            }
+
+            if window.len() == self.window_size {
+                window.pop_front();
+            }
+            window.push_back(original);
+        }
+
+        replaced
+    }
+
+    /// Retorna la mediana de la ventana si el valor es un pico
+    fn spike_median(&self, window: &VecDeque<f32>, value: f32) -> Option<f32> {
+        if window.len() < MIN_SAMPLES.min(self.window_size) {
+            return None;
+        }
+
+        let mut values: Vec<f32> = window.iter().copied().collect();
+        let center = median(&mut values);
+
+        let mut deviations: Vec<f32> = values.iter().map(|v| (v - center).abs()).collect();
+        let mut spread = MAD_SCALE * median(&mut deviations);
+
+        // Ventanas casi constantes (sensores cuantizados): MAD nula, se usa la desviación media
+        if spread <= f32::EPSILON {
+            spread = deviations.iter().sum::<f32>() / deviations.len() as f32;
+        }
+        if spread <= f32::EPSILON {
+            // Serie constante: no hay dispersión contra la cual comparar
+            return None;
+        }
+
+        ((value - center).abs() > self.threshold * spread).then_some(center)
+    }
+}
@@ -0,0 +1,100 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Muestras mínimas en la ventana antes de evaluar el z-score
+const MIN_SAMPLES: usize = 10;
+
+/// Ventana deslizante de los últimos valores de una serie
+struct RollingWindow {
+    values: VecDeque<f32>,
+    capacity: usize,
+}
+
+impl RollingWindow {
+    fn new(capacity: usize) -> Self {
+        Self {
+            values: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    fn push(&mut self, value: f32) {
+        if self.values.len() == self.capacity {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    /// Media y desviación estándar de la ventana
+    fn mean_std(&self) -> (f32, f32) {
+        let n = self.values.len() as f32;
+        let mean = self.values.iter().sum::<f32>() / n;
+        let variance = self.values.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / n;
+        (mean, variance.sqrt())
+    }
+
+    /// Z-score del valor respecto a la ventana (None si aún no hay suficiente histórico)
+    fn zscore(&self, value: f32) -> Option<f32> {
+        if self.values.len() < MIN_SAMPLES.min(self.capacity) {
+            return None;
+        }
+
+        let (mean, std) = self.mean_std();
+        if std <= f32::EPSILON {
+            // Serie constante: no hay dispersión contra la cual comparar
+            return None;
+        }
+
+        Some((value - mean) / std)
+    }
+}
+
+/// Histórico en memoria por dispositivo y medición
+pub struct MetricHistory {
+    windows: Mutex<HashMap<(String, String), RollingWindow>>,
+    window_size: usize,
+}
+
+impl MetricHistory {
+    pub fn new(window_size: usize) -> Self {
+        Self {
+            windows: Mutex::new(HashMap::new()),
+            window_size,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.window_size > 0
+    }
+
+    /// Evalúa el valor contra la ventana de la serie y luego lo agrega a ella
+    /// Retorna el z-score del valor (None si no se puede calcular)
+    pub fn observe(&self, device_id: &str, measurement: &str, value: f32) -> Option<f32> {
+        if !self.is_enabled() || !value.is_finite() {
+            return None;
+        }
+
+        let mut windows = self.windows.lock().unwrap();
+        let window = windows
+            .entry((device_id.to_string(), measurement.to_lowercase()))
+            .or_insert_with(|| RollingWindow::new(self.window_size));
+
+        let zscore = window.zscore(value);
+        window.push(value);
+        zscore
+    }
+
+    /// Carga valores históricos (en orden cronológico) sin evaluarlos
+    pub fn seed(&self, device_id: &str, measurement: &str, value: f32) {
+        if !self.is_enabled() || !value.is_finite() {
+            return;
+        }
+
+        self.windows
+            .lock()
+            .unwrap()
+            .entry((device_id.to_string(), measurement.to_lowercase()))
+            .or_insert_with(|| RollingWindow::new(self.window_size))
+            .push(value);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::config::InterpolationMethod;
+use crate::models::SensorMetric;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Últimas observaciones reales de una medición
+struct LastObservations {
+    /// Nombre original de la medición (para reconstruir la métrica)
+    measurement: String,
+    unit: Option<String>,
+    last: (DateTime<Utc>, f32),
+    previous: Option<(DateTime<Utc>, f32)>,
+}
+
+/// Relleno de métricas faltantes a partir del histórico reciente del dispositivo
+///
+/// El conjunto esperado de un dispositivo son las mediciones que reportó dentro
+/// de la antigüedad máxima; si una falta o llega como NaN se rellena con el
+/// último valor (LOCF) o extrapolando con las dos últimas observaciones.
+pub struct MissingValueFiller {
+    series: Mutex<HashMap<String, HashMap<String, LastObservations>>>,
+    method: InterpolationMethod,
+    max_age: chrono::Duration,
+}
+
+impl MissingValueFiller {
+    pub fn new(method: InterpolationMethod, max_age_secs: u64) -> Self {
+        Self {
+            series: Mutex::new(HashMap::new()),
+            method,
+            max_age: chrono::Duration::seconds(max_age_secs as i64),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.method != InterpolationMethod::Off
+    }
+
+    /// Rellena las métricas faltantes o NaN y registra las observaciones reales
+    /// Retorna los nombres de las mediciones rellenadas
+    pub fn fill(
+        &self,
+        device_id: &str,
+        metrics: &mut Vec<SensorMetric>,
+        at: DateTime<Utc>,
+    ) -> Vec<String> {
+        // Un mensaje sin métricas no se reconstruye por completo desde el histórico
+        if !self.is_enabled() || metrics.is_empty() {
+            return Vec::new();
+        }
+
+        let mut series = self.series.lock().unwrap();
+        let device = series.entry(device_id.to_string()).or_default();
+
+        // Las observaciones antiguas dejan de formar parte del conjunto esperado
+        device.retain(|_, obs| at - obs.last.0 <= self.max_age);
+
+        let mut filled = Vec::new();
+
+        for metric in metrics.iter_mut() {
+            let key = metric.measurement.to_lowercase();
+
+            if metric.value.is_finite() {
+                let value = metric.value;
+                device
+                    .entry(key)
+                    .and_modify(|obs| {
+                        obs.previous = Some(obs.last);
+                        obs.last = (at, value);
+                        obs.unit = metric.unit.clone();
+                    })
+                    .or_insert_with(|| LastObservations {
+                        measurement: metric.measurement.clone(),
+                        unit: metric.unit.clone(),
+                        last: (at, value),
+                        previous: None,
+                    });
+            } else if metric.value.is_nan()
+                && let Some(obs) = device.get(&key)
+            {
+                metric.value = self.estimate(obs, at);
+                filled.push(metric.measurement.clone());
+            }
+        }
+
+        let present: Vec<String> = metrics
+            .iter()
+            .map(|m| m.measurement.to_lowercase())
+            .collect();
+
+        for (key, obs) in device.iter() {
+            if present.contains(key) {
+                continue;
+            }
+
+            metrics.push(SensorMetric {
+                measurement: obs.measurement.clone(),
+                value: self.estimate(obs, at),
+                unit: obs.unit.clone(),
+            });
+            filled.push(obs.measurement.clone());
+        }
+
+        filled
+    }
+
+    fn estimate(&self, obs: &LastObservations, at: DateTime<Utc>) -> f32 {
+        let (last_at, last) = obs.last;
+
+        match (self.method, obs.previous) {
+            (InterpolationMethod::Linear, Some((prev_at, prev))) if last_at > prev_at => {
+                let span = (last_at - prev_at).num_milliseconds() as f32;
+                let elapsed = (at - last_at).num_milliseconds() as f32;
+                last + (last - prev) * elapsed / span
+            }
+            _ => last,
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use crate::config::Config;
+use crate::models::{DevicePowerStatus, SensorMetric};
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Mediciones de batería reconocidas (porcentaje y voltaje)
+pub const BATTERY_MEASUREMENTS: [&str; 2] = ["battery", "vbat"];
+
+/// Medición de intensidad de señal
+pub const RSSI_MEASUREMENT: &str = "rssi";
+
+/// Horas mínimas cubiertas por las muestras para estimar la tendencia de batería
+const MIN_TREND_SPAN_HOURS: f64 = 1.0;
+
+/// Descuentos de calidad por señal débil y crítica
+const RSSI_POOR_PENALTY: u8 = 10;
+const RSSI_CRITICAL_PENALTY: u8 = 25;
+
+type Samples = VecDeque<(DateTime<Utc>, f32)>;
+
+/// Último nivel de batería de un dispositivo
+pub struct BatteryLevel {
+    /// `battery` (%) o `vbat` (V)
+    pub measurement: String,
+    pub value: f32,
+    /// Motivo por el que requiere atención, si lo hay
+    pub attention: Option<String>,
+}
+
+/// Muestras recientes de batería y señal de un dispositivo
+#[derive(Default)]
+struct DevicePower {
+    battery_measurement: Option<String>,
+    battery: Samples,
+    rssi: Samples,
+    last_seen: Option<DateTime<Utc>>,
+}
+
+/// Seguimiento de batería y señal por dispositivo
+///
+/// La tendencia de batería es la pendiente de una regresión lineal sobre las
+/// muestras de la ventana; con ella se estiman los días hasta el nivel vacío.
+pub struct PowerTracker {
+    devices: Mutex<HashMap<String, DevicePower>>,
+    window: chrono::Duration,
+    empty_percent: f32,
+    empty_voltage: f32,
+    attention_days: f32,
+    rssi_poor: f32,
+    rssi_critical: f32,
+}
+
+impl PowerTracker {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            devices: Mutex::new(HashMap::new()),
+            window: chrono::Duration::hours(config.battery_trend_hours),
+            empty_percent: config.battery_empty_percent,
+            empty_voltage: config.battery_empty_voltage,
+            attention_days: config.battery_attention_days,
+            rssi_poor: config.rssi_poor_dbm,
+            rssi_critical: config.rssi_critical_dbm,
+        }
+    }
+
+    /// Registra las muestras de batería y señal de una lectura
+    /// Publica la tendencia en stats y retorna el descuento de calidad y los issues
+    pub fn observe(
+        &self,
+        device_id: &str,
+        metrics: &[SensorMetric],
+        at: DateTime<Utc>,
+        stats: &mut HashMap<String, f32>,
+    ) -> (u8, Vec<String>) {
+        let mut devices = self.devices.lock().unwrap();
+        let mut penalty = 0u8;
+        let mut issues = Vec::new();
+
+        for metric in metrics {
+            if !metric.value.is_finite() {
+                continue;
+            }
+
+            let key = metric.measurement.to_lowercase();
+
+            if BATTERY_MEASUREMENTS.contains(&key.as_str()) {
+                let device = devices.entry(device_id.to_string()).or_default();
+                self.record(device, &key, metric.value, at);
+
+                if let Some(trend) = trend_per_day(&device.battery) {
+                    stats.insert("battery_trend_per_day".to_string(), trend);
+
+                    if let Some(days) = self.days_to_empty(&key, metric.value, trend) {
+                        stats.insert("battery_days_to_empty".to_string(), days);
+                    }
+                }
+            } else if key == RSSI_MEASUREMENT {
+                let device = devices.entry(device_id.to_string()).or_default();
+                self.record(device, &key, metric.value, at);
+
+                if metric.value < self.rssi_critical {
+                    penalty = penalty.saturating_add(RSSI_CRITICAL_PENALTY);
+                    issues.push(format!("Señal WiFi crítica: {} dBm", metric.value));
+                } else if metric.value < self.rssi_poor {
+                    penalty = penalty.saturating_add(RSSI_POOR_PENALTY);
+                    issues.push(format!("Señal WiFi débil: {} dBm", metric.value));
+                }
+            }
+        }
+
+        (penalty, issues)
+    }
+
+    /// Carga muestras históricas (en orden cronológico) sin evaluarlas
+    pub fn seed(&self, device_id: &str, measurement: &str, value: f32, at: DateTime<Utc>) {
+        let key = measurement.to_lowercase();
+        let mut devices = self.devices.lock().unwrap();
+        let device = devices.entry(device_id.to_string()).or_default();
+        self.record(device, &key, value, at);
+    }
+
+    /// Reporte de batería y señal de todos los dispositivos seguidos
+    /// Los nodos que requieren atención van primero, los de menor autonomía antes
+    pub fn report(&self) -> Vec<DevicePowerStatus> {
+        let devices = self.devices.lock().unwrap();
+
+        let mut report: Vec<DevicePowerStatus> = devices
+            .iter()
+            .filter_map(|(device_id, device)| self.status(device_id, device))
+            .collect();
+
+        report.sort_by(|a, b| {
+            b.needs_attention
+                .cmp(&a.needs_attention)
+                .then_with(|| {
+                    let a_days = a.days_to_empty.unwrap_or(f32::INFINITY);
+                    let b_days = b.days_to_empty.unwrap_or(f32::INFINITY);
+                    a_days.total_cmp(&b_days)
+                })
+                .then_with(|| a.device_id.cmp(&b.device_id))
+        });
+
+        report
+    }
+
+    fn status(&self, device_id: &str, device: &DevicePower) -> Option<DevicePowerStatus> {
+        let last_seen = device.last_seen?;
+        let battery = device.battery.back().map(|(_, value)| *value);
+        let rssi = device.rssi.back().map(|(_, value)| *value);
+        let rssi_avg = (!device.rssi.is_empty()).then(|| {
+            device.rssi.iter().map(|(_, value)| value).sum::<f32>() / device.rssi.len() as f32
+        });
+
+        let battery_trend_per_day = trend_per_day(&device.battery);
+        let days_to_empty = self.device_days_to_empty(device);
+
+        let mut reasons: Vec<String> = self.battery_reason(device).into_iter().collect();
+        if let Some(avg) = rssi_avg {
+            if avg < self.rssi_critical {
+                reasons.push(format!("Señal WiFi crítica ({:.0} dBm promedio)", avg));
+            } else if avg < self.rssi_poor {
+                reasons.push(format!("Señal WiFi débil ({:.0} dBm promedio)", avg));
+            }
+        }
+
+        Some(DevicePowerStatus {
+            device_id: device_id.to_string(),
+            battery,
+            battery_measurement: device.battery_measurement.clone(),
+            battery_trend_per_day,
+            days_to_empty,
+            rssi,
+            rssi_avg,
+            last_seen,
+            needs_attention: !reasons.is_empty(),
+            reasons,
+        })
+    }
+
+    /// Estado de la batería de un dispositivo; None si no informa batería
+    pub fn battery(&self, device_id: &str) -> Option<BatteryLevel> {
+        let devices = self.devices.lock().unwrap();
+        let device = devices.get(device_id)?;
+
+        Some(BatteryLevel {
+            measurement: device.battery_measurement.clone()?,
+            value: device.battery.back()?.1,
+            attention: self.battery_reason(device),
+        })
+    }
+
+    fn device_days_to_empty(&self, device: &DevicePower) -> Option<f32> {
+        let measurement = device.battery_measurement.as_ref()?;
+        let (_, value) = device.battery.back()?;
+        self.days_to_empty(measurement, *value, trend_per_day(&device.battery)?)
+    }
+
+    /// Motivo por el que la batería requiere atención: agotada o por agotarse
+    fn battery_reason(&self, device: &DevicePower) -> Option<String> {
+        let measurement = device.battery_measurement.as_ref()?;
+        let (_, value) = device.battery.back()?;
+
+        if *value <= self.empty_level(measurement) {
+            return Some("Batería agotada".to_string());
+        }
+        self.device_days_to_empty(device)
+            .filter(|days| *days < self.attention_days)
+            .map(|days| format!("Batería se agota en {:.1} días", days))
+    }
+
+    fn record(&self, device: &mut DevicePower, key: &str, value: f32, at: DateTime<Utc>) {
+        let samples = if key == RSSI_MEASUREMENT {
+            &mut device.rssi
+        } else {
+            // Si el dispositivo cambia de medición de batería, la tendencia previa no aplica
+            if device.battery_measurement.as_deref() != Some(key) {
+                device.battery.clear();
+                device.battery_measurement = Some(key.to_string());
+            }
+            &mut device.battery
+        };
+
+        samples.push_back((at, value));
+        while samples
+            .front()
+            .is_some_and(|(first, _)| at - *first > self.window)
+        {
+            samples.pop_front();
+        }
+
+        device.last_seen = device.last_seen.max(Some(at));
+    }
+
+    fn empty_level(&self, measurement: &str) -> f32 {
+        if measurement == "vbat" {
+            self.empty_voltage
+        } else {
+            self.empty_percent
+        }
+    }
+
+    /// Días hasta el nivel vacío; None si la batería no se está descargando
+    fn days_to_empty(&self, measurement: &str, value: f32, trend_per_day: f32) -> Option<f32> {
+        if trend_per_day >= 0.0 {
+            return None;
+        }
+
+        Some(((value - self.empty_level(measurement)) / -trend_per_day).max(0.0))
+    }
+}
+
+/// Pendiente por día de la regresión lineal de las muestras
+fn trend_per_day(samples: &Samples) -> Option<f32> {
+    let (first, _) = samples.front()?;
+    let (last, _) = samples.back()?;
+    if ((*last - *first).num_seconds() as f64) < MIN_TREND_SPAN_HOURS * 3600.0 {
+        return None;
+    }
+
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|(at, value)| {
+            let days = (*at - *first).num_milliseconds() as f64 / 86_400_000.0;
+            (days, *value as f64)
+        })
+        .collect();
+
+    let n = points.len() as f64;
+    let mean_t = points.iter().map(|(t, _)| t).sum::<f64>() / n;
+    let mean_v = points.iter().map(|(_, v)| v).sum::<f64>() / n;
+
+    let covariance: f64 = points
+        .iter()
+        .map(|(t, v)| (t - mean_t) * (v - mean_v))
+        .sum();
+    let variance: f64 = points.iter().map(|(t, _)| (t - mean_t).powi(2)).sum();
+
+    (variance > 0.0).then(|| (covariance / variance) as f32)
+}
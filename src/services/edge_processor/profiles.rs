@@ -0,0 +1,104 @@
+use crate::models::{DeviceProfile, ProfileCheck, SensorMetric};
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Descuentos de calidad por cada incumplimiento del perfil
+const MISSING_PENALTY: u8 = 15;
+const UNKNOWN_PENALTY: u8 = 5;
+const OUT_OF_RANGE_PENALTY: u8 = 20;
+const UNIT_MISMATCH_PENALTY: u8 = 10;
+
+/// Perfiles de métricas por tipo de dispositivo
+#[derive(Default)]
+pub struct ProfileStore {
+    profiles: RwLock<HashMap<String, DeviceProfile>>,
+}
+
+impl ProfileStore {
+    /// Reemplaza los perfiles cargados
+    pub fn replace(&self, profiles: Vec<DeviceProfile>) {
+        *self.profiles.write().unwrap() = profiles
+            .into_iter()
+            .map(|profile| (profile.device_type.clone(), profile))
+            .collect();
+    }
+
+    /// Valida las métricas contra el perfil del tipo de dispositivo
+    /// Retorna el resultado estructurado, el descuento de calidad y los issues
+    pub fn check(
+        &self,
+        device_type: &str,
+        metrics: &[SensorMetric],
+    ) -> Option<(ProfileCheck, u8, Vec<String>)> {
+        let profiles = self.profiles.read().unwrap();
+        let profile = profiles.get(device_type)?;
+
+        let mut check = ProfileCheck {
+            device_type: device_type.to_string(),
+            ..Default::default()
+        };
+        let mut issues = Vec::new();
+
+        for expected in &profile.measurements {
+            let metric = metrics
+                .iter()
+                .find(|m| m.measurement.eq_ignore_ascii_case(&expected.measurement));
+
+            let Some(metric) = metric else {
+                if expected.required {
+                    issues.push(format!(
+                        "Falta la métrica esperada por el perfil {}: {}",
+                        device_type, expected.measurement
+                    ));
+                    check.missing.push(expected.measurement.clone());
+                }
+                continue;
+            };
+
+            let below = expected.min.is_some_and(|min| metric.value < min);
+            let above = expected.max.is_some_and(|max| metric.value > max);
+            if below || above {
+                issues.push(format!(
+                    "Métrica fuera del rango del perfil {}: {} = {}",
+                    device_type, metric.measurement, metric.value
+                ));
+                check.out_of_range.push(metric.measurement.clone());
+            }
+
+            if let (Some(expected_unit), Some(unit)) = (&expected.unit, &metric.unit)
+                && !expected_unit.eq_ignore_ascii_case(unit)
+            {
+                issues.push(format!(
+                    "Unidad inesperada en métrica {}: {} (perfil {}: {})",
+                    metric.measurement, unit, device_type, expected_unit
+                ));
+                check.unit_mismatch.push(metric.measurement.clone());
+            }
+        }
+
+        if !profile.allow_unknown {
+            for metric in metrics {
+                let declared = profile
+                    .measurements
+                    .iter()
+                    .any(|m| m.measurement.eq_ignore_ascii_case(&metric.measurement));
+
+                if !declared {
+                    issues.push(format!(
+                        "Métrica no declarada en el perfil {}: {}",
+                        device_type, metric.measurement
+                    ));
+                    check.unknown.push(metric.measurement.clone());
+                }
+            }
+        }
+
+        let penalty = (check.missing.len() * MISSING_PENALTY as usize
+            + check.unknown.len() * UNKNOWN_PENALTY as usize
+            + check.out_of_range.len() * OUT_OF_RANGE_PENALTY as usize
+            + check.unit_mismatch.len() * UNIT_MISMATCH_PENALTY as usize)
+            .min(u8::MAX as usize) as u8;
+
+        Some((check, penalty, issues))
+    }
+}
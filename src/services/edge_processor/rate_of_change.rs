@@ -0,0 +1,63 @@
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Muestra aceptada: momento y valor
+type Sample = (DateTime<Utc>, f32);
+
+/// Detección de saltos físicamente imposibles entre muestras consecutivas
+pub struct RateOfChangeDetector {
+    /// Variación máxima por minuto (clave en minúsculas)
+    limits: HashMap<String, f32>,
+    /// Última muestra aceptada por dispositivo y medición
+    previous: Mutex<HashMap<(String, String), Sample>>,
+}
+
+impl RateOfChangeDetector {
+    pub fn new(limits: &HashMap<String, f32>) -> Self {
+        Self {
+            limits: limits
+                .iter()
+                .map(|(measurement, limit)| (measurement.to_lowercase(), *limit))
+                .collect(),
+            previous: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Retorna la variación por minuto si supera el límite de la medición
+    ///
+    /// Una muestra rechazada no reemplaza a la anterior: si el cambio es real,
+    /// el tiempo transcurrido termina haciéndolo admisible.
+    pub fn check(
+        &self,
+        device_id: &str,
+        measurement: &str,
+        value: f32,
+        at: DateTime<Utc>,
+    ) -> Option<f32> {
+        let measurement = measurement.to_lowercase();
+        let limit = *self.limits.get(&measurement)?;
+        if !value.is_finite() {
+            return None;
+        }
+
+        let mut previous = self.previous.lock().unwrap();
+        let key = (device_id.to_string(), measurement);
+
+        if let Some(&(prev_at, prev)) = previous.get(&key) {
+            let minutes = (at - prev_at).num_milliseconds() as f32 / 60_000.0;
+            let rate = if minutes > 0.0 {
+                (value - prev).abs() / minutes
+            } else {
+                f32::INFINITY
+            };
+
+            if rate > limit && value != prev {
+                return Some(rate);
+            }
+        }
+
+        previous.insert(key, (at, value));
+        None
+    }
+}
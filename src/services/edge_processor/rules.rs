@@ -0,0 +1,181 @@
+use crate::models::{Rule, RuleAction, RuleOperator, SensorMetric};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+/// Transición de estado de una regla para un dispositivo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RuleTransition {
+    Fired,
+    Resolved,
+}
+
+/// Evento emitido cuando una regla se activa o deja de cumplirse
+#[derive(Debug, Clone)]
+pub struct RuleEvent {
+    pub rule: Rule,
+    pub transition: RuleTransition,
+    pub device_id: String,
+    pub location: String,
+    pub value: f32,
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Resultado de evaluar las reglas sobre una lectura
+#[derive(Default)]
+pub struct RuleOutcome {
+    /// Nombres de las reglas activas con acción `flag`
+    pub flagged: Vec<String>,
+    pub events: Vec<RuleEvent>,
+}
+
+/// Estado de una regla para un dispositivo
+#[derive(Default)]
+struct RuleState {
+    /// Desde cuándo se cumple la condición de forma continua
+    since: Option<DateTime<Utc>>,
+    /// Lecturas seguidas que cumplen la condición
+    breaches: u32,
+    firing: bool,
+    /// Última activación, para el cooldown
+    last_fired: Option<DateTime<Utc>>,
+}
+
+/// Motor de reglas evaluado en línea por el procesador edge
+#[derive(Default)]
+pub struct RuleEngine {
+    rules: RwLock<Vec<Rule>>,
+    /// Estado por (rule_id, device_id)
+    states: RwLock<HashMap<(String, String), RuleState>>,
+    /// Etiquetas de cada dispositivo, para las reglas limitadas a un grupo
+    device_tags: RwLock<HashMap<String, Vec<String>>>,
+}
+
+impl RuleEngine {
+    /// Reemplaza las reglas; conserva el estado de las reglas que siguen existiendo
+    pub fn replace(&self, rules: Vec<Rule>) {
+        self.states
+            .write()
+            .unwrap()
+            .retain(|(rule_id, _), _| rules.iter().any(|r| &r.id == rule_id && r.enabled));
+        *self.rules.write().unwrap() = rules;
+    }
+
+    /// Reemplaza las etiquetas de los dispositivos
+    pub fn set_device_tags(&self, tags: HashMap<String, Vec<String>>) {
+        *self.device_tags.write().unwrap() = tags;
+    }
+
+    /// Evalúa las reglas que aplican a la lectura
+    ///
+    /// `stats` son las estadísticas ya calculadas de la lectura, de donde las
+    /// reglas `anomaly` toman los z-scores de cada medición.
+    pub fn evaluate(
+        &self,
+        device_id: &str,
+        location: &str,
+        metrics: &[SensorMetric],
+        stats: &HashMap<String, f32>,
+        at: DateTime<Utc>,
+    ) -> RuleOutcome {
+        let rules = self.rules.read().unwrap();
+        let mut outcome = RuleOutcome::default();
+        if rules.is_empty() {
+            return outcome;
+        }
+
+        let mut states = self.states.write().unwrap();
+        let device_tags = self.device_tags.read().unwrap();
+        let tags = device_tags.get(device_id);
+
+        for rule in rules.iter().filter(|r| r.enabled) {
+            if rule.device_id.as_deref().is_some_and(|d| d != device_id)
+                || rule.location.as_deref().is_some_and(|l| l != location)
+                || rule
+                    .tag
+                    .as_ref()
+                    .is_some_and(|t| !tags.is_some_and(|tags| tags.contains(t)))
+            {
+                continue;
+            }
+
+            let Some(metric) = metrics
+                .iter()
+                .find(|m| m.measurement.eq_ignore_ascii_case(&rule.measurement))
+            else {
+                continue;
+            };
+
+            // Sin z-score (histórico aún insuficiente) la regla anomaly no se cumple
+            let compared = match rule.operator {
+                RuleOperator::Anomaly => ["zscore", "baseline_zscore"]
+                    .iter()
+                    .filter_map(|kind| stats.get(&format!("{}_{}", metric.measurement, kind)))
+                    .map(|zscore| zscore.abs())
+                    .reduce(f32::max),
+                _ => Some(metric.value),
+            };
+
+            let state = states
+                .entry((rule.id.clone(), device_id.to_string()))
+                .or_default();
+
+            // Con histéresis, la regla activa se mantiene hasta cruzar el umbral de resolución
+            let threshold = if state.firing {
+                rule.clear_threshold.unwrap_or(rule.threshold)
+            } else {
+                rule.threshold
+            };
+
+            let holds = compared.is_some_and(|value| rule.operator.matches(value, threshold));
+            let transition = if holds {
+                let since = *state.since.get_or_insert(at);
+                state.breaches = state.breaches.saturating_add(1);
+                let held = state.firing
+                    || ((at - since).num_seconds() >= rule.for_secs as i64
+                        && state.breaches >= rule.consecutive);
+
+                if held && rule.actions.iter().any(|a| matches!(a, RuleAction::Flag)) {
+                    outcome.flagged.push(rule.name.clone());
+                }
+
+                // Durante el cooldown la activación queda pendiente mientras se siga cumpliendo
+                let cooled = state
+                    .last_fired
+                    .is_none_or(|last| (at - last).num_seconds() >= rule.cooldown_secs as i64);
+
+                if held && !state.firing && cooled {
+                    state.firing = true;
+                    state.last_fired = Some(at);
+                    Some(RuleTransition::Fired)
+                } else {
+                    None
+                }
+            } else {
+                state.since = None;
+                state.breaches = 0;
+                if state.firing {
+                    state.firing = false;
+                    Some(RuleTransition::Resolved)
+                } else {
+                    None
+                }
+            };
+
+            if let Some(transition) = transition {
+                outcome.events.push(RuleEvent {
+                    rule: rule.clone(),
+                    transition,
+                    device_id: device_id.to_string(),
+                    location: location.to_string(),
+                    value: metric.value,
+                    timestamp: at,
+                });
+            }
+        }
+
+        outcome
+    }
+}
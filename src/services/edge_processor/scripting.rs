@@ -0,0 +1,239 @@
+use crate::models::SensorMetric;
+use rhai::module_resolvers::DummyModuleResolver;
+use rhai::{AST, Dynamic, Engine, Map, Scope};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+use std::time::SystemTime;
+
+/// Operaciones máximas por ejecución (evita bucles infinitos en scripts)
+/// Cada lectura puede ejecutar hasta cuatro scripts en el hilo de procesamiento
+const MAX_OPERATIONS: u64 = 20_000;
+
+/// Tamaño máximo de strings, arrays y mapas creados por un script
+const MAX_STRING_SIZE: usize = 4 * 1024;
+const MAX_ARRAY_SIZE: usize = 1024;
+const MAX_MAP_SIZE: usize = 256;
+
+/// Script compilado y la fecha de modificación del archivo del que proviene
+struct CompiledScript {
+    ast: AST,
+    modified: SystemTime,
+}
+
+/// Scripts Rhai que agregan métricas derivadas a `ComputedMetrics.stats`
+///
+/// Estructura del directorio:
+/// - `all.rhai`: se aplica a todas las lecturas
+/// - `location/<ubicación>.rhai`: lecturas de una ubicación
+/// - `type/<device_type>.rhai`: lecturas de un tipo de dispositivo
+/// - `device/<device_id>.rhai`: lecturas de un dispositivo
+///
+/// Cada script recibe `metrics` (mapa medición → valor), `device_id`,
+/// `location` y `device_type` (`()` si el dispositivo no lo reporta), y retorna
+/// un mapa con los valores a agregar.
+pub struct ScriptHooks {
+    engine: Engine,
+    dir: PathBuf,
+    /// Scripts por clave ("all", "location/<nombre>", "type/<tipo>", "device/<id>")
+    scripts: RwLock<HashMap<String, CompiledScript>>,
+    /// Versiones de archivo que no compilaron (para no reintentar ni repetir el error)
+    failed: RwLock<HashMap<String, SystemTime>>,
+}
+
+impl ScriptHooks {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_expr_depths(64, 32);
+        engine.set_max_string_size(MAX_STRING_SIZE);
+        engine.set_max_array_size(MAX_ARRAY_SIZE);
+        engine.set_max_map_size(MAX_MAP_SIZE);
+
+        // Los scripts no importan módulos del disco ni escriben en stdout
+        engine.set_module_resolver(DummyModuleResolver::new());
+        engine.on_print(|text| tracing::info!(target: "script", "{}", text));
+        engine.on_debug(|text, source, pos| {
+            tracing::debug!(target: "script", source = source.unwrap_or(""), %pos, "{}", text)
+        });
+
+        Self {
+            engine,
+            dir: dir.into(),
+            scripts: RwLock::new(HashMap::new()),
+            failed: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Recompila los scripts nuevos o modificados y descarta los eliminados
+    /// Un script con errores conserva su versión anterior
+    pub fn reload(&self) {
+        let mut found = Vec::new();
+        collect_scripts(&self.dir, "", &mut found);
+
+        let mut scripts = self.scripts.write().unwrap();
+        let mut failed = self.failed.write().unwrap();
+        scripts.retain(|key, _| found.iter().any(|(k, _, _)| k == key));
+
+        for (key, path, modified) in found {
+            if scripts.get(&key).is_some_and(|s| s.modified == modified)
+                || failed.get(&key) == Some(&modified)
+            {
+                continue;
+            }
+
+            match self.engine.compile_file(path.clone()) {
+                Ok(ast) => {
+                    tracing::info!(script = %key, "Script cargado");
+                    failed.remove(&key);
+                    scripts.insert(key, CompiledScript { ast, modified });
+                }
+                Err(e) => {
+                    failed.insert(key, modified);
+                    tracing::error!(
+                        path = %path.display(),
+                        error = %e,
+                        "Error compilando script"
+                    );
+                }
+            }
+        }
+    }
+
+    /// Ejecuta los scripts que aplican a la lectura y agrega sus resultados a `stats`
+    /// En el runtime multihilo la ejecución se marca como bloqueante para que tokio
+    /// reubique las demás tareas del worker mientras corre el script
+    pub fn run(
+        &self,
+        device_id: &str,
+        location: &str,
+        device_type: Option<&str>,
+        metrics: &[SensorMetric],
+        stats: &mut HashMap<String, f32>,
+    ) {
+        let scripts = self.scripts.read().unwrap();
+        if scripts.is_empty() {
+            return;
+        }
+
+        let multi_thread = tokio::runtime::Handle::try_current()
+            .is_ok_and(|h| h.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread);
+        if multi_thread {
+            tokio::task::block_in_place(|| {
+                self.run_scripts(&scripts, device_id, location, device_type, metrics, stats)
+            });
+        } else {
+            self.run_scripts(&scripts, device_id, location, device_type, metrics, stats);
+        }
+    }
+
+    fn run_scripts(
+        &self,
+        scripts: &HashMap<String, CompiledScript>,
+        device_id: &str,
+        location: &str,
+        device_type: Option<&str>,
+        metrics: &[SensorMetric],
+        stats: &mut HashMap<String, f32>,
+    ) {
+        let metrics_map: Map = metrics
+            .iter()
+            .map(|m| {
+                (
+                    m.measurement.as_str().into(),
+                    Dynamic::from_float(m.value as f64),
+                )
+            })
+            .collect();
+
+        let keys = [
+            Some("all".to_string()),
+            Some(format!("location/{}", location)),
+            device_type.map(|t| format!("type/{}", t)),
+            Some(format!("device/{}", device_id)),
+        ];
+
+        for key in keys.iter().flatten() {
+            let Some(script) = scripts.get(key) else {
+                continue;
+            };
+
+            let mut scope = Scope::new();
+            scope.push("metrics", metrics_map.clone());
+            scope.push_constant("device_id", device_id.to_string());
+            scope.push_constant("location", location.to_string());
+            scope.push_constant(
+                "device_type",
+                device_type.map_or(Dynamic::UNIT, |t| t.to_string().into()),
+            );
+
+            match self
+                .engine
+                .eval_ast_with_scope::<Map>(&mut scope, &script.ast)
+            {
+                Ok(result) => {
+                    for (name, value) in result {
+                        match value
+                            .as_float()
+                            .or_else(|_| value.as_int().map(|i| i as f64))
+                        {
+                            Ok(v) => {
+                                stats.insert(name.to_string(), v as f32);
+                            }
+                            Err(_) => tracing::warn!(
+                                script = %key,
+                                stat = %name,
+                                "Valor no numérico retornado por script"
+                            ),
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::warn!(script = %key, error = %e, "Error ejecutando script");
+                }
+            }
+        }
+    }
+}
+
+/// Recorre el directorio de scripts y retorna (clave, ruta, fecha de modificación)
+fn collect_scripts(dir: &Path, prefix: &str, found: &mut Vec<(String, PathBuf, SystemTime)>) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+
+        if metadata.is_dir() {
+            // Solo un nivel de subdirectorios: location/, type/ y device/
+            if prefix.is_empty()
+                && let Some(name) = path.file_name().and_then(|n| n.to_str())
+            {
+                collect_scripts(&path, name, found);
+            }
+            continue;
+        }
+
+        if path.extension().and_then(|e| e.to_str()) != Some("rhai") {
+            continue;
+        }
+
+        let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+
+        let key = if prefix.is_empty() {
+            stem.to_string()
+        } else {
+            format!("{}/{}", prefix, stem)
+        };
+
+        if let Ok(modified) = metadata.modified() {
+            found.push((key, path, modified));
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Media móvil exponencial (EWMA) por dispositivo y medición
+pub struct EwmaTracker {
+    values: Mutex<HashMap<(String, String), f32>>,
+    alpha: f32,
+}
+
+impl EwmaTracker {
+    /// `alpha` en (0, 1]: valores altos siguen más rápido al dato crudo
+    pub fn new(alpha: f32) -> Self {
+        Self {
+            values: Mutex::new(HashMap::new()),
+            alpha,
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.alpha > 0.0 && self.alpha <= 1.0
+    }
+
+    /// Actualiza la media de la serie con el nuevo valor y la retorna
+    pub fn update(&self, device_id: &str, measurement: &str, value: f32) -> Option<f32> {
+        if !self.is_enabled() || !value.is_finite() {
+            return None;
+        }
+
+        let mut values = self.values.lock().unwrap();
+        let smoothed = values
+            .entry((device_id.to_string(), measurement.to_lowercase()))
+            .and_modify(|ewma| *ewma = self.alpha * value + (1.0 - self.alpha) * *ewma)
+            .or_insert(value);
+
+        Some(*smoothed)
+    }
+}
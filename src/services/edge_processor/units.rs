@@ -0,0 +1,52 @@
+use crate::models::SensorMetric;
+
+/// Conversión de una unidad de entrada a la unidad canónica de su magnitud
+struct Conversion {
+    canonical: &'static str,
+    convert: fn(f32) -> f32,
+}
+
+/// Busca la conversión para una unidad (sin distinguir mayúsculas)
+/// Las unidades canónicas y las desconocidas no tienen conversión
+fn conversion_for(unit: &str) -> Option<Conversion> {
+    let conversion = |canonical, convert| Some(Conversion { canonical, convert });
+
+    match unit.trim().to_lowercase().as_str() {
+        // Temperatura → °C
+        "°f" | "f" | "degf" | "fahrenheit" => conversion("°C", |v| (v - 32.0) * 5.0 / 9.0),
+        "k" | "kelvin" => conversion("°C", |v| v - 273.15),
+
+        // Presión → hPa
+        "pa" => conversion("hPa", |v| v / 100.0),
+        "kpa" => conversion("hPa", |v| v * 10.0),
+        "bar" => conversion("hPa", |v| v * 1000.0),
+        "mbar" => conversion("hPa", |v| v),
+        "inhg" => conversion("hPa", |v| v * 33.863_89),
+        "mmhg" => conversion("hPa", |v| v * 1.333_224),
+        "psi" => conversion("hPa", |v| v * 68.947_57),
+
+        // Longitud / distancia / precipitación → mm
+        "in" | "inch" | "inches" => conversion("mm", |v| v * 25.4),
+        "ft" | "feet" => conversion("mm", |v| v * 304.8),
+        "cm" => conversion("mm", |v| v * 10.0),
+        "m" => conversion("mm", |v| v * 1000.0),
+
+        // Velocidad → m/s
+        "km/h" | "kmh" => conversion("m/s", |v| v / 3.6),
+        "mph" => conversion("m/s", |v| v * 0.447_04),
+        "kn" | "knots" => conversion("m/s", |v| v * 0.514_444),
+
+        // Tensión → V
+        "mv" => conversion("V", |v| v / 1000.0),
+
+        _ => None,
+    }
+}
+
+/// Convierte la métrica a la unidad canónica si su unidad lo requiere
+pub fn normalize(metric: &mut SensorMetric) {
+    if let Some(conversion) = metric.unit.as_deref().and_then(conversion_for) {
+        metric.value = (conversion.convert)(metric.value);
+        metric.unit = Some(conversion.canonical.to_string());
+    }
+}
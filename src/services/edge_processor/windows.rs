@@ -0,0 +1,93 @@
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Muestras de una serie dentro de la ventana más larga, de la más antigua a la más nueva
+type Samples = VecDeque<(DateTime<Utc>, f32)>;
+
+/// Agregados (promedio, mínimo y máximo) en ventanas deslizantes de tiempo
+/// por dispositivo y medición, mantenidos en memoria
+pub struct WindowAggregator {
+    /// Duración en segundos y sufijo usado en stats (`5m`)
+    windows: Vec<(i64, String)>,
+    samples: Mutex<HashMap<(String, String), Samples>>,
+}
+
+impl WindowAggregator {
+    pub fn new(windows_secs: &[u64]) -> Self {
+        let mut windows: Vec<(i64, String)> = windows_secs
+            .iter()
+            .map(|&secs| (secs as i64, window_label(secs)))
+            .collect();
+        windows.sort();
+        windows.dedup();
+
+        Self {
+            windows,
+            samples: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Agrega la muestra y publica los agregados de cada ventana en `stats`
+    pub fn update(
+        &self,
+        device_id: &str,
+        measurement: &str,
+        value: f32,
+        at: DateTime<Utc>,
+        stats: &mut HashMap<String, f32>,
+    ) {
+        let Some(&(longest, _)) = self.windows.last() else {
+            return;
+        };
+        if !value.is_finite() {
+            return;
+        }
+
+        let mut samples = self.samples.lock().unwrap();
+        let series = samples
+            .entry((device_id.to_string(), measurement.to_lowercase()))
+            .or_default();
+
+        series.push_back((at, value));
+        while let Some(&(oldest, _)) = series.front() {
+            if (at - oldest).num_seconds() < longest {
+                break;
+            }
+            series.pop_front();
+        }
+
+        for (secs, label) in &self.windows {
+            let mut count = 0usize;
+            let mut sum = 0.0;
+            let mut min = f32::INFINITY;
+            let mut max = f32::NEG_INFINITY;
+
+            for &(_, v) in series
+                .iter()
+                .rev()
+                .take_while(|(sample_at, _)| (at - *sample_at).num_seconds() < *secs)
+            {
+                count += 1;
+                sum += v;
+                min = min.min(v);
+                max = max.max(v);
+            }
+
+            stats.insert(format!("{}_avg_{}", measurement, label), sum / count as f32);
+            stats.insert(format!("{}_min_{}", measurement, label), min);
+            stats.insert(format!("{}_max_{}", measurement, label), max);
+        }
+    }
+}
+
+/// Sufijo legible de una ventana (`90s`, `5m`, `1h`)
+fn window_label(secs: u64) -> String {
+    if secs.is_multiple_of(3600) {
+        format!("{}h", secs / 3600)
+    } else if secs.is_multiple_of(60) {
+        format!("{}m", secs / 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
@@ -0,0 +1,393 @@
+use crate::database::{Database, MetricFilter};
+use crate::models::ProcessedSensorData;
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use parquet::basic::{LogicalType, Repetition, Type as PhysicalType};
+use parquet::data_type::{BoolType, ByteArray, ByteArrayType, FloatType, Int32Type, Int64Type};
+use parquet::file::properties::WriterProperties;
+use parquet::file::writer::SerializedFileWriter;
+use parquet::format::{MilliSeconds, TimeUnit};
+use parquet::schema::types::Type;
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+/// Lecturas leídas de SQLite por página durante la exportación
+const EXPORT_PAGE_SIZE: usize = 500;
+
+/// Filas por row group en los archivos Parquet
+const PARQUET_ROW_GROUP_SIZE: usize = 10_000;
+
+/// Columnas fijas de cada fila exportada; las mediciones se agregan a continuación
+const BASE_COLUMNS: [&str; 15] = [
+    "id",
+    "device_id",
+    "location",
+    "gateway_timestamp",
+    "measured_at",
+    "quality_score",
+    "is_anomaly",
+    "heat_index",
+    "dew_point",
+    "comfort_level",
+    "absolute_humidity",
+    "vapor_pressure_deficit",
+    "wet_bulb",
+    "air_quality_index",
+    "ventilation_score",
+];
+
+/// Prefijo de las columnas de medición que coinciden con una columna fija
+const MEASUREMENT_COLUMN_PREFIX: &str = "metric_";
+
+/// Nombre de columna de una medición, sin chocar con las columnas fijas
+fn measurement_column(measurement: &str) -> String {
+    if BASE_COLUMNS
+        .iter()
+        .any(|c| c.eq_ignore_ascii_case(measurement))
+    {
+        format!("{MEASUREMENT_COLUMN_PREFIX}{measurement}")
+    } else {
+        measurement.to_string()
+    }
+}
+
+/// Formatos de exportación soportados
+#[derive(Debug, Clone, Copy, Default, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Parquet,
+}
+
+/// Lectura aplanada: una columna por medición
+struct ExportRow {
+    id: String,
+    device_id: String,
+    location: String,
+    timestamp: DateTime<Utc>,
+    measured_at: DateTime<Utc>,
+    quality_score: u8,
+    is_anomaly: bool,
+    /// Métricas computadas en el orden de BASE_COLUMNS y luego una entrada por medición
+    values: Vec<Option<f32>>,
+}
+
+impl ExportRow {
+    fn new(data: &ProcessedSensorData, measurements: &[String]) -> Self {
+        let mut values = vec![
+            data.computed.heat_index,
+            data.computed.dew_point,
+            data.computed.comfort_level,
+            data.computed.absolute_humidity,
+            data.computed.vapor_pressure_deficit,
+            data.computed.wet_bulb,
+            data.computed.air_quality_index,
+            data.computed.ventilation_score,
+        ];
+
+        for measurement in measurements {
+            values.push(
+                data.metrics
+                    .iter()
+                    .rev()
+                    .find(|m| m.measurement.eq_ignore_ascii_case(measurement))
+                    .map(|m| m.value)
+                    .filter(|v| v.is_finite()),
+            );
+        }
+
+        Self {
+            id: data.id.to_string(),
+            device_id: data.header.device_id.clone(),
+            location: data.header.location.clone(),
+            timestamp: data.gateway_timestamp,
+            measured_at: data.measured_at,
+            quality_score: data.quality.score,
+            is_anomaly: data.computed.is_anomaly,
+            values,
+        }
+    }
+
+    fn to_csv_record(&self) -> Vec<String> {
+        let mut record = vec![
+            self.id.clone(),
+            self.device_id.clone(),
+            self.location.clone(),
+            self.timestamp.to_rfc3339(),
+            self.measured_at.to_rfc3339(),
+            self.quality_score.to_string(),
+            self.is_anomaly.to_string(),
+        ];
+        record.extend(
+            self.values
+                .iter()
+                .map(|v| v.map(|v| v.to_string()).unwrap_or_default()),
+        );
+        record
+    }
+}
+
+/// Servicio de exportación de lecturas a CSV y Parquet
+pub struct ExportService {
+    db: Database,
+    filter: MetricFilter,
+    measurements: Vec<String>,
+}
+
+impl ExportService {
+    /// Prepara una exportación; las columnas de métricas se derivan de las
+    /// mediciones presentes en el periodo
+    pub async fn prepare(db: Database, filter: MetricFilter) -> anyhow::Result<Self> {
+        // La exportación incluye las lecturas aún en el búfer de escritura
+        db.flush_writes().await;
+        let measurements = db.distinct_measurements(&filter).await?;
+
+        Ok(Self {
+            db,
+            filter,
+            measurements,
+        })
+    }
+
+    fn header(&self) -> Vec<String> {
+        BASE_COLUMNS
+            .iter()
+            .map(|c| c.to_string())
+            .chain(self.measurements.iter().map(|m| measurement_column(m)))
+            .collect()
+    }
+
+    /// Recorre las lecturas del periodo página a página
+    async fn next_page(
+        &self,
+        after: &mut Option<(DateTime<Utc>, String)>,
+    ) -> anyhow::Result<Vec<ExportRow>> {
+        // El filtro por medición solo limita las columnas, no las lecturas
+        let readings_filter = MetricFilter {
+            measurement: None,
+            ..self.filter.clone()
+        };
+
+        let page = self
+            .db
+            .get_readings_page(&readings_filter, after.as_ref(), EXPORT_PAGE_SIZE)
+            .await?;
+
+        if let Some(last) = page.last() {
+            *after = Some((last.measured_at, last.id.to_string()));
+        }
+
+        Ok(page
+            .iter()
+            .map(|data| ExportRow::new(data, &self.measurements))
+            .collect())
+    }
+
+    /// Genera el CSV en segundo plano y lo entrega como stream de bloques
+    pub fn stream_csv(self) -> ReceiverStream<Result<Bytes, std::io::Error>> {
+        let (tx, rx) = mpsc::channel(4);
+
+        tokio::spawn(async move {
+            if let Err(e) = self.write_csv(&tx).await {
+                tracing::error!("Error exportando CSV: {}", e);
+                let _ = tx.send(Err(std::io::Error::other(e.to_string()))).await;
+            }
+        });
+
+        ReceiverStream::new(rx)
+    }
+
+    async fn write_csv(
+        &self,
+        tx: &mpsc::Sender<Result<Bytes, std::io::Error>>,
+    ) -> anyhow::Result<()> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer.write_record(self.header())?;
+        if tx
+            .send(Ok(Bytes::from(writer.into_inner()?)))
+            .await
+            .is_err()
+        {
+            return Ok(()); // Cliente desconectado
+        }
+
+        let mut after = None;
+        loop {
+            let rows = self.next_page(&mut after).await?;
+            if rows.is_empty() {
+                break;
+            }
+
+            let mut writer = csv::WriterBuilder::new()
+                .has_headers(false)
+                .from_writer(Vec::new());
+            for row in &rows {
+                writer.write_record(row.to_csv_record())?;
+            }
+
+            if tx
+                .send(Ok(Bytes::from(writer.into_inner()?)))
+                .await
+                .is_err()
+            {
+                return Ok(());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Escribe las lecturas a un archivo Parquet y retorna el número de filas
+    pub async fn write_parquet(&self, path: &Path) -> anyhow::Result<usize> {
+        let file = std::fs::File::create(path)?;
+        let props = Arc::new(WriterProperties::builder().build());
+        let mut writer = SerializedFileWriter::new(file, self.parquet_schema()?, props)?;
+
+        let mut total = 0;
+        let mut buffer = Vec::with_capacity(PARQUET_ROW_GROUP_SIZE);
+        let mut after = None;
+
+        loop {
+            let rows = self.next_page(&mut after).await?;
+            let done = rows.is_empty();
+            total += rows.len();
+            buffer.extend(rows);
+
+            if buffer.len() >= PARQUET_ROW_GROUP_SIZE || (done && !buffer.is_empty()) {
+                write_row_group(&mut writer, &buffer)?;
+                buffer.clear();
+            }
+
+            if done {
+                break;
+            }
+        }
+
+        writer.close()?;
+        Ok(total)
+    }
+
+    fn parquet_schema(&self) -> anyhow::Result<Arc<Type>> {
+        let text = |name: &str| {
+            Type::primitive_type_builder(name, PhysicalType::BYTE_ARRAY)
+                .with_repetition(Repetition::REQUIRED)
+                .with_logical_type(Some(LogicalType::String))
+                .build()
+        };
+
+        let timestamp = |name: &str| {
+            Type::primitive_type_builder(name, PhysicalType::INT64)
+                .with_repetition(Repetition::REQUIRED)
+                .with_logical_type(Some(LogicalType::Timestamp {
+                    is_adjusted_to_u_t_c: true,
+                    unit: TimeUnit::MILLIS(MilliSeconds {}),
+                }))
+                .build()
+        };
+
+        let mut fields = vec![
+            text("id")?,
+            text("device_id")?,
+            text("location")?,
+            timestamp("gateway_timestamp")?,
+            timestamp("measured_at")?,
+            Type::primitive_type_builder("quality_score", PhysicalType::INT32)
+                .with_repetition(Repetition::REQUIRED)
+                .build()?,
+            Type::primitive_type_builder("is_anomaly", PhysicalType::BOOLEAN)
+                .with_repetition(Repetition::REQUIRED)
+                .build()?,
+        ];
+
+        let float_columns = BASE_COLUMNS[7..]
+            .iter()
+            .map(|c| c.to_string())
+            .chain(self.measurements.iter().map(|m| measurement_column(m)));
+        for name in float_columns {
+            fields.push(
+                Type::primitive_type_builder(&name, PhysicalType::FLOAT)
+                    .with_repetition(Repetition::OPTIONAL)
+                    .build()?,
+            );
+        }
+
+        let schema = Type::group_type_builder("sensor_reading")
+            .with_fields(fields.into_iter().map(Arc::new).collect())
+            .build()?;
+
+        Ok(Arc::new(schema))
+    }
+}
+
+/// Escribe un row group con las columnas en el orden del esquema
+fn write_row_group(
+    writer: &mut SerializedFileWriter<std::fs::File>,
+    rows: &[ExportRow],
+) -> anyhow::Result<()> {
+    let mut row_group = writer.next_row_group()?;
+    let mut index = 0;
+
+    while let Some(mut column) = row_group.next_column()? {
+        match index {
+            0..=2 => {
+                let values: Vec<ByteArray> = rows
+                    .iter()
+                    .map(|r| match index {
+                        0 => ByteArray::from(r.id.as_str()),
+                        1 => ByteArray::from(r.device_id.as_str()),
+                        _ => ByteArray::from(r.location.as_str()),
+                    })
+                    .collect();
+                column
+                    .typed::<ByteArrayType>()
+                    .write_batch(&values, None, None)?;
+            }
+            3 | 4 => {
+                let values: Vec<i64> = rows
+                    .iter()
+                    .map(|r| match index {
+                        3 => r.timestamp.timestamp_millis(),
+                        _ => r.measured_at.timestamp_millis(),
+                    })
+                    .collect();
+                column
+                    .typed::<Int64Type>()
+                    .write_batch(&values, None, None)?;
+            }
+            5 => {
+                let values: Vec<i32> = rows.iter().map(|r| r.quality_score as i32).collect();
+                column
+                    .typed::<Int32Type>()
+                    .write_batch(&values, None, None)?;
+            }
+            6 => {
+                let values: Vec<bool> = rows.iter().map(|r| r.is_anomaly).collect();
+                column
+                    .typed::<BoolType>()
+                    .write_batch(&values, None, None)?;
+            }
+            _ => {
+                // Columnas opcionales: solo se escriben los valores presentes
+                let position = index - 7;
+                let values: Vec<f32> = rows.iter().filter_map(|r| r.values[position]).collect();
+                let definition_levels: Vec<i16> = rows
+                    .iter()
+                    .map(|r| r.values[position].is_some() as i16)
+                    .collect();
+                column
+                    .typed::<FloatType>()
+                    .write_batch(&values, Some(&definition_levels), None)?;
+            }
+        }
+
+        column.close()?;
+        index += 1;
+    }
+
+    row_group.close()?;
+    Ok(())
+}
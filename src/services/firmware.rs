@@ -0,0 +1,212 @@
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::{FirmwareImage, FirmwareUploadInput, OtaStatusReport, RolloutState};
+use chrono::Utc;
+use rumqttc::{AsyncClient, QoS};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Distribución de firmware a los nodos ESP32 (OTA)
+///
+/// Los binarios se guardan en `FIRMWARE_DIR` y se descargan por HTTP desde
+/// `/api/v1/firmware/{id}/download`. Cada despliegue se anuncia retenido en
+/// `sensors/{id}/ota`, y el dispositivo informa su progreso en
+/// `sensors/{id}/ota/status`: pending → downloading → applied | failed.
+pub struct FirmwareService {
+    config: Arc<Config>,
+    db: Database,
+    client: AsyncClient,
+}
+
+impl FirmwareService {
+    pub fn new(config: Arc<Config>, db: Database, client: AsyncClient) -> Self {
+        Self { config, db, client }
+    }
+
+    /// Topic en el que se anuncia el firmware disponible para un dispositivo
+    pub fn topic(device_id: &str) -> String {
+        format!("sensors/{}/ota", device_id)
+    }
+
+    /// Ruta de descarga de una imagen (relativa al gateway)
+    pub fn download_path(firmware_id: i64) -> String {
+        format!("/api/v1/firmware/{}/download", firmware_id)
+    }
+
+    /// Archivo del binario de una imagen
+    pub fn file_path(&self, image: &FirmwareImage) -> PathBuf {
+        PathBuf::from(&self.config.firmware_dir).join(&image.file_name)
+    }
+
+    /// Guarda el binario en disco y registra la imagen con su checksum
+    pub async fn store(
+        &self,
+        input: &FirmwareUploadInput,
+        binary: &[u8],
+    ) -> anyhow::Result<FirmwareImage> {
+        let dir = PathBuf::from(&self.config.firmware_dir);
+        tokio::fs::create_dir_all(&dir).await?;
+
+        let sha256 = format!("{:x}", Sha256::digest(binary));
+        let file_name = format!("{}.bin", uuid::Uuid::new_v4());
+
+        // Se escribe con otro nombre y se renombra para no servir binarios incompletos
+        let partial = dir.join(format!("{}.part", file_name));
+        tokio::fs::write(&partial, binary).await?;
+        tokio::fs::rename(&partial, dir.join(&file_name)).await?;
+
+        let image = self
+            .db
+            .insert_firmware(input, &file_name, binary.len() as i64, &sha256, Utc::now())
+            .await?;
+
+        tracing::info!(
+            firmware_id = image.id,
+            version = %image.version,
+            size_bytes = image.size_bytes,
+            sha256 = %image.sha256,
+            "Imagen de firmware registrada"
+        );
+        Ok(image)
+    }
+
+    /// Elimina una imagen, su binario y el historial de su despliegue
+    pub async fn delete(&self, image: &FirmwareImage) -> anyhow::Result<()> {
+        self.db.delete_firmware(image.id).await?;
+
+        if let Err(e) = tokio::fs::remove_file(self.file_path(image)).await {
+            tracing::warn!(
+                firmware_id = image.id,
+                "No se pudo eliminar el binario: {}",
+                e
+            );
+        }
+        tracing::info!(firmware_id = image.id, version = %image.version, "Imagen de firmware eliminada");
+        Ok(())
+    }
+
+    /// Anuncia una imagen a los dispositivos y deja su actualización en pending
+    pub async fn rollout(
+        &self,
+        image: &FirmwareImage,
+        device_ids: &[String],
+    ) -> anyhow::Result<()> {
+        let now = Utc::now();
+
+        for device_id in device_ids {
+            self.db.start_rollout(image.id, device_id, now).await?;
+            self.announce(image, device_id);
+        }
+
+        tracing::info!(
+            firmware_id = image.id,
+            version = %image.version,
+            devices = device_ids.len(),
+            "Actualización de firmware anunciada"
+        );
+        Ok(())
+    }
+
+    /// Marca la descarga iniciada por un dispositivo con actualización pendiente
+    pub async fn mark_downloading(&self, firmware_id: i64, device_id: &str) -> anyhow::Result<()> {
+        let Some(rollout) = self.db.get_rollout(device_id, Some(firmware_id)).await? else {
+            return Ok(());
+        };
+
+        // Los reintentos de descarga tras un fallo también cuentan como progreso
+        if matches!(rollout.state, RolloutState::Pending | RolloutState::Failed) {
+            self.db
+                .set_rollout_state(
+                    firmware_id,
+                    device_id,
+                    RolloutState::Downloading,
+                    None,
+                    Utc::now(),
+                )
+                .await?;
+            tracing::info!(device_id = %device_id, firmware_id, "Descarga de firmware iniciada");
+        }
+        Ok(())
+    }
+
+    /// Registra el progreso informado por un dispositivo
+    pub async fn apply_status(&self, device_id: &str, payload: &[u8]) -> anyhow::Result<()> {
+        let report: OtaStatusReport = serde_json::from_slice(payload)?;
+
+        let Some(rollout) = self.db.get_rollout(device_id, report.firmware_id).await? else {
+            tracing::warn!(
+                device_id = %device_id,
+                firmware_id = ?report.firmware_id,
+                "Estado OTA sin actualización asociada"
+            );
+            return Ok(());
+        };
+
+        self.db
+            .set_rollout_state(
+                rollout.firmware_id,
+                device_id,
+                report.state,
+                report.error.as_deref(),
+                Utc::now(),
+            )
+            .await?;
+
+        match report.state {
+            RolloutState::Applied => {
+                tracing::info!(device_id = %device_id, firmware_id = rollout.firmware_id, "Firmware aplicado");
+                // Un anuncio retenido vacío evita que el nodo repita la actualización
+                self.clear_announcement(device_id);
+            }
+            RolloutState::Failed => tracing::warn!(
+                device_id = %device_id,
+                firmware_id = rollout.firmware_id,
+                error = ?report.error,
+                "El dispositivo no pudo aplicar el firmware"
+            ),
+            _ => tracing::debug!(
+                device_id = %device_id,
+                state = report.state.as_str(),
+                "Progreso OTA recibido"
+            ),
+        }
+        Ok(())
+    }
+
+    /// try_publish evita bloquear el loop MQTT, desde el que se procesan los reportes
+    fn announce(&self, image: &FirmwareImage, device_id: &str) {
+        let path = format!("{}?device_id={}", Self::download_path(image.id), device_id);
+        let url = match &self.config.firmware_base_url {
+            Some(base) => format!("{}{}", base, path),
+            None => path,
+        };
+
+        let payload = serde_json::json!({
+            "firmware_id": image.id,
+            "version": image.version,
+            "size_bytes": image.size_bytes,
+            "sha256": image.sha256,
+            "url": url,
+            "at": Utc::now(),
+        });
+
+        if let Err(e) = self.client.try_publish(
+            Self::topic(device_id),
+            QoS::AtLeastOnce,
+            true,
+            payload.to_string(),
+        ) {
+            tracing::warn!(device_id = %device_id, "No se pudo anunciar el firmware: {}", e);
+        }
+    }
+
+    fn clear_announcement(&self, device_id: &str) {
+        if let Err(e) =
+            self.client
+                .try_publish(Self::topic(device_id), QoS::AtLeastOnce, true, Vec::new())
+        {
+            tracing::warn!(device_id = %device_id, "No se pudo retirar el anuncio de firmware: {}", e);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use crate::config::Config;
+use crate::database::{Database, MetricFilter};
+use crate::models::{SensorDataInput, SensorHeader, SensorMetric};
+use crate::services::edge_processor::EdgeProcessor;
+use chrono::Utc;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Identificador de las lecturas sintéticas de consenso por ubicación
+pub const FUSION_DEVICE_ID: &str = "location-aggregate";
+
+/// Máximo de valores leídos por ventana de fusión
+const FUSION_MAX_POINTS: usize = 50_000;
+
+/// Último valor de cada dispositivo para una medición
+type DeviceValues = BTreeMap<String, f32>;
+
+/// Dispositivo cuyo valor diverge del consenso de su ubicación
+struct Outlier {
+    device_id: String,
+    measurement: String,
+    value: f32,
+    median: f32,
+}
+
+/// Fusión de sensores por ubicación
+/// Agrupa las lecturas de cada ubicación dentro de una ventana de tiempo,
+/// genera una lectura de consenso (mediana) y marca los dispositivos divergentes
+pub struct FusionService {
+    config: Arc<Config>,
+    db: Database,
+    edge_processor: Arc<EdgeProcessor>,
+    /// Tolerancia absoluta por medición (clave en minúsculas)
+    tolerances: HashMap<String, f32>,
+}
+
+impl FusionService {
+    pub fn new(config: Arc<Config>, db: Database, edge_processor: Arc<EdgeProcessor>) -> Self {
+        Self {
+            tolerances: config
+                .fusion_tolerances
+                .iter()
+                .map(|(measurement, tolerance)| (measurement.to_lowercase(), *tolerance))
+                .collect(),
+            config,
+            db,
+            edge_processor,
+        }
+    }
+
+    /// Fusiona la última ventana y almacena una lectura de consenso por ubicación
+    /// Retorna el número de lecturas sintéticas generadas
+    pub async fn run(&self) -> anyhow::Result<usize> {
+        let to = Utc::now();
+        let filter = MetricFilter {
+            device_ids: Vec::new(),
+            locations: Vec::new(),
+            tags: Vec::new(),
+            measurement: None,
+            from: to - chrono::Duration::seconds(self.config.fusion_window_secs as i64),
+            to,
+            quality: Default::default(),
+        };
+
+        let points = self.db.metric_range(&filter, FUSION_MAX_POINTS).await?;
+
+        // Último valor de cada dispositivo por ubicación y medición
+        let mut latest: BTreeMap<(String, String), DeviceValues> = BTreeMap::new();
+        for point in points {
+            if point.device_id == FUSION_DEVICE_ID {
+                continue;
+            }
+            if let Some(value) = point.value {
+                latest
+                    .entry((point.location, point.measurement))
+                    .or_default()
+                    .insert(point.device_id, value);
+            }
+        }
+
+        // Agrupar por ubicación
+        let mut locations: BTreeMap<String, Vec<(String, DeviceValues)>> = BTreeMap::new();
+        for ((location, measurement), devices) in latest {
+            // El consenso requiere al menos dos sensores
+            if devices.len() >= 2 {
+                locations
+                    .entry(location)
+                    .or_default()
+                    .push((measurement, devices));
+            }
+        }
+
+        let mut generated = 0;
+        for (location, measurements) in locations {
+            let mut metrics = Vec::with_capacity(measurements.len());
+            let mut outliers = Vec::new();
+            let mut peers = Vec::with_capacity(measurements.len());
+
+            for (measurement, devices) in measurements {
+                let median = median(devices.values().copied().collect());
+
+                // Con dos sensores no es posible decidir cuál diverge
+                if devices.len() >= 3 {
+                    let tolerance = self.tolerance(&measurement, median);
+                    for (device_id, value) in &devices {
+                        if (value - median).abs() > tolerance {
+                            outliers.push(Outlier {
+                                device_id: device_id.clone(),
+                                measurement: measurement.clone(),
+                                value: *value,
+                                median,
+                            });
+                        }
+                    }
+                }
+
+                peers.push((measurement.clone(), devices.len()));
+                metrics.push(SensorMetric {
+                    measurement,
+                    value: median,
+                    unit: None,
+                });
+            }
+
+            let input = SensorDataInput {
+                header: SensorHeader {
+                    user_uuid: None,
+                    device_id: FUSION_DEVICE_ID.to_string(),
+                    device_type: None,
+                    message_id: None,
+                    location: location.clone(),
+                    topic: FUSION_DEVICE_ID.to_string(),
+                    should_requeue: false,
+                    firmware_version: None,
+                    hardware_model: None,
+                    rssi: None,
+                    sequence: None,
+                    sent_at: None,
+                },
+                metrics,
+                device_timestamp: None,
+            };
+
+            let mut processed = self.edge_processor.process_reading(input).await;
+
+            for (measurement, count) in peers {
+                processed
+                    .computed
+                    .stats
+                    .insert(format!("{}_devices", measurement), count as f32);
+            }
+            processed
+                .computed
+                .stats
+                .insert("outlier_devices".to_string(), outliers.len() as f32);
+
+            for outlier in &outliers {
+                tracing::warn!(
+                    device_id = %outlier.device_id,
+                    location = %location,
+                    measurement = %outlier.measurement,
+                    value = outlier.value,
+                    median = outlier.median,
+                    "Dispositivo divergente respecto a su ubicación"
+                );
+                processed.quality.issues.push(format!(
+                    "Dispositivo divergente: {} ({} = {:.2}, mediana {:.2})",
+                    outlier.device_id, outlier.measurement, outlier.value, outlier.median
+                ));
+            }
+
+            self.db.insert_reading(&processed).await?;
+            generated += 1;
+        }
+
+        Ok(generated)
+    }
+
+    /// Tolerancia de la medición o, si no está configurada, un porcentaje de la mediana
+    fn tolerance(&self, measurement: &str, median: f32) -> f32 {
+        self.tolerances
+            .get(&measurement.to_lowercase())
+            .copied()
+            .unwrap_or(median.abs() * self.config.fusion_default_tolerance_pct / 100.0)
+    }
+
+    /// Tarea periódica de fusión por ubicación
+    pub async fn start_fusion_task(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+            self.config.fusion_window_secs,
+        ));
+        interval.tick().await;
+
+        tracing::info!(
+            window_secs = self.config.fusion_window_secs,
+            "Tarea de fusión de sensores por ubicación iniciada"
+        );
+
+        loop {
+            interval.tick().await;
+
+            match self.run().await {
+                Ok(generated) if generated > 0 => {
+                    tracing::debug!(readings = generated, "Lecturas de consenso generadas");
+                }
+                Ok(_) => {}
+                Err(e) => tracing::error!("Error en la fusión de sensores: {}", e),
+            }
+        }
+    }
+}
+
+fn median(mut values: Vec<f32>) -> f32 {
+    values.sort_by(f32::total_cmp);
+    let mid = values.len() / 2;
+
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
@@ -0,0 +1,125 @@
+use crate::config::Config;
+use crate::services::gpio::{GpioOutputState, GpioOutputs};
+use crate::services::host_metrics::{HostMetrics, HostSnapshot};
+use crate::services::lifecycle::{LifecycleReport, ProcessLifecycle};
+use crate::telemetry::{Telemetry, WriteBufferStats};
+use chrono::{DateTime, Utc};
+use rumqttc::{AsyncClient, LastWill, QoS};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Mensaje retenido con el estado del gateway
+#[derive(Debug, Serialize)]
+struct GatewayStatusMessage<'a> {
+    gateway_id: &'a str,
+    /// `online`, o `offline` en el last will publicado por el broker
+    status: &'static str,
+    version: &'static str,
+    environment: &'static str,
+    at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    host: Option<HostSnapshot>,
+    /// Tiempo en marcha, reinicios y tipo del último apagado
+    lifecycle: LifecycleReport,
+    /// Salidas GPIO y su estado
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    gpio: Vec<GpioOutputState>,
+    /// Búfer de escritura en SQLite y lecturas descartadas por saturación
+    write_buffer: WriteBufferStats,
+}
+
+/// Publica periódicamente el estado del gateway en `gateways/{gateway_id}/status`
+/// del broker local
+///
+/// El mensaje es retenido para que un cliente que se suscriba vea el último
+/// estado; si el gateway pierde la conexión, el broker publica el last will
+/// con `status: offline`.
+pub struct GatewayStatus {
+    config: Arc<Config>,
+    client: AsyncClient,
+    host_metrics: Arc<HostMetrics>,
+    lifecycle: Arc<ProcessLifecycle>,
+    gpio: Arc<GpioOutputs>,
+    telemetry: Arc<Telemetry>,
+}
+
+impl GatewayStatus {
+    pub fn new(
+        config: Arc<Config>,
+        client: AsyncClient,
+        host_metrics: Arc<HostMetrics>,
+        lifecycle: Arc<ProcessLifecycle>,
+        gpio: Arc<GpioOutputs>,
+        telemetry: Arc<Telemetry>,
+    ) -> Self {
+        Self {
+            config,
+            client,
+            host_metrics,
+            lifecycle,
+            gpio,
+            telemetry,
+        }
+    }
+
+    /// Topic de estado del gateway en el broker local
+    pub fn topic(config: &Config) -> String {
+        format!("gateways/{}/status", config.gateway_id)
+    }
+
+    /// Last will que el broker publica si el gateway se desconecta sin avisar
+    pub fn last_will(config: &Config) -> LastWill {
+        let payload = serde_json::json!({
+            "gateway_id": config.gateway_id,
+            "status": "offline",
+        });
+        LastWill::new(
+            Self::topic(config),
+            payload.to_string(),
+            QoS::AtLeastOnce,
+            true,
+        )
+    }
+
+    /// Tarea de publicación periódica del estado
+    pub async fn start_publish_task(self) {
+        let topic = Self::topic(&self.config);
+        let mut interval = tokio::time::interval(Duration::from_secs(
+            self.config.gateway_status_interval_secs,
+        ));
+        tracing::info!(topic = %topic, "Publicación del estado del gateway iniciada");
+
+        loop {
+            interval.tick().await;
+
+            let message = GatewayStatusMessage {
+                gateway_id: &self.config.gateway_id,
+                status: "online",
+                version: env!("CARGO_PKG_VERSION"),
+                environment: self.config.environment.as_str(),
+                at: Utc::now(),
+                host: self.host_metrics.latest(),
+                lifecycle: self.lifecycle.report(),
+                gpio: self.gpio.states(),
+                write_buffer: self.telemetry.write_buffer_stats(),
+            };
+
+            let payload = match serde_json::to_vec(&message) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    tracing::error!("Error serializando el estado del gateway: {}", e);
+                    continue;
+                }
+            };
+
+            // try_publish evita bloquear la tarea si el broker no está disponible
+            if let Err(e) = self
+                .client
+                .try_publish(&topic, QoS::AtLeastOnce, true, payload)
+            {
+                tracing::warn!("No se pudo publicar el estado del gateway: {}", e);
+            }
+        }
+    }
+}
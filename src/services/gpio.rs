@@ -0,0 +1,265 @@
+use crate::config::{Config, GpioOutput};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+/// Control manual de una salida
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GpioMode {
+    /// La salida sigue a las reglas
+    #[default]
+    Auto,
+    /// Encendida sin importar las reglas
+    On,
+    /// Apagada sin importar las reglas
+    Off,
+}
+
+/// Estado de una salida en `/metrics`, la API y el topic de estado del gateway
+#[derive(Debug, Clone, Serialize)]
+pub struct GpioOutputState {
+    pub name: String,
+    pub pin: u8,
+    pub active_low: bool,
+    /// Estado aplicado (encendido = relé activado)
+    pub on: bool,
+    pub mode: GpioMode,
+    /// Reglas activas que mantienen la salida encendida (`regla/dispositivo`)
+    pub held_by: Vec<String>,
+    /// El pin pudo abrirse; si no, el estado solo se registra
+    pub available: bool,
+    pub changed_at: DateTime<Utc>,
+}
+
+struct Output {
+    config: GpioOutput,
+    mode: GpioMode,
+    /// Reglas activas que la encienden, por regla y dispositivo
+    holders: HashSet<(String, String)>,
+    on: bool,
+    changed_at: DateTime<Utc>,
+    #[cfg(feature = "gpio")]
+    pin: Option<rppal::gpio::OutputPin>,
+}
+
+impl Output {
+    fn desired(&self) -> bool {
+        match self.mode {
+            GpioMode::Auto => !self.holders.is_empty(),
+            GpioMode::On => true,
+            GpioMode::Off => false,
+        }
+    }
+
+    /// Lleva el pin al estado deseado; `cause` describe el cambio en el log
+    fn apply(&mut self, cause: &str) {
+        let on = self.desired();
+        if on == self.on {
+            return;
+        }
+        self.on = on;
+        self.changed_at = Utc::now();
+
+        #[cfg(feature = "gpio")]
+        if let Some(pin) = &mut self.pin {
+            // Con active_low el relé se activa con nivel bajo
+            if on != self.config.active_low {
+                pin.set_high();
+            } else {
+                pin.set_low();
+            }
+        }
+        tracing::info!(
+            output = %self.config.name,
+            pin = self.config.pin,
+            cause = %cause,
+            "Salida GPIO {}",
+            if on { "encendida" } else { "apagada" }
+        );
+    }
+
+    fn state(&self) -> GpioOutputState {
+        let mut held_by: Vec<String> = self
+            .holders
+            .iter()
+            .map(|(rule_id, device_id)| format!("{}/{}", rule_id, device_id))
+            .collect();
+        held_by.sort();
+        GpioOutputState {
+            name: self.config.name.clone(),
+            pin: self.config.pin,
+            active_low: self.config.active_low,
+            on: self.on,
+            mode: self.mode,
+            held_by,
+            #[cfg(feature = "gpio")]
+            available: self.pin.is_some(),
+            #[cfg(not(feature = "gpio"))]
+            available: false,
+            changed_at: self.changed_at,
+        }
+    }
+}
+
+/// Salidas GPIO del gateway (relés de ventiladores, calefactores, sirenas)
+/// accionadas por la acción `gpio` de las reglas
+///
+/// Una salida está encendida mientras al menos una regla activa la mantenga,
+/// en cualquier dispositivo, salvo que un operador la fije en `on` u `off`
+/// desde la API. El control manual no se persiste: al reiniciar, todas las
+/// salidas arrancan apagadas y en `auto`.
+pub struct GpioOutputs {
+    outputs: Mutex<Vec<Output>>,
+}
+
+impl GpioOutputs {
+    /// Abre los pines de `GPIO_OUTPUTS` en estado apagado
+    ///
+    /// Si el GPIO no está disponible (otro equipo que no es una Raspberry Pi,
+    /// pin ocupado) el error se registra y la salida solo lleva su estado.
+    pub fn new(config: &Config) -> Self {
+        #[cfg(feature = "gpio")]
+        let gpio = if config.gpio_outputs.is_empty() {
+            None
+        } else {
+            match rppal::gpio::Gpio::new() {
+                Ok(gpio) => Some(gpio),
+                Err(e) => {
+                    tracing::error!(
+                        "No se pudo acceder al GPIO, las salidas no se accionarán: {}",
+                        e
+                    );
+                    None
+                }
+            }
+        };
+
+        let outputs = config
+            .gpio_outputs
+            .iter()
+            .map(|output| Output {
+                #[cfg(feature = "gpio")]
+                pin: gpio.as_ref().and_then(|gpio| match gpio.get(output.pin) {
+                    // Arranca en el nivel que deja el relé desactivado
+                    Ok(pin) if output.active_low => Some(pin.into_output_high()),
+                    Ok(pin) => Some(pin.into_output_low()),
+                    Err(e) => {
+                        tracing::error!(
+                            output = %output.name,
+                            pin = output.pin,
+                            "No se pudo abrir el pin de la salida GPIO: {}",
+                            e
+                        );
+                        None
+                    }
+                }),
+                config: output.clone(),
+                mode: GpioMode::Auto,
+                holders: HashSet::new(),
+                on: false,
+                changed_at: Utc::now(),
+            })
+            .collect();
+
+        if !config.gpio_outputs.is_empty() {
+            tracing::info!(
+                outputs = config.gpio_outputs.len(),
+                "Salidas GPIO inicializadas (apagadas)"
+            );
+        }
+        Self {
+            outputs: Mutex::new(outputs),
+        }
+    }
+
+    /// Indica si hay una salida con ese nombre
+    pub fn contains(&self, name: &str) -> bool {
+        self.outputs
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|output| output.config.name == name)
+    }
+
+    /// Registra que una regla se activó (`held`) o se resolvió en un dispositivo
+    pub fn set_rule_hold(
+        &self,
+        name: &str,
+        rule_id: &str,
+        device_id: &str,
+        held: bool,
+    ) -> anyhow::Result<()> {
+        let mut outputs = self.outputs.lock().unwrap();
+        let output = outputs
+            .iter_mut()
+            .find(|output| output.config.name == name)
+            .ok_or_else(|| anyhow::anyhow!("La salida GPIO {} no está configurada", name))?;
+
+        let key = (rule_id.to_string(), device_id.to_string());
+        if held {
+            output.holders.insert(key);
+        } else {
+            output.holders.remove(&key);
+        }
+        output.apply(&format!("regla {} en {}", rule_id, device_id));
+        Ok(())
+    }
+
+    /// Fija el control manual de una salida; None si no existe
+    pub fn set_mode(&self, name: &str, mode: GpioMode) -> Option<GpioOutputState> {
+        let mut outputs = self.outputs.lock().unwrap();
+        let output = outputs
+            .iter_mut()
+            .find(|output| output.config.name == name)?;
+        output.mode = mode;
+        output.apply("control manual");
+        Some(output.state())
+    }
+
+    /// Estado de todas las salidas, en el orden de `GPIO_OUTPUTS`
+    pub fn states(&self) -> Vec<GpioOutputState> {
+        self.outputs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(Output::state)
+            .collect()
+    }
+
+    /// Estado de las salidas en formato Prometheus (vacío sin salidas)
+    pub fn render_prometheus(&self) -> String {
+        let states = self.states();
+        let mut out = String::new();
+        if states.is_empty() {
+            return out;
+        }
+
+        out.push_str(
+            "# HELP gateway_gpio_output_on Salida GPIO encendida (1 = relé activado)\n\
+             # TYPE gateway_gpio_output_on gauge\n",
+        );
+        for state in &states {
+            let _ = writeln!(
+                out,
+                "gateway_gpio_output_on{{output=\"{}\",pin=\"{}\"}} {}",
+                state.name, state.pin, state.on as u8
+            );
+        }
+        out.push_str(
+            "# HELP gateway_gpio_output_manual Salida GPIO bajo control manual (1 = on u off fijado por un operador)\n\
+             # TYPE gateway_gpio_output_manual gauge\n",
+        );
+        for state in &states {
+            let _ = writeln!(
+                out,
+                "gateway_gpio_output_manual{{output=\"{}\"}} {}",
+                state.name,
+                (state.mode != GpioMode::Auto) as u8
+            );
+        }
+        out
+    }
+}
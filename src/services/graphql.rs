@@ -0,0 +1,565 @@
+use crate::database::{AlertFilter, Database, MetricFilter};
+use crate::models::{AlertRecord, DeviceRecord, MetricSummary, ProcessedSensorData};
+use async_graphql::{
+    Context, EmptyMutation, EmptySubscription, Json, Object, Request, Response, Result, Schema,
+    SchemaBuilder, ServerError, SimpleObject,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::Value;
+
+/// Anidamiento máximo de selecciones
+const MAX_DEPTH: usize = 16;
+
+/// Campos máximos por consulta (cada uno suma 1, alias incluidos)
+const MAX_COMPLEXITY: usize = 500;
+
+/// Anidamiento máximo de llaves, corchetes y paréntesis en el documento
+/// El parser es recursivo: sin este límite `{a{a{a…` agota la pila del worker
+/// antes de que se apliquen los límites del esquema
+const MAX_NESTING: usize = 64;
+
+/// Esquema GraphQL de consulta sobre los datos locales del gateway
+pub type GatewaySchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Construye el esquema; solo expone consultas (sin mutaciones ni suscripciones)
+pub fn schema(db: Database) -> GatewaySchema {
+    builder().data(db).finish()
+}
+
+/// Ejecuta una consulta, rechazando antes de parsearla los documentos
+/// anidados en exceso
+pub async fn execute(schema: &GatewaySchema, request: Request) -> Response {
+    if nesting(&request.query) > MAX_NESTING {
+        return Response::from_errors(vec![ServerError::new(
+            format!("La consulta supera el anidamiento máximo ({})", MAX_NESTING),
+            None,
+        )]);
+    }
+
+    schema.execute(request).await
+}
+
+/// Profundidad máxima de llaves, corchetes y paréntesis (también cuenta los
+/// que aparecen dentro de textos, lo que solo la sobreestima)
+fn nesting(query: &str) -> usize {
+    let mut depth = 0usize;
+    let mut max = 0;
+
+    for c in query.chars() {
+        match c {
+            '{' | '[' | '(' => {
+                depth += 1;
+                max = max.max(depth);
+            }
+            '}' | ']' | ')' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+
+    max
+}
+
+fn builder() -> SchemaBuilder<Query, EmptyMutation, EmptySubscription> {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+}
+
+/// Campos raíz: dispositivos, lecturas, agregados y alertas
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// Dispositivos registrados, filtrados por ubicación o etiqueta
+    async fn devices(
+        &self,
+        ctx: &Context<'_>,
+        location: Option<String>,
+        tag: Option<String>,
+    ) -> Result<Vec<Device>> {
+        let devices = ctx
+            .data::<Database>()?
+            .list_devices()
+            .await
+            .map_err(db_error)?;
+
+        Ok(devices
+            .into_iter()
+            .filter(|device| {
+                location.as_ref().is_none_or(|l| *l == device.location)
+                    && tag.as_ref().is_none_or(|t| device.tags.contains(t))
+            })
+            .map(Device)
+            .collect())
+    }
+
+    async fn device(&self, ctx: &Context<'_>, id: String) -> Result<Option<Device>> {
+        let device = ctx
+            .data::<Database>()?
+            .get_device(&id)
+            .await
+            .map_err(db_error)?;
+
+        Ok(device.map(Device))
+    }
+
+    /// Lecturas del periodo (por defecto, últimas 24 horas)
+    #[allow(clippy::too_many_arguments)]
+    async fn readings(
+        &self,
+        ctx: &Context<'_>,
+        device_id: Option<String>,
+        location: Option<String>,
+        tag: Option<String>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        #[graphql(default = 100, validator(minimum = 1, maximum = 1000))] limit: usize,
+        #[graphql(default)] anomalies_only: bool,
+    ) -> Result<Vec<Reading>> {
+        let filter = MetricFilter {
+            device_ids: device_id.into_iter().collect(),
+            locations: location.into_iter().collect(),
+            tags: tag.into_iter().collect(),
+            ..metric_filter(None, from, to)?
+        };
+
+        readings(ctx.data()?, &filter, limit, anomalies_only).await
+    }
+
+    /// Mínimo, máximo y promedio por medición en el periodo
+    #[allow(clippy::too_many_arguments)]
+    async fn aggregates(
+        &self,
+        ctx: &Context<'_>,
+        device_id: Option<String>,
+        location: Option<String>,
+        tag: Option<String>,
+        measurement: Option<String>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Aggregate>> {
+        let filter = MetricFilter {
+            device_ids: device_id.into_iter().collect(),
+            locations: location.into_iter().collect(),
+            tags: tag.into_iter().collect(),
+            ..metric_filter(measurement, from, to)?
+        };
+
+        aggregates(ctx.data()?, &filter).await
+    }
+
+    /// Historial de alertas
+    #[allow(clippy::too_many_arguments)]
+    async fn alerts(
+        &self,
+        ctx: &Context<'_>,
+        device_id: Option<String>,
+        tag: Option<String>,
+        rule_id: Option<String>,
+        #[graphql(default)] active: bool,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        #[graphql(default = 100, validator(minimum = 1, maximum = 1000))] limit: usize,
+    ) -> Result<Vec<Alert>> {
+        let filter = AlertFilter {
+            device_id,
+            tags: tag.into_iter().collect(),
+            rule_id,
+            active_only: active,
+            from,
+            to,
+        };
+
+        alerts(ctx.data()?, &filter, limit).await
+    }
+}
+
+/// Dispositivo del registro con sus lecturas, agregados y alertas
+pub struct Device(DeviceRecord);
+
+#[Object]
+impl Device {
+    async fn device_id(&self) -> &str {
+        &self.0.device_id
+    }
+
+    async fn location(&self) -> &str {
+        &self.0.location
+    }
+
+    async fn status(&self) -> &str {
+        self.0.status
+    }
+
+    async fn first_seen(&self) -> Option<DateTime<Utc>> {
+        self.0.first_seen
+    }
+
+    async fn last_seen(&self) -> Option<DateTime<Utc>> {
+        self.0.last_seen
+    }
+
+    async fn offline_since(&self) -> Option<DateTime<Utc>> {
+        self.0.offline_since
+    }
+
+    async fn provisioned_at(&self) -> Option<DateTime<Utc>> {
+        self.0.provisioned_at
+    }
+
+    async fn expected_location(&self) -> Option<&str> {
+        self.0.expected_location.as_deref()
+    }
+
+    async fn device_type(&self) -> Option<&str> {
+        self.0.device_type.as_deref()
+    }
+
+    async fn has_credentials(&self) -> bool {
+        self.0.has_credentials
+    }
+
+    async fn message_count(&self) -> i64 {
+        self.0.message_count
+    }
+
+    async fn last_quality(&self) -> Option<u8> {
+        self.0.last_quality
+    }
+
+    async fn battery(&self) -> Option<f32> {
+        self.0.battery
+    }
+
+    async fn rssi(&self) -> Option<i32> {
+        self.0.rssi
+    }
+
+    async fn firmware_version(&self) -> Option<&str> {
+        self.0.firmware_version.as_deref()
+    }
+
+    async fn hardware_model(&self) -> Option<&str> {
+        self.0.hardware_model.as_deref()
+    }
+
+    async fn tags(&self) -> &[String] {
+        &self.0.tags
+    }
+
+    async fn quarantined(&self) -> i64 {
+        self.0.quarantined
+    }
+
+    async fn decommissioned_at(&self) -> Option<DateTime<Utc>> {
+        self.0.decommissioned_at
+    }
+
+    async fn decommission_reason(&self) -> Option<&str> {
+        self.0.decommission_reason.as_deref()
+    }
+
+    async fn metadata(&self) -> Json<&Value> {
+        Json(&self.0.metadata)
+    }
+
+    /// Sin periodo ni `anomaliesOnly` devuelve las últimas lecturas (servidas desde la caché)
+    async fn readings(
+        &self,
+        ctx: &Context<'_>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        #[graphql(default = 100, validator(minimum = 1, maximum = 1000))] limit: usize,
+        #[graphql(default)] anomalies_only: bool,
+    ) -> Result<Vec<Reading>> {
+        let db = ctx.data::<Database>()?;
+
+        if from.is_none() && to.is_none() && !anomalies_only {
+            let recent = db
+                .get_recent_readings(&self.0.device_id, limit)
+                .await
+                .map_err(db_error)?;
+            return Ok(recent.into_iter().map(Reading).collect());
+        }
+
+        let filter = MetricFilter {
+            device_ids: vec![self.0.device_id.clone()],
+            ..metric_filter(None, from, to)?
+        };
+        readings(db, &filter, limit, anomalies_only).await
+    }
+
+    async fn aggregates(
+        &self,
+        ctx: &Context<'_>,
+        measurement: Option<String>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<Vec<Aggregate>> {
+        let filter = MetricFilter {
+            device_ids: vec![self.0.device_id.clone()],
+            ..metric_filter(measurement, from, to)?
+        };
+
+        aggregates(ctx.data()?, &filter).await
+    }
+
+    async fn alerts(
+        &self,
+        ctx: &Context<'_>,
+        rule_id: Option<String>,
+        #[graphql(default)] active: bool,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        #[graphql(default = 100, validator(minimum = 1, maximum = 1000))] limit: usize,
+    ) -> Result<Vec<Alert>> {
+        let filter = AlertFilter {
+            device_id: Some(self.0.device_id.clone()),
+            tags: Vec::new(),
+            rule_id,
+            active_only: active,
+            from,
+            to,
+        };
+
+        alerts(ctx.data()?, &filter, limit).await
+    }
+}
+
+/// Lectura procesada por el gateway
+pub struct Reading(ProcessedSensorData);
+
+#[Object]
+impl Reading {
+    async fn id(&self) -> String {
+        self.0.id.to_string()
+    }
+
+    async fn device_id(&self) -> &str {
+        &self.0.header.device_id
+    }
+
+    async fn location(&self) -> &str {
+        &self.0.header.location
+    }
+
+    async fn gateway_timestamp(&self) -> DateTime<Utc> {
+        self.0.gateway_timestamp
+    }
+
+    async fn measured_at(&self) -> DateTime<Utc> {
+        self.0.measured_at
+    }
+
+    async fn metrics(&self) -> Vec<Metric> {
+        self.0
+            .metrics
+            .iter()
+            .map(|metric| Metric {
+                measurement: metric.measurement.clone(),
+                value: metric.value,
+                unit: metric.unit.clone(),
+            })
+            .collect()
+    }
+
+    async fn is_anomaly(&self) -> bool {
+        self.0.computed.is_anomaly
+    }
+
+    async fn quality_score(&self) -> u8 {
+        self.0.quality.score
+    }
+
+    async fn quality_issues(&self) -> &[String] {
+        &self.0.quality.issues
+    }
+
+    /// Métricas calculadas en el gateway (índice de calor, punto de rocío, etc.)
+    async fn computed(&self) -> Json<&crate::models::ComputedMetrics> {
+        Json(&self.0.computed)
+    }
+
+    async fn metadata(&self) -> Json<&crate::models::ProcessedMetadata> {
+        Json(&self.0.metadata)
+    }
+}
+
+/// Valor de una medición en una lectura
+#[derive(SimpleObject)]
+pub struct Metric {
+    measurement: String,
+    value: f32,
+    unit: Option<String>,
+}
+
+/// Resumen de una medición en el periodo
+#[derive(SimpleObject)]
+pub struct Aggregate {
+    measurement: String,
+    min: f32,
+    max: f32,
+    avg: f32,
+    count: u32,
+}
+
+impl From<MetricSummary> for Aggregate {
+    fn from(summary: MetricSummary) -> Self {
+        Self {
+            measurement: summary.measurement,
+            min: summary.min,
+            max: summary.max,
+            avg: summary.avg,
+            count: summary.count,
+        }
+    }
+}
+
+/// Alerta del historial
+pub struct Alert(AlertRecord);
+
+#[Object]
+impl Alert {
+    async fn id(&self) -> i64 {
+        self.0.id
+    }
+
+    async fn rule_id(&self) -> &str {
+        &self.0.rule_id
+    }
+
+    async fn device_id(&self) -> &str {
+        &self.0.device_id
+    }
+
+    async fn metric(&self) -> &str {
+        &self.0.metric
+    }
+
+    async fn value(&self) -> f64 {
+        self.0.value
+    }
+
+    async fn severity(&self) -> &str {
+        self.0.severity.as_str()
+    }
+
+    async fn fired_at(&self) -> DateTime<Utc> {
+        self.0.fired_at
+    }
+
+    async fn resolved_at(&self) -> Option<DateTime<Utc>> {
+        self.0.resolved_at
+    }
+
+    async fn acked_by(&self) -> Option<&str> {
+        self.0.acked_by.as_deref()
+    }
+
+    async fn acked_at(&self) -> Option<DateTime<Utc>> {
+        self.0.acked_at
+    }
+
+    async fn ack_note(&self) -> Option<&str> {
+        self.0.ack_note.as_deref()
+    }
+}
+
+async fn readings(
+    db: &Database,
+    filter: &MetricFilter,
+    limit: usize,
+    anomalies_only: bool,
+) -> Result<Vec<Reading>> {
+    let readings = if anomalies_only {
+        db.get_anomalies(filter, limit).await
+    } else {
+        db.get_readings_page(filter, None, limit).await
+    }
+    .map_err(db_error)?;
+
+    Ok(readings.into_iter().map(Reading).collect())
+}
+
+async fn aggregates(db: &Database, filter: &MetricFilter) -> Result<Vec<Aggregate>> {
+    let mut summaries: Vec<_> = db
+        .metric_summary(filter)
+        .await
+        .map_err(db_error)?
+        .into_values()
+        .collect();
+    summaries.sort_by(|a, b| a.measurement.cmp(&b.measurement));
+
+    Ok(summaries.into_iter().map(Aggregate::from).collect())
+}
+
+async fn alerts(db: &Database, filter: &AlertFilter, limit: usize) -> Result<Vec<Alert>> {
+    let alerts = db.list_alerts(filter, limit).await.map_err(db_error)?;
+
+    Ok(alerts.into_iter().map(Alert).collect())
+}
+
+/// Construye el filtro de métricas del periodo (por defecto, últimas 24 horas)
+fn metric_filter(
+    measurement: Option<String>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<MetricFilter> {
+    let to = to.unwrap_or_else(Utc::now);
+    let from = from.unwrap_or(to - Duration::hours(24));
+
+    if from > to {
+        return Err("El argumento 'from' debe ser anterior a 'to'".into());
+    }
+
+    Ok(MetricFilter {
+        device_ids: Vec::new(),
+        locations: Vec::new(),
+        tags: Vec::new(),
+        measurement,
+        from,
+        to,
+        quality: Default::default(),
+    })
+}
+
+fn db_error(e: anyhow::Error) -> async_graphql::Error {
+    tracing::error!("Error en consulta GraphQL: {}", e);
+    "Error interno de base de datos".into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Ejecuta la consulta sin base de datos: solo se valida el documento
+    async fn validation_errors(query: &str) -> Vec<String> {
+        execute(&builder().finish(), Request::new(query))
+            .await
+            .errors
+            .into_iter()
+            .map(|e| e.message)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn rejects_limits_out_of_range() {
+        let errors = validation_errors("{ readings(limit: 0) { id } }").await;
+        assert!(!errors.is_empty());
+
+        let errors = validation_errors("{ alerts(limit: 1001) { id } }").await;
+        assert!(!errors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_deeply_nested_documents() {
+        let depth = 10_000;
+        let query = format!("{}{}", "{a".repeat(depth), "}".repeat(depth));
+
+        assert!(!validation_errors(&query).await.is_empty());
+
+        let depth = MAX_NESTING;
+        let query = format!("{}{}", "{a".repeat(depth), "}".repeat(depth));
+        assert!(!validation_errors(&query).await.is_empty());
+    }
+}
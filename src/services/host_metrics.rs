@@ -0,0 +1,253 @@
+use crate::config::Config;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use sysinfo::{Disks, System};
+
+/// Zonas térmicas del kernel (en la Raspberry Pi, `thermal_zone0` es el SoC)
+const THERMAL_DIR: &str = "/sys/class/thermal";
+
+/// Estado de throttling del firmware de la Raspberry Pi (mismo valor que `vcgencmd get_throttled`)
+const THROTTLED_PATH: &str = "/sys/devices/platform/soc/soc:firmware/get_throttled";
+
+/// Muestra de los recursos del host
+#[derive(Debug, Clone, Serialize)]
+pub struct HostSnapshot {
+    pub at: DateTime<Utc>,
+    /// Uso de CPU desde la muestra anterior (0-100)
+    pub cpu_usage_percent: f32,
+    /// Carga media a 1, 5 y 15 minutos
+    pub load_average: [f64; 3],
+    pub memory_total_bytes: u64,
+    pub memory_used_bytes: u64,
+    pub memory_available_bytes: u64,
+    pub swap_total_bytes: u64,
+    pub swap_used_bytes: u64,
+    /// Sistema de archivos donde está la base de datos
+    pub disk_mount: Option<String>,
+    pub disk_total_bytes: Option<u64>,
+    pub disk_available_bytes: Option<u64>,
+    /// Temperatura máxima de las zonas térmicas (SoC en la Raspberry Pi)
+    pub soc_temperature_c: Option<f32>,
+    /// Bits de `get_throttled`: 0 subtensión, 1 frecuencia limitada, 2 throttling,
+    /// 3 límite térmico; los bits 16-19 indican lo mismo desde el arranque
+    pub throttled: Option<u32>,
+    pub system_uptime_secs: u64,
+}
+
+/// Recolector periódico de CPU, memoria, disco y temperatura del gateway
+///
+/// El throttling térmico y las tarjetas SD llenas son los fallos de campo más
+/// frecuentes; la última muestra se expone en `/metrics` y en el estado del gateway.
+pub struct HostMetrics {
+    config: Arc<Config>,
+    system: Mutex<System>,
+    latest: RwLock<Option<HostSnapshot>>,
+}
+
+impl HostMetrics {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self {
+            config,
+            system: Mutex::new(System::new()),
+            latest: RwLock::new(None),
+        }
+    }
+
+    /// Última muestra tomada, si hay alguna
+    pub fn latest(&self) -> Option<HostSnapshot> {
+        self.latest.read().unwrap().clone()
+    }
+
+    /// Toma una muestra (E/S síncrona sobre /proc y /sys)
+    pub fn sample(&self) -> HostSnapshot {
+        let mut system = self.system.lock().unwrap();
+        system.refresh_cpu_usage();
+        system.refresh_memory();
+        let load = System::load_average();
+
+        let disk = self.database_disk();
+
+        let snapshot = HostSnapshot {
+            at: Utc::now(),
+            cpu_usage_percent: system.global_cpu_usage(),
+            load_average: [load.one, load.five, load.fifteen],
+            memory_total_bytes: system.total_memory(),
+            memory_used_bytes: system.used_memory(),
+            memory_available_bytes: system.available_memory(),
+            swap_total_bytes: system.total_swap(),
+            swap_used_bytes: system.used_swap(),
+            disk_mount: disk.as_ref().map(|(mount, _, _)| mount.clone()),
+            disk_total_bytes: disk.as_ref().map(|(_, total, _)| *total),
+            disk_available_bytes: disk.as_ref().map(|(_, _, available)| *available),
+            soc_temperature_c: read_soc_temperature(),
+            throttled: read_throttled(),
+            system_uptime_secs: System::uptime(),
+        };
+
+        *self.latest.write().unwrap() = Some(snapshot.clone());
+        snapshot
+    }
+
+    /// Tarea de muestreo periódico
+    pub async fn start_sampling_task(self: Arc<Self>) {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.host_metrics_interval_secs));
+        tracing::info!(
+            interval_secs = self.config.host_metrics_interval_secs,
+            "Muestreo de métricas del host iniciado"
+        );
+
+        loop {
+            interval.tick().await;
+
+            let metrics = self.clone();
+            match tokio::task::spawn_blocking(move || metrics.sample()).await {
+                Ok(snapshot) => {
+                    if let Some(temperature) = snapshot.soc_temperature_c
+                        && temperature >= self.config.host_temperature_warning_c
+                    {
+                        tracing::warn!(temperature, "Temperatura del SoC elevada");
+                    }
+                    if snapshot.throttled.is_some_and(|bits| bits & 0xF != 0) {
+                        tracing::warn!(
+                            throttled = ?snapshot.throttled,
+                            "La Raspberry Pi está limitando la frecuencia (subtensión o temperatura)"
+                        );
+                    }
+                }
+                Err(e) => tracing::error!("Error muestreando métricas del host: {}", e),
+            }
+        }
+    }
+
+    /// Punto de montaje, tamaño y espacio libre del disco de la base de datos
+    fn database_disk(&self) -> Option<(String, u64, u64)> {
+        let target = database_dir(&self.config.database_url);
+        let disks = Disks::new_with_refreshed_list();
+
+        // El montaje más específico que contiene el directorio de la base de datos
+        disks
+            .list()
+            .iter()
+            .filter(|disk| target.starts_with(disk.mount_point()))
+            .max_by_key(|disk| disk.mount_point().as_os_str().len())
+            .map(|disk| {
+                (
+                    disk.mount_point().display().to_string(),
+                    disk.total_space(),
+                    disk.available_space(),
+                )
+            })
+    }
+
+    /// Gauges en formato de texto de Prometheus a partir de la última muestra
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        let Some(snapshot) = self.latest() else {
+            return out;
+        };
+
+        let mut gauge = |name: &str, help: &str, value: f64| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} gauge", name);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+
+        gauge(
+            "gateway_host_cpu_usage_percent",
+            "Uso de CPU del host",
+            snapshot.cpu_usage_percent as f64,
+        );
+        gauge(
+            "gateway_host_load1",
+            "Carga media a 1 minuto",
+            snapshot.load_average[0],
+        );
+        gauge(
+            "gateway_host_memory_used_bytes",
+            "Memoria en uso",
+            snapshot.memory_used_bytes as f64,
+        );
+        gauge(
+            "gateway_host_memory_total_bytes",
+            "Memoria total",
+            snapshot.memory_total_bytes as f64,
+        );
+        if let (Some(total), Some(available)) =
+            (snapshot.disk_total_bytes, snapshot.disk_available_bytes)
+        {
+            gauge(
+                "gateway_host_disk_total_bytes",
+                "Tamaño del disco de la base de datos",
+                total as f64,
+            );
+            gauge(
+                "gateway_host_disk_available_bytes",
+                "Espacio libre en el disco de la base de datos",
+                available as f64,
+            );
+        }
+        if let Some(temperature) = snapshot.soc_temperature_c {
+            gauge(
+                "gateway_host_soc_temperature_celsius",
+                "Temperatura del SoC",
+                temperature as f64,
+            );
+        }
+        if let Some(throttled) = snapshot.throttled {
+            gauge(
+                "gateway_host_throttled",
+                "Bits de get_throttled del firmware",
+                throttled as f64,
+            );
+        }
+
+        out
+    }
+}
+
+/// Directorio de la base de datos; las bases en memoria se asocian a la raíz
+fn database_dir(url: &str) -> PathBuf {
+    let path = url
+        .strip_prefix("sqlite://")
+        .or_else(|| url.strip_prefix("sqlite:"))
+        .unwrap_or(url);
+    let path = path.split('?').next().unwrap_or_default();
+
+    if path.is_empty() || path.contains(":memory:") {
+        return PathBuf::from("/");
+    }
+
+    let path = Path::new(path);
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+    dir.unwrap_or(Path::new("."))
+        .canonicalize()
+        .unwrap_or_else(|_| PathBuf::from("/"))
+}
+
+/// Temperatura máxima de las zonas térmicas en °C
+fn read_soc_temperature() -> Option<f32> {
+    std::fs::read_dir(THERMAL_DIR)
+        .ok()?
+        .flatten()
+        .filter(|entry| {
+            entry
+                .file_name()
+                .to_string_lossy()
+                .starts_with("thermal_zone")
+        })
+        .filter_map(|entry| std::fs::read_to_string(entry.path().join("temp")).ok())
+        .filter_map(|millis| millis.trim().parse::<f32>().ok())
+        .map(|millis| millis / 1000.0)
+        .reduce(f32::max)
+}
+
+fn read_throttled() -> Option<u32> {
+    let value = std::fs::read_to_string(THROTTLED_PATH).ok()?;
+    let value = value.trim();
+    u32::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+}
@@ -0,0 +1,159 @@
+use crate::config::Config;
+use crate::database::{Database, ProcessRun};
+use crate::models::AlertSeverity;
+use crate::services::alerting::{AlertContext, Alerting};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Regla con la que se registran los bucles de reinicio en el historial de alertas
+pub const CRASH_LOOP_RULE: &str = "system:crash_loop";
+
+/// Ventana para contar reinicios y apagados abruptos
+const RESTART_WINDOW_HOURS: i64 = 24;
+
+/// Tiempo en marcha y reinicios del gateway
+#[derive(Debug, Clone, Serialize)]
+pub struct LifecycleReport {
+    pub started_at: DateTime<Utc>,
+    pub uptime_secs: u64,
+    /// Arranques anteriores en las últimas 24 horas
+    pub restarts_24h: usize,
+    /// Arranques de las últimas 24 horas que terminaron sin apagado limpio
+    pub unclean_shutdowns_24h: usize,
+    /// Si el arranque anterior terminó con un apagado limpio (None en el primer arranque)
+    pub previous_shutdown_clean: Option<bool>,
+    pub previous_started_at: Option<DateTime<Utc>>,
+    pub previous_stopped_at: Option<DateTime<Utc>>,
+    /// Versión del arranque anterior (distinta tras una actualización)
+    pub previous_version: Option<String>,
+    /// Se alcanzó `CRASH_LOOP_RESTARTS` apagados abruptos en 24 horas
+    pub crash_loop: bool,
+}
+
+/// Registra los arranques del proceso y los apagados limpios
+///
+/// Un arranque sin apagado limpio registrado indica que el proceso terminó
+/// abruptamente (corte de energía, OOM, pánico o watchdog), lo que ayuda a
+/// diagnosticar fuentes de alimentación inestables.
+pub struct ProcessLifecycle {
+    config: Arc<Config>,
+    db: Database,
+    run_id: i64,
+    started_at: DateTime<Utc>,
+    started: Instant,
+    /// Arranques anteriores dentro de la retención (más recientes primero)
+    previous_runs: Vec<ProcessRun>,
+}
+
+impl ProcessLifecycle {
+    /// Registra el arranque actual y evalúa los anteriores
+    pub async fn start(
+        config: Arc<Config>,
+        db: Database,
+        alerting: &Alerting,
+    ) -> anyhow::Result<Self> {
+        let started_at = Utc::now();
+        let run_id = db
+            .record_process_start(started_at, env!("CARGO_PKG_VERSION"))
+            .await?;
+        let previous_runs = db.previous_process_runs(run_id).await?;
+
+        let lifecycle = Self {
+            config,
+            db,
+            run_id,
+            started_at,
+            started: Instant::now(),
+            previous_runs,
+        };
+
+        let report = lifecycle.report();
+        if report.previous_shutdown_clean == Some(false) {
+            tracing::warn!(
+                previous_started_at = ?report.previous_started_at,
+                "El arranque anterior terminó sin apagado limpio (¿corte de energía?)"
+            );
+        }
+        tracing::info!(
+            restarts_24h = report.restarts_24h,
+            unclean_shutdowns_24h = report.unclean_shutdowns_24h,
+            "Arranque del gateway registrado"
+        );
+
+        lifecycle.evaluate_crash_loop(&report, alerting).await?;
+        Ok(lifecycle)
+    }
+
+    /// Estado actual de tiempo en marcha y reinicios
+    pub fn report(&self) -> LifecycleReport {
+        let window_start = Utc::now() - chrono::Duration::hours(RESTART_WINDOW_HOURS);
+        let recent = self
+            .previous_runs
+            .iter()
+            .filter(|run| run.started_at >= window_start);
+
+        let restarts_24h = recent.clone().count();
+        let unclean_shutdowns_24h = recent.filter(|run| run.stopped_at.is_none()).count();
+        let previous = self.previous_runs.first();
+        let threshold = self.config.crash_loop_restarts;
+
+        LifecycleReport {
+            started_at: self.started_at,
+            uptime_secs: self.started.elapsed().as_secs(),
+            restarts_24h,
+            unclean_shutdowns_24h,
+            previous_shutdown_clean: previous.map(|run| run.stopped_at.is_some()),
+            previous_started_at: previous.map(|run| run.started_at),
+            previous_stopped_at: previous.and_then(|run| run.stopped_at),
+            previous_version: previous.map(|run| run.version.clone()),
+            crash_loop: threshold > 0 && unclean_shutdowns_24h >= threshold,
+        }
+    }
+
+    /// Registra el apagado limpio del arranque actual
+    pub async fn mark_clean_shutdown(&self) {
+        match self.db.mark_clean_shutdown(self.run_id, Utc::now()).await {
+            Ok(()) => tracing::info!(
+                uptime_secs = self.started.elapsed().as_secs(),
+                "Apagado limpio registrado"
+            ),
+            Err(e) => tracing::error!("No se pudo registrar el apagado limpio: {}", e),
+        }
+    }
+
+    /// Registra una alerta crítica si el gateway está en un bucle de reinicios
+    async fn evaluate_crash_loop(
+        &self,
+        report: &LifecycleReport,
+        alerting: &Alerting,
+    ) -> anyhow::Result<()> {
+        let alert = AlertContext {
+            rule_id: CRASH_LOOP_RULE.to_string(),
+            rule_name: "Bucle de reinicios".to_string(),
+            severity: AlertSeverity::Critical,
+            device_id: self.config.gateway_id.clone(),
+            location: None,
+            metric: "unclean_shutdowns_24h".to_string(),
+            value: report.unclean_shutdowns_24h as f64,
+            condition: format!(
+                "{} o más apagados abruptos en 24 h",
+                self.config.crash_loop_restarts
+            ),
+            at: Utc::now(),
+        };
+
+        if report.crash_loop {
+            tracing::error!(
+                unclean_shutdowns_24h = report.unclean_shutdowns_24h,
+                "El gateway se reinicia repetidamente sin apagado limpio"
+            );
+            alerting.fire(&alert).await?;
+        } else {
+            alerting.resolve(&alert).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+use crate::database::Database;
+use crate::models::{SensorDataInput, SensorHeader};
+use crate::services::edge_processor::EdgeProcessor;
+use std::sync::Arc;
+
+/// Entrega al pipeline las lecturas que el propio gateway obtiene de equipos
+/// locales (Modbus...)
+///
+/// Siguen el mismo procesamiento que las recibidas por MQTT, sin autenticación
+/// ni cuarentena: el dispositivo está declarado en la configuración del gateway.
+#[derive(Clone)]
+pub struct LocalIngest {
+    db: Database,
+    edge_processor: Arc<EdgeProcessor>,
+}
+
+impl LocalIngest {
+    pub fn new(db: Database, edge_processor: Arc<EdgeProcessor>) -> Self {
+        Self { db, edge_processor }
+    }
+
+    /// Cabecera de una lectura obtenida por el gateway; el topic identifica el origen
+    pub fn header(
+        source: &str,
+        device_id: &str,
+        location: &str,
+        device_type: Option<&str>,
+    ) -> SensorHeader {
+        SensorHeader {
+            user_uuid: None,
+            device_id: device_id.to_string(),
+            device_type: device_type.map(str::to_string),
+            message_id: None,
+            location: location.to_string(),
+            topic: format!("{}/{}", source, device_id),
+            should_requeue: false,
+            firmware_version: None,
+            hardware_model: None,
+            rssi: None,
+            sequence: None,
+            sent_at: None,
+        }
+    }
+
+    /// Procesa y almacena una lectura; retorna false si se descartó
+    pub async fn submit(&self, input: SensorDataInput) -> anyhow::Result<bool> {
+        let device_id = input.header.device_id.clone();
+        if self.db.is_decommissioned(&device_id) {
+            tracing::debug!(device_id = %device_id, "Lectura local de dispositivo dado de baja descartada");
+            return Ok(false);
+        }
+
+        let Some(processed) = self.edge_processor.ingest_reading(input).await else {
+            tracing::debug!(device_id = %device_id, "Lectura local descartada por cuota");
+            return Ok(false);
+        };
+
+        if processed.computed.is_anomaly {
+            tracing::warn!(device_id = %device_id, topic = %processed.header.topic, "Anomalía detectada");
+        }
+
+        self.db.insert_reading(&processed).await?;
+        Ok(true)
+    }
+}
@@ -0,0 +1,250 @@
+use crate::config::Config;
+use crate::models::ProcessedSensorData;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Réplica de las lecturas procesadas en una base de series temporales local
+/// (InfluxDB 1.x/2.x o VictoriaMetrics) para los tableros Grafana del sitio
+///
+/// Cada lectura se escribe en line protocol con precisión de nanosegundos:
+/// tags `gateway_id`, `device_id`, `location` y `device_type`; un campo por
+/// métrica más los valores calculados, `quality_score` e `is_anomaly`. SQLite
+/// sigue siendo la fuente de verdad de la sincronización y la retención: si la
+/// base local no responde, las lecturas esperan en memoria hasta
+/// `LOCAL_SINK_MAX_BUFFER` y luego se descartan las más antiguas.
+pub struct LocalSink {
+    url: String,
+    token: Option<String>,
+    measurement: String,
+    gateway_id: String,
+    client: reqwest::Client,
+    batch_size: usize,
+    flush_interval: Duration,
+    max_buffer: usize,
+    /// Líneas pendientes de escribir, en orden de llegada
+    pending: VecDeque<String>,
+    dropped: u64,
+    failing: bool,
+}
+
+impl LocalSink {
+    pub fn new(config: &Config, url: &str) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.local_sink_timeout_secs))
+            .user_agent(concat!("env_edge_gateway_rpi/", env!("CARGO_PKG_VERSION")))
+            .build()?;
+
+        Ok(Self {
+            url: url.to_string(),
+            token: config.local_sink_token.clone(),
+            measurement: escape(&config.local_sink_measurement, &[',', ' ']),
+            gateway_id: escape(&config.gateway_id, &[',', '=', ' ']),
+            client,
+            batch_size: config.local_sink_batch_size,
+            flush_interval: Duration::from_secs(config.local_sink_flush_secs),
+            max_buffer: config.local_sink_max_buffer,
+            pending: VecDeque::new(),
+            dropped: 0,
+            failing: false,
+        })
+    }
+
+    /// Replica las lecturas hasta que se cierre el canal
+    pub async fn run(mut self, mut readings: broadcast::Receiver<Arc<ProcessedSensorData>>) {
+        tracing::info!(
+            url = %self.url,
+            batch_size = self.batch_size,
+            "Réplica de lecturas en la base de series temporales local iniciada"
+        );
+
+        let mut interval = tokio::time::interval(self.flush_interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                reading = readings.recv() => match reading {
+                    Ok(data) => {
+                        self.push(&data);
+                        // Con la base caída solo se reintenta en cada intervalo
+                        if self.pending.len() >= self.batch_size && !self.failing {
+                            self.flush().await;
+                        }
+                    }
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!(
+                            skipped,
+                            "La réplica local no siguió el ritmo de las lecturas; se omitieron lecturas"
+                        );
+                    }
+                    Err(RecvError::Closed) => {
+                        self.flush().await;
+                        return;
+                    }
+                },
+                _ = interval.tick() => self.flush().await,
+            }
+        }
+    }
+
+    fn push(&mut self, data: &ProcessedSensorData) {
+        let Some(line) = self.line(data) else {
+            return;
+        };
+        if self.pending.len() >= self.max_buffer {
+            self.pending.pop_front();
+            self.dropped += 1;
+            // Se avisa al llenarse y luego cada 1000 descartes
+            if self.dropped % 1000 == 1 {
+                tracing::warn!(
+                    dropped = self.dropped,
+                    max_buffer = self.max_buffer,
+                    "Réplica local llena: se descartan las lecturas pendientes más antiguas"
+                );
+            }
+        }
+        self.pending.push_back(line);
+    }
+
+    /// Línea de la lectura; None si no tiene ningún valor representable
+    fn line(&self, data: &ProcessedSensorData) -> Option<String> {
+        let tag = |value: &str| escape(value, &[',', '=', ' ']);
+
+        let mut fields = Vec::new();
+        for metric in &data.metrics {
+            if metric.value.is_finite() {
+                fields.push(format!("{}={}", tag(&metric.measurement), metric.value));
+            }
+        }
+        if fields.is_empty() {
+            return None;
+        }
+
+        let computed = &data.computed;
+        for (name, value) in [
+            ("heat_index", computed.heat_index),
+            ("dew_point", computed.dew_point),
+            ("comfort_level", computed.comfort_level),
+            ("absolute_humidity", computed.absolute_humidity),
+            ("vapor_pressure_deficit", computed.vapor_pressure_deficit),
+            ("wet_bulb", computed.wet_bulb),
+            ("air_quality_index", computed.air_quality_index),
+            ("ventilation_score", computed.ventilation_score),
+        ] {
+            if let Some(value) = value.filter(|value| value.is_finite()) {
+                fields.push(format!("{}={}", name, value));
+            }
+        }
+        fields.push(format!("quality_score={}i", data.quality.score));
+        fields.push(format!("is_anomaly={}", computed.is_anomaly));
+
+        let mut line = format!(
+            "{},gateway_id={},device_id={},location={}",
+            self.measurement,
+            self.gateway_id,
+            tag(&data.header.device_id),
+            tag(&data.header.location)
+        );
+        // InfluxDB rechaza tags vacíos
+        if let Some(device_type) = data.header.device_type.as_deref().filter(|t| !t.is_empty()) {
+            let _ = write!(line, ",device_type={}", tag(device_type));
+        }
+        let _ = write!(line, " {}", fields.join(","));
+
+        // Momento de la medición (hora del dispositivo corregida o, si no, la de recepción)
+        if let Some(nanos) = data.measured_at.timestamp_nanos_opt() {
+            let _ = write!(line, " {}", nanos);
+        }
+        Some(line)
+    }
+
+    /// Escribe las líneas pendientes por lotes; si falla, las conserva para el
+    /// siguiente intervalo
+    async fn flush(&mut self) {
+        while !self.pending.is_empty() {
+            let count = self.pending.len().min(self.batch_size);
+            let body = self
+                .pending
+                .range(..count)
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            let mut request = self.client.post(&self.url).body(body);
+            if let Some(token) = &self.token {
+                request =
+                    request.header(reqwest::header::AUTHORIZATION, format!("Token {}", token));
+            }
+            let error = match request.send().await {
+                Ok(response) if response.status().is_success() => None,
+                Ok(response) => {
+                    let status = response.status();
+                    let detail = response.text().await.unwrap_or_default();
+                    // Un 4xx (salvo 408/429) no mejora reintentando: el lote se descarta
+                    if status.is_client_error()
+                        && status != reqwest::StatusCode::REQUEST_TIMEOUT
+                        && status != reqwest::StatusCode::TOO_MANY_REQUESTS
+                    {
+                        tracing::error!(
+                            status = status.as_u16(),
+                            lines = count,
+                            "La base local rechazó el lote, se descarta: {}",
+                            detail.trim()
+                        );
+                        self.pending.drain(..count);
+                        continue;
+                    }
+                    Some(format!("HTTP {}: {}", status.as_u16(), detail.trim()))
+                }
+                Err(e) => Some(e.to_string()),
+            };
+
+            match error {
+                None => {
+                    self.pending.drain(..count);
+                    if self.failing {
+                        tracing::info!("Réplica local recuperada");
+                        self.failing = false;
+                    }
+                }
+                Some(error) => {
+                    // Una base apagada fallaría en cada intervalo: solo se avisa al empezar
+                    if self.failing {
+                        tracing::debug!(
+                            pending = self.pending.len(),
+                            "Escritura en la base local fallida: {}",
+                            error
+                        );
+                    } else {
+                        tracing::warn!(
+                            url = %self.url,
+                            pending = self.pending.len(),
+                            "Escritura en la base local fallida: {}",
+                            error
+                        );
+                        self.failing = true;
+                    }
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Escapa los caracteres especiales del line protocol; los saltos de línea se
+/// reemplazan porque separan registros
+fn escape(value: &str, special: &[char]) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\n' | '\r' => escaped.push_str("\\ "),
+            c if special.contains(&c) => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
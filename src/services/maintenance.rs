@@ -0,0 +1,136 @@
+use crate::config::Config;
+use crate::database::Database;
+use crate::services::runtime_config::RuntimeSettings;
+use crate::services::scheduling::duration_until_local_hour;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{RwLock, watch};
+
+/// Resultado de la última ejecución de mantenimiento
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceReport {
+    pub started_at: DateTime<Utc>,
+    pub duration_ms: u64,
+    pub deleted_readings: u64,
+    pub deleted_alerts: u64,
+    pub vacuum_mode: String,
+    pub size_before_bytes: i64,
+    pub size_after_bytes: i64,
+    pub integrity_ok: bool,
+    pub integrity_issues: Vec<String>,
+}
+
+/// Servicio de mantenimiento de la base de datos
+/// Aplica la retención de datos, compacta el archivo y verifica su integridad
+pub struct MaintenanceService {
+    config: Arc<Config>,
+    db: Database,
+    /// Días de retención vigentes (modificables en tiempo de ejecución)
+    settings: watch::Receiver<RuntimeSettings>,
+    last_report: RwLock<Option<MaintenanceReport>>,
+}
+
+impl MaintenanceService {
+    pub fn new(
+        config: Arc<Config>,
+        db: Database,
+        settings: watch::Receiver<RuntimeSettings>,
+    ) -> Self {
+        Self {
+            config,
+            db,
+            settings,
+            last_report: RwLock::new(None),
+        }
+    }
+
+    /// Último reporte de mantenimiento (si ya se ejecutó)
+    pub async fn last_report(&self) -> Option<MaintenanceReport> {
+        self.last_report.read().await.clone()
+    }
+
+    /// Ejecuta un ciclo completo de mantenimiento
+    pub async fn run(&self) -> anyhow::Result<MaintenanceReport> {
+        let started_at = Utc::now();
+        let timer = Instant::now();
+
+        let size_before_bytes = self.db.database_size_bytes().await?;
+        let retention = self.settings.borrow().clone();
+
+        let deleted_readings = self
+            .db
+            .cleanup_old_synced(retention.data_retention_days)
+            .await?;
+
+        let deleted_alerts = self
+            .db
+            .cleanup_old_alerts(retention.alert_retention_days)
+            .await?;
+
+        let vacuum_mode = self.db.vacuum().await?;
+        let size_after_bytes = self.db.database_size_bytes().await?;
+
+        let integrity_issues = self.db.quick_check().await?;
+
+        let report = MaintenanceReport {
+            started_at,
+            duration_ms: timer.elapsed().as_millis() as u64,
+            deleted_readings,
+            deleted_alerts,
+            vacuum_mode: vacuum_mode.to_string(),
+            size_before_bytes,
+            size_after_bytes,
+            integrity_ok: integrity_issues.is_empty(),
+            integrity_issues,
+        };
+
+        if report.integrity_ok {
+            tracing::info!(
+                deleted = report.deleted_readings,
+                deleted_alerts = report.deleted_alerts,
+                vacuum = %report.vacuum_mode,
+                freed_bytes = report.size_before_bytes - report.size_after_bytes,
+                duration_ms = report.duration_ms,
+                "Mantenimiento de base de datos completado"
+            );
+        } else {
+            tracing::error!(
+                issues = ?report.integrity_issues,
+                "quick_check detectó problemas de integridad en la base de datos"
+            );
+        }
+
+        *self.last_report.write().await = Some(report.clone());
+        Ok(report)
+    }
+
+    /// Tarea periódica de mantenimiento
+    /// Se ejecuta en la hora de baja actividad configurada, respetando la cadencia
+    pub async fn start_maintenance_task(self: Arc<Self>) {
+        let interval = chrono::Duration::hours(self.config.maintenance_interval_hours as i64);
+
+        tracing::info!(
+            interval_hours = self.config.maintenance_interval_hours,
+            hour = self.config.maintenance_hour,
+            "Tarea de mantenimiento de base de datos iniciada"
+        );
+
+        loop {
+            tokio::time::sleep(duration_until_local_hour(self.config.maintenance_hour)).await;
+
+            // Tolerancia de una hora para no saltar una ventana por desfase del sleep
+            let due = match self.last_report().await {
+                Some(report) => {
+                    Utc::now() - report.started_at >= interval - chrono::Duration::hours(1)
+                }
+                None => true,
+            };
+
+            if due && let Err(e) = self.run().await {
+                tracing::error!("Error en mantenimiento de base de datos: {}", e);
+            }
+        }
+    }
+}
@@ -1,4 +1,40 @@
 // Módulo de servicios de negocio
+pub mod alerting;
+pub mod backlog_watchdog;
+pub mod backup;
+#[cfg(feature = "ble")]
+pub mod ble;
 pub mod cloud_sync;
+pub mod connection;
+pub mod device_overrides;
+pub mod device_presence;
+pub mod device_quotas;
+pub mod device_twin;
 pub mod edge_processor;
-pub mod mqtt_handler;
\ No newline at end of file
+pub mod export;
+pub mod firmware;
+pub mod fusion;
+pub mod gateway_status;
+pub mod gpio;
+pub mod graphql;
+pub mod host_metrics;
+pub mod lifecycle;
+pub mod local_ingest;
+pub mod local_sink;
+pub mod maintenance;
+pub mod modbus;
+pub mod mqtt_handler;
+pub mod notifications;
+pub mod onboard_sensors;
+#[cfg(feature = "opcua")]
+pub mod opcua;
+pub mod remote_config;
+pub mod rule_actions;
+pub mod runtime_config;
+pub mod scheduling;
+pub mod self_health;
+pub mod sensor_health;
+pub mod serial;
+pub mod snmp;
+pub mod time_sync;
+pub mod webhook_mappings;
@@ -0,0 +1,471 @@
+use crate::config::Config;
+use crate::models::{SensorDataInput, SensorMetric};
+use crate::services::local_ingest::LocalIngest;
+use anyhow::Context as _;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio_modbus::client::{Context, Reader, rtu, tcp};
+use tokio_modbus::prelude::{Slave, SlaveContext};
+use tokio_serial::{DataBits, Parity, SerialStream, StopBits};
+
+/// Origen de las lecturas Modbus en el topic (`modbus/<device_id>`)
+const SOURCE: &str = "modbus";
+
+/// Archivo `MODBUS_DEVICES_PATH`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ModbusFile {
+    devices: Vec<ModbusDevice>,
+}
+
+/// Equipo Modbus que se publica como un dispositivo del gateway
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ModbusDevice {
+    device_id: String,
+    location: String,
+    #[serde(default)]
+    device_type: Option<String>,
+    connection: Connection,
+    /// Dirección del esclavo (unit id)
+    #[serde(default = "default_unit_id")]
+    unit_id: u8,
+    #[serde(default)]
+    poll_secs: Option<u64>,
+    registers: Vec<Register>,
+}
+
+fn default_unit_id() -> u8 {
+    1
+}
+
+/// Conexión con el equipo: TCP (`host:puerto`) o RTU por un puerto serie
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "lowercase", deny_unknown_fields)]
+enum Connection {
+    Tcp(String),
+    Rtu(SerialSettings),
+}
+
+impl Connection {
+    /// Los equipos de un mismo bus RS-485 o de la misma dirección TCP comparten conexión
+    fn key(&self) -> String {
+        match self {
+            Connection::Tcp(address) => format!("tcp://{}", address),
+            Connection::Rtu(serial) => format!("rtu://{}", serial.port),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SerialSettings {
+    port: String,
+    #[serde(default = "default_baud_rate")]
+    baud_rate: u32,
+    #[serde(default)]
+    parity: SerialParity,
+    #[serde(default = "default_data_bits")]
+    data_bits: u8,
+    #[serde(default = "default_stop_bits")]
+    stop_bits: u8,
+}
+
+fn default_baud_rate() -> u32 {
+    9600
+}
+
+fn default_data_bits() -> u8 {
+    8
+}
+
+fn default_stop_bits() -> u8 {
+    1
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SerialParity {
+    #[default]
+    None,
+    Even,
+    Odd,
+}
+
+/// Registro leído como una métrica (`valor * scale + offset`)
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Register {
+    measurement: String,
+    address: u16,
+    #[serde(default)]
+    kind: RegisterKind,
+    #[serde(default)]
+    data_type: DataType,
+    /// Orden de los bytes dentro de cada registro de 16 bits
+    #[serde(default)]
+    byte_order: Endianness,
+    /// Orden de los registros en los tipos de 32 y 64 bits
+    #[serde(default)]
+    word_order: Endianness,
+    #[serde(default = "default_scale")]
+    scale: f64,
+    #[serde(default)]
+    offset: f64,
+    #[serde(default)]
+    unit: Option<String>,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum RegisterKind {
+    #[default]
+    Holding,
+    Input,
+    Coil,
+    Discrete,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum DataType {
+    #[default]
+    U16,
+    I16,
+    U32,
+    I32,
+    U64,
+    I64,
+    F32,
+    F64,
+}
+
+impl DataType {
+    /// Registros de 16 bits que ocupa el valor
+    fn words(self) -> u16 {
+        match self {
+            DataType::U16 | DataType::I16 => 1,
+            DataType::U32 | DataType::I32 | DataType::F32 => 2,
+            DataType::U64 | DataType::I64 | DataType::F64 => 4,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Endianness {
+    #[default]
+    Big,
+    Little,
+}
+
+impl Register {
+    /// Lee el registro; el error interno es la excepción Modbus del equipo
+    async fn read(&self, context: &mut Context) -> tokio_modbus::Result<f64> {
+        let words = match self.kind {
+            RegisterKind::Coil => {
+                let bits = context.read_coils(self.address, 1).await?;
+                return Ok(bits.map(|bits| self.scaled(bits.first().copied().unwrap_or(false))));
+            }
+            RegisterKind::Discrete => {
+                let bits = context.read_discrete_inputs(self.address, 1).await?;
+                return Ok(bits.map(|bits| self.scaled(bits.first().copied().unwrap_or(false))));
+            }
+            RegisterKind::Holding => {
+                context
+                    .read_holding_registers(self.address, self.data_type.words())
+                    .await?
+            }
+            RegisterKind::Input => {
+                context
+                    .read_input_registers(self.address, self.data_type.words())
+                    .await?
+            }
+        };
+
+        Ok(words.map(|words| self.decode(&words)))
+    }
+
+    fn scaled(&self, bit: bool) -> f64 {
+        f64::from(u8::from(bit)) * self.scale + self.offset
+    }
+
+    /// Interpreta los registros según el tipo y el orden configurados
+    fn decode(&self, words: &[u16]) -> f64 {
+        let count = self.data_type.words() as usize;
+        if words.len() < count {
+            return f64::NAN;
+        }
+
+        let mut ordered = words[..count].to_vec();
+        if self.word_order == Endianness::Little {
+            ordered.reverse();
+        }
+        let mut bytes = [0u8; 8];
+        for (index, word) in ordered.iter().enumerate() {
+            let word = if self.byte_order == Endianness::Little {
+                word.swap_bytes()
+            } else {
+                *word
+            };
+            bytes[index * 2..index * 2 + 2].copy_from_slice(&word.to_be_bytes());
+        }
+
+        let raw = match self.data_type {
+            DataType::U16 => u16::from_be_bytes([bytes[0], bytes[1]]) as f64,
+            DataType::I16 => i16::from_be_bytes([bytes[0], bytes[1]]) as f64,
+            DataType::U32 => u32::from_be_bytes(bytes[..4].try_into().unwrap()) as f64,
+            DataType::I32 => i32::from_be_bytes(bytes[..4].try_into().unwrap()) as f64,
+            DataType::F32 => f32::from_be_bytes(bytes[..4].try_into().unwrap()) as f64,
+            DataType::U64 => u64::from_be_bytes(bytes) as f64,
+            DataType::I64 => i64::from_be_bytes(bytes) as f64,
+            DataType::F64 => f64::from_be_bytes(bytes),
+        };
+        raw * self.scale + self.offset
+    }
+}
+
+/// Conexión compartida por los equipos de un mismo bus o dirección
+struct Bus {
+    connection: Connection,
+    /// Se abre en la primera consulta y se descarta tras un error de transporte
+    context: Mutex<Option<Context>>,
+}
+
+impl Bus {
+    async fn connect(&self, timeout: Duration) -> anyhow::Result<Context> {
+        match &self.connection {
+            Connection::Tcp(address) => {
+                let address = tokio::net::lookup_host(address)
+                    .await?
+                    .next()
+                    .with_context(|| format!("dirección no resuelta: {}", address))?;
+                let context = tokio::time::timeout(timeout, tcp::connect(address))
+                    .await
+                    .with_context(|| format!("sin conexión con {}", address))??;
+                Ok(context)
+            }
+            Connection::Rtu(serial) => {
+                let builder = tokio_serial::new(&serial.port, serial.baud_rate)
+                    .parity(match serial.parity {
+                        SerialParity::None => Parity::None,
+                        SerialParity::Even => Parity::Even,
+                        SerialParity::Odd => Parity::Odd,
+                    })
+                    .data_bits(match serial.data_bits {
+                        7 => DataBits::Seven,
+                        _ => DataBits::Eight,
+                    })
+                    .stop_bits(match serial.stop_bits {
+                        2 => StopBits::Two,
+                        _ => StopBits::One,
+                    });
+                let stream = SerialStream::open(&builder)
+                    .with_context(|| format!("no se pudo abrir {}", serial.port))?;
+                Ok(rtu::attach(stream))
+            }
+        }
+    }
+}
+
+/// Consulta periódicamente los registros de equipos Modbus TCP/RTU (medidores,
+/// variadores...) y los inyecta en el pipeline como lecturas de dispositivos
+///
+/// Cada equipo de `MODBUS_DEVICES_PATH` se consulta cada `poll_secs` (o
+/// `MODBUS_POLL_SECS`) segundos. Los equipos de un mismo puerto serie o de la
+/// misma dirección TCP (pasarelas Modbus) comparten la conexión y se consultan
+/// de a uno.
+pub struct ModbusPoller {
+    config: Arc<Config>,
+    ingest: LocalIngest,
+    devices: Vec<Arc<ModbusDevice>>,
+}
+
+impl ModbusPoller {
+    /// Lee y valida el archivo de equipos
+    pub fn load(config: Arc<Config>, ingest: LocalIngest, path: &str) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("MODBUS_DEVICES_PATH: no se pudo leer {}", path))?;
+        let file: ModbusFile = serde_json::from_str(&content)
+            .with_context(|| format!("MODBUS_DEVICES_PATH: {} no es válido", path))?;
+
+        let mut device_ids = HashSet::new();
+        for device in &file.devices {
+            anyhow::ensure!(
+                !device.device_id.is_empty() && !device.location.is_empty(),
+                "MODBUS_DEVICES_PATH: cada equipo necesita device_id y location"
+            );
+            anyhow::ensure!(
+                device_ids.insert(device.device_id.as_str()),
+                "MODBUS_DEVICES_PATH: device_id repetido: {}",
+                device.device_id
+            );
+            anyhow::ensure!(
+                !device.registers.is_empty(),
+                "MODBUS_DEVICES_PATH: {} no tiene registros",
+                device.device_id
+            );
+            anyhow::ensure!(
+                device.poll_secs != Some(0),
+                "MODBUS_DEVICES_PATH: poll_secs de {} debe ser al menos 1",
+                device.device_id
+            );
+            if let Connection::Rtu(serial) = &device.connection {
+                anyhow::ensure!(
+                    matches!(serial.data_bits, 7 | 8) && matches!(serial.stop_bits, 1 | 2),
+                    "MODBUS_DEVICES_PATH: {} admite data_bits 7 u 8 y stop_bits 1 o 2",
+                    device.device_id
+                );
+            }
+        }
+
+        Ok(Self {
+            config,
+            ingest,
+            devices: file.devices.into_iter().map(Arc::new).collect(),
+        })
+    }
+
+    /// Inicia una tarea de consulta por equipo
+    pub fn start(self) {
+        let mut buses: HashMap<String, Arc<Bus>> = HashMap::new();
+        tracing::info!(
+            devices = self.devices.len(),
+            "Consulta de equipos Modbus iniciada"
+        );
+
+        for device in self.devices {
+            let bus = buses
+                .entry(device.connection.key())
+                .or_insert_with(|| {
+                    Arc::new(Bus {
+                        connection: device.connection.clone(),
+                        context: Mutex::new(None),
+                    })
+                })
+                .clone();
+            tokio::spawn(poll_task(
+                self.config.clone(),
+                self.ingest.clone(),
+                bus,
+                device,
+            ));
+        }
+    }
+}
+
+async fn poll_task(
+    config: Arc<Config>,
+    ingest: LocalIngest,
+    bus: Arc<Bus>,
+    device: Arc<ModbusDevice>,
+) {
+    let timeout = Duration::from_millis(config.modbus_timeout_ms);
+    let mut interval = tokio::time::interval(Duration::from_secs(
+        device.poll_secs.unwrap_or(config.modbus_poll_secs),
+    ));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut failing = false;
+
+    loop {
+        interval.tick().await;
+
+        let metrics = match poll(&bus, &device, timeout).await {
+            Ok(metrics) => {
+                if failing {
+                    tracing::info!(device_id = %device.device_id, "Equipo Modbus recuperado");
+                    failing = false;
+                }
+                metrics
+            }
+            Err(e) => {
+                // Un equipo apagado fallaría en cada consulta: solo se avisa al empezar
+                if failing {
+                    tracing::debug!(device_id = %device.device_id, "Consulta Modbus fallida: {:#}", e);
+                } else {
+                    tracing::warn!(device_id = %device.device_id, "Consulta Modbus fallida: {:#}", e);
+                    failing = true;
+                }
+                continue;
+            }
+        };
+        if metrics.is_empty() {
+            continue;
+        }
+
+        let input = SensorDataInput {
+            header: LocalIngest::header(
+                SOURCE,
+                &device.device_id,
+                &device.location,
+                device.device_type.as_deref(),
+            ),
+            metrics,
+            device_timestamp: None,
+        };
+        if let Err(e) = ingest.submit(input).await {
+            tracing::error!(device_id = %device.device_id, "Error almacenando la lectura Modbus: {}", e);
+        }
+    }
+}
+
+/// Lee los registros del equipo; una excepción Modbus omite solo ese registro
+async fn poll(
+    bus: &Bus,
+    device: &ModbusDevice,
+    timeout: Duration,
+) -> anyhow::Result<Vec<SensorMetric>> {
+    let mut context = bus.context.lock().await;
+    let active = match context.as_mut() {
+        Some(active) => active,
+        None => context.insert(bus.connect(timeout).await?),
+    };
+    active.set_slave(Slave(device.unit_id));
+
+    let mut metrics = Vec::with_capacity(device.registers.len());
+    for register in &device.registers {
+        let result = match tokio::time::timeout(timeout, register.read(active)).await {
+            Ok(Ok(result)) => result,
+            // Tras un timeout o un error de transporte la conexión puede quedar
+            // desincronizada: se vuelve a abrir en la próxima consulta
+            Ok(Err(e)) => {
+                *context = None;
+                return Err(e.into());
+            }
+            Err(_) => {
+                *context = None;
+                anyhow::bail!(
+                    "sin respuesta de la unidad {} al leer {} ({})",
+                    device.unit_id,
+                    register.address,
+                    register.measurement
+                );
+            }
+        };
+
+        match result {
+            Ok(value) if value.is_finite() => metrics.push(SensorMetric {
+                measurement: register.measurement.clone(),
+                value: value as f32,
+                unit: register.unit.clone(),
+            }),
+            Ok(_) => {}
+            Err(exception) => tracing::debug!(
+                device_id = %device.device_id,
+                address = register.address,
+                measurement = %register.measurement,
+                "Excepción Modbus: {}",
+                exception
+            ),
+        }
+    }
+
+    Ok(metrics)
+}
@@ -1,22 +1,85 @@
-use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+use chrono::Utc;
+use rumqttc::{AsyncClient, Event, EventLoop, MqttOptions, Packet, QoS};
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::sync::Arc;
 use std::time::Duration;
+use tokio::sync::mpsc::{self, error::TrySendError};
 use tokio::task::JoinHandle;
 
 use crate::{
-    config::Config, database::Database, models::SensorDataInput, services::cloud_sync::CloudSync,
-    services::edge_processor::EdgeProcessor,
+    config::Config, config::QueueOverflow, database::Database, models::SensorDataInput,
+    services::cloud_sync::CloudSyncHandle, services::connection::ConnectionStatus,
+    services::device_twin::DeviceTwins, services::edge_processor::EdgeProcessor,
+    services::firmware::FirmwareService, services::gateway_status::GatewayStatus,
+    services::runtime_config::RuntimeSettings, services::time_sync::TimeSync, telemetry::Telemetry,
 };
-use tokio::sync::Mutex;
+use tokio::sync::watch;
+
+/// Tiempo máximo para procesar los mensajes encolados al apagar
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Espera máxima por lugar en la cola con `MQTT_QUEUE_OVERFLOW=block`: muy por
+/// debajo del keep-alive (60 s) para que el eventloop siga enviando PINGREQ
+const BLOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Tareas del handler MQTT en marcha
+pub struct MqttTask {
+    /// Loop de recepción
+    receiver: JoinHandle<()>,
+    /// Pide al loop de recepción que deje de leer del broker
+    stop: watch::Sender<bool>,
+    /// Tareas que procesan las colas
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl MqttTask {
+    /// Espera a que el loop de recepción termine, lo que solo ocurre si falla
+    pub async fn finished(&mut self) {
+        let _ = (&mut self.receiver).await;
+    }
+
+    /// Deja de recibir mensajes y espera a que se procesen los ya encolados
+    ///
+    /// El broker ya confirmó esos mensajes (QoS 1): descartarlos al apagar los
+    /// perdería sin que el nodo los reenvíe. Retorna `false` si quedaron
+    /// mensajes sin procesar.
+    pub async fn shutdown(self) -> bool {
+        let _ = self.stop.send(true);
+        let drain = async {
+            if !self.receiver.is_finished() {
+                let _ = self.receiver.await;
+            }
+            for worker in self.workers {
+                let _ = worker.await;
+            }
+        };
+        if tokio::time::timeout(DRAIN_TIMEOUT, drain).await.is_err() {
+            tracing::warn!("Quedaron mensajes MQTT sin procesar al apagar");
+            return false;
+        }
+        true
+    }
+}
 
 /// Handler MQTT para recibir datos de sensores ESP32
 /// Los sensores publican en topics: sensors/{device_id}/data
 pub struct MqttHandler {
-    client: AsyncClient,
     config: Arc<Config>,
+    client: AsyncClient,
+    eventloop: EventLoop,
     db: Database,
     edge_processor: Arc<EdgeProcessor>,
-    cloud_sync: Arc<Mutex<CloudSync>>,
+    cloud_sync: CloudSyncHandle,
+    settings: watch::Receiver<RuntimeSettings>,
+    /// Conectividad real con el broker local (para los health checks)
+    status: Arc<ConnectionStatus>,
+    telemetry: Arc<Telemetry>,
+    /// Device twins (consume `sensors/+/reported`)
+    twins: Arc<DeviceTwins>,
+    /// Actualizaciones de firmware (consume `sensors/+/ota/status`)
+    firmware: Arc<FirmwareService>,
+    /// Sincronización horaria (consume `sensors/+/time/request`)
+    time_sync: Arc<TimeSync>,
 }
 
 impl MqttHandler {
@@ -25,7 +88,10 @@ impl MqttHandler {
         config: Arc<Config>,
         db: Database,
         edge_processor: Arc<EdgeProcessor>,
-        cloud_sync: Arc<Mutex<CloudSync>>,
+        cloud_sync: CloudSyncHandle,
+        settings: watch::Receiver<RuntimeSettings>,
+        status: Arc<ConnectionStatus>,
+        telemetry: Arc<Telemetry>,
     ) -> anyhow::Result<Self> {
         // Configurar opciones MQTT
         let mut mqttoptions = MqttOptions::new(
@@ -42,8 +108,13 @@ impl MqttHandler {
             mqttoptions.set_credentials(username, password);
         }
 
+        // El broker marca el gateway como offline si se pierde la conexión
+        if config.gateway_status_interval_secs > 0 {
+            mqttoptions.set_last_will(GatewayStatus::last_will(&config));
+        }
+
         // Crear cliente async
-        let (client, mut _eventloop) = AsyncClient::new(mqttoptions, 100);
+        let (client, eventloop) = AsyncClient::new(mqttoptions, 100);
 
         tracing::info!(
             broker = %config.mqtt_broker_host,
@@ -58,69 +129,132 @@ impl MqttHandler {
         client
             .subscribe("sensors/+/batch", QoS::AtLeastOnce)
             .await?;
+        // sensors/+/reported - Estado reportado para el device twin
+        client
+            .subscribe("sensors/+/reported", QoS::AtLeastOnce)
+            .await?;
+        // sensors/+/ota/status - Progreso de las actualizaciones de firmware
+        client
+            .subscribe("sensors/+/ota/status", QoS::AtLeastOnce)
+            .await?;
 
-        tracing::info!("Suscrito a topics: sensors/+/data, sensors/+/batch");
+        // sensors/+/time/request - Solicitudes de hora de los nodos sin RTC
+        client
+            .subscribe("sensors/+/time/request", QoS::AtLeastOnce)
+            .await?;
+
+        tracing::info!(
+            "Suscrito a topics: sensors/+/data, sensors/+/batch, sensors/+/reported, sensors/+/ota/status, sensors/+/time/request"
+        );
+
+        let twins = Arc::new(DeviceTwins::new(db.clone(), client.clone()));
+        let firmware = Arc::new(FirmwareService::new(
+            config.clone(),
+            db.clone(),
+            client.clone(),
+        ));
+        let time_sync = Arc::new(TimeSync::new(
+            config.clone(),
+            client.clone(),
+            edge_processor.clone(),
+        ));
 
         Ok(Self {
-            client,
             config,
+            client,
+            eventloop,
             db,
             edge_processor,
             cloud_sync,
+            settings,
+            status,
+            telemetry,
+            twins,
+            firmware,
+            time_sync,
         })
     }
 
-    /// Inicia el loop de procesamiento de mensajes MQTT
-    pub async fn start(self) -> JoinHandle<()> {
-        let (client, mut eventloop) = {
-            let mut mqttoptions = MqttOptions::new(
-                &self.config.mqtt_client_id,
-                &self.config.mqtt_broker_host,
-                self.config.mqtt_broker_port,
-            );
-
-            mqttoptions.set_keep_alive(Duration::from_secs(60));
-            mqttoptions.set_max_packet_size(1024 * 1024, 1024 * 1024);
+    /// Cliente MQTT del broker local (para publicar desde otros servicios)
+    pub fn client(&self) -> AsyncClient {
+        self.client.clone()
+    }
 
-            if let (Some(username), Some(password)) =
-                (&self.config.mqtt_username, &self.config.mqtt_password)
-            {
-                mqttoptions.set_credentials(username, password);
-            }
+    /// Device twins, compartidos con la API HTTP
+    pub fn twins(&self) -> Arc<DeviceTwins> {
+        self.twins.clone()
+    }
 
-            AsyncClient::new(mqttoptions, 100)
-        };
+    /// Servicio de firmware OTA, compartido con la API HTTP
+    pub fn firmware(&self) -> Arc<FirmwareService> {
+        self.firmware.clone()
+    }
 
-        // Suscribirse
-        let subscribe_client = client.clone();
-        tokio::spawn(async move {
-            if let Err(e) = subscribe_client
-                .subscribe("sensors/+/data", QoS::AtLeastOnce)
-                .await
-            {
-                tracing::error!("Error suscribiéndose a sensors/+/data: {}", e);
-            }
-            if let Err(e) = subscribe_client
-                .subscribe("sensors/+/batch", QoS::AtLeastOnce)
-                .await
-            {
-                tracing::error!("Error suscribiéndose a sensors/+/batch: {}", e);
-            }
-        });
+    /// Sincronización horaria, para lanzar la baliza periódica
+    pub fn time_sync(&self) -> Arc<TimeSync> {
+        self.time_sync.clone()
+    }
 
-        let db = self.db.clone();
-        let edge_processor = self.edge_processor.clone();
-        let cloud_sync = self.cloud_sync.clone();
-        let config = self.config.clone();
+    /// Inicia el loop de recepción de mensajes MQTT y las tareas que los procesan
+    ///
+    /// El eventloop solo recibe: cada publicación pasa por una cola acotada a
+    /// una de las `MQTT_WORKERS` tareas, elegida por dispositivo para conservar
+    /// el orden de sus lecturas. Así una escritura lenta en SQLite no retrasa
+    /// los keep-alive y el broker no corta la conexión.
+    pub async fn start(self) -> MqttTask {
+        let mut eventloop = self.eventloop;
+        let status = self.status.clone();
+        let telemetry = self.telemetry.clone();
+        let overflow = self.config.mqtt_queue_overflow;
+
+        let worker = MessageWorker {
+            client: self.client.clone(),
+            db: self.db.clone(),
+            edge_processor: self.edge_processor.clone(),
+            cloud_sync: self.cloud_sync.clone(),
+            settings: self.settings.clone(),
+            telemetry: self.telemetry.clone(),
+            twins: self.twins.clone(),
+            firmware: self.firmware.clone(),
+            time_sync: self.time_sync.clone(),
+        };
+        let per_worker = self.config.mqtt_queue_capacity / self.config.mqtt_workers;
+        let (queues, workers): (Vec<mpsc::Sender<MqttMessage>>, Vec<JoinHandle<()>>) =
+            (0..self.config.mqtt_workers)
+                .map(|_| {
+                    let (tx, rx) = mpsc::channel(per_worker);
+                    (tx, tokio::spawn(worker.clone().run(rx)))
+                })
+                .unzip();
+        telemetry.set_mqtt_queue_capacity(per_worker * queues.len());
+
+        if let Some(paused) = self.db.ingest_pause() {
+            tokio::spawn(Self::follow_ingest_pause(self.client.clone(), paused));
+        }
 
-        tokio::spawn(async move {
-            tracing::info!("MQTT Handler iniciado, escuchando mensajes...");
+        let (stop, mut stopped) = watch::channel(false);
+        let receiver = tokio::spawn(async move {
+            tracing::info!(
+                workers = queues.len(),
+                "MQTT Handler iniciado, escuchando mensajes..."
+            );
 
+            // Al salir se sueltan las colas: cada tarea termina tras vaciar la suya
             loop {
-                match eventloop.poll().await {
+                let notification = tokio::select! {
+                    biased;
+                    _ = stopped.wait_for(|stop| *stop) => break,
+                    notification = eventloop.poll() => notification,
+                };
+                match notification {
                     Ok(notification) => {
+                        if matches!(notification, Event::Incoming(Packet::ConnAck(_))) {
+                            status.set_connected();
+                            tracing::info!("Conectado al broker MQTT local");
+                        }
+
                         if let Event::Incoming(Packet::Publish(publish)) = notification {
-                            let topic = publish.topic.clone();
+                            let topic = publish.topic;
                             let payload = publish.payload.to_vec();
 
                             tracing::debug!(
@@ -129,57 +263,157 @@ impl MqttHandler {
                                 "Mensaje MQTT recibido"
                             );
 
-                            // Procesar mensaje
-                            if let Err(e) = Self::process_message(
-                                &topic,
-                                &payload,
-                                db.clone(),
-                                edge_processor.clone(),
-                                cloud_sync.clone(),
-                                config.clone(),
-                                client.clone(),
-                            )
-                            .await
-                            {
-                                tracing::error!(
-                                    topic = %topic,
-                                    error = %e,
-                                    "Error procesando mensaje MQTT"
-                                );
+                            let pattern = Self::topic_pattern(&topic);
+                            let device_id = topic
+                                .split('/')
+                                .nth(1)
+                                .filter(|_| pattern != "other")
+                                .map(String::from);
+                            if let Some(device_id) = &device_id {
+                                telemetry.device_message(device_id, payload.len());
                             }
+
+                            let message = MqttMessage {
+                                topic,
+                                payload,
+                                pattern,
+                                device_id,
+                            };
+                            Self::enqueue(&queues, overflow, &telemetry, message).await;
                         }
                     }
                     Err(e) => {
+                        status.set_disconnected(&e);
                         tracing::error!("Error en MQTT eventloop: {}", e);
                         tokio::time::sleep(Duration::from_secs(5)).await;
                     }
                 }
             }
-        })
+        });
+
+        MqttTask {
+            receiver,
+            stop,
+            workers,
+        }
+    }
+
+    /// Deja de recibir lecturas mientras el búfer de escritura en SQLite está
+    /// saturado (`DB_WRITE_OVERLOAD=pause-subscriptions`); los demás topics
+    /// siguen suscritos
+    async fn follow_ingest_pause(client: AsyncClient, mut paused: watch::Receiver<bool>) {
+        while paused.changed().await.is_ok() {
+            let pause = *paused.borrow_and_update();
+            for topic in ["sensors/+/data", "sensors/+/batch"] {
+                let result = if pause {
+                    client.unsubscribe(topic).await
+                } else {
+                    client.subscribe(topic, QoS::AtLeastOnce).await
+                };
+                if let Err(e) = result {
+                    tracing::error!(
+                        topic,
+                        "No se pudo {} el topic de lecturas: {}",
+                        if pause { "pausar" } else { "reanudar" },
+                        e
+                    );
+                }
+            }
+        }
     }
 
-    /// Procesa un mensaje MQTT recibido
+    /// Entrega el mensaje a la tarea de su dispositivo; con la cola llena lo
+    /// descarta o espera (hasta `BLOCK_TIMEOUT`) según `MQTT_QUEUE_OVERFLOW`
+    async fn enqueue(
+        queues: &[mpsc::Sender<MqttMessage>],
+        overflow: QueueOverflow,
+        telemetry: &Telemetry,
+        message: MqttMessage,
+    ) {
+        // Los topics sin dispositivo se reparten por el topic completo
+        let key = message.device_id.as_deref().unwrap_or(&message.topic);
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let queue = &queues[(hasher.finish() % queues.len() as u64) as usize];
+
+        let permit = match overflow {
+            QueueOverflow::Block => {
+                match tokio::time::timeout(BLOCK_TIMEOUT, queue.reserve()).await {
+                    Ok(permit) => permit.ok(),
+                    Err(_) => {
+                        let dropped = telemetry.mqtt_queue_drop(message.pattern);
+                        tracing::warn!(
+                            topic = %message.topic,
+                            dropped,
+                            "Cola de mensajes MQTT llena durante {} s: mensaje descartado",
+                            BLOCK_TIMEOUT.as_secs()
+                        );
+                        return;
+                    }
+                }
+            }
+            QueueOverflow::Drop => match queue.try_reserve() {
+                Ok(permit) => Some(permit),
+                Err(TrySendError::Full(())) => {
+                    let dropped = telemetry.mqtt_queue_drop(message.pattern);
+                    // Se avisa al llenarse y luego cada 1000 descartes
+                    if dropped % 1000 == 1 {
+                        tracing::warn!(
+                            topic = %message.topic,
+                            dropped,
+                            "Cola de mensajes MQTT llena: se descartan mensajes"
+                        );
+                    }
+                    return;
+                }
+                Err(TrySendError::Closed(())) => None,
+            },
+        };
+        let Some(permit) = permit else {
+            tracing::error!(
+                topic = %message.topic,
+                "La tarea de procesamiento MQTT terminó; mensaje descartado"
+            );
+            return;
+        };
+        telemetry.mqtt_queue_add(1);
+        permit.send(message);
+    }
+
+    /// Patrón suscrito al que pertenece un topic (etiqueta de las métricas)
+    fn topic_pattern(topic: &str) -> &'static str {
+        match topic.split('/').collect::<Vec<_>>().as_slice() {
+            ["sensors", _, "data"] => "sensors/+/data",
+            ["sensors", _, "batch"] => "sensors/+/batch",
+            ["sensors", _, "reported"] => "sensors/+/reported",
+            ["sensors", _, "ota", "status"] => "sensors/+/ota/status",
+            ["sensors", _, "time", "request"] => "sensors/+/time/request",
+            _ => "other",
+        }
+    }
+
+    /// Procesa un mensaje MQTT recibido y devuelve su resultado para las métricas
     async fn process_message(
         topic: &str,
         payload: &[u8],
         db: Database,
         edge_processor: Arc<EdgeProcessor>,
-        cloud_sync: Arc<Mutex<CloudSync>>,
-        config: Arc<Config>,
+        cloud_sync: CloudSyncHandle,
+        settings: watch::Receiver<RuntimeSettings>,
         client: AsyncClient,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<&'static str> {
         // Parsear topic para obtener device_id y tipo
         let parts: Vec<&str> = topic.split('/').collect();
 
         if parts.len() < 3 {
             tracing::warn!("Topic inválido: {}", topic);
-            return Ok(());
+            return Ok("ignored");
         }
 
         let device_id = parts[1];
         let message_type = parts[2]; // "data" o "batch"
 
-        match message_type {
+        let outcome = match message_type {
             "data" => {
                 Self::process_single_data(
                     device_id,
@@ -187,10 +421,10 @@ impl MqttHandler {
                     db.clone(),
                     edge_processor.clone(),
                     cloud_sync.clone(),
-                    config.clone(),
+                    settings.clone(),
                     client.clone(),
                 )
-                .await?;
+                .await?
             }
             "batch" => {
                 Self::process_batch_data(
@@ -199,17 +433,18 @@ impl MqttHandler {
                     db.clone(),
                     edge_processor.clone(),
                     cloud_sync.clone(),
-                    config.clone(),
+                    settings.clone(),
                     client.clone(),
                 )
-                .await?;
+                .await?
             }
             _ => {
                 tracing::warn!("Tipo de mensaje desconocido: {}", message_type);
+                "ignored"
             }
-        }
+        };
 
-        Ok(())
+        Ok(outcome)
     }
 
     /// Procesa un dato individual
@@ -218,16 +453,41 @@ impl MqttHandler {
         payload: &[u8],
         db: Database,
         edge_processor: Arc<EdgeProcessor>,
-        cloud_sync: Arc<Mutex<CloudSync>>,
-        config: Arc<Config>,
+        cloud_sync: CloudSyncHandle,
+        settings: watch::Receiver<RuntimeSettings>,
         client: AsyncClient,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<&'static str> {
         // Deserializar payload JSON con el nuevo formato
         let mut input: SensorDataInput = serde_json::from_slice(payload)?;
 
         // Asegurar que el device_id del header coincida con el topic
         input.header.device_id = device_id.to_string();
 
+        if db.is_decommissioned(device_id) {
+            tracing::debug!(device_id = %device_id, "Lectura de dispositivo dado de baja rechazada vía MQTT");
+            return Ok("rejected");
+        }
+
+        if db.is_duplicate(&input.header).await? {
+            tracing::debug!(
+                device_id = %device_id,
+                message_id = ?input.header.message_id,
+                "Mensaje duplicado descartado vía MQTT"
+            );
+            return Ok("duplicate");
+        }
+
+        if db.check_replay(&input.header, Utc::now()).is_err() {
+            return Ok("replayed");
+        }
+
+        // Los dispositivos desconocidos esperan aprobación con sus lecturas en cuarentena
+        let (mut approved, _) = db.quarantine_unapproved(vec![input], Utc::now()).await?;
+        let Some(input) = approved.pop() else {
+            tracing::debug!(device_id = %device_id, "Lectura en cuarentena vía MQTT");
+            return Ok("quarantined");
+        };
+
         tracing::info!(
             device_id = %device_id,
             location = %input.header.location,
@@ -235,8 +495,11 @@ impl MqttHandler {
             "Dato recibido vía MQTT"
         );
 
-        // Procesar con edge computing
-        let processed = edge_processor.process_reading(input).await;
+        // Procesar con edge computing, salvo que la cuota del dispositivo la descarte
+        let Some(processed) = edge_processor.ingest_reading(input).await else {
+            tracing::debug!(device_id = %device_id, "Lectura descartada por cuota vía MQTT");
+            return Ok("throttled");
+        };
 
         if processed.computed.is_anomaly {
             tracing::warn!(
@@ -253,6 +516,7 @@ impl MqttHandler {
         let response_payload = serde_json::json!({
             "id": processed.id,
             "gateway_timestamp": processed.gateway_timestamp,
+            "measured_at": processed.measured_at,
             "computed_metrics": processed.computed,
             "quality_score": processed.quality.score,
             "quality_issues": processed.quality.issues,
@@ -271,17 +535,12 @@ impl MqttHandler {
 
         // Verificar si es necesario sincronizar
         let pending_count = db.count_pending_sync().await?;
-        if pending_count >= config.cloud_sync_batch_size as i64 {
+        if pending_count >= settings.borrow().cloud_sync_batch_size as i64 {
             tracing::info!("Iniciando sincronización con cloud");
-            tokio::spawn(async move {
-                let mut cs = cloud_sync.lock().await;
-                if let Err(e) = cs.sync_data(db).await {
-                    tracing::error!("Error en sincronización: {}", e);
-                }
-            });
+            cloud_sync.sync_now();
         }
 
-        Ok(())
+        Ok("processed")
     }
 
     /// Procesa un batch de datos
@@ -290,10 +549,10 @@ impl MqttHandler {
         payload: &[u8],
         db: Database,
         edge_processor: Arc<EdgeProcessor>,
-        cloud_sync: Arc<Mutex<CloudSync>>,
-        config: Arc<Config>,
+        cloud_sync: CloudSyncHandle,
+        settings: watch::Receiver<RuntimeSettings>,
         client: AsyncClient,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<&'static str> {
         // Deserializar batch
         #[derive(serde::Deserialize)]
         struct BatchPayload {
@@ -307,15 +566,23 @@ impl MqttHandler {
             reading.header.device_id = device_id.to_string();
         }
 
-        let batch_size = batch.readings.len();
         tracing::info!(
             device_id = %device_id,
-            batch_size = batch_size,
+            batch_size = batch.readings.len(),
             "Batch recibido vía MQTT"
         );
 
-        // Procesar batch
-        let processed_batch = edge_processor.process_batch(batch.readings).await;
+        // Descartar lecturas ya almacenadas
+        let (readings, rejected) = db.reject_decommissioned(batch.readings);
+        let (readings, duplicates) = db.filter_duplicates(readings).await?;
+        let (readings, replayed) = db.reject_replayed(readings, Utc::now());
+        let (readings, quarantined) = db.quarantine_unapproved(readings, Utc::now()).await?;
+        let received = readings.len();
+
+        // Procesar batch (sin las lecturas descartadas por cuota)
+        let processed_batch = edge_processor.process_batch(readings).await;
+        let batch_size = processed_batch.len();
+        let throttled = received - batch_size;
 
         // Estadísticas
         let mut anomalies = 0;
@@ -340,6 +607,11 @@ impl MqttHandler {
         tracing::info!(
             device_id = %device_id,
             processed = batch_size,
+            duplicates = duplicates,
+            rejected = rejected,
+            replayed = replayed,
+            quarantined = quarantined,
+            throttled = throttled,
             anomalies = anomalies,
             avg_quality = %avg_quality,
             "Batch procesado vía MQTT"
@@ -350,6 +622,11 @@ impl MqttHandler {
         let response_payload = serde_json::json!({
             "status": "success",
             "processed_count": batch_size,
+            "duplicates_dropped": duplicates,
+            "rejected_count": rejected,
+            "replayed_count": replayed,
+            "quarantined_count": quarantined,
+            "throttled_dropped": throttled,
             "anomalies_detected": anomalies,
             "average_quality_score": avg_quality,
         });
@@ -367,13 +644,100 @@ impl MqttHandler {
 
         // Verificar sincronización
         let pending_count = db.count_pending_sync().await?;
-        if pending_count >= config.cloud_sync_batch_size as i64 {
-            tokio::spawn(async move {
-                let mut cs = cloud_sync.lock().await;
-                let _ = cs.sync_data(db).await;
-            });
+        if pending_count >= settings.borrow().cloud_sync_batch_size as i64 {
+            cloud_sync.sync_now();
         }
 
-        Ok(())
+        Ok("processed")
+    }
+}
+
+/// Publicación recibida, en espera de procesarse
+struct MqttMessage {
+    topic: String,
+    payload: Vec<u8>,
+    /// Patrón suscrito del topic (etiqueta de las métricas)
+    pattern: &'static str,
+    device_id: Option<String>,
+}
+
+/// Tarea que procesa los mensajes de su cola en orden
+#[derive(Clone)]
+struct MessageWorker {
+    client: AsyncClient,
+    db: Database,
+    edge_processor: Arc<EdgeProcessor>,
+    cloud_sync: CloudSyncHandle,
+    settings: watch::Receiver<RuntimeSettings>,
+    telemetry: Arc<Telemetry>,
+    twins: Arc<DeviceTwins>,
+    firmware: Arc<FirmwareService>,
+    time_sync: Arc<TimeSync>,
+}
+
+impl MessageWorker {
+    async fn run(self, mut messages: mpsc::Receiver<MqttMessage>) {
+        while let Some(message) = messages.recv().await {
+            self.telemetry.mqtt_queue_add(-1);
+            self.handle(message).await;
+        }
+    }
+
+    async fn handle(&self, message: MqttMessage) {
+        let MqttMessage {
+            topic,
+            payload,
+            pattern,
+            device_id,
+        } = message;
+
+        let result = match (pattern, &device_id) {
+            ("sensors/+/reported", Some(device_id)) => self
+                .twins
+                .apply_reported(device_id, &payload)
+                .await
+                .map(|_| "processed"),
+            ("sensors/+/ota/status", Some(device_id)) => self
+                .firmware
+                .apply_status(device_id, &payload)
+                .await
+                .map(|_| "processed"),
+            ("sensors/+/time/request", Some(device_id)) => self
+                .time_sync
+                .answer(device_id, &payload)
+                .map(|_| "processed"),
+            _ => {
+                MqttHandler::process_message(
+                    &topic,
+                    &payload,
+                    self.db.clone(),
+                    self.edge_processor.clone(),
+                    self.cloud_sync.clone(),
+                    self.settings.clone(),
+                    self.client.clone(),
+                )
+                .await
+            }
+        };
+        match result {
+            Ok(outcome) => self.telemetry.mqtt_message(pattern, outcome),
+            Err(e) => {
+                let outcome = if e.is::<serde_json::Error>() {
+                    self.telemetry.parse_failure(pattern);
+                    if let Some(device_id) = &device_id {
+                        self.telemetry.device_parse_failure(device_id);
+                    }
+                    "parse_error"
+                } else {
+                    "error"
+                };
+                self.telemetry.mqtt_message(pattern, outcome);
+                tracing::error!(
+                    topic = %topic,
+                    error = %e,
+                    "Error procesando mensaje MQTT"
+                );
+            }
+        }
     }
 }
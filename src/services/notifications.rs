@@ -0,0 +1,165 @@
+//! Canales de notificación de alertas
+//!
+//! Cada canal se suscribe a los eventos del servicio de alertas y los entrega
+//! de forma independiente: un canal lento o caído no retrasa a los demás.
+
+mod chat;
+mod digest;
+mod email;
+mod slack;
+mod telegram;
+mod template;
+mod webhook;
+
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::AlertEvent;
+use crate::services::alerting::Alerting;
+use chat::ChatNotifier;
+use digest::DailyDigest;
+use email::Mailer;
+use slack::SlackChannel;
+use std::sync::Arc;
+use std::time::Duration;
+use telegram::TelegramChannel;
+use tokio::sync::broadcast::{self, error::RecvError};
+use webhook::WebhookNotifier;
+
+/// Espera máxima entre reintentos
+const MAX_BACKOFF_SECS: u64 = 300;
+
+/// Resultado de un intento de entrega fallido
+enum Failure {
+    /// Error de red, timeout, 408, 429 o 5xx: se reintenta
+    Transient(String),
+    /// Otra respuesta 4xx: el receptor rechazó el mensaje
+    Permanent(String),
+}
+
+impl Failure {
+    /// Clasifica una respuesta HTTP no exitosa
+    fn from_status(status: reqwest::StatusCode) -> Self {
+        let reason = format!("HTTP {}", status.as_u16());
+        if status.is_server_error()
+            || status == reqwest::StatusCode::REQUEST_TIMEOUT
+            || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        {
+            Failure::Transient(reason)
+        } else {
+            Failure::Permanent(reason)
+        }
+    }
+}
+
+/// Espera antes del reintento `attempt` (desde 1): se duplica en cada uno
+fn backoff_delay(base: Duration, attempt: u32) -> Duration {
+    let factor = 2u64.saturating_pow(attempt.saturating_sub(1));
+    Duration::from_secs(base.as_secs().saturating_mul(factor).min(MAX_BACKOFF_SECS))
+}
+
+/// Siguiente evento que el canal debe entregar; None cuando se cierra el canal
+///
+/// Omite los eventos cubiertos por un silencio, de modo que todos los canales
+/// los respetan por igual.
+async fn next_event(
+    events: &mut broadcast::Receiver<AlertEvent>,
+    channel: &str,
+) -> Option<AlertEvent> {
+    loop {
+        match events.recv().await {
+            Ok(event) if event.silenced => tracing::debug!(
+                channel,
+                alert_id = event.alert_id,
+                "Notificación de alerta silenciada"
+            ),
+            Ok(event) => return Some(event),
+            // Mientras se reintenta un envío pueden acumularse más eventos de los que caben
+            Err(RecvError::Lagged(skipped)) => tracing::warn!(
+                channel,
+                skipped,
+                "Eventos de alertas descartados por el canal atrasado"
+            ),
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+/// Cliente HTTP compartido por los canales
+fn http_client(timeout: Duration) -> anyhow::Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .timeout(timeout)
+        .user_agent(concat!("env_edge_gateway_rpi/", env!("CARGO_PKG_VERSION")))
+        .build()?)
+}
+
+/// Inicia los canales configurados
+pub fn start(config: &Arc<Config>, alerting: &Alerting, db: &Database) -> anyhow::Result<()> {
+    for url in &config.alert_webhook_urls {
+        let notifier = WebhookNotifier::new(config, url)?;
+        tokio::spawn(notifier.run(alerting.subscribe()));
+    }
+
+    if !config.alert_webhook_urls.is_empty() {
+        tracing::info!(
+            webhooks = config.alert_webhook_urls.len(),
+            signed = config.alert_webhook_secret.is_some(),
+            "Webhooks de alertas habilitados"
+        );
+    }
+
+    if let Some(token) = &config.telegram_bot_token
+        && !config.telegram_chat_ids.is_empty()
+    {
+        let notifier = ChatNotifier::new(
+            config,
+            TelegramChannel::new(config, token)?,
+            config.telegram_chat_ids.clone(),
+            config.telegram_template.clone(),
+            config.telegram_rate_limit_per_min,
+        );
+        tokio::spawn(notifier.run(alerting.subscribe()));
+        tracing::info!(
+            chats = config.telegram_chat_ids.len(),
+            "Notificaciones de alertas por Telegram habilitadas"
+        );
+    }
+
+    if !config.slack_webhook_urls.is_empty() {
+        let notifier = ChatNotifier::new(
+            config,
+            SlackChannel::new(config)?,
+            config.slack_webhook_urls.clone(),
+            config.slack_template.clone(),
+            config.slack_rate_limit_per_min,
+        );
+        tokio::spawn(notifier.run(alerting.subscribe()));
+        tracing::info!(
+            webhooks = config.slack_webhook_urls.len(),
+            "Notificaciones de alertas por Slack habilitadas"
+        );
+    }
+
+    if let Some(mailer) = Mailer::from_config(config)? {
+        if !config.alert_email_to.is_empty() {
+            let notifier = ChatNotifier::new(
+                config,
+                mailer.clone(),
+                config.alert_email_to.clone(),
+                config.alert_email_template.clone(),
+                config.alert_email_rate_limit_per_min,
+            );
+            tokio::spawn(notifier.run(alerting.subscribe()));
+            tracing::info!(
+                recipients = config.alert_email_to.len(),
+                "Notificaciones de alertas por email habilitadas"
+            );
+        }
+
+        if !config.email_digest_to.is_empty() {
+            let digest = DailyDigest::new(config.clone(), db.clone(), mailer);
+            tokio::spawn(digest.start_schedule_task());
+        }
+    }
+
+    Ok(())
+}
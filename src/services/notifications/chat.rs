@@ -0,0 +1,169 @@
+use super::template::{self, DEFAULT_TEMPLATE};
+use super::{Failure, backoff_delay, next_event};
+use crate::config::{Config, SeverityRoute};
+use crate::models::AlertEvent;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Reintentos por mensaje; un chat no necesita la insistencia de un webhook
+const MAX_RETRIES: u32 = 3;
+
+/// Espera antes del primer reintento
+const BACKOFF: Duration = Duration::from_secs(2);
+
+/// Servicio de mensajería que recibe texto (Telegram, Slack)
+pub trait ChatChannel: Send + Sync + 'static {
+    /// Nombre del canal para los logs
+    const NAME: &'static str;
+
+    /// Envía un mensaje al destino (chat o URL)
+    fn send(&self, target: &str, text: &str) -> impl Future<Output = Result<(), Failure>> + Send;
+}
+
+/// Límite de mensajes por minuto (ventana fija)
+struct RateLimiter {
+    per_minute: u32,
+    window_start: Instant,
+    sent: u32,
+    /// Mensajes descartados desde el último enviado
+    dropped: u64,
+}
+
+impl RateLimiter {
+    fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            window_start: Instant::now(),
+            sent: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Reserva un envío; devuelve los mensajes descartados hasta ahora
+    fn acquire(&mut self) -> Option<u64> {
+        if self.per_minute == 0 {
+            return Some(0);
+        }
+        if self.window_start.elapsed() >= Duration::from_secs(60) {
+            self.window_start = Instant::now();
+            self.sent = 0;
+        }
+        if self.sent >= self.per_minute {
+            self.dropped += 1;
+            return None;
+        }
+        self.sent += 1;
+        Some(std::mem::take(&mut self.dropped))
+    }
+}
+
+/// Envía los eventos de alertas como mensajes de texto a un canal de chat
+///
+/// Cada destino recibe solo las alertas de su severidad mínima o superior. Los
+/// mensajes que superan el límite por minuto se descartan para no saturar el
+/// chat durante una avalancha de alertas; el siguiente mensaje indica cuántos
+/// se omitieron.
+pub struct ChatNotifier<C> {
+    channel: C,
+    routes: Vec<SeverityRoute>,
+    template: String,
+    chart_base_url: Option<String>,
+    limiter: RateLimiter,
+}
+
+impl<C: ChatChannel> ChatNotifier<C> {
+    pub fn new(
+        config: &Config,
+        channel: C,
+        routes: Vec<SeverityRoute>,
+        template: Option<String>,
+        rate_limit_per_min: u32,
+    ) -> Self {
+        Self {
+            channel,
+            routes,
+            template: template.unwrap_or_else(|| DEFAULT_TEMPLATE.to_string()),
+            chart_base_url: config.alert_chart_base_url.clone(),
+            limiter: RateLimiter::new(rate_limit_per_min),
+        }
+    }
+
+    /// Entrega los eventos hasta que se cierre el canal
+    pub async fn run(mut self, mut events: broadcast::Receiver<AlertEvent>) {
+        while let Some(event) = next_event(&mut events, C::NAME).await {
+            self.deliver(&event).await;
+        }
+    }
+
+    async fn deliver(&mut self, event: &AlertEvent) {
+        let text = template::render(&self.template, event, self.chart_base_url.as_deref());
+
+        for route in &self.routes {
+            if event.severity < route.min_severity {
+                continue;
+            }
+
+            let Some(dropped) = self.limiter.acquire() else {
+                tracing::warn!(
+                    channel = C::NAME,
+                    alert_id = event.alert_id,
+                    "Notificación de alerta descartada por el límite de envío"
+                );
+                continue;
+            };
+
+            let message = if dropped > 0 {
+                format!(
+                    "{}\n\n(+{} alertas omitidas por el límite de envío)",
+                    text, dropped
+                )
+            } else {
+                text.clone()
+            };
+
+            Self::send_with_retries(&self.channel, &route.target, &message, event).await;
+        }
+    }
+
+    async fn send_with_retries(channel: &C, target: &str, text: &str, event: &AlertEvent) {
+        for attempt in 0..=MAX_RETRIES {
+            if attempt > 0 {
+                tokio::time::sleep(backoff_delay(BACKOFF, attempt)).await;
+            }
+
+            match channel.send(target, text).await {
+                Ok(()) => {
+                    tracing::debug!(
+                        channel = C::NAME,
+                        alert_id = event.alert_id,
+                        "Notificación de alerta entregada"
+                    );
+                    return;
+                }
+                Err(Failure::Permanent(reason)) => {
+                    tracing::error!(
+                        channel = C::NAME,
+                        alert_id = event.alert_id,
+                        reason = %reason,
+                        "Notificación de alerta rechazada"
+                    );
+                    return;
+                }
+                Err(Failure::Transient(reason)) => tracing::warn!(
+                    channel = C::NAME,
+                    alert_id = event.alert_id,
+                    attempt = attempt + 1,
+                    reason = %reason,
+                    "Envío de la notificación de alerta fallido"
+                ),
+            }
+        }
+
+        tracing::error!(
+            channel = C::NAME,
+            alert_id = event.alert_id,
+            retries = MAX_RETRIES,
+            "Notificación de alerta descartada tras agotar los reintentos"
+        );
+    }
+}
@@ -0,0 +1,183 @@
+use super::Failure;
+use super::email::Mailer;
+use crate::config::Config;
+use crate::database::{AlertFilter, Database, GroupBy, MetricFilter};
+use crate::services::scheduling::duration_until_local_hour;
+use chrono::{DateTime, Duration as ChronoDuration, Local, Utc};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Arc;
+
+/// Alertas listadas en el resumen; el resto solo se cuenta
+const DIGEST_MAX_ALERTS: usize = 20;
+
+/// Alertas y lecturas anómalas consideradas en los conteos
+const DIGEST_SCAN_LIMIT: usize = 5000;
+
+/// Envía por email un resumen de las últimas 24 h a la hora configurada
+///
+/// Incluye los dispositivos offline, las alertas disparadas, las lecturas
+/// anómalas por dispositivo y el mínimo y máximo de cada medición por
+/// ubicación, para quien sigue la instalación desde su bandeja de entrada.
+pub struct DailyDigest {
+    config: Arc<Config>,
+    db: Database,
+    mailer: Mailer,
+}
+
+impl DailyDigest {
+    pub fn new(config: Arc<Config>, db: Database, mailer: Mailer) -> Self {
+        Self { config, db, mailer }
+    }
+
+    pub async fn start_schedule_task(self) {
+        tracing::info!(
+            hour = self.config.email_digest_hour,
+            recipients = self.config.email_digest_to.len(),
+            "Resumen diario por email habilitado"
+        );
+
+        loop {
+            tokio::time::sleep(duration_until_local_hour(self.config.email_digest_hour)).await;
+
+            if let Err(e) = self.send().await {
+                tracing::error!("Error generando el resumen diario: {}", e);
+            }
+        }
+    }
+
+    async fn send(&self) -> anyhow::Result<()> {
+        let to = Utc::now();
+        let from = to - ChronoDuration::hours(24);
+        let body = self.build(from, to).await?;
+        let subject = format!(
+            "Resumen diario de {} ({})",
+            self.config.gateway_id,
+            to.with_timezone(&Local).format("%Y-%m-%d")
+        );
+
+        for address in &self.config.email_digest_to {
+            match self.mailer.send(address, &subject, &body).await {
+                Ok(()) => tracing::info!(to = %address, "Resumen diario enviado"),
+                Err(Failure::Permanent(reason) | Failure::Transient(reason)) => {
+                    tracing::error!(to = %address, reason = %reason, "Error enviando el resumen diario")
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Texto del resumen del periodo
+    async fn build(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> anyhow::Result<String> {
+        let mut text = String::new();
+        writeln!(
+            text,
+            "Resumen del gateway {} del {} al {}\n",
+            self.config.gateway_id,
+            format_local(from),
+            format_local(to)
+        )?;
+
+        // Dispositivos offline
+        let mut offline: Vec<_> = self
+            .db
+            .list_device_presence()
+            .await?
+            .into_iter()
+            .filter_map(|device| Some((device.offline_since?, device.device_id)))
+            .collect();
+        offline.sort();
+        writeln!(text, "Dispositivos offline: {}", offline.len())?;
+        for (since, device_id) in &offline {
+            writeln!(
+                text,
+                "  - {} sin datos desde {}",
+                device_id,
+                format_local(*since)
+            )?;
+        }
+
+        // Alertas disparadas
+        let filter = AlertFilter {
+            from: Some(from),
+            to: Some(to),
+            ..Default::default()
+        };
+        let alerts = self.db.list_alerts(&filter, DIGEST_SCAN_LIMIT).await?;
+        writeln!(text, "\nAlertas disparadas: {}", alerts.len())?;
+        for alert in alerts.iter().take(DIGEST_MAX_ALERTS) {
+            writeln!(
+                text,
+                "  - [{}] {}: {} = {:.2} ({}{})",
+                alert.severity.as_str().to_uppercase(),
+                alert.device_id,
+                alert.metric,
+                alert.value,
+                format_local(alert.fired_at),
+                if alert.resolved_at.is_none() {
+                    ", activa"
+                } else {
+                    ""
+                }
+            )?;
+        }
+        if alerts.len() > DIGEST_MAX_ALERTS {
+            writeln!(text, "  ... y {} más", alerts.len() - DIGEST_MAX_ALERTS)?;
+        }
+
+        // Lecturas anómalas por dispositivo
+        let metrics_filter = MetricFilter {
+            device_ids: Vec::new(),
+            locations: Vec::new(),
+            tags: Vec::new(),
+            measurement: None,
+            from,
+            to,
+            quality: Default::default(),
+        };
+        let anomalies = self
+            .db
+            .get_anomalies(&metrics_filter, DIGEST_SCAN_LIMIT)
+            .await?;
+        let mut by_device: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+        for reading in &anomalies {
+            *by_device
+                .entry((&reading.header.device_id, &reading.header.location))
+                .or_default() += 1;
+        }
+        writeln!(text, "\nLecturas anómalas: {}", anomalies.len())?;
+        for ((device_id, location), count) in &by_device {
+            writeln!(text, "  - {} ({}): {}", device_id, location, count)?;
+        }
+
+        // Mínimo y máximo por ubicación
+        let locations = self
+            .db
+            .grouped_summary(&metrics_filter, GroupBy::Location)
+            .await?;
+        writeln!(text, "\nMínimos y máximos por ubicación:")?;
+        if locations.is_empty() {
+            writeln!(text, "  Sin lecturas en el periodo")?;
+        }
+        for (location, group) in &locations {
+            writeln!(text, "  {} ({} lecturas)", location, group.count)?;
+            let summaries: BTreeMap<_, _> = group.metrics_summary.iter().collect();
+            for (measurement, summary) in summaries {
+                writeln!(
+                    text,
+                    "    {}: mín {:.2} / máx {:.2} (prom. {:.2})",
+                    measurement, summary.min, summary.max, summary.avg
+                )?;
+            }
+        }
+
+        Ok(text)
+    }
+}
+
+fn format_local(at: DateTime<Utc>) -> String {
+    at.with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
@@ -0,0 +1,76 @@
+use super::Failure;
+use super::chat::ChatChannel;
+use crate::config::{Config, SmtpSecurity};
+use anyhow::Context;
+use lettre::message::{Mailbox, header::ContentType};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::time::Duration;
+
+/// Tiempo máximo de una sesión SMTP
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Envía emails de texto plano por SMTP
+///
+/// Se comparte entre los emails de alertas y el resumen diario.
+#[derive(Clone)]
+pub struct Mailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl Mailer {
+    /// Mailer configurado, o None sin `SMTP_HOST`
+    pub fn from_config(config: &Config) -> anyhow::Result<Option<Self>> {
+        let (Some(host), Some(from)) = (&config.smtp_host, &config.smtp_from) else {
+            return Ok(None);
+        };
+
+        let builder = match config.smtp_security {
+            SmtpSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+            SmtpSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+            SmtpSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        };
+        let mut builder = builder.port(config.smtp_port).timeout(Some(SMTP_TIMEOUT));
+        if let (Some(username), Some(password)) = (&config.smtp_username, &config.smtp_password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        Ok(Some(Self {
+            transport: builder.build(),
+            from: from.parse().context("SMTP_FROM inválido")?,
+        }))
+    }
+
+    /// Envía un email a una dirección
+    pub async fn send(&self, to: &str, subject: &str, body: &str) -> Result<(), Failure> {
+        let to: Mailbox = to
+            .parse()
+            .map_err(|e| Failure::Permanent(format!("Destinatario inválido: {}", e)))?;
+
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body.to_string())
+            .map_err(|e| Failure::Permanent(e.to_string()))?;
+
+        match self.transport.send(message).await {
+            Ok(_) => Ok(()),
+            // Código 5xx del servidor: la dirección o el mensaje no se aceptarán
+            Err(e) if e.is_permanent() => Err(Failure::Permanent(e.to_string())),
+            Err(e) => Err(Failure::Transient(e.to_string())),
+        }
+    }
+}
+
+impl ChatChannel for Mailer {
+    const NAME: &'static str = "email";
+
+    /// La primera línea del mensaje es el asunto y el resto el cuerpo
+    async fn send(&self, target: &str, text: &str) -> Result<(), Failure> {
+        let (subject, body) = text.split_once('\n').unwrap_or((text, ""));
+        Mailer::send(self, target, subject.trim(), body.trim_start()).await
+    }
+}
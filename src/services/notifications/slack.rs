@@ -0,0 +1,40 @@
+use super::chat::ChatChannel;
+use super::{Failure, http_client};
+use crate::config::Config;
+use std::time::Duration;
+
+/// Envía mensajes a incoming webhooks de Slack
+///
+/// Cada destino es la URL de un webhook, ligada a un canal de Slack.
+pub struct SlackChannel {
+    client: reqwest::Client,
+}
+
+impl SlackChannel {
+    pub fn new(config: &Config) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: http_client(Duration::from_secs(config.alert_webhook_timeout_secs))?,
+        })
+    }
+}
+
+impl ChatChannel for SlackChannel {
+    const NAME: &'static str = "slack";
+
+    async fn send(&self, target: &str, text: &str) -> Result<(), Failure> {
+        // La URL del webhook es un secreto: no se incluye en los errores
+        let response = self
+            .client
+            .post(target)
+            .json(&serde_json::json!({ "text": text }))
+            .send()
+            .await
+            .map_err(|e| Failure::Transient(e.without_url().to_string()))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(Failure::from_status(response.status()))
+        }
+    }
+}
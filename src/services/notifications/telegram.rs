@@ -0,0 +1,50 @@
+use super::chat::ChatChannel;
+use super::{Failure, http_client};
+use crate::config::Config;
+use std::time::Duration;
+
+/// Envía mensajes con la Bot API de Telegram (`sendMessage`)
+///
+/// Los destinos son identificadores de chat; para grupos y canales son
+/// negativos (`-100...`). El texto se envía sin formato para no tener que
+/// escapar los nombres de reglas y dispositivos.
+pub struct TelegramChannel {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl TelegramChannel {
+    pub fn new(config: &Config, token: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: http_client(Duration::from_secs(config.alert_webhook_timeout_secs))?,
+            url: format!("https://api.telegram.org/bot{}/sendMessage", token),
+        })
+    }
+}
+
+impl ChatChannel for TelegramChannel {
+    const NAME: &'static str = "telegram";
+
+    async fn send(&self, target: &str, text: &str) -> Result<(), Failure> {
+        let body = serde_json::json!({
+            "chat_id": target,
+            "text": text,
+            "disable_web_page_preview": true,
+        });
+
+        // El error de reqwest incluye la URL, que contiene el token del bot
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| Failure::Transient(e.without_url().to_string()))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(Failure::from_status(response.status()))
+        }
+    }
+}
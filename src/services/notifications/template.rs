@@ -0,0 +1,48 @@
+use crate::models::{AlertEvent, AlertState};
+
+/// Plantilla usada cuando el canal no define una propia
+pub const DEFAULT_TEMPLATE: &str = "[{severity}] {rule}: {state}\n\
+     {device} ({location}): {metric} = {value}\n\
+     {condition}\n\
+     {chart}";
+
+/// Sustituye los marcadores de la plantilla con los datos del evento
+///
+/// Marcadores: `{gateway}`, `{rule}`, `{state}`, `{severity}`, `{device}`,
+/// `{location}`, `{metric}`, `{value}`, `{condition}`, `{fired_at}` y
+/// `{chart}` (vacío sin `ALERT_CHART_BASE_URL`).
+pub fn render(template: &str, event: &AlertEvent, chart_base_url: Option<&str>) -> String {
+    let state = match event.state {
+        AlertState::Firing => "ACTIVA",
+        AlertState::Resolved => "RESUELTA",
+    };
+    let chart = chart_base_url
+        .and_then(|base| chart_link(base, &event.device_id, &event.metric))
+        .unwrap_or_default();
+
+    let text = template
+        .replace("{gateway}", &event.gateway_id)
+        .replace("{rule}", &event.rule_name)
+        .replace("{state}", state)
+        .replace("{severity}", &event.severity.as_str().to_uppercase())
+        .replace("{device}", &event.device_id)
+        .replace("{location}", event.location.as_deref().unwrap_or("-"))
+        .replace("{metric}", &event.metric)
+        .replace("{value}", &format!("{:.2}", event.value))
+        .replace("{condition}", &event.condition)
+        .replace("{fired_at}", &event.fired_at.to_rfc3339())
+        .replace("{chart}", &chart);
+
+    // Sin enlace al gráfico, la plantilla por defecto termina en una línea vacía
+    text.trim_end().to_string()
+}
+
+/// Enlace al gráfico del dashboard con el dispositivo y la métrica seleccionados
+fn chart_link(base: &str, device_id: &str, metric: &str) -> Option<String> {
+    let mut url = reqwest::Url::parse(&format!("{}/", base)).ok()?;
+    url.query_pairs_mut()
+        .append_pair("device", device_id)
+        .append_pair("measurement", metric);
+    url.set_fragment(Some("chart"));
+    Some(url.to_string())
+}
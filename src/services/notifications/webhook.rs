@@ -0,0 +1,135 @@
+use super::{Failure, backoff_delay, http_client, next_event};
+use crate::config::Config;
+use crate::models::AlertEvent;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// Envía los eventos de alertas como JSON a una URL (n8n, Node-RED, gestión de incidentes)
+///
+/// Los eventos se entregan en orden; un envío fallido se reintenta con espera
+/// exponencial. Con `ALERT_WEBHOOK_SECRET`, cada intento lleva la cabecera
+/// `X-Gateway-Signature: sha256=<hex>`, el HMAC-SHA256 de
+/// `<X-Gateway-Timestamp>.<cuerpo>`, para que el receptor verifique el origen
+/// y descarte reenvíos antiguos.
+pub struct WebhookNotifier {
+    url: String,
+    secret: Option<String>,
+    client: reqwest::Client,
+    max_retries: u32,
+    backoff: Duration,
+}
+
+impl WebhookNotifier {
+    pub fn new(config: &Config, url: &str) -> anyhow::Result<Self> {
+        let client = http_client(Duration::from_secs(config.alert_webhook_timeout_secs))?;
+
+        Ok(Self {
+            url: url.to_string(),
+            secret: config.alert_webhook_secret.clone(),
+            client,
+            max_retries: config.alert_webhook_max_retries,
+            backoff: Duration::from_secs(config.alert_webhook_backoff_secs),
+        })
+    }
+
+    /// Entrega los eventos hasta que se cierre el canal
+    pub async fn run(self, mut events: broadcast::Receiver<AlertEvent>) {
+        while let Some(event) = next_event(&mut events, "webhook").await {
+            self.deliver(&event).await;
+        }
+    }
+
+    async fn deliver(&self, event: &AlertEvent) {
+        let body = match serde_json::to_vec(event) {
+            Ok(body) => body,
+            Err(e) => {
+                tracing::error!("Error serializando el evento de alerta: {}", e);
+                return;
+            }
+        };
+        // Identifica la entrega en todos sus intentos, para que el receptor descarte duplicados
+        let delivery_id = Uuid::new_v4().to_string();
+
+        for attempt in 0..=self.max_retries {
+            if attempt > 0 {
+                tokio::time::sleep(backoff_delay(self.backoff, attempt)).await;
+            }
+
+            match self.send(event, &delivery_id, &body).await {
+                Ok(()) => {
+                    tracing::debug!(url = %self.url, alert_id = event.alert_id, "Webhook de alerta entregado");
+                    return;
+                }
+                Err(Failure::Permanent(reason)) => {
+                    tracing::error!(
+                        url = %self.url,
+                        alert_id = event.alert_id,
+                        reason = %reason,
+                        "Webhook de alerta rechazado por el receptor"
+                    );
+                    return;
+                }
+                Err(Failure::Transient(reason)) => tracing::warn!(
+                    url = %self.url,
+                    alert_id = event.alert_id,
+                    attempt = attempt + 1,
+                    reason = %reason,
+                    "Envío del webhook de alerta fallido"
+                ),
+            }
+        }
+
+        tracing::error!(
+            url = %self.url,
+            alert_id = event.alert_id,
+            retries = self.max_retries,
+            "Webhook de alerta descartado tras agotar los reintentos"
+        );
+    }
+
+    async fn send(
+        &self,
+        event: &AlertEvent,
+        delivery_id: &str,
+        body: &[u8],
+    ) -> Result<(), Failure> {
+        let timestamp = Utc::now().timestamp().to_string();
+
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header("X-Gateway-Event", format!("alert.{}", event.state.as_str()))
+            .header("X-Gateway-Delivery", delivery_id)
+            .header("X-Gateway-Timestamp", &timestamp);
+        if let Some(signature) = self.sign(&timestamp, body) {
+            request = request.header("X-Gateway-Signature", format!("sha256={}", signature));
+        }
+
+        let response = request
+            .body(body.to_vec())
+            .send()
+            .await
+            .map_err(|e| Failure::Transient(e.to_string()))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(Failure::from_status(response.status()))
+        }
+    }
+
+    /// HMAC-SHA256 (hex) de `<timestamp>.<cuerpo>`
+    fn sign(&self, timestamp: &str, body: &[u8]) -> Option<String> {
+        let secret = self.secret.as_ref()?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).ok()?;
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        Some(format!("{:x}", mac.finalize().into_bytes()))
+    }
+}
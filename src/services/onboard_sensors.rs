@@ -0,0 +1,221 @@
+#[cfg(feature = "i2c")]
+mod i2c;
+
+use crate::config::Config;
+use crate::models::{SensorDataInput, SensorMetric};
+use crate::services::local_ingest::LocalIngest;
+use std::collections::HashSet;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Origen de las lecturas propias del gateway en el topic (`onboard/<device_id>`)
+const SOURCE: &str = "onboard";
+
+/// Familias 1-Wire con el formato de `w1_slave` del DS18B20 (DS18S20, DS1822, DS18B20)
+const ONEWIRE_THERMOMETER_FAMILIES: [&str; 3] = ["10-", "22-", "28-"];
+
+/// Valor de encendido del DS18B20: indica una conversión que no llegó a completarse
+const POWER_ON_RESET_MILLIDEGREES: i32 = 85_000;
+
+/// Sensores conectados directamente al gateway (sondas 1-Wire DS18B20 y
+/// sensores I2C), para vigilar el gabinete sin un ESP32 adicional
+///
+/// Sus mediciones se publican cada `ONBOARD_INTERVAL_SECS` como el dispositivo
+/// `ONBOARD_DEVICE_ID`, con el mismo procesamiento que las lecturas MQTT.
+pub struct OnboardSensors {
+    config: Arc<Config>,
+    ingest: LocalIngest,
+    /// Sondas cuya última lectura falló, para avisar solo del primer error
+    failing_probes: HashSet<String>,
+    /// El bus 1-Wire no tenía sondas en la última muestra
+    onewire_missing: bool,
+    /// Drivers de `I2C_SENSORS`, usados desde un hilo bloqueante en cada muestra
+    #[cfg(feature = "i2c")]
+    i2c: Arc<std::sync::Mutex<i2c::I2cSensors>>,
+}
+
+impl OnboardSensors {
+    pub fn new(config: Arc<Config>, ingest: LocalIngest) -> Self {
+        Self {
+            ingest,
+            failing_probes: HashSet::new(),
+            onewire_missing: false,
+            #[cfg(feature = "i2c")]
+            i2c: Arc::new(std::sync::Mutex::new(i2c::I2cSensors::new(
+                &config.i2c_sensors,
+            ))),
+            config,
+        }
+    }
+
+    /// Toma una muestra en cada intervalo hasta que se detenga el gateway
+    pub async fn run(mut self) {
+        tracing::info!(
+            device_id = %self.config.onboard_device_id,
+            interval_secs = self.config.onboard_interval_secs,
+            "Lectura de sensores propios del gateway iniciada"
+        );
+
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.onboard_interval_secs));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+
+            let mut metrics = Vec::new();
+            if self.config.onewire_enabled {
+                metrics.extend(self.sample_onewire().await);
+            }
+            #[cfg(feature = "i2c")]
+            if !self.config.i2c_sensors.is_empty() {
+                metrics.extend(self.sample_i2c().await);
+            }
+            if metrics.is_empty() {
+                continue;
+            }
+
+            let input = SensorDataInput {
+                header: LocalIngest::header(
+                    SOURCE,
+                    &self.config.onboard_device_id,
+                    &self.config.onboard_location,
+                    None,
+                ),
+                metrics,
+                device_timestamp: None,
+            };
+            if let Err(e) = self.ingest.submit(input).await {
+                tracing::error!(
+                    device_id = %self.config.onboard_device_id,
+                    "Error almacenando la lectura de los sensores del gateway: {}",
+                    e
+                );
+            }
+        }
+    }
+
+    /// Temperatura de cada sonda del bus; las que fallan se omiten
+    async fn sample_onewire(&mut self) -> Vec<SensorMetric> {
+        let dir = self.config.onewire_devices_dir.clone();
+        // Cada lectura de `w1_slave` espera la conversión de la sonda (~750 ms)
+        let readings =
+            match tokio::task::spawn_blocking(move || read_onewire(Path::new(&dir))).await {
+                Ok(readings) => readings,
+                Err(e) => {
+                    tracing::error!("Error leyendo las sondas 1-Wire: {}", e);
+                    return Vec::new();
+                }
+            };
+
+        if readings.is_empty() {
+            if !self.onewire_missing {
+                tracing::warn!(
+                    dir = %self.config.onewire_devices_dir,
+                    "No se encontraron sondas 1-Wire (¿dtoverlay=w1-gpio en /boot/config.txt?)"
+                );
+                self.onewire_missing = true;
+            }
+            return Vec::new();
+        }
+        self.onewire_missing = false;
+
+        let single_probe = readings.len() == 1;
+        let mut metrics = Vec::with_capacity(readings.len());
+        for (probe, result) in readings {
+            match result {
+                Ok(celsius) => {
+                    if self.failing_probes.remove(&probe) {
+                        tracing::info!(probe = %probe, "Sonda 1-Wire recuperada");
+                    }
+                    metrics.push(SensorMetric {
+                        measurement: self.probe_measurement(&probe, single_probe),
+                        value: celsius,
+                        unit: Some("°C".to_string()),
+                    });
+                }
+                Err(e) => {
+                    if self.failing_probes.insert(probe.clone()) {
+                        tracing::warn!(probe = %probe, "Lectura de la sonda 1-Wire fallida: {}", e);
+                    } else {
+                        tracing::debug!(probe = %probe, "Lectura de la sonda 1-Wire fallida: {}", e);
+                    }
+                }
+            }
+        }
+        metrics
+    }
+
+    /// Mediciones de los sensores I2C; los que fallan se omiten
+    #[cfg(feature = "i2c")]
+    async fn sample_i2c(&self) -> Vec<SensorMetric> {
+        let sensors = self.i2c.clone();
+        match tokio::task::spawn_blocking(move || sensors.lock().unwrap().sample()).await {
+            Ok(metrics) => metrics,
+            Err(e) => {
+                tracing::error!("Error leyendo los sensores I2C: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Medición de una sonda: la de `ONEWIRE_PROBES`, o `Temperature` si es la
+    /// única y `Temperature_<serie>` si hay varias
+    fn probe_measurement(&self, probe: &str, single_probe: bool) -> String {
+        if let Some(measurement) = self.config.onewire_probes.get(probe) {
+            return measurement.clone();
+        }
+        if single_probe {
+            return "Temperature".to_string();
+        }
+        let serial = probe.split_once('-').map_or(probe, |(_, serial)| serial);
+        format!("Temperature_{}", serial)
+    }
+}
+
+/// Lee todas las sondas de temperatura del bus, ordenadas por ID
+fn read_onewire(dir: &Path) -> Vec<(String, Result<f32, String>)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut probes: Vec<String> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|name| {
+            ONEWIRE_THERMOMETER_FAMILIES
+                .iter()
+                .any(|family| name.starts_with(family))
+        })
+        .collect();
+    probes.sort();
+
+    probes
+        .into_iter()
+        .map(|probe| {
+            let result = std::fs::read_to_string(dir.join(&probe).join("w1_slave"))
+                .map_err(|e| e.to_string())
+                .and_then(|content| parse_w1_slave(&content));
+            (probe, result)
+        })
+        .collect()
+}
+
+/// Interpreta `w1_slave`: la primera línea termina en `YES` si el CRC es válido
+/// y la segunda en `t=<milésimas de °C>`
+fn parse_w1_slave(content: &str) -> Result<f32, String> {
+    let mut lines = content.lines();
+    if !lines
+        .next()
+        .is_some_and(|line| line.trim_end().ends_with("YES"))
+    {
+        return Err("CRC inválido".to_string());
+    }
+    let millidegrees: i32 = lines
+        .next()
+        .and_then(|line| line.rsplit_once("t="))
+        .and_then(|(_, value)| value.trim().parse().ok())
+        .ok_or_else(|| "respuesta sin temperatura".to_string())?;
+    if millidegrees == POWER_ON_RESET_MILLIDEGREES {
+        return Err("la sonda devolvió el valor de encendido (85 °C)".to_string());
+    }
+    Ok(millidegrees as f32 / 1000.0)
+}
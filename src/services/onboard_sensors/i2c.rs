@@ -0,0 +1,140 @@
+use crate::config::{I2cSensor, I2cSensorModel};
+use crate::models::SensorMetric;
+use bme280::i2c::BME280;
+use linux_embedded_hal::{Delay, I2cdev};
+use sht31::prelude::*;
+
+/// Driver inicializado de un sensor
+enum Driver {
+    Bme280(BME280<I2cdev>),
+    Sht31(SHT31<SimpleSingleShot<Delay>, I2cdev>),
+}
+
+/// Sensor de `I2C_SENSORS` y su driver, si pudo inicializarse
+struct Slot {
+    config: I2cSensor,
+    driver: Option<Driver>,
+    /// La última lectura falló, para avisar solo del primer error
+    failing: bool,
+}
+
+/// Sensores I2C conectados al header del gateway (BME280, SHT31)
+///
+/// La E/S es bloqueante (el SHT31 espera ~15 ms por medición): `sample` debe
+/// llamarse fuera del runtime. Un sensor que falla se vuelve a inicializar en
+/// la siguiente muestra, para que un cable suelto no obligue a reiniciar.
+pub struct I2cSensors {
+    slots: Vec<Slot>,
+}
+
+impl I2cSensors {
+    pub fn new(sensors: &[I2cSensor]) -> Self {
+        Self {
+            slots: sensors
+                .iter()
+                .map(|config| Slot {
+                    config: config.clone(),
+                    driver: None,
+                    failing: false,
+                })
+                .collect(),
+        }
+    }
+
+    /// Mediciones de todos los sensores; los que fallan se omiten
+    pub fn sample(&mut self) -> Vec<SensorMetric> {
+        let mut metrics = Vec::new();
+        for slot in &mut self.slots {
+            let sensor = format!(
+                "{}@i2c-{}:0x{:02x}",
+                format!("{:?}", slot.config.model).to_lowercase(),
+                slot.config.bus,
+                slot.config.address
+            );
+            match slot.read() {
+                Ok(values) => {
+                    if std::mem::take(&mut slot.failing) {
+                        tracing::info!(sensor = %sensor, "Sensor I2C recuperado");
+                    }
+                    metrics.extend(values.into_iter().map(|(measurement, value, unit)| {
+                        SensorMetric {
+                            measurement: match &slot.config.name {
+                                Some(name) => format!("{}_{}", measurement, name),
+                                None => measurement.to_string(),
+                            },
+                            value,
+                            unit: Some(unit.to_string()),
+                        }
+                    }));
+                }
+                Err(e) => {
+                    slot.driver = None;
+                    if slot.failing {
+                        tracing::debug!(sensor = %sensor, "Lectura del sensor I2C fallida: {}", e);
+                    } else {
+                        tracing::warn!(sensor = %sensor, "Lectura del sensor I2C fallida: {}", e);
+                        slot.failing = true;
+                    }
+                }
+            }
+        }
+        metrics
+    }
+}
+
+impl Slot {
+    /// Mide con el driver, inicializándolo si hace falta
+    fn read(&mut self) -> Result<Vec<(&'static str, f32, &'static str)>, String> {
+        let driver = match &mut self.driver {
+            Some(driver) => driver,
+            None => self.driver.insert(open(&self.config)?),
+        };
+
+        match driver {
+            Driver::Bme280(bme280) => {
+                let measurements = bme280.measure(&mut Delay).map_err(|e| format!("{:?}", e))?;
+                Ok(vec![
+                    ("Temperature", measurements.temperature, "°C"),
+                    ("Humidity", measurements.humidity, "%"),
+                    // El driver entrega Pa; las estaciones meteorológicas usan hPa
+                    ("Pressure", measurements.pressure / 100.0, "hPa"),
+                ])
+            }
+            Driver::Sht31(sht31) => {
+                let reading = sht31.read().map_err(|e| e.to_string())?;
+                Ok(vec![
+                    ("Temperature", reading.temperature, "°C"),
+                    ("Humidity", reading.humidity, "%"),
+                ])
+            }
+        }
+    }
+}
+
+fn open(config: &I2cSensor) -> Result<Driver, String> {
+    let path = format!("/dev/i2c-{}", config.bus);
+    let i2c = I2cdev::new(&path).map_err(|e| format!("no se pudo abrir {}: {}", path, e))?;
+
+    match config.model {
+        I2cSensorModel::Bme280 => {
+            let mut bme280 = BME280::new(i2c, config.address);
+            // Lee la calibración del chip; falla si en la dirección hay otro sensor (BMP280)
+            bme280
+                .init(&mut Delay)
+                .map_err(|e| format!("inicialización fallida: {:?}", e))?;
+            Ok(Driver::Bme280(bme280))
+        }
+        I2cSensorModel::Sht31 => {
+            let address = if config.address == DeviceAddr::AD1 as u8 {
+                DeviceAddr::AD1
+            } else {
+                DeviceAddr::AD0
+            };
+            Ok(Driver::Sht31(
+                SHT31::new(i2c, Delay)
+                    .with_address(address)
+                    .with_unit(TemperatureUnit::Celsius),
+            ))
+        }
+    }
+}
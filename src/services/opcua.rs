@@ -0,0 +1,385 @@
+use crate::config::Config;
+use crate::models::{DeviceTimestamp, SensorDataInput, SensorMetric};
+use crate::services::local_ingest::LocalIngest;
+use anyhow::Context as _;
+use opcua::client::{ClientBuilder, DataChangeCallback, IdentityToken, Session, SessionPollResult};
+use opcua::types::{
+    DataValue, EndpointDescription, MessageSecurityMode, MonitoredItemCreateRequest, NodeId,
+    TimestampsToReturn, UserTokenPolicy, Variant,
+};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio_stream::StreamExt;
+
+/// Origen de las lecturas OPC UA en el topic (`opcua/<device_id>`)
+const SOURCE: &str = "opcua";
+
+/// Espera antes de reintentar la suscripción cuando el servidor la rechaza
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Cambios de valor pendientes de procesar por equipo
+const CHANGES_CAPACITY: usize = 1024;
+
+/// Archivo `OPCUA_DEVICES_PATH`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OpcUaFile {
+    devices: Vec<OpcUaDevice>,
+}
+
+/// PLC o servidor OPC UA que se publica como un dispositivo del gateway
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OpcUaDevice {
+    device_id: String,
+    location: String,
+    #[serde(default)]
+    device_type: Option<String>,
+    /// URL del servidor (`opc.tcp://host:puerto/ruta`)
+    endpoint: String,
+    #[serde(default)]
+    sampling_ms: Option<u64>,
+    nodes: Vec<Node>,
+}
+
+/// Nodo suscrito como una métrica (`valor * scale + offset`)
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Node {
+    measurement: String,
+    #[serde(deserialize_with = "deserialize_node_id")]
+    node_id: NodeId,
+    #[serde(default = "default_scale")]
+    scale: f64,
+    #[serde(default)]
+    offset: f64,
+    #[serde(default)]
+    unit: Option<String>,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+/// NodeId en notación de texto (`ns=2;s=Caldera.Temperatura`, `ns=3;i=1001`)
+fn deserialize_node_id<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<NodeId, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    NodeId::from_str(&value)
+        .map_err(|_| serde::de::Error::custom(format!("node_id inválido: {}", value)))
+}
+
+/// Cliente OPC UA que suscribe los nodos de cada equipo de `OPCUA_DEVICES_PATH`
+///
+/// Cada notificación del servidor se convierte en una lectura con los nodos
+/// que cambiaron. La sesión se reconecta sola y recrea las suscripciones; por
+/// ahora solo se admiten endpoints sin cifrado y con acceso anónimo.
+pub struct OpcUaClient {
+    config: Arc<Config>,
+    ingest: LocalIngest,
+    devices: Vec<Arc<OpcUaDevice>>,
+}
+
+impl OpcUaClient {
+    /// Lee y valida el archivo de equipos
+    pub fn load(config: Arc<Config>, ingest: LocalIngest, path: &str) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("OPCUA_DEVICES_PATH: no se pudo leer {}", path))?;
+        let file: OpcUaFile = serde_json::from_str(&content)
+            .with_context(|| format!("OPCUA_DEVICES_PATH: {} no es válido", path))?;
+
+        let mut device_ids = HashSet::new();
+        for device in &file.devices {
+            anyhow::ensure!(
+                !device.device_id.is_empty() && !device.location.is_empty(),
+                "OPCUA_DEVICES_PATH: cada equipo necesita device_id y location"
+            );
+            anyhow::ensure!(
+                device_ids.insert(device.device_id.as_str()),
+                "OPCUA_DEVICES_PATH: device_id repetido: {}",
+                device.device_id
+            );
+            anyhow::ensure!(
+                device.endpoint.starts_with("opc.tcp://"),
+                "OPCUA_DEVICES_PATH: el endpoint de {} debe empezar por opc.tcp://",
+                device.device_id
+            );
+            anyhow::ensure!(
+                !device.nodes.is_empty(),
+                "OPCUA_DEVICES_PATH: {} no tiene nodos",
+                device.device_id
+            );
+            anyhow::ensure!(
+                device.sampling_ms != Some(0),
+                "OPCUA_DEVICES_PATH: sampling_ms de {} debe ser al menos 1",
+                device.device_id
+            );
+            let mut node_ids = HashSet::new();
+            for node in &device.nodes {
+                anyhow::ensure!(
+                    node_ids.insert(&node.node_id),
+                    "OPCUA_DEVICES_PATH: nodo repetido en {}: {}",
+                    device.device_id,
+                    node.node_id
+                );
+            }
+        }
+
+        Ok(Self {
+            config,
+            ingest,
+            devices: file.devices.into_iter().map(Arc::new).collect(),
+        })
+    }
+
+    /// Inicia una sesión por equipo
+    pub fn start(self) {
+        tracing::info!(
+            devices = self.devices.len(),
+            "Suscripciones OPC UA iniciadas"
+        );
+        for device in self.devices {
+            tokio::spawn(session_task(
+                self.config.clone(),
+                self.ingest.clone(),
+                device,
+            ));
+        }
+    }
+}
+
+async fn session_task(config: Arc<Config>, ingest: LocalIngest, device: Arc<OpcUaDevice>) {
+    let client = ClientBuilder::new()
+        .application_name("env_edge_gateway_rpi")
+        .application_uri(format!("urn:env-edge-gateway:{}", config.gateway_id))
+        .product_uri("urn:env-edge-gateway")
+        .pki_dir(&config.opcua_pki_dir)
+        .create_sample_keypair(true)
+        .session_retry_limit(-1)
+        .session_retry_max(Duration::from_secs(60))
+        .client();
+    let mut client = match client {
+        Ok(client) => client,
+        Err(errors) => {
+            tracing::error!(device_id = %device.device_id, errors = ?errors, "Configuración del cliente OPC UA inválida");
+            return;
+        }
+    };
+
+    let endpoint: EndpointDescription = (
+        device.endpoint.as_str(),
+        "None",
+        MessageSecurityMode::None,
+        UserTokenPolicy::anonymous(),
+    )
+        .into();
+    let (session, event_loop) = match client
+        .connect_to_endpoint_directly(endpoint, IdentityToken::Anonymous)
+    {
+        Ok(connection) => connection,
+        Err(e) => {
+            tracing::error!(device_id = %device.device_id, "Error preparando la sesión OPC UA: {}", e);
+            return;
+        }
+    };
+
+    let (changes_tx, changes_rx) = mpsc::channel(CHANGES_CAPACITY);
+    tokio::spawn(collect(ingest, device.clone(), changes_rx));
+    tokio::spawn(subscribe(
+        config,
+        session.clone(),
+        device.clone(),
+        changes_tx,
+    ));
+
+    // El bucle de la sesión mantiene la conexión; aquí solo se informa su estado
+    let events = event_loop.enter();
+    tokio::pin!(events);
+    let mut failing = false;
+    let mut connected_before = false;
+    while let Some(result) = events.next().await {
+        match result {
+            Ok(SessionPollResult::Reconnected(_)) => {
+                tracing::info!(device_id = %device.device_id, endpoint = %device.endpoint, "Sesión OPC UA establecida");
+                failing = false;
+                // La sesión recrea la suscripción antes de reanudar su bucle de
+                // publicación y este no la ve: sin este aviso no llegan más cambios
+                if connected_before {
+                    session.trigger_publish_now();
+                }
+                connected_before = true;
+            }
+            Ok(SessionPollResult::ConnectionLost(status)) => {
+                tracing::warn!(device_id = %device.device_id, "Conexión OPC UA perdida: {}", status);
+            }
+            // Un PLC apagado fallaría en cada reintento: solo se avisa al empezar
+            Ok(SessionPollResult::ReconnectFailed(status)) => {
+                if failing {
+                    tracing::debug!(device_id = %device.device_id, "Conexión OPC UA fallida: {}", status);
+                } else {
+                    tracing::warn!(device_id = %device.device_id, endpoint = %device.endpoint, "Conexión OPC UA fallida: {}", status);
+                    failing = true;
+                }
+            }
+            Ok(_) => {}
+            Err(status) => {
+                tracing::error!(device_id = %device.device_id, "Sesión OPC UA terminada: {}", status);
+                break;
+            }
+        }
+    }
+}
+
+/// Crea la suscripción y sus nodos en cuanto hay conexión
+///
+/// Tras una reconexión la sesión la recrea por su cuenta; un nodo que el
+/// servidor rechaza se avisa y se omite.
+async fn subscribe(
+    config: Arc<Config>,
+    session: Arc<Session>,
+    device: Arc<OpcUaDevice>,
+    changes: mpsc::Sender<(NodeId, DataValue)>,
+) {
+    let sampling = Duration::from_millis(device.sampling_ms.unwrap_or(config.opcua_sampling_ms));
+    let mut subscription_id = None;
+    loop {
+        if !session.wait_for_connection().await {
+            return;
+        }
+
+        let id = match subscription_id {
+            Some(id) => id,
+            None => {
+                let changes = changes.clone();
+                let callback = DataChangeCallback::new(move |value, item| {
+                    if changes
+                        .try_send((item.item_to_monitor().node_id.clone(), value))
+                        .is_err()
+                    {
+                        tracing::debug!("Cambio de valor OPC UA descartado: cola llena");
+                    }
+                });
+                match session
+                    .create_subscription(sampling, 60, 20, 0, 0, true, callback)
+                    .await
+                {
+                    Ok(id) => *subscription_id.insert(id),
+                    Err(e) => {
+                        tracing::warn!(device_id = %device.device_id, "Error creando la suscripción OPC UA: {}", e);
+                        tokio::time::sleep(RETRY_DELAY).await;
+                        continue;
+                    }
+                }
+            }
+        };
+
+        let items = device
+            .nodes
+            .iter()
+            .map(|node| {
+                let mut item = MonitoredItemCreateRequest::from(node.node_id.clone());
+                item.requested_parameters.sampling_interval = sampling.as_millis() as f64;
+                item.requested_parameters.queue_size = 1;
+                item
+            })
+            .collect();
+        match session
+            .create_monitored_items(id, TimestampsToReturn::Source, items)
+            .await
+        {
+            Ok(created) => {
+                for item in created
+                    .iter()
+                    .filter(|item| !item.result.status_code.is_good())
+                {
+                    tracing::warn!(
+                        device_id = %device.device_id,
+                        node_id = %item.item_to_monitor.node_id,
+                        "Nodo OPC UA rechazado: {}",
+                        item.result.status_code
+                    );
+                }
+                return;
+            }
+            Err(e) => {
+                tracing::warn!(device_id = %device.device_id, "Error suscribiendo los nodos OPC UA: {}", e);
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+        }
+    }
+}
+
+/// Convierte los cambios de valor en lecturas del equipo
+async fn collect(
+    ingest: LocalIngest,
+    device: Arc<OpcUaDevice>,
+    mut changes: mpsc::Receiver<(NodeId, DataValue)>,
+) {
+    let nodes: HashMap<&NodeId, &Node> = device
+        .nodes
+        .iter()
+        .map(|node| (&node.node_id, node))
+        .collect();
+
+    while let Some(change) = changes.recv().await {
+        // Los cambios de una misma notificación llegan juntos
+        let mut batch = vec![change];
+        while let Ok(change) = changes.try_recv() {
+            batch.push(change);
+        }
+
+        let mut metrics: Vec<SensorMetric> = Vec::new();
+        let mut timestamp = None;
+        for (node_id, value) in batch {
+            let Some(node) = nodes.get(&node_id) else {
+                continue;
+            };
+            if value.status.is_some_and(|status| !status.is_good()) {
+                tracing::debug!(device_id = %device.device_id, node_id = %node_id, "Valor OPC UA sin calidad buena omitido");
+                continue;
+            }
+            let Some(number) = value.value.as_ref().and_then(numeric) else {
+                continue;
+            };
+            timestamp = timestamp.max(value.source_timestamp.map(|t| t.as_chrono()));
+
+            // Si el nodo cambió varias veces en la notificación vale el último valor
+            metrics.retain(|metric| metric.measurement != node.measurement);
+            metrics.push(SensorMetric {
+                measurement: node.measurement.clone(),
+                value: (number * node.scale + node.offset) as f32,
+                unit: node.unit.clone(),
+            });
+        }
+        if metrics.is_empty() {
+            continue;
+        }
+
+        let input = SensorDataInput {
+            header: LocalIngest::header(
+                SOURCE,
+                &device.device_id,
+                &device.location,
+                device.device_type.as_deref(),
+            ),
+            metrics,
+            device_timestamp: timestamp.map(DeviceTimestamp::Date),
+        };
+        if let Err(e) = ingest.submit(input).await {
+            tracing::error!(device_id = %device.device_id, "Error almacenando la lectura OPC UA: {}", e);
+        }
+    }
+}
+
+/// Valor numérico de un nodo; los booleanos valen 0 o 1
+fn numeric(value: &Variant) -> Option<f64> {
+    match value {
+        Variant::Boolean(value) => Some(f64::from(u8::from(*value))),
+        value => value.as_f64(),
+    }
+}
@@ -0,0 +1,221 @@
+use crate::config::Config;
+use crate::database::Database;
+use crate::models::{MeasurementRange, RuleInput};
+use crate::services::edge_processor::EdgeProcessor;
+use crate::services::runtime_config::{RuntimeConfig, RuntimeSettingsPatch};
+use chrono::{DateTime, Utc};
+use rumqttc::{AsyncClient, QoS};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use validator::Validate;
+
+/// Mensajes de configuración remota en espera de aplicarse
+pub const REMOTE_CONFIG_CAPACITY: usize = 16;
+
+/// Mensaje recibido en el topic de configuración del broker cloud
+pub struct RemoteConfigMessage {
+    pub payload: Vec<u8>,
+    /// Cliente del broker cloud con el que se publica el acuse
+    pub client: AsyncClient,
+}
+
+/// Configuración publicada por la plataforma central
+///
+/// Ejemplo: `{"id": "cfg-42", "settings": {"cloud_sync_interval_secs": 60},
+/// "rules": [{"id": "r-1", "name": "...", ...}], "delete_rules": ["r-2"],
+/// "ranges": [{"measurement": "Temperature", "min": -10, "max": 80}]}`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RemoteConfigUpdate {
+    /// Identificador del cambio, devuelto en el acuse
+    #[serde(default)]
+    id: Option<String>,
+
+    /// Ajustes en vivo (mismos campos que `PATCH /api/v1/admin/config`)
+    #[serde(default)]
+    settings: Option<RuntimeSettingsPatch>,
+
+    /// Reglas creadas o reemplazadas con el ID asignado por la plataforma
+    #[serde(default)]
+    rules: Vec<RemoteRule>,
+
+    #[serde(default)]
+    delete_rules: Vec<String>,
+
+    /// Conjunto completo de rangos de validez por medición
+    #[serde(default)]
+    ranges: Option<Vec<MeasurementRange>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RemoteRule {
+    id: String,
+    #[serde(flatten)]
+    rule: RuleInput,
+}
+
+/// Acuse publicado en `gateways/{gateway_id}/config/ack`
+#[derive(Debug, Serialize)]
+struct RemoteConfigAck<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    gateway_id: &'a str,
+    /// `applied` o `rejected`
+    status: &'static str,
+    /// Secciones aplicadas (`settings`, `rules`, `ranges`)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    applied: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    at: DateTime<Utc>,
+}
+
+/// Aplica la configuración enviada por la plataforma central a través del broker cloud
+///
+/// Cada mensaje se valida completo antes de aplicar nada; los cambios se
+/// persisten en SQLite igual que los hechos desde la API de administración.
+pub struct RemoteConfig {
+    config: Arc<Config>,
+    db: Database,
+    runtime_config: Arc<RuntimeConfig>,
+    edge_processor: Arc<EdgeProcessor>,
+}
+
+impl RemoteConfig {
+    pub fn new(
+        config: Arc<Config>,
+        db: Database,
+        runtime_config: Arc<RuntimeConfig>,
+        edge_processor: Arc<EdgeProcessor>,
+    ) -> Self {
+        Self {
+            config,
+            db,
+            runtime_config,
+            edge_processor,
+        }
+    }
+
+    /// Topic de configuración del gateway en el broker cloud
+    pub fn topic(config: &Config) -> String {
+        format!("gateways/{}/config", config.gateway_id)
+    }
+
+    /// Procesa los mensajes recibidos hasta que se cierre el canal
+    pub async fn run(self, mut messages: mpsc::Receiver<RemoteConfigMessage>) {
+        tracing::info!(
+            topic = %Self::topic(&self.config),
+            "Configuración remota habilitada"
+        );
+
+        while let Some(message) = messages.recv().await {
+            self.handle(message).await;
+        }
+    }
+
+    async fn handle(&self, message: RemoteConfigMessage) {
+        let (id, result) = match serde_json::from_slice::<RemoteConfigUpdate>(&message.payload) {
+            Ok(update) => (update.id.clone(), self.apply(update).await),
+            Err(e) => {
+                // Incluir el ID en el acuse aunque el resto del mensaje sea inválido
+                let id = serde_json::from_slice::<serde_json::Value>(&message.payload)
+                    .ok()
+                    .and_then(|value| value.get("id")?.as_str().map(String::from));
+                (
+                    id,
+                    Err(anyhow::anyhow!("Mensaje de configuración inválido: {}", e)),
+                )
+            }
+        };
+
+        let ack = match result {
+            Ok(applied) => {
+                tracing::info!(id = ?id, ?applied, "Configuración remota aplicada");
+                RemoteConfigAck {
+                    id,
+                    gateway_id: &self.config.gateway_id,
+                    status: "applied",
+                    applied,
+                    error: None,
+                    at: Utc::now(),
+                }
+            }
+            Err(e) => {
+                tracing::warn!(id = ?id, error = %e, "Configuración remota rechazada");
+                RemoteConfigAck {
+                    id,
+                    gateway_id: &self.config.gateway_id,
+                    status: "rejected",
+                    applied: Vec::new(),
+                    error: Some(e.to_string()),
+                    at: Utc::now(),
+                }
+            }
+        };
+
+        let topic = format!("{}/ack", Self::topic(&self.config));
+        let published = match serde_json::to_vec(&ack) {
+            Ok(payload) => message
+                .client
+                .publish(&topic, QoS::AtLeastOnce, false, payload)
+                .await
+                .map_err(anyhow::Error::from),
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = published {
+            tracing::error!(
+                "No se pudo publicar el acuse de configuración remota: {}",
+                e
+            );
+        }
+    }
+
+    /// Valida el cambio completo y después lo aplica
+    async fn apply(&self, update: RemoteConfigUpdate) -> anyhow::Result<Vec<&'static str>> {
+        if let Some(settings) = &update.settings {
+            settings.validate()?;
+        }
+        for remote in &update.rules {
+            if remote.id.is_empty() || remote.id.len() > 100 {
+                anyhow::bail!("ID de regla inválido: '{}'", remote.id);
+            }
+            remote
+                .rule
+                .check()
+                .map_err(|e| anyhow::anyhow!("Regla {}: {}", remote.id, e))?;
+        }
+        if let Some(ranges) = &update.ranges {
+            MeasurementRange::check_all(ranges)?;
+        }
+
+        let mut applied = Vec::new();
+
+        if let Some(ranges) = &update.ranges {
+            self.db.replace_measurement_ranges(ranges).await?;
+            self.edge_processor
+                .set_ranges(self.db.list_measurement_ranges().await?);
+            applied.push("ranges");
+        }
+
+        if !update.rules.is_empty() || !update.delete_rules.is_empty() {
+            for remote in &update.rules {
+                if !self.db.update_rule(&remote.id, &remote.rule).await? {
+                    self.db.insert_rule(&remote.id, &remote.rule).await?;
+                }
+            }
+            for id in &update.delete_rules {
+                self.db.delete_rule(id).await?;
+            }
+            self.edge_processor.set_rules(self.db.list_rules().await?);
+            applied.push("rules");
+        }
+
+        if let Some(settings) = &update.settings {
+            self.runtime_config.update(settings).await?;
+            applied.push("settings");
+        }
+
+        Ok(applied)
+    }
+}
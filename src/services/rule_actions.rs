@@ -0,0 +1,114 @@
+use crate::config::Config;
+use crate::models::RuleAction;
+use crate::services::alerting::{AlertContext, Alerting};
+use crate::services::edge_processor::{RuleEvent, RuleTransition};
+use crate::services::gpio::GpioOutputs;
+use rumqttc::{AsyncClient, QoS};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Ejecuta en segundo plano las acciones de las reglas activadas
+/// (publicación MQTT, alertas y salidas GPIO) para no bloquear la ingesta
+pub struct RuleActionExecutor {
+    config: Arc<Config>,
+    alerting: Arc<Alerting>,
+    mqtt_client: AsyncClient,
+    gpio: Arc<GpioOutputs>,
+}
+
+impl RuleActionExecutor {
+    pub fn new(
+        config: Arc<Config>,
+        alerting: Arc<Alerting>,
+        mqtt_client: AsyncClient,
+        gpio: Arc<GpioOutputs>,
+    ) -> Self {
+        Self {
+            config,
+            alerting,
+            mqtt_client,
+            gpio,
+        }
+    }
+
+    /// Consume eventos de reglas hasta que se cierre el canal
+    pub async fn run(self, mut events: mpsc::Receiver<RuleEvent>) {
+        while let Some(event) = events.recv().await {
+            tracing::info!(
+                rule = %event.rule.name,
+                device_id = %event.device_id,
+                transition = ?event.transition,
+                value = event.value,
+                "Regla evaluada con cambio de estado"
+            );
+
+            for action in &event.rule.actions {
+                if let Err(e) = self.execute(action, &event).await {
+                    tracing::error!(
+                        rule = %event.rule.name,
+                        error = %e,
+                        "Error ejecutando acción de regla"
+                    );
+                }
+            }
+        }
+    }
+
+    async fn execute(&self, action: &RuleAction, event: &RuleEvent) -> anyhow::Result<()> {
+        match action {
+            RuleAction::Mqtt { topic } => {
+                let payload = serde_json::json!({
+                    "rule_id": event.rule.id,
+                    "rule_name": event.rule.name,
+                    "state": event.transition,
+                    "gateway_id": self.config.gateway_id,
+                    "device_id": event.device_id,
+                    "location": event.location,
+                    "measurement": event.rule.measurement,
+                    "value": event.value,
+                    "operator": event.rule.operator,
+                    "threshold": event.rule.threshold,
+                    "timestamp": event.timestamp,
+                });
+
+                // try_publish evita bloquear el ejecutor si el broker no está disponible
+                self.mqtt_client.try_publish(
+                    topic,
+                    QoS::AtLeastOnce,
+                    false,
+                    serde_json::to_vec(&payload)?,
+                )?;
+            }
+            RuleAction::Alert { severity } => {
+                let alert = AlertContext::from_rule(
+                    &event.rule,
+                    *severity,
+                    &event.device_id,
+                    &event.location,
+                    event.value as f64,
+                    event.timestamp,
+                );
+                match event.transition {
+                    RuleTransition::Fired => {
+                        self.alerting.fire(&alert).await?;
+                    }
+                    RuleTransition::Resolved => {
+                        self.alerting.resolve(&alert).await?;
+                    }
+                }
+            }
+            RuleAction::Gpio { output } => {
+                self.gpio.set_rule_hold(
+                    output,
+                    &event.rule.id,
+                    &event.device_id,
+                    event.transition == RuleTransition::Fired,
+                )?;
+            }
+            // Se aplica en línea sobre la lectura
+            RuleAction::Flag => {}
+        }
+
+        Ok(())
+    }
+}
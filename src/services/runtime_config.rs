@@ -0,0 +1,258 @@
+use crate::config::Config;
+use crate::database::Database;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{Mutex, watch};
+use validator::Validate;
+
+/// Clave de los overrides persistidos en la tabla de ajustes
+const OVERRIDES_KEY: &str = "runtime_config";
+
+/// Ajustes seguros de cambiar con el gateway en marcha
+#[derive(Debug, Clone, Serialize)]
+pub struct RuntimeSettings {
+    pub cloud_sync_batch_size: u32,
+    pub cloud_sync_interval_secs: u64,
+    pub cloud_mqtt_topic: String,
+    pub data_retention_days: i64,
+    pub alert_retention_days: i64,
+    pub anomaly_zscore_threshold: f32,
+    pub baseline_zscore_threshold: f32,
+}
+
+impl RuntimeSettings {
+    fn from_config(config: &Config) -> Self {
+        Self {
+            cloud_sync_batch_size: config.cloud_sync_batch_size,
+            cloud_sync_interval_secs: config.cloud_sync_interval_secs,
+            cloud_mqtt_topic: config.cloud_mqtt_topic.clone(),
+            data_retention_days: config.data_retention_days,
+            alert_retention_days: config.alert_retention_days,
+            anomaly_zscore_threshold: config.anomaly_zscore_threshold,
+            baseline_zscore_threshold: config.baseline_zscore_threshold,
+        }
+    }
+
+    fn apply(&mut self, patch: &RuntimeSettingsPatch) {
+        if let Some(value) = patch.cloud_sync_batch_size {
+            self.cloud_sync_batch_size = value;
+        }
+        if let Some(value) = patch.cloud_sync_interval_secs {
+            self.cloud_sync_interval_secs = value;
+        }
+        if let Some(value) = &patch.cloud_mqtt_topic {
+            self.cloud_mqtt_topic = value.clone();
+        }
+        if let Some(value) = patch.data_retention_days {
+            self.data_retention_days = value;
+        }
+        if let Some(value) = patch.alert_retention_days {
+            self.alert_retention_days = value;
+        }
+        if let Some(value) = patch.anomaly_zscore_threshold {
+            self.anomaly_zscore_threshold = value;
+        }
+        if let Some(value) = patch.baseline_zscore_threshold {
+            self.baseline_zscore_threshold = value;
+        }
+    }
+}
+
+/// Cambio parcial de ajustes; los campos ausentes conservan su valor
+#[derive(Debug, Default, Clone, Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct RuntimeSettingsPatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1, max = 10000))]
+    pub cloud_sync_batch_size: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1, max = 86400))]
+    pub cloud_sync_interval_secs: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(length(min = 1, max = 200))]
+    pub cloud_mqtt_topic: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1, max = 3650))]
+    pub data_retention_days: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 1, max = 3650))]
+    pub alert_retention_days: Option<i64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 0.5, max = 20.0))]
+    pub anomaly_zscore_threshold: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[validate(range(min = 0.5, max = 20.0))]
+    pub baseline_zscore_threshold: Option<f32>,
+}
+
+impl RuntimeSettingsPatch {
+    /// Combina otro cambio sobre este; los valores del otro tienen prioridad
+    fn merge(&mut self, other: &RuntimeSettingsPatch) {
+        self.cloud_sync_batch_size = other.cloud_sync_batch_size.or(self.cloud_sync_batch_size);
+        self.cloud_sync_interval_secs = other
+            .cloud_sync_interval_secs
+            .or(self.cloud_sync_interval_secs);
+        self.cloud_mqtt_topic = other
+            .cloud_mqtt_topic
+            .clone()
+            .or(self.cloud_mqtt_topic.take());
+        self.data_retention_days = other.data_retention_days.or(self.data_retention_days);
+        self.alert_retention_days = other.alert_retention_days.or(self.alert_retention_days);
+        self.anomaly_zscore_threshold = other
+            .anomaly_zscore_threshold
+            .or(self.anomaly_zscore_threshold);
+        self.baseline_zscore_threshold = other
+            .baseline_zscore_threshold
+            .or(self.baseline_zscore_threshold);
+    }
+}
+
+/// Resultado de recargar el archivo de configuración
+#[derive(Debug, Serialize)]
+pub struct ReloadReport {
+    /// Archivo leído (ninguno si la configuración solo viene del entorno)
+    pub file: Option<PathBuf>,
+    /// Ajustes en vivo cuyo valor vigente cambió
+    pub applied: Vec<String>,
+    /// Variables modificadas que solo se aplican al reiniciar el gateway
+    pub restart_required: Vec<String>,
+    pub settings: RuntimeSettings,
+}
+
+/// Campos de primer nivel cuyo valor difiere entre dos estructuras
+fn changed_fields<T: Serialize>(before: &T, after: &T) -> anyhow::Result<Vec<String>> {
+    let before = serde_json::to_value(before)?;
+    let after = serde_json::to_value(after)?;
+
+    let (Some(before), Some(after)) = (before.as_object(), after.as_object()) else {
+        return Ok(Vec::new());
+    };
+
+    Ok(after
+        .iter()
+        .filter(|(key, value)| before.get(*key) != Some(*value))
+        .map(|(key, _)| key.clone())
+        .collect())
+}
+
+/// Configuración modificable en tiempo de ejecución
+///
+/// Los ajustes parten del archivo de configuración y las variables de entorno,
+/// se les aplican los overrides persistidos en SQLite y se publican en un canal
+/// `watch` que los servicios consultan en cada uso, de modo que los cambios
+/// aplican en vivo.
+pub struct RuntimeConfig {
+    db: Database,
+    /// Configuración con la que arrancó el proceso
+    config: Arc<Config>,
+    defaults: std::sync::Mutex<RuntimeSettings>,
+    overrides: Mutex<RuntimeSettingsPatch>,
+    settings: watch::Sender<RuntimeSettings>,
+}
+
+impl RuntimeConfig {
+    /// Carga los ajustes aplicando los overrides persistidos
+    pub async fn load(config: Arc<Config>, db: Database) -> anyhow::Result<Self> {
+        let defaults = RuntimeSettings::from_config(&config);
+
+        let overrides: RuntimeSettingsPatch = match db.get_setting(OVERRIDES_KEY).await? {
+            Some(json) => serde_json::from_str(&json)?,
+            None => RuntimeSettingsPatch::default(),
+        };
+
+        let mut settings = defaults.clone();
+        settings.apply(&overrides);
+
+        if serde_json::to_value(&overrides)?
+            .as_object()
+            .is_some_and(|o| !o.is_empty())
+        {
+            tracing::info!(?overrides, "Overrides de configuración aplicados");
+        }
+
+        Ok(Self {
+            db,
+            config,
+            defaults: std::sync::Mutex::new(defaults),
+            overrides: Mutex::new(overrides),
+            settings: watch::channel(settings).0,
+        })
+    }
+
+    /// Ajustes vigentes
+    pub fn current(&self) -> RuntimeSettings {
+        self.settings.borrow().clone()
+    }
+
+    /// Suscripción a los ajustes vigentes (para servicios de larga duración)
+    pub fn subscribe(&self) -> watch::Receiver<RuntimeSettings> {
+        self.settings.subscribe()
+    }
+
+    /// Ajustes tomados del archivo de configuración y las variables de entorno
+    pub fn defaults(&self) -> RuntimeSettings {
+        self.defaults.lock().unwrap().clone()
+    }
+
+    /// Overrides persistidos sobre el archivo y las variables de entorno
+    pub async fn overrides(&self) -> RuntimeSettingsPatch {
+        self.overrides.lock().await.clone()
+    }
+
+    /// Aplica un cambio en vivo y lo persiste para sobrevivir reinicios
+    pub async fn update(&self, patch: &RuntimeSettingsPatch) -> anyhow::Result<RuntimeSettings> {
+        let mut overrides = self.overrides.lock().await;
+
+        let mut merged = overrides.clone();
+        merged.merge(patch);
+        self.db
+            .set_setting(OVERRIDES_KEY, &serde_json::to_string(&merged)?)
+            .await?;
+        *overrides = merged;
+
+        let mut settings = self.defaults();
+        settings.apply(&overrides);
+        self.settings.send_replace(settings.clone());
+
+        Ok(settings)
+    }
+
+    /// Vuelve a leer el archivo de configuración y publica los ajustes en vivo
+    ///
+    /// Los overrides persistidos siguen teniendo prioridad. Los cambios en el
+    /// resto de la configuración se informan pero solo aplican al reiniciar.
+    pub async fn reload(&self) -> anyhow::Result<ReloadReport> {
+        let reloaded = self.config.reload()?;
+        let defaults = RuntimeSettings::from_config(&reloaded);
+
+        let overrides = self.overrides.lock().await;
+        let mut settings = defaults.clone();
+        settings.apply(&overrides);
+
+        let applied = changed_fields(&self.current(), &settings)?;
+        *self.defaults.lock().unwrap() = defaults;
+        self.settings.send_replace(settings.clone());
+
+        // Los ajustes en vivo no requieren reinicio aunque cambien en la configuración
+        let live = serde_json::to_value(&settings)?;
+        let restart_required = changed_fields(self.config.as_ref(), &reloaded)?
+            .into_iter()
+            .filter(|field| live.get(field).is_none())
+            .map(|field| field.to_uppercase())
+            .collect();
+
+        Ok(ReloadReport {
+            file: reloaded.config_file,
+            applied,
+            restart_required,
+            settings,
+        })
+    }
+}
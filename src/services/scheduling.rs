@@ -0,0 +1,23 @@
+use chrono::{Duration as ChronoDuration, Local, Timelike};
+use std::time::Duration;
+
+/// Calcula el tiempo restante hasta la próxima ocurrencia de una hora local (0-23)
+/// Se usa para ejecutar tareas pesadas en horario de baja actividad
+pub fn duration_until_local_hour(hour: u32) -> Duration {
+    let now = Local::now();
+    let today_run = now
+        .with_hour(hour)
+        .and_then(|t| t.with_minute(0))
+        .and_then(|t| t.with_second(0))
+        .unwrap_or(now);
+
+    let next_run = if today_run > now {
+        today_run
+    } else {
+        today_run + ChronoDuration::days(1)
+    };
+
+    (next_run - now)
+        .to_std()
+        .unwrap_or(Duration::from_secs(24 * 3600))
+}
@@ -0,0 +1,273 @@
+use crate::config::Config;
+use crate::models::AlertSeverity;
+use crate::services::alerting::{AlertContext, Alerting};
+use crate::services::host_metrics::HostMetrics;
+use crate::telemetry::Telemetry;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+
+/// Regla de la alerta de disco de la base de datos casi lleno
+pub const DISK_FULL_RULE: &str = "system:disk_full";
+
+/// Regla de la alerta de errores repetidos de SQLite
+pub const DB_ERRORS_RULE: &str = "system:db_errors";
+
+/// Regla de la alerta de temperatura del SoC
+pub const SOC_TEMPERATURE_RULE: &str = "system:soc_temperature";
+
+/// Regla de la alerta de deriva del reloj
+pub const CLOCK_DRIFT_RULE: &str = "system:clock_drift";
+
+/// Grados por debajo del umbral que resuelven la alerta de temperatura
+const TEMPERATURE_HYSTERESIS_C: f32 = 5.0;
+
+/// Espera máxima de la respuesta NTP
+const NTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Segundos entre la época NTP (1900) y la época Unix (1970)
+const NTP_UNIX_OFFSET_SECS: i64 = 2_208_988_800;
+
+/// Vigila la salud del propio gateway y la registra como alertas
+///
+/// Comprueba cada `SELF_HEALTH_CHECK_SECS` el disco de la base de datos, la
+/// temperatura del SoC (de la última muestra de métricas del host) y los
+/// errores de SQLite, y cada `CLOCK_DRIFT_CHECK_SECS` la deriva del reloj
+/// respecto de un servidor NTP. Cada alerta se resuelve sola al normalizarse.
+pub struct SelfHealthMonitor {
+    config: Arc<Config>,
+    alerting: Arc<Alerting>,
+    host_metrics: Arc<HostMetrics>,
+    telemetry: Arc<Telemetry>,
+    /// Estado de cada regla; sin entrada hasta la primera comprobación, que
+    /// resuelve las alertas que quedaran activas de la ejecución anterior
+    firing: HashMap<String, bool>,
+    db_errors_seen: u64,
+}
+
+impl SelfHealthMonitor {
+    pub fn new(
+        config: Arc<Config>,
+        alerting: Arc<Alerting>,
+        host_metrics: Arc<HostMetrics>,
+        telemetry: Arc<Telemetry>,
+    ) -> Self {
+        let db_errors_seen = telemetry.db_errors_total();
+        Self {
+            config,
+            alerting,
+            host_metrics,
+            telemetry,
+            firing: HashMap::new(),
+            db_errors_seen,
+        }
+    }
+
+    /// Tarea de comprobación periódica
+    pub async fn start_check_task(mut self) {
+        let mut health =
+            tokio::time::interval(Duration::from_secs(self.config.self_health_check_secs));
+        let mut clock =
+            tokio::time::interval(Duration::from_secs(self.config.clock_drift_check_secs));
+        tracing::info!(
+            interval_secs = self.config.self_health_check_secs,
+            ntp_server = ?self.config.clock_drift_ntp_server,
+            "Vigilancia de la salud del gateway iniciada"
+        );
+
+        loop {
+            tokio::select! {
+                _ = health.tick() => {
+                    if let Err(e) = self.check().await {
+                        tracing::error!("Error comprobando la salud del gateway: {}", e);
+                    }
+                }
+                _ = clock.tick(), if self.config.clock_drift_ntp_server.is_some() => {
+                    if let Err(e) = self.check_clock().await {
+                        tracing::error!("Error comprobando la deriva del reloj: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    async fn check(&mut self) -> anyhow::Result<()> {
+        if let Some(snapshot) = self.host_metrics.latest() {
+            if self.config.host_disk_alert_percent > 0.0
+                && let (Some(total), Some(available)) =
+                    (snapshot.disk_total_bytes, snapshot.disk_available_bytes)
+                && total > 0
+            {
+                let used = (total - available.min(total)) as f64 / total as f64 * 100.0;
+                let threshold = self.config.host_disk_alert_percent;
+                let alert = self.alert(
+                    DISK_FULL_RULE,
+                    "Disco casi lleno",
+                    AlertSeverity::Critical,
+                    "disk_used_percent",
+                    used,
+                    format!(
+                        "uso del disco de {} >= {}%",
+                        snapshot.disk_mount.as_deref().unwrap_or("la base de datos"),
+                        threshold
+                    ),
+                );
+                self.update(used >= threshold as f64, alert).await?;
+            }
+
+            if let Some(temperature) = snapshot.soc_temperature_c {
+                let threshold = self.config.host_temperature_warning_c;
+                let breaching = if self.is_firing(SOC_TEMPERATURE_RULE) {
+                    temperature > threshold - TEMPERATURE_HYSTERESIS_C
+                } else {
+                    temperature >= threshold
+                };
+                let alert = self.alert(
+                    SOC_TEMPERATURE_RULE,
+                    "Temperatura del SoC elevada",
+                    AlertSeverity::Warning,
+                    "soc_temperature_c",
+                    temperature as f64,
+                    format!(
+                        "temperatura del SoC >= {} °C, se resuelve por debajo de {} °C",
+                        threshold,
+                        threshold - TEMPERATURE_HYSTERESIS_C
+                    ),
+                );
+                self.update(breaching, alert).await?;
+            }
+        }
+
+        if self.config.db_error_alert_count > 0 {
+            let total = self.telemetry.db_errors_total();
+            let errors = total - self.db_errors_seen;
+            self.db_errors_seen = total;
+
+            // Una vez disparada, la alerta sigue activa mientras haya errores nuevos
+            let breaching = if self.is_firing(DB_ERRORS_RULE) {
+                errors > 0
+            } else {
+                errors >= self.config.db_error_alert_count
+            };
+            let alert = self.alert(
+                DB_ERRORS_RULE,
+                "Errores de la base de datos",
+                AlertSeverity::Critical,
+                "db_errors",
+                errors as f64,
+                format!(
+                    "{} o más errores de SQLite en {} s",
+                    self.config.db_error_alert_count, self.config.self_health_check_secs
+                ),
+            );
+            self.update(breaching, alert).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn check_clock(&mut self) -> anyhow::Result<()> {
+        let Some(server) = self.config.clock_drift_ntp_server.clone() else {
+            return Ok(());
+        };
+
+        // Sin conexión no hay con qué comparar: se mantiene el estado anterior
+        let drift_ms = match ntp_offset(&server).await {
+            Ok(offset) => offset.num_milliseconds(),
+            Err(e) => {
+                tracing::debug!(server = %server, "Consulta NTP fallida: {}", e);
+                return Ok(());
+            }
+        };
+
+        let max_ms = self.config.clock_drift_max_ms;
+        let alert = self.alert(
+            CLOCK_DRIFT_RULE,
+            "Deriva del reloj",
+            AlertSeverity::Warning,
+            "clock_drift_ms",
+            drift_ms as f64,
+            format!("deriva respecto de {} >= {} ms", server, max_ms),
+        );
+        self.update(drift_ms.unsigned_abs() >= max_ms, alert).await
+    }
+
+    fn is_firing(&self, rule_id: &str) -> bool {
+        self.firing.get(rule_id).copied().unwrap_or(false)
+    }
+
+    /// Dispara o resuelve la alerta cuando cambia su estado
+    async fn update(&mut self, breaching: bool, alert: AlertContext) -> anyhow::Result<()> {
+        if self.firing.insert(alert.rule_id.clone(), breaching) == Some(breaching) {
+            return Ok(());
+        }
+
+        if breaching {
+            self.alerting.fire(&alert).await?;
+        } else {
+            self.alerting.resolve(&alert).await?;
+        }
+        Ok(())
+    }
+
+    fn alert(
+        &self,
+        rule_id: &str,
+        rule_name: &str,
+        severity: AlertSeverity,
+        metric: &str,
+        value: f64,
+        condition: String,
+    ) -> AlertContext {
+        AlertContext {
+            rule_id: rule_id.to_string(),
+            rule_name: rule_name.to_string(),
+            severity,
+            device_id: self.config.gateway_id.clone(),
+            location: None,
+            metric: metric.to_string(),
+            value,
+            condition,
+            at: Utc::now(),
+        }
+    }
+}
+
+/// Diferencia entre la hora del servidor NTP y la local (positiva si el reloj
+/// local atrasa), con una consulta SNTP (RFC 4330)
+async fn ntp_offset(server: &str) -> anyhow::Result<chrono::Duration> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(server).await?;
+
+    // LI = 0, versión 4, modo 3 (cliente)
+    let mut packet = [0u8; 48];
+    packet[0] = 0x23;
+
+    let sent_at = Utc::now();
+    socket.send(&packet).await?;
+    let len = tokio::time::timeout(NTP_TIMEOUT, socket.recv(&mut packet))
+        .await
+        .context("sin respuesta del servidor NTP")??;
+    let received_at = Utc::now();
+
+    anyhow::ensure!(len >= 48, "respuesta NTP incompleta");
+    // Estrato 0: el servidor rechaza la consulta (kiss-o'-death)
+    anyhow::ensure!(packet[1] != 0, "el servidor NTP rechazó la consulta");
+
+    let server_received = ntp_timestamp(&packet[32..40])?;
+    let server_sent = ntp_timestamp(&packet[40..48])?;
+
+    Ok(((server_received - sent_at) + (server_sent - received_at)) / 2)
+}
+
+fn ntp_timestamp(bytes: &[u8]) -> anyhow::Result<DateTime<Utc>> {
+    let seconds = u32::from_be_bytes(bytes[..4].try_into()?) as i64;
+    let fraction = u32::from_be_bytes(bytes[4..].try_into()?) as u64;
+    let nanos = ((fraction * 1_000_000_000) >> 32) as u32;
+
+    DateTime::from_timestamp(seconds - NTP_UNIX_OFFSET_SECS, nanos)
+        .context("marca de tiempo NTP inválida")
+}
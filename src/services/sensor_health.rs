@@ -0,0 +1,247 @@
+use crate::config::Config;
+use crate::models::ProcessedSensorData;
+use crate::services::alerting::{AlertContext, Alerting};
+use crate::services::edge_processor::{BATTERY_MEASUREMENTS, EdgeProcessor, RSSI_MEASUREMENT};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Regla con la que se registran los sensores que repiten siempre el mismo valor
+pub const SENSOR_STUCK_RULE: &str = "system:sensor_stuck";
+
+/// Regla con la que se registran las baterías que requieren atención
+pub const BATTERY_LOW_RULE: &str = "system:battery_low";
+
+/// Valor repetido de una medición
+struct RepeatedValue {
+    value: f32,
+    since: DateTime<Utc>,
+    readings: u32,
+}
+
+/// Estado de salud de los sensores de un dispositivo
+///
+/// El estado de las alertas empieza desconocido (None): tras un reinicio puede
+/// quedar una alerta activa de la ejecución anterior que hay que resolver.
+#[derive(Default)]
+struct DeviceHealth {
+    values: HashMap<String, RepeatedValue>,
+    stuck: BTreeSet<String>,
+    stuck_alert: Option<bool>,
+    battery_alert: Option<bool>,
+}
+
+/// Detecta sensores bloqueados y baterías bajas y los registra como alertas
+///
+/// Un sensor está bloqueado cuando una medición repite exactamente el mismo
+/// valor durante `SENSOR_STUCK_AFTER_SECS` y al menos
+/// `SENSOR_STUCK_MIN_READINGS` lecturas; hay una alerta por dispositivo, que se
+/// resuelve cuando todas sus mediciones vuelven a cambiar. La batería requiere
+/// atención con el mismo criterio que el reporte de `/api/v1/devices/power`.
+pub struct SensorHealthMonitor {
+    config: Arc<Config>,
+    edge_processor: Arc<EdgeProcessor>,
+    alerting: Arc<Alerting>,
+    devices: HashMap<String, DeviceHealth>,
+}
+
+impl SensorHealthMonitor {
+    pub fn new(
+        config: Arc<Config>,
+        edge_processor: Arc<EdgeProcessor>,
+        alerting: Arc<Alerting>,
+    ) -> Self {
+        Self {
+            config,
+            edge_processor,
+            alerting,
+            devices: HashMap::new(),
+        }
+    }
+
+    /// Revisa las lecturas procesadas hasta que se cierre el canal
+    pub async fn run(mut self, mut readings: broadcast::Receiver<Arc<ProcessedSensorData>>) {
+        tracing::info!(
+            stuck_after_secs = self.config.sensor_stuck_after_secs,
+            battery_low_alerts = self.config.battery_low_alerts,
+            "Detección de sensores bloqueados y batería baja iniciada"
+        );
+
+        loop {
+            match readings.recv().await {
+                Ok(data) => {
+                    if let Err(e) = self.observe(&data).await {
+                        tracing::error!(
+                            device_id = %data.header.device_id,
+                            "Error revisando la salud de los sensores: {}",
+                            e
+                        );
+                    }
+                }
+                // Las lecturas siguientes actualizan el estado
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => return,
+            }
+        }
+    }
+
+    async fn observe(&mut self, data: &ProcessedSensorData) -> anyhow::Result<()> {
+        if self.config.sensor_stuck_after_secs > 0 {
+            self.check_stuck(data).await?;
+        }
+        if self.config.battery_low_alerts {
+            self.check_battery(data).await?;
+        }
+        Ok(())
+    }
+
+    async fn check_stuck(&mut self, data: &ProcessedSensorData) -> anyhow::Result<()> {
+        let at = data.gateway_timestamp;
+        let after_secs = self.config.sensor_stuck_after_secs as i64;
+        let min_readings = self.config.sensor_stuck_min_readings;
+        let mut changed = None;
+        let mut newly_stuck = None;
+
+        let device = self
+            .devices
+            .entry(data.header.device_id.clone())
+            .or_default();
+        for metric in &data.metrics {
+            if !metric.value.is_finite() || !watched(&self.config, &metric.measurement) {
+                continue;
+            }
+            let key = metric.measurement.clone();
+
+            let repeated = device.values.entry(key.clone()).or_insert(RepeatedValue {
+                value: metric.value,
+                since: at,
+                readings: 0,
+            });
+            // Un sensor bloqueado repite el valor exacto, sin variaciones de redondeo
+            if repeated.value == metric.value {
+                repeated.readings += 1;
+            } else {
+                *repeated = RepeatedValue {
+                    value: metric.value,
+                    since: at,
+                    readings: 1,
+                };
+                changed = Some((key.clone(), metric.value));
+            }
+
+            if repeated.readings >= min_readings
+                && (at - repeated.since).num_seconds() >= after_secs
+            {
+                if device.stuck.insert(key.clone()) && newly_stuck.is_none() {
+                    newly_stuck = Some((key, metric.value));
+                }
+            } else {
+                device.stuck.remove(&key);
+            }
+        }
+
+        let stuck: Vec<&str> = device.stuck.iter().map(String::as_str).collect();
+        if let Some((measurement, value)) = newly_stuck
+            && device.stuck_alert != Some(true)
+        {
+            let alert = AlertContext {
+                rule_id: SENSOR_STUCK_RULE.to_string(),
+                rule_name: "Sensor bloqueado".to_string(),
+                severity: self.config.sensor_stuck_alert_severity,
+                device_id: data.header.device_id.clone(),
+                location: Some(data.header.location.clone()),
+                metric: measurement,
+                value: value as f64,
+                condition: format!(
+                    "{} sin cambios durante más de {} s y {} lecturas",
+                    stuck.join(", "),
+                    after_secs,
+                    min_readings
+                ),
+                at,
+            };
+            tracing::warn!(device_id = %alert.device_id, measurements = ?stuck, "Sensor bloqueado");
+            device.stuck_alert = Some(true);
+            self.alerting.fire(&alert).await?;
+        } else if stuck.is_empty()
+            && device.stuck_alert != Some(false)
+            // Sin cambios no se sabe si una alerta previa al reinicio sigue vigente
+            && let Some((measurement, value)) = changed
+        {
+            let alert = AlertContext {
+                rule_id: SENSOR_STUCK_RULE.to_string(),
+                rule_name: "Sensor bloqueado".to_string(),
+                severity: self.config.sensor_stuck_alert_severity,
+                device_id: data.header.device_id.clone(),
+                location: Some(data.header.location.clone()),
+                metric: measurement,
+                value: value as f64,
+                condition: "las mediciones vuelven a cambiar".to_string(),
+                at,
+            };
+            device.stuck_alert = Some(false);
+            self.alerting.resolve(&alert).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn check_battery(&mut self, data: &ProcessedSensorData) -> anyhow::Result<()> {
+        let reports_battery = data.metrics.iter().any(|metric| {
+            BATTERY_MEASUREMENTS.contains(&metric.measurement.to_lowercase().as_str())
+        });
+        if !reports_battery {
+            return Ok(());
+        }
+        let Some(level) = self.edge_processor.battery(&data.header.device_id) else {
+            return Ok(());
+        };
+
+        let device = self
+            .devices
+            .entry(data.header.device_id.clone())
+            .or_default();
+        let low = level.attention.is_some();
+        if device.battery_alert == Some(low) {
+            return Ok(());
+        }
+        device.battery_alert = Some(low);
+
+        let alert = AlertContext {
+            rule_id: BATTERY_LOW_RULE.to_string(),
+            rule_name: "Batería baja".to_string(),
+            severity: self.config.battery_low_alert_severity,
+            device_id: data.header.device_id.clone(),
+            location: Some(data.header.location.clone()),
+            metric: level.measurement,
+            value: level.value as f64,
+            condition: level
+                .attention
+                .unwrap_or_else(|| "batería sin riesgo de agotarse".to_string()),
+            at: data.gateway_timestamp,
+        };
+        if low {
+            tracing::warn!(device_id = %alert.device_id, reason = %alert.condition, "Batería baja");
+            self.alerting.fire(&alert).await?;
+        } else {
+            self.alerting.resolve(&alert).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// La medición se vigila por bloqueo
+fn watched(config: &Config, measurement: &str) -> bool {
+    let measurement = measurement.to_lowercase();
+    let measurement = measurement.as_str();
+    if config.sensor_stuck_measurements.is_empty() {
+        !BATTERY_MEASUREMENTS.contains(&measurement) && measurement != RSSI_MEASUREMENT
+    } else {
+        config
+            .sensor_stuck_measurements
+            .iter()
+            .any(|watched| watched == measurement)
+    }
+}
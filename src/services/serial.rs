@@ -0,0 +1,362 @@
+use crate::models::{SensorDataInput, SensorMetric};
+use crate::services::local_ingest::LocalIngest;
+use anyhow::Context as _;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio_serial::{DataBits, Parity, SerialStream, StopBits};
+
+/// Origen de las lecturas serie en el topic (`serial/<device_id>`)
+const SOURCE: &str = "serial";
+
+/// Espera antes de reabrir un puerto que falló o se desconectó
+const RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Longitud máxima de una línea; las más largas se descartan
+const MAX_LINE_BYTES: usize = 4096;
+
+/// Archivo `SERIAL_DEVICES_PATH`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SerialFile {
+    devices: Vec<SerialDevice>,
+}
+
+/// Sensor conectado a un puerto serie del gateway (USB, RS-485, UART)
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SerialDevice {
+    device_id: String,
+    location: String,
+    #[serde(default)]
+    device_type: Option<String>,
+    port: String,
+    #[serde(default = "default_baud_rate")]
+    baud_rate: u32,
+    #[serde(default)]
+    parity: SerialParity,
+    #[serde(default = "default_data_bits")]
+    data_bits: u8,
+    #[serde(default = "default_stop_bits")]
+    stop_bits: u8,
+    format: Format,
+    /// Separación mínima entre lecturas; las líneas intermedias se descartan
+    #[serde(default)]
+    min_interval_secs: u64,
+}
+
+fn default_baud_rate() -> u32 {
+    9600
+}
+
+fn default_data_bits() -> u8 {
+    8
+}
+
+fn default_stop_bits() -> u8 {
+    1
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum SerialParity {
+    #[default]
+    None,
+    Even,
+    Odd,
+}
+
+/// Formato de las líneas que envía el sensor
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+enum Format {
+    /// Un objeto JSON por línea; cada valor numérico es una métrica
+    Json {
+        /// Unidad de cada medición (`{"Temperature": "°C"}`)
+        #[serde(default)]
+        units: HashMap<String, String>,
+    },
+    /// Sentencias tipo NMEA (`$ID,campo1,campo2*checksum`)
+    Nmea { sentences: Vec<Sentence> },
+}
+
+/// Sentencia NMEA y los campos que se leen de ella
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Sentence {
+    /// Identificador tras el `$` (`WIMDA`, `PTHS`...)
+    id: String,
+    fields: Vec<Field>,
+}
+
+/// Campo numérico leído como una métrica (`valor * scale + offset`)
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Field {
+    measurement: String,
+    /// Posición del campo después del identificador (desde 1)
+    index: usize,
+    #[serde(default = "default_scale")]
+    scale: f64,
+    #[serde(default)]
+    offset: f64,
+    #[serde(default)]
+    unit: Option<String>,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+/// Lee los sensores conectados por puerto serie al gateway y los inyecta en el
+/// pipeline como lecturas de dispositivos
+///
+/// Cada puerto de `SERIAL_DEVICES_PATH` pertenece a un dispositivo y envía una
+/// lectura por línea, en JSON o en sentencias tipo NMEA. Un puerto que falla o
+/// se desconecta (adaptador USB retirado) se vuelve a abrir cada 10 segundos.
+pub struct SerialReader {
+    ingest: LocalIngest,
+    devices: Vec<SerialDevice>,
+}
+
+impl SerialReader {
+    /// Lee y valida el archivo de puertos
+    pub fn load(ingest: LocalIngest, path: &str) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("SERIAL_DEVICES_PATH: no se pudo leer {}", path))?;
+        let file: SerialFile = serde_json::from_str(&content)
+            .with_context(|| format!("SERIAL_DEVICES_PATH: {} no es válido", path))?;
+
+        let mut device_ids = HashSet::new();
+        let mut ports = HashSet::new();
+        for device in &file.devices {
+            anyhow::ensure!(
+                !device.device_id.is_empty() && !device.location.is_empty(),
+                "SERIAL_DEVICES_PATH: cada puerto necesita device_id y location"
+            );
+            anyhow::ensure!(
+                device_ids.insert(device.device_id.as_str()),
+                "SERIAL_DEVICES_PATH: device_id repetido: {}",
+                device.device_id
+            );
+            anyhow::ensure!(
+                ports.insert(device.port.as_str()),
+                "SERIAL_DEVICES_PATH: el puerto {} aparece más de una vez",
+                device.port
+            );
+            anyhow::ensure!(
+                matches!(device.data_bits, 7 | 8) && matches!(device.stop_bits, 1 | 2),
+                "SERIAL_DEVICES_PATH: {} admite data_bits 7 u 8 y stop_bits 1 o 2",
+                device.device_id
+            );
+            if let Format::Nmea { sentences } = &device.format {
+                anyhow::ensure!(
+                    !sentences.is_empty()
+                        && sentences.iter().all(|sentence| !sentence.fields.is_empty()),
+                    "SERIAL_DEVICES_PATH: {} necesita sentencias con campos",
+                    device.device_id
+                );
+                anyhow::ensure!(
+                    sentences
+                        .iter()
+                        .flat_map(|sentence| &sentence.fields)
+                        .all(|field| field.index > 0),
+                    "SERIAL_DEVICES_PATH: los campos de {} se numeran desde 1",
+                    device.device_id
+                );
+            }
+        }
+
+        Ok(Self {
+            ingest,
+            devices: file.devices,
+        })
+    }
+
+    /// Inicia una tarea de lectura por puerto
+    pub fn start(self) {
+        tracing::info!(
+            devices = self.devices.len(),
+            "Lectura de sensores por puerto serie iniciada"
+        );
+        for device in self.devices {
+            tokio::spawn(read_task(self.ingest.clone(), device));
+        }
+    }
+}
+
+async fn read_task(ingest: LocalIngest, device: SerialDevice) {
+    let mut failing = false;
+    loop {
+        let stream = match open(&device) {
+            Ok(stream) => {
+                if failing {
+                    tracing::info!(device_id = %device.device_id, port = %device.port, "Puerto serie recuperado");
+                    failing = false;
+                }
+                stream
+            }
+            Err(e) => {
+                // Un adaptador desconectado fallaría en cada intento: solo se avisa al empezar
+                if failing {
+                    tracing::debug!(device_id = %device.device_id, "Error abriendo el puerto serie: {:#}", e);
+                } else {
+                    tracing::warn!(device_id = %device.device_id, port = %device.port, "Error abriendo el puerto serie: {:#}", e);
+                    failing = true;
+                }
+                tokio::time::sleep(RETRY_DELAY).await;
+                continue;
+            }
+        };
+
+        if let Err(e) = read_lines(&ingest, &device, stream).await {
+            tracing::warn!(device_id = %device.device_id, port = %device.port, "Puerto serie cerrado: {:#}", e);
+            failing = true;
+        }
+        tokio::time::sleep(RETRY_DELAY).await;
+    }
+}
+
+fn open(device: &SerialDevice) -> anyhow::Result<SerialStream> {
+    let builder = tokio_serial::new(&device.port, device.baud_rate)
+        .parity(match device.parity {
+            SerialParity::None => Parity::None,
+            SerialParity::Even => Parity::Even,
+            SerialParity::Odd => Parity::Odd,
+        })
+        .data_bits(match device.data_bits {
+            7 => DataBits::Seven,
+            _ => DataBits::Eight,
+        })
+        .stop_bits(match device.stop_bits {
+            2 => StopBits::Two,
+            _ => StopBits::One,
+        });
+    SerialStream::open(&builder).with_context(|| format!("no se pudo abrir {}", device.port))
+}
+
+/// Procesa las líneas del puerto hasta que se cierre o falle
+async fn read_lines(
+    ingest: &LocalIngest,
+    device: &SerialDevice,
+    stream: SerialStream,
+) -> anyhow::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    let mut oversized = false;
+    let mut last_reading: Option<Instant> = None;
+    let min_interval = Duration::from_secs(device.min_interval_secs);
+
+    loop {
+        line.clear();
+        let limit = MAX_LINE_BYTES as u64 + 1;
+        if (&mut reader)
+            .take(limit)
+            .read_until(b'\n', &mut line)
+            .await?
+            == 0
+        {
+            anyhow::bail!("el puerto dejó de enviar datos");
+        }
+        // Una línea demasiado larga (ruido, baudios incorrectos) se descarta entera
+        if line.last() != Some(&b'\n') {
+            oversized = true;
+            continue;
+        }
+        if std::mem::take(&mut oversized) {
+            tracing::debug!(device_id = %device.device_id, "Línea serie de más de {} bytes descartada", MAX_LINE_BYTES);
+            continue;
+        }
+
+        let text = String::from_utf8_lossy(&line);
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+        let metrics = match parse(&device.format, text) {
+            Ok(metrics) => metrics,
+            Err(e) => {
+                tracing::debug!(device_id = %device.device_id, "Línea serie descartada ({}): {}", e, text);
+                continue;
+            }
+        };
+        if metrics.is_empty() || last_reading.is_some_and(|at| at.elapsed() < min_interval) {
+            continue;
+        }
+        last_reading = Some(Instant::now());
+
+        let input = SensorDataInput {
+            header: LocalIngest::header(
+                SOURCE,
+                &device.device_id,
+                &device.location,
+                device.device_type.as_deref(),
+            ),
+            metrics,
+            device_timestamp: None,
+        };
+        if let Err(e) = ingest.submit(input).await {
+            tracing::error!(device_id = %device.device_id, "Error almacenando la lectura serie: {}", e);
+        }
+    }
+}
+
+/// Métricas de una línea; las sentencias NMEA no configuradas no producen ninguna
+fn parse(format: &Format, line: &str) -> anyhow::Result<Vec<SensorMetric>> {
+    match format {
+        Format::Json { units } => {
+            let object: serde_json::Map<String, serde_json::Value> = serde_json::from_str(line)?;
+            Ok(object
+                .into_iter()
+                .filter_map(|(measurement, value)| {
+                    let value = value.as_f64().filter(|value| value.is_finite())?;
+                    Some(SensorMetric {
+                        unit: units.get(&measurement).cloned(),
+                        measurement,
+                        value: value as f32,
+                    })
+                })
+                .collect())
+        }
+        Format::Nmea { sentences } => {
+            let body = line
+                .strip_prefix('$')
+                .context("la sentencia no empieza por $")?;
+            let body = match body.rsplit_once('*') {
+                Some((body, checksum)) => {
+                    let expected = u8::from_str_radix(checksum, 16)
+                        .ok()
+                        .context("checksum inválido")?;
+                    let actual = body.bytes().fold(0, |sum, byte| sum ^ byte);
+                    anyhow::ensure!(
+                        actual == expected,
+                        "checksum {:02X}, se esperaba {:02X}",
+                        actual,
+                        expected
+                    );
+                    body
+                }
+                None => body,
+            };
+
+            let fields: Vec<&str> = body.split(',').collect();
+            let Some(sentence) = sentences.iter().find(|sentence| sentence.id == fields[0]) else {
+                return Ok(Vec::new());
+            };
+            Ok(sentence
+                .fields
+                .iter()
+                .filter_map(|field| {
+                    let value: f64 = fields.get(field.index)?.trim().parse().ok()?;
+                    let value = value * field.scale + field.offset;
+                    value.is_finite().then(|| SensorMetric {
+                        measurement: field.measurement.clone(),
+                        value: value as f32,
+                        unit: field.unit.clone(),
+                    })
+                })
+                .collect())
+        }
+    }
+}
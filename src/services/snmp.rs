@@ -0,0 +1,360 @@
+use crate::config::Config;
+use crate::models::{SensorDataInput, SensorMetric};
+use crate::services::local_ingest::LocalIngest;
+use anyhow::Context as _;
+use serde::Deserialize;
+use snmp2::v3::{Auth, AuthProtocol, Cipher, Security};
+use snmp2::{AsyncSession, Oid, Value};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Origen de las lecturas SNMP en el topic (`snmp/<device_id>`)
+const SOURCE: &str = "snmp";
+
+/// OIDs por solicitud GET, para no superar el tamaño de respuesta de equipos pequeños
+const OIDS_PER_REQUEST: usize = 16;
+
+/// Archivo `SNMP_DEVICES_PATH`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SnmpFile {
+    devices: Vec<SnmpDevice>,
+}
+
+/// Equipo SNMP que se publica como un dispositivo del gateway
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SnmpDevice {
+    device_id: String,
+    location: String,
+    #[serde(default)]
+    device_type: Option<String>,
+    /// `host` o `host:puerto` (161 por defecto)
+    address: String,
+    snmp: Credentials,
+    #[serde(default)]
+    poll_secs: Option<u64>,
+    oids: Vec<OidMetric>,
+}
+
+impl SnmpDevice {
+    fn target(&self) -> String {
+        if self.address.contains(':') {
+            self.address.clone()
+        } else {
+            format!("{}:161", self.address)
+        }
+    }
+}
+
+/// Versión del protocolo y credenciales
+#[derive(Debug, Deserialize)]
+#[serde(tag = "version", rename_all = "lowercase", deny_unknown_fields)]
+enum Credentials {
+    V2c {
+        community: String,
+    },
+    V3 {
+        username: String,
+        #[serde(default)]
+        auth_protocol: AuthAlgorithm,
+        #[serde(default)]
+        auth_password: Option<String>,
+        #[serde(default)]
+        privacy: PrivacyAlgorithm,
+        #[serde(default)]
+        privacy_password: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum AuthAlgorithm {
+    Md5,
+    #[default]
+    Sha1,
+    Sha256,
+    Sha512,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum PrivacyAlgorithm {
+    Des,
+    #[default]
+    Aes128,
+    Aes256,
+}
+
+/// OID leído como una métrica (`valor * scale + offset`)
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct OidMetric {
+    measurement: String,
+    #[serde(deserialize_with = "deserialize_oid")]
+    oid: Oid<'static>,
+    #[serde(default = "default_scale")]
+    scale: f64,
+    #[serde(default)]
+    offset: f64,
+    #[serde(default)]
+    unit: Option<String>,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+/// OID en notación numérica con puntos (`1.3.6.1.2.1.33.1.2.4.0`)
+fn deserialize_oid<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Oid<'static>, D::Error> {
+    let value = String::deserialize(deserializer)?;
+    let invalid = || serde::de::Error::custom(format!("oid inválido: {}", value));
+    let arcs = value
+        .trim_start_matches('.')
+        .split('.')
+        .map(str::parse)
+        .collect::<Result<Vec<u64>, _>>()
+        .map_err(|_| invalid())?;
+    Oid::from(&arcs).map_err(|_| invalid())
+}
+
+/// Consulta periódica por SNMP de UPS, PDU, switches y otros equipos de red
+///
+/// Cada equipo de `SNMP_DEVICES_PATH` se publica como un dispositivo y sus
+/// OIDs como métricas, con el mismo procesamiento que las lecturas MQTT.
+pub struct SnmpPoller {
+    config: Arc<Config>,
+    ingest: LocalIngest,
+    devices: Vec<SnmpDevice>,
+}
+
+impl SnmpPoller {
+    /// Lee y valida el archivo de equipos
+    pub fn load(config: Arc<Config>, ingest: LocalIngest, path: &str) -> anyhow::Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("SNMP_DEVICES_PATH: no se pudo leer {}", path))?;
+        let file: SnmpFile = serde_json::from_str(&content)
+            .with_context(|| format!("SNMP_DEVICES_PATH: {} no es válido", path))?;
+
+        let mut device_ids = HashSet::new();
+        for device in &file.devices {
+            anyhow::ensure!(
+                !device.device_id.is_empty() && !device.location.is_empty(),
+                "SNMP_DEVICES_PATH: cada equipo necesita device_id y location"
+            );
+            anyhow::ensure!(
+                device_ids.insert(device.device_id.as_str()),
+                "SNMP_DEVICES_PATH: device_id repetido: {}",
+                device.device_id
+            );
+            anyhow::ensure!(
+                !device.oids.is_empty(),
+                "SNMP_DEVICES_PATH: {} no tiene OIDs",
+                device.device_id
+            );
+            anyhow::ensure!(
+                device.poll_secs != Some(0),
+                "SNMP_DEVICES_PATH: poll_secs de {} debe ser al menos 1",
+                device.device_id
+            );
+            if let Credentials::V3 {
+                auth_password,
+                privacy_password,
+                ..
+            } = &device.snmp
+            {
+                anyhow::ensure!(
+                    auth_password.is_some() || privacy_password.is_none(),
+                    "SNMP_DEVICES_PATH: {} usa privacy_password sin auth_password",
+                    device.device_id
+                );
+            }
+        }
+
+        Ok(Self {
+            config,
+            ingest,
+            devices: file.devices,
+        })
+    }
+
+    /// Inicia una tarea de consulta por equipo
+    pub fn start(self) {
+        tracing::info!(
+            devices = self.devices.len(),
+            "Consulta de equipos SNMP iniciada"
+        );
+        for device in self.devices {
+            tokio::spawn(poll_task(self.config.clone(), self.ingest.clone(), device));
+        }
+    }
+}
+
+async fn poll_task(config: Arc<Config>, ingest: LocalIngest, device: SnmpDevice) {
+    let timeout = Duration::from_millis(config.snmp_timeout_ms);
+    let mut interval = tokio::time::interval(Duration::from_secs(
+        device.poll_secs.unwrap_or(config.snmp_poll_secs),
+    ));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    let mut session = None;
+    let mut failing = false;
+    loop {
+        interval.tick().await;
+
+        let metrics = match poll(&mut session, &device, timeout).await {
+            Ok(metrics) => {
+                if failing {
+                    tracing::info!(device_id = %device.device_id, "Equipo SNMP recuperado");
+                    failing = false;
+                }
+                metrics
+            }
+            Err(e) => {
+                // Tras un error la sesión se vuelve a abrir (en v3 renegocia el engine)
+                session = None;
+                // Un equipo apagado fallaría en cada consulta: solo se avisa al empezar
+                if failing {
+                    tracing::debug!(device_id = %device.device_id, "Consulta SNMP fallida: {:#}", e);
+                } else {
+                    tracing::warn!(device_id = %device.device_id, address = %device.address, "Consulta SNMP fallida: {:#}", e);
+                    failing = true;
+                }
+                continue;
+            }
+        };
+        if metrics.is_empty() {
+            continue;
+        }
+
+        let input = SensorDataInput {
+            header: LocalIngest::header(
+                SOURCE,
+                &device.device_id,
+                &device.location,
+                device.device_type.as_deref(),
+            ),
+            metrics,
+            device_timestamp: None,
+        };
+        if let Err(e) = ingest.submit(input).await {
+            tracing::error!(device_id = %device.device_id, "Error almacenando la lectura SNMP: {}", e);
+        }
+    }
+}
+
+/// Abre la sesión con el equipo; en v3 descubre su engine ID
+async fn connect(device: &SnmpDevice) -> anyhow::Result<AsyncSession> {
+    let target = device.target();
+    // Cada sesión usa su propio socket: los IDs de solicitud pueden empezar en 1
+    let request_id = 1;
+    match &device.snmp {
+        Credentials::V2c { community } => {
+            Ok(AsyncSession::new_v2c(target, community.as_bytes(), request_id).await?)
+        }
+        Credentials::V3 {
+            username,
+            auth_protocol,
+            auth_password,
+            privacy,
+            privacy_password,
+        } => {
+            let auth = match (auth_password, privacy_password) {
+                (None, _) => Auth::NoAuthNoPriv,
+                (Some(_), None) => Auth::AuthNoPriv,
+                (Some(_), Some(password)) => Auth::AuthPriv {
+                    cipher: match privacy {
+                        PrivacyAlgorithm::Des => Cipher::Des,
+                        PrivacyAlgorithm::Aes128 => Cipher::Aes128,
+                        PrivacyAlgorithm::Aes256 => Cipher::Aes256,
+                    },
+                    privacy_password: password.as_bytes().to_vec(),
+                },
+            };
+            let security = Security::new(
+                username.as_bytes(),
+                auth_password.as_deref().unwrap_or_default().as_bytes(),
+            )
+            .with_auth(auth)
+            .with_auth_protocol(match auth_protocol {
+                AuthAlgorithm::Md5 => AuthProtocol::Md5,
+                AuthAlgorithm::Sha1 => AuthProtocol::Sha1,
+                AuthAlgorithm::Sha256 => AuthProtocol::Sha256,
+                AuthAlgorithm::Sha512 => AuthProtocol::Sha512,
+            });
+
+            let mut session = AsyncSession::new_v3(target, request_id, security).await?;
+            session.init().await?;
+            Ok(session)
+        }
+    }
+}
+
+/// Lee los OIDs del equipo; los que no existen en el agente se omiten
+async fn poll(
+    session: &mut Option<AsyncSession>,
+    device: &SnmpDevice,
+    timeout: Duration,
+) -> anyhow::Result<Vec<SensorMetric>> {
+    let active = match session {
+        Some(active) => active,
+        None => session.insert(
+            tokio::time::timeout(timeout, connect(device))
+                .await
+                .with_context(|| format!("sin respuesta de {}", device.target()))??,
+        ),
+    };
+
+    let mut metrics = Vec::with_capacity(device.oids.len());
+    for chunk in device.oids.chunks(OIDS_PER_REQUEST) {
+        let oids: Vec<&Oid> = chunk.iter().map(|metric| &metric.oid).collect();
+        let response = tokio::time::timeout(timeout, active.get_many(&oids))
+            .await
+            .with_context(|| format!("sin respuesta de {}", device.target()))??;
+        anyhow::ensure!(
+            response.error_status == 0,
+            "el agente respondió con error {} en el OID {}",
+            response.error_status,
+            response.error_index
+        );
+
+        for (oid, value) in response.varbinds {
+            let Some(metric) = chunk.iter().find(|metric| metric.oid == oid) else {
+                continue;
+            };
+            match numeric(&value) {
+                Some(number) if number.is_finite() => metrics.push(SensorMetric {
+                    measurement: metric.measurement.clone(),
+                    value: (number * metric.scale + metric.offset) as f32,
+                    unit: metric.unit.clone(),
+                }),
+                _ => tracing::debug!(
+                    device_id = %device.device_id,
+                    oid = %oid,
+                    measurement = %metric.measurement,
+                    "Valor SNMP no numérico: {:?}",
+                    value
+                ),
+            }
+        }
+    }
+
+    Ok(metrics)
+}
+
+/// Valor numérico de un OID; algunos agentes reportan los números como texto
+fn numeric(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(value) => Some(*value as f64),
+        Value::Counter32(value) | Value::Unsigned32(value) | Value::Timeticks(value) => {
+            Some(f64::from(*value))
+        }
+        Value::Counter64(value) => Some(*value as f64),
+        Value::Boolean(value) => Some(f64::from(u8::from(*value))),
+        Value::OctetString(bytes) => std::str::from_utf8(bytes).ok()?.trim().parse().ok(),
+        _ => None,
+    }
+}
@@ -0,0 +1,149 @@
+use crate::config::Config;
+use crate::services::edge_processor::{EdgeProcessor, UptimeAnchor};
+use chrono::{DateTime, Utc};
+use rumqttc::{AsyncClient, QoS};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Topic de la baliza horaria del gateway
+pub const BEACON_TOPIC: &str = "gateway/time";
+
+/// Solicitud de hora de un dispositivo en `sensors/{id}/time/request`
+///
+/// Ejemplo: `{"request_id": "42", "device_millis": 183204}`; el cuerpo vacío
+/// también es válido.
+#[derive(Debug, Default, Deserialize)]
+struct TimeRequest {
+    /// Identificador que el dispositivo usa para emparejar la respuesta
+    request_id: Option<String>,
+    /// `millis()` del dispositivo al enviar la solicitud
+    device_millis: Option<u64>,
+}
+
+/// Respuesta en `sensors/{id}/time`
+#[derive(Debug, Serialize)]
+struct TimeResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+    /// Eco del `device_millis` de la solicitud, para estimar la latencia
+    #[serde(skip_serializing_if = "Option::is_none")]
+    device_millis: Option<u64>,
+    /// Llegada de la solicitud al gateway (epoch en milisegundos)
+    received_at_ms: i64,
+    /// Hora del gateway al responder (epoch en milisegundos)
+    epoch_ms: i64,
+    at: DateTime<Utc>,
+}
+
+/// Baliza publicada en `gateway/time`
+#[derive(Debug, Serialize)]
+struct TimeBeacon<'a> {
+    gateway_id: &'a str,
+    epoch_ms: i64,
+    at: DateTime<Utc>,
+}
+
+/// Sincronización horaria de los ESP32 sin RTC
+///
+/// El gateway publica su hora periódicamente en `gateway/time` y responde las
+/// solicitudes de `sensors/{id}/time/request` en `sensors/{id}/time`. El
+/// `device_millis` de cada solicitud queda como referencia para convertir las
+/// marcas basadas en `millis()` de las lecturas acumuladas offline.
+pub struct TimeSync {
+    config: Arc<Config>,
+    client: AsyncClient,
+    edge_processor: Arc<EdgeProcessor>,
+}
+
+impl TimeSync {
+    pub fn new(
+        config: Arc<Config>,
+        client: AsyncClient,
+        edge_processor: Arc<EdgeProcessor>,
+    ) -> Self {
+        Self {
+            config,
+            client,
+            edge_processor,
+        }
+    }
+
+    /// Topic de la respuesta para un dispositivo
+    pub fn topic(device_id: &str) -> String {
+        format!("sensors/{}/time", device_id)
+    }
+
+    /// Responde la solicitud de hora de un dispositivo
+    pub fn answer(&self, device_id: &str, payload: &[u8]) -> anyhow::Result<()> {
+        let received_at = Utc::now();
+        let request: TimeRequest = if payload.iter().all(u8::is_ascii_whitespace) {
+            TimeRequest::default()
+        } else {
+            serde_json::from_slice(payload)?
+        };
+
+        // La latencia del broker local es despreciable frente a la tolerancia de las marcas
+        if let Some(uptime_ms) = request.device_millis {
+            self.edge_processor.record_time_sync(
+                device_id,
+                UptimeAnchor {
+                    uptime_ms: uptime_ms as f64,
+                    at: received_at,
+                },
+            );
+        }
+
+        let now = Utc::now();
+        let response = TimeResponse {
+            request_id: request.request_id,
+            device_millis: request.device_millis,
+            received_at_ms: received_at.timestamp_millis(),
+            epoch_ms: now.timestamp_millis(),
+            at: now,
+        };
+
+        // try_publish evita bloquear el loop MQTT, desde el que se atienden las solicitudes
+        self.client.try_publish(
+            Self::topic(device_id),
+            QoS::AtMostOnce,
+            false,
+            serde_json::to_vec(&response)?,
+        )?;
+        tracing::debug!(device_id = %device_id, "Hora enviada al dispositivo");
+        Ok(())
+    }
+
+    /// Tarea de publicación periódica de la baliza horaria
+    pub async fn start_beacon_task(self: Arc<Self>) {
+        let mut interval =
+            tokio::time::interval(Duration::from_secs(self.config.time_sync_interval_secs));
+        tracing::info!(topic = BEACON_TOPIC, "Baliza horaria iniciada");
+
+        loop {
+            interval.tick().await;
+
+            let now = Utc::now();
+            let beacon = TimeBeacon {
+                gateway_id: &self.config.gateway_id,
+                epoch_ms: now.timestamp_millis(),
+                at: now,
+            };
+            let payload = match serde_json::to_vec(&beacon) {
+                Ok(payload) => payload,
+                Err(e) => {
+                    tracing::error!("Error serializando la baliza horaria: {}", e);
+                    continue;
+                }
+            };
+
+            // No se retiene: una hora antigua sería peor que ninguna
+            if let Err(e) = self
+                .client
+                .try_publish(BEACON_TOPIC, QoS::AtMostOnce, false, payload)
+            {
+                tracing::warn!("No se pudo publicar la baliza horaria: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,369 @@
+use crate::error::AppError;
+use crate::models::{DeviceTimestamp, SensorDataInput, SensorHeader, SensorMetric};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+/// Registros máximos por solicitud, igual que en `/api/v1/sensor/batch`
+const MAX_RECORDS: usize = 100;
+
+/// Mapeo de los webhooks de una fuente (`<fuente>.json`)
+///
+/// Cada campo es una ruta JSON (`$.a.b[0]['c d']`) o, si no empieza por `$`,
+/// un valor fijo. Con `records` cada elemento del arreglo indicado es una
+/// lectura y las rutas se evalúan sobre él; sin `records` el payload completo
+/// es una sola lectura.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookMapping {
+    #[serde(default)]
+    records: Option<JsonPath>,
+    device_id: Field,
+    location: Field,
+    #[serde(default)]
+    device_type: Option<Field>,
+    #[serde(default)]
+    message_id: Option<Field>,
+    #[serde(default)]
+    timestamp: Option<Field>,
+    #[serde(default)]
+    rssi: Option<Field>,
+    metrics: Vec<MetricMapping>,
+}
+
+/// Métrica extraída del payload, con conversión lineal opcional (`valor * scale + offset`)
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MetricMapping {
+    measurement: String,
+    path: JsonPath,
+    #[serde(default)]
+    unit: Option<String>,
+    #[serde(default = "default_scale")]
+    scale: f64,
+    #[serde(default)]
+    offset: f64,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+/// Ruta JSON o valor fijo
+#[derive(Debug)]
+enum Field {
+    Path(JsonPath),
+    Literal(String),
+}
+
+impl<'de> Deserialize<'de> for Field {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        if value.starts_with('$') {
+            JsonPath::parse(&value)
+                .map(Field::Path)
+                .map_err(serde::de::Error::custom)
+        } else {
+            Ok(Field::Literal(value))
+        }
+    }
+}
+
+impl Field {
+    /// Valor del campo como texto (los números se convierten)
+    fn text(&self, record: &Value) -> Option<String> {
+        match self {
+            Field::Literal(value) => Some(value.clone()),
+            Field::Path(path) => match path.select(record)? {
+                Value::String(value) => Some(value.clone()),
+                Value::Number(value) => Some(value.to_string()),
+                _ => None,
+            },
+        }
+    }
+
+    fn value(&self, record: &Value) -> Option<Value> {
+        match self {
+            Field::Literal(value) => Some(Value::String(value.clone())),
+            Field::Path(path) => path.select(record).cloned(),
+        }
+    }
+
+    fn describe(&self) -> &str {
+        match self {
+            Field::Path(path) => &path.source,
+            Field::Literal(value) => value,
+        }
+    }
+}
+
+/// Segmento de una ruta JSON
+#[derive(Debug)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// Subconjunto de JSONPath: claves (`.clave` o `['clave']`) e índices (`[n]`)
+#[derive(Debug)]
+struct JsonPath {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+impl<'de> Deserialize<'de> for JsonPath {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        JsonPath::parse(&value).map_err(serde::de::Error::custom)
+    }
+}
+
+impl JsonPath {
+    fn parse(source: &str) -> Result<Self, String> {
+        let invalid = |reason: &str| format!("ruta JSON inválida {}: {}", source, reason);
+        let Some(mut rest) = source.strip_prefix('$') else {
+            return Err(invalid("debe empezar por $"));
+        };
+
+        let mut segments = Vec::new();
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                if end == 0 {
+                    return Err(invalid("clave vacía"));
+                }
+                segments.push(Segment::Key(after[..end].to_string()));
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']').ok_or_else(|| invalid("falta ]"))?;
+                let inner = &after[..end];
+                let quoted = inner
+                    .strip_prefix('\'')
+                    .and_then(|key| key.strip_suffix('\''))
+                    .or_else(|| {
+                        inner
+                            .strip_prefix('"')
+                            .and_then(|key| key.strip_suffix('"'))
+                    });
+                segments.push(match quoted {
+                    Some(key) => Segment::Key(key.to_string()),
+                    None => Segment::Index(inner.parse().map_err(|_| {
+                        invalid("se esperaba un índice o una clave entre comillas")
+                    })?),
+                });
+                rest = &after[end + 1..];
+            } else {
+                return Err(invalid("se esperaba . o ["));
+            }
+        }
+
+        Ok(Self {
+            source: source.to_string(),
+            segments,
+        })
+    }
+
+    /// Valor en la ruta; None si no existe o es null
+    fn select<'a>(&self, value: &'a Value) -> Option<&'a Value> {
+        let mut current = value;
+        for segment in &self.segments {
+            current = match segment {
+                Segment::Key(key) => current.get(key)?,
+                Segment::Index(index) => current.get(index)?,
+            };
+        }
+        Some(current).filter(|value| !value.is_null())
+    }
+}
+
+impl WebhookMapping {
+    /// Convierte el payload de la fuente en lecturas del gateway
+    ///
+    /// Los registros sin ninguna de las métricas del mapeo se omiten; falta de
+    /// identificación o un payload sin métricas es un error de validación.
+    pub fn readings(
+        &self,
+        source: &str,
+        payload: &Value,
+    ) -> Result<Vec<SensorDataInput>, AppError> {
+        let records: Vec<&Value> = match &self.records {
+            None => vec![payload],
+            Some(path) => match path.select(payload) {
+                Some(Value::Array(records)) => records.iter().collect(),
+                Some(record @ Value::Object(_)) => vec![record],
+                _ => {
+                    return Err(AppError::ValidationError(format!(
+                        "El payload no contiene registros en {}",
+                        path.source
+                    )));
+                }
+            },
+        };
+        if records.len() > MAX_RECORDS {
+            return Err(AppError::ValidationError(format!(
+                "El payload contiene {} registros; el máximo es {}",
+                records.len(),
+                MAX_RECORDS
+            )));
+        }
+
+        let mut readings = Vec::with_capacity(records.len());
+        for (index, record) in records.into_iter().enumerate() {
+            let metrics = self.metrics(record);
+            if metrics.is_empty() {
+                continue;
+            }
+
+            let required = |field: &Field, name: &str| {
+                field.text(record).ok_or_else(|| {
+                    AppError::ValidationError(format!(
+                        "Registro {}: falta {} ({})",
+                        index,
+                        name,
+                        field.describe()
+                    ))
+                })
+            };
+            let device_id = required(&self.device_id, "device_id")?;
+            let location = required(&self.location, "location")?;
+
+            let device_timestamp = match self.timestamp.as_ref().and_then(|f| f.value(record)) {
+                Some(value) => Some(serde_json::from_value::<DeviceTimestamp>(value).map_err(
+                    |_| {
+                        AppError::ValidationError(format!(
+                            "Registro {}: marca de tiempo inválida",
+                            index
+                        ))
+                    },
+                )?),
+                None => None,
+            };
+
+            readings.push(SensorDataInput {
+                header: SensorHeader {
+                    user_uuid: None,
+                    topic: format!("webhook/{}", source),
+                    device_id,
+                    device_type: self.device_type.as_ref().and_then(|f| f.text(record)),
+                    message_id: self.message_id.as_ref().and_then(|f| f.text(record)),
+                    location,
+                    should_requeue: false,
+                    firmware_version: None,
+                    hardware_model: None,
+                    rssi: self
+                        .rssi
+                        .as_ref()
+                        .and_then(|f| f.value(record))
+                        .and_then(|value| value.as_f64())
+                        .map(|rssi| rssi.round() as i32),
+                    sequence: None,
+                    sent_at: None,
+                },
+                metrics,
+                device_timestamp,
+            });
+        }
+
+        if readings.is_empty() {
+            return Err(AppError::ValidationError(
+                "El payload no contiene ninguna métrica del mapeo".to_string(),
+            ));
+        }
+        Ok(readings)
+    }
+
+    fn metrics(&self, record: &Value) -> Vec<SensorMetric> {
+        self.metrics
+            .iter()
+            .filter_map(|mapping| {
+                let value = match mapping.path.select(record)? {
+                    Value::Number(value) => value.as_f64()?,
+                    Value::String(value) => value.trim().parse().ok()?,
+                    Value::Bool(value) => f64::from(u8::from(*value)),
+                    _ => return None,
+                };
+                Some(SensorMetric {
+                    measurement: mapping.measurement.clone(),
+                    value: (value * mapping.scale + mapping.offset) as f32,
+                    unit: mapping.unit.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Mapeos de las fuentes de webhooks, leídos de `WEBHOOK_MAPPINGS_DIR`
+///
+/// Se cargan al arrancar y al recargar la configuración (SIGHUP o
+/// `POST /api/v1/admin/reload`). Un archivo con errores conserva su versión
+/// anterior.
+pub struct WebhookMappings {
+    dir: PathBuf,
+    mappings: RwLock<HashMap<String, Arc<WebhookMapping>>>,
+}
+
+impl WebhookMappings {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            mappings: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Mapeo de la fuente, si está configurada
+    pub fn get(&self, source: &str) -> Option<Arc<WebhookMapping>> {
+        self.mappings.read().unwrap().get(source).cloned()
+    }
+
+    /// Vuelve a leer los archivos del directorio
+    pub fn reload(&self) {
+        let Ok(entries) = std::fs::read_dir(&self.dir) else {
+            self.mappings.write().unwrap().clear();
+            return;
+        };
+
+        let previous = self.mappings.read().unwrap().clone();
+        let mut loaded = HashMap::new();
+        for path in entries.flatten().map(|entry| entry.path()) {
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(source) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+
+            let parsed = std::fs::read_to_string(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|content| {
+                    serde_json::from_str::<WebhookMapping>(&content).map_err(|e| e.to_string())
+                });
+            match parsed {
+                Ok(mapping) if mapping.metrics.is_empty() => tracing::error!(
+                    path = %path.display(),
+                    "Mapeo de webhook sin métricas"
+                ),
+                Ok(mapping) => {
+                    loaded.insert(source.to_string(), Arc::new(mapping));
+                    continue;
+                }
+                Err(e) => tracing::error!(
+                    path = %path.display(),
+                    error = %e,
+                    "Error leyendo el mapeo de webhook"
+                ),
+            }
+            if let Some(mapping) = previous.get(source) {
+                loaded.insert(source.to_string(), mapping.clone());
+            }
+        }
+
+        if !loaded.is_empty() {
+            let mut sources: Vec<&str> = loaded.keys().map(String::as_str).collect();
+            sources.sort_unstable();
+            tracing::info!(sources = ?sources, "Mapeos de webhooks cargados");
+        }
+        *self.mappings.write().unwrap() = loaded;
+    }
+}
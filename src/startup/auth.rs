@@ -0,0 +1,292 @@
+use super::lockout::AuthLockout;
+use crate::{config::Config, database::Database, error::AppError};
+use axum::{
+    extract::{ConnectInfo, OriginalUri, Query, Request, State},
+    http::{HeaderMap, Method, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Rol de un cliente de la API; cada rol incluye los permisos de los anteriores
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Role {
+    /// Consulta datos
+    Viewer,
+    /// Además sincroniza y envía órdenes a los dispositivos
+    Operator,
+    /// Además edita configuración, reglas y dispositivos
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+/// Cliente autenticado de una solicitud (disponible como extensión)
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub name: String,
+    pub role: Role,
+}
+
+/// Tokens de la API y el cliente que identifica cada uno
+pub struct ApiTokens {
+    clients: Vec<(String, Principal)>,
+    /// Hay tokens de viewer: las consultas dejan de ser públicas
+    viewers: bool,
+    /// Hay ADMIN_API_TOKEN: las operaciones exigen token
+    admin: bool,
+}
+
+impl ApiTokens {
+    pub fn new(config: &Config) -> Self {
+        let mut clients = Vec::new();
+        for (tokens, role) in [
+            (&config.viewer_api_tokens, Role::Viewer),
+            (&config.operator_api_tokens, Role::Operator),
+        ] {
+            for entry in tokens {
+                let principal = Principal {
+                    name: entry.name.clone(),
+                    role,
+                };
+                clients.push((entry.token.clone(), principal));
+            }
+        }
+        if let Some(token) = &config.admin_api_token {
+            let principal = Principal {
+                name: Role::Admin.as_str().to_string(),
+                role: Role::Admin,
+            };
+            clients.push((token.clone(), principal));
+        }
+
+        Self {
+            clients,
+            viewers: !config.viewer_api_tokens.is_empty(),
+            admin: config.admin_api_token.is_some(),
+        }
+    }
+
+    /// Si las rutas del rol exigen token
+    ///
+    /// Las consultas solo se protegen con `VIEWER_API_TOKENS`, para no romper
+    /// los clientes existentes que leen sin token.
+    fn protects(&self, role: Role) -> bool {
+        match role {
+            Role::Viewer => self.viewers,
+            Role::Operator | Role::Admin => self.admin,
+        }
+    }
+
+    /// Cliente al que pertenece el token (se comparan todos para no filtrar cuál coincide)
+    fn authenticate(&self, token: &str) -> Option<&Principal> {
+        self.clients
+            .iter()
+            .fold(None, |found, (expected, principal)| {
+                let matches = constant_time_eq(token.as_bytes(), expected.as_bytes());
+                found.or(matches.then_some(principal))
+            })
+    }
+}
+
+/// Control de acceso de un grupo de rutas por rol
+#[derive(Clone)]
+pub struct RoleAuth {
+    tokens: Arc<ApiTokens>,
+    lockout: Arc<AuthLockout>,
+    role: Role,
+    /// Rechazar las solicitudes si no hay token configurado (operaciones destructivas)
+    required: bool,
+    /// Registro de auditoría de las operaciones que modifican el gateway
+    audit: Option<Database>,
+    /// Aceptar el token en `?access_token=` (solo streams del navegador)
+    query_token: bool,
+}
+
+impl RoleAuth {
+    /// Protege las rutas solo si hay tokens configurados (compatibilidad con
+    /// instalaciones existentes sin token)
+    pub fn optional(tokens: &Arc<ApiTokens>, lockout: &Arc<AuthLockout>, role: Role) -> Self {
+        Self {
+            tokens: tokens.clone(),
+            lockout: lockout.clone(),
+            role,
+            required: false,
+            audit: None,
+            query_token: false,
+        }
+    }
+
+    /// Exige siempre un token configurado y válido
+    pub fn required(tokens: &Arc<ApiTokens>, lockout: &Arc<AuthLockout>, role: Role) -> Self {
+        Self {
+            required: true,
+            ..Self::optional(tokens, lockout, role)
+        }
+    }
+
+    /// Registra en la auditoría las solicitudes que no son consultas
+    pub fn audited(self, db: &Database) -> Self {
+        Self {
+            audit: Some(db.clone()),
+            ..self
+        }
+    }
+
+    /// Acepta el token en la URL para WebSocket y EventSource, que no envían
+    /// cabeceras; en el resto de las rutas quedaría en el log de solicitudes
+    pub fn with_query_token(self) -> Self {
+        Self {
+            query_token: true,
+            ..self
+        }
+    }
+
+    /// Los tokens inválidos cuentan para el bloqueo de la IP y del grupo de rutas
+    fn authorize(&self, client: IpAddr, request: &Request) -> Result<Principal, AppError> {
+        if !self.tokens.protects(self.role) {
+            if self.required {
+                return Err(AppError::Forbidden(
+                    "Operación deshabilitada: configure ADMIN_API_TOKEN".to_string(),
+                ));
+            }
+            return Ok(Principal {
+                name: ANONYMOUS.to_string(),
+                role: self.role,
+            });
+        }
+
+        let key = format!("api:{}", self.role.as_str());
+        self.lockout.check_ip(client)?;
+
+        let token = bearer_token(request.headers())
+            .map(String::from)
+            .or_else(|| query_token(request).filter(|_| self.query_token))
+            .ok_or_else(|| AppError::Unauthorized("Se requiere token de acceso".to_string()))?;
+        let principal = self.tokens.authenticate(&token);
+        self.lockout.check_key(client, &key, principal.is_some())?;
+        let Some(principal) = principal else {
+            self.lockout
+                .record_failure(client, &key, "token de API inválido");
+            return Err(AppError::Unauthorized(
+                "Token de acceso inválido".to_string(),
+            ));
+        };
+        self.lockout.record_success(client, &key);
+
+        if principal.role < self.role {
+            return Err(AppError::Forbidden(format!(
+                "El rol {} no permite esta operación (requiere {})",
+                principal.role.as_str(),
+                self.role.as_str()
+            )));
+        }
+        Ok(principal.clone())
+    }
+}
+
+/// Cliente registrado cuando las rutas no exigen token
+const ANONYMOUS: &str = "anónimo";
+
+/// Genera el secreto de un dispositivo; retorna el secreto y su hash
+///
+/// Solo se guarda el hash: el secreto se entrega una única vez al provisionar.
+pub fn issue_device_secret() -> (String, String) {
+    let secret = format!("{}{}", Uuid::new_v4().simple(), Uuid::new_v4().simple());
+    let hash = hash_device_secret(&secret);
+    (secret, hash)
+}
+
+/// Hash SHA-256 (hex) con el que se guarda el secreto de un dispositivo
+pub fn hash_device_secret(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
+
+/// Token de `Authorization: Bearer <token>`
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Token del parámetro `access_token`, para clientes que no pueden enviar
+/// cabeceras (WebSocket y EventSource del navegador)
+fn query_token(request: &Request) -> Option<String> {
+    Query::<HashMap<String, String>>::try_from_uri(request.uri())
+        .ok()?
+        .0
+        .remove("access_token")
+        .filter(|token| !token.is_empty())
+}
+
+/// Compara en tiempo constante para no filtrar el token por tiempos de respuesta
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Middleware que exige un token con al menos el rol del grupo de rutas
+///
+/// El cliente queda como extensión `Principal` de la solicitud y, si el grupo
+/// se audita, cada operación que no es una consulta se registra con su resultado.
+pub async fn require_role(
+    State(auth): State<RoleAuth>,
+    ConnectInfo(client): ConnectInfo<SocketAddr>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let principal = match auth.authorize(client.ip(), &request) {
+        Ok(principal) => principal,
+        Err(e) => return e.into_response(),
+    };
+
+    let method = request.method().clone();
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|uri| uri.path().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    request.extensions_mut().insert(principal.clone());
+
+    let response = next.run(request).await;
+
+    if let Some(db) = &auth.audit
+        && !matches!(method, Method::GET | Method::HEAD | Method::OPTIONS)
+    {
+        tracing::info!(
+            principal = %principal.name,
+            role = principal.role.as_str(),
+            method = %method,
+            path = %path,
+            status = response.status().as_u16(),
+            "Operación de la API"
+        );
+        if let Err(e) = db
+            .record_audit(
+                &principal.name,
+                principal.role.as_str(),
+                method.as_str(),
+                &path,
+                response.status().as_u16(),
+            )
+            .await
+        {
+            tracing::error!("Error registrando la auditoría: {}", e);
+        }
+    }
+
+    response
+}
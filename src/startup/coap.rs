@@ -0,0 +1,305 @@
+//! Servidor CoAP (RFC 7252) para nodos con recursos limitados
+//!
+//! Acepta las mismas lecturas que la API HTTP, en JSON o CBOR, y las pasa por
+//! el mismo procesamiento (autenticación, duplicados, cuarentena, cuotas,
+//! edge computing y almacenamiento).
+
+use crate::{
+    error::AppError,
+    handlers::sensor::{ingest_reading, ingest_readings},
+    models::{SensorDataBatch, SensorDataInput},
+    startup::state::AppState,
+};
+use coap_lite::{
+    CoapOption, CoapRequest, ContentFormat, MessageClass, Packet, RequestType as Method,
+    ResponseType as Status,
+};
+use lru::LruCache;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, Mutex};
+use tokio::net::UdpSocket;
+use tokio::sync::Semaphore;
+use validator::Validate;
+
+/// Solicitudes atendidas en paralelo; el resto espera en el socket
+const MAX_CONCURRENT_REQUESTS: usize = 32;
+
+/// Intercambios recordados para responder retransmisiones sin reprocesarlas
+const EXCHANGE_CACHE_SIZE: usize = 256;
+
+/// Espacio para cabecera, token y opciones además del payload
+const HEADER_ROOM_BYTES: usize = 1024;
+
+/// Recursos anunciados en `/.well-known/core` (RFC 6690)
+const CORE_LINKS: &str =
+    r#"</sensor/data>;rt="sensor-data";ct="50 60",</sensor/batch>;rt="sensor-batch";ct="50 60""#;
+
+/// Respuesta de cada intercambio por cliente e ID de mensaje; None mientras se procesa
+type ExchangeCache = Mutex<LruCache<(SocketAddr, u16), Option<Vec<u8>>>>;
+
+/// Formato del payload según la opción Content-Format
+#[derive(Clone, Copy)]
+enum Format {
+    Json,
+    Cbor,
+}
+
+impl Format {
+    fn content_format(self) -> ContentFormat {
+        match self {
+            Format::Json => ContentFormat::ApplicationJSON,
+            Format::Cbor => ContentFormat::ApplicationCBOR,
+        }
+    }
+}
+
+/// Respuesta CoAP: código, formato y payload
+type Reply = (Status, Option<ContentFormat>, Vec<u8>);
+
+/// Atiende solicitudes CoAP en `COAP_PORT` hasta que falle el socket
+pub async fn serve(state: AppState) -> anyhow::Result<()> {
+    let addr = format!("0.0.0.0:{}", state.config.coap_port);
+    let socket = Arc::new(UdpSocket::bind(&addr).await?);
+    tracing::info!("Servidor CoAP escuchando en {}", addr);
+
+    let exchanges: Arc<ExchangeCache> = Arc::new(Mutex::new(LruCache::new(
+        NonZeroUsize::new(EXCHANGE_CACHE_SIZE).unwrap(),
+    )));
+    let permits = Arc::new(Semaphore::new(MAX_CONCURRENT_REQUESTS));
+    let mut buf = vec![0u8; state.config.coap_max_payload_bytes + HEADER_ROOM_BYTES];
+
+    loop {
+        let (len, peer) = socket.recv_from(&mut buf).await?;
+        let packet = match Packet::from_bytes(&buf[..len]) {
+            Ok(packet) => packet,
+            Err(e) => {
+                tracing::debug!(peer = %peer, "Mensaje CoAP inválido descartado: {}", e);
+                continue;
+            }
+        };
+        // Solo se atienden solicitudes; ACK y RST vacíos no requieren respuesta
+        if !matches!(packet.header.code, MessageClass::Request(_)) {
+            continue;
+        }
+
+        // Una retransmisión recibe la misma respuesta, o nada si aún se procesa
+        let key = (peer, packet.header.message_id);
+        let cached = {
+            let mut exchanges = exchanges.lock().unwrap();
+            let cached = exchanges.get(&key).cloned();
+            if cached.is_none() {
+                exchanges.put(key, None);
+            }
+            cached
+        };
+        if let Some(response) = cached {
+            if let Some(response) = response {
+                let _ = socket.send_to(&response, peer).await;
+            }
+            continue;
+        }
+
+        let permit = permits.clone().acquire_owned().await?;
+        let state = state.clone();
+        let socket = socket.clone();
+        let exchanges = exchanges.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            let Some(response) = handle(&state, packet, peer).await else {
+                return;
+            };
+
+            match response.to_bytes() {
+                Ok(bytes) => {
+                    if let Err(e) = socket.send_to(&bytes, peer).await {
+                        tracing::warn!(peer = %peer, "Error enviando la respuesta CoAP: {}", e);
+                    }
+                    exchanges.lock().unwrap().put(key, Some(bytes));
+                }
+                Err(e) => tracing::error!("Error codificando la respuesta CoAP: {:?}", e),
+            }
+        });
+    }
+}
+
+/// Atiende una solicitud y construye la respuesta (piggybacked en el ACK si es confirmable)
+async fn handle(state: &AppState, packet: Packet, peer: SocketAddr) -> Option<Packet> {
+    let mut request = CoapRequest::from_packet(packet, peer);
+    let mut response = request.response.take()?;
+
+    let (status, content_format, payload) = route(state, &request, peer).await;
+    tracing::debug!(
+        peer = %peer,
+        path = %request.get_path(),
+        status = ?status,
+        "Solicitud CoAP atendida"
+    );
+
+    response.set_status(status);
+    if let Some(content_format) = content_format {
+        response.message.set_content_format(content_format);
+    }
+    response.message.payload = payload;
+    Some(response.message)
+}
+
+async fn route(state: &AppState, request: &CoapRequest<SocketAddr>, peer: SocketAddr) -> Reply {
+    let path = request.get_path();
+    match (request.get_method(), path.as_str()) {
+        (Method::Get, ".well-known/core") => (
+            Status::Content,
+            Some(ContentFormat::ApplicationLinkFormat),
+            CORE_LINKS.as_bytes().to_vec(),
+        ),
+        (Method::Post, "sensor/data" | "sensor/batch") => {
+            let Some(format) = payload_format(&request.message) else {
+                return diagnostic(
+                    Status::UnsupportedContentFormat,
+                    "Content-Format admitido: application/json (50) o application/cbor (60)",
+                );
+            };
+            if request.message.payload.len() > state.config.coap_max_payload_bytes {
+                return diagnostic(
+                    Status::RequestEntityTooLarge,
+                    &format!(
+                        "El payload supera {} bytes",
+                        state.config.coap_max_payload_bytes
+                    ),
+                );
+            }
+
+            let token = uri_query(&request.message, "token");
+            let result = if path == "sensor/data" {
+                ingest_single(
+                    state,
+                    token.as_deref(),
+                    peer,
+                    &request.message.payload,
+                    format,
+                )
+                .await
+            } else {
+                ingest_batch(
+                    state,
+                    token.as_deref(),
+                    peer,
+                    &request.message.payload,
+                    format,
+                )
+                .await
+            };
+
+            match result.and_then(|body| encode(&body, format)) {
+                Ok(body) => (Status::Changed, Some(format.content_format()), body),
+                Err(e) => error_reply(e),
+            }
+        }
+        (_, ".well-known/core" | "sensor/data" | "sensor/batch") => {
+            diagnostic(Status::MethodNotAllowed, "Método no permitido")
+        }
+        _ => diagnostic(Status::NotFound, "Recurso no encontrado"),
+    }
+}
+
+async fn ingest_single(
+    state: &AppState,
+    token: Option<&str>,
+    peer: SocketAddr,
+    payload: &[u8],
+    format: Format,
+) -> Result<Value, AppError> {
+    let reading: SensorDataInput = decode(payload, format)?;
+    Ok(ingest_reading(state, token, peer.ip(), reading).await?.0)
+}
+
+async fn ingest_batch(
+    state: &AppState,
+    token: Option<&str>,
+    peer: SocketAddr,
+    payload: &[u8],
+    format: Format,
+) -> Result<Value, AppError> {
+    let batch: SensorDataBatch = decode(payload, format)?;
+    batch
+        .validate()
+        .map_err(|e| AppError::ValidationError(e.to_string()))?;
+    Ok(ingest_readings(state, token, peer.ip(), batch.readings)
+        .await?
+        .0)
+}
+
+/// Formato del payload; sin Content-Format se asume JSON
+fn payload_format(packet: &Packet) -> Option<Format> {
+    if packet.get_option(CoapOption::ContentFormat).is_none() {
+        return Some(Format::Json);
+    }
+    match packet.get_content_format()? {
+        ContentFormat::ApplicationJSON => Some(Format::Json),
+        ContentFormat::ApplicationCBOR => Some(Format::Cbor),
+        _ => None,
+    }
+}
+
+/// Valor de un parámetro `nombre=valor` de las opciones Uri-Query
+fn uri_query(packet: &Packet, name: &str) -> Option<String> {
+    packet
+        .get_option(CoapOption::UriQuery)?
+        .iter()
+        .filter_map(|option| std::str::from_utf8(option).ok())
+        .find_map(|query| {
+            let (key, value) = query.split_once('=')?;
+            (key == name).then(|| value.to_string())
+        })
+}
+
+fn decode<T: DeserializeOwned>(payload: &[u8], format: Format) -> Result<T, AppError> {
+    match format {
+        Format::Json => serde_json::from_slice(payload)
+            .map_err(|e| AppError::ValidationError(format!("JSON inválido: {}", e))),
+        Format::Cbor => ciborium::from_reader(payload)
+            .map_err(|e| AppError::ValidationError(format!("CBOR inválido: {}", e))),
+    }
+}
+
+fn encode(body: &Value, format: Format) -> Result<Vec<u8>, AppError> {
+    match format {
+        Format::Json => Ok(serde_json::to_vec(body)?),
+        Format::Cbor => {
+            let mut bytes = Vec::new();
+            ciborium::into_writer(body, &mut bytes)
+                .map_err(|e| AppError::InternalError(e.to_string()))?;
+            Ok(bytes)
+        }
+    }
+}
+
+/// Respuesta de error con un mensaje de diagnóstico en texto (RFC 7252, 5.5.2)
+fn diagnostic(status: Status, message: &str) -> Reply {
+    (
+        status,
+        Some(ContentFormat::TextPlain),
+        message.as_bytes().to_vec(),
+    )
+}
+
+/// Código CoAP equivalente al código HTTP de cada error
+fn error_reply(error: AppError) -> Reply {
+    let status = match &error {
+        AppError::ValidationError(_) => Status::BadRequest,
+        AppError::Unauthorized(_) => Status::Unauthorized,
+        AppError::Forbidden(_) => Status::Forbidden,
+        AppError::NotFound(_) => Status::NotFound,
+        AppError::Conflict(_) => Status::Conflict,
+        AppError::TooManyRequests(_) => Status::TooManyRequests,
+        AppError::ServiceUnavailable(_) => Status::ServiceUnavailable,
+        _ => {
+            // Los detalles internos solo van al log, como en la API HTTP
+            tracing::error!("Error atendiendo la solicitud CoAP: {}", error);
+            return diagnostic(Status::InternalServerError, "Error interno del servidor");
+        }
+    };
+    diagnostic(status, &error.to_string())
+}
@@ -0,0 +1,102 @@
+use crate::{config::Config, error::AppError};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::Semaphore;
+
+/// Clientes seguidos antes de descartar los que tienen la ráfaga completa
+const MAX_TRACKED_CLIENTS: usize = 1024;
+
+/// Cubeta de tokens de un cliente
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Límites de tasa por cliente y de concurrencia global de la API HTTP
+pub struct HttpLimiter {
+    buckets: Mutex<HashMap<IpAddr, Bucket>>,
+    rate: f64,
+    burst: f64,
+    concurrency: Option<Arc<Semaphore>>,
+}
+
+impl HttpLimiter {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            buckets: Mutex::new(HashMap::new()),
+            rate: config.http_rate_limit_per_sec,
+            burst: config.http_rate_limit_burst.max(1) as f64,
+            concurrency: (config.http_max_concurrency > 0)
+                .then(|| Arc::new(Semaphore::new(config.http_max_concurrency))),
+        }
+    }
+
+    /// Consume un token del cliente; false si agotó su ráfaga
+    fn allow(&self, client: IpAddr) -> bool {
+        if self.rate <= 0.0 {
+            return true;
+        }
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(&client) {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * self.rate
+                    < self.burst
+            });
+        }
+
+        let bucket = buckets.entry(client).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.burst);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Middleware que aplica los límites antes de llegar a los handlers
+pub async fn limit_requests(
+    State(limiter): State<Arc<HttpLimiter>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !limiter.allow(addr.ip()) {
+        return AppError::TooManyRequests(format!(
+            "Límite de solicitudes excedido para {}",
+            addr.ip()
+        ))
+        .into_response();
+    }
+
+    let _permit = match &limiter.concurrency {
+        Some(semaphore) => match semaphore.clone().try_acquire_owned() {
+            Ok(permit) => Some(permit),
+            Err(_) => {
+                return AppError::ServiceUnavailable("Demasiadas solicitudes en curso".to_string())
+                    .into_response();
+            }
+        },
+        None => None,
+    };
+
+    next.run(request).await
+}
@@ -0,0 +1,319 @@
+//! Recepción de lecturas por líneas en TCP y UDP para dataloggers antiguos que
+//! no hablan MQTT ni HTTP
+//!
+//! Cada línea es una lectura JSON (el formato plano de `/api/v2/sensor/data`) o
+//! CSV `dispositivo,medición,valor[,marca de tiempo]`. Las líneas CSV seguidas
+//! de un mismo dispositivo y marca de tiempo forman una sola lectura, para que
+//! el edge computing reciba juntas la temperatura y la humedad. Las lecturas
+//! siguen el mismo procesamiento que un batch de la API HTTP (token de
+//! dispositivo, duplicados, cuarentena, cuotas y almacenamiento).
+
+use crate::{
+    handlers::sensor::ingest_readings,
+    models::{DeviceTimestamp, SensorDataInput, SensorDataInputV2, SensorMetric},
+    services::local_ingest::LocalIngest,
+    startup::state::AppState,
+};
+use chrono::{DateTime, Utc};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::Semaphore;
+use validator::Validate;
+
+/// Longitud máxima de una línea; una conexión que la supera se cierra
+const MAX_LINE_BYTES: usize = 16 * 1024;
+
+/// Conexiones TCP atendidas a la vez; las siguientes se rechazan
+const MAX_CONNECTIONS: usize = 32;
+
+/// Lecturas procesadas juntas como un batch
+const MAX_BATCH: usize = 100;
+
+/// Pausa de la conexión tras la que se procesan las líneas recibidas
+const IDLE_FLUSH: Duration = Duration::from_millis(200);
+
+/// Atiende conexiones TCP en `LINE_TCP_PORT` hasta que falle el socket
+pub async fn serve_tcp(state: AppState, port: u16) -> anyhow::Result<()> {
+    let addr = format!("0.0.0.0:{}", port);
+    let listener = TcpListener::bind(&addr).await?;
+    tracing::info!(
+        "Recepción de lecturas por líneas escuchando en {} (TCP)",
+        addr
+    );
+
+    let permits = Arc::new(Semaphore::new(MAX_CONNECTIONS));
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                // Sin descriptores libres el error se repite: se espera antes de seguir
+                tracing::warn!("Error aceptando una conexión de lecturas por líneas: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        if !allowed(&state, peer) {
+            tracing::debug!(peer = %peer, "Conexión de lecturas por líneas no permitida");
+            continue;
+        }
+        let Ok(permit) = permits.clone().try_acquire_owned() else {
+            tracing::warn!(peer = %peer, "Demasiadas conexiones de lecturas por líneas, se rechaza");
+            continue;
+        };
+
+        let state = state.clone();
+        tokio::spawn(async move {
+            let _permit = permit;
+            tracing::debug!(peer = %peer, "Conexión de lecturas por líneas abierta");
+            match handle_connection(&state, stream, peer).await {
+                Ok(()) => tracing::debug!(peer = %peer, "Conexión de lecturas por líneas cerrada"),
+                Err(e) => {
+                    tracing::warn!(peer = %peer, "Conexión de lecturas por líneas cerrada: {:#}", e)
+                }
+            }
+        });
+    }
+}
+
+/// Atiende datagramas UDP en `LINE_UDP_PORT`; cada uno puede traer varias líneas
+pub async fn serve_udp(state: AppState, port: u16) -> anyhow::Result<()> {
+    let addr = format!("0.0.0.0:{}", port);
+    let socket = UdpSocket::bind(&addr).await?;
+    tracing::info!(
+        "Recepción de lecturas por líneas escuchando en {} (UDP)",
+        addr
+    );
+
+    let mut buf = vec![0u8; u16::MAX as usize];
+    loop {
+        let (len, peer) = socket.recv_from(&mut buf).await?;
+        if !allowed(&state, peer) {
+            tracing::debug!(peer = %peer, "Datagrama de lecturas por líneas no permitido");
+            continue;
+        }
+
+        let mut batch = Batch::new(&state, peer, "udp");
+        for line in buf[..len].split(|byte| *byte == b'\n') {
+            batch.push(line);
+        }
+        batch.flush().await;
+    }
+}
+
+fn allowed(state: &AppState, peer: SocketAddr) -> bool {
+    let allowed_ips = &state.config.line_allowed_ips;
+    allowed_ips.is_empty() || allowed_ips.contains(&peer.ip())
+}
+
+async fn handle_connection(
+    state: &AppState,
+    stream: TcpStream,
+    peer: SocketAddr,
+) -> anyhow::Result<()> {
+    let mut reader = BufReader::new(stream);
+    let mut batch = Batch::new(state, peer, "tcp");
+    let mut line = Vec::new();
+
+    loop {
+        // Con lecturas pendientes, una pausa del datalogger las envía al pipeline
+        let read = if batch.is_empty() {
+            read_line(&mut reader, &mut line).await
+        } else {
+            match tokio::time::timeout(IDLE_FLUSH, read_line(&mut reader, &mut line)).await {
+                Ok(read) => read,
+                Err(_) => {
+                    batch.flush().await;
+                    continue;
+                }
+            }
+        };
+        let read = match read {
+            Ok(read) => read,
+            Err(e) => {
+                batch.flush().await;
+                return Err(e);
+            }
+        };
+
+        if read == 0 {
+            // Fin de la conexión; la última línea puede no terminar en salto de línea
+            batch.push(&line);
+            batch.flush().await;
+            return Ok(());
+        }
+        if line.last() != Some(&b'\n') {
+            continue;
+        }
+
+        batch.push(&line);
+        line.clear();
+        if batch.len() >= MAX_BATCH {
+            batch.flush().await;
+        }
+    }
+}
+
+/// Agrega a `line` los bytes recibidos hasta el salto de línea; retorna 0 al
+/// cerrarse la conexión
+///
+/// Si se interrumpe (pausa sin datos), lo leído queda en `line` y la siguiente
+/// llamada continúa la misma línea.
+async fn read_line(reader: &mut BufReader<TcpStream>, line: &mut Vec<u8>) -> anyhow::Result<usize> {
+    let limit = (MAX_LINE_BYTES + 1).saturating_sub(line.len()) as u64;
+    let read = (&mut *reader).take(limit).read_until(b'\n', line).await?;
+    anyhow::ensure!(
+        line.len() <= MAX_LINE_BYTES,
+        "línea de más de {} bytes",
+        MAX_LINE_BYTES
+    );
+    Ok(read)
+}
+
+/// Lecturas recibidas de un cliente, pendientes de procesar
+struct Batch<'a> {
+    state: &'a AppState,
+    peer: SocketAddr,
+    /// Origen en el topic de las lecturas (`tcp/<device_id>` o `udp/<device_id>`)
+    transport: &'static str,
+    readings: Vec<SensorDataInput>,
+    /// Dispositivo y marca de tiempo de la última lectura CSV, que reúne las
+    /// métricas de las líneas siguientes con los mismos valores
+    csv_key: Option<(String, String)>,
+    invalid: usize,
+}
+
+impl<'a> Batch<'a> {
+    fn new(state: &'a AppState, peer: SocketAddr, transport: &'static str) -> Self {
+        Self {
+            state,
+            peer,
+            transport,
+            readings: Vec::new(),
+            csv_key: None,
+            invalid: 0,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.readings.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.readings.is_empty() && self.invalid == 0
+    }
+
+    fn push(&mut self, line: &[u8]) {
+        let line = String::from_utf8_lossy(line);
+        let line = line.trim();
+        // Líneas vacías y comentarios (cabeceras de algunos dataloggers)
+        if line.is_empty() || line.starts_with('#') {
+            return;
+        }
+
+        let result = if line.starts_with('{') {
+            self.push_json(line)
+        } else {
+            self.push_csv(line)
+        };
+        if let Err(e) = result {
+            self.invalid += 1;
+            tracing::debug!(peer = %self.peer, "Línea descartada ({}): {}", e, line);
+        }
+    }
+
+    fn push_json(&mut self, line: &str) -> anyhow::Result<()> {
+        let input: SensorDataInputV2 = serde_json::from_str(line)?;
+        input.validate()?;
+
+        let mut reading = SensorDataInput::from(input);
+        reading.header.topic = format!("{}/{}", self.transport, reading.header.device_id);
+        self.readings.push(reading);
+        self.csv_key = None;
+        Ok(())
+    }
+
+    fn push_csv(&mut self, line: &str) -> anyhow::Result<()> {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let (device_id, measurement, value, timestamp) = match fields[..] {
+            [device_id, measurement, value] => (device_id, measurement, value, ""),
+            [device_id, measurement, value, timestamp] => {
+                (device_id, measurement, value, timestamp)
+            }
+            _ => anyhow::bail!("se esperaban 3 o 4 campos separados por comas"),
+        };
+        anyhow::ensure!(!measurement.is_empty(), "medición vacía");
+        let value: f32 = value
+            .parse()
+            .ok()
+            .filter(|value: &f32| value.is_finite())
+            .ok_or_else(|| anyhow::anyhow!("valor no numérico: {}", value))?;
+        let metric = SensorMetric {
+            measurement: measurement.to_string(),
+            value,
+            unit: None,
+        };
+
+        let key = (device_id.to_string(), timestamp.to_string());
+        if self.csv_key.as_ref() == Some(&key)
+            && let Some(reading) = self.readings.last_mut()
+        {
+            reading.metrics.push(metric);
+            return Ok(());
+        }
+
+        let reading = SensorDataInput {
+            header: LocalIngest::header(
+                self.transport,
+                device_id,
+                &self.state.config.line_location,
+                None,
+            ),
+            metrics: vec![metric],
+            device_timestamp: parse_timestamp(timestamp)?,
+        };
+        reading.validate()?;
+        self.readings.push(reading);
+        self.csv_key = Some(key);
+        Ok(())
+    }
+
+    /// Procesa las lecturas pendientes; los rechazos solo se registran, el
+    /// datalogger no espera respuesta
+    async fn flush(&mut self) {
+        if self.invalid > 0 {
+            tracing::warn!(
+                peer = %self.peer,
+                lines = self.invalid,
+                "Líneas inválidas descartadas (el detalle se registra en debug)"
+            );
+            self.invalid = 0;
+        }
+        self.csv_key = None;
+        if self.readings.is_empty() {
+            return;
+        }
+
+        let readings = std::mem::take(&mut self.readings);
+        if let Err(e) = ingest_readings(self.state, None, self.peer.ip(), readings).await {
+            tracing::warn!(peer = %self.peer, "Lecturas por líneas rechazadas: {}", e);
+        }
+    }
+}
+
+/// Marca de tiempo CSV: vacía, RFC 3339 o numérica (epoch o `millis()`)
+fn parse_timestamp(value: &str) -> anyhow::Result<Option<DeviceTimestamp>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    if let Ok(number) = value.parse::<f64>()
+        && number.is_finite()
+    {
+        return Ok(Some(DeviceTimestamp::Number(number)));
+    }
+    DateTime::parse_from_rfc3339(value)
+        .map(|date| Some(DeviceTimestamp::Date(date.with_timezone(&Utc))))
+        .map_err(|_| anyhow::anyhow!("marca de tiempo inválida: {}", value))
+}
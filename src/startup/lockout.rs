@@ -0,0 +1,236 @@
+use crate::{config::Config, error::AppError};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// IPs o claves seguidas antes de descartar las que ya no cuentan
+const MAX_TRACKED: usize = 4096;
+
+/// Fallos recientes de una IP o de una clave
+struct Failures {
+    count: u32,
+    /// Inicio de la ventana de conteo
+    since: Instant,
+    locked_until: Option<Instant>,
+}
+
+/// Bloqueo temporal tras fallos de autenticación repetidos
+///
+/// Cuenta los tokens inválidos por IP de origen y por clave atacada (el grupo
+/// de rutas de la API o el dispositivo). Al superar el máximo dentro de
+/// `AUTH_FAILURE_WINDOW_SECS`, la IP o la clave quedan bloqueadas durante
+/// `AUTH_LOCKOUT_SECS`. Una IP bloqueada se rechaza aun con credenciales
+/// válidas; una clave bloqueada, solo para las IPs con fallos recientes o
+/// credenciales inválidas: el límite por clave frena los ataques repartidos
+/// entre varias IPs de la red del edificio sin dejar afuera al cliente legítimo.
+pub struct AuthLockout {
+    ips: Mutex<HashMap<IpAddr, Failures>>,
+    keys: Mutex<HashMap<String, Failures>>,
+    /// 0 = sin bloqueo por IP
+    max_per_ip: u32,
+    /// 0 = sin bloqueo por clave
+    max_per_key: u32,
+    window: Duration,
+    lockout: Duration,
+    failures: AtomicU64,
+    lockouts: AtomicU64,
+}
+
+impl AuthLockout {
+    pub fn new(config: &Config) -> Self {
+        Self {
+            ips: Mutex::new(HashMap::new()),
+            keys: Mutex::new(HashMap::new()),
+            max_per_ip: config.auth_max_failures_per_ip,
+            max_per_key: config.auth_max_failures_per_key,
+            window: Duration::from_secs(config.auth_failure_window_secs),
+            lockout: Duration::from_secs(config.auth_lockout_secs),
+            failures: AtomicU64::new(0),
+            lockouts: AtomicU64::new(0),
+        }
+    }
+
+    /// Rechaza la solicitud si la IP está bloqueada (antes de verificar la credencial)
+    pub fn check_ip(&self, ip: IpAddr) -> Result<(), AppError> {
+        if let Some(remaining) = remaining_lock(&self.ips, &ip, Instant::now()) {
+            return Err(AppError::TooManyRequests(format!(
+                "Demasiados fallos de autenticación desde {}: reintente en {} s",
+                ip,
+                remaining.as_secs().max(1)
+            )));
+        }
+        Ok(())
+    }
+
+    /// Rechaza la solicitud si la clave está bloqueada, una vez verificada la credencial
+    ///
+    /// Una credencial válida desde una IP sin fallos recientes pasa igual: el
+    /// bloqueo por clave no debe servir para dejar afuera al cliente legítimo.
+    pub fn check_key(&self, ip: IpAddr, key: &str, valid: bool) -> Result<(), AppError> {
+        let now = Instant::now();
+        let Some(remaining) = remaining_lock(&self.keys, key, now) else {
+            return Ok(());
+        };
+        if valid && !self.has_recent_failures(ip, now) {
+            return Ok(());
+        }
+
+        Err(AppError::TooManyRequests(format!(
+            "Demasiados fallos de autenticación para {}: reintente en {} s",
+            key,
+            remaining.as_secs().max(1)
+        )))
+    }
+
+    /// Cuenta un fallo y bloquea la IP o la clave si superan su máximo
+    pub fn record_failure(&self, ip: IpAddr, key: &str, reason: &str) {
+        let now = Instant::now();
+        self.failures.fetch_add(1, Ordering::Relaxed);
+
+        let (ip_failures, ip_locked) = self.register(&self.ips, ip, self.max_per_ip, now);
+        let (key_failures, key_locked) =
+            self.register(&self.keys, key.to_string(), self.max_per_key, now);
+
+        tracing::warn!(
+            security_event = "auth_failure",
+            ip = %ip,
+            key = %key,
+            reason = reason,
+            ip_failures,
+            key_failures,
+            "Fallo de autenticación"
+        );
+
+        if ip_locked {
+            self.lockouts.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(
+                security_event = "auth_lockout",
+                ip = %ip,
+                failures = ip_failures,
+                lockout_secs = self.lockout.as_secs(),
+                "IP bloqueada por fallos de autenticación repetidos"
+            );
+        }
+        if key_locked {
+            self.lockouts.fetch_add(1, Ordering::Relaxed);
+            tracing::warn!(
+                security_event = "auth_lockout",
+                key = %key,
+                failures = key_failures,
+                lockout_secs = self.lockout.as_secs(),
+                "Clave bloqueada por fallos de autenticación repetidos"
+            );
+        }
+    }
+
+    /// Una autenticación correcta reinicia los contadores de la IP y la clave
+    ///
+    /// El bloqueo vigente de la clave se mantiene hasta vencer: lo causaron otras IPs.
+    pub fn record_success(&self, ip: IpAddr, key: &str) {
+        self.ips.lock().unwrap().remove(&ip);
+        let mut keys = self.keys.lock().unwrap();
+        if keys
+            .get(key)
+            .is_none_or(|failures| failures.locked_until.is_none())
+        {
+            keys.remove(key);
+        }
+    }
+
+    /// Fallos de autenticación y bloqueos desde el arranque
+    pub fn stats(&self) -> (u64, u64) {
+        (
+            self.failures.load(Ordering::Relaxed),
+            self.lockouts.load(Ordering::Relaxed),
+        )
+    }
+
+    /// La IP falló dentro de la ventana de conteo
+    fn has_recent_failures(&self, ip: IpAddr, now: Instant) -> bool {
+        self.ips
+            .lock()
+            .unwrap()
+            .get(&ip)
+            .is_some_and(|failures| now.duration_since(failures.since) < self.window)
+    }
+
+    /// Suma un fallo dentro de la ventana; retorna los fallos y si se bloqueó ahora
+    fn register<K: Hash + Eq + Clone>(
+        &self,
+        map: &Mutex<HashMap<K, Failures>>,
+        key: K,
+        max: u32,
+        now: Instant,
+    ) -> (u32, bool) {
+        let mut map = map.lock().unwrap();
+
+        if map.len() >= MAX_TRACKED && !map.contains_key(&key) {
+            map.retain(|_, failures| {
+                now.duration_since(failures.since) < self.window
+                    || failures.locked_until.is_some_and(|until| until > now)
+            });
+
+            // Todas siguen vigentes: se descarta la ventana más antigua, primero
+            // entre las que no están bloqueadas, para no superar el límite
+            if map.len() >= MAX_TRACKED {
+                let oldest = map
+                    .iter()
+                    .min_by_key(|(_, failures)| {
+                        (
+                            failures.locked_until.is_some_and(|until| until > now),
+                            failures.since,
+                        )
+                    })
+                    .map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    map.remove(&oldest);
+                    tracing::debug!(
+                        "Límite de {} claves de autenticación seguidas: se descarta la más antigua",
+                        MAX_TRACKED
+                    );
+                }
+            }
+        }
+
+        let failures = map.entry(key).or_insert(Failures {
+            count: 0,
+            since: now,
+            locked_until: None,
+        });
+        let lock_expired = failures.locked_until.is_some_and(|until| until <= now);
+        if lock_expired || now.duration_since(failures.since) >= self.window {
+            failures.locked_until = None;
+            failures.count = 0;
+            failures.since = now;
+        }
+        failures.count += 1;
+
+        let locked = max > 0 && failures.count >= max && failures.locked_until.is_none();
+        if locked {
+            failures.locked_until = Some(now + self.lockout);
+        }
+        (failures.count, locked)
+    }
+}
+
+/// Tiempo restante de bloqueo; un bloqueo vencido reinicia el conteo
+fn remaining_lock<K, Q>(
+    map: &Mutex<HashMap<K, Failures>>,
+    key: &Q,
+    now: Instant,
+) -> Option<Duration>
+where
+    K: Hash + Eq + std::borrow::Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+{
+    let mut map = map.lock().unwrap();
+    let until = map.get(key)?.locked_until?;
+    if until > now {
+        return Some(until - now);
+    }
+    map.remove(key);
+    None
+}
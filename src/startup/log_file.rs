@@ -0,0 +1,116 @@
+use chrono::{Local, NaiveDate};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Archivo de log activo; los rotados añaden el timestamp (`gateway.log.20250115-103000`)
+const LOG_FILE_NAME: &str = "gateway.log";
+
+/// Archivo de log con rotación por tamaño y por día
+///
+/// Al rotar, el archivo activo se renombra con el timestamp y se conservan solo
+/// los últimos `keep` archivos rotados. Se escribe desde el hilo de
+/// `tracing_appender::non_blocking`, por lo que la E/S es síncrona.
+pub struct RotatingFile {
+    dir: PathBuf,
+    file: File,
+    size: u64,
+    opened_on: NaiveDate,
+    /// Tamaño máximo en bytes (0 = sin límite)
+    max_bytes: u64,
+    daily: bool,
+    keep: usize,
+}
+
+impl RotatingFile {
+    pub fn open(dir: &Path, max_bytes: u64, daily: bool, keep: usize) -> io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(LOG_FILE_NAME);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let metadata = file.metadata()?;
+
+        // Un archivo de otro día rota con la primera escritura
+        let opened_on = metadata
+            .modified()
+            .map(|modified| chrono::DateTime::<Local>::from(modified).date_naive())
+            .unwrap_or_else(|_| Local::now().date_naive());
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            file,
+            size: metadata.len(),
+            opened_on,
+            max_bytes,
+            daily,
+            keep,
+        })
+    }
+
+    fn needs_rotation(&self, incoming: usize) -> bool {
+        let oversized =
+            self.max_bytes > 0 && self.size > 0 && self.size + incoming as u64 > self.max_bytes;
+        let new_day = self.daily && Local::now().date_naive() != self.opened_on;
+        oversized || new_day
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        let stamp = Local::now().format("%Y%m%d-%H%M%S").to_string();
+        let mut rotated = self.dir.join(format!("{}.{}", LOG_FILE_NAME, stamp));
+        let mut suffix = 1;
+        while rotated.exists() {
+            rotated = self
+                .dir
+                .join(format!("{}.{}-{:03}", LOG_FILE_NAME, stamp, suffix));
+            suffix += 1;
+        }
+
+        let path = self.dir.join(LOG_FILE_NAME);
+        std::fs::rename(&path, &rotated)?;
+        self.file = OpenOptions::new().create(true).append(true).open(&path)?;
+        self.size = 0;
+        self.opened_on = Local::now().date_naive();
+
+        self.prune()
+    }
+
+    /// Elimina los archivos rotados más antiguos conservando los últimos `keep`
+    fn prune(&self) -> io::Result<()> {
+        let prefix = format!("{}.", LOG_FILE_NAME);
+        let mut rotated = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)?.flatten() {
+            if entry.file_name().to_string_lossy().starts_with(&prefix) {
+                rotated.push(entry.path());
+            }
+        }
+
+        // Los nombres incluyen el timestamp, por lo que el orden lexicográfico es cronológico
+        rotated.sort();
+
+        let excess = rotated.len().saturating_sub(self.keep);
+        for old in rotated.into_iter().take(excess) {
+            std::fs::remove_file(old)?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.needs_rotation(buf.len())
+            && let Err(e) = self.rotate()
+        {
+            // El logger no puede registrar sus propios errores
+            eprintln!("Error rotando el archivo de log: {}", e);
+        }
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
@@ -1,11 +1,64 @@
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use super::log_file::RotatingFile;
+use crate::config::{Config, LogFormat};
+use std::path::Path;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt};
 
-pub fn init() {
+/// Logger del servicio; `RUST_LOG` tiene prioridad sobre el nivel configurado
+///
+/// Con `LOG_FORMAT=json` cada línea es un objeto JSON con timestamp, nivel,
+/// target y campos, para enviar los logs a Loki o ELK. Con `LOG_FILE_DIR` los
+/// logs se escriben también (o solo, con `LOG_STDOUT=false`) en un archivo
+/// rotado; el guard devuelto debe conservarse para vaciar el buffer al salir.
+pub fn init(config: &Config) -> anyhow::Result<Option<WorkerGuard>> {
+    let json = config.log_format == LogFormat::Json;
+    let stdout = config.log_stdout;
+
+    let (file, guard) = match &config.log_file_dir {
+        Some(dir) => {
+            let file = RotatingFile::open(
+                Path::new(dir),
+                config.log_file_max_bytes,
+                config.log_file_rotate_daily,
+                config.log_file_keep,
+            )
+            .map_err(|e| anyhow::anyhow!("No se pudo abrir el archivo de log en {}: {}", dir, e))?;
+            let (writer, guard) = tracing_appender::non_blocking(file);
+            (Some(writer), Some(guard))
+        }
+        None => (None, None),
+    };
+
+    tracing_subscriber::registry()
+        .with(
+            tracing_subscriber::EnvFilter::try_from_default_env().unwrap_or_else(|_| {
+                format!("env_edge_gateway_rpi={},tower_http=info", config.log_level).into()
+            }),
+        )
+        .with((stdout && !json).then(fmt::layer))
+        .with((stdout && json).then(|| fmt::layer().json()))
+        .with(
+            file.clone()
+                .filter(|_| !json)
+                .map(|writer| fmt::layer().with_ansi(false).with_writer(writer)),
+        )
+        .with(
+            file.filter(|_| json)
+                .map(|writer| fmt::layer().json().with_writer(writer)),
+        )
+        .init();
+
+    Ok(guard)
+}
+
+/// Logger de los subcomandos de operación: escribe en stderr para no mezclarse
+/// con la salida del comando (p. ej. un CSV exportado a stdout)
+pub fn init_cli() {
     tracing_subscriber::registry()
         .with(
             tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "env_edge_gateway_rpi=debug,tower_http=info".into()),
+                .unwrap_or_else(|_| "env_edge_gateway_rpi=warn".into()),
         )
-        .with(tracing_subscriber::fmt::layer())
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
         .init();
 }
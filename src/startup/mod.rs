@@ -1,3 +1,11 @@
+pub mod auth;
+pub mod coap;
+pub mod limits;
+pub mod line_listener;
+pub mod lockout;
+pub mod log_file;
 pub mod logger;
 pub mod router;
 pub mod state;
+pub mod tls;
+pub mod versioning;
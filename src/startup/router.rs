@@ -1,27 +1,329 @@
+use super::auth::{self, ApiTokens, Role, RoleAuth};
+use super::limits::{self, HttpLimiter};
 use super::state::AppState;
+use super::versioning::{self, ApiVersion, VersionPolicy};
+use crate::config::Config;
 use crate::handlers;
 use axum::{
     Router,
-    routing::{get, post},
+    extract::DefaultBodyLimit,
+    http::HeaderValue,
+    middleware,
+    routing::{delete, get, patch, post, put},
 };
-use tower_http::{compression::CompressionLayer, cors::CorsLayer, trace::TraceLayer};
+use std::sync::Arc;
+use tower_http::{
+    compression::{
+        CompressionLayer,
+        predicate::{DefaultPredicate, NotForContentType, Predicate},
+    },
+    cors::{Any, CorsLayer},
+    trace::TraceLayer,
+};
+
+/// CORS permisivo salvo que se configuren orígenes permitidos (`*` = cualquiera)
+fn cors_layer(config: &Config) -> CorsLayer {
+    if config.cors_allowed_origins.is_empty()
+        || config
+            .cors_allowed_origins
+            .iter()
+            .any(|origin| origin == "*")
+    {
+        return CorsLayer::permissive();
+    }
+
+    let origins: Vec<HeaderValue> = config
+        .cors_allowed_origins
+        .iter()
+        .filter_map(|origin| match origin.parse() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                tracing::warn!(origin = %origin, "Origen CORS inválido ignorado");
+                None
+            }
+        })
+        .collect();
+
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods(Any)
+        .allow_headers(Any)
+}
+
+/// Endpoints de operación (rol operator): sincronización y órdenes a
+/// los dispositivos (relativos a `/api/vN`)
+fn operator_routes(state: &AppState, tokens: &Arc<ApiTokens>) -> Router<AppState> {
+    Router::new()
+        .route("/sync/requeue", post(handlers::sync::requeue))
+        .route("/sync/now", post(handlers::sync::sync_now))
+        .route("/sync/pause", post(handlers::sync::pause_sync))
+        .route("/sync/resume", post(handlers::sync::resume_sync))
+        .route(
+            "/devices/{id}/twin",
+            patch(handlers::devices::update_device_twin),
+        )
+        .route(
+            "/groups/{tag}/twin",
+            patch(handlers::devices::update_group_twin),
+        )
+        .route(
+            "/admin/firmware/{id}/rollout",
+            post(handlers::firmware::rollout_firmware),
+        )
+        .route("/alerts/{id}/ack", post(handlers::alerts::ack_alert))
+        .route("/alerts/silence", post(handlers::alerts::create_silence))
+        .route(
+            "/alerts/silences/{id}",
+            delete(handlers::alerts::delete_silence),
+        )
+        .route("/gpio/{name}", put(handlers::gpio::set_output_mode))
+        .route_layer(middleware::from_fn_with_state(
+            RoleAuth::optional(tokens, &state.auth_lockout, Role::Operator).audited(&state.db),
+            auth::require_role,
+        ))
+}
+
+/// Endpoints de administración (rol admin, ADMIN_API_TOKEN), relativos a `/api/vN`
+fn admin_routes(state: &AppState, tokens: &Arc<ApiTokens>) -> Router<AppState> {
+    let config = &state.config;
+    let admin = Router::new()
+        .route("/admin/audit", get(handlers::admin::list_audit))
+        .route(
+            "/admin/config",
+            get(handlers::config::get_config).patch(handlers::config::patch_config),
+        )
+        .route("/admin/reload", post(handlers::config::reload_config))
+        .route(
+            "/admin/rules",
+            get(handlers::rules::list_rules).post(handlers::rules::create_rule),
+        )
+        .route(
+            "/admin/rules/{id}",
+            get(handlers::rules::get_rule)
+                .put(handlers::rules::update_rule)
+                .delete(handlers::rules::delete_rule),
+        )
+        .route(
+            "/admin/rules/ranges",
+            get(handlers::rules::get_ranges).put(handlers::rules::put_ranges),
+        )
+        .route(
+            "/admin/calibrations",
+            get(handlers::calibrations::list_calibrations)
+                .post(handlers::calibrations::create_calibration),
+        )
+        .route(
+            "/admin/calibrations/{id}",
+            get(handlers::calibrations::get_calibration)
+                .put(handlers::calibrations::update_calibration)
+                .delete(handlers::calibrations::delete_calibration),
+        )
+        .route("/admin/profiles", get(handlers::profiles::list_profiles))
+        .route(
+            "/admin/profiles/{device_type}",
+            get(handlers::profiles::get_profile)
+                .put(handlers::profiles::upsert_profile)
+                .delete(handlers::profiles::delete_profile),
+        )
+        .route("/devices", post(handlers::devices::provision_device))
+        .route(
+            "/devices/{id}",
+            patch(handlers::devices::update_device).delete(handlers::devices::delete_device),
+        )
+        .route(
+            "/devices/{id}/approve",
+            post(handlers::devices::approve_device),
+        )
+        .route(
+            "/devices/{id}/decommission",
+            post(handlers::devices::decommission_device),
+        )
+        .route(
+            "/devices/{id}/token",
+            post(handlers::devices::rotate_device_token),
+        )
+        .route(
+            "/devices/{id}/sequence",
+            delete(handlers::devices::reset_device_sequence),
+        )
+        .route(
+            "/admin/firmware",
+            get(handlers::firmware::list_firmware)
+                .post(handlers::firmware::upload_firmware)
+                .layer(DefaultBodyLimit::max(config.firmware_max_bytes)),
+        )
+        .route(
+            "/admin/firmware/{id}",
+            get(handlers::firmware::get_firmware).delete(handlers::firmware::delete_firmware),
+        )
+        .route("/admin/overrides", get(handlers::overrides::list_overrides))
+        .route(
+            "/admin/overrides/effective",
+            get(handlers::overrides::get_effective_override),
+        )
+        .route(
+            "/admin/overrides/{scope}/{target}",
+            get(handlers::overrides::get_override)
+                .put(handlers::overrides::upsert_override)
+                .delete(handlers::overrides::delete_override),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            RoleAuth::optional(tokens, &state.auth_lockout, Role::Admin).audited(&state.db),
+            auth::require_role,
+        ));
+
+    // Las operaciones destructivas y el respaldo (que incluye los hashes de las
+    // credenciales y la auditoría) exigen siempre un token configurado
+    let destructive = Router::new()
+        .route("/data", delete(handlers::admin::purge_data))
+        .route("/admin/backup", post(handlers::admin::create_backup))
+        .route_layer(middleware::from_fn_with_state(
+            RoleAuth::required(tokens, &state.auth_lockout, Role::Admin).audited(&state.db),
+            auth::require_role,
+        ));
+
+    admin.merge(destructive)
+}
+
+/// Dashboard web embebido (vacío si está deshabilitado)
+fn dashboard_routes(config: &Config) -> Router<AppState> {
+    if !config.dashboard_enabled {
+        return Router::new();
+    }
+
+    Router::new()
+        .route("/", get(handlers::dashboard::index))
+        .route("/dashboard/{*path}", get(handlers::dashboard::asset))
+}
+
+/// Endpoints de consulta comunes a todas las versiones (rol viewer, relativos a `/api/vN`)
+fn query_routes(state: &AppState, tokens: &Arc<ApiTokens>) -> Router<AppState> {
+    Router::new()
+        .route("/data/recent", get(handlers::query::get_recent_data))
+        .route("/data/latest", get(handlers::query::get_latest))
+        .route("/data/stats", get(handlers::query::get_statistics))
+        .route("/data/range", get(handlers::query::get_range))
+        .route("/data/anomalies", get(handlers::query::get_anomalies))
+        .route("/data/export", get(handlers::export::export_data))
+        .route("/devices", get(handlers::devices::list_devices))
+        .route("/devices/{id}", get(handlers::devices::get_device))
+        .route(
+            "/devices/{id}/stats",
+            get(handlers::devices::get_device_stats),
+        )
+        .route(
+            "/devices/{id}/twin",
+            get(handlers::devices::get_device_twin),
+        )
+        .route("/graphql", post(handlers::graphql::graphql))
+        .route("/alerts", get(handlers::alerts::list_alerts))
+        .route("/alerts/silences", get(handlers::alerts::list_silences))
+        .route("/sync/pending", get(handlers::sync::list_pending))
+        .route("/fleet/power", get(handlers::fleet::get_power_report))
+        .route("/groups", get(handlers::devices::list_groups))
+        .route("/quotas", get(handlers::devices::list_quotas))
+        .route("/gpio", get(handlers::gpio::list_outputs))
+        .route_layer(middleware::from_fn_with_state(
+            RoleAuth::optional(tokens, &state.auth_lockout, Role::Viewer),
+            auth::require_role,
+        ))
+        .merge(stream_routes(state, tokens))
+}
+
+/// Streams del navegador (rol viewer), que pueden pasar el token en `?access_token=`
+fn stream_routes(state: &AppState, tokens: &Arc<ApiTokens>) -> Router<AppState> {
+    Router::new()
+        .route("/stream", get(handlers::stream::stream_readings))
+        .route("/events", get(handlers::events::stream_events))
+        .route_layer(middleware::from_fn_with_state(
+            RoleAuth::optional(tokens, &state.auth_lockout, Role::Viewer).with_query_token(),
+            auth::require_role,
+        ))
+}
+
+/// Endpoints que consultan los nodos, sin token de la API
+fn node_routes() -> Router<AppState> {
+    Router::new().route(
+        "/firmware/{id}/download",
+        get(handlers::firmware::download_firmware),
+    )
+}
+
+/// Endpoints de ingesta; cada versión acepta su propio formato de payload
+fn ingest_routes(version: ApiVersion, config: &Config) -> Router<AppState> {
+    let batch_limit = DefaultBodyLimit::max(config.http_batch_body_limit_bytes);
+
+    match version {
+        ApiVersion::V1 => Router::new()
+            .route("/sensor/data", post(handlers::sensor::ingest_sensor_data))
+            .route(
+                "/sensor/batch",
+                post(handlers::sensor::ingest_batch_data).layer(batch_limit),
+            )
+            .route(
+                "/ingest/webhook/{source}",
+                post(handlers::webhook::ingest_webhook).layer(batch_limit),
+            ),
+        ApiVersion::V2 => Router::new()
+            .route(
+                "/sensor/data",
+                post(handlers::sensor::ingest_sensor_data_v2),
+            )
+            .route(
+                "/sensor/batch",
+                post(handlers::sensor::ingest_batch_data_v2).layer(batch_limit),
+            ),
+    }
+}
+
+/// Árbol completo de una versión de la API, con su contador de uso
+fn api_routes(version: ApiVersion, state: &AppState, tokens: &Arc<ApiTokens>) -> Router<AppState> {
+    let policy = VersionPolicy::new(version, state.api_usage.clone(), &state.config);
+
+    query_routes(state, tokens)
+        .merge(node_routes())
+        .merge(ingest_routes(version, &state.config))
+        .merge(operator_routes(state, tokens))
+        .merge(admin_routes(state, tokens))
+        .layer(middleware::from_fn_with_state(
+            policy,
+            versioning::track_version,
+        ))
+}
 
 pub fn build_router(state: AppState) -> Router {
+    let config = state.config.clone();
+    let limiter = Arc::new(HttpLimiter::new(&config));
+    let tokens = Arc::new(ApiTokens::new(&config));
+
     Router::new()
         .route("/health", get(handlers::health::health_check))
+        .route("/health/live", get(handlers::health::liveness))
+        .route("/health/ready", get(handlers::health::readiness))
         .route("/metrics", get(handlers::metrics::get_metrics))
         .route(
-            "/api/v1/sensor/data",
-            post(handlers::sensor::ingest_sensor_data),
+            "/metrics/prometheus",
+            get(handlers::metrics::get_prometheus_metrics),
         )
-        .route(
-            "/api/v1/sensor/batch",
-            post(handlers::sensor::ingest_batch_data),
+        .nest(
+            ApiVersion::V1.prefix(),
+            api_routes(ApiVersion::V1, &state, &tokens),
+        )
+        .nest(
+            ApiVersion::V2.prefix(),
+            api_routes(ApiVersion::V2, &state, &tokens),
         )
-        .route("/api/v1/data/recent", get(handlers::query::get_recent_data))
-        .route("/api/v1/data/stats", get(handlers::query::get_statistics))
+        .merge(dashboard_routes(&config))
         .with_state(state)
-        .layer(CompressionLayer::new())
-        .layer(CorsLayer::permissive())
+        .layer(DefaultBodyLimit::max(config.http_body_limit_bytes))
+        .layer(middleware::from_fn_with_state(
+            limiter,
+            limits::limit_requests,
+        ))
+        // Los binarios de firmware se sirven sin comprimir para respetar Content-Length y Range
+        .layer(CompressionLayer::new().compress_when(
+            DefaultPredicate::new().and(NotForContentType::const_new("application/octet-stream")),
+        ))
+        .layer(cors_layer(&config))
         .layer(TraceLayer::new_for_http())
 }
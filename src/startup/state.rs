@@ -1,15 +1,98 @@
+use super::lockout::AuthLockout;
+use super::versioning::ApiUsage;
 use crate::{
     config::Config,
     database::Database,
-    services::{cloud_sync::CloudSync, edge_processor::EdgeProcessor},
+    services::{
+        alerting::Alerting,
+        backlog_watchdog::BacklogWatchdog,
+        backup::BackupService,
+        cloud_sync::CloudSyncHandle,
+        connection::ConnectionStatus,
+        device_presence::PresenceMonitor,
+        device_twin::DeviceTwins,
+        edge_processor::EdgeProcessor,
+        firmware::FirmwareService,
+        gpio::GpioOutputs,
+        graphql::GatewaySchema,
+        host_metrics::HostMetrics,
+        lifecycle::ProcessLifecycle,
+        maintenance::MaintenanceService,
+        runtime_config::{ReloadReport, RuntimeConfig},
+        webhook_mappings::WebhookMappings,
+    },
+    telemetry::Telemetry,
 };
 use std::sync::Arc;
-use tokio::{sync::Mutex};
 
 #[derive(Clone)]
 pub struct AppState {
     pub db: Database,
     pub edge_processor: Arc<EdgeProcessor>,
-    pub cloud_sync: Arc<Mutex<CloudSync>>,
+    /// Tarea de sincronización con el cloud
+    pub cloud_sync: CloudSyncHandle,
+    /// Conectividad con el broker local y con el broker cloud
+    pub mqtt_status: Arc<ConnectionStatus>,
+    pub cloud_status: Arc<ConnectionStatus>,
+    pub backup: Arc<BackupService>,
+    pub maintenance: Arc<MaintenanceService>,
+    pub runtime_config: Arc<RuntimeConfig>,
+    /// Bloqueo temporal tras fallos de autenticación repetidos
+    pub auth_lockout: Arc<AuthLockout>,
+    /// Solicitudes atendidas por cada versión de la API HTTP
+    pub api_usage: Arc<ApiUsage>,
+    /// Contadores e histogramas del pipeline de ingesta (formato Prometheus)
+    pub telemetry: Arc<Telemetry>,
+    /// Última muestra de CPU, memoria, disco y temperatura del host
+    pub host_metrics: Arc<HostMetrics>,
+    /// Tendencia del backlog de sincronización y alarma de crecimiento
+    pub backlog_watchdog: Arc<BacklogWatchdog>,
+    /// Tiempo en marcha, reinicios y apagados abruptos del proceso
+    pub lifecycle: Arc<ProcessLifecycle>,
+    /// Detección de dispositivos offline y stream de cambios de conectividad
+    pub presence: Arc<PresenceMonitor>,
+    /// Ciclo de vida de las alertas y su difusión a los canales de notificación
+    pub alerting: Arc<Alerting>,
+    /// Estado deseado y reportado de los dispositivos
+    pub twins: Arc<DeviceTwins>,
+    /// Imágenes de firmware y su despliegue OTA
+    pub firmware: Arc<FirmwareService>,
+    /// Mapeo de los payloads de cada fuente de webhooks a lecturas
+    pub webhooks: Arc<WebhookMappings>,
+    /// Salidas GPIO accionadas por las reglas y su control manual
+    pub gpio: Arc<GpioOutputs>,
+    /// Esquema de la API GraphQL de consulta
+    pub graphql: GatewaySchema,
     pub config: Arc<Config>,
 }
+
+impl AppState {
+    /// Recarga la configuración sin reiniciar (SIGHUP o `POST /api/v1/admin/reload`)
+    ///
+    /// Publica los ajustes en vivo del archivo de configuración y vuelve a leer
+    /// las reglas, rangos, calibraciones, perfiles y scripts del procesador edge
+    /// y los mapeos de webhooks.
+    pub async fn reload_configuration(&self) -> anyhow::Result<ReloadReport> {
+        let report = self.runtime_config.reload().await?;
+
+        self.edge_processor.load_definitions(&self.db).await?;
+        let processor = self.edge_processor.clone();
+        tokio::task::spawn_blocking(move || processor.reload_scripts()).await?;
+        let webhooks = self.webhooks.clone();
+        tokio::task::spawn_blocking(move || webhooks.reload()).await?;
+
+        tracing::info!(
+            file = ?report.file,
+            applied = ?report.applied,
+            "Configuración recargada"
+        );
+        if !report.restart_required.is_empty() {
+            tracing::warn!(
+                settings = ?report.restart_required,
+                "Cambios de configuración que solo se aplican al reiniciar el gateway"
+            );
+        }
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,126 @@
+use crate::config::Config;
+use axum_server::tls_rustls::RustlsConfig;
+use chrono::{Datelike, Utc};
+use rcgen::{CertificateParams, DistinguishedName, DnType, KeyPair};
+use sha2::{Digest, Sha256};
+use std::io::Write;
+use std::path::Path;
+
+/// Validez del certificado autofirmado
+const SELF_SIGNED_VALID_DAYS: i64 = 3650;
+
+/// Carga el certificado y la clave del servidor HTTPS
+///
+/// Si no existe ninguno de los dos y `HTTP_TLS_SELF_SIGNED` está activo, se
+/// genera un certificado autofirmado (primer arranque). Un certificado sin su
+/// clave, o al revés, es un error: nunca se sobrescribe material existente.
+pub async fn load(config: &Config) -> anyhow::Result<RustlsConfig> {
+    // Las dependencias habilitan ring y aws-lc-rs en rustls, que entonces no elige proveedor
+    let _ = rustls::crypto::aws_lc_rs::default_provider().install_default();
+
+    let cert_path = Path::new(&config.http_tls_cert_path);
+    let key_path = Path::new(&config.http_tls_key_path);
+
+    match (cert_path.exists(), key_path.exists()) {
+        (true, true) => {}
+        (false, false) if config.http_tls_self_signed => {
+            generate_self_signed(config, cert_path, key_path)?
+        }
+        (false, false) => anyhow::bail!(
+            "No existe el certificado TLS {} (HTTP_TLS_SELF_SIGNED=false)",
+            cert_path.display()
+        ),
+        _ => anyhow::bail!(
+            "Falta el certificado {} o su clave {}",
+            cert_path.display(),
+            key_path.display()
+        ),
+    }
+
+    RustlsConfig::from_pem_file(cert_path, key_path)
+        .await
+        .map_err(|e| {
+            anyhow::anyhow!(
+                "Error cargando el certificado TLS {}: {}",
+                cert_path.display(),
+                e
+            )
+        })
+}
+
+/// Vuelve a leer el certificado y la clave (renovación sin reiniciar)
+pub async fn reload(config: &Config, tls: &RustlsConfig) -> anyhow::Result<()> {
+    tls.reload_from_pem_file(&config.http_tls_cert_path, &config.http_tls_key_path)
+        .await?;
+    tracing::info!(cert = %config.http_tls_cert_path, "Certificado TLS recargado");
+    Ok(())
+}
+
+/// Nombres del certificado autofirmado: localhost, el gateway, el hostname y
+/// los de `HTTP_TLS_HOSTNAMES` (las IPs se incluyen como tales)
+fn self_signed_names(config: &Config) -> Vec<String> {
+    let mut names = vec![
+        "localhost".to_string(),
+        "127.0.0.1".to_string(),
+        config.gateway_id.clone(),
+        format!("{}.local", config.gateway_id),
+    ];
+    if let Some(host) = sysinfo::System::host_name() {
+        names.push(format!("{}.local", host));
+        names.push(host);
+    }
+    names.extend(config.http_tls_hostnames.iter().cloned());
+
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn generate_self_signed(config: &Config, cert_path: &Path, key_path: &Path) -> anyhow::Result<()> {
+    let names = self_signed_names(config);
+
+    let mut params = CertificateParams::new(names.clone())?;
+    params.distinguished_name = DistinguishedName::new();
+    params
+        .distinguished_name
+        .push(DnType::CommonName, config.gateway_id.as_str());
+
+    let now = Utc::now();
+    let expires = now + chrono::Duration::days(SELF_SIGNED_VALID_DAYS);
+    params.not_before = rcgen::date_time_ymd(now.year(), now.month() as u8, now.day() as u8);
+    params.not_after =
+        rcgen::date_time_ymd(expires.year(), expires.month() as u8, expires.day() as u8);
+
+    let key = KeyPair::generate()?;
+    let cert = params.self_signed(&key)?;
+
+    for path in [cert_path, key_path] {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+    }
+
+    // La clave solo es legible por el usuario del gateway
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options
+        .open(key_path)?
+        .write_all(key.serialize_pem().as_bytes())?;
+    std::fs::write(cert_path, cert.pem())?;
+
+    let fingerprint: String = Sha256::digest(cert.der())
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect::<Vec<_>>()
+        .join(":");
+    tracing::warn!(
+        cert = %cert_path.display(),
+        names = ?names,
+        sha256 = %fingerprint,
+        "Certificado TLS autofirmado generado; los clientes deben aceptarlo o importarlo"
+    );
+
+    Ok(())
+}
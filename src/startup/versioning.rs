@@ -0,0 +1,127 @@
+use crate::config::Config;
+use axum::{
+    extract::{OriginalUri, Request, State},
+    http::{HeaderValue, header},
+    middleware::Next,
+    response::Response,
+};
+use serde::Serialize;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Versiones publicadas de la API HTTP
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiVersion {
+    V1,
+    V2,
+}
+
+impl ApiVersion {
+    pub fn prefix(self) -> &'static str {
+        match self {
+            ApiVersion::V1 => "/api/v1",
+            ApiVersion::V2 => "/api/v2",
+        }
+    }
+}
+
+/// Solicitudes atendidas por cada versión de la API
+///
+/// Permite saber cuándo dejan de llegar peticiones a `/api/v1` (firmware ESP32
+/// antiguo) y puede retirarse.
+#[derive(Default)]
+pub struct ApiUsage {
+    v1: AtomicU64,
+    v2: AtomicU64,
+}
+
+/// Contadores de solicitudes por versión
+#[derive(Debug, Serialize)]
+pub struct ApiUsageSnapshot {
+    pub v1: u64,
+    pub v2: u64,
+}
+
+impl ApiUsage {
+    fn record(&self, version: ApiVersion) {
+        let counter = match version {
+            ApiVersion::V1 => &self.v1,
+            ApiVersion::V2 => &self.v2,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ApiUsageSnapshot {
+        ApiUsageSnapshot {
+            v1: self.v1.load(Ordering::Relaxed),
+            v2: self.v2.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Estado del middleware de una versión de la API
+#[derive(Clone)]
+pub struct VersionPolicy {
+    version: ApiVersion,
+    usage: Arc<ApiUsage>,
+    /// Valor de la cabecera `Sunset` (fecha HTTP), solo para versiones obsoletas
+    sunset: Option<HeaderValue>,
+}
+
+impl VersionPolicy {
+    pub fn new(version: ApiVersion, usage: Arc<ApiUsage>, config: &Config) -> Self {
+        let sunset = match version {
+            ApiVersion::V1 => config.api_v1_sunset.and_then(|date| {
+                HeaderValue::from_str(&date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()).ok()
+            }),
+            ApiVersion::V2 => None,
+        };
+
+        Self {
+            version,
+            usage,
+            sunset,
+        }
+    }
+}
+
+/// Middleware que cuenta las solicitudes de la versión y marca `/api/v1` como
+/// obsoleta con las cabeceras `Deprecation`, `Sunset` y `Link` a su sucesora
+pub async fn track_version(
+    State(policy): State<VersionPolicy>,
+    request: Request,
+    next: Next,
+) -> Response {
+    policy.usage.record(policy.version);
+
+    let successor = match policy.version {
+        ApiVersion::V1 => request
+            .extensions()
+            .get::<OriginalUri>()
+            .map(|uri| uri.path().to_string())
+            .and_then(|path| {
+                path.strip_prefix(ApiVersion::V1.prefix())
+                    .map(|rest| format!("{}{}", ApiVersion::V2.prefix(), rest))
+            }),
+        ApiVersion::V2 => None,
+    };
+
+    let mut response = next.run(request).await;
+
+    if policy.version == ApiVersion::V1 {
+        let headers = response.headers_mut();
+        headers.insert("deprecation", HeaderValue::from_static("true"));
+
+        if let Some(sunset) = &policy.sunset {
+            headers.insert("sunset", sunset.clone());
+        }
+
+        if let Some(link) = successor.and_then(|path| {
+            HeaderValue::from_str(&format!("<{path}>; rel=\"successor-version\"")).ok()
+        }) {
+            headers.insert(header::LINK, link);
+        }
+    }
+
+    response
+}
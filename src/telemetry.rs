@@ -0,0 +1,533 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Límites (en segundos) de los histogramas de duración
+const DURATION_BUCKETS: &[f64] = &[
+    0.0005, 0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5,
+];
+
+/// Ventana usada para calcular la tasa de mensajes por dispositivo
+const DEVICE_RATE_WINDOW_SECS: i64 = 300;
+
+/// Serie por dispositivo: nombre, tipo, ayuda y valor
+type DeviceSeries = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&DeviceIngestStats) -> Option<f64>,
+);
+
+/// Contador con etiquetas
+struct CounterVec {
+    name: &'static str,
+    help: &'static str,
+    labels: &'static [&'static str],
+    values: Mutex<BTreeMap<Vec<String>, u64>>,
+}
+
+impl CounterVec {
+    fn new(name: &'static str, help: &'static str, labels: &'static [&'static str]) -> Self {
+        Self {
+            name,
+            help,
+            labels,
+            values: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    fn inc(&self, values: &[&str]) {
+        let key = values.iter().map(|value| value.to_string()).collect();
+        *self.values.lock().unwrap().entry(key).or_default() += 1;
+    }
+
+    /// Suma de todas las series
+    fn total(&self) -> u64 {
+        self.values.lock().unwrap().values().sum()
+    }
+
+    fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} counter", self.name);
+        for (values, count) in self.values.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "{}{{{}}} {}",
+                self.name,
+                label_pairs(self.labels, values),
+                count
+            );
+        }
+    }
+}
+
+#[derive(Default)]
+struct HistogramData {
+    /// Observaciones por bucket (no acumuladas)
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+/// Histograma de duraciones en segundos
+struct Histogram {
+    name: &'static str,
+    help: &'static str,
+    data: Mutex<HistogramData>,
+}
+
+impl Histogram {
+    fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            data: Mutex::new(HistogramData {
+                buckets: vec![0; DURATION_BUCKETS.len()],
+                ..Default::default()
+            }),
+        }
+    }
+
+    fn observe(&self, duration: Duration) {
+        let secs = duration.as_secs_f64();
+        let mut data = self.data.lock().unwrap();
+        if let Some(index) = DURATION_BUCKETS.iter().position(|limit| secs <= *limit) {
+            data.buckets[index] += 1;
+        }
+        data.sum += secs;
+        data.count += 1;
+    }
+
+    fn render(&self, out: &mut String) {
+        let data = self.data.lock().unwrap();
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        let _ = writeln!(out, "# TYPE {} histogram", self.name);
+
+        let mut cumulative = 0;
+        for (limit, count) in DURATION_BUCKETS.iter().zip(&data.buckets) {
+            cumulative += count;
+            let _ = writeln!(
+                out,
+                "{}_bucket{{le=\"{}\"}} {}",
+                self.name, limit, cumulative
+            );
+        }
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", self.name, data.count);
+        let _ = writeln!(out, "{}_sum {}", self.name, data.sum);
+        let _ = writeln!(out, "{}_count {}", self.name, data.count);
+    }
+}
+
+/// Contadores de ingesta MQTT de un dispositivo
+#[derive(Default)]
+struct DeviceCounters {
+    messages: u64,
+    bytes: u64,
+    parse_failures: u64,
+    first_message_at: Option<DateTime<Utc>>,
+    last_message_at: Option<DateTime<Utc>>,
+    /// Mensajes dentro de la ventana de la tasa
+    recent: VecDeque<DateTime<Utc>>,
+}
+
+impl DeviceCounters {
+    fn trim(&mut self, now: DateTime<Utc>) {
+        let cutoff = now - chrono::Duration::seconds(DEVICE_RATE_WINDOW_SECS);
+        while self.recent.front().is_some_and(|at| *at < cutoff) {
+            self.recent.pop_front();
+        }
+    }
+}
+
+/// Estadísticas de ingesta MQTT de un dispositivo desde el arranque
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceIngestStats {
+    pub device_id: String,
+    pub messages_total: u64,
+    pub bytes_total: u64,
+    pub parse_failures: u64,
+    /// Mensajes por minuto en los últimos 5 minutos
+    pub messages_per_minute: f64,
+    pub first_message_at: Option<DateTime<Utc>>,
+    pub last_message_at: Option<DateTime<Utc>>,
+    /// Segundos desde el último mensaje
+    pub last_message_age_secs: Option<i64>,
+}
+
+/// Métricas del pipeline de ingesta en formato Prometheus
+///
+/// Se comparte entre el handler MQTT, el procesador edge, la base de datos y la
+/// sincronización, y se expone en `GET /metrics/prometheus`.
+pub struct Telemetry {
+    mqtt_messages: CounterVec,
+    parse_failures: CounterVec,
+    processing: Histogram,
+    db_insert: Histogram,
+    db_errors: CounterVec,
+    cloud_publish: Histogram,
+    cloud_messages: CounterVec,
+    sync_backlog: AtomicI64,
+    /// Mensajes MQTT en la cola de procesamiento y su capacidad
+    mqtt_queue_depth: AtomicI64,
+    mqtt_queue_capacity: AtomicI64,
+    mqtt_queue_dropped: AtomicU64,
+    /// Lecturas en el búfer de escritura en SQLite y su capacidad
+    write_buffer_depth: AtomicI64,
+    write_buffer_capacity: AtomicI64,
+    readings_shed: CounterVec,
+    ingest_paused: AtomicBool,
+    devices: Mutex<HashMap<String, DeviceCounters>>,
+}
+
+/// Ocupación de la cola de mensajes MQTT (`GET /metrics`)
+#[derive(Debug, Clone, Serialize)]
+pub struct QueueStats {
+    pub depth: i64,
+    pub capacity: i64,
+    /// Mensajes descartados por cola llena desde el arranque
+    pub dropped: u64,
+}
+
+/// Ocupación del búfer de escritura en SQLite (`GET /metrics` y estado del gateway)
+#[derive(Debug, Clone, Serialize)]
+pub struct WriteBufferStats {
+    pub depth: i64,
+    pub capacity: i64,
+    /// Lecturas descartadas por búfer lleno desde el arranque
+    pub shed: u64,
+    /// Suscripciones MQTT de lecturas pausadas por saturación
+    pub paused: bool,
+}
+
+impl Default for Telemetry {
+    fn default() -> Self {
+        Self {
+            mqtt_messages: CounterVec::new(
+                "gateway_mqtt_messages_total",
+                "Mensajes MQTT recibidos por topic y resultado",
+                &["topic", "outcome"],
+            ),
+            parse_failures: CounterVec::new(
+                "gateway_parse_failures_total",
+                "Payloads MQTT que no se pudieron deserializar",
+                &["topic"],
+            ),
+            processing: Histogram::new(
+                "gateway_processing_duration_seconds",
+                "Duración del procesamiento edge de una lectura",
+            ),
+            db_insert: Histogram::new(
+                "gateway_db_insert_duration_seconds",
+                "Duración de la inserción de lecturas en SQLite",
+            ),
+            db_errors: CounterVec::new(
+                "gateway_db_errors_total",
+                "Operaciones fallidas en SQLite por tipo",
+                &["operation"],
+            ),
+            cloud_publish: Histogram::new(
+                "gateway_cloud_publish_duration_seconds",
+                "Duración de la publicación de un mensaje en el cloud",
+            ),
+            cloud_messages: CounterVec::new(
+                "gateway_cloud_messages_total",
+                "Mensajes enviados al cloud por resultado",
+                &["outcome"],
+            ),
+            sync_backlog: AtomicI64::new(0),
+            mqtt_queue_depth: AtomicI64::new(0),
+            mqtt_queue_capacity: AtomicI64::new(0),
+            mqtt_queue_dropped: AtomicU64::new(0),
+            write_buffer_depth: AtomicI64::new(0),
+            write_buffer_capacity: AtomicI64::new(0),
+            readings_shed: CounterVec::new(
+                "gateway_readings_shed_total",
+                "Lecturas descartadas por saturación del almacenamiento, por criterio",
+                &["reason"],
+            ),
+            ingest_paused: AtomicBool::new(false),
+            devices: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl Telemetry {
+    /// Mensaje MQTT recibido; `topic` es el patrón suscrito (`sensors/+/data`)
+    pub fn mqtt_message(&self, topic: &str, outcome: &str) {
+        self.mqtt_messages.inc(&[topic, outcome]);
+    }
+
+    /// Payload MQTT que no se pudo deserializar
+    pub fn parse_failure(&self, topic: &str) {
+        self.parse_failures.inc(&[topic]);
+    }
+
+    pub fn observe_processing(&self, duration: Duration) {
+        self.processing.observe(duration);
+    }
+
+    pub fn observe_db_insert(&self, duration: Duration) {
+        self.db_insert.observe(duration);
+    }
+
+    /// Operación fallida en SQLite (`insert`)
+    pub fn db_error(&self, operation: &str) {
+        self.db_errors.inc(&[operation]);
+    }
+
+    /// Errores de SQLite desde el arranque
+    pub fn db_errors_total(&self) -> u64 {
+        self.db_errors.total()
+    }
+
+    pub fn observe_cloud_publish(&self, duration: Duration) {
+        self.cloud_publish.observe(duration);
+    }
+
+    /// Resultado del envío de un mensaje al cloud (`sent` o `failed`)
+    pub fn cloud_message(&self, outcome: &str) {
+        self.cloud_messages.inc(&[outcome]);
+    }
+
+    /// Lecturas pendientes de sincronizar
+    pub fn set_sync_backlog(&self, pending: i64) {
+        self.sync_backlog.store(pending, Ordering::Relaxed);
+    }
+
+    pub fn set_mqtt_queue_capacity(&self, capacity: usize) {
+        self.mqtt_queue_capacity
+            .store(capacity as i64, Ordering::Relaxed);
+    }
+
+    /// Mensaje MQTT que entra (`1`) o sale (`-1`) de la cola de procesamiento
+    pub fn mqtt_queue_add(&self, delta: i64) {
+        self.mqtt_queue_depth.fetch_add(delta, Ordering::Relaxed);
+    }
+
+    /// Mensaje MQTT descartado por cola llena; retorna el total de descartes
+    pub fn mqtt_queue_drop(&self, topic: &str) -> u64 {
+        self.mqtt_message(topic, "dropped");
+        self.mqtt_queue_dropped.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn set_write_buffer(&self, depth: usize, capacity: usize) {
+        self.write_buffer_depth
+            .store(depth as i64, Ordering::Relaxed);
+        self.write_buffer_capacity
+            .store(capacity as i64, Ordering::Relaxed);
+    }
+
+    /// Lectura descartada por búfer de escritura lleno; retorna el total de descartes
+    pub fn reading_shed(&self, reason: &str) -> u64 {
+        self.readings_shed.inc(&[reason]);
+        self.readings_shed.total()
+    }
+
+    pub fn set_ingest_paused(&self, paused: bool) {
+        self.ingest_paused.store(paused, Ordering::Relaxed);
+    }
+
+    pub fn write_buffer_stats(&self) -> WriteBufferStats {
+        WriteBufferStats {
+            depth: self.write_buffer_depth.load(Ordering::Relaxed),
+            capacity: self.write_buffer_capacity.load(Ordering::Relaxed),
+            shed: self.readings_shed.total(),
+            paused: self.ingest_paused.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn mqtt_queue_stats(&self) -> QueueStats {
+        QueueStats {
+            depth: self.mqtt_queue_depth.load(Ordering::Relaxed),
+            capacity: self.mqtt_queue_capacity.load(Ordering::Relaxed),
+            dropped: self.mqtt_queue_dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Mensaje MQTT recibido de un dispositivo (antes de procesarlo)
+    pub fn device_message(&self, device_id: &str, bytes: usize) {
+        let now = Utc::now();
+        let mut devices = self.devices.lock().unwrap();
+        let counters = devices.entry(device_id.to_string()).or_default();
+
+        counters.messages += 1;
+        counters.bytes += bytes as u64;
+        counters.first_message_at.get_or_insert(now);
+        counters.last_message_at = Some(now);
+        counters.recent.push_back(now);
+        counters.trim(now);
+    }
+
+    /// Payload de un dispositivo que no se pudo deserializar
+    pub fn device_parse_failure(&self, device_id: &str) {
+        let mut devices = self.devices.lock().unwrap();
+        devices
+            .entry(device_id.to_string())
+            .or_default()
+            .parse_failures += 1;
+    }
+
+    /// Estadísticas de un dispositivo (sin mensajes MQTT desde el arranque, todo a cero)
+    pub fn device_stats(&self, device_id: &str) -> DeviceIngestStats {
+        let mut devices = self.devices.lock().unwrap();
+        match devices.get_mut(device_id) {
+            Some(counters) => Self::stats(device_id, counters, Utc::now()),
+            None => Self::stats(device_id, &mut DeviceCounters::default(), Utc::now()),
+        }
+    }
+
+    /// Estadísticas de todos los dispositivos que publicaron por MQTT
+    pub fn devices_stats(&self) -> Vec<DeviceIngestStats> {
+        let now = Utc::now();
+        let mut devices = self.devices.lock().unwrap();
+        let mut stats: Vec<_> = devices
+            .iter_mut()
+            .map(|(device_id, counters)| Self::stats(device_id, counters, now))
+            .collect();
+        stats.sort_by(|a, b| a.device_id.cmp(&b.device_id));
+        stats
+    }
+
+    fn stats(
+        device_id: &str,
+        counters: &mut DeviceCounters,
+        now: DateTime<Utc>,
+    ) -> DeviceIngestStats {
+        counters.trim(now);
+        DeviceIngestStats {
+            device_id: device_id.to_string(),
+            messages_total: counters.messages,
+            bytes_total: counters.bytes,
+            parse_failures: counters.parse_failures,
+            messages_per_minute: counters.recent.len() as f64 * 60.0
+                / DEVICE_RATE_WINDOW_SECS as f64,
+            first_message_at: counters.first_message_at,
+            last_message_at: counters.last_message_at,
+            last_message_age_secs: counters.last_message_at.map(|at| (now - at).num_seconds()),
+        }
+    }
+
+    /// Exposición en formato de texto de Prometheus
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.mqtt_messages.render(&mut out);
+        self.parse_failures.render(&mut out);
+        self.processing.render(&mut out);
+        self.db_insert.render(&mut out);
+        self.db_errors.render(&mut out);
+        self.cloud_publish.render(&mut out);
+        self.cloud_messages.render(&mut out);
+
+        let _ = writeln!(
+            out,
+            "# HELP gateway_sync_backlog Lecturas pendientes de sincronizar con el cloud"
+        );
+        let _ = writeln!(out, "# TYPE gateway_sync_backlog gauge");
+        let _ = writeln!(
+            out,
+            "gateway_sync_backlog {}",
+            self.sync_backlog.load(Ordering::Relaxed)
+        );
+
+        self.readings_shed.render(&mut out);
+
+        let queue = self.mqtt_queue_stats();
+        let writes = self.write_buffer_stats();
+        for (name, help, value) in [
+            (
+                "gateway_mqtt_queue_depth",
+                "Mensajes MQTT en espera de procesarse",
+                queue.depth,
+            ),
+            (
+                "gateway_mqtt_queue_capacity",
+                "Capacidad de la cola de mensajes MQTT",
+                queue.capacity,
+            ),
+            (
+                "gateway_write_buffer_depth",
+                "Lecturas en espera de escribirse en SQLite",
+                writes.depth,
+            ),
+            (
+                "gateway_write_buffer_capacity",
+                "Lecturas que retiene como máximo el búfer de escritura",
+                writes.capacity,
+            ),
+            (
+                "gateway_ingest_paused",
+                "Suscripciones MQTT de lecturas pausadas por saturación (1 = pausadas)",
+                writes.paused as i64,
+            ),
+        ] {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} gauge", name);
+            let _ = writeln!(out, "{} {}", name, value);
+        }
+
+        let devices = self.devices_stats();
+        let series: [DeviceSeries; 4] = [
+            (
+                "gateway_device_messages_total",
+                "counter",
+                "Mensajes MQTT recibidos por dispositivo",
+                |stats| Some(stats.messages_total as f64),
+            ),
+            (
+                "gateway_device_bytes_total",
+                "counter",
+                "Bytes de payload MQTT recibidos por dispositivo",
+                |stats| Some(stats.bytes_total as f64),
+            ),
+            (
+                "gateway_device_messages_per_minute",
+                "gauge",
+                "Mensajes por minuto en los últimos 5 minutos",
+                |stats| Some(stats.messages_per_minute),
+            ),
+            (
+                "gateway_device_last_message_age_seconds",
+                "gauge",
+                "Segundos desde el último mensaje del dispositivo",
+                |stats| stats.last_message_age_secs.map(|age| age as f64),
+            ),
+        ];
+        for (name, kind, help, value) in series {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            for stats in &devices {
+                if let Some(value) = value(stats) {
+                    let _ = writeln!(
+                        out,
+                        "{}{{device_id=\"{}\"}} {}",
+                        name,
+                        escape_label(&stats.device_id),
+                        value
+                    );
+                }
+            }
+        }
+
+        out
+    }
+}
+
+fn label_pairs(names: &[&str], values: &[String]) -> String {
+    names
+        .iter()
+        .zip(values)
+        .map(|(name, value)| format!("{}=\"{}\"", name, escape_label(value)))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}